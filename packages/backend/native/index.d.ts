@@ -5,6 +5,18 @@ declare const _default: typeof import('./index')
 export default _default
 
 export declare class BackendRuntime {
+  /** Append an event to the hash-chained audit log. */
+  appendAuditEvent(input: RuntimeAuditEventInput): Promise<RuntimeAuditEvent>
+  /**
+   * List audit events newest first. Pass `next_cursor` back as `cursor` to
+   * fetch the following page.
+   */
+  listAuditEvents(query: RuntimeAuditEventQuery): Promise<RuntimeAuditEventPage>
+  /**
+   * Recompute hashes for up to `limit` events after `after_seq` and report
+   * the first event whose link or hash does not match.
+   */
+  verifyAuditChain(afterSeq: number | undefined | null, limit: number): Promise<RuntimeAuditChainVerification>
   acquireCoordinationLease(key: string, owner: string, ttlMs: number): Promise<CoordinationLeaseGrant | null>
   releaseCoordinationLease(key: string, owner: string, fencingToken: bigint | number): Promise<boolean>
   renewCoordinationLease(key: string, owner: string, fencingToken: bigint | number, ttlMs: number): Promise<boolean>
@@ -20,16 +32,87 @@ export declare class BackendRuntime {
    * fresh quota state, fail and retry after Node reconciles it.
    */
   compactPendingDocUpdates(workspaceId: string, docId: string, batchLimit: number, historyMinIntervalMs: number, historyMaxAgeSeconds: number, owner: string, leaseTtlMs: number): Promise<RuntimeDocCompactionResult>
+  listWorkspaceDocKeys(workspaceId: string): Promise<Array<RuntimeDocDataKey>>
+  /**
+   * Retire the active doc data key of a workspace and create a new one.
+   * Existing blobs stay readable with the retired key until
+   * `reencrypt_workspace_docs` re-seals them.
+   */
+  rotateWorkspaceDocKey(workspaceId: string): Promise<RuntimeDocDataKey>
+  /**
+   * Re-wrap data keys still wrapped by a previous master key with the
+   * current one. Returns how many keys were re-wrapped in this batch.
+   */
+  rewrapDocDataKeys(batchLimit: number): Promise<number>
+  /**
+   * Encrypt plaintext doc blobs of a workspace and re-seal blobs sealed with
+   * retired data keys. Call repeatedly until `completed` is true. Requires
+   * `crypto.docEncryption.masterKey`.
+   */
+  reencryptWorkspaceDocs(workspaceId: string, batchLimit: number, owner: string, leaseTtlMs: number): Promise<RuntimeDocReencryptionResult>
   upsertDocSnapshot(workspaceId: string, docId: string, blob: Buffer, timestampMs: number, editorId?: string | undefined | null): Promise<boolean>
   createDocHistory(input: RuntimeDocHistoryInput): Promise<boolean>
+  /**
+   * Append doc updates, sealing them when `crypto.docEncryption.masterKey`
+   * is set.
+   * Updates are timestamped one millisecond apart after the current time;
+   * returns the last timestamp, or 0 when `updates` is empty.
+   */
+  pushDocUpdates(workspaceId: string, docId: string, updates: Array<Buffer>, editorId?: string | undefined | null): Promise<number>
+  getDocSnapshot(workspaceId: string, docId: string): Promise<RuntimeDocRecord | null>
+  getDocUpdates(workspaceId: string, docId: string): Promise<Array<RuntimeDocRecord>>
+  getDocHistory(workspaceId: string, docId: string, timestampMs: number): Promise<RuntimeDocRecord | null>
   deleteDocStorage(workspaceId: string, docId: string): Promise<void>
   putRuntimeGateIfAbsent(key: string, ttlMs: number): Promise<boolean>
+  /**
+   * Token-bucket check shared across instances. Each call refills the bucket
+   * for the elapsed time and takes `cost` tokens only when enough are left.
+   */
+  rateLimitCheck(key: string, capacity: number, refillPerSec: number, cost: number): Promise<RuntimeRateLimitResult>
+  /**
+   * Sliding-window check that admits at most `limit` cost units within any
+   * `window_ms` span.
+   */
+  rateLimitCheckSlidingWindow(key: string, limit: number, windowMs: number, cost: number): Promise<RuntimeRateLimitResult>
+  /**
+   * Delete up to `limit` expired gates, token buckets and sliding window
+   * hits, counted together across the three tables.
+   */
   cleanupExpiredRuntimeGates(limit: number): Promise<number>
   cleanupExpiredUserSessions(limit: number): Promise<number>
   cleanupExpiredSnapshotHistories(limit: number): Promise<number>
+  cleanupExpiredAuditEvents(retentionMs: number, limit: number): Promise<number>
+  enqueueRuntimeJob(input: RuntimeJobEnqueueInput): Promise<RuntimeJobEnqueueResult>
+  claimRuntimeJobs(queue: string, workerId: string, visibilityTimeoutMs: number, limit: number): Promise<Array<RuntimeJobRecord>>
+  heartbeatRuntimeJob(jobId: string, workerId: string, visibilityTimeoutMs: number): Promise<boolean>
+  completeRuntimeJob(jobId: string, workerId: string): Promise<boolean>
+  /**
+   * Report a failed attempt. The job is rescheduled with exponential backoff
+   * or moved to the `dead` state once it has used all of its attempts.
+   * Returns `null` when the worker no longer holds the job.
+   */
+  failRuntimeJob(jobId: string, workerId: string, error: string): Promise<RuntimeJobFailResult | null>
+  /**
+   * Move a dead job back to `pending` with its attempts reset. Returns
+   * `false` when the job is not dead or a live job with the same dedupe key
+   * already exists.
+   */
+  requeueDeadRuntimeJob(jobId: string): Promise<boolean>
+  cleanupCompletedRuntimeJobs(limit: number): Promise<number>
   createAuthChallenge(purpose: string, token: string, payload: any, ttlMs: number): Promise<boolean>
   getAuthChallenge(purpose: string, token: string): Promise<any | null>
   consumeAuthChallenge(purpose: string, token: string): Promise<any | null>
+  /**
+   * Verify a passkey registration. The challenge must have been stored with
+   * `create_auth_challenge` under its base64url form; it is consumed here and
+   * its payload is returned with the verified credential.
+   */
+  consumeWebauthnRegistrationChallenge(purpose: string, input: RuntimeWebauthnRegistrationInput): Promise<RuntimeWebauthnCredential | null>
+  /**
+   * Verify a passkey assertion against a stored credential public key and
+   * signature counter, consuming the challenge it was issued for.
+   */
+  consumeWebauthnAssertionChallenge(purpose: string, input: RuntimeWebauthnAssertionInput): Promise<RuntimeWebauthnAssertion | null>
   createVerificationToken(tokenType: number, credential: string | undefined | null, ttlMs: number): Promise<string>
   getVerificationToken(tokenType: number, token: string, keep?: boolean | undefined | null): Promise<RuntimeVerificationTokenRecord | null>
   verifyVerificationToken(tokenType: number, token: string, credential?: string | undefined | null, keep?: boolean | undefined | null): Promise<RuntimeVerificationTokenRecord | null>
@@ -40,17 +123,60 @@ export declare class BackendRuntime {
   getWorkspaceInviteLink(workspaceId: string): Promise<RuntimeWorkspaceInviteLinkRecord | null>
   getWorkspaceInviteLinkById(inviteId: string): Promise<RuntimeWorkspaceInviteLinkRecord | null>
   revokeWorkspaceInviteLink(workspaceId: string): Promise<boolean>
+  /**
+   * Create an extra invite link with an optional role, use limit and email
+   * domain restriction. The default link stays managed by
+   * `create_workspace_invite_link`.
+   */
+  addWorkspaceInviteLink(input: RuntimeWorkspaceInviteLinkInput): Promise<RuntimeWorkspaceInviteLinkRecord>
+  listWorkspaceInviteLinks(workspaceId: string): Promise<Array<RuntimeWorkspaceInviteLinkRecord>>
+  /**
+   * Atomically count one use of an invite link. `reason` is one of
+   * `not_found`, `domain_mismatch` or `exhausted` when `ok` is false.
+   */
+  redeemWorkspaceInviteLink(inviteId: string, email?: string | undefined | null): Promise<RuntimeWorkspaceInviteLinkRedeemResult>
+  revokeWorkspaceInviteLinkById(inviteId: string): Promise<boolean>
   createByokLocalLease(activeKey: string, leaseId: string, payload: any, ttlMs: number): Promise<RuntimeByokLocalLeaseRecord>
   getByokLocalLease(leaseId: string): Promise<RuntimeByokLocalLeaseRecord | null>
+  /**
+   * Start TOTP enrollment. The returned secret stays pending until
+   * `confirm_totp_enrollment` succeeds with a code generated from it.
+   */
+  beginTotpEnrollment(userId: string, issuer: string, accountName: string, ttlMs: number): Promise<RuntimeTotpEnrollment>
+  /** Returns fresh recovery codes on success; they are only stored hashed. */
+  confirmTotpEnrollment(userId: string, code: string): Promise<RuntimeTotpVerifyResult>
+  verifyTotp(userId: string, code: string): Promise<RuntimeTotpVerifyResult>
+  consumeTotpRecoveryCode(userId: string, code: string): Promise<boolean>
+  regenerateTotpRecoveryCodes(userId: string): Promise<Array<string> | null>
+  disableTotp(userId: string): Promise<boolean>
   cleanupExpiredRuntimeStates(limit: number): Promise<number>
   refreshWorkspaceAdminStatsDirty(batchLimit: number, owner: string, leaseTtlMs: number): Promise<RuntimeWorkspaceStatsRefreshResult>
   recalibrateWorkspaceAdminStats(lastSid: number, batchLimit: number, owner: string, leaseTtlMs: number): Promise<RuntimeWorkspaceStatsRecalibrationResult>
   writeWorkspaceAdminStatsDailySnapshot(owner: string, leaseTtlMs: number): Promise<RuntimeWorkspaceStatsSnapshotResult>
   recalibrateWorkspaceAdminStatsDaily(batchLimit: number, owner: string, leaseTtlMs: number, lockRetryTimes: number, lockRetryDelayMs: number): Promise<RuntimeWorkspaceStatsDailyRecalibrationResult>
+  /**
+   * Read daily admin stats snapshots back as a gap-filled series.
+   * `metrics` accepts `storage_bytes`, `doc_count`, `member_count` and
+   * `blob_count`; `granularity` is `day`, `week` or `month`.
+   */
+  queryWorkspaceStatsSeries(workspaceIds: Array<string>, metrics: Array<string>, fromMs: number, toMs: number, granularity: string): Promise<Array<RuntimeWorkspaceStatsSeries>>
+  /**
+   * Rank workspaces by how much `metric` grew between the last snapshot
+   * before `from_ms` and the last snapshot on or before `to_ms`.
+   */
+  topWorkspacesByStatsGrowth(metric: string, fromMs: number, toMs: number, limit: number): Promise<Array<RuntimeWorkspaceStatsGrowth>>
   constructor()
   start(): Promise<void>
+  /**
+   * Re-read config files and, once started, `app_configs` overrides, then
+   * swap the active config in place. Invalid config leaves the current one
+   * untouched.
+   */
+  reloadConfig(): Promise<RuntimeConfigReloadResult>
   stop(): Promise<void>
   health(): Promise<BackendRuntimeHealth>
+  /** Render runtime metrics in the Prometheus text exposition format. */
+  renderMetrics(): Promise<string>
   runMigrations(): Promise<void>
 }
 
@@ -58,35 +184,240 @@ export declare class LlmStreamHandle {
   abort(): void
 }
 
+/**
+ * An object opened for chunked reading. Node pulls chunks with `read()`, so
+ * at most one chunk is held in memory regardless of the object size.
+ */
+export declare class ObjectReadStream {
+  /**
+   * Metadata of the whole object; `contentLength` is the full size even for
+   * ranged reads.
+   */
+  get metadata(): RuntimeObjectMetadata
+  /**
+   * False when the requested range lies outside the object. Nothing can be
+   * read and the caller should answer 416.
+   */
+  get satisfiable(): boolean
+  /** First byte of the served range, for the `Content-Range` header. */
+  get rangeStart(): number | null
+  /** Last byte (inclusive) of the served range. */
+  get rangeEnd(): number | null
+  /** Read the next chunk, or `null` once the object or range is exhausted. */
+  read(): Promise<Buffer | null>
+  /** Stop reading early and release the file or connection. */
+  close(): Promise<void>
+}
+
+/**
+ * An object opened for chunked writing. Object storage backends upload a
+ * multipart part whenever a full part is buffered; objects smaller than one
+ * part are written with a single put on `finish()`.
+ */
+export declare class ObjectWriteStream {
+  write(chunk: Buffer): Promise<void>
+  /**
+   * Commit the object. A `contentLength` or `checksumCRC32` given when the
+   * stream was opened is verified here; on mismatch nothing is stored.
+   */
+  finish(): Promise<RuntimeObjectMetadata>
+  /** Discard everything written so far. */
+  abort(): Promise<void>
+}
+
 export declare class StorageRuntime {
   planUnreferencedWorkspaceBlobs(workspaceId: string, gracePeriodDays: number, limit: number): Promise<RuntimeBlobCleanupPlanResult>
-  executeBlobCleanupCandidates(runId: string, gracePeriodDays: number, limit: number): Promise<RuntimeBlobCleanupExecuteResult>
+  /**
+   * Remove marked candidates. Objects are moved to the quarantine prefix and
+   * can be restored for `quarantine_days` (default 7) before
+   * `purge_quarantined_blobs` deletes them; `0` deletes them immediately.
+   * Image derivatives are deleted together with the object.
+   */
+  executeBlobCleanupCandidates(runId: string, gracePeriodDays: number, limit: number, quarantineDays?: number | undefined | null): Promise<RuntimeBlobCleanupExecuteResult>
+  /**
+   * Move quarantined candidates back to their original keys and restore
+   * their `blobs` rows. Pass a cleanup `run_id`, the `workspaceId/blobKey`
+   * object keys to restore, or both.
+   */
+  restoreBlobCleanupCandidates(runId: string | undefined | null, objectKeys: Array<string> | undefined | null, limit: number): Promise<RuntimeBlobCleanupRestoreResult>
+  /**
+   * Delete quarantined objects whose restore deadline has passed, together
+   * with their image derivatives.
+   */
+  purgeQuarantinedBlobs(limit: number): Promise<RuntimeBlobCleanupPurgeResult>
+  /**
+   * Generate the configured derivatives of an existing image blob. The
+   * server runs this as a job after each completed upload, since it reads
+   * the whole source (up to 64 MiB) and resizes it once per edge.
+   */
+  generateBlobDerivatives(workspaceId: string, key: string): Promise<RuntimeBlobDerivativeResult>
+  /**
+   * Read a blob for display at most `max_edge` pixels on its longest side:
+   * the smallest derivative that covers `max_edge`, or the original when
+   * none does. Scopes other than `blob` always return the original.
+   */
+  getObjectDerivative(scope: string, key: string, maxEdge: number): Promise<RuntimeObjectGetResult | null>
+  /**
+   * Delete pending uploads created before `cutoff_ms` and release their
+   * quota reservations, along with reservations that expired without their
+   * upload being completed.
+   */
   cleanupExpiredPendingBlobs(cutoffMs: number, limit: number): Promise<RuntimeBlobCleanupResult>
+  /**
+   * Seal plaintext blobs of a workspace and re-seal blobs sealed with
+   * retired data keys. Progress is checkpointed; call repeatedly until
+   * `completed` is true. A call after completion starts a new pass.
+   */
+  reencryptWorkspaceBlobs(workspaceId: string, batchLimit: number): Promise<RuntimeBlobReencryptionResult>
+  /**
+   * Reserve space for an upload the runtime does not see, such as a proxied
+   * presigned upload. The reservation is committed by
+   * `complete_workspace_blob_upload` and released by
+   * `release_workspace_blob_quota` or on expiry.
+   */
+  reserveWorkspaceBlobQuota(workspaceId: string, key: string, size: number, quota: RuntimeBlobQuota, uploadId?: string | undefined | null): Promise<RuntimeBlobQuotaReservation>
+  releaseWorkspaceBlobQuota(workspaceId: string, key: string): Promise<boolean>
+  workspaceStorageUsage(workspaceId: string): Promise<RuntimeWorkspaceStorageUsage>
+  /**
+   * Recount storage usage from `blobs` for one workspace, or for the
+   * `limit` workspaces reconciled longest ago. Blob rows written outside
+   * the runtime only reach the counters through this.
+   */
+  reconcileWorkspaceStorageUsage(workspaceId: string | undefined | null, limit: number): Promise<RuntimeStorageUsageReconcileResult>
+  /**
+   * Abort multipart uploads of the blob bucket that were started before
+   * `cutoff_ms` and never completed, dropping the pending `blobs` rows they
+   * belong to. Scans at most `limit` uploads after `cursor`; pass the
+   * returned opaque `next_cursor` to continue. Uploads outside the
+   * `<workspace>/<key>` layout and uploads whose start time the provider
+   * does not report are left alone. Providers without multipart
+   * listing report nothing.
+   */
+  sweepAbandonedMultipartUploads(cutoffMs: number, limit: number, cursor?: string | undefined | null): Promise<RuntimeMultipartSweepResult>
+  /**
+   * Delete soft-deleted blobs of a workspace together with their image
+   * derivatives, and subtract them from the workspace's used bytes.
+   */
   releaseDeletedBlobs(workspaceId: string, limit: number): Promise<RuntimeBlobCleanupResult>
   backfillMissingBlobMetadata(workspaceId: string | undefined | null, limit: number): Promise<RuntimeBlobMetadataBackfillResult>
+  /**
+   * Verify the next batch of a workspace's blobs: every `blobs` row must
+   * have an object of the recorded size whose content hashes to its key,
+   * and every object must have a row. Findings are written to
+   * `blob_integrity_findings` under the run id. Omit `run_id` to start a new
+   * run and pass the returned id until `completed` is true.
+   */
+  scrubWorkspaceBlobs(workspaceId: string, runId: string | undefined | null, batchLimit: number): Promise<RuntimeBlobScrubResult>
+  /**
+   * Totals of a scrub run with its findings counted by kind and the most
+   * recent `finding_limit` findings.
+   */
+  blobScrubSummary(runId: string, findingLimit: number): Promise<RuntimeBlobScrubSummary>
   rebuildDocBlobRefs(workspaceId: string, docId: string): Promise<RuntimeDocBlobRefsResult>
   rebuildWorkspaceDocBlobRefs(workspaceId: string, limit: number): Promise<RuntimeDocBlobRefsResult>
+  /**
+   * Open an object for chunked reading, optionally limited to an HTTP `Range`
+   * header value. Resolves to `null` when the object does not exist.
+   */
+  openObjectReadStream(scope: string, key: string, range?: string | undefined | null): Promise<ObjectReadStream | null>
+  /**
+   * Open an object for chunked writing. The object becomes visible only once
+   * `finish()` resolves.
+   */
+  openObjectWriteStream(scope: string, key: string, metadata?: RuntimeObjectStoragePutOptions | undefined | null): Promise<ObjectWriteStream>
+  /**
+   * Copy every blob from one provider config to another, e.g. `fs` to S3.
+   * Objects are copied as stored, keeping content type, last-modified time
+   * and encryption metadata. Once all keys are copied a verification pass
+   * reports keys missing or different on the target, and how many only
+   * matched on length because a provider has no checksum. Progress is
+   * checkpointed; call repeatedly until `completed` is true. A dry run only
+   * counts the keys it would copy.
+   */
+  migrateStorage(sourceConfigJson: string, targetConfigJson: string, batchLimit: number, dryRun?: boolean | undefined | null): Promise<RuntimeStorageMigrationResult>
+  /**
+   * Append the next batch of a workspace export to the archive at `path`.
+   * Call repeatedly with the same `archive_id` until `completed` is true;
+   * progress is checkpointed so an interrupted export resumes where the last
+   * committed batch ended.
+   */
+  exportWorkspaceArchive(workspaceId: string, archiveId: string, path: string, batchLimit: number): Promise<RuntimeWorkspaceArchiveProgress>
+  /**
+   * Replay the next batch of an exported archive into `target_workspace_id`,
+   * remapping workspace-scoped doc ids. The target workspace must already
+   * exist. Records are written idempotently, so a batch retried after a crash
+   * does not duplicate data.
+   */
+  importWorkspaceArchive(archiveId: string, path: string, targetWorkspaceId: string, batchLimit: number): Promise<RuntimeWorkspaceArchiveProgress>
+  /**
+   * Delete what is left of a deleted workspace in bounded batches: doc rows,
+   * blob metadata and objects, runtime state and doc data keys. At most
+   * `batch_limit` rows or objects are removed per call, and progress is
+   * checkpointed so housekeeping can call this until `completed` is true.
+   * Without a blob storage backend only rows are purged.
+   */
+  purgeWorkspace(workspaceId: string, batchLimit: number): Promise<RuntimeWorkspacePurgeResult>
   constructor()
   start(): Promise<void>
   configure(configJson: string): void
+  /**
+   * Re-read config files and, once started, `app_configs` overrides, then
+   * swap the storage backends in place. Invalid config leaves the current
+   * backends untouched.
+   */
+  reloadConfig(): Promise<RuntimeConfigReloadResult>
   stop(): Promise<void>
   runMigrations(): Promise<void>
   health(): Promise<StorageRuntimeHealth>
+  /** Render runtime metrics in the Prometheus text exposition format. */
+  renderMetrics(): Promise<string>
   providerCapabilities(scope: string): Promise<StorageProviderCapabilities>
-  putObject(scope: string, key: string, body: Buffer, metadata?: RuntimeObjectStoragePutOptions | undefined | null): Promise<RuntimeObjectMetadata>
+  /**
+   * Write an object. With a `quota`, a workspace blob is counted against
+   * it: space is reserved before the write and the blob is recorded as
+   * completed once stored.
+   */
+  putObject(scope: string, key: string, body: Buffer, metadata?: RuntimeObjectStoragePutOptions | undefined | null, quota?: RuntimeBlobQuota | undefined | null): Promise<RuntimeObjectMetadata>
   headObject(scope: string, key: string): Promise<RuntimeObjectMetadata | null>
   getObject(scope: string, key: string): Promise<RuntimeObjectGetResult | null>
+  /**
+   * Read a single byte range of an object. `range` is an HTTP `Range`
+   * header value such as `bytes=0-1023`, `bytes=1024-` or `bytes=-512`.
+   */
+  getObjectRange(scope: string, key: string, range: string): Promise<RuntimeObjectRangeResult | null>
   listObjects(scope: string, prefix?: string | undefined | null): Promise<Array<RuntimeObjectListEntry>>
   deleteObject(scope: string, key: string): Promise<void>
-  presignPut(scope: string, key: string, metadata?: RuntimeObjectStoragePutOptions | undefined | null): Promise<RuntimePresignedObjectRequest | null>
+  /**
+   * Presign a direct upload. With a `quota`, space for
+   * `metadata.contentLength` bytes is reserved until the upload is
+   * completed through `complete_workspace_blob_upload`.
+   */
+  presignPut(scope: string, key: string, metadata?: RuntimeObjectStoragePutOptions | undefined | null, quota?: RuntimeBlobQuota | undefined | null): Promise<RuntimePresignedObjectRequest | null>
   presignGet(scope: string, key: string): Promise<RuntimePresignedObjectRequest | null>
-  createMultipartUpload(scope: string, key: string, metadata?: RuntimeObjectStoragePutOptions | undefined | null): Promise<RuntimeMultipartUploadInit | null>
+  /**
+   * Start a multipart upload. With a `quota`, space for
+   * `metadata.contentLength` bytes is reserved for the upload; the upload
+   * is aborted again when the reservation is rejected.
+   */
+  createMultipartUpload(scope: string, key: string, metadata?: RuntimeObjectStoragePutOptions | undefined | null, quota?: RuntimeBlobQuota | undefined | null): Promise<RuntimeMultipartUploadInit | null>
   presignUploadPart(scope: string, key: string, uploadId: string, partNumber: number): Promise<RuntimePresignedObjectRequest | null>
   proxyUploadPart(scope: string, key: string, uploadId: string, partNumber: number, body: Buffer, contentLength?: number | undefined | null): Promise<string | null>
   listMultipartUploadParts(scope: string, key: string, uploadId: string): Promise<Array<RuntimeMultipartUploadPart> | null>
-  completeMultipartUpload(scope: string, key: string, uploadId: string, parts: Array<RuntimeMultipartUploadPart>): Promise<boolean>
+  /**
+   * Assemble a multipart upload. With a `quota`, the reservation is
+   * re-checked against the assembled size, since the size given at create
+   * time is only what the client announced; an upload that no longer fits
+   * is deleted.
+   */
+  completeMultipartUpload(scope: string, key: string, uploadId: string, parts: Array<RuntimeMultipartUploadPart>, quota?: RuntimeBlobQuota | undefined | null): Promise<boolean>
   abortMultipartUpload(scope: string, key: string, uploadId: string): Promise<boolean>
-  completeWorkspaceBlobUpload(workspaceId: string, key: string, expectedSize: number, expectedMime: string): Promise<RuntimeBlobCompleteResult>
+  /**
+   * Verify an uploaded blob and record it in `blobs`. With a `quota`, a
+   * blob that does not fit is deleted and reported as
+   * `blob_limit_exceeded` or `storage_quota_exceeded`. Image derivatives
+   * are left to `generate_blob_derivatives` so completion stays fast.
+   */
+  completeWorkspaceBlobUpload(workspaceId: string, key: string, expectedSize: number, expectedMime: string, quota?: RuntimeBlobQuota | undefined | null): Promise<RuntimeBlobCompleteResult>
 }
 
 export declare class Tokenizer {
@@ -834,9 +1165,53 @@ export declare function resolveEntitlementV1(input: ResolveEntitlementInput): Re
 
 export declare function runNativeActionRecipePreparedStream(input: ActionRuntimeInput, callback: ((err: Error | null, arg: string) => void)): LlmStreamHandle
 
+export interface RuntimeAuditChainVerification {
+  checked: number
+  lastSeq?: number
+  brokenAtSeq?: number
+}
+
+export interface RuntimeAuditEvent {
+  seq: number
+  workspaceId?: string
+  actorId?: string
+  action: string
+  targetType?: string
+  targetId?: string
+  details: any
+  prevHash: string
+  hash: string
+  createdAtMs: number
+}
+
+export interface RuntimeAuditEventInput {
+  workspaceId?: string
+  actorId?: string
+  action: string
+  targetType?: string
+  targetId?: string
+  details?: any
+}
+
+export interface RuntimeAuditEventPage {
+  events: Array<RuntimeAuditEvent>
+  nextCursor?: string
+}
+
+export interface RuntimeAuditEventQuery {
+  workspaceId?: string
+  actorId?: string
+  action?: string
+  sinceMs?: number
+  untilMs?: number
+  cursor?: string
+  limit?: number
+}
+
 export interface RuntimeBlobCleanupExecuteResult {
   scannedCandidates: number
   deletedObjects: number
+  quarantinedObjects: number
   deletedMetadata: number
   skippedStillReferenced: number
   failed: number
@@ -853,10 +1228,24 @@ export interface RuntimeBlobCleanupPlanResult {
   nextCursor?: string
 }
 
+export interface RuntimeBlobCleanupPurgeResult {
+  scannedCandidates: number
+  purgedObjects: number
+  failed: number
+}
+
+export interface RuntimeBlobCleanupRestoreResult {
+  scannedCandidates: number
+  restoredObjects: number
+  failed: number
+  workspaceIds: Array<string>
+}
+
 export interface RuntimeBlobCleanupResult {
   scanned: number
   deleted: number
   abortedMultipart: number
+  releasedReservations: number
   workspaceIds: Array<string>
 }
 
@@ -868,6 +1257,11 @@ export interface RuntimeBlobCompleteResult {
   lastModifiedMs?: number
 }
 
+/** Longest-edge sizes of the image derivatives that were written. */
+export interface RuntimeBlobDerivativeResult {
+  generatedEdges: Array<number>
+}
+
 export interface RuntimeBlobMetadataBackfillResult {
   scannedObjects: number
   headedObjects: number
@@ -879,12 +1273,87 @@ export interface RuntimeBlobMetadataBackfillResult {
   workspaceIds: Array<string>
 }
 
+/** Limits of a workspace's plan, as resolved by `resolveEntitlementV1`. */
+export interface RuntimeBlobQuota {
+  blobLimit: number
+  storageQuota: number
+}
+
+export interface RuntimeBlobQuotaReservation {
+  ok: boolean
+  reason?: string
+  usedBytes: number
+  reservedBytes: number
+  storageQuota: number
+}
+
+export interface RuntimeBlobReencryptionResult {
+  workspaceId: string
+  scanned: number
+  reencrypted: number
+  skipped: number
+  failed: number
+  completed: boolean
+}
+
+export interface RuntimeBlobScrubFinding {
+  workspaceId: string
+  blobKey: string
+  kind: string
+  expectedSize?: number
+  actualSize?: number
+  detail: any
+  foundAtMs: number
+}
+
+export interface RuntimeBlobScrubFindingCount {
+  kind: string
+  count: number
+}
+
+export interface RuntimeBlobScrubResult {
+  runId: string
+  phase: string
+  scanned: number
+  findings: number
+  failed: number
+  completed: boolean
+}
+
+export interface RuntimeBlobScrubSummary {
+  runId: string
+  workspaceId?: string
+  status: string
+  scanned: number
+  findings: number
+  failed: number
+  startedAtMs: number
+  finishedAtMs?: number
+  counts: Array<RuntimeBlobScrubFindingCount>
+  recentFindings: Array<RuntimeBlobScrubFinding>
+}
+
 export interface RuntimeByokLocalLeaseRecord {
   leaseId: string
   payload: any
   expiresAtMs: number
 }
 
+export interface RuntimeConfigIssue {
+  path: string
+  message: string
+}
+
+export interface RuntimeConfigReloadResult {
+  /** Storage scopes with a configured backend after the reload. */
+  backends: Array<string>
+  /**
+   * The configured datasource URL, pool or replica settings differ from the
+   * connected ones; they only take effect after `stop()` and `start()`.
+   */
+  restartRequired: boolean
+}
+
 export interface RuntimeDocBlobRefsResult {
   scannedDocs: number
   parsedDocs: number
@@ -903,6 +1372,15 @@ export interface RuntimeDocCompactionResult {
   historyCreated: boolean
 }
 
+export interface RuntimeDocDataKey {
+  workspaceId: string
+  version: number
+  masterKeyId: string
+  active: boolean
+  createdAtMs: number
+  retiredAtMs?: number
+}
+
 export interface RuntimeDocHistoryInput {
   workspaceId: string
   docId: string
@@ -914,12 +1392,73 @@ export interface RuntimeDocHistoryInput {
   historyMaxAgeMs: number
 }
 
+export interface RuntimeDocRecord {
+  blob: Buffer
+  timestampMs: number
+  editorId?: string
+}
+
+export interface RuntimeDocReencryptionResult {
+  leaseAcquired: boolean
+  workspaceId: string
+  snapshots: number
+  updates: number
+  histories: number
+  completed: boolean
+}
+
+export interface RuntimeJobEnqueueInput {
+  queue: string
+  payload: any
+  dedupeKey?: string
+  runAtMs?: number
+  maxAttempts?: number
+}
+
+export interface RuntimeJobEnqueueResult {
+  jobId: string
+  deduplicated: boolean
+}
+
+export interface RuntimeJobFailResult {
+  status: string
+  nextRunAtMs?: number
+}
+
+export interface RuntimeJobRecord {
+  jobId: string
+  queue: string
+  dedupeKey?: string
+  payload: any
+  attempts: number
+  maxAttempts: number
+  lockedUntilMs: number
+}
+
 export interface RuntimeMagicLinkOtpConsumeResult {
   ok: boolean
   token?: string
   reason?: string
 }
 
+/**
+ * Outcome of one multipart upload sweep. `freed_part_bytes` counts the
+ * parts of aborted uploads; `skipped_foreign` counts uploads outside the
+ * blob key layout, which are left alone; `next_cursor` is set while uploads
+ * remain.
+ */
+export interface RuntimeMultipartSweepResult {
+  scannedUploads: number
+  abortedUploads: number
+  matchedPendingBlobs: number
+  freedPartBytes: number
+  skippedRecent: number
+  skippedForeign: number
+  failed: number
+  nextCursor?: string
+  workspaceIds: Array<string>
+}
+
 export interface RuntimeMultipartUploadInit {
   uploadId: string
   expiresAtMs: number
@@ -948,6 +1487,19 @@ export interface RuntimeObjectMetadata {
   checksumCrc32?: string
 }
 
+/**
+ * Result of a ranged read. `start` and `end` are inclusive offsets for the
+ * `Content-Range` header; when `satisfiable` is false the body is empty and
+ * the caller should answer 416 with `metadata.contentLength`.
+ */
+export interface RuntimeObjectRangeResult {
+  body: Buffer
+  metadata: RuntimeObjectMetadata
+  satisfiable: boolean
+  start: number
+  end: number
+}
+
 export interface RuntimeObjectStoragePutOptions {
   contentType?: string
   contentLength?: number
@@ -960,6 +1512,53 @@ export interface RuntimePresignedObjectRequest {
   expiresAtMs: number
 }
 
+export interface RuntimeRateLimitResult {
+  allowed: boolean
+  remaining: number
+  retryAfterMs?: number
+}
+
+export interface RuntimeStorageMigrationResult {
+  phase: string
+  dryRun: boolean
+  scanned: number
+  copied: number
+  skipped: number
+  failed: number
+  missingKeys: Array<string>
+  mismatchedKeys: Array<string>
+  /**
+   * Keys that could not be copied or compared in this batch. The checkpoint
+   * moves past them; the verification pass reports them again if the
+   * target still lacks them.
+   */
+  failedKeys: Array<string>
+  /**
+   * Verified keys that matched on length and encryption only, because a
+   * provider reported no CRC32 checksum for them.
+   */
+  unverifiedChecksums: number
+  completed: boolean
+}
+
+export interface RuntimeStorageUsageReconcileResult {
+  scannedWorkspaces: number
+  correctedWorkspaces: number
+  workspaceIds: Array<string>
+}
+
+export interface RuntimeTotpEnrollment {
+  secret: string
+  otpauthUrl: string
+  expiresAtMs: number
+}
+
+export interface RuntimeTotpVerifyResult {
+  ok: boolean
+  reason?: string
+  recoveryCodes?: Array<string>
+}
+
 export interface RuntimeVerificationTokenRecord {
   tokenType: number
   token: string
@@ -967,13 +1566,99 @@ export interface RuntimeVerificationTokenRecord {
   expiresAtMs: number
 }
 
+export interface RuntimeWebauthnAssertion {
+  credentialId: string
+  signCount: number
+  userVerified: boolean
+  backedUp: boolean
+  challengePayload: any
+}
+
+export interface RuntimeWebauthnAssertionInput {
+  credentialId: string
+  clientDataJson: Buffer
+  authenticatorData: Buffer
+  signature: Buffer
+  publicKey: Buffer
+  storedSignCount: number
+  rpId: string
+  expectedOrigins: Array<string>
+  requireUserVerification?: boolean
+}
+
+export interface RuntimeWebauthnCredential {
+  credentialId: string
+  publicKey: Buffer
+  algorithm: number
+  signCount: number
+  aaguid: string
+  attestationFormat: string
+  userVerified: boolean
+  backupEligible: boolean
+  backedUp: boolean
+  challengePayload: any
+}
+
+export interface RuntimeWebauthnRegistrationInput {
+  clientDataJson: Buffer
+  attestationObject: Buffer
+  rpId: string
+  expectedOrigins: Array<string>
+  requireUserVerification?: boolean
+}
+
+export interface RuntimeWorkspaceArchiveProgress {
+  archiveId: string
+  sourceWorkspaceId: string
+  targetWorkspaceId?: string
+  phase: string
+  docs: number
+  updates: number
+  blobs: number
+  missingBlobs: number
+  bytes: number
+  completed: boolean
+}
+
+export interface RuntimeWorkspaceInviteLinkInput {
+  workspaceId: string
+  inviteId: string
+  inviterUserId: string
+  role?: string
+  maxUses?: number
+  emailDomain?: string
+  ttlMs: number
+}
+
 export interface RuntimeWorkspaceInviteLinkRecord {
   workspaceId: string
   inviteId: string
   inviterUserId: string
+  role?: string
+  maxUses?: number
+  uses: number
+  emailDomain?: string
   expiresAtMs: number
 }
 
+export interface RuntimeWorkspaceInviteLinkRedeemResult {
+  ok: boolean
+  reason?: string
+  link?: RuntimeWorkspaceInviteLinkRecord
+}
+
+export interface RuntimeWorkspacePurgeResult {
+  workspaceId: string
+  phase: string
+  deletedRows: number
+  deletedObjects: number
+  totalDeletedRows: number
+  totalDeletedObjects: number
+  remainingRows: number
+  remainingObjects: boolean
+  completed: boolean
+}
+
 export interface RuntimeWorkspaceStatsDailyRecalibrationResult {
   processed: number
   lastSid: number
@@ -981,6 +1666,13 @@ export interface RuntimeWorkspaceStatsDailyRecalibrationResult {
   skipped: boolean
 }
 
+export interface RuntimeWorkspaceStatsGrowth {
+  workspaceId: string
+  startValue: number
+  endValue: number
+  growth: number
+}
+
 export interface RuntimeWorkspaceStatsRecalibrationResult {
   processed: number
   lastSid: number
@@ -993,11 +1685,31 @@ export interface RuntimeWorkspaceStatsRefreshResult {
   skipped: boolean
 }
 
+export interface RuntimeWorkspaceStatsSeries {
+  workspaceId: string
+  points: Array<RuntimeWorkspaceStatsSeriesPoint>
+}
+
+export interface RuntimeWorkspaceStatsSeriesPoint {
+  bucketStartMs: number
+  storageBytes?: number
+  docCount?: number
+  memberCount?: number
+  blobCount?: number
+}
+
 export interface RuntimeWorkspaceStatsSnapshotResult {
   snapshotted: number
   skipped: boolean
 }
 
+export interface RuntimeWorkspaceStorageUsage {
+  workspaceId: string
+  usedBytes: number
+  reservedBytes: number
+  reconciledAtMs?: number
+}
+
 export declare function safeFetch(request: SafeFetchRequest): Promise<SafeFetchResponse>
 
 export type SafeFetchMethod =  'get'|
@@ -1050,6 +1762,11 @@ export interface StorageRuntimeHealth {
   providerConfigured: boolean
   provider?: string
   bucket?: string
+  /**
+   * Circuit breaker state of the blob bucket: `closed`, `open` or
+   * `half_open`. Unset for local providers.
+   */
+  circuitState?: string
 }
 
 export interface ToolContract {
@@ -1118,6 +1835,12 @@ export declare function updateLicenseSeats(request: LicenseSeatsRequest): Promis
  */
 export declare function updateRootDocMetaTitle(rootDocBin: Buffer, docId: string, title: string): Buffer
 
+/**
+ * Validate every runtime-owned module of an app config document. Issues are
+ * reported with dotted paths such as `storages.blob.storage.config.region`.
+ */
+export declare function validateConfig(configJson: string): Array<RuntimeConfigIssue>
+
 /**
  * Check whether a Yjs update binary can be decoded without applying it to a
  * document state.
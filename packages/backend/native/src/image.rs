@@ -52,7 +52,7 @@ pub fn process_image(input: Buffer, max_edge: u32, keep_exif: bool) -> AsyncTask
   })
 }
 
-fn process_image_inner(input: &[u8], max_edge: u32, keep_exif: bool) -> AnyResult<Vec<u8>> {
+pub(crate) fn process_image_inner(input: &[u8], max_edge: u32, keep_exif: bool) -> AnyResult<Vec<u8>> {
   if max_edge == 0 {
     bail!("max_edge must be greater than 0");
   }
@@ -77,6 +77,14 @@ fn process_image_inner(input: &[u8], max_edge: u32, keep_exif: bool) -> AnyResul
   Ok(output)
 }
 
+/// Width and height read from the image header, without decoding pixels.
+pub(crate) fn image_dimensions(input: &[u8]) -> AnyResult<(u32, u32)> {
+  let format = image::guess_format(input).context("unsupported image format")?;
+  let (width, height) = read_dimensions(input, format)?;
+  validate_dimensions(width, height)?;
+  Ok((width, height))
+}
+
 fn read_dimensions(input: &[u8], format: ImageFormat) -> AnyResult<(u32, u32)> {
   ImageReader::with_format(Cursor::new(input), format)
     .into_dimensions()
@@ -300,6 +308,12 @@ mod tests {
     assert_eq!(decoded.dimensions(), (8, 6));
   }
 
+  #[test]
+  fn image_dimensions_reads_header() {
+    assert_eq!(image_dimensions(&encode_png(1024, 256)).unwrap(), (1024, 256));
+    assert!(image_dimensions(b"not-an-image").is_err());
+  }
+
   #[test]
   fn process_image_scales_down_large_dimensions() {
     let png = encode_png(1024, 256);
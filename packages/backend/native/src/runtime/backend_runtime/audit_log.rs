@@ -0,0 +1,382 @@
+use chrono::{DateTime, Utc};
+use napi::Result;
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgPool};
+
+use super::{
+  BackendRuntime, RuntimeError, RuntimeResult,
+  constants::{
+    AUDIT_EVENTS_DEFAULT_PAGE_SIZE, AUDIT_EVENTS_GENESIS_HASH, AUDIT_EVENTS_LOCK_KEY, AUDIT_EVENTS_MAX_PAGE_SIZE,
+  },
+  napi_error,
+  types::{
+    RuntimeAuditChainVerification, RuntimeAuditEvent, RuntimeAuditEventInput, RuntimeAuditEventPage,
+    RuntimeAuditEventQuery,
+  },
+};
+
+#[derive(FromRow)]
+struct AuditEventRow {
+  seq: i64,
+  workspace_id: Option<String>,
+  actor_id: Option<String>,
+  action: String,
+  target_type: Option<String>,
+  target_id: Option<String>,
+  details: serde_json::Value,
+  prev_hash: String,
+  hash: String,
+  created_at_ms: i64,
+}
+
+impl AuditEventRow {
+  fn computed_hash(&self) -> String {
+    event_hash(
+      &self.prev_hash,
+      serde_json::json!([
+        self.created_at_ms,
+        self.workspace_id,
+        self.actor_id,
+        self.action,
+        self.target_type,
+        self.target_id,
+        self.details,
+      ]),
+    )
+  }
+}
+
+impl From<AuditEventRow> for RuntimeAuditEvent {
+  fn from(row: AuditEventRow) -> Self {
+    Self {
+      seq: row.seq,
+      workspace_id: row.workspace_id,
+      actor_id: row.actor_id,
+      action: row.action,
+      target_type: row.target_type,
+      target_id: row.target_id,
+      details: row.details,
+      prev_hash: row.prev_hash,
+      hash: row.hash,
+      created_at_ms: row.created_at_ms,
+    }
+  }
+}
+
+struct AuditEventFilter {
+  workspace_id: Option<String>,
+  actor_id: Option<String>,
+  action: Option<String>,
+  since: Option<DateTime<Utc>>,
+  until: Option<DateTime<Utc>>,
+  before_seq: Option<i64>,
+  limit: i64,
+}
+
+struct AuditLogStore {
+  pool: PgPool,
+}
+
+impl AuditLogStore {
+  fn new(pool: PgPool) -> Self {
+    Self { pool }
+  }
+
+  async fn append(&self, input: RuntimeAuditEventInput) -> RuntimeResult<RuntimeAuditEvent> {
+    let mut tx = self
+      .pool
+      .begin()
+      .await
+      .map_err(|err| RuntimeError::database("AuditLog transaction failed", err))?;
+
+    // Appends are serialized so every row links to the one before it.
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+      .bind(AUDIT_EVENTS_LOCK_KEY)
+      .execute(&mut *tx)
+      .await
+      .map_err(|err| RuntimeError::database("AuditLog append lock failed", err))?;
+
+    let prev_hash = sqlx::query_scalar::<_, String>("SELECT hash FROM audit_events ORDER BY seq DESC LIMIT 1")
+      .fetch_optional(&mut *tx)
+      .await
+      .map_err(|err| RuntimeError::database("AuditLog chain head lookup failed", err))?
+      .unwrap_or_else(|| AUDIT_EVENTS_GENESIS_HASH.to_string());
+
+    // Hash the details exactly as JSONB will hand them back on verification.
+    let details = sqlx::query_scalar::<_, serde_json::Value>("SELECT $1::JSONB")
+      .bind(input.details.unwrap_or_else(|| serde_json::json!({})))
+      .fetch_one(&mut *tx)
+      .await
+      .map_err(|err| RuntimeError::database("AuditLog details normalization failed", err))?;
+
+    let created_at_ms = Utc::now().timestamp_millis();
+    let created_at = DateTime::<Utc>::from_timestamp_millis(created_at_ms)
+      .ok_or_else(|| RuntimeError::invalid_state("AuditLog clock is out of range"))?;
+    let hash = event_hash(
+      &prev_hash,
+      serde_json::json!([
+        created_at_ms,
+        input.workspace_id,
+        input.actor_id,
+        input.action,
+        input.target_type,
+        input.target_id,
+        details,
+      ]),
+    );
+
+    let row = sqlx::query_as::<_, AuditEventRow>(
+      r#"
+      INSERT INTO audit_events (
+        workspace_id, actor_id, action, target_type, target_id, details, prev_hash, hash, created_at
+      )
+      VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+      RETURNING seq, workspace_id, actor_id, action, target_type, target_id, details, prev_hash, hash,
+        (EXTRACT(EPOCH FROM created_at) * 1000)::BIGINT AS created_at_ms
+      "#,
+    )
+    .bind(&input.workspace_id)
+    .bind(&input.actor_id)
+    .bind(&input.action)
+    .bind(&input.target_type)
+    .bind(&input.target_id)
+    .bind(&details)
+    .bind(&prev_hash)
+    .bind(&hash)
+    .bind(created_at)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|err| RuntimeError::database("AuditLog append failed", err))?;
+
+    tx.commit()
+      .await
+      .map_err(|err| RuntimeError::database("AuditLog transaction commit failed", err))?;
+
+    Ok(row.into())
+  }
+
+  async fn list(&self, filter: AuditEventFilter) -> RuntimeResult<RuntimeAuditEventPage> {
+    let rows = sqlx::query_as::<_, AuditEventRow>(
+      r#"
+      SELECT seq, workspace_id, actor_id, action, target_type, target_id, details, prev_hash, hash,
+        (EXTRACT(EPOCH FROM created_at) * 1000)::BIGINT AS created_at_ms
+      FROM audit_events
+      WHERE ($1::TEXT IS NULL OR workspace_id = $1)
+        AND ($2::TEXT IS NULL OR actor_id = $2)
+        AND ($3::TEXT IS NULL OR action = $3)
+        AND ($4::TIMESTAMPTZ IS NULL OR created_at >= $4)
+        AND ($5::TIMESTAMPTZ IS NULL OR created_at < $5)
+        AND ($6::BIGINT IS NULL OR seq < $6)
+      ORDER BY seq DESC
+      LIMIT $7
+      "#,
+    )
+    .bind(&filter.workspace_id)
+    .bind(&filter.actor_id)
+    .bind(&filter.action)
+    .bind(filter.since)
+    .bind(filter.until)
+    .bind(filter.before_seq)
+    .bind(filter.limit)
+    .fetch_all(&self.pool)
+    .await
+    .map_err(|err| RuntimeError::database("AuditLog list failed", err))?;
+
+    let next_cursor = if rows.len() as i64 == filter.limit {
+      rows.last().map(|row| row.seq.to_string())
+    } else {
+      None
+    };
+    Ok(RuntimeAuditEventPage {
+      events: rows.into_iter().map(RuntimeAuditEvent::from).collect(),
+      next_cursor,
+    })
+  }
+
+  async fn verify(&self, after_seq: Option<i64>, limit: i64) -> RuntimeResult<RuntimeAuditChainVerification> {
+    let anchor = match after_seq {
+      Some(after_seq) => {
+        sqlx::query_scalar::<_, String>("SELECT hash FROM audit_events WHERE seq <= $1 ORDER BY seq DESC LIMIT 1")
+          .bind(after_seq)
+          .fetch_optional(&self.pool)
+          .await
+          .map_err(|err| RuntimeError::database("AuditLog chain anchor lookup failed", err))?
+      }
+      None => None,
+    };
+    let rows = sqlx::query_as::<_, AuditEventRow>(
+      r#"
+      SELECT seq, workspace_id, actor_id, action, target_type, target_id, details, prev_hash, hash,
+        (EXTRACT(EPOCH FROM created_at) * 1000)::BIGINT AS created_at_ms
+      FROM audit_events
+      WHERE seq > $1
+      ORDER BY seq ASC
+      LIMIT $2
+      "#,
+    )
+    .bind(after_seq.unwrap_or(0))
+    .bind(limit)
+    .fetch_all(&self.pool)
+    .await
+    .map_err(|err| RuntimeError::database("AuditLog chain scan failed", err))?;
+
+    // Without an anchor the oldest retained row is trusted as the start of the
+    // chain, since retention cleanup removes its predecessors.
+    let mut expected_prev = anchor;
+    let mut result = RuntimeAuditChainVerification {
+      checked: 0,
+      last_seq: None,
+      broken_at_seq: None,
+    };
+    for row in rows {
+      let linked = expected_prev
+        .as_deref()
+        .is_none_or(|expected| expected == row.prev_hash);
+      if !linked || row.computed_hash() != row.hash {
+        result.broken_at_seq = Some(row.seq);
+        break;
+      }
+      result.checked += 1;
+      result.last_seq = Some(row.seq);
+      expected_prev = Some(row.hash);
+    }
+
+    Ok(result)
+  }
+}
+
+fn event_hash(prev_hash: &str, fields: serde_json::Value) -> String {
+  let mut material = String::from(prev_hash);
+  material.push('\n');
+  write_canonical_json(&fields, &mut material);
+  hex::encode(Sha256::digest(material.as_bytes()))
+}
+
+/// Serializes JSON with object keys sorted so hashes do not depend on map
+/// ordering.
+fn write_canonical_json(value: &serde_json::Value, out: &mut String) {
+  match value {
+    serde_json::Value::Object(map) => {
+      let mut entries = map.iter().collect::<Vec<_>>();
+      entries.sort_by_key(|(key, _)| *key);
+      out.push('{');
+      for (index, (key, value)) in entries.into_iter().enumerate() {
+        if index > 0 {
+          out.push(',');
+        }
+        out.push_str(&serde_json::Value::String(key.clone()).to_string());
+        out.push(':');
+        write_canonical_json(value, out);
+      }
+      out.push('}');
+    }
+    serde_json::Value::Array(items) => {
+      out.push('[');
+      for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+          out.push(',');
+        }
+        write_canonical_json(item, out);
+      }
+      out.push(']');
+    }
+    _ => out.push_str(&value.to_string()),
+  }
+}
+
+fn timestamp_filter(value: Option<i64>, name: &str) -> Result<Option<DateTime<Utc>>> {
+  value
+    .map(|ms| {
+      DateTime::<Utc>::from_timestamp_millis(ms).ok_or_else(|| napi_error(format!("Invalid audit event {name}: {ms}")))
+    })
+    .transpose()
+}
+
+#[napi_derive::napi]
+impl BackendRuntime {
+  /// Append an event to the hash-chained audit log.
+  #[napi]
+  pub async fn append_audit_event(&self, input: RuntimeAuditEventInput) -> Result<RuntimeAuditEvent> {
+    if input.action.is_empty() {
+      return Err(napi_error("audit event action is required"));
+    }
+
+    AuditLogStore::new(self.pool().await?)
+      .append(input)
+      .await
+      .map_err(napi::Error::from)
+  }
+
+  /// List audit events newest first. Pass `next_cursor` back as `cursor` to
+  /// fetch the following page.
+  #[napi]
+  pub async fn list_audit_events(&self, query: RuntimeAuditEventQuery) -> Result<RuntimeAuditEventPage> {
+    let limit = query.limit.unwrap_or(AUDIT_EVENTS_DEFAULT_PAGE_SIZE);
+    if limit <= 0 || limit > AUDIT_EVENTS_MAX_PAGE_SIZE {
+      return Err(napi_error(format!(
+        "audit event page size must be between 1 and {AUDIT_EVENTS_MAX_PAGE_SIZE}"
+      )));
+    }
+    let before_seq = query
+      .cursor
+      .as_deref()
+      .map(|cursor| {
+        cursor
+          .parse::<i64>()
+          .map_err(|_| napi_error(format!("Invalid audit event cursor: {cursor}")))
+      })
+      .transpose()?;
+    let filter = AuditEventFilter {
+      since: timestamp_filter(query.since_ms, "since")?,
+      until: timestamp_filter(query.until_ms, "until")?,
+      workspace_id: query.workspace_id,
+      actor_id: query.actor_id,
+      action: query.action,
+      before_seq,
+      limit,
+    };
+
+    AuditLogStore::new(self.pool().await?)
+      .list(filter)
+      .await
+      .map_err(napi::Error::from)
+  }
+
+  /// Recompute hashes for up to `limit` events after `after_seq` and report
+  /// the first event whose link or hash does not match.
+  #[napi]
+  pub async fn verify_audit_chain(&self, after_seq: Option<i64>, limit: i64) -> Result<RuntimeAuditChainVerification> {
+    if limit <= 0 {
+      return Err(napi_error("audit chain verification limit must be positive"));
+    }
+
+    AuditLogStore::new(self.pool().await?)
+      .verify(after_seq, limit)
+      .await
+      .map_err(napi::Error::from)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn canonical_json_sorts_nested_object_keys() {
+    let mut out = String::new();
+    write_canonical_json(
+      &serde_json::json!({ "b": [1, { "z": null, "a": "x" }], "a": true }),
+      &mut out,
+    );
+    assert_eq!(out, r#"{"a":true,"b":[1,{"a":"x","z":null}]}"#);
+  }
+
+  #[test]
+  fn event_hash_links_to_previous_hash() {
+    let fields = serde_json::json!([1, "workspace", "actor", "invite_link.create", null, null, {}]);
+    let first = event_hash(AUDIT_EVENTS_GENESIS_HASH, fields.clone());
+    assert_eq!(first.len(), 64);
+    assert_eq!(first, event_hash(AUDIT_EVENTS_GENESIS_HASH, fields.clone()));
+    assert_ne!(first, event_hash(&first, fields));
+  }
+}
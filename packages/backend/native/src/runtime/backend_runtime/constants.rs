@@ -1,9 +1,29 @@
+pub(super) const AUDIT_EVENTS_DEFAULT_PAGE_SIZE: i64 = 100;
+pub(super) const AUDIT_EVENTS_GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+pub(super) const AUDIT_EVENTS_LOCK_KEY: i64 = 97_302;
+pub(super) const AUDIT_EVENTS_MAX_PAGE_SIZE: i64 = 1_000;
 pub(super) const BYOK_LOCAL_LEASE_ACTIVE_PURPOSE: &str = "copilot_byok_local_lease:active";
 pub(super) const BYOK_LOCAL_LEASE_PURPOSE: &str = "copilot_byok_local_lease";
 pub(super) const MAGIC_LINK_OTP_PURPOSE: &str = "magic_link_otp";
 pub(super) const MAX_MAGIC_LINK_OTP_ATTEMPTS: i32 = 10;
+pub(super) const MAX_TOTP_ATTEMPTS: i32 = 5;
+pub(super) const MAX_WORKSPACE_INVITE_LINKS: i64 = 50;
+pub(super) const RUNTIME_JOB_DEFAULT_MAX_ATTEMPTS: i32 = 5;
+pub(super) const RUNTIME_JOB_RETRY_BASE_MS: i64 = 1_000;
+pub(super) const RUNTIME_JOB_RETRY_MAX_MS: i64 = 60 * 60 * 1_000;
+pub(super) const TOTP_DIGITS: u32 = 6;
+pub(super) const TOTP_DRIFT_STEPS: i64 = 1;
+pub(super) const TOTP_ENROLLMENT_PURPOSE: &str = "totp:enrollment";
+pub(super) const TOTP_LOCKOUT_MS: i64 = 15 * 60 * 1_000;
+pub(super) const TOTP_PERIOD_SECONDS: i64 = 30;
+pub(super) const TOTP_RECOVERY_CODE_COUNT: usize = 10;
+pub(super) const TOTP_RECOVERY_CODE_PURPOSE: &str = "totp:recovery_code";
+pub(super) const TOTP_SECRET_PURPOSE: &str = "totp:secret";
+pub(super) const TOTP_SECRET_TTL_MS: i64 = 100 * 365 * 24 * 60 * 60 * 1_000;
 pub(super) const WORKSPACE_INVITE_LINK_ID_PURPOSE: &str = "workspace_invite_link:id";
 pub(super) const WORKSPACE_INVITE_LINK_WORKSPACE_PURPOSE: &str = "workspace_invite_link:workspace";
 pub(super) const WORKSPACE_STATS_LEASE_KEY: &str = "workspace:admin-stats:refresh";
 pub(super) const WORKSPACE_STATS_LOCK_NAMESPACE: i64 = 97_301;
 pub(super) const WORKSPACE_STATS_REFRESH_LOCK_KEY: i64 = 1;
+pub(super) const WORKSPACE_STATS_SERIES_MAX_POINTS: i64 = 1_000;
+pub(super) const WORKSPACE_STATS_SERIES_MAX_WORKSPACES: usize = 100;
@@ -1,41 +1,78 @@
+use std::{
+  future::poll_fn,
+  pin::pin,
+  task::Poll,
+  time::{Duration, Instant},
+};
+
 use napi::Result;
-use sqlx::{FromRow, PgPool};
+use sqlx::{FromRow, Postgres, Sqlite, Transaction};
+use tokio::{sync::watch, task::JoinHandle};
 
-use super::{BackendRuntime, RuntimeError, RuntimeResult, napi_error, types::CoordinationLeaseGrant};
+use super::{
+  BackendRuntime, RuntimeDatabase, RuntimeError, RuntimeResult, metrics, napi_error, types::CoordinationLeaseGrant,
+  unix_ms_now,
+};
 
 #[derive(FromRow)]
 struct LeaseGrantRow {
   fencing_token: i64,
 }
 
+#[derive(Clone)]
 struct CoordinationLeaseStore {
-  pool: PgPool,
+  database: RuntimeDatabase,
 }
 
 impl CoordinationLeaseStore {
-  fn new(pool: PgPool) -> Self {
-    Self { pool }
+  fn new(database: RuntimeDatabase) -> Self {
+    Self { database }
   }
 
   async fn acquire(&self, key: String, owner: String, ttl_ms: i64) -> RuntimeResult<Option<CoordinationLeaseGrant>> {
-    let row = sqlx::query_as::<_, LeaseGrantRow>(
-      r#"
-      INSERT INTO runtime_leases (key, owner, fencing_token, expires_at)
-      VALUES ($1, $2, 1, CURRENT_TIMESTAMP + ($3 * INTERVAL '1 millisecond'))
-      ON CONFLICT (key) DO UPDATE
-        SET owner = EXCLUDED.owner,
-            fencing_token = runtime_leases.fencing_token + 1,
-            expires_at = EXCLUDED.expires_at,
-            updated_at = CURRENT_TIMESTAMP
-        WHERE runtime_leases.expires_at <= CURRENT_TIMESTAMP
-      RETURNING fencing_token
-      "#,
-    )
-    .bind(&key)
-    .bind(&owner)
-    .bind(ttl_ms as f64)
-    .fetch_optional(&self.pool)
-    .await
+    let row = match &self.database {
+      RuntimeDatabase::Postgres(pool) => {
+        sqlx::query_as::<_, LeaseGrantRow>(
+          r#"
+          INSERT INTO runtime_leases (key, owner, fencing_token, expires_at)
+          VALUES ($1, $2, 1, CURRENT_TIMESTAMP + ($3 * INTERVAL '1 millisecond'))
+          ON CONFLICT (key) DO UPDATE
+            SET owner = EXCLUDED.owner,
+                fencing_token = runtime_leases.fencing_token + 1,
+                expires_at = EXCLUDED.expires_at,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE runtime_leases.expires_at <= CURRENT_TIMESTAMP
+          RETURNING fencing_token
+          "#,
+        )
+        .bind(&key)
+        .bind(&owner)
+        .bind(ttl_ms as f64)
+        .fetch_optional(pool)
+        .await
+      }
+      RuntimeDatabase::Sqlite(pool) => {
+        sqlx::query_as::<_, LeaseGrantRow>(
+          r#"
+          INSERT INTO runtime_leases (key, owner, fencing_token, expires_at, created_at, updated_at)
+          VALUES (?1, ?2, 1, ?3 + ?4, ?3, ?3)
+          ON CONFLICT (key) DO UPDATE
+            SET owner = excluded.owner,
+                fencing_token = runtime_leases.fencing_token + 1,
+                expires_at = excluded.expires_at,
+                updated_at = excluded.updated_at
+            WHERE runtime_leases.expires_at <= ?3
+          RETURNING fencing_token
+          "#,
+        )
+        .bind(&key)
+        .bind(&owner)
+        .bind(unix_ms_now())
+        .bind(ttl_ms)
+        .fetch_optional(pool)
+        .await
+      }
+    }
     .map_err(|err| RuntimeError::database("CoordinationLease acquire failed", err))?;
 
     Ok(row.map(|row| CoordinationLeaseGrant {
@@ -46,43 +83,226 @@ impl CoordinationLeaseStore {
   }
 
   async fn release(&self, key: &str, owner: &str, fencing_token: i64) -> RuntimeResult<bool> {
-    let result = sqlx::query(
-      r#"
-      DELETE FROM runtime_leases
-      WHERE key = $1 AND owner = $2 AND fencing_token = $3
-      "#,
-    )
-    .bind(key)
-    .bind(owner)
-    .bind(fencing_token)
-    .execute(&self.pool)
-    .await
+    let rows_affected = match &self.database {
+      RuntimeDatabase::Postgres(pool) => sqlx::query(
+        r#"
+        DELETE FROM runtime_leases
+        WHERE key = $1 AND owner = $2 AND fencing_token = $3
+        "#,
+      )
+      .bind(key)
+      .bind(owner)
+      .bind(fencing_token)
+      .execute(pool)
+      .await
+      .map(|result| result.rows_affected()),
+      RuntimeDatabase::Sqlite(pool) => sqlx::query(
+        r#"
+        DELETE FROM runtime_leases
+        WHERE key = ?1 AND owner = ?2 AND fencing_token = ?3
+        "#,
+      )
+      .bind(key)
+      .bind(owner)
+      .bind(fencing_token)
+      .execute(pool)
+      .await
+      .map(|result| result.rows_affected()),
+    }
     .map_err(|err| RuntimeError::database("CoordinationLease release failed", err))?;
 
-    Ok(result.rows_affected() == 1)
+    Ok(rows_affected == 1)
   }
 
   async fn renew(&self, key: &str, owner: &str, fencing_token: i64, ttl_ms: i64) -> RuntimeResult<bool> {
-    let result = sqlx::query(
+    let rows_affected = match &self.database {
+      RuntimeDatabase::Postgres(pool) => sqlx::query(
+        r#"
+        UPDATE runtime_leases
+        SET expires_at = CURRENT_TIMESTAMP + ($4 * INTERVAL '1 millisecond'),
+            updated_at = CURRENT_TIMESTAMP
+        WHERE key = $1
+          AND owner = $2
+          AND fencing_token = $3
+          AND expires_at > CURRENT_TIMESTAMP
+        "#,
+      )
+      .bind(key)
+      .bind(owner)
+      .bind(fencing_token)
+      .bind(ttl_ms as f64)
+      .execute(pool)
+      .await
+      .map(|result| result.rows_affected()),
+      RuntimeDatabase::Sqlite(pool) => sqlx::query(
+        r#"
+        UPDATE runtime_leases
+        SET expires_at = ?4 + ?5,
+            updated_at = ?4
+        WHERE key = ?1
+          AND owner = ?2
+          AND fencing_token = ?3
+          AND expires_at > ?4
+        "#,
+      )
+      .bind(key)
+      .bind(owner)
+      .bind(fencing_token)
+      .bind(unix_ms_now())
+      .bind(ttl_ms)
+      .execute(pool)
+      .await
+      .map(|result| result.rows_affected()),
+    }
+    .map_err(|err| RuntimeError::database("CoordinationLease renew failed", err))?;
+
+    Ok(rows_affected == 1)
+  }
+}
+
+/// A held coordination lease that renews itself every `ttl / 3` until it is
+/// released or dropped.
+///
+/// Renewal failures are surfaced through [`Self::is_lost`] and
+/// [`Self::until_lost`]; callers should stop work once the lease is lost and
+/// rely on [`Self::assert_held`] to fence writes that must not outlive it.
+pub(super) struct CoordinationLeaseGuard {
+  grant: CoordinationLeaseGrant,
+  store: CoordinationLeaseStore,
+  lost: watch::Receiver<bool>,
+  renewer: JoinHandle<()>,
+}
+
+impl CoordinationLeaseGuard {
+  fn spawn(store: CoordinationLeaseStore, grant: CoordinationLeaseGrant, ttl_ms: i64) -> Self {
+    let (lost_tx, lost) = watch::channel(false);
+    let renew_store = store.clone();
+    let key = grant.key.clone();
+    let owner = grant.owner.clone();
+    let fencing_token = grant.fencing_token;
+    let ttl = Duration::from_millis(ttl_ms as u64);
+    let renewer = tokio::spawn(async move {
+      let mut last_renewed = Instant::now();
+      loop {
+        tokio::time::sleep(ttl / 3).await;
+        match renew_store.renew(&key, &owner, fencing_token, ttl_ms).await {
+          Ok(true) => last_renewed = Instant::now(),
+          // Transient errors are retried until the lease would have expired.
+          Err(_) if last_renewed.elapsed() < ttl => {}
+          Ok(false) | Err(_) => {
+            let _ = lost_tx.send(true);
+            return;
+          }
+        }
+      }
+    });
+
+    Self {
+      grant,
+      store,
+      lost,
+      renewer,
+    }
+  }
+
+  pub(super) fn is_lost(&self) -> bool {
+    *self.lost.borrow()
+  }
+
+  /// Drive `work` until it finishes or the lease is lost, whichever comes
+  /// first. Returns `None` when the lease was lost; `work` is dropped, which
+  /// rolls back any transaction it had open.
+  pub(super) async fn until_lost<F: Future>(&self, work: F) -> Option<F::Output> {
+    let mut lost = self.lost.clone();
+    let mut work = pin!(work);
+    // `wait_for` only errors when the renewer stopped without signalling,
+    // which means the lease is no longer being renewed either.
+    let mut lost = pin!(async move {
+      let _ = lost.wait_for(|lost| *lost).await;
+    });
+    let output = poll_fn(|cx| {
+      if let Poll::Ready(output) = work.as_mut().poll(cx) {
+        return Poll::Ready(Some(output));
+      }
+      lost.as_mut().poll(cx).map(|_| None)
+    })
+    .await;
+    if output.is_none() {
+      metrics::COORDINATION_LEASES_LOST.inc(&[]);
+    }
+    output
+  }
+
+  /// Lock the lease row for the rest of `tx` and fail unless it is still held
+  /// with this guard's fencing token. A competing acquire blocks on the row
+  /// lock, so writes made later in the same transaction are fenced.
+  pub(super) async fn assert_held(&self, tx: &mut Transaction<'_, Postgres>) -> RuntimeResult<()> {
+    let held = sqlx::query(
       r#"
-      UPDATE runtime_leases
-      SET expires_at = CURRENT_TIMESTAMP + ($4 * INTERVAL '1 millisecond'),
-          updated_at = CURRENT_TIMESTAMP
+      SELECT 1
+      FROM runtime_leases
       WHERE key = $1
         AND owner = $2
         AND fencing_token = $3
         AND expires_at > CURRENT_TIMESTAMP
+      FOR SHARE
       "#,
     )
-    .bind(key)
-    .bind(owner)
-    .bind(fencing_token)
-    .bind(ttl_ms as f64)
-    .execute(&self.pool)
+    .bind(&self.grant.key)
+    .bind(&self.grant.owner)
+    .bind(self.grant.fencing_token)
+    .fetch_optional(&mut **tx)
     .await
-    .map_err(|err| RuntimeError::database("CoordinationLease renew failed", err))?;
+    .map_err(|err| RuntimeError::database("CoordinationLease fencing check failed", err))?
+    .is_some();
+
+    if held { Ok(()) } else { Err(self.not_held()) }
+  }
+
+  /// [`Self::assert_held`] for the embedded SQLite database. SQLite has no
+  /// row locks; its single writer fences the rest of `tx` instead.
+  pub(super) async fn assert_held_sqlite(&self, tx: &mut Transaction<'_, Sqlite>) -> RuntimeResult<()> {
+    let held = sqlx::query(
+      r#"
+      SELECT 1
+      FROM runtime_leases
+      WHERE key = ?1
+        AND owner = ?2
+        AND fencing_token = ?3
+        AND expires_at > ?4
+      "#,
+    )
+    .bind(&self.grant.key)
+    .bind(&self.grant.owner)
+    .bind(self.grant.fencing_token)
+    .bind(unix_ms_now())
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(|err| RuntimeError::database("CoordinationLease fencing check failed", err))?
+    .is_some();
+
+    if held { Ok(()) } else { Err(self.not_held()) }
+  }
+
+  fn not_held(&self) -> RuntimeError {
+    RuntimeError::invalid_state(format!(
+      "coordination lease {} is no longer held by fencing token {}",
+      self.grant.key, self.grant.fencing_token
+    ))
+  }
+
+  pub(super) async fn release(self) -> RuntimeResult<bool> {
+    self.renewer.abort();
+    self
+      .store
+      .release(&self.grant.key, &self.grant.owner, self.grant.fencing_token)
+      .await
+  }
+}
 
-    Ok(result.rows_affected() == 1)
+impl Drop for CoordinationLeaseGuard {
+  fn drop(&mut self) {
+    self.renewer.abort();
   }
 }
 
@@ -101,9 +321,33 @@ impl BackendRuntime {
       return Err(RuntimeError::invalid_input("coordination lease owner is required"));
     }
 
-    CoordinationLeaseStore::new(self.pool().await?)
-      .acquire(key, owner, ttl_ms)
-      .await
+    let store = CoordinationLeaseStore::new(self.database().await?);
+    let grant = metrics::timed(
+      metrics::BACKEND,
+      "acquire_coordination_lease",
+      store.acquire(key, owner, ttl_ms),
+    )
+    .await?;
+    let result = if grant.is_some() { "acquired" } else { "contended" };
+    metrics::COORDINATION_LEASE_ACQUIRES.inc(&[("result", result)]);
+    Ok(grant)
+  }
+
+  pub(super) async fn acquire_coordination_lease_guard(
+    &self,
+    key: String,
+    owner: String,
+    ttl_ms: i64,
+  ) -> RuntimeResult<Option<CoordinationLeaseGuard>> {
+    let Some(grant) = self.acquire_coordination_lease_inner(key, owner, ttl_ms).await? else {
+      return Ok(None);
+    };
+
+    Ok(Some(CoordinationLeaseGuard::spawn(
+      CoordinationLeaseStore::new(self.database().await?),
+      grant,
+      ttl_ms,
+    )))
   }
 
   pub(crate) async fn release_coordination_lease_inner(
@@ -112,7 +356,7 @@ impl BackendRuntime {
     owner: String,
     fencing_token: i64,
   ) -> RuntimeResult<bool> {
-    CoordinationLeaseStore::new(self.pool().await?)
+    CoordinationLeaseStore::new(self.database().await?)
       .release(&key, &owner, fencing_token)
       .await
   }
@@ -155,7 +399,7 @@ impl BackendRuntime {
       return Err(napi_error("coordination lease ttl must be positive"));
     }
 
-    CoordinationLeaseStore::new(self.pool().await?)
+    CoordinationLeaseStore::new(self.database().await?)
       .renew(&key, &owner, fencing_token, ttl_ms)
       .await
       .map_err(napi::Error::from)
@@ -1,8 +1,16 @@
+use std::time::Instant;
+
 use chrono::{DateTime, Duration, Utc};
-use sqlx::{FromRow, PgPool, Postgres, Row, Transaction};
+use sqlx::{FromRow, PgPool, Postgres, Row, Sqlite, SqlitePool, Transaction};
 use y_octo::Doc;
 
-use super::{BackendRuntime, RuntimeError, RuntimeResult, napi_error, types::RuntimeDocCompactionResult};
+use super::{
+  BackendRuntime, RuntimeDatabase, RuntimeError, RuntimeResult,
+  coordination_lease::CoordinationLeaseGuard,
+  doc_encryption::{DocMasterKeys, WorkspaceDocCipher},
+  metrics, napi_error,
+  types::RuntimeDocCompactionResult,
+};
 
 #[derive(FromRow)]
 struct SnapshotRow {
@@ -18,32 +26,68 @@ struct UpdateRow {
   created_by: Option<String>,
 }
 
+/// Snapshot row in the embedded SQLite tables, timestamped in unix epoch
+/// milliseconds. Doc encryption is Postgres-only, so the blob is plaintext.
+#[derive(FromRow)]
+struct SqliteSnapshotRow {
+  blob: Vec<u8>,
+  updated_at: i64,
+  updated_by: Option<String>,
+}
+
+#[derive(FromRow)]
+struct SqliteUpdateRow {
+  blob: Vec<u8>,
+  created_at: i64,
+  created_by: Option<String>,
+}
+
 struct DocCompactorStore {
-  pool: PgPool,
+  database: RuntimeDatabase,
+  master_keys: DocMasterKeys,
 }
 
 impl DocCompactorStore {
-  fn new(pool: PgPool) -> Self {
-    Self { pool }
+  fn new(database: RuntimeDatabase, master_keys: DocMasterKeys) -> Self {
+    Self { database, master_keys }
   }
 
   async fn compact_doc(
     &self,
+    lease: &CoordinationLeaseGuard,
     workspace_id: &str,
     doc_id: &str,
     batch_limit: i64,
     history_min_interval_ms: i64,
     history_max_age_seconds: i64,
   ) -> RuntimeResult<(i64, bool)> {
-    compact_doc(
-      self.pool.clone(),
-      workspace_id,
-      doc_id,
-      batch_limit,
-      history_min_interval_ms,
-      history_max_age_seconds,
-    )
-    .await
+    match &self.database {
+      RuntimeDatabase::Postgres(pool) => {
+        compact_doc(
+          pool.clone(),
+          &self.master_keys,
+          lease,
+          workspace_id,
+          doc_id,
+          batch_limit,
+          history_min_interval_ms,
+          history_max_age_seconds,
+        )
+        .await
+      }
+      RuntimeDatabase::Sqlite(pool) => {
+        compact_doc_sqlite(
+          pool,
+          lease,
+          workspace_id,
+          doc_id,
+          batch_limit,
+          history_min_interval_ms,
+          history_max_age_seconds,
+        )
+        .await
+      }
+    }
   }
 }
 
@@ -63,6 +107,23 @@ fn apply_updates(updates: impl IntoIterator<Item = Vec<u8>>) -> RuntimeResult<Ve
     .map_err(|err| RuntimeError::invalid_state(format!("DocCompactor encode failed: {err}")))
 }
 
+/// Merge `updates` on top of `snapshot`, skipping the merge when there is a
+/// single input.
+fn merge_doc<'a>(
+  snapshot: Option<&'a Vec<u8>>,
+  updates: impl ExactSizeIterator<Item = &'a Vec<u8>>,
+) -> RuntimeResult<Vec<u8>> {
+  let mut merge_inputs = Vec::with_capacity(updates.len() + usize::from(snapshot.is_some()));
+  merge_inputs.extend(snapshot.cloned());
+  merge_inputs.extend(updates.cloned());
+
+  if merge_inputs.len() == 1 {
+    Ok(merge_inputs.remove(0))
+  } else {
+    apply_updates(merge_inputs)
+  }
+}
+
 fn checked_milliseconds(value: i64, field: &str) -> RuntimeResult<Duration> {
   Duration::try_milliseconds(value)
     .ok_or_else(|| RuntimeError::invalid_input(format!("DocCompactor {field} is too large")))
@@ -118,6 +179,7 @@ async fn load_updates(
 
 async fn upsert_snapshot(
   tx: &mut Transaction<'_, Postgres>,
+  cipher: &WorkspaceDocCipher,
   workspace_id: &str,
   doc_id: &str,
   blob: &[u8],
@@ -148,7 +210,7 @@ async fn upsert_snapshot(
   )
   .bind(workspace_id)
   .bind(doc_id)
-  .bind(blob)
+  .bind(cipher.encrypt(doc_id, blob)?)
   .bind(blob.len() as i64)
   .bind(timestamp)
   .bind(editor)
@@ -205,6 +267,7 @@ async fn should_create_history(
 
 async fn create_history(
   tx: &mut Transaction<'_, Postgres>,
+  cipher: &WorkspaceDocCipher,
   workspace_id: &str,
   doc_id: &str,
   snapshot: &SnapshotRow,
@@ -231,7 +294,7 @@ async fn create_history(
   .bind(workspace_id)
   .bind(doc_id)
   .bind(snapshot.updated_at)
-  .bind(&snapshot.blob)
+  .bind(cipher.encrypt(doc_id, &snapshot.blob)?)
   .bind(expired_at)
   .bind(snapshot.updated_by.as_deref())
   .execute(&mut **tx)
@@ -265,8 +328,11 @@ async fn delete_updates(
   Ok(result.rows_affected() as i64)
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn compact_doc(
   pool: PgPool,
+  master_keys: &DocMasterKeys,
+  lease: &CoordinationLeaseGuard,
   workspace_id: &str,
   doc_id: &str,
   batch_limit: i64,
@@ -278,8 +344,8 @@ async fn compact_doc(
     .await
     .map_err(|err| RuntimeError::database("DocCompactor begin transaction failed", err))?;
 
-  let snapshot = load_snapshot(&mut tx, workspace_id, doc_id).await?;
-  let updates = load_updates(&mut tx, workspace_id, doc_id, batch_limit).await?;
+  let mut snapshot = load_snapshot(&mut tx, workspace_id, doc_id).await?;
+  let mut updates = load_updates(&mut tx, workspace_id, doc_id, batch_limit).await?;
   if updates.is_empty() {
     tx.commit()
       .await
@@ -287,21 +353,25 @@ async fn compact_doc(
     return Ok((0, false));
   }
 
-  let last = updates.last().expect("updates is not empty");
-  let mut merge_inputs = Vec::with_capacity(updates.len() + usize::from(snapshot.is_some()));
-  if let Some(snapshot) = &snapshot {
-    merge_inputs.push(snapshot.blob.clone());
+  // Merge and history checks work on plaintext; every write re-seals with the
+  // active data key, which also migrates blobs sealed with a retired one.
+  let cipher = WorkspaceDocCipher::for_write(&mut tx, master_keys, workspace_id).await?;
+  if let Some(snapshot) = snapshot.as_mut() {
+    snapshot.blob = cipher.decrypt(doc_id, std::mem::take(&mut snapshot.blob))?;
+  }
+  for update in updates.iter_mut() {
+    update.blob = cipher.decrypt(doc_id, std::mem::take(&mut update.blob))?;
   }
-  merge_inputs.extend(updates.iter().map(|update| update.blob.clone()));
 
-  let final_blob = if merge_inputs.len() == 1 {
-    merge_inputs.remove(0)
-  } else {
-    apply_updates(merge_inputs)?
-  };
+  let last = updates.last().expect("updates is not empty");
+  let final_blob = merge_doc(
+    snapshot.as_ref().map(|snapshot| &snapshot.blob),
+    updates.iter().map(|update| &update.blob),
+  )?;
 
   let snapshot_updated = upsert_snapshot(
     &mut tx,
+    &cipher,
     workspace_id,
     doc_id,
     &final_blob,
@@ -315,11 +385,20 @@ async fn compact_doc(
     && let Some(snapshot) = &snapshot
     && should_create_history(&mut tx, snapshot, workspace_id, doc_id, history_min_interval_ms).await?
   {
-    history_created = create_history(&mut tx, workspace_id, doc_id, snapshot, history_max_age_seconds).await?;
+    history_created = create_history(
+      &mut tx,
+      &cipher,
+      workspace_id,
+      doc_id,
+      snapshot,
+      history_max_age_seconds,
+    )
+    .await?;
   }
 
   let timestamps = updates.iter().map(|update| update.created_at).collect::<Vec<_>>();
   let deleted = delete_updates(&mut tx, workspace_id, doc_id, &timestamps).await?;
+  lease.assert_held(&mut tx).await?;
 
   tx.commit()
     .await
@@ -328,6 +407,171 @@ async fn compact_doc(
   Ok((deleted, history_created))
 }
 
+#[allow(clippy::too_many_arguments)]
+async fn compact_doc_sqlite(
+  pool: &SqlitePool,
+  lease: &CoordinationLeaseGuard,
+  workspace_id: &str,
+  doc_id: &str,
+  batch_limit: i64,
+  history_min_interval_ms: i64,
+  history_max_age_seconds: i64,
+) -> RuntimeResult<(i64, bool)> {
+  let mut tx = pool
+    .begin()
+    .await
+    .map_err(|err| RuntimeError::database("DocCompactor begin transaction failed", err))?;
+
+  let snapshot = sqlx::query_as::<_, SqliteSnapshotRow>(
+    r#"
+    SELECT blob, updated_at, updated_by
+    FROM snapshots
+    WHERE workspace_id = ?1 AND guid = ?2
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(doc_id)
+  .fetch_optional(&mut *tx)
+  .await
+  .map_err(|err| RuntimeError::database("DocCompactor load snapshot failed", err))?;
+  let updates = sqlx::query_as::<_, SqliteUpdateRow>(
+    r#"
+    SELECT blob, created_at, created_by
+    FROM updates
+    WHERE workspace_id = ?1 AND guid = ?2
+    ORDER BY created_at ASC
+    LIMIT ?3
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(doc_id)
+  .bind(batch_limit)
+  .fetch_all(&mut *tx)
+  .await
+  .map_err(|err| RuntimeError::database("DocCompactor load updates failed", err))?;
+  let Some(last) = updates.last() else {
+    tx.commit()
+      .await
+      .map_err(|err| RuntimeError::database("DocCompactor commit transaction failed", err))?;
+    return Ok((0, false));
+  };
+
+  let final_blob = merge_doc(
+    snapshot.as_ref().map(|snapshot| &snapshot.blob),
+    updates.iter().map(|update| &update.blob),
+  )?;
+  let snapshot_updated = !is_empty_doc(&final_blob)
+    && sqlx::query(
+      r#"
+      INSERT INTO snapshots
+        (workspace_id, guid, blob, size, created_at, updated_at, created_by, updated_by)
+      VALUES
+        (?1, ?2, ?3, ?4, ?5, ?5, ?6, ?6)
+      ON CONFLICT (workspace_id, guid)
+      DO UPDATE SET
+        blob = excluded.blob,
+        size = excluded.size,
+        updated_at = excluded.updated_at,
+        updated_by = excluded.updated_by
+      WHERE snapshots.updated_at <= excluded.updated_at
+      RETURNING updated_at
+      "#,
+    )
+    .bind(workspace_id)
+    .bind(doc_id)
+    .bind(&final_blob)
+    .bind(final_blob.len() as i64)
+    .bind(last.created_at)
+    .bind(last.created_by.as_deref())
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|err| RuntimeError::database("DocCompactor upsert snapshot failed", err))?
+    .is_some();
+
+  let mut history_created = false;
+  if snapshot_updated
+    && history_max_age_seconds > 0
+    && let Some(snapshot) = &snapshot
+    && should_create_history_sqlite(&mut tx, snapshot, workspace_id, doc_id, history_min_interval_ms).await?
+  {
+    let max_age = checked_seconds(history_max_age_seconds, "history max age")?;
+    sqlx::query(
+      r#"
+      INSERT INTO snapshot_histories
+        (workspace_id, guid, timestamp, blob, expired_at, created_by)
+      VALUES
+        (?1, ?2, ?3, ?4, ?5, ?6)
+      ON CONFLICT (workspace_id, guid, timestamp)
+      DO UPDATE SET expired_at = excluded.expired_at
+      "#,
+    )
+    .bind(workspace_id)
+    .bind(doc_id)
+    .bind(snapshot.updated_at)
+    .bind(&snapshot.blob)
+    .bind((Utc::now() + max_age).timestamp_millis())
+    .bind(snapshot.updated_by.as_deref())
+    .execute(&mut *tx)
+    .await
+    .map_err(|err| RuntimeError::database("DocCompactor create history failed", err))?;
+    history_created = true;
+  }
+
+  let mut deleted = 0;
+  for update in &updates {
+    deleted += sqlx::query("DELETE FROM updates WHERE workspace_id = ?1 AND guid = ?2 AND created_at = ?3")
+      .bind(workspace_id)
+      .bind(doc_id)
+      .bind(update.created_at)
+      .execute(&mut *tx)
+      .await
+      .map_err(|err| RuntimeError::database("DocCompactor delete updates failed", err))?
+      .rows_affected() as i64;
+  }
+  lease.assert_held_sqlite(&mut tx).await?;
+
+  tx.commit()
+    .await
+    .map_err(|err| RuntimeError::database("DocCompactor commit transaction failed", err))?;
+
+  Ok((deleted, history_created))
+}
+
+async fn should_create_history_sqlite(
+  tx: &mut Transaction<'_, Sqlite>,
+  snapshot: &SqliteSnapshotRow,
+  workspace_id: &str,
+  doc_id: &str,
+  history_min_interval_ms: i64,
+) -> RuntimeResult<bool> {
+  if is_empty_doc(&snapshot.blob) {
+    return Ok(false);
+  }
+
+  let last_timestamp = sqlx::query_scalar::<_, i64>(
+    r#"
+    SELECT timestamp
+    FROM snapshot_histories
+    WHERE workspace_id = ?1 AND guid = ?2
+    ORDER BY timestamp DESC
+    LIMIT 1
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(doc_id)
+  .fetch_optional(&mut **tx)
+  .await
+  .map_err(|err| RuntimeError::database("DocCompactor load latest history failed", err))?;
+
+  Ok(match last_timestamp {
+    None => true,
+    Some(last_timestamp) => {
+      last_timestamp != snapshot.updated_at
+        && last_timestamp < snapshot.updated_at.saturating_sub(history_min_interval_ms)
+    }
+  })
+}
+
 #[napi_derive::napi]
 impl BackendRuntime {
   /// Merge pending doc updates with y-octo and persist the merged snapshot.
@@ -368,8 +612,14 @@ impl BackendRuntime {
         .ok_or_else(|| RuntimeError::invalid_input("DocCompactor history max age is out of range"))?;
     }
 
+    let started = Instant::now();
+    let master_keys = self.doc_master_keys()?;
     let lease_key = format!("doc:update:{workspace_id}:{doc_id}");
-    let Some(lease) = self.acquire_coordination_lease(lease_key, owner, lease_ttl_ms).await? else {
+    let Some(lease) = self
+      .acquire_coordination_lease_guard(lease_key, owner, lease_ttl_ms)
+      .await?
+    else {
+      metrics::DOC_COMPACTIONS.inc(&[("outcome", "lease_busy")]);
       return Ok(RuntimeDocCompactionResult {
         lease_acquired: false,
         merged: false,
@@ -380,24 +630,40 @@ impl BackendRuntime {
       });
     };
 
-    let result = DocCompactorStore::new(self.pool().await?)
-      .compact_doc(
+    let store = DocCompactorStore::new(self.database().await?, master_keys);
+    let result = lease
+      .until_lost(store.compact_doc(
+        &lease,
         &workspace_id,
         &doc_id,
         batch_limit,
         history_min_interval_ms,
         history_max_age_seconds,
-      )
-      .await;
-
-    let released = self
-      .release_coordination_lease(lease.key, lease.owner, lease.fencing_token)
-      .await?;
+      ))
+      .await
+      .unwrap_or_else(|| Err(RuntimeError::invalid_state("DocCompactor lost coordination lease")));
+
+    let released = lease.release().await?;
+    metrics::observe_operation(
+      metrics::BACKEND,
+      "compact_pending_doc_updates",
+      started,
+      result.is_ok() && released,
+    );
     if !released {
+      metrics::DOC_COMPACTIONS.inc(&[("outcome", "error")]);
       return Err(RuntimeError::invalid_state("DocCompactor failed to release coordination lease").into());
     }
 
-    let (updates_merged, history_created) = result?;
+    let (updates_merged, history_created) = result.inspect_err(|_| {
+      metrics::DOC_COMPACTIONS.inc(&[("outcome", "error")]);
+    })?;
+    if updates_merged > 0 {
+      metrics::DOC_COMPACTIONS.inc(&[("outcome", "merged")]);
+      metrics::DOC_COMPACTION_MERGED_UPDATES.observe(&[], updates_merged as f64);
+    } else {
+      metrics::DOC_COMPACTIONS.inc(&[("outcome", "noop")]);
+    }
     Ok(RuntimeDocCompactionResult {
       lease_acquired: true,
       merged: updates_merged > 0,
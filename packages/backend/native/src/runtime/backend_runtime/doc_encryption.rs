@@ -0,0 +1,878 @@
+use std::collections::HashMap;
+
+use aes_gcm::{
+  AesGcm, KeyInit,
+  aead::{
+    Aead, Payload,
+    generic_array::{GenericArray, typenum::U12},
+  },
+  aes::Aes256,
+};
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgConnection, PgPool, Postgres, Transaction};
+
+use super::{
+  BackendRuntime, DocEncryptionConfig, RuntimeError, RuntimeResult,
+  coordination_lease::CoordinationLeaseGuard,
+  napi_error,
+  types::{RuntimeDocDataKey, RuntimeDocReencryptionResult},
+};
+
+type Aes256Gcm12 = AesGcm<Aes256, U12, U12>;
+
+/// Sealed blobs start with a byte sequence that a Yjs v1 update cannot begin
+/// with in practice: zero structs followed by an 8447-client delete set.
+const ENVELOPE_MAGIC: &[u8] = b"\x00\xffAFENC";
+const ENVELOPE_FORMAT: u8 = 1;
+const ENVELOPE_HEADER_BYTES: usize = ENVELOPE_MAGIC.len() + 1 + 4;
+const NONCE_BYTES: usize = 12;
+const TAG_BYTES: usize = 16;
+const DATA_KEY_BYTES: usize = 32;
+
+struct MasterKey {
+  id: String,
+  cipher: Aes256Gcm12,
+}
+
+impl MasterKey {
+  fn from_secret(secret: &str) -> RuntimeResult<Self> {
+    let key = Sha256::new()
+      .chain_update(b"affine:doc-encryption:master:")
+      .chain_update(secret.as_bytes())
+      .finalize();
+    let id = hex::encode(&Sha256::digest(key)[..8]);
+    let cipher = Aes256Gcm12::new_from_slice(&key)
+      .map_err(|_| RuntimeError::invalid_state("DocEncryption master key is invalid"))?;
+    Ok(Self { id, cipher })
+  }
+}
+
+/// Master keys from `crypto.docEncryption`. The current key wraps new data
+/// keys; previous keys are only used to unwrap data keys created before a
+/// master key rotation.
+pub(crate) struct DocMasterKeys {
+  current: Option<MasterKey>,
+  previous: Vec<MasterKey>,
+}
+
+impl DocMasterKeys {
+  pub(crate) fn from_config(config: &DocEncryptionConfig) -> RuntimeResult<Self> {
+    let current = config
+      .master_key
+      .as_deref()
+      .filter(|secret| !secret.trim().is_empty())
+      .map(MasterKey::from_secret)
+      .transpose()?;
+    let previous = config
+      .previous_master_keys
+      .iter()
+      .filter(|secret| !secret.trim().is_empty())
+      .map(|secret| MasterKey::from_secret(secret))
+      .collect::<RuntimeResult<Vec<_>>>()?;
+    Ok(Self { current, previous })
+  }
+
+  pub(crate) fn enabled(&self) -> bool {
+    self.current.is_some()
+  }
+
+  fn has_keys(&self) -> bool {
+    self.current.is_some() || !self.previous.is_empty()
+  }
+
+  fn current(&self) -> RuntimeResult<&MasterKey> {
+    self
+      .current
+      .as_ref()
+      .ok_or_else(|| RuntimeError::config("crypto.docEncryption.masterKey is required for doc encryption"))
+  }
+
+  fn find(&self, id: &str) -> Option<&MasterKey> {
+    self.current.iter().chain(&self.previous).find(|key| key.id == id)
+  }
+
+  fn wrap(&self, workspace_id: &str, version: i32, data_key: &[u8]) -> RuntimeResult<(String, Vec<u8>)> {
+    let master_key = self.current()?;
+    let mut nonce = [0u8; NONCE_BYTES];
+    rand::rng().fill_bytes(&mut nonce);
+    let aad = wrap_aad(workspace_id, version);
+    let encrypted = master_key
+      .cipher
+      .encrypt(
+        GenericArray::from_slice(&nonce),
+        Payload {
+          msg: data_key,
+          aad: &aad,
+        },
+      )
+      .map_err(|_| RuntimeError::invalid_state("DocEncryption data key wrapping failed"))?;
+
+    let mut wrapped = Vec::with_capacity(NONCE_BYTES + encrypted.len());
+    wrapped.extend_from_slice(&nonce);
+    wrapped.extend_from_slice(&encrypted);
+    Ok((master_key.id.clone(), wrapped))
+  }
+
+  fn unwrap(&self, workspace_id: &str, version: i32, master_key_id: &str, wrapped: &[u8]) -> Option<Vec<u8>> {
+    let master_key = self.find(master_key_id)?;
+    if wrapped.len() <= NONCE_BYTES {
+      return None;
+    }
+    let (nonce, encrypted) = wrapped.split_at(NONCE_BYTES);
+    let aad = wrap_aad(workspace_id, version);
+    master_key
+      .cipher
+      .decrypt(
+        GenericArray::from_slice(nonce),
+        Payload {
+          msg: encrypted,
+          aad: &aad,
+        },
+      )
+      .ok()
+      .filter(|data_key| data_key.len() == DATA_KEY_BYTES)
+  }
+}
+
+fn wrap_aad(workspace_id: &str, version: i32) -> Vec<u8> {
+  format!("affine:doc-data-key:{workspace_id}:{version}").into_bytes()
+}
+
+fn envelope_header(version: i32) -> [u8; ENVELOPE_HEADER_BYTES] {
+  let mut header = [0u8; ENVELOPE_HEADER_BYTES];
+  header[..ENVELOPE_MAGIC.len()].copy_from_slice(ENVELOPE_MAGIC);
+  header[ENVELOPE_MAGIC.len()] = ENVELOPE_FORMAT;
+  header[ENVELOPE_MAGIC.len() + 1..].copy_from_slice(&(version as u32).to_be_bytes());
+  header
+}
+
+/// Returns the data key version a blob was sealed with, or `None` for a
+/// plaintext Yjs binary.
+fn sealed_key_version(blob: &[u8]) -> Option<i32> {
+  if blob.len() < ENVELOPE_HEADER_BYTES + NONCE_BYTES + TAG_BYTES
+    || !blob.starts_with(ENVELOPE_MAGIC)
+    || blob[ENVELOPE_MAGIC.len()] != ENVELOPE_FORMAT
+  {
+    return None;
+  }
+  let version = blob[ENVELOPE_MAGIC.len() + 1..ENVELOPE_HEADER_BYTES]
+    .try_into()
+    .map(u32::from_be_bytes)
+    .ok()?;
+  i32::try_from(version).ok()
+}
+
+fn doc_aad(header: &[u8], workspace_id: &str, doc_id: &str) -> Vec<u8> {
+  let mut aad = Vec::with_capacity(header.len() + workspace_id.len() + doc_id.len() + 1);
+  aad.extend_from_slice(header);
+  aad.extend_from_slice(workspace_id.as_bytes());
+  aad.push(0);
+  aad.extend_from_slice(doc_id.as_bytes());
+  aad
+}
+
+#[derive(FromRow)]
+struct DocDataKeyRow {
+  workspace_id: String,
+  version: i32,
+  master_key_id: String,
+  wrapped_key: Vec<u8>,
+  active: bool,
+  created_at_ms: i64,
+  retired_at_ms: Option<i64>,
+}
+
+impl From<DocDataKeyRow> for RuntimeDocDataKey {
+  fn from(row: DocDataKeyRow) -> Self {
+    Self {
+      workspace_id: row.workspace_id,
+      version: row.version,
+      master_key_id: row.master_key_id,
+      active: row.active,
+      created_at_ms: row.created_at_ms,
+      retired_at_ms: row.retired_at_ms,
+    }
+  }
+}
+
+const SELECT_DOC_DATA_KEYS_SQL: &str = r#"
+  SELECT
+    workspace_id,
+    version,
+    master_key_id,
+    wrapped_key,
+    active,
+    (EXTRACT(EPOCH FROM created_at) * 1000)::BIGINT AS created_at_ms,
+    (EXTRACT(EPOCH FROM retired_at) * 1000)::BIGINT AS retired_at_ms
+  FROM doc_data_keys
+  WHERE workspace_id = $1
+  ORDER BY version ASC
+"#;
+
+/// The unwrapped data keys of one workspace by version. Doc blobs are sealed
+/// with them directly; storage blobs derive their own keys from them.
+pub(crate) struct WorkspaceDataKeys {
+  pub(crate) active: Option<i32>,
+  pub(crate) keys: HashMap<i32, Vec<u8>>,
+}
+
+impl WorkspaceDataKeys {
+  /// Resolve keys for reading. Never creates a data key.
+  pub(crate) async fn for_read(
+    conn: &mut PgConnection,
+    master_keys: &DocMasterKeys,
+    workspace_id: &str,
+  ) -> RuntimeResult<Self> {
+    if !master_keys.has_keys() {
+      return Ok(Self {
+        active: None,
+        keys: HashMap::new(),
+      });
+    }
+    Self::load(conn, master_keys, workspace_id).await
+  }
+
+  /// Resolve keys for writing, creating the first data key of the workspace
+  /// when encryption is enabled.
+  pub(crate) async fn for_write(
+    conn: &mut PgConnection,
+    master_keys: &DocMasterKeys,
+    workspace_id: &str,
+  ) -> RuntimeResult<Self> {
+    if !master_keys.enabled() {
+      return Self::for_read(conn, master_keys, workspace_id)
+        .await
+        .map(|keys| Self { active: None, ..keys });
+    }
+
+    ensure_active_key(conn, master_keys, workspace_id).await?;
+    let keys = Self::load(conn, master_keys, workspace_id).await?;
+    if keys.active.is_none() {
+      return Err(RuntimeError::invalid_state(format!(
+        "DocEncryption active data key for workspace {workspace_id} is unavailable"
+      )));
+    }
+    Ok(keys)
+  }
+
+  async fn load(conn: &mut PgConnection, master_keys: &DocMasterKeys, workspace_id: &str) -> RuntimeResult<Self> {
+    let rows = sqlx::query_as::<_, DocDataKeyRow>(SELECT_DOC_DATA_KEYS_SQL)
+      .bind(workspace_id)
+      .fetch_all(&mut *conn)
+      .await
+      .map_err(|err| RuntimeError::database("DocEncryption load data keys failed", err))?;
+
+    let mut keys = Self {
+      active: None,
+      keys: HashMap::new(),
+    };
+    for row in rows {
+      // Keys wrapped by a master key that is no longer configured stay
+      // unavailable; only blobs sealed with them fail to open.
+      let Some(data_key) = master_keys.unwrap(workspace_id, row.version, &row.master_key_id, &row.wrapped_key) else {
+        continue;
+      };
+      if row.active {
+        keys.active = Some(row.version);
+      }
+      keys.keys.insert(row.version, data_key);
+    }
+    Ok(keys)
+  }
+}
+
+/// The data keys of one workspace, unwrapped for sealing and opening doc
+/// blobs. When doc encryption is disabled it passes plaintext through.
+pub(crate) struct WorkspaceDocCipher {
+  workspace_id: String,
+  active: Option<i32>,
+  keys: HashMap<i32, Aes256Gcm12>,
+}
+
+impl WorkspaceDocCipher {
+  /// Resolve keys for reading. Never creates a data key.
+  pub(crate) async fn for_read(
+    conn: &mut PgConnection,
+    master_keys: &DocMasterKeys,
+    workspace_id: &str,
+  ) -> RuntimeResult<Self> {
+    let keys = WorkspaceDataKeys::for_read(conn, master_keys, workspace_id).await?;
+    Self::from_data_keys(workspace_id, keys)
+  }
+
+  /// Resolve keys for writing, creating the first data key of the workspace
+  /// when a master key is configured. Otherwise writes stay plaintext.
+  pub(crate) async fn for_write(
+    conn: &mut PgConnection,
+    master_keys: &DocMasterKeys,
+    workspace_id: &str,
+  ) -> RuntimeResult<Self> {
+    if !master_keys.enabled() {
+      return Self::for_read(conn, master_keys, workspace_id)
+        .await
+        .map(|cipher| Self { active: None, ..cipher });
+    }
+    let keys = WorkspaceDataKeys::for_write(conn, master_keys, workspace_id).await?;
+    Self::from_data_keys(workspace_id, keys)
+  }
+
+  fn plaintext(workspace_id: &str) -> Self {
+    Self {
+      workspace_id: workspace_id.to_string(),
+      active: None,
+      keys: HashMap::new(),
+    }
+  }
+
+  fn from_data_keys(workspace_id: &str, data_keys: WorkspaceDataKeys) -> RuntimeResult<Self> {
+    let mut cipher = Self::plaintext(workspace_id);
+    cipher.active = data_keys.active;
+    for (version, data_key) in data_keys.keys {
+      let key = Aes256Gcm12::new_from_slice(&data_key)
+        .map_err(|_| RuntimeError::invalid_state("DocEncryption data key is invalid"))?;
+      cipher.keys.insert(version, key);
+    }
+    Ok(cipher)
+  }
+
+  /// Envelope header of blobs sealed with the active key, used to find blobs
+  /// that still need re-encryption.
+  fn active_header(&self) -> Option<[u8; ENVELOPE_HEADER_BYTES]> {
+    self.active.map(envelope_header)
+  }
+
+  pub(crate) fn encrypt(&self, doc_id: &str, plaintext: &[u8]) -> RuntimeResult<Vec<u8>> {
+    let Some(version) = self.active else {
+      return Ok(plaintext.to_vec());
+    };
+    let key = self
+      .keys
+      .get(&version)
+      .ok_or_else(|| RuntimeError::invalid_state("DocEncryption active data key is missing"))?;
+    let header = envelope_header(version);
+    let mut nonce = [0u8; NONCE_BYTES];
+    rand::rng().fill_bytes(&mut nonce);
+    let aad = doc_aad(&header, &self.workspace_id, doc_id);
+    let encrypted = key
+      .encrypt(
+        GenericArray::from_slice(&nonce),
+        Payload {
+          msg: plaintext,
+          aad: &aad,
+        },
+      )
+      .map_err(|_| RuntimeError::invalid_state("DocEncryption doc blob encryption failed"))?;
+
+    let mut sealed = Vec::with_capacity(ENVELOPE_HEADER_BYTES + NONCE_BYTES + encrypted.len());
+    sealed.extend_from_slice(&header);
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&encrypted);
+    Ok(sealed)
+  }
+
+  pub(crate) fn decrypt(&self, doc_id: &str, blob: Vec<u8>) -> RuntimeResult<Vec<u8>> {
+    let Some(version) = sealed_key_version(&blob) else {
+      return Ok(blob);
+    };
+    let key = self.keys.get(&version).ok_or_else(|| {
+      RuntimeError::config(format!(
+        "DocEncryption data key v{version} of workspace {} is unavailable; check crypto.docEncryption",
+        self.workspace_id
+      ))
+    })?;
+    let (header, rest) = blob.split_at(ENVELOPE_HEADER_BYTES);
+    let (nonce, encrypted) = rest.split_at(NONCE_BYTES);
+    let aad = doc_aad(header, &self.workspace_id, doc_id);
+    key
+      .decrypt(
+        GenericArray::from_slice(nonce),
+        Payload {
+          msg: encrypted,
+          aad: &aad,
+        },
+      )
+      .map_err(|_| {
+        RuntimeError::invalid_state(format!(
+          "DocEncryption failed to decrypt doc {doc_id} of workspace {}",
+          self.workspace_id
+        ))
+      })
+  }
+}
+
+async fn ensure_active_key(
+  conn: &mut PgConnection,
+  master_keys: &DocMasterKeys,
+  workspace_id: &str,
+) -> RuntimeResult<()> {
+  let exists = sqlx::query("SELECT 1 FROM doc_data_keys WHERE workspace_id = $1 AND active")
+    .bind(workspace_id)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|err| RuntimeError::database("DocEncryption load active data key failed", err))?
+    .is_some();
+  if exists {
+    return Ok(());
+  }
+
+  let mut data_key = [0u8; DATA_KEY_BYTES];
+  rand::rng().fill_bytes(&mut data_key);
+  let (master_key_id, wrapped_key) = master_keys.wrap(workspace_id, 1, &data_key)?;
+  // Concurrent writers race on the primary key; the loser reloads the
+  // winner's key.
+  sqlx::query(
+    r#"
+    INSERT INTO doc_data_keys (workspace_id, version, master_key_id, wrapped_key, active)
+    VALUES ($1, 1, $2, $3, TRUE)
+    ON CONFLICT DO NOTHING
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(master_key_id)
+  .bind(wrapped_key)
+  .execute(&mut *conn)
+  .await
+  .map_err(|err| RuntimeError::database("DocEncryption create data key failed", err))?;
+  Ok(())
+}
+
+/// Tables holding doc blobs. `row_key` is the column that, with workspace_id
+/// and guid, identifies a row; snapshots have one row per doc.
+struct DocBlobTable {
+  table: &'static str,
+  row_key: Option<&'static str>,
+}
+
+const DOC_BLOB_TABLES: [DocBlobTable; 3] = [
+  DocBlobTable {
+    table: "snapshots",
+    row_key: None,
+  },
+  DocBlobTable {
+    table: "updates",
+    row_key: Some("created_at"),
+  },
+  DocBlobTable {
+    table: "snapshot_histories",
+    row_key: Some("timestamp"),
+  },
+];
+
+#[derive(FromRow)]
+struct DocBlobRow {
+  guid: String,
+  row_key: Option<DateTime<Utc>>,
+  blob: Vec<u8>,
+}
+
+struct DocEncryptionStore {
+  pool: PgPool,
+}
+
+impl DocEncryptionStore {
+  fn new(pool: PgPool) -> Self {
+    Self { pool }
+  }
+
+  async fn begin(&self, context: &str) -> RuntimeResult<Transaction<'static, Postgres>> {
+    self
+      .pool
+      .begin()
+      .await
+      .map_err(|err| RuntimeError::database(format!("{context} begin transaction failed"), err))
+  }
+
+  async fn list_keys(&self, workspace_id: &str) -> RuntimeResult<Vec<RuntimeDocDataKey>> {
+    let rows = sqlx::query_as::<_, DocDataKeyRow>(SELECT_DOC_DATA_KEYS_SQL)
+      .bind(workspace_id)
+      .fetch_all(&self.pool)
+      .await
+      .map_err(|err| RuntimeError::database("DocEncryption list data keys failed", err))?;
+    Ok(rows.into_iter().map(Into::into).collect())
+  }
+
+  async fn rotate(&self, master_keys: &DocMasterKeys, workspace_id: &str) -> RuntimeResult<RuntimeDocDataKey> {
+    let mut tx = self.begin("DocEncryption rotate").await?;
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended($1, 0))")
+      .bind(format!("doc_data_keys:{workspace_id}"))
+      .execute(&mut *tx)
+      .await
+      .map_err(|err| RuntimeError::database("DocEncryption rotate lock failed", err))?;
+
+    let version: i32 =
+      sqlx::query_scalar("SELECT COALESCE(MAX(version), 0) + 1 FROM doc_data_keys WHERE workspace_id = $1")
+        .bind(workspace_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|err| RuntimeError::database("DocEncryption load latest data key failed", err))?;
+
+    let mut data_key = [0u8; DATA_KEY_BYTES];
+    rand::rng().fill_bytes(&mut data_key);
+    let (master_key_id, wrapped_key) = master_keys.wrap(workspace_id, version, &data_key)?;
+
+    sqlx::query(
+      r#"
+      UPDATE doc_data_keys
+      SET active = FALSE, retired_at = CURRENT_TIMESTAMP
+      WHERE workspace_id = $1 AND active
+      "#,
+    )
+    .bind(workspace_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|err| RuntimeError::database("DocEncryption retire data key failed", err))?;
+
+    let row = sqlx::query_as::<_, DocDataKeyRow>(
+      r#"
+      INSERT INTO doc_data_keys (workspace_id, version, master_key_id, wrapped_key, active)
+      VALUES ($1, $2, $3, $4, TRUE)
+      RETURNING
+        workspace_id,
+        version,
+        master_key_id,
+        wrapped_key,
+        active,
+        (EXTRACT(EPOCH FROM created_at) * 1000)::BIGINT AS created_at_ms,
+        (EXTRACT(EPOCH FROM retired_at) * 1000)::BIGINT AS retired_at_ms
+      "#,
+    )
+    .bind(workspace_id)
+    .bind(version)
+    .bind(master_key_id)
+    .bind(wrapped_key)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|err| RuntimeError::database("DocEncryption create data key failed", err))?;
+
+    tx.commit()
+      .await
+      .map_err(|err| RuntimeError::database("DocEncryption rotate commit failed", err))?;
+    Ok(row.into())
+  }
+
+  async fn rewrap(&self, master_keys: &DocMasterKeys, batch_limit: i64) -> RuntimeResult<i64> {
+    let current = master_keys.current()?;
+    let previous_ids = master_keys
+      .previous
+      .iter()
+      .map(|key| key.id.clone())
+      .collect::<Vec<_>>();
+    let mut tx = self.begin("DocEncryption rewrap").await?;
+    // Keys wrapped by unknown master keys are left alone: they cannot be
+    // unwrapped here, and skipping them keeps the batch making progress.
+    let rows = sqlx::query_as::<_, DocDataKeyRow>(
+      r#"
+      SELECT
+        workspace_id,
+        version,
+        master_key_id,
+        wrapped_key,
+        active,
+        (EXTRACT(EPOCH FROM created_at) * 1000)::BIGINT AS created_at_ms,
+        (EXTRACT(EPOCH FROM retired_at) * 1000)::BIGINT AS retired_at_ms
+      FROM doc_data_keys
+      WHERE master_key_id = ANY($1) AND master_key_id <> $2
+      ORDER BY workspace_id, version
+      LIMIT $3
+      FOR UPDATE SKIP LOCKED
+      "#,
+    )
+    .bind(&previous_ids)
+    .bind(&current.id)
+    .bind(batch_limit)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|err| RuntimeError::database("DocEncryption load wrapped data keys failed", err))?;
+
+    let mut rewrapped = 0;
+    for row in rows {
+      let Some(data_key) = master_keys.unwrap(&row.workspace_id, row.version, &row.master_key_id, &row.wrapped_key)
+      else {
+        return Err(RuntimeError::invalid_state(format!(
+          "DocEncryption failed to unwrap data key v{} of workspace {}",
+          row.version, row.workspace_id
+        )));
+      };
+      let (master_key_id, wrapped_key) = master_keys.wrap(&row.workspace_id, row.version, &data_key)?;
+      sqlx::query(
+        r#"
+        UPDATE doc_data_keys
+        SET master_key_id = $3, wrapped_key = $4
+        WHERE workspace_id = $1 AND version = $2
+        "#,
+      )
+      .bind(&row.workspace_id)
+      .bind(row.version)
+      .bind(master_key_id)
+      .bind(wrapped_key)
+      .execute(&mut *tx)
+      .await
+      .map_err(|err| RuntimeError::database("DocEncryption rewrap data key failed", err))?;
+      rewrapped += 1;
+    }
+
+    tx.commit()
+      .await
+      .map_err(|err| RuntimeError::database("DocEncryption rewrap commit failed", err))?;
+    Ok(rewrapped)
+  }
+
+  /// Re-seal up to `batch_limit` blobs per table that are plaintext or sealed
+  /// with a retired data key. Rows are locked like the doc compactor does, so
+  /// a concurrent compaction either finishes first or waits for this batch.
+  async fn reencrypt(
+    &self,
+    lease: &CoordinationLeaseGuard,
+    master_keys: &DocMasterKeys,
+    workspace_id: &str,
+    batch_limit: i64,
+  ) -> RuntimeResult<[i64; 3]> {
+    let mut tx = self.begin("DocEncryption reencrypt").await?;
+    let cipher = WorkspaceDocCipher::for_write(&mut tx, master_keys, workspace_id).await?;
+    let header = cipher
+      .active_header()
+      .ok_or_else(|| RuntimeError::invalid_state("DocEncryption active data key is missing"))?;
+
+    let mut counts = [0; 3];
+    for (table, count) in DOC_BLOB_TABLES.iter().zip(counts.iter_mut()) {
+      let row_key = table.row_key.unwrap_or("NULL::TIMESTAMPTZ");
+      let rows = sqlx::query_as::<_, DocBlobRow>(&format!(
+        r#"
+        SELECT guid, {row_key} AS row_key, blob
+        FROM {table}
+        WHERE workspace_id = $1
+          AND substring(blob FROM 1 FOR {ENVELOPE_HEADER_BYTES}) <> $2
+        ORDER BY guid, row_key
+        LIMIT $3
+        FOR UPDATE
+        "#,
+        table = table.table,
+      ))
+      .bind(workspace_id)
+      .bind(header.as_slice())
+      .bind(batch_limit)
+      .fetch_all(&mut *tx)
+      .await
+      .map_err(|err| RuntimeError::database(format!("DocEncryption load {} failed", table.table), err))?;
+
+      for row in rows {
+        let plaintext = cipher.decrypt(&row.guid, row.blob)?;
+        let sealed = cipher.encrypt(&row.guid, &plaintext)?;
+        sqlx::query(&format!(
+          r#"
+          UPDATE {table}
+          SET blob = $4
+          WHERE workspace_id = $1 AND guid = $2 AND {row_key} IS NOT DISTINCT FROM $3
+          "#,
+          table = table.table,
+        ))
+        .bind(workspace_id)
+        .bind(&row.guid)
+        .bind(row.row_key)
+        .bind(sealed)
+        .execute(&mut *tx)
+        .await
+        .map_err(|err| RuntimeError::database(format!("DocEncryption reseal {} failed", table.table), err))?;
+        *count += 1;
+      }
+    }
+
+    lease.assert_held(&mut tx).await?;
+    tx.commit()
+      .await
+      .map_err(|err| RuntimeError::database("DocEncryption reencrypt commit failed", err))?;
+    Ok(counts)
+  }
+}
+
+impl BackendRuntime {
+  pub(super) fn doc_master_keys(&self) -> RuntimeResult<DocMasterKeys> {
+    DocMasterKeys::from_config(&self.config()?.doc_encryption)
+  }
+}
+
+#[napi_derive::napi]
+impl BackendRuntime {
+  #[napi]
+  pub async fn list_workspace_doc_keys(&self, workspace_id: String) -> napi::Result<Vec<RuntimeDocDataKey>> {
+    DocEncryptionStore::new(self.pool().await?)
+      .list_keys(&workspace_id)
+      .await
+      .map_err(napi::Error::from)
+  }
+
+  /// Retire the active doc data key of a workspace and create a new one.
+  /// Existing blobs stay readable with the retired key until
+  /// `reencrypt_workspace_docs` re-seals them.
+  #[napi]
+  pub async fn rotate_workspace_doc_key(&self, workspace_id: String) -> napi::Result<RuntimeDocDataKey> {
+    let master_keys = self.doc_master_keys()?;
+    DocEncryptionStore::new(self.pool().await?)
+      .rotate(&master_keys, &workspace_id)
+      .await
+      .map_err(napi::Error::from)
+  }
+
+  /// Re-wrap data keys still wrapped by a previous master key with the
+  /// current one. Returns how many keys were re-wrapped in this batch.
+  #[napi]
+  pub async fn rewrap_doc_data_keys(&self, batch_limit: i64) -> napi::Result<i64> {
+    if batch_limit <= 0 {
+      return Err(napi_error("doc data key rewrap limit must be positive"));
+    }
+
+    let master_keys = self.doc_master_keys()?;
+    DocEncryptionStore::new(self.pool().await?)
+      .rewrap(&master_keys, batch_limit)
+      .await
+      .map_err(napi::Error::from)
+  }
+
+  /// Encrypt plaintext doc blobs of a workspace and re-seal blobs sealed with
+  /// retired data keys. Call repeatedly until `completed` is true. Requires
+  /// `crypto.docEncryption.masterKey`.
+  #[napi]
+  pub async fn reencrypt_workspace_docs(
+    &self,
+    workspace_id: String,
+    batch_limit: i64,
+    owner: String,
+    lease_ttl_ms: i64,
+  ) -> napi::Result<RuntimeDocReencryptionResult> {
+    if batch_limit <= 0 {
+      return Err(napi_error("doc reencryption batch limit must be positive"));
+    }
+    let master_keys = self.doc_master_keys()?;
+    master_keys.current()?;
+
+    let lease_key = format!("doc:reencrypt:{workspace_id}");
+    let Some(lease) = self
+      .acquire_coordination_lease_guard(lease_key, owner, lease_ttl_ms)
+      .await?
+    else {
+      return Ok(RuntimeDocReencryptionResult {
+        lease_acquired: false,
+        workspace_id,
+        snapshots: 0,
+        updates: 0,
+        histories: 0,
+        completed: false,
+      });
+    };
+
+    let store = DocEncryptionStore::new(self.pool().await?);
+    let result = lease
+      .until_lost(store.reencrypt(&lease, &master_keys, &workspace_id, batch_limit))
+      .await
+      .unwrap_or_else(|| Err(RuntimeError::invalid_state("DocEncryption lost coordination lease")));
+
+    let released = lease.release().await?;
+    if !released {
+      return Err(RuntimeError::invalid_state("DocEncryption failed to release coordination lease").into());
+    }
+
+    let [snapshots, updates, histories] = result?;
+    Ok(RuntimeDocReencryptionResult {
+      lease_acquired: true,
+      workspace_id,
+      snapshots,
+      updates,
+      histories,
+      completed: [snapshots, updates, histories].iter().all(|count| *count < batch_limit),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn master_keys(current: Option<&str>, previous: &[&str]) -> DocMasterKeys {
+    DocMasterKeys::from_config(&DocEncryptionConfig {
+      master_key: current.map(str::to_string),
+      previous_master_keys: previous.iter().map(|key| key.to_string()).collect(),
+    })
+    .unwrap()
+  }
+
+  fn cipher(master_keys: &DocMasterKeys, rows: &[(i32, bool, u8)]) -> WorkspaceDocCipher {
+    let mut cipher = WorkspaceDocCipher::plaintext("ws");
+    for (version, active, fill) in rows {
+      let (master_key_id, wrapped) = master_keys.wrap("ws", *version, &[*fill; DATA_KEY_BYTES]).unwrap();
+      let data_key = master_keys.unwrap("ws", *version, &master_key_id, &wrapped).unwrap();
+      if *active {
+        cipher.active = Some(*version);
+      }
+      cipher
+        .keys
+        .insert(*version, Aes256Gcm12::new_from_slice(&data_key).unwrap());
+    }
+    cipher
+  }
+
+  #[test]
+  fn disabled_cipher_passes_plaintext_through() {
+    let cipher = WorkspaceDocCipher::plaintext("ws");
+    let blob = vec![1, 2, 3];
+    assert_eq!(cipher.encrypt("doc", &blob).unwrap(), blob);
+    assert_eq!(cipher.decrypt("doc", blob.clone()).unwrap(), blob);
+  }
+
+  #[test]
+  fn sealed_blobs_round_trip_and_are_bound_to_the_doc() {
+    let keys = master_keys(Some("master"), &[]);
+    let cipher = cipher(&keys, &[(1, true, 7)]);
+    let sealed = cipher.encrypt("doc", b"yjs update").unwrap();
+
+    assert_eq!(sealed_key_version(&sealed), Some(1));
+    assert!(sealed.starts_with(&envelope_header(1)));
+    assert_eq!(cipher.decrypt("doc", sealed.clone()).unwrap(), b"yjs update");
+    assert!(cipher.decrypt("other-doc", sealed).is_err());
+  }
+
+  #[test]
+  fn retired_keys_still_open_old_blobs() {
+    let keys = master_keys(Some("master"), &[]);
+    let old = cipher(&keys, &[(1, true, 1)]);
+    let sealed = old.encrypt("doc", b"before rotation").unwrap();
+
+    let rotated = cipher(&keys, &[(1, false, 1), (2, true, 2)]);
+    assert_eq!(rotated.decrypt("doc", sealed).unwrap(), b"before rotation");
+    assert_eq!(sealed_key_version(&rotated.encrypt("doc", b"after").unwrap()), Some(2));
+  }
+
+  #[test]
+  fn doc_sealing_follows_the_current_master_key() {
+    assert!(master_keys(Some("master"), &[]).enabled());
+    assert!(!master_keys(None, &["old"]).enabled());
+    assert!(!master_keys(Some(" "), &[]).enabled());
+  }
+
+  #[test]
+  fn master_key_rotation_keeps_previous_keys_for_unwrap() {
+    let old = master_keys(Some("old-master"), &[]);
+    let (master_key_id, wrapped) = old.wrap("ws", 1, &[9; DATA_KEY_BYTES]).unwrap();
+
+    let rotated = master_keys(Some("new-master"), &["old-master"]);
+    assert!(rotated.current().unwrap().id != master_key_id);
+    assert_eq!(
+      rotated.unwrap("ws", 1, &master_key_id, &wrapped).unwrap(),
+      vec![9; DATA_KEY_BYTES]
+    );
+    assert!(rotated.unwrap("other-ws", 1, &master_key_id, &wrapped).is_none());
+    assert!(
+      master_keys(Some("new-master"), &[])
+        .unwrap("ws", 1, &master_key_id, &wrapped)
+        .is_none()
+    );
+  }
+
+  #[test]
+  fn plaintext_yjs_binaries_are_not_mistaken_for_envelopes() {
+    assert_eq!(sealed_key_version(&[0, 0]), None);
+    assert_eq!(sealed_key_version(&[1, 1, 0, 0, 1, 0]), None);
+    assert_eq!(sealed_key_version(&envelope_header(1)), None);
+  }
+}
@@ -1,35 +1,99 @@
 use chrono::{DateTime, Duration, Utc};
 use napi::bindgen_prelude::Buffer;
-use sqlx::{PgPool, Row};
+use sqlx::{FromRow, PgPool, Postgres, Row, SqlitePool, pool::PoolConnection};
 
-use super::{BackendRuntime, RuntimeError, RuntimeResult, napi_error, types::RuntimeDocHistoryInput};
+use super::{
+  BackendRuntime, RuntimeDatabase, RuntimeError, RuntimeResult,
+  doc_encryption::WorkspaceDocCipher,
+  metrics, napi_error,
+  types::{RuntimeDocHistoryInput, RuntimeDocRecord},
+};
+
+#[derive(FromRow)]
+struct DocRecordRow {
+  blob: Vec<u8>,
+  timestamp: DateTime<Utc>,
+  editor_id: Option<String>,
+}
+
+impl DocRecordRow {
+  fn open(self, cipher: &WorkspaceDocCipher, doc_id: &str) -> RuntimeResult<RuntimeDocRecord> {
+    Ok(RuntimeDocRecord {
+      blob: cipher.decrypt(doc_id, self.blob)?.into(),
+      timestamp_ms: self.timestamp.timestamp_millis(),
+      editor_id: self.editor_id,
+    })
+  }
+}
+
+/// Doc row in the embedded SQLite tables. Doc encryption is Postgres-only,
+/// so these blobs are never sealed.
+#[derive(FromRow)]
+struct SqliteDocRecordRow {
+  blob: Vec<u8>,
+  timestamp: i64,
+  editor_id: Option<String>,
+}
+
+impl From<SqliteDocRecordRow> for RuntimeDocRecord {
+  fn from(row: SqliteDocRecordRow) -> Self {
+    Self {
+      blob: row.blob.into(),
+      timestamp_ms: row.timestamp,
+      editor_id: row.editor_id,
+    }
+  }
+}
 
 fn is_empty_doc(bin: &[u8]) -> bool {
   bin.is_empty() || (bin.len() == 1 && bin[0] == 0) || (bin.len() == 2 && bin[0] == 0 && bin[1] == 0)
 }
 
 async fn latest_history_timestamp(
-  pool: &PgPool,
+  database: &RuntimeDatabase,
   workspace_id: &str,
   doc_id: &str,
 ) -> RuntimeResult<Option<DateTime<Utc>>> {
-  sqlx::query(
-    r#"
-    SELECT timestamp
-    FROM snapshot_histories
-    WHERE workspace_id = $1 AND guid = $2
-    ORDER BY timestamp DESC
-    LIMIT 1
-    "#,
-  )
-  .bind(workspace_id)
-  .bind(doc_id)
-  .fetch_optional(pool)
-  .await
-  .map(|row| row.map(|row| row.get("timestamp")))
+  match database {
+    RuntimeDatabase::Postgres(pool) => sqlx::query(
+      r#"
+      SELECT timestamp
+      FROM snapshot_histories
+      WHERE workspace_id = $1 AND guid = $2
+      ORDER BY timestamp DESC
+      LIMIT 1
+      "#,
+    )
+    .bind(workspace_id)
+    .bind(doc_id)
+    .fetch_optional(pool)
+    .await
+    .map(|row| row.map(|row| row.get("timestamp"))),
+    RuntimeDatabase::Sqlite(pool) => sqlx::query_scalar::<_, i64>(
+      r#"
+      SELECT timestamp
+      FROM snapshot_histories
+      WHERE workspace_id = ?1 AND guid = ?2
+      ORDER BY timestamp DESC
+      LIMIT 1
+      "#,
+    )
+    .bind(workspace_id)
+    .bind(doc_id)
+    .fetch_optional(pool)
+    .await
+    .map(|timestamp| timestamp.and_then(DateTime::<Utc>::from_timestamp_millis)),
+  }
   .map_err(|err| RuntimeError::database("DocStorage load latest history failed", err))
 }
 
+async fn acquire(pool: &PgPool) -> RuntimeResult<PoolConnection<Postgres>> {
+  pool
+    .acquire()
+    .await
+    .map_err(|err| RuntimeError::database("DocStorage acquire connection failed", err))
+}
+
 #[napi_derive::napi]
 impl BackendRuntime {
   #[napi]
@@ -47,9 +111,34 @@ impl BackendRuntime {
 
     let timestamp = DateTime::<Utc>::from_timestamp_millis(timestamp_ms)
       .ok_or_else(|| RuntimeError::invalid_input(format!("Invalid doc snapshot timestamp: {timestamp_ms}")))?;
-    let pool = self.pool().await?;
-    let row = sqlx::query(
-      r#"
+    let pool = match self.database().await? {
+      RuntimeDatabase::Postgres(pool) => pool,
+      RuntimeDatabase::Sqlite(pool) => {
+        return metrics::timed(
+          metrics::BACKEND,
+          "upsert_doc_snapshot",
+          upsert_snapshot_sqlite(
+            &pool,
+            &workspace_id,
+            &doc_id,
+            blob.as_ref(),
+            timestamp_ms,
+            editor_id.as_deref(),
+          ),
+        )
+        .await
+        .map_err(napi::Error::from);
+      }
+    };
+    let master_keys = self.doc_master_keys()?;
+    let mut conn = acquire(&pool).await?;
+    let cipher = WorkspaceDocCipher::for_write(&mut conn, &master_keys, &workspace_id).await?;
+    let sealed = cipher.encrypt(&doc_id, blob.as_ref())?;
+    let row = metrics::timed(
+      metrics::BACKEND,
+      "upsert_doc_snapshot",
+      sqlx::query(
+        r#"
       INSERT INTO snapshots
         (workspace_id, guid, blob, size, created_at, updated_at, created_by, updated_by)
       VALUES
@@ -65,14 +154,15 @@ impl BackendRuntime {
         AND snapshots.updated_at <= $5
       RETURNING updated_at
       "#,
+      )
+      .bind(&workspace_id)
+      .bind(&doc_id)
+      .bind(sealed)
+      .bind(blob.len() as i64)
+      .bind(timestamp)
+      .bind(editor_id.as_deref())
+      .fetch_optional(&mut *conn),
     )
-    .bind(&workspace_id)
-    .bind(&doc_id)
-    .bind(blob.as_ref())
-    .bind(blob.len() as i64)
-    .bind(timestamp)
-    .bind(editor_id.as_deref())
-    .fetch_optional(&pool)
     .await
     .map_err(|err| RuntimeError::database("DocStorage upsert snapshot failed", err))?;
 
@@ -90,8 +180,8 @@ impl BackendRuntime {
 
     let timestamp = DateTime::<Utc>::from_timestamp_millis(input.timestamp_ms)
       .ok_or_else(|| RuntimeError::invalid_input(format!("Invalid doc history timestamp: {}", input.timestamp_ms)))?;
-    let pool = self.pool().await?;
-    let should_create = match latest_history_timestamp(&pool, &input.workspace_id, &input.doc_id).await? {
+    let database = self.database().await?;
+    let should_create = match latest_history_timestamp(&database, &input.workspace_id, &input.doc_id).await? {
       None => true,
       Some(last_timestamp) if last_timestamp == timestamp => false,
       Some(last_timestamp) => {
@@ -104,6 +194,17 @@ impl BackendRuntime {
     }
 
     let expired_at = Utc::now() + Duration::milliseconds(input.history_max_age_ms);
+    let pool = match database {
+      RuntimeDatabase::Postgres(pool) => pool,
+      RuntimeDatabase::Sqlite(pool) => {
+        insert_history_sqlite(&pool, &input, expired_at).await?;
+        return Ok(true);
+      }
+    };
+    let master_keys = self.doc_master_keys()?;
+    let mut conn = acquire(&pool).await?;
+    let cipher = WorkspaceDocCipher::for_write(&mut conn, &master_keys, &input.workspace_id).await?;
+    let sealed = cipher.encrypt(&input.doc_id, input.blob.as_ref())?;
     sqlx::query(
       r#"
       INSERT INTO snapshot_histories
@@ -117,19 +218,256 @@ impl BackendRuntime {
     .bind(&input.workspace_id)
     .bind(&input.doc_id)
     .bind(timestamp)
-    .bind(input.blob.as_ref())
+    .bind(sealed)
     .bind(expired_at)
     .bind(input.editor_id.as_deref())
-    .execute(&pool)
+    .execute(&mut *conn)
     .await
     .map_err(|err| RuntimeError::database("DocStorage create history failed", err))?;
 
     Ok(true)
   }
 
+  /// Append doc updates, sealing them when `crypto.docEncryption.masterKey`
+  /// is set.
+  /// Updates are timestamped one millisecond apart after the current time;
+  /// returns the last timestamp, or 0 when `updates` is empty.
+  #[napi]
+  pub async fn push_doc_updates(
+    &self,
+    workspace_id: String,
+    doc_id: String,
+    updates: Vec<Buffer>,
+    editor_id: Option<String>,
+  ) -> napi::Result<i64> {
+    if updates.is_empty() {
+      return Ok(0);
+    }
+
+    let pool = match self.database().await? {
+      RuntimeDatabase::Postgres(pool) => pool,
+      RuntimeDatabase::Sqlite(pool) => {
+        return metrics::timed(
+          metrics::BACKEND,
+          "push_doc_updates",
+          push_updates_sqlite(&pool, &workspace_id, &doc_id, &updates, editor_id.as_deref()),
+        )
+        .await
+        .map_err(napi::Error::from);
+      }
+    };
+    let master_keys = self.doc_master_keys()?;
+    let mut conn = acquire(&pool).await?;
+    let cipher = WorkspaceDocCipher::for_write(&mut conn, &master_keys, &workspace_id).await?;
+    let now = Utc::now().timestamp_millis();
+    let mut blobs = Vec::with_capacity(updates.len());
+    let mut timestamps = Vec::with_capacity(updates.len());
+    for (index, update) in updates.iter().enumerate() {
+      let timestamp_ms = now + index as i64 + 1;
+      blobs.push(cipher.encrypt(&doc_id, update.as_ref())?);
+      timestamps.push(
+        DateTime::<Utc>::from_timestamp_millis(timestamp_ms)
+          .ok_or_else(|| RuntimeError::invalid_state("DocStorage update timestamp is out of range"))?,
+      );
+    }
+
+    metrics::timed(
+      metrics::BACKEND,
+      "push_doc_updates",
+      sqlx::query(
+        r#"
+      INSERT INTO updates (workspace_id, guid, blob, created_at, created_by)
+      SELECT $1, $2, blob, created_at, $5
+      FROM UNNEST($3::BYTEA[], $4::TIMESTAMPTZ[]) AS pending(blob, created_at)
+      "#,
+      )
+      .bind(&workspace_id)
+      .bind(&doc_id)
+      .bind(&blobs)
+      .bind(&timestamps)
+      .bind(editor_id.as_deref())
+      .execute(&mut *conn),
+    )
+    .await
+    .map_err(|err| RuntimeError::database("DocStorage push updates failed", err))?;
+
+    Ok(now + updates.len() as i64)
+  }
+
+  #[napi]
+  pub async fn get_doc_snapshot(&self, workspace_id: String, doc_id: String) -> napi::Result<Option<RuntimeDocRecord>> {
+    let pool = match self.database().await? {
+      RuntimeDatabase::Postgres(pool) => pool,
+      RuntimeDatabase::Sqlite(pool) => {
+        let row = metrics::timed(
+          metrics::BACKEND,
+          "get_doc_snapshot",
+          sqlx::query_as::<_, SqliteDocRecordRow>(
+            r#"
+          SELECT blob, updated_at AS timestamp, updated_by AS editor_id
+          FROM snapshots
+          WHERE workspace_id = ?1 AND guid = ?2
+          "#,
+          )
+          .bind(&workspace_id)
+          .bind(&doc_id)
+          .fetch_optional(&pool),
+        )
+        .await
+        .map_err(|err| RuntimeError::database("DocStorage load snapshot failed", err))?;
+        return Ok(row.map(RuntimeDocRecord::from));
+      }
+    };
+    let master_keys = self.doc_master_keys()?;
+    let mut conn = acquire(&pool).await?;
+    let row = metrics::timed(
+      metrics::BACKEND,
+      "get_doc_snapshot",
+      sqlx::query_as::<_, DocRecordRow>(
+        r#"
+      SELECT blob, updated_at AS timestamp, updated_by AS editor_id
+      FROM snapshots
+      WHERE workspace_id = $1 AND guid = $2
+      "#,
+      )
+      .bind(&workspace_id)
+      .bind(&doc_id)
+      .fetch_optional(&mut *conn),
+    )
+    .await
+    .map_err(|err| RuntimeError::database("DocStorage load snapshot failed", err))?;
+    let Some(row) = row else {
+      return Ok(None);
+    };
+
+    let cipher = WorkspaceDocCipher::for_read(&mut conn, &master_keys, &workspace_id).await?;
+    Ok(Some(row.open(&cipher, &doc_id)?))
+  }
+
+  #[napi]
+  pub async fn get_doc_updates(&self, workspace_id: String, doc_id: String) -> napi::Result<Vec<RuntimeDocRecord>> {
+    let pool = match self.database().await? {
+      RuntimeDatabase::Postgres(pool) => pool,
+      RuntimeDatabase::Sqlite(pool) => {
+        let rows = metrics::timed(
+          metrics::BACKEND,
+          "get_doc_updates",
+          sqlx::query_as::<_, SqliteDocRecordRow>(
+            r#"
+          SELECT blob, created_at AS timestamp, created_by AS editor_id
+          FROM updates
+          WHERE workspace_id = ?1 AND guid = ?2
+          ORDER BY created_at ASC
+          "#,
+          )
+          .bind(&workspace_id)
+          .bind(&doc_id)
+          .fetch_all(&pool),
+        )
+        .await
+        .map_err(|err| RuntimeError::database("DocStorage load updates failed", err))?;
+        return Ok(rows.into_iter().map(RuntimeDocRecord::from).collect());
+      }
+    };
+    let master_keys = self.doc_master_keys()?;
+    let mut conn = acquire(&pool).await?;
+    let rows = metrics::timed(
+      metrics::BACKEND,
+      "get_doc_updates",
+      sqlx::query_as::<_, DocRecordRow>(
+        r#"
+      SELECT blob, created_at AS timestamp, created_by AS editor_id
+      FROM updates
+      WHERE workspace_id = $1 AND guid = $2
+      ORDER BY created_at ASC
+      "#,
+      )
+      .bind(&workspace_id)
+      .bind(&doc_id)
+      .fetch_all(&mut *conn),
+    )
+    .await
+    .map_err(|err| RuntimeError::database("DocStorage load updates failed", err))?;
+    if rows.is_empty() {
+      return Ok(Vec::new());
+    }
+
+    let cipher = WorkspaceDocCipher::for_read(&mut conn, &master_keys, &workspace_id).await?;
+    rows
+      .into_iter()
+      .map(|row| row.open(&cipher, &doc_id).map_err(napi::Error::from))
+      .collect()
+  }
+
+  #[napi]
+  pub async fn get_doc_history(
+    &self,
+    workspace_id: String,
+    doc_id: String,
+    timestamp_ms: i64,
+  ) -> napi::Result<Option<RuntimeDocRecord>> {
+    let timestamp = DateTime::<Utc>::from_timestamp_millis(timestamp_ms)
+      .ok_or_else(|| RuntimeError::invalid_input(format!("Invalid doc history timestamp: {timestamp_ms}")))?;
+    let pool = match self.database().await? {
+      RuntimeDatabase::Postgres(pool) => pool,
+      RuntimeDatabase::Sqlite(pool) => {
+        let row = metrics::timed(
+          metrics::BACKEND,
+          "get_doc_history",
+          sqlx::query_as::<_, SqliteDocRecordRow>(
+            r#"
+          SELECT blob, timestamp, created_by AS editor_id
+          FROM snapshot_histories
+          WHERE workspace_id = ?1 AND guid = ?2 AND timestamp = ?3
+          "#,
+          )
+          .bind(&workspace_id)
+          .bind(&doc_id)
+          .bind(timestamp_ms)
+          .fetch_optional(&pool),
+        )
+        .await
+        .map_err(|err| RuntimeError::database("DocStorage load history failed", err))?;
+        return Ok(row.map(RuntimeDocRecord::from));
+      }
+    };
+    let master_keys = self.doc_master_keys()?;
+    let mut conn = acquire(&pool).await?;
+    let row = metrics::timed(
+      metrics::BACKEND,
+      "get_doc_history",
+      sqlx::query_as::<_, DocRecordRow>(
+        r#"
+      SELECT blob, timestamp, created_by AS editor_id
+      FROM snapshot_histories
+      WHERE workspace_id = $1 AND guid = $2 AND timestamp = $3
+      "#,
+      )
+      .bind(&workspace_id)
+      .bind(&doc_id)
+      .bind(timestamp)
+      .fetch_optional(&mut *conn),
+    )
+    .await
+    .map_err(|err| RuntimeError::database("DocStorage load history failed", err))?;
+    let Some(row) = row else {
+      return Ok(None);
+    };
+
+    let cipher = WorkspaceDocCipher::for_read(&mut conn, &master_keys, &workspace_id).await?;
+    Ok(Some(row.open(&cipher, &doc_id)?))
+  }
+
   #[napi]
   pub async fn delete_doc_storage(&self, workspace_id: String, doc_id: String) -> napi::Result<()> {
-    let pool = self.pool().await?;
+    let pool = match self.database().await? {
+      RuntimeDatabase::Postgres(pool) => pool,
+      RuntimeDatabase::Sqlite(pool) => {
+        return delete_doc_storage_sqlite(&pool, &workspace_id, &doc_id)
+          .await
+          .map_err(napi::Error::from);
+      }
+    };
     let mut tx = pool
       .begin()
       .await
@@ -160,3 +498,127 @@ impl BackendRuntime {
     Ok(())
   }
 }
+
+async fn upsert_snapshot_sqlite(
+  pool: &SqlitePool,
+  workspace_id: &str,
+  doc_id: &str,
+  blob: &[u8],
+  timestamp_ms: i64,
+  editor_id: Option<&str>,
+) -> RuntimeResult<bool> {
+  let row = sqlx::query(
+    r#"
+    INSERT INTO snapshots
+      (workspace_id, guid, blob, size, created_at, updated_at, created_by, updated_by)
+    VALUES
+      (?1, ?2, ?3, ?4, ?5, ?5, ?6, ?6)
+    ON CONFLICT (workspace_id, guid)
+    DO UPDATE SET
+      blob = excluded.blob,
+      size = excluded.size,
+      updated_at = excluded.updated_at,
+      updated_by = excluded.updated_by
+    WHERE snapshots.updated_at <= excluded.updated_at
+    RETURNING updated_at
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(doc_id)
+  .bind(blob)
+  .bind(blob.len() as i64)
+  .bind(timestamp_ms)
+  .bind(editor_id)
+  .fetch_optional(pool)
+  .await
+  .map_err(|err| RuntimeError::database("DocStorage upsert snapshot failed", err))?;
+
+  Ok(row.is_some())
+}
+
+async fn insert_history_sqlite(
+  pool: &SqlitePool,
+  input: &RuntimeDocHistoryInput,
+  expired_at: DateTime<Utc>,
+) -> RuntimeResult<()> {
+  sqlx::query(
+    r#"
+    INSERT INTO snapshot_histories
+      (workspace_id, guid, timestamp, blob, expired_at, created_by)
+    VALUES
+      (?1, ?2, ?3, ?4, ?5, ?6)
+    ON CONFLICT (workspace_id, guid, timestamp)
+    DO UPDATE SET expired_at = excluded.expired_at
+    "#,
+  )
+  .bind(&input.workspace_id)
+  .bind(&input.doc_id)
+  .bind(input.timestamp_ms)
+  .bind(input.blob.as_ref())
+  .bind(expired_at.timestamp_millis())
+  .bind(input.editor_id.as_deref())
+  .execute(pool)
+  .await
+  .map_err(|err| RuntimeError::database("DocStorage create history failed", err))?;
+
+  Ok(())
+}
+
+async fn push_updates_sqlite(
+  pool: &SqlitePool,
+  workspace_id: &str,
+  doc_id: &str,
+  updates: &[Buffer],
+  editor_id: Option<&str>,
+) -> RuntimeResult<i64> {
+  let now = Utc::now().timestamp_millis();
+  let mut tx = pool
+    .begin()
+    .await
+    .map_err(|err| RuntimeError::database("DocStorage push updates begin transaction failed", err))?;
+  for (index, update) in updates.iter().enumerate() {
+    sqlx::query(
+      r#"
+      INSERT INTO updates (workspace_id, guid, blob, created_at, created_by)
+      VALUES (?1, ?2, ?3, ?4, ?5)
+      "#,
+    )
+    .bind(workspace_id)
+    .bind(doc_id)
+    .bind(update.as_ref())
+    .bind(now + index as i64 + 1)
+    .bind(editor_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|err| RuntimeError::database("DocStorage push updates failed", err))?;
+  }
+  tx.commit()
+    .await
+    .map_err(|err| RuntimeError::database("DocStorage push updates commit failed", err))?;
+
+  Ok(now + updates.len() as i64)
+}
+
+async fn delete_doc_storage_sqlite(pool: &SqlitePool, workspace_id: &str, doc_id: &str) -> RuntimeResult<()> {
+  let mut tx = pool
+    .begin()
+    .await
+    .map_err(|err| RuntimeError::database("DocStorage delete begin transaction failed", err))?;
+  for (table, context) in [
+    ("snapshots", "DocStorage delete snapshot failed"),
+    ("updates", "DocStorage delete updates failed"),
+    ("snapshot_histories", "DocStorage delete histories failed"),
+  ] {
+    sqlx::query(&format!("DELETE FROM {table} WHERE workspace_id = ?1 AND guid = ?2"))
+      .bind(workspace_id)
+      .bind(doc_id)
+      .execute(&mut *tx)
+      .await
+      .map_err(|err| RuntimeError::database(context, err))?;
+  }
+  tx.commit()
+    .await
+    .map_err(|err| RuntimeError::database("DocStorage delete commit failed", err))?;
+
+  Ok(())
+}
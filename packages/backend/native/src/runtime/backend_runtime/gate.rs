@@ -1,20 +1,37 @@
 use napi::Result;
-use sqlx::PgPool;
+use sqlx::{FromRow, SqlitePool};
 
-use super::{BackendRuntime, RuntimeError, RuntimeResult, napi_error};
+use super::{
+  BackendRuntime, RuntimeDatabase, RuntimeError, RuntimeResult, napi_error, types::RuntimeRateLimitResult, unix_ms_now,
+};
+
+#[derive(FromRow)]
+struct TokenBucketRow {
+  allowed: bool,
+  tokens: f64,
+}
+
+#[derive(FromRow)]
+struct WindowHitRow {
+  cost: i64,
+  expires_at_ms: i64,
+}
 
 struct RuntimeGateStore {
-  pool: PgPool,
+  database: RuntimeDatabase,
 }
 
 impl RuntimeGateStore {
-  fn new(pool: PgPool) -> Self {
-    Self { pool }
+  fn new(database: RuntimeDatabase) -> Self {
+    Self { database }
   }
 
   async fn put_if_absent(&self, key: &str, ttl_ms: i64) -> RuntimeResult<bool> {
-    let mut tx = self
-      .pool
+    let pool = match &self.database {
+      RuntimeDatabase::Postgres(pool) => pool,
+      RuntimeDatabase::Sqlite(pool) => return Self::put_if_absent_sqlite(pool, key, ttl_ms).await,
+    };
+    let mut tx = pool
       .begin()
       .await
       .map_err(|err| RuntimeError::database("RuntimeGate transaction failed", err))?;
@@ -47,7 +64,148 @@ impl RuntimeGateStore {
     Ok(inserted)
   }
 
+  async fn take_tokens(
+    &self,
+    key: &str,
+    capacity: f64,
+    refill_per_sec: f64,
+    cost: f64,
+  ) -> RuntimeResult<RuntimeRateLimitResult> {
+    let pool = match &self.database {
+      RuntimeDatabase::Postgres(pool) => pool,
+      RuntimeDatabase::Sqlite(pool) => {
+        let row = Self::take_tokens_sqlite(pool, key, capacity, refill_per_sec, cost).await?;
+        return Ok(token_bucket_result(row, refill_per_sec, cost));
+      }
+    };
+    // The refilled balance is recomputed from the conflicting row inside the
+    // upsert, so concurrent checks against one key serialize on its row lock.
+    let row = sqlx::query_as::<_, TokenBucketRow>(
+      r#"
+      INSERT INTO runtime_gate_buckets (key, capacity, refill_per_sec, tokens, allowed, refilled_at)
+      VALUES ($1, $2, $3, $2 - $4, TRUE, CURRENT_TIMESTAMP)
+      ON CONFLICT (key) DO UPDATE
+        SET capacity = EXCLUDED.capacity,
+            refill_per_sec = EXCLUDED.refill_per_sec,
+            allowed = LEAST(
+              $2,
+              runtime_gate_buckets.tokens
+                + EXTRACT(EPOCH FROM (CURRENT_TIMESTAMP - runtime_gate_buckets.refilled_at))::DOUBLE PRECISION * $3
+            ) >= $4,
+            tokens = LEAST(
+              $2,
+              runtime_gate_buckets.tokens
+                + EXTRACT(EPOCH FROM (CURRENT_TIMESTAMP - runtime_gate_buckets.refilled_at))::DOUBLE PRECISION * $3
+            ) - CASE
+              WHEN LEAST(
+                $2,
+                runtime_gate_buckets.tokens
+                  + EXTRACT(EPOCH FROM (CURRENT_TIMESTAMP - runtime_gate_buckets.refilled_at))::DOUBLE PRECISION * $3
+              ) >= $4 THEN $4
+              ELSE 0
+            END,
+            refilled_at = CURRENT_TIMESTAMP
+      RETURNING allowed, tokens
+      "#,
+    )
+    .bind(key)
+    .bind(capacity)
+    .bind(refill_per_sec)
+    .bind(cost)
+    .fetch_one(pool)
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeGate token bucket check failed", err))?;
+
+    Ok(token_bucket_result(row, refill_per_sec, cost))
+  }
+
+  async fn record_window_hit(
+    &self,
+    key: &str,
+    limit: i64,
+    window_ms: i64,
+    cost: i64,
+  ) -> RuntimeResult<RuntimeRateLimitResult> {
+    let pool = match &self.database {
+      RuntimeDatabase::Postgres(pool) => pool,
+      RuntimeDatabase::Sqlite(pool) => return Self::record_window_hit_sqlite(pool, key, limit, window_ms, cost).await,
+    };
+    let mut tx = pool
+      .begin()
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeGate transaction failed", err))?;
+
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended($1, 0))")
+      .bind(key)
+      .execute(&mut *tx)
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeGate sliding window lock failed", err))?;
+
+    sqlx::query(
+      r#"
+      DELETE FROM runtime_gate_window_hits
+      WHERE key = $1 AND created_at <= CURRENT_TIMESTAMP - ($2 * INTERVAL '1 millisecond')
+      "#,
+    )
+    .bind(key)
+    .bind(window_ms as f64)
+    .execute(&mut *tx)
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeGate sliding window expired cleanup failed", err))?;
+
+    let hits = sqlx::query_as::<_, WindowHitRow>(
+      r#"
+      SELECT cost,
+             (EXTRACT(EPOCH FROM created_at + ($2 * INTERVAL '1 millisecond')) * 1000)::BIGINT AS expires_at_ms
+      FROM runtime_gate_window_hits
+      WHERE key = $1
+      ORDER BY created_at ASC
+      "#,
+    )
+    .bind(key)
+    .bind(window_ms as f64)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeGate sliding window load failed", err))?;
+
+    let used: i64 = hits.iter().map(|hit| hit.cost).sum();
+    let allowed = used + cost <= limit;
+    let retry_after_ms = if allowed {
+      sqlx::query(
+        r#"
+        INSERT INTO runtime_gate_window_hits (key, cost, expires_at)
+        VALUES ($1, $2, CURRENT_TIMESTAMP + ($3 * INTERVAL '1 millisecond'))
+        "#,
+      )
+      .bind(key)
+      .bind(cost)
+      .bind(window_ms as f64)
+      .execute(&mut *tx)
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeGate sliding window insert failed", err))?;
+      None
+    } else {
+      let now_ms = sqlx::query_scalar::<_, i64>("SELECT (EXTRACT(EPOCH FROM CURRENT_TIMESTAMP) * 1000)::BIGINT")
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|err| RuntimeError::database("RuntimeGate sliding window clock failed", err))?;
+      window_retry_after_ms(&hits, used, limit, cost, now_ms)
+    };
+
+    tx.commit()
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeGate transaction commit failed", err))?;
+
+    Ok(window_result(allowed, used, limit, cost, retry_after_ms))
+  }
+
+  /// Delete at most `limit` expired rows in total, taking plain gates first,
+  /// then refilled token buckets, then expired sliding window hits.
   async fn cleanup_expired(&self, limit: i64) -> RuntimeResult<i64> {
+    let pool = match &self.database {
+      RuntimeDatabase::Postgres(pool) => pool,
+      RuntimeDatabase::Sqlite(pool) => return Self::cleanup_expired_sqlite(pool, limit).await,
+    };
     let result = sqlx::query(
       r#"
       DELETE FROM runtime_gates
@@ -60,11 +218,310 @@ impl RuntimeGateStore {
       "#,
     )
     .bind(limit)
-    .execute(&self.pool)
+    .execute(pool)
     .await
     .map_err(|err| RuntimeError::database("RuntimeGate cleanup failed", err))?;
+    let mut deleted = result.rows_affected() as i64;
+    if deleted >= limit {
+      return Ok(deleted);
+    }
 
-    Ok(result.rows_affected() as i64)
+    // A bucket that has refilled to capacity is indistinguishable from a
+    // missing one, so it can be dropped.
+    let buckets = sqlx::query(
+      r#"
+      DELETE FROM runtime_gate_buckets
+      WHERE key IN (
+        SELECT key FROM runtime_gate_buckets
+        WHERE refilled_at + ((capacity - tokens) / refill_per_sec * INTERVAL '1 second') <= CURRENT_TIMESTAMP
+        ORDER BY refilled_at ASC
+        LIMIT $1
+      )
+      "#,
+    )
+    .bind(limit - deleted)
+    .execute(pool)
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeGate token bucket cleanup failed", err))?;
+    deleted += buckets.rows_affected() as i64;
+    if deleted >= limit {
+      return Ok(deleted);
+    }
+
+    let hits = sqlx::query(
+      r#"
+      DELETE FROM runtime_gate_window_hits
+      WHERE id IN (
+        SELECT id FROM runtime_gate_window_hits
+        WHERE expires_at <= CURRENT_TIMESTAMP
+        ORDER BY expires_at ASC
+        LIMIT $1
+      )
+      "#,
+    )
+    .bind(limit - deleted)
+    .execute(pool)
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeGate sliding window cleanup failed", err))?;
+
+    Ok(deleted + hits.rows_affected() as i64)
+  }
+
+  async fn put_if_absent_sqlite(pool: &SqlitePool, key: &str, ttl_ms: i64) -> RuntimeResult<bool> {
+    let now_ms = unix_ms_now();
+    let mut tx = pool
+      .begin()
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeGate transaction failed", err))?;
+
+    sqlx::query("DELETE FROM runtime_gates WHERE key = ?1 AND expires_at <= ?2")
+      .bind(key)
+      .bind(now_ms)
+      .execute(&mut *tx)
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeGate expired cleanup failed", err))?;
+
+    let inserted = sqlx::query(
+      r#"
+      INSERT INTO runtime_gates (key, expires_at, created_at)
+      VALUES (?1, ?2 + ?3, ?2)
+      ON CONFLICT (key) DO NOTHING
+      "#,
+    )
+    .bind(key)
+    .bind(now_ms)
+    .bind(ttl_ms)
+    .execute(&mut *tx)
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeGate put_if_absent failed", err))?
+    .rows_affected()
+      == 1;
+
+    tx.commit()
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeGate transaction commit failed", err))?;
+
+    Ok(inserted)
+  }
+
+  async fn take_tokens_sqlite(
+    pool: &SqlitePool,
+    key: &str,
+    capacity: f64,
+    refill_per_sec: f64,
+    cost: f64,
+  ) -> RuntimeResult<TokenBucketRow> {
+    let now_ms = unix_ms_now();
+    let mut tx = pool
+      .begin()
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeGate transaction failed", err))?;
+
+    let bucket = sqlx::query_as::<_, (f64, i64)>("SELECT tokens, refilled_at FROM runtime_gate_buckets WHERE key = ?1")
+      .bind(key)
+      .fetch_optional(&mut *tx)
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeGate token bucket check failed", err))?;
+    let available = match bucket {
+      Some((tokens, refilled_at)) => {
+        capacity.min(tokens + (now_ms - refilled_at).max(0) as f64 / 1000.0 * refill_per_sec)
+      }
+      None => capacity,
+    };
+    let allowed = available >= cost;
+    let tokens = if allowed { available - cost } else { available };
+
+    sqlx::query(
+      r#"
+      INSERT INTO runtime_gate_buckets (key, capacity, refill_per_sec, tokens, allowed, refilled_at)
+      VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+      ON CONFLICT (key) DO UPDATE
+        SET capacity = excluded.capacity,
+            refill_per_sec = excluded.refill_per_sec,
+            tokens = excluded.tokens,
+            allowed = excluded.allowed,
+            refilled_at = excluded.refilled_at
+      "#,
+    )
+    .bind(key)
+    .bind(capacity)
+    .bind(refill_per_sec)
+    .bind(tokens)
+    .bind(allowed)
+    .bind(now_ms)
+    .execute(&mut *tx)
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeGate token bucket check failed", err))?;
+
+    tx.commit()
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeGate transaction commit failed", err))?;
+
+    Ok(TokenBucketRow { allowed, tokens })
+  }
+
+  async fn record_window_hit_sqlite(
+    pool: &SqlitePool,
+    key: &str,
+    limit: i64,
+    window_ms: i64,
+    cost: i64,
+  ) -> RuntimeResult<RuntimeRateLimitResult> {
+    let now_ms = unix_ms_now();
+    let mut tx = pool
+      .begin()
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeGate transaction failed", err))?;
+
+    sqlx::query("DELETE FROM runtime_gate_window_hits WHERE key = ?1 AND created_at <= ?2 - ?3")
+      .bind(key)
+      .bind(now_ms)
+      .bind(window_ms)
+      .execute(&mut *tx)
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeGate sliding window expired cleanup failed", err))?;
+
+    let hits = sqlx::query_as::<_, WindowHitRow>(
+      r#"
+      SELECT cost, created_at + ?2 AS expires_at_ms
+      FROM runtime_gate_window_hits
+      WHERE key = ?1
+      ORDER BY created_at ASC, id ASC
+      "#,
+    )
+    .bind(key)
+    .bind(window_ms)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeGate sliding window load failed", err))?;
+
+    let used: i64 = hits.iter().map(|hit| hit.cost).sum();
+    let allowed = used + cost <= limit;
+    let retry_after_ms = if allowed {
+      sqlx::query(
+        r#"
+        INSERT INTO runtime_gate_window_hits (key, cost, created_at, expires_at)
+        VALUES (?1, ?2, ?3, ?3 + ?4)
+        "#,
+      )
+      .bind(key)
+      .bind(cost)
+      .bind(now_ms)
+      .bind(window_ms)
+      .execute(&mut *tx)
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeGate sliding window insert failed", err))?;
+      None
+    } else {
+      window_retry_after_ms(&hits, used, limit, cost, now_ms)
+    };
+
+    tx.commit()
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeGate transaction commit failed", err))?;
+
+    Ok(window_result(allowed, used, limit, cost, retry_after_ms))
+  }
+
+  async fn cleanup_expired_sqlite(pool: &SqlitePool, limit: i64) -> RuntimeResult<i64> {
+    let now_ms = unix_ms_now();
+    let result = sqlx::query(
+      r#"
+      DELETE FROM runtime_gates
+      WHERE key IN (
+        SELECT key FROM runtime_gates
+        WHERE expires_at <= ?1
+        ORDER BY expires_at ASC
+        LIMIT ?2
+      )
+      "#,
+    )
+    .bind(now_ms)
+    .bind(limit)
+    .execute(pool)
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeGate cleanup failed", err))?;
+    let mut deleted = result.rows_affected() as i64;
+    if deleted >= limit {
+      return Ok(deleted);
+    }
+
+    let buckets = sqlx::query(
+      r#"
+      DELETE FROM runtime_gate_buckets
+      WHERE key IN (
+        SELECT key FROM runtime_gate_buckets
+        WHERE refilled_at + (capacity - tokens) / refill_per_sec * 1000 <= ?1
+        ORDER BY refilled_at ASC
+        LIMIT ?2
+      )
+      "#,
+    )
+    .bind(now_ms)
+    .bind(limit - deleted)
+    .execute(pool)
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeGate token bucket cleanup failed", err))?;
+    deleted += buckets.rows_affected() as i64;
+    if deleted >= limit {
+      return Ok(deleted);
+    }
+
+    let hits = sqlx::query(
+      r#"
+      DELETE FROM runtime_gate_window_hits
+      WHERE id IN (
+        SELECT id FROM runtime_gate_window_hits
+        WHERE expires_at <= ?1
+        ORDER BY expires_at ASC
+        LIMIT ?2
+      )
+      "#,
+    )
+    .bind(now_ms)
+    .bind(limit - deleted)
+    .execute(pool)
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeGate sliding window cleanup failed", err))?;
+
+    Ok(deleted + hits.rows_affected() as i64)
+  }
+}
+
+fn token_bucket_result(row: TokenBucketRow, refill_per_sec: f64, cost: f64) -> RuntimeRateLimitResult {
+  let retry_after_ms = (!row.allowed).then(|| ((cost - row.tokens) / refill_per_sec * 1000.0).ceil() as i64);
+
+  RuntimeRateLimitResult {
+    allowed: row.allowed,
+    remaining: row.tokens,
+    retry_after_ms,
+  }
+}
+
+/// Time until enough of the oldest hits expire to admit `cost`.
+fn window_retry_after_ms(hits: &[WindowHitRow], used: i64, limit: i64, cost: i64, now_ms: i64) -> Option<i64> {
+  let mut remaining_used = used;
+  hits
+    .iter()
+    .find(|hit| {
+      remaining_used -= hit.cost;
+      remaining_used + cost <= limit
+    })
+    .map(|hit| (hit.expires_at_ms - now_ms).max(0))
+}
+
+fn window_result(
+  allowed: bool,
+  used: i64,
+  limit: i64,
+  cost: i64,
+  retry_after_ms: Option<i64>,
+) -> RuntimeRateLimitResult {
+  let used = if allowed { used + cost } else { used };
+  RuntimeRateLimitResult {
+    allowed,
+    remaining: (limit - used).max(0) as f64,
+    retry_after_ms,
   }
 }
 
@@ -75,18 +532,78 @@ impl BackendRuntime {
     if ttl_ms <= 0 {
       return Err(napi_error("runtime gate ttl must be positive"));
     }
-    RuntimeGateStore::new(self.pool().await?)
+    RuntimeGateStore::new(self.database().await?)
       .put_if_absent(&key, ttl_ms)
       .await
       .map_err(napi::Error::from)
   }
 
+  /// Token-bucket check shared across instances. Each call refills the bucket
+  /// for the elapsed time and takes `cost` tokens only when enough are left.
+  #[napi]
+  pub async fn rate_limit_check(
+    &self,
+    key: String,
+    capacity: f64,
+    refill_per_sec: f64,
+    cost: f64,
+  ) -> Result<RuntimeRateLimitResult> {
+    if !(capacity.is_finite() && capacity > 0.0) {
+      return Err(napi_error("rate limit capacity must be positive"));
+    }
+    if !(refill_per_sec.is_finite() && refill_per_sec > 0.0) {
+      return Err(napi_error("rate limit refill rate must be positive"));
+    }
+    if !(cost.is_finite() && cost > 0.0) {
+      return Err(napi_error("rate limit cost must be positive"));
+    }
+    if cost > capacity {
+      return Err(napi_error("rate limit cost must not exceed capacity"));
+    }
+
+    RuntimeGateStore::new(self.database().await?)
+      .take_tokens(&key, capacity, refill_per_sec, cost)
+      .await
+      .map_err(napi::Error::from)
+  }
+
+  /// Sliding-window check that admits at most `limit` cost units within any
+  /// `window_ms` span.
+  #[napi]
+  pub async fn rate_limit_check_sliding_window(
+    &self,
+    key: String,
+    limit: i64,
+    window_ms: i64,
+    cost: i64,
+  ) -> Result<RuntimeRateLimitResult> {
+    if limit <= 0 {
+      return Err(napi_error("rate limit window limit must be positive"));
+    }
+    if window_ms <= 0 {
+      return Err(napi_error("rate limit window must be positive"));
+    }
+    if cost <= 0 {
+      return Err(napi_error("rate limit cost must be positive"));
+    }
+    if cost > limit {
+      return Err(napi_error("rate limit cost must not exceed window limit"));
+    }
+
+    RuntimeGateStore::new(self.database().await?)
+      .record_window_hit(&key, limit, window_ms, cost)
+      .await
+      .map_err(napi::Error::from)
+  }
+
+  /// Delete up to `limit` expired gates, token buckets and sliding window
+  /// hits, counted together across the three tables.
   #[napi]
   pub async fn cleanup_expired_runtime_gates(&self, limit: i64) -> Result<i64> {
     if limit <= 0 {
       return Err(napi_error("runtime gate cleanup limit must be positive"));
     }
-    RuntimeGateStore::new(self.pool().await?)
+    RuntimeGateStore::new(self.database().await?)
       .cleanup_expired(limit)
       .await
       .map_err(napi::Error::from)
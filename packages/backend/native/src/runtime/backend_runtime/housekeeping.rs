@@ -1,18 +1,22 @@
 use napi::Result;
-use sqlx::PgPool;
 
-use super::{BackendRuntime, RuntimeError, RuntimeResult, napi_error};
+use super::{BackendRuntime, RuntimeDatabase, RuntimeError, RuntimeResult, napi_error, unix_ms_now};
 
 struct HousekeepingStore {
-  pool: PgPool,
+  database: RuntimeDatabase,
 }
 
 impl HousekeepingStore {
-  fn new(pool: PgPool) -> Self {
-    Self { pool }
+  fn new(database: RuntimeDatabase) -> Self {
+    Self { database }
   }
 
   async fn cleanup_expired_user_sessions(&self, limit: i64) -> RuntimeResult<i64> {
+    // Sessions live in the server's Prisma schema, which has no embedded
+    // SQLite counterpart, so there is nothing to expire there.
+    let RuntimeDatabase::Postgres(pool) = &self.database else {
+      return Ok(0);
+    };
     let result = sqlx::query(
       r#"
       DELETE FROM user_sessions
@@ -25,7 +29,7 @@ impl HousekeepingStore {
       "#,
     )
     .bind(limit)
-    .execute(&self.pool)
+    .execute(pool)
     .await
     .map_err(|err| RuntimeError::database("Housekeeping user sessions cleanup failed", err))?;
 
@@ -33,22 +37,68 @@ impl HousekeepingStore {
   }
 
   async fn cleanup_expired_snapshot_histories(&self, limit: i64) -> RuntimeResult<i64> {
+    let rows_affected = match &self.database {
+      RuntimeDatabase::Postgres(pool) => sqlx::query(
+        r#"
+        DELETE FROM snapshot_histories
+        WHERE (workspace_id, guid, timestamp) IN (
+          SELECT workspace_id, guid, timestamp
+          FROM snapshot_histories
+          WHERE expired_at <= CURRENT_TIMESTAMP
+          ORDER BY expired_at ASC
+          LIMIT $1
+        )
+        "#,
+      )
+      .bind(limit)
+      .execute(pool)
+      .await
+      .map(|result| result.rows_affected()),
+      RuntimeDatabase::Sqlite(pool) => sqlx::query(
+        r#"
+        DELETE FROM snapshot_histories
+        WHERE rowid IN (
+          SELECT rowid
+          FROM snapshot_histories
+          WHERE expired_at <= ?1
+          ORDER BY expired_at ASC
+          LIMIT ?2
+        )
+        "#,
+      )
+      .bind(unix_ms_now())
+      .bind(limit)
+      .execute(pool)
+      .await
+      .map(|result| result.rows_affected()),
+    }
+    .map_err(|err| RuntimeError::database("Housekeeping snapshot histories cleanup failed", err))?;
+
+    Ok(rows_affected as i64)
+  }
+
+  async fn cleanup_expired_audit_events(&self, retention_ms: i64, limit: i64) -> RuntimeResult<i64> {
+    // The audit log is Postgres-only; embedded SQLite mode never records it.
+    let RuntimeDatabase::Postgres(pool) = &self.database else {
+      return Ok(0);
+    };
+    // Oldest rows go first so the retained audit chain stays contiguous.
     let result = sqlx::query(
       r#"
-      DELETE FROM snapshot_histories
-      WHERE (workspace_id, guid, timestamp) IN (
-        SELECT workspace_id, guid, timestamp
-        FROM snapshot_histories
-        WHERE expired_at <= CURRENT_TIMESTAMP
-        ORDER BY expired_at ASC
-        LIMIT $1
+      DELETE FROM audit_events
+      WHERE seq IN (
+        SELECT seq FROM audit_events
+        WHERE created_at <= CURRENT_TIMESTAMP - ($1 * INTERVAL '1 millisecond')
+        ORDER BY seq ASC
+        LIMIT $2
       )
       "#,
     )
+    .bind(retention_ms as f64)
     .bind(limit)
-    .execute(&self.pool)
+    .execute(pool)
     .await
-    .map_err(|err| RuntimeError::database("Housekeeping snapshot histories cleanup failed", err))?;
+    .map_err(|err| RuntimeError::database("Housekeeping audit events cleanup failed", err))?;
 
     Ok(result.rows_affected() as i64)
   }
@@ -62,7 +112,7 @@ impl BackendRuntime {
       return Err(napi_error("user sessions cleanup limit must be positive"));
     }
 
-    HousekeepingStore::new(self.pool().await?)
+    HousekeepingStore::new(self.database().await?)
       .cleanup_expired_user_sessions(limit)
       .await
       .map_err(napi::Error::from)
@@ -74,9 +124,24 @@ impl BackendRuntime {
       return Err(napi_error("snapshot histories cleanup limit must be positive"));
     }
 
-    HousekeepingStore::new(self.pool().await?)
+    HousekeepingStore::new(self.database().await?)
       .cleanup_expired_snapshot_histories(limit)
       .await
       .map_err(napi::Error::from)
   }
+
+  #[napi]
+  pub async fn cleanup_expired_audit_events(&self, retention_ms: i64, limit: i64) -> Result<i64> {
+    if retention_ms <= 0 {
+      return Err(napi_error("audit events retention must be positive"));
+    }
+    if limit <= 0 {
+      return Err(napi_error("audit events cleanup limit must be positive"));
+    }
+
+    HousekeepingStore::new(self.database().await?)
+      .cleanup_expired_audit_events(retention_ms, limit)
+      .await
+      .map_err(napi::Error::from)
+  }
 }
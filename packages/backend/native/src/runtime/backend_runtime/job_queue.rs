@@ -0,0 +1,477 @@
+use chrono::{DateTime, Utc};
+use napi::Result;
+use sqlx::{FromRow, PgPool};
+
+use super::{
+  BackendRuntime, RuntimeError, RuntimeResult,
+  constants::{RUNTIME_JOB_DEFAULT_MAX_ATTEMPTS, RUNTIME_JOB_RETRY_BASE_MS, RUNTIME_JOB_RETRY_MAX_MS},
+  metrics, napi_error,
+  types::{RuntimeJobEnqueueInput, RuntimeJobEnqueueResult, RuntimeJobFailResult, RuntimeJobRecord},
+};
+
+#[derive(FromRow)]
+struct JobIdRow {
+  id: String,
+}
+
+#[derive(FromRow)]
+struct ClaimedJobRow {
+  id: String,
+  queue: String,
+  dedupe_key: Option<String>,
+  payload: serde_json::Value,
+  attempts: i32,
+  max_attempts: i32,
+  locked_until_ms: i64,
+}
+
+#[derive(FromRow)]
+struct FailedJobRow {
+  status: String,
+  next_run_at_ms: Option<i64>,
+}
+
+struct JobQueueStore {
+  pool: PgPool,
+}
+
+impl JobQueueStore {
+  fn new(pool: PgPool) -> Self {
+    Self { pool }
+  }
+
+  async fn enqueue(
+    &self,
+    input: RuntimeJobEnqueueInput,
+    run_at: Option<DateTime<Utc>>,
+  ) -> RuntimeResult<RuntimeJobEnqueueResult> {
+    let mut tx = self
+      .pool
+      .begin()
+      .await
+      .map_err(|err| RuntimeError::database("JobQueue transaction failed", err))?;
+
+    let inserted = sqlx::query_as::<_, JobIdRow>(
+      r#"
+      INSERT INTO runtime_jobs (queue, dedupe_key, payload, max_attempts, run_at)
+      VALUES ($1, $2, $3, $4, COALESCE($5, CURRENT_TIMESTAMP))
+      ON CONFLICT (queue, dedupe_key) WHERE dedupe_key IS NOT NULL AND status IN ('pending', 'running')
+      DO NOTHING
+      RETURNING id::TEXT AS id
+      "#,
+    )
+    .bind(&input.queue)
+    .bind(input.dedupe_key.as_deref())
+    .bind(&input.payload)
+    .bind(input.max_attempts.unwrap_or(RUNTIME_JOB_DEFAULT_MAX_ATTEMPTS))
+    .bind(run_at)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|err| RuntimeError::database("JobQueue enqueue failed", err))?;
+
+    let result = match inserted {
+      Some(row) => RuntimeJobEnqueueResult {
+        job_id: row.id,
+        deduplicated: false,
+      },
+      None => {
+        let existing = sqlx::query_as::<_, JobIdRow>(
+          r#"
+          SELECT id::TEXT AS id
+          FROM runtime_jobs
+          WHERE queue = $1 AND dedupe_key = $2 AND status IN ('pending', 'running')
+          "#,
+        )
+        .bind(&input.queue)
+        .bind(input.dedupe_key.as_deref())
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|err| RuntimeError::database("JobQueue dedupe lookup failed", err))?
+        .ok_or_else(|| RuntimeError::invalid_state("JobQueue dedupe conflict without active job"))?;
+
+        RuntimeJobEnqueueResult {
+          job_id: existing.id,
+          deduplicated: true,
+        }
+      }
+    };
+
+    tx.commit()
+      .await
+      .map_err(|err| RuntimeError::database("JobQueue transaction commit failed", err))?;
+
+    Ok(result)
+  }
+
+  async fn claim(
+    &self,
+    queue: &str,
+    worker_id: &str,
+    visibility_timeout_ms: i64,
+    limit: i64,
+  ) -> RuntimeResult<Vec<RuntimeJobRecord>> {
+    let mut tx = self
+      .pool
+      .begin()
+      .await
+      .map_err(|err| RuntimeError::database("JobQueue transaction failed", err))?;
+
+    // A worker that vanished on its final attempt never reports a failure, so
+    // its job is dead-lettered here instead of being handed out again.
+    sqlx::query(
+      r#"
+      UPDATE runtime_jobs
+      SET status = 'dead',
+          locked_by = NULL,
+          locked_until = NULL,
+          last_error = COALESCE(last_error, 'visibility timeout expired'),
+          updated_at = CURRENT_TIMESTAMP
+      WHERE id IN (
+        SELECT id FROM runtime_jobs
+        WHERE queue = $1
+          AND status = 'running'
+          AND locked_until <= CURRENT_TIMESTAMP
+          AND attempts >= max_attempts
+        FOR UPDATE SKIP LOCKED
+      )
+      "#,
+    )
+    .bind(queue)
+    .execute(&mut *tx)
+    .await
+    .map_err(|err| RuntimeError::database("JobQueue expired dead-letter failed", err))?;
+
+    let rows = sqlx::query_as::<_, ClaimedJobRow>(
+      r#"
+      WITH next_jobs AS (
+        SELECT id FROM runtime_jobs
+        WHERE queue = $1
+          AND (
+            (status = 'pending' AND run_at <= CURRENT_TIMESTAMP)
+            OR (status = 'running' AND locked_until <= CURRENT_TIMESTAMP AND attempts < max_attempts)
+          )
+        ORDER BY run_at ASC, created_at ASC
+        LIMIT $4
+        FOR UPDATE SKIP LOCKED
+      )
+      UPDATE runtime_jobs AS job
+      SET status = 'running',
+          attempts = job.attempts + 1,
+          locked_by = $2,
+          locked_until = CURRENT_TIMESTAMP + ($3 * INTERVAL '1 millisecond'),
+          updated_at = CURRENT_TIMESTAMP
+      FROM next_jobs
+      WHERE job.id = next_jobs.id
+      RETURNING job.id::TEXT AS id,
+                job.queue,
+                job.dedupe_key,
+                job.payload,
+                job.attempts,
+                job.max_attempts,
+                (EXTRACT(EPOCH FROM job.locked_until) * 1000)::BIGINT AS locked_until_ms
+      "#,
+    )
+    .bind(queue)
+    .bind(worker_id)
+    .bind(visibility_timeout_ms as f64)
+    .bind(limit)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|err| RuntimeError::database("JobQueue claim failed", err))?;
+
+    tx.commit()
+      .await
+      .map_err(|err| RuntimeError::database("JobQueue transaction commit failed", err))?;
+
+    Ok(
+      rows
+        .into_iter()
+        .map(|row| RuntimeJobRecord {
+          job_id: row.id,
+          queue: row.queue,
+          dedupe_key: row.dedupe_key,
+          payload: row.payload,
+          attempts: row.attempts,
+          max_attempts: row.max_attempts,
+          locked_until_ms: row.locked_until_ms,
+        })
+        .collect(),
+    )
+  }
+
+  async fn heartbeat(&self, job_id: &str, worker_id: &str, visibility_timeout_ms: i64) -> RuntimeResult<bool> {
+    let result = sqlx::query(
+      r#"
+      UPDATE runtime_jobs
+      SET locked_until = CURRENT_TIMESTAMP + ($3 * INTERVAL '1 millisecond'),
+          updated_at = CURRENT_TIMESTAMP
+      WHERE id = $1::uuid
+        AND locked_by = $2
+        AND status = 'running'
+        AND locked_until > CURRENT_TIMESTAMP
+      "#,
+    )
+    .bind(job_id)
+    .bind(worker_id)
+    .bind(visibility_timeout_ms as f64)
+    .execute(&self.pool)
+    .await
+    .map_err(|err| RuntimeError::database("JobQueue heartbeat failed", err))?;
+
+    Ok(result.rows_affected() == 1)
+  }
+
+  async fn complete(&self, job_id: &str, worker_id: &str) -> RuntimeResult<bool> {
+    let result = sqlx::query(
+      r#"
+      UPDATE runtime_jobs
+      SET status = 'completed',
+          locked_by = NULL,
+          locked_until = NULL,
+          completed_at = CURRENT_TIMESTAMP,
+          updated_at = CURRENT_TIMESTAMP
+      WHERE id = $1::uuid AND locked_by = $2 AND status = 'running'
+      "#,
+    )
+    .bind(job_id)
+    .bind(worker_id)
+    .execute(&self.pool)
+    .await
+    .map_err(|err| RuntimeError::database("JobQueue complete failed", err))?;
+
+    Ok(result.rows_affected() == 1)
+  }
+
+  async fn fail(&self, job_id: &str, worker_id: &str, error: &str) -> RuntimeResult<Option<RuntimeJobFailResult>> {
+    let row = sqlx::query_as::<_, FailedJobRow>(
+      r#"
+      UPDATE runtime_jobs
+      SET status = CASE WHEN attempts >= max_attempts THEN 'dead' ELSE 'pending' END,
+          run_at = CASE
+            WHEN attempts >= max_attempts THEN run_at
+            ELSE CURRENT_TIMESTAMP
+              + (LEAST($4 * POWER(2, GREATEST(attempts - 1, 0)), $5) * INTERVAL '1 millisecond')
+          END,
+          locked_by = NULL,
+          locked_until = NULL,
+          last_error = $3,
+          updated_at = CURRENT_TIMESTAMP
+      WHERE id = $1::uuid AND locked_by = $2 AND status = 'running'
+      RETURNING status,
+                CASE WHEN status = 'pending' THEN (EXTRACT(EPOCH FROM run_at) * 1000)::BIGINT END AS next_run_at_ms
+      "#,
+    )
+    .bind(job_id)
+    .bind(worker_id)
+    .bind(error)
+    .bind(RUNTIME_JOB_RETRY_BASE_MS as f64)
+    .bind(RUNTIME_JOB_RETRY_MAX_MS as f64)
+    .fetch_optional(&self.pool)
+    .await
+    .map_err(|err| RuntimeError::database("JobQueue fail failed", err))?;
+
+    Ok(row.map(|row| RuntimeJobFailResult {
+      status: row.status,
+      next_run_at_ms: row.next_run_at_ms,
+    }))
+  }
+
+  async fn requeue_dead(&self, job_id: &str) -> RuntimeResult<bool> {
+    // A live job with the same dedupe key already covers the dead one; the
+    // unique index would reject the update, so it is left dead instead.
+    let result = sqlx::query(
+      r#"
+      UPDATE runtime_jobs AS job
+      SET status = 'pending',
+          attempts = 0,
+          run_at = CURRENT_TIMESTAMP,
+          updated_at = CURRENT_TIMESTAMP
+      WHERE job.id = $1::uuid
+        AND job.status = 'dead'
+        AND NOT EXISTS (
+          SELECT 1 FROM runtime_jobs AS live
+          WHERE live.queue = job.queue
+            AND live.dedupe_key = job.dedupe_key
+            AND live.status IN ('pending', 'running')
+        )
+      "#,
+    )
+    .bind(job_id)
+    .execute(&self.pool)
+    .await;
+
+    match result {
+      Ok(result) => Ok(result.rows_affected() == 1),
+      // A duplicate enqueued concurrently won the race for the dedupe key.
+      Err(sqlx::Error::Database(err)) if err.is_unique_violation() => Ok(false),
+      Err(err) => Err(RuntimeError::database("JobQueue requeue failed", err)),
+    }
+  }
+
+  async fn cleanup_completed(&self, limit: i64) -> RuntimeResult<i64> {
+    let result = sqlx::query(
+      r#"
+      DELETE FROM runtime_jobs
+      WHERE id IN (
+        SELECT id FROM runtime_jobs
+        WHERE status = 'completed'
+        ORDER BY completed_at ASC
+        LIMIT $1
+      )
+      "#,
+    )
+    .bind(limit)
+    .execute(&self.pool)
+    .await
+    .map_err(|err| RuntimeError::database("JobQueue cleanup failed", err))?;
+
+    Ok(result.rows_affected() as i64)
+  }
+}
+
+fn validate_job_id(job_id: &str) -> Result<()> {
+  uuid::Uuid::parse_str(job_id)
+    .map(|_| ())
+    .map_err(|_| napi_error(format!("Invalid runtime job id: {job_id}")))
+}
+
+#[napi_derive::napi]
+impl BackendRuntime {
+  #[napi]
+  pub async fn enqueue_runtime_job(&self, input: RuntimeJobEnqueueInput) -> Result<RuntimeJobEnqueueResult> {
+    if input.queue.is_empty() {
+      return Err(napi_error("runtime job queue is required"));
+    }
+    if input.max_attempts.is_some_and(|max_attempts| max_attempts <= 0) {
+      return Err(napi_error("runtime job max attempts must be positive"));
+    }
+    let run_at = input
+      .run_at_ms
+      .map(|run_at_ms| {
+        DateTime::<Utc>::from_timestamp_millis(run_at_ms)
+          .ok_or_else(|| napi_error(format!("Invalid runtime job run_at: {run_at_ms}")))
+      })
+      .transpose()?;
+
+    let store = JobQueueStore::new(self.pool().await?);
+    metrics::timed(metrics::BACKEND, "enqueue_runtime_job", store.enqueue(input, run_at))
+      .await
+      .map_err(napi::Error::from)
+  }
+
+  #[napi]
+  pub async fn claim_runtime_jobs(
+    &self,
+    queue: String,
+    worker_id: String,
+    visibility_timeout_ms: i64,
+    limit: i64,
+  ) -> Result<Vec<RuntimeJobRecord>> {
+    if worker_id.is_empty() {
+      return Err(napi_error("runtime job worker id is required"));
+    }
+    if visibility_timeout_ms <= 0 {
+      return Err(napi_error("runtime job visibility timeout must be positive"));
+    }
+    if limit <= 0 {
+      return Err(napi_error("runtime job claim limit must be positive"));
+    }
+
+    let store = JobQueueStore::new(self.pool().await?);
+    metrics::timed(
+      metrics::BACKEND,
+      "claim_runtime_jobs",
+      store.claim(&queue, &worker_id, visibility_timeout_ms, limit),
+    )
+    .await
+    .map_err(napi::Error::from)
+  }
+
+  #[napi]
+  pub async fn heartbeat_runtime_job(
+    &self,
+    job_id: String,
+    worker_id: String,
+    visibility_timeout_ms: i64,
+  ) -> Result<bool> {
+    validate_job_id(&job_id)?;
+    if visibility_timeout_ms <= 0 {
+      return Err(napi_error("runtime job visibility timeout must be positive"));
+    }
+
+    let store = JobQueueStore::new(self.pool().await?);
+    metrics::timed(
+      metrics::BACKEND,
+      "heartbeat_runtime_job",
+      store.heartbeat(&job_id, &worker_id, visibility_timeout_ms),
+    )
+    .await
+    .map_err(napi::Error::from)
+  }
+
+  #[napi]
+  pub async fn complete_runtime_job(&self, job_id: String, worker_id: String) -> Result<bool> {
+    validate_job_id(&job_id)?;
+    let store = JobQueueStore::new(self.pool().await?);
+    metrics::timed(
+      metrics::BACKEND,
+      "complete_runtime_job",
+      store.complete(&job_id, &worker_id),
+    )
+    .await
+    .map_err(napi::Error::from)
+  }
+
+  /// Report a failed attempt. The job is rescheduled with exponential backoff
+  /// or moved to the `dead` state once it has used all of its attempts.
+  /// Returns `null` when the worker no longer holds the job.
+  #[napi]
+  pub async fn fail_runtime_job(
+    &self,
+    job_id: String,
+    worker_id: String,
+    error: String,
+  ) -> Result<Option<RuntimeJobFailResult>> {
+    validate_job_id(&job_id)?;
+    let store = JobQueueStore::new(self.pool().await?);
+    metrics::timed(
+      metrics::BACKEND,
+      "fail_runtime_job",
+      store.fail(&job_id, &worker_id, &error),
+    )
+    .await
+    .map_err(napi::Error::from)
+  }
+
+  /// Move a dead job back to `pending` with its attempts reset. Returns
+  /// `false` when the job is not dead or a live job with the same dedupe key
+  /// already exists.
+  #[napi]
+  pub async fn requeue_dead_runtime_job(&self, job_id: String) -> Result<bool> {
+    validate_job_id(&job_id)?;
+    let store = JobQueueStore::new(self.pool().await?);
+    metrics::timed(
+      metrics::BACKEND,
+      "requeue_dead_runtime_job",
+      store.requeue_dead(&job_id),
+    )
+    .await
+    .map_err(napi::Error::from)
+  }
+
+  #[napi]
+  pub async fn cleanup_completed_runtime_jobs(&self, limit: i64) -> Result<i64> {
+    if limit <= 0 {
+      return Err(napi_error("runtime job cleanup limit must be positive"));
+    }
+
+    let store = JobQueueStore::new(self.pool().await?);
+    metrics::timed(
+      metrics::BACKEND,
+      "cleanup_completed_runtime_jobs",
+      store.cleanup_completed(limit),
+    )
+    .await
+    .map_err(napi::Error::from)
+  }
+}
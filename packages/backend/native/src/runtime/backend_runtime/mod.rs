@@ -1,34 +1,49 @@
+mod audit_log;
 mod constants;
 mod coordination_lease;
 mod doc_compactor;
+pub(crate) mod doc_encryption;
 mod doc_storage;
 mod gate;
 mod housekeeping;
+mod job_queue;
 mod runtime_state;
 #[cfg(test)]
 mod tests;
 mod workspace_stats;
-use std::{sync::RwLock, time::Duration};
+mod workspace_stats_series;
+use std::sync::RwLock;
 
 use napi::Result;
 use sha2::{Digest, Sha256};
-use sqlx::{PgPool, Row, postgres::PgPoolOptions};
+use sqlx::PgPool;
 use tokio::sync::Mutex;
 
-use self::types::BackendRuntimeHealth;
+use self::types::{BackendRuntimeHealth, RuntimeConfigReloadResult};
 pub(crate) use super::types;
 use super::{
-  BackendRuntimeConfig, RuntimeError, RuntimeResult, migrations::migrate_runtime_tables, napi_error, to_napi_error,
+  BackendRuntimeConfig, DocEncryptionConfig, RuntimeError, RuntimeResult,
+  database::{ReplicaSet, RuntimeDatabase},
+  metrics,
+  migrations::migrate_runtime_database,
+  napi_error, to_napi_error,
 };
 
 pub(super) fn token_hash(token: &str) -> String {
   hex::encode(Sha256::digest(token.as_bytes()))
 }
 
+/// Wall-clock time for the embedded SQLite mode, which stores timestamps as
+/// unix epoch milliseconds and has no server clock of its own.
+pub(super) fn unix_ms_now() -> i64 {
+  chrono::Utc::now().timestamp_millis()
+}
+
 #[napi_derive::napi]
 pub struct BackendRuntime {
   config: RwLock<BackendRuntimeConfig>,
-  pool: Mutex<Option<PgPool>>,
+  database: Mutex<Option<RuntimeDatabase>>,
+  replicas: Mutex<Option<ReplicaSet>>,
 }
 
 #[napi_derive::napi]
@@ -37,7 +52,8 @@ impl BackendRuntime {
   pub fn new() -> Result<Self> {
     Ok(Self {
       config: RwLock::new(BackendRuntimeConfig::from_config_files().map_err(to_napi_error)?),
-      pool: Mutex::new(None),
+      database: Mutex::new(None),
+      replicas: Mutex::new(None),
     })
   }
 
@@ -47,72 +63,136 @@ impl BackendRuntime {
   }
 
   async fn start_inner(&self) -> RuntimeResult<()> {
-    let mut guard = self.pool.lock().await;
+    let mut guard = self.database.lock().await;
     if guard.is_some() {
       return Ok(());
     }
 
-    let database_url = self.config()?.database_url;
-    let pool = PgPoolOptions::new()
-      .max_connections(5)
-      .acquire_timeout(Duration::from_secs(5))
-      .connect(&database_url)
-      .await
-      .map_err(|err| RuntimeError::database("BackendRuntime failed to connect postgres", err))?;
-
-    sqlx::query("SELECT 1")
-      .execute(&pool)
-      .await
-      .map_err(|err| RuntimeError::database("BackendRuntime postgres health check failed", err))?;
+    let config = self.config()?;
+    let database = RuntimeDatabase::connect(&config.database_url, &config.pool, "BackendRuntime").await?;
+    let replicas = match database {
+      RuntimeDatabase::Postgres(_) => ReplicaSet::connect(&config.replicas, &config.pool, "BackendRuntime")?,
+      RuntimeDatabase::Sqlite(_) => None,
+    };
 
-    let config = self.config()?.with_db_overrides(&pool).await?;
+    let config = config.with_db_overrides(&database).await?;
+    if database.is_sqlite() && config.doc_encryption.master_key.is_some() {
+      database.close().await;
+      return Err(RuntimeError::config(
+        "crypto.docEncryption is not supported in embedded SQLite mode",
+      ));
+    }
     self.update_config(config)?;
-
-    *guard = Some(pool);
+    *self.replicas.lock().await = replicas;
+    *guard = Some(database);
     Ok(())
   }
 
+  /// Re-read config files and, once started, `app_configs` overrides, then
+  /// swap the active config in place. Invalid config leaves the current one
+  /// untouched.
+  #[napi]
+  pub async fn reload_config(&self) -> Result<RuntimeConfigReloadResult> {
+    self.reload_config_inner().await.map_err(to_napi_error)
+  }
+
+  async fn reload_config_inner(&self) -> RuntimeResult<RuntimeConfigReloadResult> {
+    let database = self.database.lock().await.as_ref().cloned();
+    let mut config = BackendRuntimeConfig::from_config_files()?;
+    let mut restart_required = false;
+    if let Some(database) = database.as_ref() {
+      let active = self.config()?;
+      restart_required =
+        config.database_url != active.database_url || config.pool != active.pool || config.replicas != active.replicas;
+      config.database_url = active.database_url;
+      config.pool = active.pool;
+      config.replicas = active.replicas;
+      config = config.with_db_overrides(database).await?;
+      if database.is_sqlite() && config.doc_encryption.master_key.is_some() {
+        return Err(RuntimeError::config(
+          "crypto.docEncryption is not supported in embedded SQLite mode",
+        ));
+      }
+    }
+
+    self.update_config(config)?;
+    Ok(RuntimeConfigReloadResult {
+      backends: Vec::new(),
+      restart_required,
+    })
+  }
+
   #[napi]
   pub async fn stop(&self) -> Result<()> {
-    let pool = self.pool.lock().await.take();
-    if let Some(pool) = pool {
-      pool.close().await;
+    let database = self.database.lock().await.take();
+    if let Some(database) = database {
+      database.close().await;
+    }
+    let replicas = self.replicas.lock().await.take();
+    if let Some(replicas) = replicas {
+      replicas.close().await;
     }
     Ok(())
   }
 
   #[napi]
   pub async fn health(&self) -> Result<BackendRuntimeHealth> {
-    let pool = self.pool.lock().await.as_ref().cloned();
-    let database_connected = match pool.as_ref() {
-      Some(pool) => sqlx::query("SELECT 1")
-        .fetch_one(pool)
-        .await
-        .map(|row| row.try_get::<i32, _>(0).unwrap_or(0) == 1)
-        .unwrap_or(false),
+    let database = self.database.lock().await.as_ref().cloned();
+    let database_connected = match database.as_ref() {
+      Some(database) => database.ping().await,
       None => false,
     };
 
     Ok(BackendRuntimeHealth {
-      started: pool.is_some(),
+      started: database.is_some(),
       database_connected,
     })
   }
 
+  /// Render runtime metrics in the Prometheus text exposition format.
+  #[napi]
+  pub async fn render_metrics(&self) -> Result<String> {
+    if let Some(RuntimeDatabase::Postgres(pool)) = self.database.lock().await.as_ref() {
+      metrics::record_pool(metrics::BACKEND, pool);
+    }
+    Ok(metrics::render())
+  }
+
   #[napi]
   pub async fn run_migrations(&self) -> Result<()> {
-    let pool = self.pool().await?;
-    migrate_runtime_tables(&pool).await.map_err(to_napi_error)
+    let database = self.database().await?;
+    migrate_runtime_database(&database).await.map_err(to_napi_error)
   }
 
-  pub(crate) async fn pool(&self) -> RuntimeResult<PgPool> {
+  pub(crate) async fn database(&self) -> RuntimeResult<RuntimeDatabase> {
     self
-      .pool
+      .database
       .lock()
       .await
       .as_ref()
       .cloned()
-      .ok_or_else(|| RuntimeError::invalid_state("BackendRuntime must be started before using postgres operations"))
+      .ok_or_else(|| RuntimeError::invalid_state("BackendRuntime must be started before using database operations"))
+  }
+
+  /// Postgres pool for operations without an embedded SQLite implementation.
+  pub(crate) async fn pool(&self) -> RuntimeResult<PgPool> {
+    match self.database().await? {
+      RuntimeDatabase::Postgres(pool) => Ok(pool),
+      RuntimeDatabase::Sqlite(_) => Err(RuntimeError::invalid_state(
+        "BackendRuntime operation requires postgres and is not supported in embedded SQLite mode",
+      )),
+    }
+  }
+
+  /// Pool for read-only queries that tolerate replica lag. Falls back to the
+  /// primary when no replica is configured or none is fresh enough.
+  pub(crate) async fn read_pool(&self) -> RuntimeResult<PgPool> {
+    let primary = self.pool().await?;
+    let replicas = self.replicas.lock().await.clone();
+    Ok(match replicas {
+      Some(replicas) => replicas.read_pool(&primary, metrics::BACKEND).await,
+      None => primary,
+    })
   }
 
   pub(crate) fn config(&self) -> RuntimeResult<BackendRuntimeConfig> {
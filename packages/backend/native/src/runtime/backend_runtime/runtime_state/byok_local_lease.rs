@@ -1,6 +1,6 @@
 use super::{
   BYOK_LOCAL_LEASE_ACTIVE_PURPOSE, BYOK_LOCAL_LEASE_PURPOSE, Result, RuntimeByokLocalLeaseRecord, RuntimeError,
-  dto::{RuntimeStateInsertPayload, RuntimeStatePayloadRow, RuntimeStateRows},
+  dto::{RuntimeStateInsertPayload, RuntimeStatePayloadRow, RuntimeStateRows, RuntimeStateTx},
 };
 
 pub(super) async fn get(rows: &RuntimeStateRows, lease_id: String) -> Result<Option<RuntimeByokLocalLeaseRecord>> {
@@ -19,11 +19,8 @@ pub(super) async fn create(
   }
 
   let mut tx = rows.begin("RuntimeState BYOK local lease").await?;
-  sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended($1, 0))")
-    .bind(&active_key)
-    .execute(&mut *tx)
-    .await
-    .map_err(|err| RuntimeError::database("RuntimeState BYOK local lease active lock failed", err))?;
+  tx.lock(&active_key, "RuntimeState BYOK local lease active lock failed")
+    .await?;
 
   if let Some(active) = rows
     .active_payload_with_expires_for_update_in_tx(
@@ -104,7 +101,7 @@ async fn get_lease_by_id(rows: &RuntimeStateRows, lease_id: &str) -> Result<Opti
 
 async fn get_lease_by_id_in_tx(
   rows: &RuntimeStateRows,
-  tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+  tx: &mut RuntimeStateTx,
   lease_id: &str,
 ) -> Result<Option<RuntimeByokLocalLeaseRecord>> {
   rows
@@ -1,6 +1,6 @@
-use sqlx::{PgPool, Row};
+use sqlx::{Row, postgres::PgRow, sqlite::SqliteRow};
 
-use super::{RuntimeError, RuntimeResult, token_hash};
+use super::{RuntimeDatabase, RuntimeError, RuntimeResult, token_hash, unix_ms_now};
 
 type Result<T> = RuntimeResult<T>;
 
@@ -24,26 +24,63 @@ pub(super) struct RuntimeStateInsertPayload<'a> {
   pub(super) context: &'a str,
 }
 
+/// Open runtime state transaction. Postgres serializes competing writers
+/// with `FOR UPDATE` and advisory locks; the embedded SQLite pool has a
+/// single connection, so its transactions never interleave in the first
+/// place.
+pub(super) enum RuntimeStateTx {
+  Postgres(sqlx::Transaction<'static, sqlx::Postgres>),
+  Sqlite(sqlx::Transaction<'static, sqlx::Sqlite>),
+}
+
+impl RuntimeStateTx {
+  pub(super) async fn commit(self) -> sqlx::Result<()> {
+    match self {
+      Self::Postgres(tx) => tx.commit().await,
+      Self::Sqlite(tx) => tx.commit().await,
+    }
+  }
+
+  pub(super) async fn rollback(self) -> sqlx::Result<()> {
+    match self {
+      Self::Postgres(tx) => tx.rollback().await,
+      Self::Sqlite(tx) => tx.rollback().await,
+    }
+  }
+
+  /// Hold a transaction-scoped lock on `key` until commit or rollback.
+  pub(super) async fn lock(&mut self, key: &str, context: &str) -> Result<()> {
+    if let Self::Postgres(tx) = self {
+      sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended($1, 0))")
+        .bind(key)
+        .execute(&mut **tx)
+        .await
+        .map_err(|err| RuntimeError::database(context, err))?;
+    }
+    Ok(())
+  }
+}
+
 #[derive(Clone)]
 pub(super) struct RuntimeStateRows {
-  pub(super) pool: PgPool,
+  pub(super) database: RuntimeDatabase,
 }
 
 impl RuntimeStateRows {
-  pub(super) fn new(pool: PgPool) -> Self {
-    Self { pool }
+  pub(super) fn new(database: RuntimeDatabase) -> Self {
+    Self { database }
   }
 
-  pub(super) fn pool(&self) -> &PgPool {
-    &self.pool
+  pub(super) fn database(&self) -> &RuntimeDatabase {
+    &self.database
   }
 
-  pub(super) async fn begin(&self, context: &str) -> Result<sqlx::Transaction<'_, sqlx::Postgres>> {
-    self
-      .pool
-      .begin()
-      .await
-      .map_err(|err| RuntimeError::database(format!("{context} transaction failed"), err))
+  pub(super) async fn begin(&self, context: &str) -> Result<RuntimeStateTx> {
+    match &self.database {
+      RuntimeDatabase::Postgres(pool) => pool.begin().await.map(RuntimeStateTx::Postgres),
+      RuntimeDatabase::Sqlite(pool) => pool.begin().await.map(RuntimeStateTx::Sqlite),
+    }
+    .map_err(|err| RuntimeError::database(format!("{context} transaction failed"), err))
   }
 
   pub(super) async fn insert_payload(
@@ -55,22 +92,38 @@ impl RuntimeStateRows {
     ttl_ms: i64,
     context: &str,
   ) -> Result<()> {
-    sqlx::query(
-      r#"
-      INSERT INTO runtime_states (purpose, token_hash, lookup_key, payload, expires_at)
-      VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP + ($5 * INTERVAL '1 millisecond'))
-      "#,
-    )
-    .bind(purpose)
-    .bind(token_hash(token))
-    .bind(lookup_key)
-    .bind(payload)
-    .bind(ttl_ms as f64)
-    .execute(&self.pool)
-    .await
-    .map_err(|err| RuntimeError::database(context, err))?;
-
-    Ok(())
+    match &self.database {
+      RuntimeDatabase::Postgres(pool) => sqlx::query(
+        r#"
+        INSERT INTO runtime_states (purpose, token_hash, lookup_key, payload, expires_at)
+        VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP + ($5 * INTERVAL '1 millisecond'))
+        "#,
+      )
+      .bind(purpose)
+      .bind(token_hash(token))
+      .bind(lookup_key)
+      .bind(payload)
+      .bind(ttl_ms as f64)
+      .execute(pool)
+      .await
+      .map(|_| ()),
+      RuntimeDatabase::Sqlite(pool) => sqlx::query(
+        r#"
+        INSERT INTO runtime_states (purpose, token_hash, lookup_key, payload, expires_at, created_at, updated_at)
+        VALUES (?1, ?2, ?3, ?4, ?5 + ?6, ?5, ?5)
+        "#,
+      )
+      .bind(purpose)
+      .bind(token_hash(token))
+      .bind(lookup_key)
+      .bind(payload)
+      .bind(unix_ms_now())
+      .bind(ttl_ms)
+      .execute(pool)
+      .await
+      .map(|_| ()),
+    }
+    .map_err(|err| RuntimeError::database(context, err))
   }
 
   pub(super) async fn insert_payload_if_absent(
@@ -82,25 +135,42 @@ impl RuntimeStateRows {
     ttl_ms: i64,
     context: &str,
   ) -> Result<bool> {
-    let inserted = sqlx::query(
-      r#"
-      INSERT INTO runtime_states (purpose, token_hash, lookup_key, payload, expires_at)
-      VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP + ($5 * INTERVAL '1 millisecond'))
-      ON CONFLICT (purpose, token_hash) DO NOTHING
-      "#,
-    )
-    .bind(purpose)
-    .bind(token_hash(token))
-    .bind(lookup_key)
-    .bind(payload)
-    .bind(ttl_ms as f64)
-    .execute(&self.pool)
-    .await
-    .map_err(|err| RuntimeError::database(context, err))?
-    .rows_affected()
-      == 1;
-
-    Ok(inserted)
+    let rows_affected = match &self.database {
+      RuntimeDatabase::Postgres(pool) => sqlx::query(
+        r#"
+        INSERT INTO runtime_states (purpose, token_hash, lookup_key, payload, expires_at)
+        VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP + ($5 * INTERVAL '1 millisecond'))
+        ON CONFLICT (purpose, token_hash) DO NOTHING
+        "#,
+      )
+      .bind(purpose)
+      .bind(token_hash(token))
+      .bind(lookup_key)
+      .bind(payload)
+      .bind(ttl_ms as f64)
+      .execute(pool)
+      .await
+      .map(|result| result.rows_affected()),
+      RuntimeDatabase::Sqlite(pool) => sqlx::query(
+        r#"
+        INSERT INTO runtime_states (purpose, token_hash, lookup_key, payload, expires_at, created_at, updated_at)
+        VALUES (?1, ?2, ?3, ?4, ?5 + ?6, ?5, ?5)
+        ON CONFLICT (purpose, token_hash) DO NOTHING
+        "#,
+      )
+      .bind(purpose)
+      .bind(token_hash(token))
+      .bind(lookup_key)
+      .bind(payload)
+      .bind(unix_ms_now())
+      .bind(ttl_ms)
+      .execute(pool)
+      .await
+      .map(|result| result.rows_affected()),
+    }
+    .map_err(|err| RuntimeError::database(context, err))?;
+
+    Ok(rows_affected == 1)
   }
 
   pub(super) async fn upsert_payload_reset_attempts(
@@ -112,29 +182,40 @@ impl RuntimeStateRows {
     ttl_ms: i64,
     context: &str,
   ) -> Result<()> {
-    sqlx::query(
-      r#"
-      INSERT INTO runtime_states (purpose, token_hash, lookup_key, payload, attempts, consumed_at, expires_at)
-      VALUES ($1, $2, $3, $4, 0, NULL, CURRENT_TIMESTAMP + ($5 * INTERVAL '1 millisecond'))
-      ON CONFLICT (purpose, token_hash) DO UPDATE
-        SET lookup_key = EXCLUDED.lookup_key,
-            payload = EXCLUDED.payload,
-            attempts = 0,
-            consumed_at = NULL,
-            expires_at = EXCLUDED.expires_at,
-            updated_at = CURRENT_TIMESTAMP
-      "#,
-    )
-    .bind(purpose)
-    .bind(token_hash(token))
-    .bind(lookup_key)
-    .bind(payload)
-    .bind(ttl_ms as f64)
-    .execute(&self.pool)
-    .await
-    .map_err(|err| RuntimeError::database(context, err))?;
-
-    Ok(())
+    match &self.database {
+      RuntimeDatabase::Postgres(pool) => sqlx::query(
+        r#"
+        INSERT INTO runtime_states (purpose, token_hash, lookup_key, payload, attempts, consumed_at, expires_at)
+        VALUES ($1, $2, $3, $4, 0, NULL, CURRENT_TIMESTAMP + ($5 * INTERVAL '1 millisecond'))
+        ON CONFLICT (purpose, token_hash) DO UPDATE
+          SET lookup_key = EXCLUDED.lookup_key,
+              payload = EXCLUDED.payload,
+              attempts = 0,
+              consumed_at = NULL,
+              expires_at = EXCLUDED.expires_at,
+              updated_at = CURRENT_TIMESTAMP
+        "#,
+      )
+      .bind(purpose)
+      .bind(token_hash(token))
+      .bind(lookup_key)
+      .bind(payload)
+      .bind(ttl_ms as f64)
+      .execute(pool)
+      .await
+      .map(|_| ()),
+      RuntimeDatabase::Sqlite(pool) => sqlx::query(SQLITE_UPSERT_RESET_ATTEMPTS)
+        .bind(purpose)
+        .bind(token_hash(token))
+        .bind(lookup_key)
+        .bind(payload)
+        .bind(unix_ms_now())
+        .bind(ttl_ms)
+        .execute(pool)
+        .await
+        .map(|_| ()),
+    }
+    .map_err(|err| RuntimeError::database(context, err))
   }
 
   pub(super) async fn active_payload(
@@ -143,23 +224,12 @@ impl RuntimeStateRows {
     token: &str,
     context: &str,
   ) -> Result<Option<serde_json::Value>> {
-    let row = sqlx::query(
-      r#"
-      SELECT payload
-      FROM runtime_states
-      WHERE purpose = $1
-        AND token_hash = $2
-        AND consumed_at IS NULL
-        AND expires_at > CURRENT_TIMESTAMP
-      "#,
+    Ok(
+      self
+        .active_payload_with_expires(purpose, token, context)
+        .await?
+        .map(|row| row.payload),
     )
-    .bind(purpose)
-    .bind(token_hash(token))
-    .fetch_optional(&self.pool)
-    .await
-    .map_err(|err| RuntimeError::database(context, err))?;
-
-    Ok(row.map(|row| row.get::<serde_json::Value, _>("payload")))
   }
 
   pub(super) async fn active_payload_with_expires(
@@ -168,23 +238,40 @@ impl RuntimeStateRows {
     token: &str,
     context: &str,
   ) -> Result<Option<RuntimeStatePayloadRow>> {
-    let row = sqlx::query(
-      r#"
-      SELECT payload, (EXTRACT(EPOCH FROM expires_at) * 1000)::BIGINT AS expires_at_ms
-      FROM runtime_states
-      WHERE purpose = $1
-        AND token_hash = $2
-        AND consumed_at IS NULL
-        AND expires_at > CURRENT_TIMESTAMP
-      "#,
-    )
-    .bind(purpose)
-    .bind(token_hash(token))
-    .fetch_optional(&self.pool)
-    .await
-    .map_err(|err| RuntimeError::database(context, err))?;
-
-    Ok(row.map(payload_row))
+    match &self.database {
+      RuntimeDatabase::Postgres(pool) => sqlx::query(
+        r#"
+        SELECT payload, (EXTRACT(EPOCH FROM expires_at) * 1000)::BIGINT AS expires_at_ms
+        FROM runtime_states
+        WHERE purpose = $1
+          AND token_hash = $2
+          AND consumed_at IS NULL
+          AND expires_at > CURRENT_TIMESTAMP
+        "#,
+      )
+      .bind(purpose)
+      .bind(token_hash(token))
+      .fetch_optional(pool)
+      .await
+      .map(|row| row.map(payload_row)),
+      RuntimeDatabase::Sqlite(pool) => sqlx::query(
+        r#"
+        SELECT payload, expires_at AS expires_at_ms
+        FROM runtime_states
+        WHERE purpose = ?1
+          AND token_hash = ?2
+          AND consumed_at IS NULL
+          AND expires_at > ?3
+        "#,
+      )
+      .bind(purpose)
+      .bind(token_hash(token))
+      .bind(unix_ms_now())
+      .fetch_optional(pool)
+      .await
+      .map(|row| row.map(sqlite_payload_row)),
+    }
+    .map_err(|err| RuntimeError::database(context, err))
   }
 
   pub(super) async fn consume_payload(
@@ -193,25 +280,12 @@ impl RuntimeStateRows {
     token: &str,
     context: &str,
   ) -> Result<Option<serde_json::Value>> {
-    let row = sqlx::query(
-      r#"
-      UPDATE runtime_states
-      SET consumed_at = CURRENT_TIMESTAMP,
-          updated_at = CURRENT_TIMESTAMP
-      WHERE purpose = $1
-        AND token_hash = $2
-        AND consumed_at IS NULL
-        AND expires_at > CURRENT_TIMESTAMP
-      RETURNING payload
-      "#,
+    Ok(
+      self
+        .consume_payload_with_expires(purpose, token, context)
+        .await?
+        .map(|row| row.payload),
     )
-    .bind(purpose)
-    .bind(token_hash(token))
-    .fetch_optional(&self.pool)
-    .await
-    .map_err(|err| RuntimeError::database(context, err))?;
-
-    Ok(row.map(|row| row.get::<serde_json::Value, _>("payload")))
   }
 
   pub(super) async fn consume_payload_with_expires(
@@ -220,203 +294,602 @@ impl RuntimeStateRows {
     token: &str,
     context: &str,
   ) -> Result<Option<RuntimeStatePayloadRow>> {
-    let row = sqlx::query(
-      r#"
-      UPDATE runtime_states
-      SET consumed_at = CURRENT_TIMESTAMP,
-          updated_at = CURRENT_TIMESTAMP
-      WHERE purpose = $1
-        AND token_hash = $2
-        AND consumed_at IS NULL
-        AND expires_at > CURRENT_TIMESTAMP
-      RETURNING payload, (EXTRACT(EPOCH FROM expires_at) * 1000)::BIGINT AS expires_at_ms
-      "#,
-    )
-    .bind(purpose)
-    .bind(token_hash(token))
-    .fetch_optional(&self.pool)
-    .await
-    .map_err(|err| RuntimeError::database(context, err))?;
+    match &self.database {
+      RuntimeDatabase::Postgres(pool) => sqlx::query(
+        r#"
+        UPDATE runtime_states
+        SET consumed_at = CURRENT_TIMESTAMP,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE purpose = $1
+          AND token_hash = $2
+          AND consumed_at IS NULL
+          AND expires_at > CURRENT_TIMESTAMP
+        RETURNING payload, (EXTRACT(EPOCH FROM expires_at) * 1000)::BIGINT AS expires_at_ms
+        "#,
+      )
+      .bind(purpose)
+      .bind(token_hash(token))
+      .fetch_optional(pool)
+      .await
+      .map(|row| row.map(payload_row)),
+      RuntimeDatabase::Sqlite(pool) => sqlx::query(
+        r#"
+        UPDATE runtime_states
+        SET consumed_at = ?3,
+            updated_at = ?3
+        WHERE purpose = ?1
+          AND token_hash = ?2
+          AND consumed_at IS NULL
+          AND expires_at > ?3
+        RETURNING payload, expires_at AS expires_at_ms
+        "#,
+      )
+      .bind(purpose)
+      .bind(token_hash(token))
+      .bind(unix_ms_now())
+      .fetch_optional(pool)
+      .await
+      .map(|row| row.map(sqlite_payload_row)),
+    }
+    .map_err(|err| RuntimeError::database(context, err))
+  }
 
-    Ok(row.map(payload_row))
+  pub(super) async fn active_payloads_by_lookup_key(
+    &self,
+    purpose: &str,
+    lookup_key: &str,
+    context: &str,
+  ) -> Result<Vec<RuntimeStatePayloadRow>> {
+    match &self.database {
+      RuntimeDatabase::Postgres(pool) => sqlx::query(
+        r#"
+        SELECT payload, (EXTRACT(EPOCH FROM expires_at) * 1000)::BIGINT AS expires_at_ms
+        FROM runtime_states
+        WHERE purpose = $1
+          AND lookup_key = $2
+          AND consumed_at IS NULL
+          AND expires_at > CURRENT_TIMESTAMP
+        ORDER BY created_at ASC
+        "#,
+      )
+      .bind(purpose)
+      .bind(lookup_key)
+      .fetch_all(pool)
+      .await
+      .map(|rows| rows.into_iter().map(payload_row).collect()),
+      RuntimeDatabase::Sqlite(pool) => sqlx::query(
+        r#"
+        SELECT payload, expires_at AS expires_at_ms
+        FROM runtime_states
+        WHERE purpose = ?1
+          AND lookup_key = ?2
+          AND consumed_at IS NULL
+          AND expires_at > ?3
+        ORDER BY created_at ASC, rowid ASC
+        "#,
+      )
+      .bind(purpose)
+      .bind(lookup_key)
+      .bind(unix_ms_now())
+      .fetch_all(pool)
+      .await
+      .map(|rows| rows.into_iter().map(sqlite_payload_row).collect()),
+    }
+    .map_err(|err| RuntimeError::database(context, err))
+  }
+
+  pub(super) async fn count_active_by_lookup_key_in_tx(
+    &self,
+    tx: &mut RuntimeStateTx,
+    purpose: &str,
+    lookup_key: &str,
+    context: &str,
+  ) -> Result<i64> {
+    match tx {
+      RuntimeStateTx::Postgres(tx) => {
+        sqlx::query_scalar(
+          r#"
+          SELECT COUNT(*)
+          FROM runtime_states
+          WHERE purpose = $1
+            AND lookup_key = $2
+            AND consumed_at IS NULL
+            AND expires_at > clock_timestamp()
+          "#,
+        )
+        .bind(purpose)
+        .bind(lookup_key)
+        .fetch_one(&mut **tx)
+        .await
+      }
+      RuntimeStateTx::Sqlite(tx) => {
+        sqlx::query_scalar(
+          r#"
+          SELECT COUNT(*)
+          FROM runtime_states
+          WHERE purpose = ?1
+            AND lookup_key = ?2
+            AND consumed_at IS NULL
+            AND expires_at > ?3
+          "#,
+        )
+        .bind(purpose)
+        .bind(lookup_key)
+        .bind(unix_ms_now())
+        .fetch_one(&mut **tx)
+        .await
+      }
+    }
+    .map_err(|err| RuntimeError::database(context, err))
   }
 
   pub(super) async fn active_payload_with_expires_for_update_in_tx(
     &self,
-    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    tx: &mut RuntimeStateTx,
     purpose: &str,
     token: &str,
     context: &str,
   ) -> Result<Option<RuntimeStatePayloadRow>> {
-    let row = sqlx::query(
-      r#"
-      SELECT payload, (EXTRACT(EPOCH FROM expires_at) * 1000)::BIGINT AS expires_at_ms
-      FROM runtime_states
-      WHERE purpose = $1
-        AND token_hash = $2
-        AND consumed_at IS NULL
-        AND expires_at > clock_timestamp()
-      FOR UPDATE
-      "#,
-    )
-    .bind(purpose)
-    .bind(token_hash(token))
-    .fetch_optional(&mut **tx)
-    .await
-    .map_err(|err| RuntimeError::database(context, err))?;
-
-    Ok(row.map(payload_row))
+    match tx {
+      RuntimeStateTx::Postgres(tx) => sqlx::query(
+        r#"
+        SELECT payload, (EXTRACT(EPOCH FROM expires_at) * 1000)::BIGINT AS expires_at_ms
+        FROM runtime_states
+        WHERE purpose = $1
+          AND token_hash = $2
+          AND consumed_at IS NULL
+          AND expires_at > clock_timestamp()
+        FOR UPDATE
+        "#,
+      )
+      .bind(purpose)
+      .bind(token_hash(token))
+      .fetch_optional(&mut **tx)
+      .await
+      .map(|row| row.map(payload_row)),
+      RuntimeStateTx::Sqlite(tx) => sqlx::query(
+        r#"
+        SELECT payload, expires_at AS expires_at_ms
+        FROM runtime_states
+        WHERE purpose = ?1
+          AND token_hash = ?2
+          AND consumed_at IS NULL
+          AND expires_at > ?3
+        "#,
+      )
+      .bind(purpose)
+      .bind(token_hash(token))
+      .bind(unix_ms_now())
+      .fetch_optional(&mut **tx)
+      .await
+      .map(|row| row.map(sqlite_payload_row)),
+    }
+    .map_err(|err| RuntimeError::database(context, err))
   }
 
   pub(super) async fn unconsumed_row_for_update_in_tx(
     &self,
-    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    tx: &mut RuntimeStateTx,
     purpose: &str,
     token: &str,
     context: &str,
   ) -> Result<Option<RuntimeStateLockedRow>> {
-    let row = sqlx::query(
-      r#"
-      SELECT payload, attempts, expires_at
-      FROM runtime_states
-      WHERE purpose = $1
-        AND token_hash = $2
-        AND consumed_at IS NULL
-      FOR UPDATE
-      "#,
-    )
-    .bind(purpose)
-    .bind(token_hash(token))
-    .fetch_optional(&mut **tx)
-    .await
-    .map_err(|err| RuntimeError::database(context, err))?;
-
-    Ok(row.map(|row| RuntimeStateLockedRow {
-      payload: row.get("payload"),
-      attempts: row.get("attempts"),
-      expires_at: row.get("expires_at"),
-    }))
+    match tx {
+      RuntimeStateTx::Postgres(tx) => sqlx::query(
+        r#"
+        SELECT payload, attempts, expires_at
+        FROM runtime_states
+        WHERE purpose = $1
+          AND token_hash = $2
+          AND consumed_at IS NULL
+        FOR UPDATE
+        "#,
+      )
+      .bind(purpose)
+      .bind(token_hash(token))
+      .fetch_optional(&mut **tx)
+      .await
+      .map(|row| {
+        row.map(|row| RuntimeStateLockedRow {
+          payload: row.get("payload"),
+          attempts: row.get("attempts"),
+          expires_at: row.get("expires_at"),
+        })
+      }),
+      RuntimeStateTx::Sqlite(tx) => sqlx::query(
+        r#"
+        SELECT payload, attempts, expires_at
+        FROM runtime_states
+        WHERE purpose = ?1
+          AND token_hash = ?2
+          AND consumed_at IS NULL
+        "#,
+      )
+      .bind(purpose)
+      .bind(token_hash(token))
+      .fetch_optional(&mut **tx)
+      .await
+      .map(|row| {
+        row.map(|row| RuntimeStateLockedRow {
+          payload: row.get("payload"),
+          attempts: row.get("attempts"),
+          expires_at: chrono::DateTime::from_timestamp_millis(row.get("expires_at")).unwrap_or_default(),
+        })
+      }),
+    }
+    .map_err(|err| RuntimeError::database(context, err))
   }
 
   pub(super) async fn insert_payload_returning_expires_in_tx(
     &self,
-    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    tx: &mut RuntimeStateTx,
     input: RuntimeStateInsertPayload<'_>,
   ) -> Result<i64> {
-    let row = sqlx::query(
-      r#"
-      INSERT INTO runtime_states (purpose, token_hash, lookup_key, payload, expires_at)
-      VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP + ($5 * INTERVAL '1 millisecond'))
-      RETURNING (EXTRACT(EPOCH FROM expires_at) * 1000)::BIGINT AS expires_at_ms
-      "#,
-    )
-    .bind(input.purpose)
-    .bind(token_hash(input.token))
-    .bind(input.lookup_key)
-    .bind(input.payload)
-    .bind(input.ttl_ms as f64)
-    .fetch_one(&mut **tx)
-    .await
-    .map_err(|err| RuntimeError::database(input.context, err))?;
-
-    Ok(row.get::<i64, _>("expires_at_ms"))
+    match tx {
+      RuntimeStateTx::Postgres(tx) => {
+        sqlx::query_scalar(
+          r#"
+        INSERT INTO runtime_states (purpose, token_hash, lookup_key, payload, expires_at)
+        VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP + ($5 * INTERVAL '1 millisecond'))
+        RETURNING (EXTRACT(EPOCH FROM expires_at) * 1000)::BIGINT AS expires_at_ms
+        "#,
+        )
+        .bind(input.purpose)
+        .bind(token_hash(input.token))
+        .bind(input.lookup_key)
+        .bind(input.payload)
+        .bind(input.ttl_ms as f64)
+        .fetch_one(&mut **tx)
+        .await
+      }
+      RuntimeStateTx::Sqlite(tx) => {
+        sqlx::query_scalar(
+          r#"
+        INSERT INTO runtime_states (purpose, token_hash, lookup_key, payload, expires_at, created_at, updated_at)
+        VALUES (?1, ?2, ?3, ?4, ?5 + ?6, ?5, ?5)
+        RETURNING expires_at AS expires_at_ms
+        "#,
+        )
+        .bind(input.purpose)
+        .bind(token_hash(input.token))
+        .bind(input.lookup_key)
+        .bind(input.payload)
+        .bind(unix_ms_now())
+        .bind(input.ttl_ms)
+        .fetch_one(&mut **tx)
+        .await
+      }
+    }
+    .map_err(|err| RuntimeError::database(input.context, err))
   }
 
   pub(super) async fn upsert_expired_or_consumed_payload_returning_expires_in_tx(
     &self,
-    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    tx: &mut RuntimeStateTx,
     input: RuntimeStateInsertPayload<'_>,
   ) -> Result<Option<i64>> {
-    let row = sqlx::query(
-      r#"
-      INSERT INTO runtime_states (purpose, token_hash, lookup_key, payload, expires_at)
-      VALUES ($1, $2, $3, $4, clock_timestamp() + ($5 * INTERVAL '1 millisecond'))
-      ON CONFLICT (purpose, token_hash) DO UPDATE
-        SET lookup_key = EXCLUDED.lookup_key,
-            payload = EXCLUDED.payload,
-            attempts = 0,
-            consumed_at = NULL,
-            expires_at = clock_timestamp() + ($5 * INTERVAL '1 millisecond')
-        WHERE runtime_states.consumed_at IS NOT NULL
-           OR runtime_states.expires_at <= clock_timestamp()
-      RETURNING (EXTRACT(EPOCH FROM expires_at) * 1000)::BIGINT AS expires_at_ms
-      "#,
-    )
-    .bind(input.purpose)
-    .bind(token_hash(input.token))
-    .bind(input.lookup_key)
-    .bind(input.payload)
-    .bind(input.ttl_ms as f64)
-    .fetch_optional(&mut **tx)
-    .await
-    .map_err(|err| RuntimeError::database(input.context, err))?;
-
-    Ok(row.map(|row| row.get::<i64, _>("expires_at_ms")))
+    match tx {
+      RuntimeStateTx::Postgres(tx) => {
+        sqlx::query_scalar(
+          r#"
+        INSERT INTO runtime_states (purpose, token_hash, lookup_key, payload, expires_at)
+        VALUES ($1, $2, $3, $4, clock_timestamp() + ($5 * INTERVAL '1 millisecond'))
+        ON CONFLICT (purpose, token_hash) DO UPDATE
+          SET lookup_key = EXCLUDED.lookup_key,
+              payload = EXCLUDED.payload,
+              attempts = 0,
+              consumed_at = NULL,
+              expires_at = clock_timestamp() + ($5 * INTERVAL '1 millisecond')
+          WHERE runtime_states.consumed_at IS NOT NULL
+             OR runtime_states.expires_at <= clock_timestamp()
+        RETURNING (EXTRACT(EPOCH FROM expires_at) * 1000)::BIGINT AS expires_at_ms
+        "#,
+        )
+        .bind(input.purpose)
+        .bind(token_hash(input.token))
+        .bind(input.lookup_key)
+        .bind(input.payload)
+        .bind(input.ttl_ms as f64)
+        .fetch_optional(&mut **tx)
+        .await
+      }
+      RuntimeStateTx::Sqlite(tx) => {
+        sqlx::query_scalar(
+          r#"
+        INSERT INTO runtime_states (purpose, token_hash, lookup_key, payload, expires_at, created_at, updated_at)
+        VALUES (?1, ?2, ?3, ?4, ?5 + ?6, ?5, ?5)
+        ON CONFLICT (purpose, token_hash) DO UPDATE
+          SET lookup_key = excluded.lookup_key,
+              payload = excluded.payload,
+              attempts = 0,
+              consumed_at = NULL,
+              expires_at = excluded.expires_at,
+              updated_at = excluded.updated_at
+          WHERE runtime_states.consumed_at IS NOT NULL
+             OR runtime_states.expires_at <= ?5
+        RETURNING expires_at AS expires_at_ms
+        "#,
+        )
+        .bind(input.purpose)
+        .bind(token_hash(input.token))
+        .bind(input.lookup_key)
+        .bind(input.payload)
+        .bind(unix_ms_now())
+        .bind(input.ttl_ms)
+        .fetch_optional(&mut **tx)
+        .await
+      }
+    }
+    .map_err(|err| RuntimeError::database(input.context, err))
   }
 
   pub(super) async fn update_attempts_in_tx(
     &self,
-    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    tx: &mut RuntimeStateTx,
     purpose: &str,
     token: &str,
     attempts: i32,
     context: &str,
   ) -> Result<()> {
-    sqlx::query(
-      r#"
-      UPDATE runtime_states
-      SET attempts = $3,
-          updated_at = CURRENT_TIMESTAMP
-      WHERE purpose = $1
-        AND token_hash = $2
-      "#,
-    )
-    .bind(purpose)
-    .bind(token_hash(token))
-    .bind(attempts)
-    .execute(&mut **tx)
-    .await
-    .map_err(|err| RuntimeError::database(context, err))?;
+    match tx {
+      RuntimeStateTx::Postgres(tx) => sqlx::query(
+        r#"
+        UPDATE runtime_states
+        SET attempts = $3,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE purpose = $1
+          AND token_hash = $2
+        "#,
+      )
+      .bind(purpose)
+      .bind(token_hash(token))
+      .bind(attempts)
+      .execute(&mut **tx)
+      .await
+      .map(|_| ()),
+      RuntimeStateTx::Sqlite(tx) => sqlx::query(
+        r#"
+        UPDATE runtime_states
+        SET attempts = ?3,
+            updated_at = ?4
+        WHERE purpose = ?1
+          AND token_hash = ?2
+        "#,
+      )
+      .bind(purpose)
+      .bind(token_hash(token))
+      .bind(attempts)
+      .bind(unix_ms_now())
+      .execute(&mut **tx)
+      .await
+      .map(|_| ()),
+    }
+    .map_err(|err| RuntimeError::database(context, err))
+  }
 
-    Ok(())
+  pub(super) async fn update_payload_in_tx(
+    &self,
+    tx: &mut RuntimeStateTx,
+    purpose: &str,
+    token: &str,
+    payload: &serde_json::Value,
+    context: &str,
+  ) -> Result<()> {
+    match tx {
+      RuntimeStateTx::Postgres(tx) => sqlx::query(
+        r#"
+        UPDATE runtime_states
+        SET payload = $3,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE purpose = $1
+          AND token_hash = $2
+        "#,
+      )
+      .bind(purpose)
+      .bind(token_hash(token))
+      .bind(payload)
+      .execute(&mut **tx)
+      .await
+      .map(|_| ()),
+      RuntimeStateTx::Sqlite(tx) => sqlx::query(
+        r#"
+        UPDATE runtime_states
+        SET payload = ?3,
+            updated_at = ?4
+        WHERE purpose = ?1
+          AND token_hash = ?2
+        "#,
+      )
+      .bind(purpose)
+      .bind(token_hash(token))
+      .bind(payload)
+      .bind(unix_ms_now())
+      .execute(&mut **tx)
+      .await
+      .map(|_| ()),
+    }
+    .map_err(|err| RuntimeError::database(context, err))
   }
 
-  pub(super) async fn delete_by_key_in_tx(
+  pub(super) async fn update_payload_and_attempts_in_tx(
     &self,
-    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    tx: &mut RuntimeStateTx,
     purpose: &str,
     token: &str,
+    payload: &serde_json::Value,
+    attempts: i32,
     context: &str,
   ) -> Result<()> {
-    sqlx::query("DELETE FROM runtime_states WHERE purpose = $1 AND token_hash = $2")
+    match tx {
+      RuntimeStateTx::Postgres(tx) => sqlx::query(
+        r#"
+        UPDATE runtime_states
+        SET payload = $3,
+            attempts = $4,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE purpose = $1
+          AND token_hash = $2
+        "#,
+      )
+      .bind(purpose)
+      .bind(token_hash(token))
+      .bind(payload)
+      .bind(attempts)
+      .execute(&mut **tx)
+      .await
+      .map(|_| ()),
+      RuntimeStateTx::Sqlite(tx) => sqlx::query(
+        r#"
+        UPDATE runtime_states
+        SET payload = ?3,
+            attempts = ?4,
+            updated_at = ?5
+        WHERE purpose = ?1
+          AND token_hash = ?2
+        "#,
+      )
       .bind(purpose)
       .bind(token_hash(token))
+      .bind(payload)
+      .bind(attempts)
+      .bind(unix_ms_now())
       .execute(&mut **tx)
       .await
-      .map_err(|err| RuntimeError::database(context, err))?;
+      .map(|_| ()),
+    }
+    .map_err(|err| RuntimeError::database(context, err))
+  }
 
-    Ok(())
+  pub(super) async fn upsert_payload_reset_attempts_in_tx(
+    &self,
+    tx: &mut RuntimeStateTx,
+    input: RuntimeStateInsertPayload<'_>,
+  ) -> Result<()> {
+    match tx {
+      RuntimeStateTx::Postgres(tx) => sqlx::query(
+        r#"
+        INSERT INTO runtime_states (purpose, token_hash, lookup_key, payload, attempts, consumed_at, expires_at)
+        VALUES ($1, $2, $3, $4, 0, NULL, CURRENT_TIMESTAMP + ($5 * INTERVAL '1 millisecond'))
+        ON CONFLICT (purpose, token_hash) DO UPDATE
+          SET lookup_key = EXCLUDED.lookup_key,
+              payload = EXCLUDED.payload,
+              attempts = 0,
+              consumed_at = NULL,
+              expires_at = EXCLUDED.expires_at,
+              updated_at = CURRENT_TIMESTAMP
+        "#,
+      )
+      .bind(input.purpose)
+      .bind(token_hash(input.token))
+      .bind(input.lookup_key)
+      .bind(input.payload)
+      .bind(input.ttl_ms as f64)
+      .execute(&mut **tx)
+      .await
+      .map(|_| ()),
+      RuntimeStateTx::Sqlite(tx) => sqlx::query(SQLITE_UPSERT_RESET_ATTEMPTS)
+        .bind(input.purpose)
+        .bind(token_hash(input.token))
+        .bind(input.lookup_key)
+        .bind(input.payload)
+        .bind(unix_ms_now())
+        .bind(input.ttl_ms)
+        .execute(&mut **tx)
+        .await
+        .map(|_| ()),
+    }
+    .map_err(|err| RuntimeError::database(input.context, err))
+  }
+
+  pub(super) async fn delete_by_lookup_key_in_tx(
+    &self,
+    tx: &mut RuntimeStateTx,
+    purpose: &str,
+    lookup_key: &str,
+    context: &str,
+  ) -> Result<i64> {
+    let rows_affected = match tx {
+      RuntimeStateTx::Postgres(tx) => sqlx::query("DELETE FROM runtime_states WHERE purpose = $1 AND lookup_key = $2")
+        .bind(purpose)
+        .bind(lookup_key)
+        .execute(&mut **tx)
+        .await
+        .map(|result| result.rows_affected()),
+      RuntimeStateTx::Sqlite(tx) => sqlx::query("DELETE FROM runtime_states WHERE purpose = ?1 AND lookup_key = ?2")
+        .bind(purpose)
+        .bind(lookup_key)
+        .execute(&mut **tx)
+        .await
+        .map(|result| result.rows_affected()),
+    }
+    .map_err(|err| RuntimeError::database(context, err))?;
+
+    Ok(rows_affected as i64)
+  }
+
+  pub(super) async fn delete_by_key_in_tx(
+    &self,
+    tx: &mut RuntimeStateTx,
+    purpose: &str,
+    token: &str,
+    context: &str,
+  ) -> Result<()> {
+    match tx {
+      RuntimeStateTx::Postgres(tx) => sqlx::query("DELETE FROM runtime_states WHERE purpose = $1 AND token_hash = $2")
+        .bind(purpose)
+        .bind(token_hash(token))
+        .execute(&mut **tx)
+        .await
+        .map(|_| ()),
+      RuntimeStateTx::Sqlite(tx) => sqlx::query("DELETE FROM runtime_states WHERE purpose = ?1 AND token_hash = ?2")
+        .bind(purpose)
+        .bind(token_hash(token))
+        .execute(&mut **tx)
+        .await
+        .map(|_| ()),
+    }
+    .map_err(|err| RuntimeError::database(context, err))
   }
 
   pub(super) async fn cleanup_expired_or_consumed(&self, limit: i64, context: &str) -> Result<i64> {
-    let result = sqlx::query(
-      r#"
-      DELETE FROM runtime_states
-      WHERE (purpose, token_hash) IN (
-        SELECT purpose, token_hash FROM runtime_states
-        WHERE expires_at <= CURRENT_TIMESTAMP
-           OR consumed_at IS NOT NULL
-        ORDER BY expires_at ASC
-        LIMIT $1
+    let rows_affected = match &self.database {
+      RuntimeDatabase::Postgres(pool) => sqlx::query(
+        r#"
+        DELETE FROM runtime_states
+        WHERE (purpose, token_hash) IN (
+          SELECT purpose, token_hash FROM runtime_states
+          WHERE expires_at <= CURRENT_TIMESTAMP
+             OR consumed_at IS NOT NULL
+          ORDER BY expires_at ASC
+          LIMIT $1
+        )
+        "#,
       )
-      "#,
-    )
-    .bind(limit)
-    .execute(&self.pool)
-    .await
+      .bind(limit)
+      .execute(pool)
+      .await
+      .map(|result| result.rows_affected()),
+      RuntimeDatabase::Sqlite(pool) => sqlx::query(
+        r#"
+        DELETE FROM runtime_states
+        WHERE rowid IN (
+          SELECT rowid FROM runtime_states
+          WHERE expires_at <= ?1
+             OR consumed_at IS NOT NULL
+          ORDER BY expires_at ASC
+          LIMIT ?2
+        )
+        "#,
+      )
+      .bind(unix_ms_now())
+      .bind(limit)
+      .execute(pool)
+      .await
+      .map(|result| result.rows_affected()),
+    }
     .map_err(|err| RuntimeError::database(context, err))?;
 
-    Ok(result.rows_affected() as i64)
+    Ok(rows_affected as i64)
   }
 
   pub(super) async fn cleanup_expired_by_purpose_prefix(
@@ -425,29 +898,70 @@ impl RuntimeStateRows {
     limit: i64,
     context: &str,
   ) -> Result<i64> {
-    let result = sqlx::query(
-      r#"
-      DELETE FROM runtime_states
-      WHERE (purpose, token_hash) IN (
-        SELECT purpose, token_hash FROM runtime_states
-        WHERE purpose LIKE $1
-          AND expires_at <= CURRENT_TIMESTAMP
-        ORDER BY expires_at ASC
-        LIMIT $2
+    let rows_affected = match &self.database {
+      RuntimeDatabase::Postgres(pool) => sqlx::query(
+        r#"
+        DELETE FROM runtime_states
+        WHERE (purpose, token_hash) IN (
+          SELECT purpose, token_hash FROM runtime_states
+          WHERE purpose LIKE $1
+            AND expires_at <= CURRENT_TIMESTAMP
+          ORDER BY expires_at ASC
+          LIMIT $2
+        )
+        "#,
       )
-      "#,
-    )
-    .bind(format!("{purpose_prefix}%"))
-    .bind(limit)
-    .execute(&self.pool)
-    .await
+      .bind(format!("{purpose_prefix}%"))
+      .bind(limit)
+      .execute(pool)
+      .await
+      .map(|result| result.rows_affected()),
+      RuntimeDatabase::Sqlite(pool) => sqlx::query(
+        r#"
+        DELETE FROM runtime_states
+        WHERE rowid IN (
+          SELECT rowid FROM runtime_states
+          WHERE purpose LIKE ?1
+            AND expires_at <= ?2
+          ORDER BY expires_at ASC
+          LIMIT ?3
+        )
+        "#,
+      )
+      .bind(format!("{purpose_prefix}%"))
+      .bind(unix_ms_now())
+      .bind(limit)
+      .execute(pool)
+      .await
+      .map(|result| result.rows_affected()),
+    }
     .map_err(|err| RuntimeError::database(context, err))?;
 
-    Ok(result.rows_affected() as i64)
+    Ok(rows_affected as i64)
+  }
+}
+
+const SQLITE_UPSERT_RESET_ATTEMPTS: &str = r#"
+  INSERT INTO runtime_states
+    (purpose, token_hash, lookup_key, payload, attempts, consumed_at, expires_at, created_at, updated_at)
+  VALUES (?1, ?2, ?3, ?4, 0, NULL, ?5 + ?6, ?5, ?5)
+  ON CONFLICT (purpose, token_hash) DO UPDATE
+    SET lookup_key = excluded.lookup_key,
+        payload = excluded.payload,
+        attempts = 0,
+        consumed_at = NULL,
+        expires_at = excluded.expires_at,
+        updated_at = excluded.updated_at
+"#;
+
+pub(super) fn payload_row(row: PgRow) -> RuntimeStatePayloadRow {
+  RuntimeStatePayloadRow {
+    payload: row.get("payload"),
+    expires_at_ms: row.get("expires_at_ms"),
   }
 }
 
-fn payload_row(row: sqlx::postgres::PgRow) -> RuntimeStatePayloadRow {
+pub(super) fn sqlite_payload_row(row: SqliteRow) -> RuntimeStatePayloadRow {
   RuntimeStatePayloadRow {
     payload: row.get("payload"),
     expires_at_ms: row.get("expires_at_ms"),
@@ -1,14 +1,18 @@
 use super::{
-  Result, RuntimeError, RuntimeWorkspaceInviteLinkRecord, WORKSPACE_INVITE_LINK_ID_PURPOSE,
-  WORKSPACE_INVITE_LINK_WORKSPACE_PURPOSE,
-  dto::{RuntimeStateInsertPayload, RuntimeStatePayloadRow, RuntimeStateRows},
+  MAX_WORKSPACE_INVITE_LINKS, Result, RuntimeError, RuntimeWorkspaceInviteLinkInput, RuntimeWorkspaceInviteLinkRecord,
+  RuntimeWorkspaceInviteLinkRedeemResult, WORKSPACE_INVITE_LINK_ID_PURPOSE, WORKSPACE_INVITE_LINK_WORKSPACE_PURPOSE,
+  dto::{RuntimeStateInsertPayload, RuntimeStatePayloadRow, RuntimeStateRows, RuntimeStateTx},
 };
 
 pub(super) async fn get_by_workspace(
   rows: &RuntimeStateRows,
   workspace_id: String,
 ) -> Result<Option<RuntimeWorkspaceInviteLinkRecord>> {
-  get_by_key(rows, WORKSPACE_INVITE_LINK_WORKSPACE_PURPOSE, &workspace_id).await
+  let Some(default_link) = get_by_key(rows, WORKSPACE_INVITE_LINK_WORKSPACE_PURPOSE, &workspace_id).await? else {
+    return Ok(None);
+  };
+  // Use counts live on the id row; the workspace row only points at it.
+  get_by_key(rows, WORKSPACE_INVITE_LINK_ID_PURPOSE, &default_link.invite_id).await
 }
 
 pub(super) async fn get_by_invite_id(
@@ -18,6 +22,22 @@ pub(super) async fn get_by_invite_id(
   get_by_key(rows, WORKSPACE_INVITE_LINK_ID_PURPOSE, &invite_id).await
 }
 
+pub(super) async fn list(
+  rows: &RuntimeStateRows,
+  workspace_id: String,
+) -> Result<Vec<RuntimeWorkspaceInviteLinkRecord>> {
+  rows
+    .active_payloads_by_lookup_key(
+      WORKSPACE_INVITE_LINK_ID_PURPOSE,
+      &workspace_id,
+      "RuntimeState workspace invite link list",
+    )
+    .await?
+    .into_iter()
+    .map(record_from_row)
+    .collect()
+}
+
 pub(super) async fn create(
   rows: &RuntimeStateRows,
   workspace_id: String,
@@ -25,88 +45,155 @@ pub(super) async fn create(
   inviter_user_id: String,
   ttl_ms: i64,
 ) -> Result<RuntimeWorkspaceInviteLinkRecord> {
-  if ttl_ms <= 0 {
-    return Err(RuntimeError::invalid_input(
-      "workspace invite link ttl must be positive",
-    ));
-  }
+  let input = RuntimeWorkspaceInviteLinkInput {
+    workspace_id,
+    invite_id,
+    inviter_user_id,
+    role: None,
+    max_uses: None,
+    email_domain: None,
+    ttl_ms,
+  };
+  let payload = link_payload(&input)?;
 
   let mut tx = rows.begin("RuntimeState workspace invite link").await?;
-  sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended($1, 0))")
-    .bind(&workspace_id)
-    .execute(&mut *tx)
-    .await
-    .map_err(|err| RuntimeError::database("RuntimeState workspace invite link active lock failed", err))?;
+  lock_workspace_in_tx(&mut tx, &input.workspace_id).await?;
 
-  if let Some(existing) =
-    get_by_key_in_tx(rows, &mut tx, WORKSPACE_INVITE_LINK_WORKSPACE_PURPOSE, &workspace_id).await?
+  if let Some(existing) = get_by_key_in_tx(
+    rows,
+    &mut tx,
+    WORKSPACE_INVITE_LINK_WORKSPACE_PURPOSE,
+    &input.workspace_id,
+  )
+  .await?
   {
-    tx.commit()
-      .await
-      .map_err(|err| RuntimeError::database("RuntimeState workspace invite link transaction commit failed", err))?;
-    return Ok(existing);
+    if let Some(existing) =
+      get_by_key_in_tx(rows, &mut tx, WORKSPACE_INVITE_LINK_ID_PURPOSE, &existing.invite_id).await?
+    {
+      commit(tx).await?;
+      return Ok(existing);
+    }
+    // The default link was revoked by id; drop the dangling pointer.
+    rows
+      .delete_by_key_in_tx(
+        &mut tx,
+        WORKSPACE_INVITE_LINK_WORKSPACE_PURPOSE,
+        &input.workspace_id,
+        "RuntimeState workspace invite link create",
+      )
+      .await?;
   }
+  ensure_capacity_in_tx(rows, &mut tx, &input.workspace_id).await?;
 
-  let payload = serde_json::json!({
-    "workspaceId": workspace_id,
-    "inviteId": invite_id,
-    "inviterUserId": inviter_user_id,
-  });
-
-  let Some(expires_at_ms) = rows
+  let upserted = rows
     .upsert_expired_or_consumed_payload_returning_expires_in_tx(
       &mut tx,
       RuntimeStateInsertPayload {
         purpose: WORKSPACE_INVITE_LINK_WORKSPACE_PURPOSE,
-        token: &workspace_id,
-        lookup_key: &workspace_id,
+        token: &input.workspace_id,
+        lookup_key: &input.workspace_id,
         payload: &payload,
         ttl_ms,
         context: "RuntimeState workspace invite link create",
       },
     )
-    .await?
-  else {
-    let existing = get_by_key_in_tx(rows, &mut tx, WORKSPACE_INVITE_LINK_WORKSPACE_PURPOSE, &workspace_id).await?;
-    tx.commit()
-      .await
-      .map_err(|err| RuntimeError::database("RuntimeState workspace invite link transaction commit failed", err))?;
+    .await?;
+  if upserted.is_none() {
+    let existing = get_by_key_in_tx(
+      rows,
+      &mut tx,
+      WORKSPACE_INVITE_LINK_WORKSPACE_PURPOSE,
+      &input.workspace_id,
+    )
+    .await?;
+    commit(tx).await?;
     return existing
       .ok_or_else(|| RuntimeError::invalid_state("RuntimeState workspace invite link active conflict missing row"));
+  }
+  let record = insert_link_in_tx(rows, &mut tx, &input, &payload).await?;
+  commit(tx).await?;
+
+  Ok(record)
+}
+
+/// Creates an additional invite link next to the workspace's default one.
+pub(super) async fn add(
+  rows: &RuntimeStateRows,
+  input: RuntimeWorkspaceInviteLinkInput,
+) -> Result<RuntimeWorkspaceInviteLinkRecord> {
+  let payload = link_payload(&input)?;
+
+  let mut tx = rows.begin("RuntimeState workspace invite link").await?;
+  lock_workspace_in_tx(&mut tx, &input.workspace_id).await?;
+  ensure_capacity_in_tx(rows, &mut tx, &input.workspace_id).await?;
+  let record = insert_link_in_tx(rows, &mut tx, &input, &payload).await?;
+  commit(tx).await?;
+
+  Ok(record)
+}
+
+/// Counts one use of the link. Fails with `not_found`, `domain_mismatch` or
+/// `exhausted` without consuming a use.
+pub(super) async fn redeem(
+  rows: &RuntimeStateRows,
+  invite_id: String,
+  email: Option<String>,
+) -> Result<RuntimeWorkspaceInviteLinkRedeemResult> {
+  let mut tx = rows.begin("RuntimeState workspace invite link redeem").await?;
+  let Some(mut row) = rows
+    .active_payload_with_expires_for_update_in_tx(
+      &mut tx,
+      WORKSPACE_INVITE_LINK_ID_PURPOSE,
+      &invite_id,
+      "RuntimeState workspace invite link redeem",
+    )
+    .await?
+  else {
+    commit(tx).await?;
+    return Ok(RuntimeWorkspaceInviteLinkRedeemResult::fail("not_found"));
   };
+  let mut record = record_from_row(RuntimeStatePayloadRow {
+    payload: row.payload.clone(),
+    expires_at_ms: row.expires_at_ms,
+  })?;
+
+  if let Some(domain) = record.email_domain.as_deref() {
+    let email_domain = email
+      .as_deref()
+      .and_then(|email| email.trim().rsplit_once('@'))
+      .map(|(_, domain)| domain.to_ascii_lowercase());
+    if email_domain.as_deref() != Some(domain) {
+      commit(tx).await?;
+      return Ok(RuntimeWorkspaceInviteLinkRedeemResult::fail("domain_mismatch"));
+    }
+  }
+  if record.max_uses.is_some_and(|max_uses| record.uses >= max_uses) {
+    commit(tx).await?;
+    return Ok(RuntimeWorkspaceInviteLinkRedeemResult::fail("exhausted"));
+  }
+
+  record.uses += 1;
+  row.payload["uses"] = serde_json::json!(record.uses);
   rows
-    .insert_payload_returning_expires_in_tx(
+    .update_payload_in_tx(
       &mut tx,
-      RuntimeStateInsertPayload {
-        purpose: WORKSPACE_INVITE_LINK_ID_PURPOSE,
-        token: &invite_id,
-        lookup_key: &invite_id,
-        payload: &payload,
-        ttl_ms,
-        context: "RuntimeState workspace invite link create",
-      },
+      WORKSPACE_INVITE_LINK_ID_PURPOSE,
+      &invite_id,
+      &row.payload,
+      "RuntimeState workspace invite link redeem",
     )
     .await?;
+  commit(tx).await?;
 
-  tx.commit()
-    .await
-    .map_err(|err| RuntimeError::database("RuntimeState workspace invite link transaction commit failed", err))?;
-
-  Ok(RuntimeWorkspaceInviteLinkRecord {
-    workspace_id,
-    invite_id,
-    inviter_user_id,
-    expires_at_ms,
-  })
+  Ok(RuntimeWorkspaceInviteLinkRedeemResult::ok(record))
 }
 
 pub(super) async fn revoke(rows: &RuntimeStateRows, workspace_id: String) -> Result<bool> {
   let mut tx = rows.begin("RuntimeState workspace invite link").await?;
+  lock_workspace_in_tx(&mut tx, &workspace_id).await?;
   let existing = get_by_key_in_tx(rows, &mut tx, WORKSPACE_INVITE_LINK_WORKSPACE_PURPOSE, &workspace_id).await?;
   let Some(existing) = existing else {
-    tx.commit()
-      .await
-      .map_err(|err| RuntimeError::database("RuntimeState workspace invite link transaction commit failed", err))?;
+    commit(tx).await?;
     return Ok(false);
   };
 
@@ -127,11 +214,147 @@ pub(super) async fn revoke(rows: &RuntimeStateRows, workspace_id: String) -> Res
     )
     .await?;
 
+  commit(tx).await?;
+
+  Ok(true)
+}
+
+pub(super) async fn revoke_by_id(rows: &RuntimeStateRows, invite_id: String) -> Result<bool> {
+  let Some(link) = get_by_key(rows, WORKSPACE_INVITE_LINK_ID_PURPOSE, &invite_id).await? else {
+    return Ok(false);
+  };
+
+  let mut tx = rows.begin("RuntimeState workspace invite link").await?;
+  lock_workspace_in_tx(&mut tx, &link.workspace_id).await?;
+  if get_by_key_in_tx(rows, &mut tx, WORKSPACE_INVITE_LINK_ID_PURPOSE, &invite_id)
+    .await?
+    .is_none()
+  {
+    commit(tx).await?;
+    return Ok(false);
+  }
+
+  rows
+    .delete_by_key_in_tx(
+      &mut tx,
+      WORKSPACE_INVITE_LINK_ID_PURPOSE,
+      &invite_id,
+      "RuntimeState workspace invite link revoke",
+    )
+    .await?;
+  let default_link = get_by_key_in_tx(
+    rows,
+    &mut tx,
+    WORKSPACE_INVITE_LINK_WORKSPACE_PURPOSE,
+    &link.workspace_id,
+  )
+  .await?;
+  if default_link.is_some_and(|default_link| default_link.invite_id == invite_id) {
+    rows
+      .delete_by_key_in_tx(
+        &mut tx,
+        WORKSPACE_INVITE_LINK_WORKSPACE_PURPOSE,
+        &link.workspace_id,
+        "RuntimeState workspace invite link revoke",
+      )
+      .await?;
+  }
+  commit(tx).await?;
+
+  Ok(true)
+}
+
+async fn lock_workspace_in_tx(tx: &mut RuntimeStateTx, workspace_id: &str) -> Result<()> {
+  tx.lock(workspace_id, "RuntimeState workspace invite link active lock failed")
+    .await
+}
+
+async fn ensure_capacity_in_tx(rows: &RuntimeStateRows, tx: &mut RuntimeStateTx, workspace_id: &str) -> Result<()> {
+  let active = rows
+    .count_active_by_lookup_key_in_tx(
+      tx,
+      WORKSPACE_INVITE_LINK_ID_PURPOSE,
+      workspace_id,
+      "RuntimeState workspace invite link count",
+    )
+    .await?;
+  if active >= MAX_WORKSPACE_INVITE_LINKS {
+    return Err(RuntimeError::invalid_input(format!(
+      "workspace invite links are limited to {MAX_WORKSPACE_INVITE_LINKS} per workspace"
+    )));
+  }
+  Ok(())
+}
+
+async fn insert_link_in_tx(
+  rows: &RuntimeStateRows,
+  tx: &mut RuntimeStateTx,
+  input: &RuntimeWorkspaceInviteLinkInput,
+  payload: &serde_json::Value,
+) -> Result<RuntimeWorkspaceInviteLinkRecord> {
+  let expires_at_ms = rows
+    .insert_payload_returning_expires_in_tx(
+      tx,
+      RuntimeStateInsertPayload {
+        purpose: WORKSPACE_INVITE_LINK_ID_PURPOSE,
+        token: &input.invite_id,
+        lookup_key: &input.workspace_id,
+        payload,
+        ttl_ms: input.ttl_ms,
+        context: "RuntimeState workspace invite link create",
+      },
+    )
+    .await?;
+  record_from_row(RuntimeStatePayloadRow {
+    payload: payload.clone(),
+    expires_at_ms,
+  })
+}
+
+async fn commit(tx: RuntimeStateTx) -> Result<()> {
   tx.commit()
     .await
-    .map_err(|err| RuntimeError::database("RuntimeState workspace invite link transaction commit failed", err))?;
+    .map_err(|err| RuntimeError::database("RuntimeState workspace invite link transaction commit failed", err))
+}
 
-  Ok(true)
+fn link_payload(input: &RuntimeWorkspaceInviteLinkInput) -> Result<serde_json::Value> {
+  if input.ttl_ms <= 0 {
+    return Err(RuntimeError::invalid_input(
+      "workspace invite link ttl must be positive",
+    ));
+  }
+  if input.max_uses.is_some_and(|max_uses| max_uses <= 0) {
+    return Err(RuntimeError::invalid_input(
+      "workspace invite link max uses must be positive",
+    ));
+  }
+  let role = input.role.as_deref().map(str::trim);
+  if role.is_some_and(str::is_empty) {
+    return Err(RuntimeError::invalid_input(
+      "workspace invite link role must not be empty",
+    ));
+  }
+  let email_domain = input.email_domain.as_deref().map(normalize_email_domain).transpose()?;
+
+  Ok(serde_json::json!({
+    "workspaceId": input.workspace_id,
+    "inviteId": input.invite_id,
+    "inviterUserId": input.inviter_user_id,
+    "role": role,
+    "maxUses": input.max_uses,
+    "uses": 0,
+    "emailDomain": email_domain,
+  }))
+}
+
+fn normalize_email_domain(domain: &str) -> Result<String> {
+  let domain = domain.trim().trim_start_matches('@').to_ascii_lowercase();
+  if domain.is_empty() || domain.contains('@') {
+    return Err(RuntimeError::invalid_input(
+      "workspace invite link email domain is invalid",
+    ));
+  }
+  Ok(domain)
 }
 
 async fn get_by_key(
@@ -148,7 +371,7 @@ async fn get_by_key(
 
 async fn get_by_key_in_tx(
   rows: &RuntimeStateRows,
-  tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+  tx: &mut RuntimeStateTx,
   purpose: &str,
   key: &str,
 ) -> Result<Option<RuntimeWorkspaceInviteLinkRecord>> {
@@ -179,6 +402,85 @@ fn record_from_row(row: RuntimeStatePayloadRow) -> Result<RuntimeWorkspaceInvite
       .and_then(serde_json::Value::as_str)
       .ok_or_else(|| RuntimeError::invalid_state("RuntimeState workspace invite link payload missing inviterUserId"))?
       .to_string(),
+    role: optional_str(&row.payload, "role"),
+    max_uses: row.payload.get("maxUses").and_then(serde_json::Value::as_i64),
+    uses: row.payload.get("uses").and_then(serde_json::Value::as_i64).unwrap_or(0),
+    email_domain: optional_str(&row.payload, "emailDomain"),
     expires_at_ms: row.expires_at_ms,
   })
 }
+
+fn optional_str(payload: &serde_json::Value, key: &str) -> Option<String> {
+  payload.get(key).and_then(serde_json::Value::as_str).map(str::to_string)
+}
+
+impl RuntimeWorkspaceInviteLinkRedeemResult {
+  fn ok(link: RuntimeWorkspaceInviteLinkRecord) -> Self {
+    Self {
+      ok: true,
+      reason: None,
+      link: Some(link),
+    }
+  }
+
+  fn fail(reason: &'static str) -> Self {
+    Self {
+      ok: false,
+      reason: Some(reason.to_string()),
+      link: None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn input() -> RuntimeWorkspaceInviteLinkInput {
+    RuntimeWorkspaceInviteLinkInput {
+      workspace_id: "workspace".to_string(),
+      invite_id: "invite".to_string(),
+      inviter_user_id: "user".to_string(),
+      role: Some(" reader ".to_string()),
+      max_uses: Some(10),
+      email_domain: Some("@AFFiNE.pro".to_string()),
+      ttl_ms: 1_000,
+    }
+  }
+
+  #[test]
+  fn link_payload_normalizes_options() {
+    let payload = link_payload(&input()).unwrap();
+    let record = record_from_row(RuntimeStatePayloadRow {
+      payload,
+      expires_at_ms: 1,
+    })
+    .unwrap();
+
+    assert_eq!(record.role.as_deref(), Some("reader"));
+    assert_eq!(record.max_uses, Some(10));
+    assert_eq!(record.uses, 0);
+    assert_eq!(record.email_domain.as_deref(), Some("affine.pro"));
+  }
+
+  #[test]
+  fn link_payload_rejects_invalid_options() {
+    for input in [
+      RuntimeWorkspaceInviteLinkInput { ttl_ms: 0, ..input() },
+      RuntimeWorkspaceInviteLinkInput {
+        max_uses: Some(0),
+        ..input()
+      },
+      RuntimeWorkspaceInviteLinkInput {
+        role: Some(" ".to_string()),
+        ..input()
+      },
+      RuntimeWorkspaceInviteLinkInput {
+        email_domain: Some("a@b.c".to_string()),
+        ..input()
+      },
+    ] {
+      assert!(link_payload(&input).is_err());
+    }
+  }
+}
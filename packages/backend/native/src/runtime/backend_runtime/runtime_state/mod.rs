@@ -1,13 +1,17 @@
-use super::{BackendRuntime, RuntimeError, RuntimeResult, napi_error};
+use super::{BackendRuntime, RuntimeDatabase, RuntimeError, RuntimeResult, napi_error, unix_ms_now};
 pub(super) use super::{
   constants::{
     BYOK_LOCAL_LEASE_ACTIVE_PURPOSE, BYOK_LOCAL_LEASE_PURPOSE, MAGIC_LINK_OTP_PURPOSE, MAX_MAGIC_LINK_OTP_ATTEMPTS,
-    WORKSPACE_INVITE_LINK_ID_PURPOSE, WORKSPACE_INVITE_LINK_WORKSPACE_PURPOSE,
+    MAX_TOTP_ATTEMPTS, MAX_WORKSPACE_INVITE_LINKS, TOTP_DIGITS, TOTP_DRIFT_STEPS, TOTP_ENROLLMENT_PURPOSE,
+    TOTP_LOCKOUT_MS, TOTP_PERIOD_SECONDS, TOTP_RECOVERY_CODE_COUNT, TOTP_RECOVERY_CODE_PURPOSE, TOTP_SECRET_PURPOSE,
+    TOTP_SECRET_TTL_MS, WORKSPACE_INVITE_LINK_ID_PURPOSE, WORKSPACE_INVITE_LINK_WORKSPACE_PURPOSE,
   },
   token_hash,
   types::{
-    RuntimeByokLocalLeaseRecord, RuntimeMagicLinkOtpConsumeResult, RuntimeVerificationTokenRecord,
-    RuntimeWorkspaceInviteLinkRecord,
+    RuntimeByokLocalLeaseRecord, RuntimeMagicLinkOtpConsumeResult, RuntimeTotpEnrollment, RuntimeTotpVerifyResult,
+    RuntimeVerificationTokenRecord, RuntimeWebauthnAssertion, RuntimeWebauthnAssertionInput, RuntimeWebauthnCredential,
+    RuntimeWebauthnRegistrationInput, RuntimeWorkspaceInviteLinkInput, RuntimeWorkspaceInviteLinkRecord,
+    RuntimeWorkspaceInviteLinkRedeemResult,
   },
 };
 
@@ -17,7 +21,9 @@ mod dto;
 mod invite_link;
 mod magic_link_otp;
 mod store;
+mod totp;
 mod verification_token;
+mod webauthn;
 use store::RuntimeStateStore;
 
 pub(super) type Result<T> = RuntimeResult<T>;
@@ -43,7 +49,7 @@ impl BackendRuntime {
     if ttl_ms <= 0 {
       return Err(napi_error("auth challenge ttl must be positive"));
     }
-    RuntimeStateStore::new(self.pool().await?)
+    RuntimeStateStore::new(self.database().await?)
       .create_auth_challenge(&purpose, &token, payload, ttl_ms)
       .await
       .map_err(napi::Error::from)
@@ -51,7 +57,7 @@ impl BackendRuntime {
 
   #[napi]
   pub async fn get_auth_challenge(&self, purpose: String, token: String) -> napi::Result<Option<serde_json::Value>> {
-    RuntimeStateStore::new(self.pool().await?)
+    RuntimeStateStore::new(self.database().await?)
       .get_auth_challenge(&purpose, &token)
       .await
       .map_err(napi::Error::from)
@@ -63,12 +69,50 @@ impl BackendRuntime {
     purpose: String,
     token: String,
   ) -> napi::Result<Option<serde_json::Value>> {
-    RuntimeStateStore::new(self.pool().await?)
+    RuntimeStateStore::new(self.database().await?)
       .consume_auth_challenge(&purpose, &token)
       .await
       .map_err(napi::Error::from)
   }
 
+  /// Verify a passkey registration. The challenge must have been stored with
+  /// `create_auth_challenge` under its base64url form; it is consumed here and
+  /// its payload is returned with the verified credential.
+  #[napi]
+  pub async fn consume_webauthn_registration_challenge(
+    &self,
+    purpose: String,
+    input: RuntimeWebauthnRegistrationInput,
+  ) -> napi::Result<Option<RuntimeWebauthnCredential>> {
+    if input.expected_origins.is_empty() {
+      return Err(napi_error("webauthn expected origins must not be empty"));
+    }
+    RuntimeStateStore::new(self.database().await?)
+      .consume_webauthn_registration_challenge(&purpose, input)
+      .await
+      .map_err(napi::Error::from)
+  }
+
+  /// Verify a passkey assertion against a stored credential public key and
+  /// signature counter, consuming the challenge it was issued for.
+  #[napi]
+  pub async fn consume_webauthn_assertion_challenge(
+    &self,
+    purpose: String,
+    input: RuntimeWebauthnAssertionInput,
+  ) -> napi::Result<Option<RuntimeWebauthnAssertion>> {
+    if input.expected_origins.is_empty() {
+      return Err(napi_error("webauthn expected origins must not be empty"));
+    }
+    if input.stored_sign_count < 0 {
+      return Err(napi_error("webauthn stored sign count must not be negative"));
+    }
+    RuntimeStateStore::new(self.database().await?)
+      .consume_webauthn_assertion_challenge(&purpose, input)
+      .await
+      .map_err(napi::Error::from)
+  }
+
   #[napi]
   pub async fn create_verification_token(
     &self,
@@ -79,7 +123,7 @@ impl BackendRuntime {
     if ttl_ms <= 0 {
       return Err(napi_error("verification token ttl must be positive"));
     }
-    RuntimeStateStore::new(self.pool().await?)
+    RuntimeStateStore::new(self.database().await?)
       .create_verification_token(token_type, credential, ttl_ms)
       .await
       .map_err(napi::Error::from)
@@ -93,7 +137,7 @@ impl BackendRuntime {
     keep: Option<bool>,
   ) -> napi::Result<Option<RuntimeVerificationTokenRecord>> {
     let keep = keep.unwrap_or(false);
-    RuntimeStateStore::new(self.pool().await?)
+    RuntimeStateStore::new(self.database().await?)
       .get_verification_token(token_type, token, keep)
       .await
       .map_err(napi::Error::from)
@@ -108,7 +152,7 @@ impl BackendRuntime {
     keep: Option<bool>,
   ) -> napi::Result<Option<RuntimeVerificationTokenRecord>> {
     let keep = keep.unwrap_or(false);
-    RuntimeStateStore::new(self.pool().await?)
+    RuntimeStateStore::new(self.database().await?)
       .verify_verification_token(token_type, token, credential, keep)
       .await
       .map_err(napi::Error::from)
@@ -119,7 +163,7 @@ impl BackendRuntime {
     if limit <= 0 {
       return Err(napi_error("verification token cleanup limit must be positive"));
     }
-    RuntimeStateStore::new(self.pool().await?)
+    RuntimeStateStore::new(self.database().await?)
       .cleanup_expired_verification_tokens(limit)
       .await
       .map_err(napi::Error::from)
@@ -134,7 +178,7 @@ impl BackendRuntime {
     client_nonce: Option<String>,
     ttl_ms: i64,
   ) -> napi::Result<()> {
-    RuntimeStateStore::new(self.pool().await?)
+    RuntimeStateStore::new(self.database().await?)
       .upsert_magic_link_otp(email, otp_hash, token, client_nonce, ttl_ms)
       .await
       .map_err(napi::Error::from)
@@ -147,7 +191,7 @@ impl BackendRuntime {
     otp_hash: String,
     client_nonce: Option<String>,
   ) -> napi::Result<RuntimeMagicLinkOtpConsumeResult> {
-    RuntimeStateStore::new(self.pool().await?)
+    RuntimeStateStore::new(self.database().await?)
       .consume_magic_link_otp(email, otp_hash, client_nonce)
       .await
       .map_err(napi::Error::from)
@@ -161,7 +205,7 @@ impl BackendRuntime {
     inviter_user_id: String,
     ttl_ms: i64,
   ) -> napi::Result<RuntimeWorkspaceInviteLinkRecord> {
-    RuntimeStateStore::new(self.pool().await?)
+    RuntimeStateStore::new(self.database().await?)
       .create_workspace_invite_link(workspace_id, invite_id, inviter_user_id, ttl_ms)
       .await
       .map_err(napi::Error::from)
@@ -172,7 +216,7 @@ impl BackendRuntime {
     &self,
     workspace_id: String,
   ) -> napi::Result<Option<RuntimeWorkspaceInviteLinkRecord>> {
-    RuntimeStateStore::new(self.pool().await?)
+    RuntimeStateStore::new(self.database().await?)
       .get_workspace_invite_link(workspace_id)
       .await
       .map_err(napi::Error::from)
@@ -183,7 +227,7 @@ impl BackendRuntime {
     &self,
     invite_id: String,
   ) -> napi::Result<Option<RuntimeWorkspaceInviteLinkRecord>> {
-    RuntimeStateStore::new(self.pool().await?)
+    RuntimeStateStore::new(self.database().await?)
       .get_workspace_invite_link_by_id(invite_id)
       .await
       .map_err(napi::Error::from)
@@ -191,12 +235,59 @@ impl BackendRuntime {
 
   #[napi]
   pub async fn revoke_workspace_invite_link(&self, workspace_id: String) -> napi::Result<bool> {
-    RuntimeStateStore::new(self.pool().await?)
+    RuntimeStateStore::new(self.database().await?)
       .revoke_workspace_invite_link(workspace_id)
       .await
       .map_err(napi::Error::from)
   }
 
+  /// Create an extra invite link with an optional role, use limit and email
+  /// domain restriction. The default link stays managed by
+  /// `create_workspace_invite_link`.
+  #[napi]
+  pub async fn add_workspace_invite_link(
+    &self,
+    input: RuntimeWorkspaceInviteLinkInput,
+  ) -> napi::Result<RuntimeWorkspaceInviteLinkRecord> {
+    RuntimeStateStore::new(self.database().await?)
+      .add_workspace_invite_link(input)
+      .await
+      .map_err(napi::Error::from)
+  }
+
+  #[napi]
+  pub async fn list_workspace_invite_links(
+    &self,
+    workspace_id: String,
+  ) -> napi::Result<Vec<RuntimeWorkspaceInviteLinkRecord>> {
+    RuntimeStateStore::new(self.database().await?)
+      .list_workspace_invite_links(workspace_id)
+      .await
+      .map_err(napi::Error::from)
+  }
+
+  /// Atomically count one use of an invite link. `reason` is one of
+  /// `not_found`, `domain_mismatch` or `exhausted` when `ok` is false.
+  #[napi]
+  pub async fn redeem_workspace_invite_link(
+    &self,
+    invite_id: String,
+    email: Option<String>,
+  ) -> napi::Result<RuntimeWorkspaceInviteLinkRedeemResult> {
+    RuntimeStateStore::new(self.database().await?)
+      .redeem_workspace_invite_link(invite_id, email)
+      .await
+      .map_err(napi::Error::from)
+  }
+
+  #[napi]
+  pub async fn revoke_workspace_invite_link_by_id(&self, invite_id: String) -> napi::Result<bool> {
+    RuntimeStateStore::new(self.database().await?)
+      .revoke_workspace_invite_link_by_id(invite_id)
+      .await
+      .map_err(napi::Error::from)
+  }
+
   #[napi]
   pub async fn create_byok_local_lease(
     &self,
@@ -205,7 +296,7 @@ impl BackendRuntime {
     payload: serde_json::Value,
     ttl_ms: i64,
   ) -> napi::Result<RuntimeByokLocalLeaseRecord> {
-    RuntimeStateStore::new(self.pool().await?)
+    RuntimeStateStore::new(self.database().await?)
       .create_byok_local_lease(active_key, lease_id, payload, ttl_ms)
       .await
       .map_err(napi::Error::from)
@@ -213,18 +304,81 @@ impl BackendRuntime {
 
   #[napi]
   pub async fn get_byok_local_lease(&self, lease_id: String) -> napi::Result<Option<RuntimeByokLocalLeaseRecord>> {
-    RuntimeStateStore::new(self.pool().await?)
+    RuntimeStateStore::new(self.database().await?)
       .get_byok_local_lease(lease_id)
       .await
       .map_err(napi::Error::from)
   }
 
+  /// Start TOTP enrollment. The returned secret stays pending until
+  /// `confirm_totp_enrollment` succeeds with a code generated from it.
+  #[napi]
+  pub async fn begin_totp_enrollment(
+    &self,
+    user_id: String,
+    issuer: String,
+    account_name: String,
+    ttl_ms: i64,
+  ) -> napi::Result<RuntimeTotpEnrollment> {
+    if ttl_ms <= 0 {
+      return Err(napi_error("totp enrollment ttl must be positive"));
+    }
+    let private_key = self.config()?.crypto_private_key;
+    RuntimeStateStore::new(self.database().await?)
+      .begin_totp_enrollment(private_key.as_deref(), user_id, issuer, account_name, ttl_ms)
+      .await
+      .map_err(napi::Error::from)
+  }
+
+  /// Returns fresh recovery codes on success; they are only stored hashed.
+  #[napi]
+  pub async fn confirm_totp_enrollment(&self, user_id: String, code: String) -> napi::Result<RuntimeTotpVerifyResult> {
+    let private_key = self.config()?.crypto_private_key;
+    RuntimeStateStore::new(self.database().await?)
+      .confirm_totp_enrollment(private_key.as_deref(), user_id, code)
+      .await
+      .map_err(napi::Error::from)
+  }
+
+  #[napi]
+  pub async fn verify_totp(&self, user_id: String, code: String) -> napi::Result<RuntimeTotpVerifyResult> {
+    let private_key = self.config()?.crypto_private_key;
+    RuntimeStateStore::new(self.database().await?)
+      .verify_totp(private_key.as_deref(), user_id, code)
+      .await
+      .map_err(napi::Error::from)
+  }
+
+  #[napi]
+  pub async fn consume_totp_recovery_code(&self, user_id: String, code: String) -> napi::Result<bool> {
+    RuntimeStateStore::new(self.database().await?)
+      .consume_totp_recovery_code(user_id, code)
+      .await
+      .map_err(napi::Error::from)
+  }
+
+  #[napi]
+  pub async fn regenerate_totp_recovery_codes(&self, user_id: String) -> napi::Result<Option<Vec<String>>> {
+    RuntimeStateStore::new(self.database().await?)
+      .regenerate_totp_recovery_codes(user_id)
+      .await
+      .map_err(napi::Error::from)
+  }
+
+  #[napi]
+  pub async fn disable_totp(&self, user_id: String) -> napi::Result<bool> {
+    RuntimeStateStore::new(self.database().await?)
+      .disable_totp(user_id)
+      .await
+      .map_err(napi::Error::from)
+  }
+
   #[napi]
   pub async fn cleanup_expired_runtime_states(&self, limit: i64) -> napi::Result<i64> {
     if limit <= 0 {
       return Err(napi_error("runtime state cleanup limit must be positive"));
     }
-    RuntimeStateStore::new(self.pool().await?)
+    RuntimeStateStore::new(self.database().await?)
       .cleanup_expired_runtime_states(limit)
       .await
       .map_err(napi::Error::from)
@@ -1,9 +1,9 @@
-use sqlx::PgPool;
-
 use super::{
-  Result, RuntimeByokLocalLeaseRecord, RuntimeMagicLinkOtpConsumeResult, RuntimeVerificationTokenRecord,
-  RuntimeWorkspaceInviteLinkRecord, auth_challenge, byok_local_lease, dto::RuntimeStateRows, invite_link,
-  magic_link_otp, verification_token,
+  Result, RuntimeByokLocalLeaseRecord, RuntimeDatabase, RuntimeMagicLinkOtpConsumeResult, RuntimeTotpEnrollment,
+  RuntimeTotpVerifyResult, RuntimeVerificationTokenRecord, RuntimeWebauthnAssertion, RuntimeWebauthnAssertionInput,
+  RuntimeWebauthnCredential, RuntimeWebauthnRegistrationInput, RuntimeWorkspaceInviteLinkInput,
+  RuntimeWorkspaceInviteLinkRecord, RuntimeWorkspaceInviteLinkRedeemResult, auth_challenge, byok_local_lease,
+  dto::RuntimeStateRows, invite_link, magic_link_otp, totp, verification_token, webauthn,
 };
 
 pub(super) struct RuntimeStateStore {
@@ -11,9 +11,9 @@ pub(super) struct RuntimeStateStore {
 }
 
 impl RuntimeStateStore {
-  pub(super) fn new(pool: PgPool) -> Self {
+  pub(super) fn new(database: RuntimeDatabase) -> Self {
     Self {
-      rows: RuntimeStateRows::new(pool),
+      rows: RuntimeStateRows::new(database),
     }
   }
 
@@ -35,6 +35,22 @@ impl RuntimeStateStore {
     auth_challenge::consume(&self.rows, purpose, token).await
   }
 
+  pub(super) async fn consume_webauthn_registration_challenge(
+    &self,
+    purpose: &str,
+    input: RuntimeWebauthnRegistrationInput,
+  ) -> Result<Option<RuntimeWebauthnCredential>> {
+    webauthn::consume_registration(&self.rows, purpose, input).await
+  }
+
+  pub(super) async fn consume_webauthn_assertion_challenge(
+    &self,
+    purpose: &str,
+    input: RuntimeWebauthnAssertionInput,
+  ) -> Result<Option<RuntimeWebauthnAssertion>> {
+    webauthn::consume_assertion(&self.rows, purpose, input).await
+  }
+
   pub(super) async fn create_verification_token(
     &self,
     token_type: i32,
@@ -122,6 +138,76 @@ impl RuntimeStateStore {
     invite_link::revoke(&self.rows, workspace_id).await
   }
 
+  pub(super) async fn add_workspace_invite_link(
+    &self,
+    input: RuntimeWorkspaceInviteLinkInput,
+  ) -> Result<RuntimeWorkspaceInviteLinkRecord> {
+    invite_link::add(&self.rows, input).await
+  }
+
+  pub(super) async fn list_workspace_invite_links(
+    &self,
+    workspace_id: String,
+  ) -> Result<Vec<RuntimeWorkspaceInviteLinkRecord>> {
+    invite_link::list(&self.rows, workspace_id).await
+  }
+
+  pub(super) async fn redeem_workspace_invite_link(
+    &self,
+    invite_id: String,
+    email: Option<String>,
+  ) -> Result<RuntimeWorkspaceInviteLinkRedeemResult> {
+    invite_link::redeem(&self.rows, invite_id, email).await
+  }
+
+  pub(super) async fn revoke_workspace_invite_link_by_id(&self, invite_id: String) -> Result<bool> {
+    invite_link::revoke_by_id(&self.rows, invite_id).await
+  }
+
+  pub(super) async fn begin_totp_enrollment(
+    &self,
+    private_key: Option<&str>,
+    user_id: String,
+    issuer: String,
+    account_name: String,
+    ttl_ms: i64,
+  ) -> Result<RuntimeTotpEnrollment> {
+    let cipher = totp::TotpSecretCipher::from_private_key(private_key)?;
+    totp::begin_enrollment(&self.rows, &cipher, user_id, issuer, account_name, ttl_ms).await
+  }
+
+  pub(super) async fn confirm_totp_enrollment(
+    &self,
+    private_key: Option<&str>,
+    user_id: String,
+    code: String,
+  ) -> Result<RuntimeTotpVerifyResult> {
+    let cipher = totp::TotpSecretCipher::from_private_key(private_key)?;
+    totp::confirm_enrollment(&self.rows, &cipher, user_id, code).await
+  }
+
+  pub(super) async fn verify_totp(
+    &self,
+    private_key: Option<&str>,
+    user_id: String,
+    code: String,
+  ) -> Result<RuntimeTotpVerifyResult> {
+    let cipher = totp::TotpSecretCipher::from_private_key(private_key)?;
+    totp::verify(&self.rows, &cipher, user_id, code).await
+  }
+
+  pub(super) async fn consume_totp_recovery_code(&self, user_id: String, code: String) -> Result<bool> {
+    totp::consume_recovery_code(&self.rows, user_id, code).await
+  }
+
+  pub(super) async fn regenerate_totp_recovery_codes(&self, user_id: String) -> Result<Option<Vec<String>>> {
+    totp::regenerate_recovery_codes(&self.rows, user_id).await
+  }
+
+  pub(super) async fn disable_totp(&self, user_id: String) -> Result<bool> {
+    totp::disable(&self.rows, user_id).await
+  }
+
   pub(super) async fn create_byok_local_lease(
     &self,
     active_key: String,
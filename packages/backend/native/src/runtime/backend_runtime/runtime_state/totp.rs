@@ -0,0 +1,552 @@
+use aes_gcm::{
+  AesGcm, KeyInit,
+  aead::{
+    Aead,
+    generic_array::{GenericArray, typenum::U12},
+  },
+  aes::Aes256,
+};
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use hmac::{Hmac, Mac};
+use rand::{Rng, RngCore};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use super::{
+  MAX_TOTP_ATTEMPTS, Result, RuntimeError, RuntimeTotpEnrollment, RuntimeTotpVerifyResult, TOTP_DIGITS,
+  TOTP_DRIFT_STEPS, TOTP_ENROLLMENT_PURPOSE, TOTP_LOCKOUT_MS, TOTP_PERIOD_SECONDS, TOTP_RECOVERY_CODE_COUNT,
+  TOTP_RECOVERY_CODE_PURPOSE, TOTP_SECRET_PURPOSE, TOTP_SECRET_TTL_MS,
+  dto::{RuntimeStateInsertPayload, RuntimeStateRows, RuntimeStateTx},
+};
+
+type Aes256Gcm12 = AesGcm<Aes256, U12, U12>;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+const SECRET_BYTES: usize = 20;
+const NONCE_BYTES: usize = 12;
+
+impl RuntimeTotpVerifyResult {
+  fn ok(recovery_codes: Option<Vec<String>>) -> Self {
+    Self {
+      ok: true,
+      reason: None,
+      recovery_codes,
+    }
+  }
+
+  fn fail(reason: &'static str) -> Self {
+    Self {
+      ok: false,
+      reason: Some(reason.to_string()),
+      recovery_codes: None,
+    }
+  }
+}
+
+/// Encrypts TOTP secrets at rest with a key derived from `crypto.privateKey`.
+pub(super) struct TotpSecretCipher {
+  cipher: Aes256Gcm12,
+}
+
+impl TotpSecretCipher {
+  pub(super) fn from_private_key(private_key: Option<&str>) -> Result<Self> {
+    let private_key =
+      private_key.ok_or_else(|| RuntimeError::config("crypto.privateKey is required to store TOTP secrets"))?;
+    let key = Sha256::new()
+      .chain_update(b"affine:runtime-state:totp:")
+      .chain_update(private_key.as_bytes())
+      .finalize();
+    let cipher = Aes256Gcm12::new_from_slice(&key)
+      .map_err(|_| RuntimeError::invalid_state("RuntimeState totp cipher key is invalid"))?;
+    Ok(Self { cipher })
+  }
+
+  fn encrypt(&self, secret: &[u8]) -> Result<String> {
+    let mut nonce = [0u8; NONCE_BYTES];
+    rand::rng().fill_bytes(&mut nonce);
+    let encrypted = self
+      .cipher
+      .encrypt(GenericArray::from_slice(&nonce), secret)
+      .map_err(|_| RuntimeError::invalid_state("RuntimeState totp secret encryption failed"))?;
+
+    let mut sealed = Vec::with_capacity(NONCE_BYTES + encrypted.len());
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&encrypted);
+    Ok(STANDARD.encode(sealed))
+  }
+
+  fn decrypt(&self, sealed: &str) -> Result<Vec<u8>> {
+    let sealed = STANDARD
+      .decode(sealed)
+      .map_err(|_| RuntimeError::invalid_state("RuntimeState totp secret is not valid base64"))?;
+    if sealed.len() <= NONCE_BYTES {
+      return Err(RuntimeError::invalid_state("RuntimeState totp secret is truncated"));
+    }
+    let (nonce, encrypted) = sealed.split_at(NONCE_BYTES);
+    self
+      .cipher
+      .decrypt(GenericArray::from_slice(nonce), encrypted)
+      .map_err(|_| RuntimeError::invalid_state("RuntimeState totp secret decryption failed"))
+  }
+}
+
+pub(super) async fn begin_enrollment(
+  rows: &RuntimeStateRows,
+  cipher: &TotpSecretCipher,
+  user_id: String,
+  issuer: String,
+  account_name: String,
+  ttl_ms: i64,
+) -> Result<RuntimeTotpEnrollment> {
+  if ttl_ms <= 0 {
+    return Err(RuntimeError::invalid_input("totp enrollment ttl must be positive"));
+  }
+
+  let mut secret = [0u8; SECRET_BYTES];
+  rand::rng().fill_bytes(&mut secret);
+  let payload = serde_json::json!({ "secret": cipher.encrypt(&secret)? });
+
+  let mut tx = rows.begin("RuntimeState totp enrollment").await?;
+  rows
+    .upsert_payload_reset_attempts_in_tx(
+      &mut tx,
+      RuntimeStateInsertPayload {
+        purpose: TOTP_ENROLLMENT_PURPOSE,
+        token: &user_id,
+        lookup_key: &user_id,
+        payload: &payload,
+        ttl_ms,
+        context: "RuntimeState totp enrollment upsert",
+      },
+    )
+    .await?;
+  let expires_at_ms = rows
+    .active_payload_with_expires_for_update_in_tx(
+      &mut tx,
+      TOTP_ENROLLMENT_PURPOSE,
+      &user_id,
+      "RuntimeState totp enrollment lookup",
+    )
+    .await?
+    .map(|row| row.expires_at_ms)
+    .ok_or_else(|| RuntimeError::invalid_state("RuntimeState totp enrollment was not stored"))?;
+  tx.commit()
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeState totp enrollment transaction commit failed", err))?;
+
+  let secret = base32_encode(&secret);
+  Ok(RuntimeTotpEnrollment {
+    otpauth_url: otpauth_url(&secret, &issuer, &account_name),
+    secret,
+    expires_at_ms,
+  })
+}
+
+/// Activate a pending enrollment once the user proves possession of the
+/// secret. Any previous secret and recovery codes are replaced.
+pub(super) async fn confirm_enrollment(
+  rows: &RuntimeStateRows,
+  cipher: &TotpSecretCipher,
+  user_id: String,
+  code: String,
+) -> Result<RuntimeTotpVerifyResult> {
+  let mut tx = rows.begin("RuntimeState totp confirm").await?;
+  let Some(row) = rows
+    .unconsumed_row_for_update_in_tx(
+      &mut tx,
+      TOTP_ENROLLMENT_PURPOSE,
+      &user_id,
+      "RuntimeState totp enrollment lookup",
+    )
+    .await?
+  else {
+    tx.rollback()
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeState totp confirm transaction rollback failed", err))?;
+    return Ok(RuntimeTotpVerifyResult::fail("not_found"));
+  };
+
+  if row.expires_at <= chrono::Utc::now() || row.attempts >= MAX_TOTP_ATTEMPTS {
+    rows
+      .delete_by_key_in_tx(
+        &mut tx,
+        TOTP_ENROLLMENT_PURPOSE,
+        &user_id,
+        "RuntimeState totp enrollment delete",
+      )
+      .await?;
+    tx.commit()
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeState totp confirm transaction commit failed", err))?;
+    let reason = if row.attempts >= MAX_TOTP_ATTEMPTS {
+      "locked"
+    } else {
+      "expired"
+    };
+    return Ok(RuntimeTotpVerifyResult::fail(reason));
+  }
+
+  let sealed = payload_str(&row.payload, "secret")?;
+  let secret = cipher.decrypt(sealed)?;
+  let Some(step) = matching_step(&secret, &code, current_step()) else {
+    let attempts = row.attempts + 1;
+    rows
+      .update_attempts_in_tx(
+        &mut tx,
+        TOTP_ENROLLMENT_PURPOSE,
+        &user_id,
+        attempts,
+        "RuntimeState totp enrollment attempts update",
+      )
+      .await?;
+    tx.commit()
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeState totp confirm transaction commit failed", err))?;
+    return Ok(RuntimeTotpVerifyResult::fail(if attempts >= MAX_TOTP_ATTEMPTS {
+      "locked"
+    } else {
+      "invalid_code"
+    }));
+  };
+
+  rows
+    .delete_by_key_in_tx(
+      &mut tx,
+      TOTP_ENROLLMENT_PURPOSE,
+      &user_id,
+      "RuntimeState totp enrollment delete",
+    )
+    .await?;
+  let payload = serde_json::json!({ "secret": sealed, "lastStep": step });
+  rows
+    .upsert_payload_reset_attempts_in_tx(
+      &mut tx,
+      RuntimeStateInsertPayload {
+        purpose: TOTP_SECRET_PURPOSE,
+        token: &user_id,
+        lookup_key: &user_id,
+        payload: &payload,
+        ttl_ms: TOTP_SECRET_TTL_MS,
+        context: "RuntimeState totp secret upsert",
+      },
+    )
+    .await?;
+  let recovery_codes = replace_recovery_codes_in_tx(rows, &mut tx, &user_id).await?;
+  tx.commit()
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeState totp confirm transaction commit failed", err))?;
+
+  Ok(RuntimeTotpVerifyResult::ok(Some(recovery_codes)))
+}
+
+pub(super) async fn verify(
+  rows: &RuntimeStateRows,
+  cipher: &TotpSecretCipher,
+  user_id: String,
+  code: String,
+) -> Result<RuntimeTotpVerifyResult> {
+  let mut tx = rows.begin("RuntimeState totp verify").await?;
+  let Some(row) = rows
+    .unconsumed_row_for_update_in_tx(
+      &mut tx,
+      TOTP_SECRET_PURPOSE,
+      &user_id,
+      "RuntimeState totp secret lookup",
+    )
+    .await?
+  else {
+    tx.rollback()
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeState totp verify transaction rollback failed", err))?;
+    return Ok(RuntimeTotpVerifyResult::fail("not_enrolled"));
+  };
+
+  let now_ms = chrono::Utc::now().timestamp_millis();
+  let mut payload = row.payload;
+  let locked_until_ms = payload.get("lockedUntilMs").and_then(serde_json::Value::as_i64);
+  if locked_until_ms.is_some_and(|locked_until_ms| locked_until_ms > now_ms) {
+    tx.commit()
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeState totp verify transaction commit failed", err))?;
+    return Ok(RuntimeTotpVerifyResult::fail("locked"));
+  }
+
+  let secret = cipher.decrypt(payload_str(&payload, "secret")?)?;
+  let last_step = payload.get("lastStep").and_then(serde_json::Value::as_i64);
+  let (result, attempts) = match matching_step(&secret, &code, current_step()) {
+    // Each time step can only be used once, so a code observed in transit
+    // cannot be replayed within its validity window.
+    Some(step) if last_step.is_some_and(|last_step| step <= last_step) => {
+      (RuntimeTotpVerifyResult::fail("replayed"), row.attempts + 1)
+    }
+    Some(step) => {
+      payload["lastStep"] = step.into();
+      (RuntimeTotpVerifyResult::ok(None), 0)
+    }
+    None => (RuntimeTotpVerifyResult::fail("invalid_code"), row.attempts + 1),
+  };
+
+  let (result, attempts) = if attempts >= MAX_TOTP_ATTEMPTS {
+    payload["lockedUntilMs"] = (now_ms + TOTP_LOCKOUT_MS).into();
+    (RuntimeTotpVerifyResult::fail("locked"), 0)
+  } else {
+    if let Some(payload) = payload.as_object_mut() {
+      payload.remove("lockedUntilMs");
+    }
+    (result, attempts)
+  };
+
+  rows
+    .update_payload_and_attempts_in_tx(
+      &mut tx,
+      TOTP_SECRET_PURPOSE,
+      &user_id,
+      &payload,
+      attempts,
+      "RuntimeState totp secret update",
+    )
+    .await?;
+  tx.commit()
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeState totp verify transaction commit failed", err))?;
+
+  Ok(result)
+}
+
+pub(super) async fn consume_recovery_code(rows: &RuntimeStateRows, user_id: String, code: String) -> Result<bool> {
+  let consumed = rows
+    .consume_payload(
+      TOTP_RECOVERY_CODE_PURPOSE,
+      &recovery_code_token(&user_id, &code),
+      "RuntimeState totp recovery code consume",
+    )
+    .await?;
+
+  Ok(consumed.is_some())
+}
+
+pub(super) async fn regenerate_recovery_codes(rows: &RuntimeStateRows, user_id: String) -> Result<Option<Vec<String>>> {
+  let mut tx = rows.begin("RuntimeState totp recovery codes").await?;
+  let enrolled = rows
+    .unconsumed_row_for_update_in_tx(
+      &mut tx,
+      TOTP_SECRET_PURPOSE,
+      &user_id,
+      "RuntimeState totp secret lookup",
+    )
+    .await?
+    .is_some();
+  if !enrolled {
+    tx.rollback()
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeState totp recovery codes transaction rollback failed", err))?;
+    return Ok(None);
+  }
+
+  let recovery_codes = replace_recovery_codes_in_tx(rows, &mut tx, &user_id).await?;
+  tx.commit()
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeState totp recovery codes transaction commit failed", err))?;
+
+  Ok(Some(recovery_codes))
+}
+
+pub(super) async fn disable(rows: &RuntimeStateRows, user_id: String) -> Result<bool> {
+  let mut tx = rows.begin("RuntimeState totp disable").await?;
+  let mut deleted = 0;
+  for purpose in [TOTP_ENROLLMENT_PURPOSE, TOTP_SECRET_PURPOSE, TOTP_RECOVERY_CODE_PURPOSE] {
+    deleted += rows
+      .delete_by_lookup_key_in_tx(&mut tx, purpose, &user_id, "RuntimeState totp disable")
+      .await?;
+  }
+  tx.commit()
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeState totp disable transaction commit failed", err))?;
+
+  Ok(deleted > 0)
+}
+
+async fn replace_recovery_codes_in_tx(
+  rows: &RuntimeStateRows,
+  tx: &mut RuntimeStateTx,
+  user_id: &str,
+) -> Result<Vec<String>> {
+  rows
+    .delete_by_lookup_key_in_tx(
+      tx,
+      TOTP_RECOVERY_CODE_PURPOSE,
+      user_id,
+      "RuntimeState totp recovery codes delete",
+    )
+    .await?;
+
+  let payload = serde_json::json!({});
+  let mut codes = Vec::with_capacity(TOTP_RECOVERY_CODE_COUNT);
+  for _ in 0..TOTP_RECOVERY_CODE_COUNT {
+    let code = generate_recovery_code();
+    rows
+      .insert_payload_returning_expires_in_tx(
+        tx,
+        RuntimeStateInsertPayload {
+          purpose: TOTP_RECOVERY_CODE_PURPOSE,
+          token: &recovery_code_token(user_id, &code),
+          lookup_key: user_id,
+          payload: &payload,
+          ttl_ms: TOTP_SECRET_TTL_MS,
+          context: "RuntimeState totp recovery code insert",
+        },
+      )
+      .await?;
+    codes.push(code);
+  }
+
+  Ok(codes)
+}
+
+fn payload_str<'a>(payload: &'a serde_json::Value, key: &str) -> Result<&'a str> {
+  payload
+    .get(key)
+    .and_then(serde_json::Value::as_str)
+    .ok_or_else(|| RuntimeError::invalid_state(format!("RuntimeState totp payload missing {key}")))
+}
+
+fn current_step() -> i64 {
+  chrono::Utc::now().timestamp().div_euclid(TOTP_PERIOD_SECONDS)
+}
+
+/// Find the time step within the drift window that produced `code`.
+fn matching_step(secret: &[u8], code: &str, now_step: i64) -> Option<i64> {
+  let code = code.trim();
+  if code.len() != TOTP_DIGITS as usize || !code.bytes().all(|byte| byte.is_ascii_digit()) {
+    return None;
+  }
+  let code = code.parse::<u32>().ok()?;
+
+  (now_step - TOTP_DRIFT_STEPS..=now_step + TOTP_DRIFT_STEPS)
+    .filter(|step| *step >= 0)
+    .find(|step| hotp(secret, *step as u64) == code)
+}
+
+/// RFC 4226 HOTP with HMAC-SHA1 and dynamic truncation, as used by RFC 6238.
+fn hotp(secret: &[u8], counter: u64) -> u32 {
+  let mut mac = <Hmac<Sha1> as Mac>::new_from_slice(secret).expect("hmac accepts keys of any length");
+  mac.update(&counter.to_be_bytes());
+  let digest = mac.finalize().into_bytes();
+  let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+  let binary = u32::from_be_bytes([
+    digest[offset] & 0x7f,
+    digest[offset + 1],
+    digest[offset + 2],
+    digest[offset + 3],
+  ]);
+  binary % 10u32.pow(TOTP_DIGITS)
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+  let mut encoded = String::with_capacity(bytes.len().div_ceil(5) * 8);
+  for chunk in bytes.chunks(5) {
+    let mut buffer = [0u8; 5];
+    buffer[..chunk.len()].copy_from_slice(chunk);
+    let value = buffer.iter().fold(0u64, |value, byte| (value << 8) | u64::from(*byte));
+    let chars = (chunk.len() * 8).div_ceil(5);
+    for index in 0..chars {
+      let shift = 35 - index * 5;
+      encoded.push(BASE32_ALPHABET[((value >> shift) & 0x1f) as usize] as char);
+    }
+  }
+  encoded
+}
+
+fn otpauth_url(secret: &str, issuer: &str, account_name: &str) -> String {
+  let encode = |value: &str| url::form_urlencoded::byte_serialize(value.as_bytes()).collect::<String>();
+  format!(
+    "otpauth://totp/{}:{}?secret={secret}&issuer={}&algorithm=SHA1&digits={TOTP_DIGITS}&period={TOTP_PERIOD_SECONDS}",
+    encode(issuer),
+    encode(account_name),
+    encode(issuer),
+  )
+}
+
+fn generate_recovery_code() -> String {
+  let mut rng = rand::rng();
+  let chars = (0..10)
+    .map(|_| RECOVERY_CODE_ALPHABET[rng.random_range(0..RECOVERY_CODE_ALPHABET.len())] as char)
+    .collect::<String>();
+  format!("{}-{}", &chars[..5], &chars[5..])
+}
+
+fn recovery_code_token(user_id: &str, code: &str) -> String {
+  let normalized = code
+    .chars()
+    .filter(|char| char.is_ascii_alphanumeric())
+    .map(|char| char.to_ascii_lowercase())
+    .collect::<String>();
+  format!("{user_id}:{normalized}")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const RFC_6238_SECRET: &[u8] = b"12345678901234567890";
+
+  #[test]
+  fn hotp_matches_rfc_6238_sha1_vectors() {
+    for (unix_seconds, expected) in [
+      (59, 287_082),
+      (1_111_111_109, 81_804),
+      (1_234_567_890, 5_924),
+      (2_000_000_000, 279_037),
+    ] {
+      assert_eq!(
+        hotp(RFC_6238_SECRET, (unix_seconds / TOTP_PERIOD_SECONDS) as u64),
+        expected
+      );
+    }
+  }
+
+  #[test]
+  fn matching_step_honours_drift_window() {
+    let now_step = 1_111_111_109 / TOTP_PERIOD_SECONDS;
+    let previous = format!("{:06}", hotp(RFC_6238_SECRET, (now_step - 1) as u64));
+    let stale = format!("{:06}", hotp(RFC_6238_SECRET, (now_step - 2) as u64));
+
+    assert_eq!(matching_step(RFC_6238_SECRET, &previous, now_step), Some(now_step - 1));
+    assert_eq!(matching_step(RFC_6238_SECRET, &stale, now_step), None);
+    assert_eq!(matching_step(RFC_6238_SECRET, "12345", now_step), None);
+    assert_eq!(matching_step(RFC_6238_SECRET, "12a456", now_step), None);
+  }
+
+  #[test]
+  fn base32_encodes_rfc_4648_vectors() {
+    assert_eq!(base32_encode(b"f"), "MY");
+    assert_eq!(base32_encode(b"fooba"), "MZXW6YTB");
+    assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+    assert_eq!(base32_encode(RFC_6238_SECRET), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+  }
+
+  #[test]
+  fn secrets_round_trip_through_cipher_and_reject_other_keys() {
+    let cipher = TotpSecretCipher::from_private_key(Some("private-key")).unwrap();
+    let sealed = cipher.encrypt(RFC_6238_SECRET).unwrap();
+    assert!(!sealed.contains("12345678901234567890"));
+    assert_eq!(cipher.decrypt(&sealed).unwrap(), RFC_6238_SECRET);
+
+    let other = TotpSecretCipher::from_private_key(Some("other-key")).unwrap();
+    assert!(other.decrypt(&sealed).is_err());
+    assert!(TotpSecretCipher::from_private_key(None).is_err());
+  }
+
+  #[test]
+  fn recovery_code_tokens_are_normalized_and_user_scoped() {
+    assert_eq!(
+      recovery_code_token("user-1", "ABCDE-fghjk"),
+      recovery_code_token("user-1", " abcde fghjk ")
+    );
+    assert_ne!(
+      recovery_code_token("user-1", "abcde-fghjk"),
+      recovery_code_token("user-2", "abcde-fghjk")
+    );
+  }
+}
@@ -1,10 +1,9 @@
-use sqlx::{PgPool, Row};
 use uuid::Uuid;
 
 use super::{
-  Result, RuntimeError, RuntimeVerificationTokenRecord,
-  dto::{RuntimeStatePayloadRow, RuntimeStateRows},
-  token_hash, verification_token_purpose,
+  Result, RuntimeDatabase, RuntimeError, RuntimeVerificationTokenRecord,
+  dto::{RuntimeStatePayloadRow, RuntimeStateRows, payload_row, sqlite_payload_row},
+  token_hash, unix_ms_now, verification_token_purpose,
 };
 
 pub(super) async fn create(
@@ -59,9 +58,9 @@ pub(super) async fn verify(
 ) -> Result<Option<RuntimeVerificationTokenRecord>> {
   let purpose = verification_token_purpose(token_type);
   let row = if keep {
-    active_payload_with_credential(rows.pool(), &purpose, &token, credential.as_deref()).await
+    active_payload_with_credential(rows.database(), &purpose, &token, credential.as_deref()).await
   } else {
-    consume_payload_with_credential(rows.pool(), &purpose, &token, credential.as_deref()).await
+    consume_payload_with_credential(rows.database(), &purpose, &token, credential.as_deref()).await
   }
   .map_err(|err| RuntimeError::database("RuntimeState verification token verify failed", err))?;
 
@@ -75,63 +74,96 @@ pub(super) async fn cleanup_expired(rows: &RuntimeStateRows, limit: i64) -> Resu
 }
 
 async fn active_payload_with_credential(
-  pool: &PgPool,
+  database: &RuntimeDatabase,
   purpose: &str,
   token: &str,
   credential: Option<&str>,
 ) -> sqlx::Result<Option<RuntimeStatePayloadRow>> {
-  let row = sqlx::query(
-    r#"
-    SELECT payload, (EXTRACT(EPOCH FROM expires_at) * 1000)::BIGINT AS expires_at_ms
-    FROM runtime_states
-    WHERE purpose = $1
-      AND token_hash = $2
-      AND consumed_at IS NULL
-      AND expires_at > CURRENT_TIMESTAMP
-      AND (payload->>'credential' IS NULL OR payload->>'credential' = $3)
-    "#,
-  )
-  .bind(purpose)
-  .bind(token_hash(token))
-  .bind(credential)
-  .fetch_optional(pool)
-  .await?;
-
-  Ok(row.map(payload_row))
+  match database {
+    RuntimeDatabase::Postgres(pool) => sqlx::query(
+      r#"
+      SELECT payload, (EXTRACT(EPOCH FROM expires_at) * 1000)::BIGINT AS expires_at_ms
+      FROM runtime_states
+      WHERE purpose = $1
+        AND token_hash = $2
+        AND consumed_at IS NULL
+        AND expires_at > CURRENT_TIMESTAMP
+        AND (payload->>'credential' IS NULL OR payload->>'credential' = $3)
+      "#,
+    )
+    .bind(purpose)
+    .bind(token_hash(token))
+    .bind(credential)
+    .fetch_optional(pool)
+    .await
+    .map(|row| row.map(payload_row)),
+    RuntimeDatabase::Sqlite(pool) => sqlx::query(
+      r#"
+      SELECT payload, expires_at AS expires_at_ms
+      FROM runtime_states
+      WHERE purpose = ?1
+        AND token_hash = ?2
+        AND consumed_at IS NULL
+        AND expires_at > ?4
+        AND (json_extract(payload, '$.credential') IS NULL OR json_extract(payload, '$.credential') = ?3)
+      "#,
+    )
+    .bind(purpose)
+    .bind(token_hash(token))
+    .bind(credential)
+    .bind(unix_ms_now())
+    .fetch_optional(pool)
+    .await
+    .map(|row| row.map(sqlite_payload_row)),
+  }
 }
 
 async fn consume_payload_with_credential(
-  pool: &PgPool,
+  database: &RuntimeDatabase,
   purpose: &str,
   token: &str,
   credential: Option<&str>,
 ) -> sqlx::Result<Option<RuntimeStatePayloadRow>> {
-  let row = sqlx::query(
-    r#"
-    UPDATE runtime_states
-    SET consumed_at = CURRENT_TIMESTAMP,
-        updated_at = CURRENT_TIMESTAMP
-    WHERE purpose = $1
-      AND token_hash = $2
-      AND consumed_at IS NULL
-      AND expires_at > CURRENT_TIMESTAMP
-      AND (payload->>'credential' IS NULL OR payload->>'credential' = $3)
-    RETURNING payload, (EXTRACT(EPOCH FROM expires_at) * 1000)::BIGINT AS expires_at_ms
-    "#,
-  )
-  .bind(purpose)
-  .bind(token_hash(token))
-  .bind(credential)
-  .fetch_optional(pool)
-  .await?;
-
-  Ok(row.map(payload_row))
-}
-
-fn payload_row(row: sqlx::postgres::PgRow) -> RuntimeStatePayloadRow {
-  RuntimeStatePayloadRow {
-    payload: row.get("payload"),
-    expires_at_ms: row.get("expires_at_ms"),
+  match database {
+    RuntimeDatabase::Postgres(pool) => sqlx::query(
+      r#"
+      UPDATE runtime_states
+      SET consumed_at = CURRENT_TIMESTAMP,
+          updated_at = CURRENT_TIMESTAMP
+      WHERE purpose = $1
+        AND token_hash = $2
+        AND consumed_at IS NULL
+        AND expires_at > CURRENT_TIMESTAMP
+        AND (payload->>'credential' IS NULL OR payload->>'credential' = $3)
+      RETURNING payload, (EXTRACT(EPOCH FROM expires_at) * 1000)::BIGINT AS expires_at_ms
+      "#,
+    )
+    .bind(purpose)
+    .bind(token_hash(token))
+    .bind(credential)
+    .fetch_optional(pool)
+    .await
+    .map(|row| row.map(payload_row)),
+    RuntimeDatabase::Sqlite(pool) => sqlx::query(
+      r#"
+      UPDATE runtime_states
+      SET consumed_at = ?4,
+          updated_at = ?4
+      WHERE purpose = ?1
+        AND token_hash = ?2
+        AND consumed_at IS NULL
+        AND expires_at > ?4
+        AND (json_extract(payload, '$.credential') IS NULL OR json_extract(payload, '$.credential') = ?3)
+      RETURNING payload, expires_at AS expires_at_ms
+      "#,
+    )
+    .bind(purpose)
+    .bind(token_hash(token))
+    .bind(credential)
+    .bind(unix_ms_now())
+    .fetch_optional(pool)
+    .await
+    .map(|row| row.map(sqlite_payload_row)),
   }
 }
 
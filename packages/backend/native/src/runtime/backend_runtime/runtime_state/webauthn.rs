@@ -0,0 +1,687 @@
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use ciborium::Value;
+use p256::{
+  ecdsa::{Signature, VerifyingKey, signature::Verifier},
+  pkcs8::DecodePublicKey,
+};
+use ring::signature::{ED25519, UnparsedPublicKey};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use super::{
+  Result, RuntimeError, RuntimeWebauthnAssertion, RuntimeWebauthnAssertionInput, RuntimeWebauthnCredential,
+  RuntimeWebauthnRegistrationInput, auth_challenge, dto::RuntimeStateRows,
+};
+
+const COSE_ALG_ES256: i32 = -7;
+const COSE_ALG_EDDSA: i32 = -8;
+const COSE_CRV_P256: i128 = 1;
+const COSE_CRV_ED25519: i128 = 6;
+const COSE_KTY_OKP: i128 = 1;
+const COSE_KTY_EC2: i128 = 2;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_BACKUP_ELIGIBLE: u8 = 0x08;
+const FLAG_BACKED_UP: u8 = 0x10;
+const FLAG_ATTESTED_CREDENTIAL: u8 = 0x40;
+const FLAG_EXTENSIONS: u8 = 0x80;
+
+const CLIENT_DATA_CREATE: &str = "webauthn.create";
+const CLIENT_DATA_GET: &str = "webauthn.get";
+
+fn invalid(message: &str) -> RuntimeError {
+  RuntimeError::invalid_input(format!("WebAuthn {message}"))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ClientData {
+  #[serde(rename = "type")]
+  kind: String,
+  challenge: String,
+  origin: String,
+  #[serde(default)]
+  cross_origin: bool,
+}
+
+/// Public key decoded from a COSE_Key, limited to ES256 and EdDSA (Ed25519).
+enum CoseKey {
+  Es256(VerifyingKey),
+  EdDsa([u8; 32]),
+}
+
+impl CoseKey {
+  fn parse(bytes: &[u8]) -> Result<Self> {
+    let value: Value = ciborium::from_reader(bytes).map_err(|_| invalid("credential public key is malformed"))?;
+    Self::from_value(&value)
+  }
+
+  fn from_value(value: &Value) -> Result<Self> {
+    let entries = value
+      .as_map()
+      .ok_or_else(|| invalid("credential public key is malformed"))?;
+    let label = |label: i128| {
+      entries
+        .iter()
+        .find(|(key, _)| key.as_integer().map(i128::from) == Some(label))
+        .map(|(_, value)| value)
+    };
+    let integer = |key: i128| label(key).and_then(Value::as_integer).map(i128::from);
+    let coordinate = |key: i128| {
+      label(key)
+        .and_then(Value::as_bytes)
+        .filter(|bytes| bytes.len() == 32)
+        .ok_or_else(|| invalid("credential public key coordinate is malformed"))
+    };
+
+    match (integer(1), integer(3)) {
+      (Some(COSE_KTY_EC2), Some(alg)) if alg == COSE_ALG_ES256 as i128 => {
+        if integer(-1) != Some(COSE_CRV_P256) {
+          return Err(invalid("ES256 credential must use the P-256 curve"));
+        }
+        let mut point = Vec::with_capacity(65);
+        point.push(0x04);
+        point.extend_from_slice(coordinate(-2)?);
+        point.extend_from_slice(coordinate(-3)?);
+        let key = VerifyingKey::from_sec1_bytes(&point).map_err(|_| invalid("ES256 credential point is invalid"))?;
+        Ok(Self::Es256(key))
+      }
+      (Some(COSE_KTY_OKP), Some(alg)) if alg == COSE_ALG_EDDSA as i128 => {
+        if integer(-1) != Some(COSE_CRV_ED25519) {
+          return Err(invalid("EdDSA credential must use the Ed25519 curve"));
+        }
+        let mut key = [0u8; 32];
+        key.copy_from_slice(coordinate(-2)?);
+        Ok(Self::EdDsa(key))
+      }
+      _ => Err(invalid("credential algorithm is not supported")),
+    }
+  }
+
+  fn algorithm(&self) -> i32 {
+    match self {
+      Self::Es256(_) => COSE_ALG_ES256,
+      Self::EdDsa(_) => COSE_ALG_EDDSA,
+    }
+  }
+
+  fn verify(&self, message: &[u8], signature: &[u8]) -> Result<()> {
+    let verified = match self {
+      Self::Es256(key) => Signature::from_der(signature).is_ok_and(|signature| key.verify(message, &signature).is_ok()),
+      Self::EdDsa(key) => UnparsedPublicKey::new(&ED25519, key).verify(message, signature).is_ok(),
+    };
+    if verified {
+      Ok(())
+    } else {
+      Err(invalid("signature is invalid"))
+    }
+  }
+}
+
+struct AttestedCredential {
+  aaguid: [u8; 16],
+  credential_id: Vec<u8>,
+  public_key: Vec<u8>,
+  key: CoseKey,
+}
+
+struct AuthenticatorData {
+  flags: u8,
+  sign_count: u32,
+  attested: Option<AttestedCredential>,
+}
+
+/// Parses authenticator data and checks the RP ID hash and the user
+/// presence/verification flags.
+fn parse_authenticator_data(data: &[u8], rp_id: &str, require_user_verification: bool) -> Result<AuthenticatorData> {
+  if data.len() < 37 {
+    return Err(invalid("authenticator data is truncated"));
+  }
+  if data[..32] != Sha256::digest(rp_id.as_bytes())[..] {
+    return Err(invalid("RP ID hash does not match"));
+  }
+  let flags = data[32];
+  if flags & FLAG_USER_PRESENT == 0 {
+    return Err(invalid("user presence flag is not set"));
+  }
+  if require_user_verification && flags & FLAG_USER_VERIFIED == 0 {
+    return Err(invalid("user verification flag is not set"));
+  }
+  let sign_count = u32::from_be_bytes([data[33], data[34], data[35], data[36]]);
+
+  let mut rest = &data[37..];
+  let attested = if flags & FLAG_ATTESTED_CREDENTIAL != 0 {
+    if rest.len() < 18 {
+      return Err(invalid("attested credential data is truncated"));
+    }
+    let mut aaguid = [0u8; 16];
+    aaguid.copy_from_slice(&rest[..16]);
+    let id_len = u16::from_be_bytes([rest[16], rest[17]]) as usize;
+    rest = &rest[18..];
+    if rest.len() < id_len {
+      return Err(invalid("credential id is truncated"));
+    }
+    let credential_id = rest[..id_len].to_vec();
+    rest = &rest[id_len..];
+
+    let encoded = rest;
+    let value: Value = ciborium::from_reader(&mut rest).map_err(|_| invalid("credential public key is malformed"))?;
+    let public_key = encoded[..encoded.len() - rest.len()].to_vec();
+    Some(AttestedCredential {
+      aaguid,
+      credential_id,
+      public_key,
+      key: CoseKey::from_value(&value)?,
+    })
+  } else {
+    None
+  };
+  if flags & FLAG_EXTENSIONS == 0 && !rest.is_empty() {
+    return Err(invalid("authenticator data has trailing bytes"));
+  }
+
+  Ok(AuthenticatorData {
+    flags,
+    sign_count,
+    attested,
+  })
+}
+
+/// Checks the ceremony type and origin and returns the challenge, which is
+/// the auth challenge token the ceremony was started with.
+fn verify_client_data(raw: &[u8], expected_type: &str, expected_origins: &[String]) -> Result<String> {
+  let client_data: ClientData = serde_json::from_slice(raw).map_err(|_| invalid("client data is malformed"))?;
+  if client_data.kind != expected_type {
+    return Err(invalid("client data type does not match"));
+  }
+  if client_data.cross_origin {
+    return Err(invalid("cross-origin ceremonies are not allowed"));
+  }
+  if !expected_origins.contains(&client_data.origin) {
+    return Err(invalid("origin is not allowed"));
+  }
+  if client_data.challenge.is_empty() || URL_SAFE_NO_PAD.decode(&client_data.challenge).is_err() {
+    return Err(invalid("challenge is malformed"));
+  }
+  Ok(client_data.challenge)
+}
+
+fn signed_message(authenticator_data: &[u8], client_data_json: &[u8]) -> Vec<u8> {
+  let mut message = authenticator_data.to_vec();
+  message.extend_from_slice(&Sha256::digest(client_data_json));
+  message
+}
+
+fn map_entry<'a>(entries: &'a [(Value, Value)], key: &str) -> Option<&'a Value> {
+  entries
+    .iter()
+    .find(|(entry, _)| entry.as_text() == Some(key))
+    .map(|(_, value)| value)
+}
+
+/// Verifies `none` and `packed` attestation statements. For packed x5c
+/// attestation only the signature is checked; the certificate trust path is
+/// left to the caller.
+fn verify_attestation(format: &str, statement: &Value, message: &[u8], key: &CoseKey) -> Result<()> {
+  let statement = statement
+    .as_map()
+    .ok_or_else(|| invalid("attestation statement is malformed"))?;
+  match format {
+    "none" if statement.is_empty() => Ok(()),
+    "none" => Err(invalid("none attestation must have an empty statement")),
+    "packed" => {
+      let alg = map_entry(statement, "alg")
+        .and_then(Value::as_integer)
+        .map(i128::from)
+        .ok_or_else(|| invalid("packed attestation algorithm is missing"))?;
+      let signature = map_entry(statement, "sig")
+        .and_then(Value::as_bytes)
+        .ok_or_else(|| invalid("packed attestation signature is missing"))?;
+
+      if let Some(chain) = map_entry(statement, "x5c") {
+        let certificate = chain
+          .as_array()
+          .and_then(|chain| chain.first())
+          .and_then(Value::as_bytes)
+          .ok_or_else(|| invalid("packed attestation certificate is malformed"))?;
+        if alg != COSE_ALG_ES256 as i128 {
+          return Err(invalid("packed attestation algorithm is not supported"));
+        }
+        let spki =
+          certificate_spki(certificate).ok_or_else(|| invalid("packed attestation certificate is malformed"))?;
+        let attestation_key = VerifyingKey::from_public_key_der(spki)
+          .map_err(|_| invalid("packed attestation certificate key is not supported"))?;
+        CoseKey::Es256(attestation_key).verify(message, signature)
+      } else {
+        if alg != key.algorithm() as i128 {
+          return Err(invalid(
+            "packed self attestation algorithm does not match the credential",
+          ));
+        }
+        key.verify(message, signature)
+      }
+    }
+    _ => Err(invalid("attestation format is not supported")),
+  }
+}
+
+/// Tag, whole element, contents and remainder of a DER element.
+type DerElement<'a> = (u8, &'a [u8], &'a [u8], &'a [u8]);
+
+/// Splits one DER element off `input`.
+fn der_element(input: &[u8]) -> Option<DerElement<'_>> {
+  let (&tag, rest) = input.split_first()?;
+  let (&first, rest) = rest.split_first()?;
+  let (len, rest) = if first < 0x80 {
+    (first as usize, rest)
+  } else {
+    let width = (first & 0x7f) as usize;
+    if width == 0 || width > 4 || rest.len() < width {
+      return None;
+    }
+    let len = rest[..width]
+      .iter()
+      .fold(0usize, |len, byte| (len << 8) | *byte as usize);
+    (len, &rest[width..])
+  };
+  if rest.len() < len {
+    return None;
+  }
+  let header = input.len() - rest.len();
+  Some((tag, &input[..header + len], &rest[..len], &rest[len..]))
+}
+
+/// Returns the DER SubjectPublicKeyInfo of an X.509 certificate.
+fn certificate_spki(certificate: &[u8]) -> Option<&[u8]> {
+  let (0x30, _, certificate, _) = der_element(certificate)? else {
+    return None;
+  };
+  let (0x30, _, mut fields, _) = der_element(certificate)? else {
+    return None;
+  };
+  if fields.first() == Some(&0xa0) {
+    fields = der_element(fields)?.3;
+  }
+  // serialNumber, signature, issuer, validity, subject
+  for _ in 0..5 {
+    fields = der_element(fields)?.3;
+  }
+  let (0x30, spki, _, _) = der_element(fields)? else {
+    return None;
+  };
+  Some(spki)
+}
+
+fn format_aaguid(aaguid: &[u8; 16]) -> String {
+  let hex = hex::encode(aaguid);
+  format!(
+    "{}-{}-{}-{}-{}",
+    &hex[..8],
+    &hex[8..12],
+    &hex[12..16],
+    &hex[16..20],
+    &hex[20..]
+  )
+}
+
+struct VerifiedRegistration {
+  format: String,
+  flags: u8,
+  sign_count: u32,
+  credential: AttestedCredential,
+}
+
+fn verify_registration(
+  client_data_json: &[u8],
+  attestation_object: &[u8],
+  rp_id: &str,
+  require_user_verification: bool,
+) -> Result<VerifiedRegistration> {
+  let object: Value =
+    ciborium::from_reader(attestation_object).map_err(|_| invalid("attestation object is malformed"))?;
+  let entries = object
+    .as_map()
+    .ok_or_else(|| invalid("attestation object is malformed"))?;
+  let format = map_entry(entries, "fmt")
+    .and_then(Value::as_text)
+    .ok_or_else(|| invalid("attestation format is missing"))?;
+  let statement = map_entry(entries, "attStmt").ok_or_else(|| invalid("attestation statement is missing"))?;
+  let auth_data = map_entry(entries, "authData")
+    .and_then(Value::as_bytes)
+    .ok_or_else(|| invalid("authenticator data is missing"))?;
+
+  let parsed = parse_authenticator_data(auth_data, rp_id, require_user_verification)?;
+  let credential = parsed
+    .attested
+    .ok_or_else(|| invalid("attested credential data is missing"))?;
+  verify_attestation(
+    format,
+    statement,
+    &signed_message(auth_data, client_data_json),
+    &credential.key,
+  )?;
+
+  Ok(VerifiedRegistration {
+    format: format.to_string(),
+    flags: parsed.flags,
+    sign_count: parsed.sign_count,
+    credential,
+  })
+}
+
+fn verify_assertion(input: &RuntimeWebauthnAssertionInput) -> Result<AuthenticatorData> {
+  let parsed = parse_authenticator_data(
+    &input.authenticator_data,
+    &input.rp_id,
+    input.require_user_verification.unwrap_or(false),
+  )?;
+  CoseKey::parse(&input.public_key)?.verify(
+    &signed_message(&input.authenticator_data, &input.client_data_json),
+    &input.signature,
+  )?;
+
+  let sign_count = i64::from(parsed.sign_count);
+  if (sign_count != 0 || input.stored_sign_count != 0) && sign_count <= input.stored_sign_count {
+    return Err(invalid("signature counter did not increase"));
+  }
+  Ok(parsed)
+}
+
+/// Consumes the auth challenge named by the client data and verifies the
+/// registration against it. Returns `None` when the challenge is unknown or
+/// expired; a verification failure still burns the challenge.
+pub(super) async fn consume_registration(
+  rows: &RuntimeStateRows,
+  purpose: &str,
+  input: RuntimeWebauthnRegistrationInput,
+) -> Result<Option<RuntimeWebauthnCredential>> {
+  let challenge = verify_client_data(&input.client_data_json, CLIENT_DATA_CREATE, &input.expected_origins)?;
+  let Some(challenge_payload) = auth_challenge::consume(rows, purpose, &challenge).await? else {
+    return Ok(None);
+  };
+
+  let registration = verify_registration(
+    &input.client_data_json,
+    &input.attestation_object,
+    &input.rp_id,
+    input.require_user_verification.unwrap_or(false),
+  )?;
+  let credential = registration.credential;
+  Ok(Some(RuntimeWebauthnCredential {
+    credential_id: URL_SAFE_NO_PAD.encode(&credential.credential_id),
+    algorithm: credential.key.algorithm(),
+    public_key: credential.public_key.into(),
+    sign_count: i64::from(registration.sign_count),
+    aaguid: format_aaguid(&credential.aaguid),
+    attestation_format: registration.format,
+    user_verified: registration.flags & FLAG_USER_VERIFIED != 0,
+    backup_eligible: registration.flags & FLAG_BACKUP_ELIGIBLE != 0,
+    backed_up: registration.flags & FLAG_BACKED_UP != 0,
+    challenge_payload,
+  }))
+}
+
+/// Consumes the auth challenge named by the client data and verifies the
+/// assertion signature and counter against the stored credential.
+pub(super) async fn consume_assertion(
+  rows: &RuntimeStateRows,
+  purpose: &str,
+  input: RuntimeWebauthnAssertionInput,
+) -> Result<Option<RuntimeWebauthnAssertion>> {
+  let challenge = verify_client_data(&input.client_data_json, CLIENT_DATA_GET, &input.expected_origins)?;
+  let Some(challenge_payload) = auth_challenge::consume(rows, purpose, &challenge).await? else {
+    return Ok(None);
+  };
+
+  let parsed = verify_assertion(&input)?;
+  Ok(Some(RuntimeWebauthnAssertion {
+    credential_id: input.credential_id,
+    sign_count: i64::from(parsed.sign_count),
+    user_verified: parsed.flags & FLAG_USER_VERIFIED != 0,
+    backed_up: parsed.flags & FLAG_BACKED_UP != 0,
+    challenge_payload,
+  }))
+}
+
+#[cfg(test)]
+mod tests {
+  use p256::ecdsa::{SigningKey, signature::Signer};
+  use ring::signature::{Ed25519KeyPair, KeyPair};
+
+  use super::*;
+
+  const RP_ID: &str = "app.affine.pro";
+  const ORIGIN: &str = "https://app.affine.pro";
+  const CHALLENGE: &str = "c2lnbi1tZS1pbg";
+
+  fn cbor(value: &Value) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    ciborium::into_writer(value, &mut bytes).unwrap();
+    bytes
+  }
+
+  fn int(value: i64) -> Value {
+    Value::Integer(value.into())
+  }
+
+  fn client_data(kind: &str, origin: &str) -> Vec<u8> {
+    serde_json::to_vec(&serde_json::json!({ "type": kind, "challenge": CHALLENGE, "origin": origin })).unwrap()
+  }
+
+  fn es256_key() -> (SigningKey, Vec<u8>) {
+    let signing = SigningKey::from_slice(&[7u8; 32]).unwrap();
+    let point = signing.verifying_key().to_encoded_point(false);
+    let cose = cbor(&Value::Map(vec![
+      (int(1), int(2)),
+      (int(3), int(-7)),
+      (int(-1), int(1)),
+      (int(-2), Value::Bytes(point.x().unwrap().to_vec())),
+      (int(-3), Value::Bytes(point.y().unwrap().to_vec())),
+    ]));
+    (signing, cose)
+  }
+
+  fn es256_sign(signing: &SigningKey, message: &[u8]) -> Vec<u8> {
+    let signature: Signature = signing.sign(message);
+    signature.to_der().as_bytes().to_vec()
+  }
+
+  fn authenticator_data(flags: u8, sign_count: u32, credential: Option<(&[u8], &[u8])>) -> Vec<u8> {
+    let mut data = Sha256::digest(RP_ID.as_bytes()).to_vec();
+    data.push(flags);
+    data.extend_from_slice(&sign_count.to_be_bytes());
+    if let Some((id, cose)) = credential {
+      data.extend_from_slice(&[0x11; 16]);
+      data.extend_from_slice(&(id.len() as u16).to_be_bytes());
+      data.extend_from_slice(id);
+      data.extend_from_slice(cose);
+    }
+    data
+  }
+
+  fn attestation_object(format: &str, statement: Vec<(Value, Value)>, auth_data: &[u8]) -> Vec<u8> {
+    cbor(&Value::Map(vec![
+      (Value::Text("fmt".into()), Value::Text(format.into())),
+      (Value::Text("attStmt".into()), Value::Map(statement)),
+      (Value::Text("authData".into()), Value::Bytes(auth_data.to_vec())),
+    ]))
+  }
+
+  fn assertion_input(
+    auth_data: Vec<u8>,
+    signature: Vec<u8>,
+    public_key: Vec<u8>,
+    stored: i64,
+  ) -> RuntimeWebauthnAssertionInput {
+    RuntimeWebauthnAssertionInput {
+      credential_id: "credential".to_string(),
+      client_data_json: client_data(CLIENT_DATA_GET, ORIGIN).into(),
+      authenticator_data: auth_data.into(),
+      signature: signature.into(),
+      public_key: public_key.into(),
+      stored_sign_count: stored,
+      rp_id: RP_ID.to_string(),
+      expected_origins: vec![ORIGIN.to_string()],
+      require_user_verification: None,
+    }
+  }
+
+  #[test]
+  fn client_data_checks_type_origin_and_challenge() {
+    let origins = vec![ORIGIN.to_string()];
+    assert_eq!(
+      verify_client_data(&client_data(CLIENT_DATA_CREATE, ORIGIN), CLIENT_DATA_CREATE, &origins).unwrap(),
+      CHALLENGE
+    );
+    assert!(verify_client_data(&client_data(CLIENT_DATA_GET, ORIGIN), CLIENT_DATA_CREATE, &origins).is_err());
+    assert!(
+      verify_client_data(
+        &client_data(CLIENT_DATA_CREATE, "https://evil.example"),
+        CLIENT_DATA_CREATE,
+        &origins
+      )
+      .is_err()
+    );
+  }
+
+  #[test]
+  fn registration_accepts_none_attestation_and_returns_cose_key() {
+    let (_, cose) = es256_key();
+    let auth_data = authenticator_data(
+      FLAG_USER_PRESENT | FLAG_USER_VERIFIED | FLAG_ATTESTED_CREDENTIAL,
+      0,
+      Some((b"cred-1".as_slice(), cose.as_slice())),
+    );
+    let client_data = client_data(CLIENT_DATA_CREATE, ORIGIN);
+    let registration = verify_registration(
+      &client_data,
+      &attestation_object("none", vec![], &auth_data),
+      RP_ID,
+      true,
+    )
+    .unwrap();
+
+    assert_eq!(registration.format, "none");
+    assert_eq!(registration.credential.credential_id, b"cred-1");
+    assert_eq!(registration.credential.public_key, cose);
+    assert_eq!(registration.credential.key.algorithm(), COSE_ALG_ES256);
+    assert_eq!(
+      format_aaguid(&registration.credential.aaguid),
+      "11111111-1111-1111-1111-111111111111"
+    );
+
+    assert!(
+      verify_registration(
+        &client_data,
+        &attestation_object("none", vec![], &auth_data),
+        "other.example",
+        true
+      )
+      .is_err()
+    );
+    let unverified = authenticator_data(
+      FLAG_USER_PRESENT | FLAG_ATTESTED_CREDENTIAL,
+      0,
+      Some((b"cred-1".as_slice(), cose.as_slice())),
+    );
+    assert!(
+      verify_registration(
+        &client_data,
+        &attestation_object("none", vec![], &unverified),
+        RP_ID,
+        true
+      )
+      .is_err()
+    );
+  }
+
+  #[test]
+  fn registration_verifies_packed_self_attestation() {
+    let (signing, cose) = es256_key();
+    let auth_data = authenticator_data(
+      FLAG_USER_PRESENT | FLAG_ATTESTED_CREDENTIAL,
+      0,
+      Some((b"cred-2".as_slice(), cose.as_slice())),
+    );
+    let client_data = client_data(CLIENT_DATA_CREATE, ORIGIN);
+    let signature = es256_sign(&signing, &signed_message(&auth_data, &client_data));
+    let statement = |signature: Vec<u8>| {
+      vec![
+        (Value::Text("alg".into()), int(-7)),
+        (Value::Text("sig".into()), Value::Bytes(signature)),
+      ]
+    };
+
+    assert!(
+      verify_registration(
+        &client_data,
+        &attestation_object("packed", statement(signature.clone()), &auth_data),
+        RP_ID,
+        false,
+      )
+      .is_ok()
+    );
+    let mut tampered = signature;
+    let last = tampered.len() - 1;
+    tampered[last] ^= 1;
+    assert!(
+      verify_registration(
+        &client_data,
+        &attestation_object("packed", statement(tampered), &auth_data),
+        RP_ID,
+        false,
+      )
+      .is_err()
+    );
+  }
+
+  #[test]
+  fn assertion_verifies_ed25519_signature_and_counter() {
+    let pair = Ed25519KeyPair::from_seed_unchecked(&[9u8; 32]).unwrap();
+    let cose = cbor(&Value::Map(vec![
+      (int(1), int(1)),
+      (int(3), int(-8)),
+      (int(-1), int(6)),
+      (int(-2), Value::Bytes(pair.public_key().as_ref().to_vec())),
+    ]));
+    let client_data = client_data(CLIENT_DATA_GET, ORIGIN);
+    let assertion = |sign_count: u32, stored: i64| {
+      let auth_data = authenticator_data(FLAG_USER_PRESENT, sign_count, None);
+      let signature = pair.sign(&signed_message(&auth_data, &client_data)).as_ref().to_vec();
+      verify_assertion(&assertion_input(auth_data, signature, cose.clone(), stored))
+    };
+
+    assert_eq!(assertion(5, 4).unwrap().sign_count, 5);
+    assert_eq!(assertion(0, 0).unwrap().sign_count, 0);
+    assert!(assertion(4, 4).is_err());
+    assert!(assertion(0, 3).is_err());
+
+    let auth_data = authenticator_data(FLAG_USER_PRESENT, 1, None);
+    assert!(verify_assertion(&assertion_input(auth_data, vec![0; 64], cose, 0)).is_err());
+  }
+
+  #[test]
+  fn certificate_spki_skips_to_subject_public_key_info() {
+    let element = |tag: u8, content: &[u8]| {
+      let mut out = vec![tag, content.len() as u8];
+      out.extend_from_slice(content);
+      out
+    };
+    let spki = element(0x30, &[0x05, 0x00]);
+    let tbs = [
+      element(0xa0, &element(0x02, &[2])),
+      element(0x02, &[1]),
+      element(0x30, &[]),
+      element(0x30, &[]),
+      element(0x30, &[]),
+      element(0x30, &[]),
+      spki.clone(),
+    ]
+    .concat();
+    let certificate = element(0x30, &[element(0x30, &tbs), element(0x30, &[])].concat());
+
+    assert_eq!(certificate_spki(&certificate), Some(&spki[..]));
+    assert_eq!(certificate_spki(&certificate[..4]), None);
+  }
+}
@@ -1,8 +1,17 @@
+use std::time::Duration;
+
 use anyhow::{Context, Result as AnyResult, anyhow};
+use sqlx::postgres::PgPoolOptions;
 
 use super::{
-  super::migrations::{RUNTIME_MIGRATIONS, migrate_runtime_tables},
+  super::{
+    database::{DatabasePoolConfig, DatabaseReplicaConfig},
+    migrations::{
+      RUNTIME_MIGRATIONS, RUNTIME_SQLITE_MIGRATIONS, migrate_runtime_sqlite_tables, migrate_runtime_tables,
+    },
+  },
   runtime_state::*,
+  types::RuntimeDocHistoryInput,
   *,
 };
 
@@ -17,11 +26,20 @@ fn pg_test_lock() -> &'static tokio::sync::Mutex<()> {
 fn migrations_include_runtime_tables_without_worker_heartbeats() {
   assert!(RUNTIME_MIGRATIONS.contains("runtime_states"));
   assert!(RUNTIME_MIGRATIONS.contains("runtime_gates"));
+  assert!(RUNTIME_MIGRATIONS.contains("runtime_gate_buckets"));
+  assert!(RUNTIME_MIGRATIONS.contains("runtime_gate_window_hits"));
   assert!(RUNTIME_MIGRATIONS.contains("runtime_leases"));
   assert!(RUNTIME_MIGRATIONS.contains("blob_reconciliation_runs"));
   assert!(RUNTIME_MIGRATIONS.contains("blob_reconciliation_checkpoints"));
   assert!(RUNTIME_MIGRATIONS.contains("doc_blob_refs"));
   assert!(RUNTIME_MIGRATIONS.contains("blob_cleanup_candidates"));
+  assert!(RUNTIME_MIGRATIONS.contains("blob_integrity_findings"));
+  assert!(RUNTIME_MIGRATIONS.contains("workspace_storage_usage"));
+  assert!(RUNTIME_MIGRATIONS.contains("blob_quota_reservations"));
+  assert!(RUNTIME_MIGRATIONS.contains("blob_derivatives"));
+  assert!(RUNTIME_MIGRATIONS.contains("runtime_jobs"));
+  assert!(RUNTIME_MIGRATIONS.contains("audit_events"));
+  assert!(RUNTIME_MIGRATIONS.contains("doc_data_keys"));
   assert!(!RUNTIME_MIGRATIONS.contains("runtime_worker_heartbeats"));
 }
 
@@ -59,6 +77,8 @@ async fn runtime_from_database_url() -> AnyResult<Option<BackendRuntime>> {
     WHERE purpose LIKE 'rust_test:%'
        OR purpose LIKE 'auth_challenge:rust_test:%'
        OR purpose = 'verification_token:99999'
+       OR (purpose LIKE 'totp:%' AND lookup_key LIKE 'rust-test:%')
+       OR (purpose LIKE 'workspace_invite_link:%' AND lookup_key LIKE 'rust-test:%')
     "#,
   )
   .execute(&pool)
@@ -68,369 +88,1314 @@ async fn runtime_from_database_url() -> AnyResult<Option<BackendRuntime>> {
     .execute(&pool)
     .await
     .context("cleanup runtime_gates for backend runtime tests")?;
+  sqlx::query("DELETE FROM runtime_gate_buckets WHERE key LIKE 'rust-test:%'")
+    .execute(&pool)
+    .await
+    .context("cleanup runtime_gate_buckets for backend runtime tests")?;
+  sqlx::query("DELETE FROM runtime_gate_window_hits WHERE key LIKE 'rust-test:%'")
+    .execute(&pool)
+    .await
+    .context("cleanup runtime_gate_window_hits for backend runtime tests")?;
   sqlx::query("DELETE FROM runtime_leases WHERE key LIKE 'rust-test:%'")
     .execute(&pool)
     .await
     .context("cleanup runtime_leases for backend runtime tests")?;
+  sqlx::query("DELETE FROM runtime_jobs WHERE queue LIKE 'rust-test:%'")
+    .execute(&pool)
+    .await
+    .context("cleanup runtime_jobs for backend runtime tests")?;
+  sqlx::query("DELETE FROM audit_events WHERE action LIKE 'rust-test:%'")
+    .execute(&pool)
+    .await
+    .context("cleanup audit_events for backend runtime tests")?;
+  sqlx::query("DELETE FROM doc_data_keys WHERE workspace_id LIKE 'rust-test:%'")
+    .execute(&pool)
+    .await
+    .context("cleanup doc_data_keys for backend runtime tests")?;
+  // Doc rows reference their editor in `users`.
+  sqlx::query(
+    r#"
+    INSERT INTO users (id, name, email)
+    VALUES ('rust-test:editor', 'rust-test', 'rust-test-editor@affine.test')
+    ON CONFLICT DO NOTHING
+    "#,
+  )
+  .execute(&pool)
+  .await
+  .context("seed doc editor for backend runtime tests")?;
 
   Ok(Some(BackendRuntime {
-    config: std::sync::RwLock::new(BackendRuntimeConfig { database_url }),
-    pool: Mutex::new(Some(pool)),
+    config: std::sync::RwLock::new(BackendRuntimeConfig {
+      database_url,
+      pool: DatabasePoolConfig::default(),
+      replicas: DatabaseReplicaConfig::default(),
+      crypto_private_key: Some("rust-test-private-key".to_string()),
+      doc_encryption: DocEncryptionConfig::default(),
+    }),
+    database: Mutex::new(Some(RuntimeDatabase::Postgres(pool))),
+    replicas: Mutex::new(None),
   }))
 }
 
-#[tokio::test]
-async fn runtime_gate_sql_semantics_are_atomic_and_ttl_bound() {
-  let _guard = pg_test_lock().lock().await;
-  let Some(runtime) = runtime_from_database_url().await.unwrap() else {
-    eprintln!("skipping postgres integration test: DATABASE_URL is not set");
-    return;
+async fn sqlite_runtime() -> BackendRuntime {
+  let database_url = "sqlite::memory:".to_string();
+  let database = RuntimeDatabase::connect(&database_url, &DatabasePoolConfig::default(), "BackendRuntime")
+    .await
+    .unwrap();
+  let RuntimeDatabase::Sqlite(pool) = &database else {
+    panic!("sqlite url should open the embedded database");
   };
+  migrate_runtime_sqlite_tables(pool).await.unwrap();
 
-  struct Case {
-    key: &'static str,
-    first_ttl_ms: i64,
-    wait_ms: Option<u64>,
-    second_expected: bool,
+  BackendRuntime {
+    config: std::sync::RwLock::new(BackendRuntimeConfig {
+      database_url,
+      pool: DatabasePoolConfig::default(),
+      replicas: DatabaseReplicaConfig::default(),
+      crypto_private_key: Some("rust-test-private-key".to_string()),
+      doc_encryption: DocEncryptionConfig::default(),
+    }),
+    database: Mutex::new(Some(database)),
+    replicas: Mutex::new(None),
   }
+}
 
-  for case in [
-    Case {
-      key: "rust-test:gate:same-key",
-      first_ttl_ms: 30_000,
-      wait_ms: None,
-      second_expected: false,
-    },
-    Case {
-      key: "rust-test:gate:expired-key",
-      first_ttl_ms: 1,
-      wait_ms: Some(20),
-      second_expected: true,
-    },
+/// Runtimes the shared suite runs against: the embedded SQLite database
+/// always, plus postgres when `DATABASE_URL` is set.
+async fn test_runtimes() -> Vec<BackendRuntime> {
+  let mut runtimes = vec![sqlite_runtime().await];
+  match runtime_from_database_url().await.unwrap() {
+    Some(runtime) => runtimes.push(runtime),
+    None => eprintln!("skipping postgres integration run: DATABASE_URL is not set"),
+  }
+  runtimes
+}
+
+#[test]
+fn sqlite_migrations_cover_embedded_runtime_tables() {
+  for table in [
+    "runtime_states",
+    "runtime_gates",
+    "runtime_gate_buckets",
+    "runtime_gate_window_hits",
+    "runtime_leases",
+    "snapshots",
+    "updates",
+    "snapshot_histories",
   ] {
+    assert!(RUNTIME_SQLITE_MIGRATIONS.contains(table), "{table}");
+  }
+  assert!(!RUNTIME_SQLITE_MIGRATIONS.contains("runtime_jobs"));
+}
+
+#[tokio::test]
+async fn sqlite_runtime_rejects_postgres_only_operations() {
+  let runtime = sqlite_runtime().await;
+
+  assert!(runtime.health().await.unwrap().database_connected);
+  let err = runtime.pool().await.unwrap_err();
+  assert!(err.to_string().contains("embedded SQLite mode"), "{err}");
+}
+
+#[tokio::test]
+async fn runtime_gate_sql_semantics_are_atomic_and_ttl_bound() {
+  let _guard = pg_test_lock().lock().await;
+  for runtime in test_runtimes().await {
+    struct Case {
+      key: &'static str,
+      first_ttl_ms: i64,
+      wait_ms: Option<u64>,
+      second_expected: bool,
+    }
+
+    for case in [
+      Case {
+        key: "rust-test:gate:same-key",
+        first_ttl_ms: 30_000,
+        wait_ms: None,
+        second_expected: false,
+      },
+      Case {
+        key: "rust-test:gate:expired-key",
+        first_ttl_ms: 1,
+        wait_ms: Some(20),
+        second_expected: true,
+      },
+    ] {
+      assert!(
+        runtime
+          .put_runtime_gate_if_absent(case.key.to_string(), case.first_ttl_ms)
+          .await
+          .unwrap()
+      );
+      if let Some(wait_ms) = case.wait_ms {
+        tokio::time::sleep(Duration::from_millis(wait_ms)).await;
+      }
+      assert_eq!(
+        runtime
+          .put_runtime_gate_if_absent(case.key.to_string(), 30_000)
+          .await
+          .unwrap(),
+        case.second_expected,
+        "{}",
+        case.key
+      );
+    }
+
+    let mut tasks = Vec::new();
+    for _ in 0..16 {
+      let runtime = BackendRuntime {
+        config: std::sync::RwLock::new(runtime.config().unwrap()),
+        database: Mutex::new(Some(runtime.database().await.unwrap())),
+        replicas: Mutex::new(None),
+      };
+      tasks.push(tokio::spawn(async move {
+        runtime
+          .put_runtime_gate_if_absent("rust-test:gate:concurrent".to_string(), 30_000)
+          .await
+          .unwrap()
+      }));
+    }
+    let mut successful = 0;
+    for task in tasks {
+      if task.await.unwrap() {
+        successful += 1;
+      }
+    }
+    assert_eq!(successful, 1);
+
     assert!(
       runtime
-        .put_runtime_gate_if_absent(case.key.to_string(), case.first_ttl_ms)
+        .put_runtime_gate_if_absent("rust-test:gate:cleanup".to_string(), 1)
         .await
         .unwrap()
     );
-    if let Some(wait_ms) = case.wait_ms {
-      tokio::time::sleep(Duration::from_millis(wait_ms)).await;
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(runtime.cleanup_expired_runtime_gates(100).await.unwrap(), 1);
+    assert_eq!(runtime.cleanup_expired_runtime_gates(100).await.unwrap(), 0);
+  }
+}
+
+#[tokio::test]
+async fn runtime_gate_rate_limits_share_state_across_callers() {
+  let _guard = pg_test_lock().lock().await;
+  for runtime in test_runtimes().await {
+    for _ in 0..3 {
+      let result = runtime
+        .rate_limit_check("rust-test:bucket:basic".to_string(), 3.0, 0.001, 1.0)
+        .await
+        .unwrap();
+      assert!(result.allowed);
+      assert!(result.retry_after_ms.is_none());
     }
-    assert_eq!(
+    let denied = runtime
+      .rate_limit_check("rust-test:bucket:basic".to_string(), 3.0, 0.001, 1.0)
+      .await
+      .unwrap();
+    assert!(!denied.allowed);
+    assert!(denied.remaining < 1.0);
+    assert!(denied.retry_after_ms.unwrap() > 0);
+
+    let mut tasks = Vec::new();
+    for _ in 0..16 {
+      let runtime = BackendRuntime {
+        config: std::sync::RwLock::new(runtime.config().unwrap()),
+        database: Mutex::new(Some(runtime.database().await.unwrap())),
+        replicas: Mutex::new(None),
+      };
+      tasks.push(tokio::spawn(async move {
+        runtime
+          .rate_limit_check("rust-test:bucket:concurrent".to_string(), 4.0, 0.001, 1.0)
+          .await
+          .unwrap()
+          .allowed
+      }));
+    }
+    let mut allowed = 0;
+    for task in tasks {
+      if task.await.unwrap() {
+        allowed += 1;
+      }
+    }
+    assert_eq!(allowed, 4);
+
+    assert!(
+      runtime
+        .rate_limit_check("rust-test:bucket:refill".to_string(), 1.0, 100.0, 1.0)
+        .await
+        .unwrap()
+        .allowed
+    );
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    assert!(
       runtime
-        .put_runtime_gate_if_absent(case.key.to_string(), 30_000)
+        .rate_limit_check("rust-test:bucket:refill".to_string(), 1.0, 100.0, 1.0)
         .await
-        .unwrap(),
-      case.second_expected,
-      "{}",
-      case.key
+        .unwrap()
+        .allowed
     );
-  }
 
-  let mut tasks = Vec::new();
-  for _ in 0..16 {
-    let runtime = BackendRuntime {
-      config: std::sync::RwLock::new(runtime.config().unwrap()),
-      pool: Mutex::new(Some(runtime.pool().await.unwrap())),
-    };
-    tasks.push(tokio::spawn(async move {
+    for expected in [true, true, false] {
+      let result = runtime
+        .rate_limit_check_sliding_window("rust-test:window:basic".to_string(), 2, 50, 1)
+        .await
+        .unwrap();
+      assert_eq!(result.allowed, expected);
+    }
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert!(
       runtime
-        .put_runtime_gate_if_absent("rust-test:gate:concurrent".to_string(), 30_000)
+        .rate_limit_check_sliding_window("rust-test:window:basic".to_string(), 2, 50, 1)
         .await
         .unwrap()
-    }));
-  }
-  let mut successful = 0;
-  for task in tasks {
-    if task.await.unwrap() {
-      successful += 1;
-    }
+        .allowed
+    );
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert_eq!(runtime.cleanup_expired_runtime_gates(1).await.unwrap(), 1);
+    assert!(runtime.cleanup_expired_runtime_gates(100).await.unwrap() >= 1);
   }
-  assert_eq!(successful, 1);
-
-  assert!(
-    runtime
-      .put_runtime_gate_if_absent("rust-test:gate:cleanup".to_string(), 1)
-      .await
-      .unwrap()
-  );
-  tokio::time::sleep(Duration::from_millis(20)).await;
-  assert_eq!(runtime.cleanup_expired_runtime_gates(100).await.unwrap(), 1);
-  assert_eq!(runtime.cleanup_expired_runtime_gates(100).await.unwrap(), 0);
 }
 
 #[tokio::test]
 async fn coordination_lease_sql_semantics_are_fenced_and_ttl_bound() {
   let _guard = pg_test_lock().lock().await;
-  let Some(runtime) = runtime_from_database_url().await.unwrap() else {
-    eprintln!("skipping postgres integration test: DATABASE_URL is not set");
-    return;
-  };
-
-  let lease = runtime
-    .acquire_coordination_lease("rust-test:lease:basic".to_string(), "owner-1".to_string(), 30_000)
-    .await
-    .unwrap()
-    .expect("first owner should acquire lease");
-  assert_eq!(lease.fencing_token, 1);
-  assert!(
-    !runtime
-      .release_coordination_lease(lease.key.clone(), "owner-2".to_string(), lease.fencing_token)
-      .await
-      .unwrap()
-  );
-  assert!(
-    runtime
-      .release_coordination_lease(lease.key.clone(), lease.owner.clone(), lease.fencing_token)
+  for runtime in test_runtimes().await {
+    let lease = runtime
+      .acquire_coordination_lease("rust-test:lease:basic".to_string(), "owner-1".to_string(), 30_000)
       .await
       .unwrap()
-  );
-
-  let mut tasks = Vec::new();
-  for index in 0..16 {
-    let runtime = BackendRuntime {
-      config: std::sync::RwLock::new(runtime.config().unwrap()),
-      pool: Mutex::new(Some(runtime.pool().await.unwrap())),
-    };
-    tasks.push(tokio::spawn(async move {
+      .expect("first owner should acquire lease");
+    assert_eq!(lease.fencing_token, 1);
+    assert!(
+      !runtime
+        .release_coordination_lease(lease.key.clone(), "owner-2".to_string(), lease.fencing_token)
+        .await
+        .unwrap()
+    );
+    assert!(
       runtime
-        .acquire_coordination_lease(
-          "rust-test:lease:concurrent".to_string(),
-          format!("owner-{index}"),
-          30_000,
-        )
+        .release_coordination_lease(lease.key.clone(), lease.owner.clone(), lease.fencing_token)
         .await
         .unwrap()
-        .is_some()
-    }));
-  }
-  let mut successful = 0;
-  for task in tasks {
-    if task.await.unwrap() {
-      successful += 1;
-    }
-  }
-  assert_eq!(successful, 1);
+    );
 
-  let stale = runtime
-    .acquire_coordination_lease("rust-test:lease:stale".to_string(), "owner-1".to_string(), 1)
-    .await
-    .unwrap()
-    .expect("stale lease owner should acquire");
-  tokio::time::sleep(Duration::from_millis(20)).await;
-  let takeover = runtime
-    .acquire_coordination_lease("rust-test:lease:stale".to_string(), "owner-2".to_string(), 30_000)
-    .await
-    .unwrap()
-    .expect("expired lease should be taken over");
-  assert_eq!(takeover.fencing_token, stale.fencing_token + 1);
-  assert!(
-    !runtime
-      .release_coordination_lease(stale.key.clone(), stale.owner.clone(), stale.fencing_token)
-      .await
-      .unwrap()
-  );
+    let mut tasks = Vec::new();
+    for index in 0..16 {
+      let runtime = BackendRuntime {
+        config: std::sync::RwLock::new(runtime.config().unwrap()),
+        database: Mutex::new(Some(runtime.database().await.unwrap())),
+        replicas: Mutex::new(None),
+      };
+      tasks.push(tokio::spawn(async move {
+        runtime
+          .acquire_coordination_lease(
+            "rust-test:lease:concurrent".to_string(),
+            format!("owner-{index}"),
+            30_000,
+          )
+          .await
+          .unwrap()
+          .is_some()
+      }));
+    }
+    let mut successful = 0;
+    for task in tasks {
+      if task.await.unwrap() {
+        successful += 1;
+      }
+    }
+    assert_eq!(successful, 1);
 
-  let renew = runtime
-    .acquire_coordination_lease("rust-test:lease:renew".to_string(), "owner-1".to_string(), 30_000)
-    .await
-    .unwrap()
-    .expect("renew lease owner should acquire");
-  assert!(
-    !runtime
-      .renew_coordination_lease(renew.key.clone(), "owner-2".to_string(), renew.fencing_token, 30_000)
+    let stale = runtime
+      .acquire_coordination_lease("rust-test:lease:stale".to_string(), "owner-1".to_string(), 1)
       .await
       .unwrap()
-  );
-  assert!(
-    !runtime
-      .renew_coordination_lease(renew.key.clone(), renew.owner.clone(), renew.fencing_token + 1, 30_000)
+      .expect("stale lease owner should acquire");
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let takeover = runtime
+      .acquire_coordination_lease("rust-test:lease:stale".to_string(), "owner-2".to_string(), 30_000)
       .await
       .unwrap()
-  );
-  assert!(
-    runtime
-      .renew_coordination_lease(renew.key.clone(), renew.owner.clone(), renew.fencing_token, 30_000)
+      .expect("expired lease should be taken over");
+    assert_eq!(takeover.fencing_token, stale.fencing_token + 1);
+    assert!(
+      !runtime
+        .release_coordination_lease(stale.key.clone(), stale.owner.clone(), stale.fencing_token)
+        .await
+        .unwrap()
+    );
+
+    let renew = runtime
+      .acquire_coordination_lease("rust-test:lease:renew".to_string(), "owner-1".to_string(), 30_000)
       .await
       .unwrap()
-  );
+      .expect("renew lease owner should acquire");
+    assert!(
+      !runtime
+        .renew_coordination_lease(renew.key.clone(), "owner-2".to_string(), renew.fencing_token, 30_000)
+        .await
+        .unwrap()
+    );
+    assert!(
+      !runtime
+        .renew_coordination_lease(renew.key.clone(), renew.owner.clone(), renew.fencing_token + 1, 30_000)
+        .await
+        .unwrap()
+    );
+    assert!(
+      runtime
+        .renew_coordination_lease(renew.key.clone(), renew.owner.clone(), renew.fencing_token, 30_000)
+        .await
+        .unwrap()
+    );
+  }
 }
 
 #[tokio::test]
-async fn runtime_state_cleanup_deletes_expired_and_consumed_rows() {
+async fn coordination_lease_guard_renews_and_fences_writes() {
   let _guard = pg_test_lock().lock().await;
   let Some(runtime) = runtime_from_database_url().await.unwrap() else {
     eprintln!("skipping postgres integration test: DATABASE_URL is not set");
     return;
   };
+  let pool = runtime.pool().await.unwrap();
 
+  let lease = runtime
+    .acquire_coordination_lease_guard("rust-test:lease:guard".to_string(), "owner-1".to_string(), 150)
+    .await
+    .unwrap()
+    .expect("guard owner should acquire lease");
+  tokio::time::sleep(Duration::from_millis(400)).await;
+  assert!(!lease.is_lost());
   assert!(
     runtime
-      .create_auth_challenge(
-        "rust_test:cleanup".to_string(),
-        "expired".to_string(),
-        serde_json::json!({}),
-        1
-      )
-      .await
-      .unwrap()
-  );
-  assert!(
-    runtime
-      .create_auth_challenge(
-        "rust_test:cleanup".to_string(),
-        "consumed".to_string(),
-        serde_json::json!({}),
-        30_000,
-      )
-      .await
-      .unwrap()
-  );
-  assert!(
-    runtime
-      .consume_auth_challenge("rust_test:cleanup".to_string(), "consumed".to_string())
+      .acquire_coordination_lease("rust-test:lease:guard".to_string(), "owner-2".to_string(), 30_000)
       .await
       .unwrap()
-      .is_some()
+      .is_none()
   );
-  tokio::time::sleep(Duration::from_millis(20)).await;
+  let mut tx = pool.begin().await.unwrap();
+  lease.assert_held(&mut tx).await.unwrap();
+  tx.commit().await.unwrap();
+  assert_eq!(lease.until_lost(async { 42 }).await, Some(42));
 
-  assert_eq!(runtime.cleanup_expired_runtime_states(100).await.unwrap(), 2);
-  assert_eq!(runtime.cleanup_expired_runtime_states(100).await.unwrap(), 0);
+  sqlx::query("UPDATE runtime_leases SET owner = 'thief' WHERE key = 'rust-test:lease:guard'")
+    .execute(&pool)
+    .await
+    .unwrap();
+  assert_eq!(lease.until_lost(tokio::time::sleep(Duration::from_secs(5))).await, None);
+  assert!(lease.is_lost());
+  let mut tx = pool.begin().await.unwrap();
+  assert!(lease.assert_held(&mut tx).await.is_err());
+  tx.rollback().await.unwrap();
+  assert!(!lease.release().await.unwrap());
 }
 
 #[tokio::test]
-async fn verification_token_sql_state_machine_handles_keep_verify_and_cleanup() {
+async fn runtime_job_queue_dedupes_claims_and_dead_letters() {
   let _guard = pg_test_lock().lock().await;
   let Some(runtime) = runtime_from_database_url().await.unwrap() else {
     eprintln!("skipping postgres integration test: DATABASE_URL is not set");
     return;
   };
 
-  let mismatch_token = runtime
-    .create_verification_token(
-      TEST_VERIFICATION_TOKEN_TYPE,
-      Some("user@affine.test".to_string()),
-      30_000,
-    )
+  let queue = "rust-test:jobs".to_string();
+  let enqueue = |dedupe_key: Option<&str>| types::RuntimeJobEnqueueInput {
+    queue: queue.clone(),
+    payload: serde_json::json!({ "docId": "doc-1" }),
+    dedupe_key: dedupe_key.map(str::to_string),
+    run_at_ms: None,
+    max_attempts: Some(2),
+  };
+
+  let first = runtime.enqueue_runtime_job(enqueue(Some("doc-1"))).await.unwrap();
+  assert!(!first.deduplicated);
+  let duplicate = runtime.enqueue_runtime_job(enqueue(Some("doc-1"))).await.unwrap();
+  assert!(duplicate.deduplicated);
+  assert_eq!(duplicate.job_id, first.job_id);
+
+  let claimed = runtime
+    .claim_runtime_jobs(queue.clone(), "worker-1".to_string(), 30_000, 10)
     .await
     .unwrap();
+  assert_eq!(claimed.len(), 1);
+  assert_eq!(claimed[0].attempts, 1);
   assert!(
     runtime
-      .verify_verification_token(
-        TEST_VERIFICATION_TOKEN_TYPE,
-        mismatch_token.clone(),
-        Some("wrong@affine.test".to_string()),
-        None,
-      )
+      .claim_runtime_jobs(queue.clone(), "worker-2".to_string(), 30_000, 10)
       .await
       .unwrap()
-      .is_none()
+      .is_empty()
   );
   assert!(
-    runtime
-      .verify_verification_token(
-        TEST_VERIFICATION_TOKEN_TYPE,
-        mismatch_token.clone(),
-        Some("user@affine.test".to_string()),
-        None,
-      )
+    !runtime
+      .heartbeat_runtime_job(first.job_id.clone(), "worker-2".to_string(), 30_000)
       .await
       .unwrap()
-      .is_some()
   );
   assert!(
     runtime
-      .verify_verification_token(
-        TEST_VERIFICATION_TOKEN_TYPE,
-        mismatch_token.clone(),
-        Some("user@affine.test".to_string()),
-        None,
-      )
+      .heartbeat_runtime_job(first.job_id.clone(), "worker-1".to_string(), 30_000)
       .await
       .unwrap()
-      .is_none()
   );
 
-  let keep_token = runtime
-    .create_verification_token(
-      TEST_VERIFICATION_TOKEN_TYPE,
-      Some("keep@affine.test".to_string()),
-      30_000,
-    )
+  let retry = runtime
+    .fail_runtime_job(first.job_id.clone(), "worker-1".to_string(), "boom".to_string())
+    .await
+    .unwrap()
+    .expect("claimed job should fail");
+  assert_eq!(retry.status, "pending");
+  assert!(retry.next_run_at_ms.is_some());
+  sqlx::query("UPDATE runtime_jobs SET run_at = CURRENT_TIMESTAMP WHERE id = $1::uuid")
+    .bind(&first.job_id)
+    .execute(&runtime.pool().await.unwrap())
+    .await
+    .unwrap();
+
+  let reclaimed = runtime
+    .claim_runtime_jobs(queue.clone(), "worker-2".to_string(), 1, 10)
     .await
     .unwrap();
+  assert_eq!(reclaimed.len(), 1);
+  assert_eq!(reclaimed[0].attempts, 2);
+  tokio::time::sleep(Duration::from_millis(20)).await;
   assert!(
     runtime
-      .get_verification_token(TEST_VERIFICATION_TOKEN_TYPE, keep_token.clone(), Some(true))
+      .claim_runtime_jobs(queue.clone(), "worker-3".to_string(), 30_000, 10)
       .await
       .unwrap()
-      .is_some()
+      .is_empty()
   );
+  assert!(runtime.requeue_dead_runtime_job(first.job_id.clone()).await.unwrap());
+
+  let requeued = runtime
+    .claim_runtime_jobs(queue.clone(), "worker-3".to_string(), 30_000, 10)
+    .await
+    .unwrap();
+  assert_eq!(requeued.len(), 1);
   assert!(
     runtime
-      .get_verification_token(TEST_VERIFICATION_TOKEN_TYPE, keep_token.clone(), None)
+      .complete_runtime_job(first.job_id.clone(), "worker-3".to_string())
       .await
       .unwrap()
-      .is_some()
+  );
+  let next = runtime.enqueue_runtime_job(enqueue(Some("doc-1"))).await.unwrap();
+  assert!(!next.deduplicated);
+  assert_eq!(runtime.cleanup_completed_runtime_jobs(100).await.unwrap(), 1);
+
+  sqlx::query("UPDATE runtime_jobs SET status = 'dead' WHERE id = $1::uuid")
+    .bind(&next.job_id)
+    .execute(&runtime.pool().await.unwrap())
+    .await
+    .unwrap();
+  let live = runtime.enqueue_runtime_job(enqueue(Some("doc-1"))).await.unwrap();
+  assert!(!live.deduplicated);
+  assert!(!runtime.requeue_dead_runtime_job(next.job_id.clone()).await.unwrap());
+  assert!(
+    runtime
+      .requeue_dead_runtime_job("not-a-uuid".to_string())
+      .await
+      .is_err()
   );
   assert!(
     runtime
-      .get_verification_token(TEST_VERIFICATION_TOKEN_TYPE, keep_token.clone(), None)
+      .complete_runtime_job("not-a-uuid".to_string(), "worker-3".to_string())
       .await
-      .unwrap()
-      .is_none()
+      .is_err()
   );
+}
+
+#[tokio::test]
+async fn audit_events_are_hash_chained_and_paginated() {
+  let _guard = pg_test_lock().lock().await;
+  let Some(runtime) = runtime_from_database_url().await.unwrap() else {
+    eprintln!("skipping postgres integration test: DATABASE_URL is not set");
+    return;
+  };
+  let event = |action: &str, details: serde_json::Value| types::RuntimeAuditEventInput {
+    workspace_id: Some("rust-test:audit-workspace".to_string()),
+    actor_id: Some("rust-test:admin".to_string()),
+    action: format!("rust-test:{action}"),
+    target_type: Some("invite_link".to_string()),
+    target_id: Some("rust-test:invite".to_string()),
+    details: Some(details),
+  };
 
-  let concurrent_token = runtime
-    .create_verification_token(
-      TEST_VERIFICATION_TOKEN_TYPE,
-      Some("concurrent@affine.test".to_string()),
-      30_000,
-    )
+  let first = runtime
+    .append_audit_event(event(
+      "invite_link.create",
+      serde_json::json!({ "b": 1.0, "a": [true] }),
+    ))
     .await
     .unwrap();
-  let mut tasks = Vec::new();
-  for _ in 0..16 {
-    let runtime = BackendRuntime {
-      config: std::sync::RwLock::new(runtime.config().unwrap()),
-      pool: Mutex::new(Some(runtime.pool().await.unwrap())),
-    };
-    let token = concurrent_token.clone();
-    tasks.push(tokio::spawn(async move {
-      runtime
-        .verify_verification_token(
-          TEST_VERIFICATION_TOKEN_TYPE,
-          token,
-          Some("concurrent@affine.test".to_string()),
-          None,
-        )
-        .await
-        .unwrap()
-        .is_some()
-    }));
-  }
-  let mut successful = 0;
-  for task in tasks {
-    if task.await.unwrap() {
-      successful += 1;
-    }
-  }
-  assert_eq!(successful, 1);
-
-  let expired_token = runtime
-    .create_verification_token(TEST_VERIFICATION_TOKEN_TYPE, Some("expired@affine.test".to_string()), 1)
+  let second = runtime
+    .append_audit_event(event("permission.change", serde_json::json!({ "role": "admin" })))
     .await
     .unwrap();
-  tokio::time::sleep(Duration::from_millis(20)).await;
-  assert!(
-    runtime
-      .get_verification_token(TEST_VERIFICATION_TOKEN_TYPE, expired_token.clone(), None)
-      .await
-      .unwrap()
-      .is_none()
+  let third = runtime
+    .append_audit_event(event("invite_link.revoke", serde_json::json!({})))
+    .await
+    .unwrap();
+  assert_eq!(second.prev_hash, first.hash);
+  assert_eq!(third.prev_hash, second.hash);
+
+  let query = |cursor: Option<String>| types::RuntimeAuditEventQuery {
+    workspace_id: Some("rust-test:audit-workspace".to_string()),
+    actor_id: Some("rust-test:admin".to_string()),
+    action: None,
+    since_ms: Some(first.created_at_ms),
+    until_ms: None,
+    cursor,
+    limit: Some(2),
+  };
+  let page = runtime.list_audit_events(query(None)).await.unwrap();
+  assert_eq!(
+    page.events.iter().map(|event| event.seq).collect::<Vec<_>>(),
+    [third.seq, second.seq]
+  );
+  let page = runtime.list_audit_events(query(page.next_cursor)).await.unwrap();
+  assert_eq!(
+    page.events.iter().map(|event| event.seq).collect::<Vec<_>>(),
+    [first.seq]
   );
-  assert_eq!(runtime.cleanup_expired_verification_tokens(100).await.unwrap(), 1);
-  assert_eq!(runtime.cleanup_expired_verification_tokens(100).await.unwrap(), 0);
+  assert!(page.next_cursor.is_none());
+
+  let verified = runtime.verify_audit_chain(Some(first.seq - 1), 3).await.unwrap();
+  assert_eq!(verified.checked, 3);
+  assert_eq!(verified.last_seq, Some(third.seq));
+  assert!(verified.broken_at_seq.is_none());
+
+  let pool = runtime.pool().await.unwrap();
+  sqlx::query("UPDATE audit_events SET details = '{\"role\":\"owner\"}' WHERE seq = $1")
+    .bind(second.seq)
+    .execute(&pool)
+    .await
+    .unwrap();
+  let tampered = runtime.verify_audit_chain(Some(first.seq - 1), 3).await.unwrap();
+  assert_eq!(tampered.checked, 1);
+  assert_eq!(tampered.broken_at_seq, Some(second.seq));
+}
+
+#[tokio::test]
+async fn doc_data_keys_rotate_without_losing_sealed_blobs() {
+  let _guard = pg_test_lock().lock().await;
+  let Some(runtime) = runtime_from_database_url().await.unwrap() else {
+    eprintln!("skipping postgres integration test: DATABASE_URL is not set");
+    return;
+  };
+  let workspace_id = "rust-test:doc-keys";
+  let use_master_keys = |master_key: &str, previous: &[&str]| {
+    let doc_encryption = DocEncryptionConfig {
+      master_key: Some(master_key.to_string()),
+      previous_master_keys: previous.iter().map(|key| key.to_string()).collect(),
+    };
+    runtime
+      .update_config(BackendRuntimeConfig {
+        doc_encryption,
+        ..runtime.config().unwrap()
+      })
+      .unwrap();
+    runtime.doc_master_keys().unwrap()
+  };
+  let pool = runtime.pool().await.unwrap();
+  let mut conn = pool.acquire().await.unwrap();
+
+  let master_keys = use_master_keys("rust-test-doc-master-old", &[]);
+  let (first, second) = tokio::join!(
+    async {
+      let mut conn = pool.acquire().await.unwrap();
+      doc_encryption::WorkspaceDocCipher::for_write(&mut conn, &master_keys, workspace_id).await
+    },
+    async {
+      let mut conn = pool.acquire().await.unwrap();
+      doc_encryption::WorkspaceDocCipher::for_write(&mut conn, &master_keys, workspace_id).await
+    },
+  );
+  let sealed_v1 = first.unwrap().encrypt("doc", b"sealed with v1").unwrap();
+  assert_ne!(sealed_v1, b"sealed with v1");
+  assert_eq!(
+    second.unwrap().decrypt("doc", sealed_v1.clone()).unwrap(),
+    b"sealed with v1"
+  );
+
+  let rotated = runtime
+    .rotate_workspace_doc_key(workspace_id.to_string())
+    .await
+    .unwrap();
+  assert_eq!(rotated.version, 2);
+  assert!(rotated.active);
+  let keys = runtime.list_workspace_doc_keys(workspace_id.to_string()).await.unwrap();
+  assert_eq!(
+    keys
+      .iter()
+      .map(|key| (key.version, key.active, key.retired_at_ms.is_some()))
+      .collect::<Vec<_>>(),
+    [(1, false, true), (2, true, false)]
+  );
+
+  let cipher = doc_encryption::WorkspaceDocCipher::for_write(&mut conn, &master_keys, workspace_id)
+    .await
+    .unwrap();
+  let sealed_v2 = cipher.encrypt("doc", b"sealed with v2").unwrap();
+  assert_ne!(sealed_v2[..12], sealed_v1[..12]);
+  assert_eq!(cipher.decrypt("doc", sealed_v1.clone()).unwrap(), b"sealed with v1");
+
+  use_master_keys("rust-test-doc-master-new", &["rust-test-doc-master-old"]);
+  assert_eq!(runtime.rewrap_doc_data_keys(100).await.unwrap(), 2);
+  assert_eq!(runtime.rewrap_doc_data_keys(100).await.unwrap(), 0);
+
+  let master_keys = use_master_keys("rust-test-doc-master-new", &[]);
+  let cipher = doc_encryption::WorkspaceDocCipher::for_read(&mut conn, &master_keys, workspace_id)
+    .await
+    .unwrap();
+  assert_eq!(cipher.decrypt("doc", sealed_v1).unwrap(), b"sealed with v1");
+  assert_eq!(cipher.decrypt("doc", sealed_v2).unwrap(), b"sealed with v2");
+}
+
+#[tokio::test]
+async fn runtime_state_cleanup_deletes_expired_and_consumed_rows() {
+  let _guard = pg_test_lock().lock().await;
+  for runtime in test_runtimes().await {
+    assert!(
+      runtime
+        .create_auth_challenge(
+          "rust_test:cleanup".to_string(),
+          "expired".to_string(),
+          serde_json::json!({}),
+          1
+        )
+        .await
+        .unwrap()
+    );
+    assert!(
+      runtime
+        .create_auth_challenge(
+          "rust_test:cleanup".to_string(),
+          "consumed".to_string(),
+          serde_json::json!({}),
+          30_000,
+        )
+        .await
+        .unwrap()
+    );
+    assert!(
+      runtime
+        .consume_auth_challenge("rust_test:cleanup".to_string(), "consumed".to_string())
+        .await
+        .unwrap()
+        .is_some()
+    );
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    assert_eq!(runtime.cleanup_expired_runtime_states(100).await.unwrap(), 2);
+    assert_eq!(runtime.cleanup_expired_runtime_states(100).await.unwrap(), 0);
+  }
+}
+
+#[tokio::test]
+async fn totp_enrollment_limits_attempts_and_disables_cleanly() {
+  let _guard = pg_test_lock().lock().await;
+  for runtime in test_runtimes().await {
+    let user_id = "rust-test:totp-user".to_string();
+
+    let not_enrolled = runtime
+      .verify_totp(user_id.clone(), "000000".to_string())
+      .await
+      .unwrap();
+    assert!(!not_enrolled.ok);
+    assert_eq!(not_enrolled.reason.as_deref(), Some("not_enrolled"));
+
+    let enrollment = runtime
+      .begin_totp_enrollment(
+        user_id.clone(),
+        "AFFiNE".to_string(),
+        "user@affine.test".to_string(),
+        30_000,
+      )
+      .await
+      .unwrap();
+    assert_eq!(enrollment.secret.len(), 32);
+    assert!(
+      enrollment
+        .otpauth_url
+        .starts_with("otpauth://totp/AFFiNE:user%40affine.test?")
+    );
+    let stored: String = match runtime.database().await.unwrap() {
+      RuntimeDatabase::Postgres(pool) => sqlx::query_scalar(
+        "SELECT payload->>'secret' FROM runtime_states WHERE purpose = 'totp:enrollment' AND lookup_key = $1",
+      )
+      .bind(&user_id)
+      .fetch_one(&pool)
+      .await
+      .unwrap(),
+      RuntimeDatabase::Sqlite(pool) => sqlx::query_scalar(
+        "SELECT json_extract(payload, '$.secret') FROM runtime_states WHERE purpose = 'totp:enrollment' AND lookup_key = ?1",
+      )
+      .bind(&user_id)
+      .fetch_one(&pool)
+      .await
+      .unwrap(),
+    };
+    assert!(!stored.contains(&enrollment.secret));
+
+    let mut reasons = Vec::new();
+    for _ in 0..7 {
+      let result = runtime
+        .confirm_totp_enrollment(user_id.clone(), "not-a-code".to_string())
+        .await
+        .unwrap();
+      assert!(!result.ok);
+      reasons.push(result.reason.unwrap());
+    }
+    assert_eq!(
+      reasons,
+      [
+        "invalid_code",
+        "invalid_code",
+        "invalid_code",
+        "invalid_code",
+        "locked",
+        "locked",
+        "not_found"
+      ]
+    );
+
+    assert!(
+      runtime
+        .regenerate_totp_recovery_codes(user_id.clone())
+        .await
+        .unwrap()
+        .is_none()
+    );
+    assert!(
+      !runtime
+        .consume_totp_recovery_code(user_id.clone(), "abcde-fghjk".to_string())
+        .await
+        .unwrap()
+    );
+    assert!(!runtime.disable_totp(user_id).await.unwrap());
+  }
+}
+
+#[tokio::test]
+async fn workspace_invite_links_track_uses_and_revoke_by_id() {
+  let _guard = pg_test_lock().lock().await;
+  for runtime in test_runtimes().await {
+    let workspace_id = "rust-test:invite-workspace".to_string();
+
+    let default_link = runtime
+      .create_workspace_invite_link(
+        workspace_id.clone(),
+        "rust-test:invite-default".to_string(),
+        "rust-test:owner".to_string(),
+        60_000,
+      )
+      .await
+      .unwrap();
+    assert_eq!(default_link.uses, 0);
+    assert!(default_link.max_uses.is_none());
+
+    let limited = runtime
+      .add_workspace_invite_link(types::RuntimeWorkspaceInviteLinkInput {
+        workspace_id: workspace_id.clone(),
+        invite_id: "rust-test:invite-limited".to_string(),
+        inviter_user_id: "rust-test:owner".to_string(),
+        role: Some("external".to_string()),
+        max_uses: Some(2),
+        email_domain: Some("Affine.Test".to_string()),
+        ttl_ms: 60_000,
+      })
+      .await
+      .unwrap();
+    assert_eq!(limited.email_domain.as_deref(), Some("affine.test"));
+
+    let links = runtime.list_workspace_invite_links(workspace_id.clone()).await.unwrap();
+    assert_eq!(
+      links.iter().map(|link| link.invite_id.as_str()).collect::<Vec<_>>(),
+      ["rust-test:invite-default", "rust-test:invite-limited"]
+    );
+
+    let mismatch = runtime
+      .redeem_workspace_invite_link(limited.invite_id.clone(), Some("user@example.com".to_string()))
+      .await
+      .unwrap();
+    assert_eq!(mismatch.reason.as_deref(), Some("domain_mismatch"));
+
+    let redeem = |email: &str| runtime.redeem_workspace_invite_link(limited.invite_id.clone(), Some(email.to_string()));
+    let (first, second, third) = tokio::join!(
+      redeem("one@AFFINE.test"),
+      redeem("two@affine.test"),
+      redeem("three@affine.test")
+    );
+    let redeemed = [first.unwrap(), second.unwrap(), third.unwrap()];
+    assert_eq!(redeemed.iter().filter(|result| result.ok).count(), 2);
+    assert!(
+      redeemed
+        .iter()
+        .any(|result| result.reason.as_deref() == Some("exhausted"))
+    );
+    assert_eq!(
+      runtime
+        .get_workspace_invite_link_by_id(limited.invite_id.clone())
+        .await
+        .unwrap()
+        .unwrap()
+        .uses,
+      2
+    );
+
+    let open = runtime
+      .redeem_workspace_invite_link(default_link.invite_id.clone(), None)
+      .await
+      .unwrap();
+    assert!(open.ok);
+    assert_eq!(
+      runtime
+        .get_workspace_invite_link(workspace_id.clone())
+        .await
+        .unwrap()
+        .unwrap()
+        .uses,
+      1
+    );
+
+    assert!(
+      runtime
+        .revoke_workspace_invite_link_by_id(default_link.invite_id.clone())
+        .await
+        .unwrap()
+    );
+    assert!(
+      runtime
+        .get_workspace_invite_link(workspace_id.clone())
+        .await
+        .unwrap()
+        .is_none()
+    );
+    assert_eq!(
+      runtime
+        .redeem_workspace_invite_link(default_link.invite_id.clone(), None)
+        .await
+        .unwrap()
+        .reason
+        .as_deref(),
+      Some("not_found")
+    );
+    assert_eq!(
+      runtime.list_workspace_invite_links(workspace_id).await.unwrap().len(),
+      1
+    );
+  }
+}
+
+#[tokio::test]
+async fn verification_token_sql_state_machine_handles_keep_verify_and_cleanup() {
+  let _guard = pg_test_lock().lock().await;
+  for runtime in test_runtimes().await {
+    let mismatch_token = runtime
+      .create_verification_token(
+        TEST_VERIFICATION_TOKEN_TYPE,
+        Some("user@affine.test".to_string()),
+        30_000,
+      )
+      .await
+      .unwrap();
+    assert!(
+      runtime
+        .verify_verification_token(
+          TEST_VERIFICATION_TOKEN_TYPE,
+          mismatch_token.clone(),
+          Some("wrong@affine.test".to_string()),
+          None,
+        )
+        .await
+        .unwrap()
+        .is_none()
+    );
+    assert!(
+      runtime
+        .verify_verification_token(
+          TEST_VERIFICATION_TOKEN_TYPE,
+          mismatch_token.clone(),
+          Some("user@affine.test".to_string()),
+          None,
+        )
+        .await
+        .unwrap()
+        .is_some()
+    );
+    assert!(
+      runtime
+        .verify_verification_token(
+          TEST_VERIFICATION_TOKEN_TYPE,
+          mismatch_token.clone(),
+          Some("user@affine.test".to_string()),
+          None,
+        )
+        .await
+        .unwrap()
+        .is_none()
+    );
+
+    let keep_token = runtime
+      .create_verification_token(
+        TEST_VERIFICATION_TOKEN_TYPE,
+        Some("keep@affine.test".to_string()),
+        30_000,
+      )
+      .await
+      .unwrap();
+    assert!(
+      runtime
+        .get_verification_token(TEST_VERIFICATION_TOKEN_TYPE, keep_token.clone(), Some(true))
+        .await
+        .unwrap()
+        .is_some()
+    );
+    assert!(
+      runtime
+        .get_verification_token(TEST_VERIFICATION_TOKEN_TYPE, keep_token.clone(), None)
+        .await
+        .unwrap()
+        .is_some()
+    );
+    assert!(
+      runtime
+        .get_verification_token(TEST_VERIFICATION_TOKEN_TYPE, keep_token.clone(), None)
+        .await
+        .unwrap()
+        .is_none()
+    );
+
+    let concurrent_token = runtime
+      .create_verification_token(
+        TEST_VERIFICATION_TOKEN_TYPE,
+        Some("concurrent@affine.test".to_string()),
+        30_000,
+      )
+      .await
+      .unwrap();
+    let mut tasks = Vec::new();
+    for _ in 0..16 {
+      let runtime = BackendRuntime {
+        config: std::sync::RwLock::new(runtime.config().unwrap()),
+        database: Mutex::new(Some(runtime.database().await.unwrap())),
+        replicas: Mutex::new(None),
+      };
+      let token = concurrent_token.clone();
+      tasks.push(tokio::spawn(async move {
+        runtime
+          .verify_verification_token(
+            TEST_VERIFICATION_TOKEN_TYPE,
+            token,
+            Some("concurrent@affine.test".to_string()),
+            None,
+          )
+          .await
+          .unwrap()
+          .is_some()
+      }));
+    }
+    let mut successful = 0;
+    for task in tasks {
+      if task.await.unwrap() {
+        successful += 1;
+      }
+    }
+    assert_eq!(successful, 1);
+
+    let expired_token = runtime
+      .create_verification_token(TEST_VERIFICATION_TOKEN_TYPE, Some("expired@affine.test".to_string()), 1)
+      .await
+      .unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(
+      runtime
+        .get_verification_token(TEST_VERIFICATION_TOKEN_TYPE, expired_token.clone(), None)
+        .await
+        .unwrap()
+        .is_none()
+    );
+    assert_eq!(runtime.cleanup_expired_verification_tokens(100).await.unwrap(), 1);
+    assert_eq!(runtime.cleanup_expired_verification_tokens(100).await.unwrap(), 0);
+  }
+}
+
+fn doc_update(keys: &[&str]) -> Vec<u8> {
+  let doc = y_octo::Doc::default();
+  let mut map = doc.get_or_create_map("rust-test").unwrap();
+  for key in keys {
+    map.insert(key.to_string(), true).unwrap();
+  }
+  doc.encode_update_v1().unwrap()
+}
+
+fn doc_keys(blob: &[u8]) -> Vec<String> {
+  let mut doc = y_octo::Doc::default();
+  doc.apply_update_from_binary_v1(blob).unwrap();
+  let mut keys = doc
+    .get_or_create_map("rust-test")
+    .unwrap()
+    .keys()
+    .map(|key| key.to_string())
+    .collect::<Vec<_>>();
+  keys.sort();
+  keys
+}
+
+fn doc_history_input(
+  workspace_id: &str,
+  doc_id: &str,
+  blob: &[u8],
+  timestamp_ms: i64,
+  max_age_ms: i64,
+) -> RuntimeDocHistoryInput {
+  RuntimeDocHistoryInput {
+    workspace_id: workspace_id.to_string(),
+    doc_id: doc_id.to_string(),
+    blob: blob.to_vec().into(),
+    timestamp_ms,
+    editor_id: Some("rust-test:editor".to_string()),
+    force: false,
+    history_min_interval_ms: 0,
+    history_max_age_ms: max_age_ms,
+  }
+}
+
+#[tokio::test]
+async fn doc_storage_round_trips_snapshots_updates_and_histories() {
+  let _guard = pg_test_lock().lock().await;
+  for runtime in test_runtimes().await {
+    let (workspace_id, doc_id) = ("rust-test:doc-storage", "doc");
+    runtime
+      .delete_doc_storage(workspace_id.to_string(), doc_id.to_string())
+      .await
+      .unwrap();
+
+    let snapshot = doc_update(&["a"]);
+    assert!(
+      runtime
+        .upsert_doc_snapshot(
+          workspace_id.to_string(),
+          doc_id.to_string(),
+          snapshot.clone().into(),
+          2_000,
+          Some("rust-test:editor".to_string()),
+        )
+        .await
+        .unwrap()
+    );
+    assert!(
+      !runtime
+        .upsert_doc_snapshot(
+          workspace_id.to_string(),
+          doc_id.to_string(),
+          doc_update(&["stale"]).into(),
+          1_000,
+          None,
+        )
+        .await
+        .unwrap()
+    );
+    let stored = runtime
+      .get_doc_snapshot(workspace_id.to_string(), doc_id.to_string())
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(stored.blob.as_ref(), snapshot.as_slice());
+    assert_eq!(stored.timestamp_ms, 2_000);
+    assert_eq!(stored.editor_id.as_deref(), Some("rust-test:editor"));
+
+    let last = runtime
+      .push_doc_updates(
+        workspace_id.to_string(),
+        doc_id.to_string(),
+        vec![doc_update(&["b"]).into(), doc_update(&["c"]).into()],
+        None,
+      )
+      .await
+      .unwrap();
+    let updates = runtime
+      .get_doc_updates(workspace_id.to_string(), doc_id.to_string())
+      .await
+      .unwrap();
+    assert_eq!(
+      updates.iter().map(|update| update.timestamp_ms).collect::<Vec<_>>(),
+      [last - 1, last]
+    );
+    assert_eq!(doc_keys(updates[1].blob.as_ref()), ["c"]);
+
+    let history = doc_history_input(workspace_id, doc_id, &snapshot, 2_000, 60_000);
+    assert!(runtime.create_doc_history(history).await.unwrap());
+    let history = doc_history_input(workspace_id, doc_id, &snapshot, 2_000, 60_000);
+    assert!(!runtime.create_doc_history(history).await.unwrap());
+    let stored = runtime
+      .get_doc_history(workspace_id.to_string(), doc_id.to_string(), 2_000)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(stored.blob.as_ref(), snapshot.as_slice());
+    assert!(
+      runtime
+        .get_doc_history(workspace_id.to_string(), doc_id.to_string(), 1_999)
+        .await
+        .unwrap()
+        .is_none()
+    );
+
+    runtime
+      .delete_doc_storage(workspace_id.to_string(), doc_id.to_string())
+      .await
+      .unwrap();
+    assert!(
+      runtime
+        .get_doc_snapshot(workspace_id.to_string(), doc_id.to_string())
+        .await
+        .unwrap()
+        .is_none()
+    );
+    assert!(
+      runtime
+        .get_doc_updates(workspace_id.to_string(), doc_id.to_string())
+        .await
+        .unwrap()
+        .is_empty()
+    );
+    assert!(
+      runtime
+        .get_doc_history(workspace_id.to_string(), doc_id.to_string(), 2_000)
+        .await
+        .unwrap()
+        .is_none()
+    );
+  }
+}
+
+#[tokio::test]
+async fn doc_compactor_merges_updates_into_snapshot_and_history() {
+  let _guard = pg_test_lock().lock().await;
+  for runtime in test_runtimes().await {
+    let (workspace_id, doc_id) = ("rust-test:doc-compactor", "doc");
+    runtime
+      .delete_doc_storage(workspace_id.to_string(), doc_id.to_string())
+      .await
+      .unwrap();
+    let snapshot = doc_update(&["a"]);
+    runtime
+      .upsert_doc_snapshot(
+        workspace_id.to_string(),
+        doc_id.to_string(),
+        snapshot.clone().into(),
+        1_000,
+        None,
+      )
+      .await
+      .unwrap();
+    let last = runtime
+      .push_doc_updates(
+        workspace_id.to_string(),
+        doc_id.to_string(),
+        vec![doc_update(&["b"]).into(), doc_update(&["c"]).into()],
+        Some("rust-test:editor".to_string()),
+      )
+      .await
+      .unwrap();
+
+    let compact = || {
+      runtime.compact_pending_doc_updates(
+        workspace_id.to_string(),
+        doc_id.to_string(),
+        100,
+        0,
+        3_600,
+        "rust-test:compactor".to_string(),
+        30_000,
+      )
+    };
+    let result = compact().await.unwrap();
+    assert!(result.lease_acquired);
+    assert!(result.merged);
+    assert_eq!(result.updates_merged, 2);
+    assert!(result.history_created);
+
+    assert!(
+      runtime
+        .get_doc_updates(workspace_id.to_string(), doc_id.to_string())
+        .await
+        .unwrap()
+        .is_empty()
+    );
+    let merged = runtime
+      .get_doc_snapshot(workspace_id.to_string(), doc_id.to_string())
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(doc_keys(merged.blob.as_ref()), ["a", "b", "c"]);
+    assert_eq!(merged.timestamp_ms, last);
+    assert_eq!(merged.editor_id.as_deref(), Some("rust-test:editor"));
+    let history = runtime
+      .get_doc_history(workspace_id.to_string(), doc_id.to_string(), 1_000)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(history.blob.as_ref(), snapshot.as_slice());
+
+    let result = compact().await.unwrap();
+    assert!(result.lease_acquired);
+    assert!(!result.merged);
+    assert_eq!(result.updates_merged, 0);
+
+    runtime
+      .delete_doc_storage(workspace_id.to_string(), doc_id.to_string())
+      .await
+      .unwrap();
+  }
+}
+
+#[tokio::test]
+async fn housekeeping_cleans_up_expired_snapshot_histories() {
+  let _guard = pg_test_lock().lock().await;
+  for runtime in test_runtimes().await {
+    let (workspace_id, doc_id) = ("rust-test:housekeeping", "doc");
+    runtime
+      .delete_doc_storage(workspace_id.to_string(), doc_id.to_string())
+      .await
+      .unwrap();
+    let blob = doc_update(&["a"]);
+    assert!(
+      runtime
+        .create_doc_history(doc_history_input(workspace_id, doc_id, &blob, 1_000, 1))
+        .await
+        .unwrap()
+    );
+    assert!(
+      runtime
+        .create_doc_history(doc_history_input(workspace_id, doc_id, &blob, 2_000, 60_000))
+        .await
+        .unwrap()
+    );
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    assert!(runtime.cleanup_expired_snapshot_histories(100).await.unwrap() >= 1);
+    assert!(
+      runtime
+        .get_doc_history(workspace_id.to_string(), doc_id.to_string(), 1_000)
+        .await
+        .unwrap()
+        .is_none()
+    );
+    assert!(
+      runtime
+        .get_doc_history(workspace_id.to_string(), doc_id.to_string(), 2_000)
+        .await
+        .unwrap()
+        .is_some()
+    );
+
+    runtime
+      .delete_doc_storage(workspace_id.to_string(), doc_id.to_string())
+      .await
+      .unwrap();
+  }
 }
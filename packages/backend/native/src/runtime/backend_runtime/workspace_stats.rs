@@ -4,9 +4,10 @@ use tokio::time::{Duration as TokioDuration, sleep};
 use super::{
   BackendRuntime, RuntimeError, RuntimeResult,
   constants::{WORKSPACE_STATS_LEASE_KEY, WORKSPACE_STATS_LOCK_NAMESPACE, WORKSPACE_STATS_REFRESH_LOCK_KEY},
+  coordination_lease::CoordinationLeaseGuard,
   napi_error,
   types::{
-    CoordinationLeaseGrant, RuntimeWorkspaceStatsDailyRecalibrationResult, RuntimeWorkspaceStatsRecalibrationResult,
+    RuntimeWorkspaceStatsDailyRecalibrationResult, RuntimeWorkspaceStatsRecalibrationResult,
     RuntimeWorkspaceStatsRefreshResult, RuntimeWorkspaceStatsSnapshotResult,
   },
 };
@@ -27,7 +28,7 @@ impl BackendRuntime {
     }
 
     let Some(lease) = self
-      .acquire_coordination_lease_inner(WORKSPACE_STATS_LEASE_KEY.to_string(), owner, lease_ttl_ms)
+      .acquire_coordination_lease_guard(WORKSPACE_STATS_LEASE_KEY.to_string(), owner, lease_ttl_ms)
       .await?
     else {
       return Ok(RuntimeWorkspaceStatsRefreshResult {
@@ -38,13 +39,15 @@ impl BackendRuntime {
     };
 
     let result = async {
-      WorkspaceStatsStore::new(self.pool().await?)
-        .refresh_dirty(batch_limit)
+      let store = WorkspaceStatsStore::new(self.pool().await?);
+      lease
+        .until_lost(store.refresh_dirty(&lease, batch_limit))
         .await
+        .unwrap_or_else(|| Err(lost_workspace_stats_lease()))
     }
     .await;
 
-    release_workspace_stats_lease(self, lease).await?;
+    release_workspace_stats_lease(lease).await?;
     Ok(result?)
   }
 
@@ -61,7 +64,7 @@ impl BackendRuntime {
     }
 
     let Some(lease) = self
-      .acquire_coordination_lease_inner(WORKSPACE_STATS_LEASE_KEY.to_string(), owner, lease_ttl_ms)
+      .acquire_coordination_lease_guard(WORKSPACE_STATS_LEASE_KEY.to_string(), owner, lease_ttl_ms)
       .await?
     else {
       return Ok(RuntimeWorkspaceStatsRecalibrationResult {
@@ -72,13 +75,15 @@ impl BackendRuntime {
     };
 
     let result = async {
-      WorkspaceStatsStore::new(self.pool().await?)
-        .recalibrate(last_sid, batch_limit)
+      let store = WorkspaceStatsStore::new(self.pool().await?);
+      lease
+        .until_lost(store.recalibrate(&lease, last_sid, batch_limit))
         .await
+        .unwrap_or_else(|| Err(lost_workspace_stats_lease()))
     }
     .await;
 
-    release_workspace_stats_lease(self, lease).await?;
+    release_workspace_stats_lease(lease).await?;
     Ok(result?)
   }
 
@@ -89,7 +94,7 @@ impl BackendRuntime {
     lease_ttl_ms: i64,
   ) -> napi::Result<RuntimeWorkspaceStatsSnapshotResult> {
     let Some(lease) = self
-      .acquire_coordination_lease_inner(WORKSPACE_STATS_LEASE_KEY.to_string(), owner, lease_ttl_ms)
+      .acquire_coordination_lease_guard(WORKSPACE_STATS_LEASE_KEY.to_string(), owner, lease_ttl_ms)
       .await?
     else {
       return Ok(RuntimeWorkspaceStatsSnapshotResult {
@@ -99,13 +104,15 @@ impl BackendRuntime {
     };
 
     let result = async {
-      WorkspaceStatsStore::new(self.pool().await?)
-        .write_daily_snapshot()
+      let store = WorkspaceStatsStore::new(self.pool().await?);
+      lease
+        .until_lost(store.write_daily_snapshot(&lease))
         .await
+        .unwrap_or_else(|| Err(lost_workspace_stats_lease()))
     }
     .await;
 
-    release_workspace_stats_lease(self, lease).await?;
+    release_workspace_stats_lease(lease).await?;
     Ok(result?)
   }
 
@@ -155,8 +162,11 @@ impl BackendRuntime {
       let mut last_sid = 0;
 
       loop {
+        if lease.is_lost() {
+          return Err(lost_workspace_stats_lease());
+        }
         let batch = retry_workspace_stats_operation(lock_retry_times, lock_retry_delay_ms, || {
-          store.recalibrate(last_sid, batch_limit)
+          store.recalibrate(&lease, last_sid, batch_limit)
         })
         .await?;
 
@@ -181,8 +191,13 @@ impl BackendRuntime {
         }
       }
 
-      let snapshot =
-        retry_workspace_stats_operation(lock_retry_times, lock_retry_delay_ms, || store.write_daily_snapshot()).await?;
+      if lease.is_lost() {
+        return Err(lost_workspace_stats_lease());
+      }
+      let snapshot = retry_workspace_stats_operation(lock_retry_times, lock_retry_delay_ms, || {
+        store.write_daily_snapshot(&lease)
+      })
+      .await?;
 
       Ok(RuntimeWorkspaceStatsDailyRecalibrationResult {
         processed,
@@ -193,7 +208,7 @@ impl BackendRuntime {
     }
     .await;
 
-    release_workspace_stats_lease(self, lease).await?;
+    release_workspace_stats_lease(lease).await?;
     Ok(result?)
   }
 }
@@ -213,7 +228,11 @@ impl WorkspaceStatsStore {
     Self { pool }
   }
 
-  async fn refresh_dirty(&self, batch_limit: i64) -> RuntimeResult<RuntimeWorkspaceStatsRefreshResult> {
+  async fn refresh_dirty(
+    &self,
+    lease: &CoordinationLeaseGuard,
+    batch_limit: i64,
+  ) -> RuntimeResult<RuntimeWorkspaceStatsRefreshResult> {
     let mut tx = self
       .pool
       .begin()
@@ -245,6 +264,7 @@ impl WorkspaceStatsStore {
 
     upsert_stats(&mut tx, &dirty).await?;
     clear_dirty(&mut tx, &dirty).await?;
+    lease.assert_held(&mut tx).await?;
     tx.commit()
       .await
       .map_err(|err| RuntimeError::database("WorkspaceStats dirty refresh commit failed", err))?;
@@ -258,6 +278,7 @@ impl WorkspaceStatsStore {
 
   async fn recalibrate(
     &self,
+    lease: &CoordinationLeaseGuard,
     last_sid: i64,
     batch_limit: i64,
   ) -> RuntimeResult<RuntimeWorkspaceStatsRecalibrationResult> {
@@ -298,6 +319,7 @@ impl WorkspaceStatsStore {
       .map(|workspace| workspace.sid as i64)
       .unwrap_or(last_sid);
     upsert_stats(&mut tx, &ids).await?;
+    lease.assert_held(&mut tx).await?;
     tx.commit()
       .await
       .map_err(|err| RuntimeError::database("WorkspaceStats recalibration commit failed", err))?;
@@ -309,7 +331,10 @@ impl WorkspaceStatsStore {
     })
   }
 
-  async fn write_daily_snapshot(&self) -> RuntimeResult<RuntimeWorkspaceStatsSnapshotResult> {
+  async fn write_daily_snapshot(
+    &self,
+    lease: &CoordinationLeaseGuard,
+  ) -> RuntimeResult<RuntimeWorkspaceStatsSnapshotResult> {
     let mut tx = self
       .pool
       .begin()
@@ -325,6 +350,7 @@ impl WorkspaceStatsStore {
       });
     }
     let snapshotted = write_daily_snapshot(&mut tx).await?;
+    lease.assert_held(&mut tx).await?;
     tx.commit()
       .await
       .map_err(|err| RuntimeError::database("WorkspaceStats daily snapshot commit failed", err))?;
@@ -336,23 +362,25 @@ impl WorkspaceStatsStore {
   }
 }
 
-async fn release_workspace_stats_lease(runtime: &BackendRuntime, lease: CoordinationLeaseGrant) -> RuntimeResult<()> {
-  let _ = runtime
-    .release_coordination_lease_inner(lease.key, lease.owner, lease.fencing_token)
-    .await?;
+async fn release_workspace_stats_lease(lease: CoordinationLeaseGuard) -> RuntimeResult<()> {
+  let _ = lease.release().await?;
   Ok(())
 }
 
+fn lost_workspace_stats_lease() -> RuntimeError {
+  RuntimeError::invalid_state("WorkspaceStats lost coordination lease")
+}
+
 async fn acquire_workspace_stats_lease_with_retry(
   runtime: &BackendRuntime,
   owner: String,
   lease_ttl_ms: i64,
   retry_times: i64,
   retry_delay_ms: i64,
-) -> RuntimeResult<Option<CoordinationLeaseGrant>> {
+) -> RuntimeResult<Option<CoordinationLeaseGuard>> {
   for attempt in 0..retry_times {
     let lease = runtime
-      .acquire_coordination_lease_inner(WORKSPACE_STATS_LEASE_KEY.to_string(), owner.clone(), lease_ttl_ms)
+      .acquire_coordination_lease_guard(WORKSPACE_STATS_LEASE_KEY.to_string(), owner.clone(), lease_ttl_ms)
       .await?;
     if lease.is_some() {
       return Ok(lease);
@@ -501,7 +529,9 @@ async fn write_daily_snapshot(tx: &mut Transaction<'_, Postgres>) -> RuntimeResu
     INSERT INTO workspace_admin_stats_daily (
       workspace_id,
       date,
+      snapshot_count,
       snapshot_size,
+      blob_count,
       blob_size,
       member_count,
       updated_at
@@ -509,14 +539,18 @@ async fn write_daily_snapshot(tx: &mut Transaction<'_, Postgres>) -> RuntimeResu
     SELECT
       workspace_id,
       CURRENT_DATE,
+      snapshot_count,
       snapshot_size,
+      blob_count,
       blob_size,
       member_count,
       NOW()
     FROM workspace_admin_stats
     ON CONFLICT (workspace_id, date)
     DO UPDATE SET
+      snapshot_count = EXCLUDED.snapshot_count,
       snapshot_size = EXCLUDED.snapshot_size,
+      blob_count = EXCLUDED.blob_count,
       blob_size = EXCLUDED.blob_size,
       member_count = EXCLUDED.member_count,
       updated_at = EXCLUDED.updated_at
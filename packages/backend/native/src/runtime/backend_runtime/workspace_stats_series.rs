@@ -0,0 +1,366 @@
+use chrono::{DateTime, Datelike, Days, NaiveDate, Utc};
+use napi::Result;
+use sqlx::{FromRow, PgPool};
+
+use super::{
+  BackendRuntime, RuntimeError, RuntimeResult,
+  constants::{WORKSPACE_STATS_SERIES_MAX_POINTS, WORKSPACE_STATS_SERIES_MAX_WORKSPACES},
+  napi_error,
+  types::{RuntimeWorkspaceStatsGrowth, RuntimeWorkspaceStatsSeries, RuntimeWorkspaceStatsSeriesPoint},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StatsMetric {
+  StorageBytes,
+  DocCount,
+  MemberCount,
+  BlobCount,
+}
+
+impl StatsMetric {
+  fn parse(name: &str) -> Option<Self> {
+    match name {
+      "storage_bytes" => Some(Self::StorageBytes),
+      "doc_count" => Some(Self::DocCount),
+      "member_count" => Some(Self::MemberCount),
+      "blob_count" => Some(Self::BlobCount),
+      _ => None,
+    }
+  }
+
+  fn expression(self) -> &'static str {
+    match self {
+      Self::StorageBytes => "snapshot_size + blob_size",
+      Self::DocCount => "snapshot_count",
+      Self::MemberCount => "member_count",
+      Self::BlobCount => "blob_count",
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StatsGranularity {
+  Day,
+  Week,
+  Month,
+}
+
+impl StatsGranularity {
+  fn parse(name: &str) -> Option<Self> {
+    match name {
+      "day" => Some(Self::Day),
+      "week" => Some(Self::Week),
+      "month" => Some(Self::Month),
+      _ => None,
+    }
+  }
+
+  fn field(self) -> &'static str {
+    match self {
+      Self::Day => "day",
+      Self::Week => "week",
+      Self::Month => "month",
+    }
+  }
+
+  fn interval(self) -> &'static str {
+    match self {
+      Self::Day => "1 day",
+      Self::Week => "1 week",
+      Self::Month => "1 month",
+    }
+  }
+
+  /// Mirrors Postgres `date_trunc`: weeks start on Monday.
+  fn bucket_start(self, date: NaiveDate) -> NaiveDate {
+    match self {
+      Self::Day => date,
+      Self::Week => date - Days::new(u64::from(date.weekday().num_days_from_monday())),
+      Self::Month => date.with_day(1).unwrap_or(date),
+    }
+  }
+
+  fn bucket_count(self, from: NaiveDate, to: NaiveDate) -> i64 {
+    let start = self.bucket_start(from);
+    match self {
+      Self::Day => (to - start).num_days() + 1,
+      Self::Week => (to - start).num_days() / 7 + 1,
+      Self::Month => i64::from(to.year() - start.year()) * 12 + i64::from(to.month()) - i64::from(start.month()) + 1,
+    }
+  }
+}
+
+#[derive(FromRow)]
+struct SeriesRow {
+  workspace_id: String,
+  bucket_start_ms: i64,
+  storage_bytes: i64,
+  doc_count: i64,
+  member_count: i64,
+  blob_count: i64,
+}
+
+#[derive(FromRow)]
+struct GrowthRow {
+  workspace_id: String,
+  start_value: i64,
+  end_value: i64,
+  growth: i64,
+}
+
+struct WorkspaceStatsSeriesStore {
+  pool: PgPool,
+}
+
+impl WorkspaceStatsSeriesStore {
+  fn new(pool: PgPool) -> Self {
+    Self { pool }
+  }
+
+  async fn series(
+    &self,
+    workspace_ids: &[String],
+    metrics: &[StatsMetric],
+    from: NaiveDate,
+    to: NaiveDate,
+    granularity: StatsGranularity,
+  ) -> RuntimeResult<Vec<RuntimeWorkspaceStatsSeries>> {
+    // Daily rows are gauges, so each bucket reports the latest value on or
+    // before its last day and days without a snapshot carry the previous one.
+    let rows = sqlx::query_as::<_, SeriesRow>(
+      r#"
+      WITH targets AS (
+        SELECT DISTINCT UNNEST($1::varchar[]) AS workspace_id
+      ),
+      buckets AS (
+        SELECT bucket::date AS bucket_start,
+               LEAST((bucket + $4::TEXT::INTERVAL - INTERVAL '1 day')::date, $3) AS bucket_end
+        FROM generate_series(date_trunc($5, $2::timestamp), $3::timestamp, $4::TEXT::INTERVAL) AS bucket
+      )
+      SELECT
+        t.workspace_id,
+        (EXTRACT(EPOCH FROM b.bucket_start::timestamp) * 1000)::BIGINT AS bucket_start_ms,
+        COALESCE(s.snapshot_size + s.blob_size, 0)::BIGINT AS storage_bytes,
+        COALESCE(s.snapshot_count, 0)::BIGINT AS doc_count,
+        COALESCE(s.member_count, 0)::BIGINT AS member_count,
+        COALESCE(s.blob_count, 0)::BIGINT AS blob_count
+      FROM targets t
+      CROSS JOIN buckets b
+      LEFT JOIN LATERAL (
+        SELECT snapshot_size, blob_size, snapshot_count, blob_count, member_count
+        FROM workspace_admin_stats_daily d
+        WHERE d.workspace_id = t.workspace_id
+          AND d.date <= b.bucket_end
+        ORDER BY d.date DESC
+        LIMIT 1
+      ) s ON TRUE
+      ORDER BY t.workspace_id, b.bucket_start
+      "#,
+    )
+    .bind(workspace_ids)
+    .bind(from)
+    .bind(to)
+    .bind(granularity.interval())
+    .bind(granularity.field())
+    .fetch_all(&self.pool)
+    .await
+    .map_err(|err| RuntimeError::database("WorkspaceStats series query failed", err))?;
+
+    let pick = |metric: StatsMetric, value: i64| metrics.contains(&metric).then_some(value);
+    let mut series: Vec<RuntimeWorkspaceStatsSeries> = Vec::new();
+    for row in rows {
+      let point = RuntimeWorkspaceStatsSeriesPoint {
+        bucket_start_ms: row.bucket_start_ms,
+        storage_bytes: pick(StatsMetric::StorageBytes, row.storage_bytes),
+        doc_count: pick(StatsMetric::DocCount, row.doc_count),
+        member_count: pick(StatsMetric::MemberCount, row.member_count),
+        blob_count: pick(StatsMetric::BlobCount, row.blob_count),
+      };
+      match series.last_mut() {
+        Some(current) if current.workspace_id == row.workspace_id => current.points.push(point),
+        _ => series.push(RuntimeWorkspaceStatsSeries {
+          workspace_id: row.workspace_id,
+          points: vec![point],
+        }),
+      }
+    }
+
+    Ok(series)
+  }
+
+  async fn top_growth(
+    &self,
+    metric: StatsMetric,
+    from: NaiveDate,
+    to: NaiveDate,
+    limit: i64,
+  ) -> RuntimeResult<Vec<RuntimeWorkspaceStatsGrowth>> {
+    let expression = metric.expression();
+    let rows = sqlx::query_as::<_, GrowthRow>(&format!(
+      r#"
+      WITH end_values AS (
+        SELECT DISTINCT ON (workspace_id) workspace_id, ({expression})::BIGINT AS value
+        FROM workspace_admin_stats_daily
+        WHERE date <= $2
+        ORDER BY workspace_id, date DESC
+      ),
+      start_values AS (
+        SELECT DISTINCT ON (workspace_id) workspace_id, ({expression})::BIGINT AS value
+        FROM workspace_admin_stats_daily
+        WHERE date < $1
+        ORDER BY workspace_id, date DESC
+      )
+      SELECT
+        e.workspace_id,
+        COALESCE(s.value, 0) AS start_value,
+        e.value AS end_value,
+        e.value - COALESCE(s.value, 0) AS growth
+      FROM end_values e
+      LEFT JOIN start_values s ON s.workspace_id = e.workspace_id
+      ORDER BY growth DESC, e.workspace_id ASC
+      LIMIT $3
+      "#
+    ))
+    .bind(from)
+    .bind(to)
+    .bind(limit)
+    .fetch_all(&self.pool)
+    .await
+    .map_err(|err| RuntimeError::database("WorkspaceStats growth query failed", err))?;
+
+    Ok(
+      rows
+        .into_iter()
+        .map(|row| RuntimeWorkspaceStatsGrowth {
+          workspace_id: row.workspace_id,
+          start_value: row.start_value,
+          end_value: row.end_value,
+          growth: row.growth,
+        })
+        .collect(),
+    )
+  }
+}
+
+fn parse_metric(name: &str) -> Result<StatsMetric> {
+  StatsMetric::parse(name).ok_or_else(|| napi_error(format!("Unknown workspace stats metric: {name}")))
+}
+
+fn parse_date_range(from_ms: i64, to_ms: i64) -> Result<(NaiveDate, NaiveDate)> {
+  let date = |ms: i64| {
+    DateTime::<Utc>::from_timestamp_millis(ms)
+      .map(|timestamp| timestamp.date_naive())
+      .ok_or_else(|| napi_error(format!("Invalid workspace stats timestamp: {ms}")))
+  };
+  let (from, to) = (date(from_ms)?, date(to_ms)?);
+  if from > to {
+    return Err(napi_error("workspace stats range start must not be after its end"));
+  }
+  Ok((from, to))
+}
+
+#[napi_derive::napi]
+impl BackendRuntime {
+  /// Read daily admin stats snapshots back as a gap-filled series.
+  /// `metrics` accepts `storage_bytes`, `doc_count`, `member_count` and
+  /// `blob_count`; `granularity` is `day`, `week` or `month`.
+  #[napi]
+  pub async fn query_workspace_stats_series(
+    &self,
+    workspace_ids: Vec<String>,
+    metrics: Vec<String>,
+    from_ms: i64,
+    to_ms: i64,
+    granularity: String,
+  ) -> Result<Vec<RuntimeWorkspaceStatsSeries>> {
+    if workspace_ids.is_empty() || workspace_ids.len() > WORKSPACE_STATS_SERIES_MAX_WORKSPACES {
+      return Err(napi_error(format!(
+        "workspace stats series needs between 1 and {WORKSPACE_STATS_SERIES_MAX_WORKSPACES} workspaces"
+      )));
+    }
+    if metrics.is_empty() {
+      return Err(napi_error("workspace stats series needs at least one metric"));
+    }
+    let metrics = metrics
+      .iter()
+      .map(|metric| parse_metric(metric))
+      .collect::<Result<Vec<_>>>()?;
+    let granularity = StatsGranularity::parse(&granularity)
+      .ok_or_else(|| napi_error(format!("Unknown workspace stats granularity: {granularity}")))?;
+    let (from, to) = parse_date_range(from_ms, to_ms)?;
+    if granularity.bucket_count(from, to) > WORKSPACE_STATS_SERIES_MAX_POINTS {
+      return Err(napi_error(format!(
+        "workspace stats series is limited to {WORKSPACE_STATS_SERIES_MAX_POINTS} points"
+      )));
+    }
+
+    WorkspaceStatsSeriesStore::new(self.read_pool().await?)
+      .series(&workspace_ids, &metrics, from, to, granularity)
+      .await
+      .map_err(napi::Error::from)
+  }
+
+  /// Rank workspaces by how much `metric` grew between the last snapshot
+  /// before `from_ms` and the last snapshot on or before `to_ms`.
+  #[napi]
+  pub async fn top_workspaces_by_stats_growth(
+    &self,
+    metric: String,
+    from_ms: i64,
+    to_ms: i64,
+    limit: i64,
+  ) -> Result<Vec<RuntimeWorkspaceStatsGrowth>> {
+    if limit <= 0 || limit > WORKSPACE_STATS_SERIES_MAX_WORKSPACES as i64 {
+      return Err(napi_error(format!(
+        "workspace stats growth limit must be between 1 and {WORKSPACE_STATS_SERIES_MAX_WORKSPACES}"
+      )));
+    }
+    let metric = parse_metric(&metric)?;
+    let (from, to) = parse_date_range(from_ms, to_ms)?;
+
+    WorkspaceStatsSeriesStore::new(self.read_pool().await?)
+      .top_growth(metric, from, to, limit)
+      .await
+      .map_err(napi::Error::from)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+  }
+
+  #[test]
+  fn granularity_buckets_match_date_trunc() {
+    // 2026-06-18 is a Thursday.
+    let thursday = date(2026, 6, 18);
+    assert_eq!(StatsGranularity::Day.bucket_start(thursday), thursday);
+    assert_eq!(StatsGranularity::Week.bucket_start(thursday), date(2026, 6, 15));
+    assert_eq!(StatsGranularity::Month.bucket_start(thursday), date(2026, 6, 1));
+  }
+
+  #[test]
+  fn bucket_count_covers_partial_buckets() {
+    let from = date(2026, 6, 18);
+    assert_eq!(StatsGranularity::Day.bucket_count(from, from), 1);
+    assert_eq!(StatsGranularity::Day.bucket_count(from, date(2026, 7, 17)), 30);
+    assert_eq!(StatsGranularity::Week.bucket_count(from, date(2026, 6, 21)), 1);
+    assert_eq!(StatsGranularity::Week.bucket_count(from, date(2026, 6, 22)), 2);
+    assert_eq!(StatsGranularity::Month.bucket_count(from, date(2027, 1, 1)), 8);
+  }
+
+  #[test]
+  fn metrics_and_ranges_are_validated() {
+    assert_eq!(parse_metric("doc_count").unwrap(), StatsMetric::DocCount);
+    assert!(parse_metric("snapshot_size; DROP TABLE workspaces").is_err());
+    assert!(StatsGranularity::parse("year").is_none());
+    assert!(parse_date_range(86_400_000, 0).is_err());
+    assert_eq!(
+      parse_date_range(0, 86_400_000).unwrap(),
+      (date(1970, 1, 1), date(1970, 1, 2))
+    );
+  }
+}
@@ -7,11 +7,43 @@ use serde::Deserialize;
 use serde_json::Map;
 use sqlx::{PgPool, Row};
 
-use super::{RuntimeError, RuntimeResult};
+use super::{
+  RuntimeError, RuntimeResult,
+  config_validation::{BACKEND_SECTIONS, ensure_valid_app_config},
+  database::{DatabasePoolConfig, DatabaseReplicaConfig, RuntimeDatabase},
+};
 
 #[derive(Clone, Debug)]
 pub(crate) struct BackendRuntimeConfig {
   pub(crate) database_url: String,
+  pub(crate) pool: DatabasePoolConfig,
+  pub(crate) replicas: DatabaseReplicaConfig,
+  pub(crate) crypto_private_key: Option<String>,
+  pub(crate) doc_encryption: DocEncryptionConfig,
+}
+
+/// `crypto.docEncryption`: the master key that wraps per-workspace doc data
+/// keys, plus retired master keys that may still wrap older data keys.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DocEncryptionConfig {
+  pub(crate) master_key: Option<String>,
+  #[serde(default)]
+  pub(crate) previous_master_keys: Vec<String>,
+}
+
+impl DocEncryptionConfig {
+  pub(crate) fn with_env_override(mut self) -> Self {
+    if let Some(master_key) = env::var("AFFINE_DOC_ENCRYPTION_KEY").ok().and_then(non_empty_string) {
+      if let Some(previous) = self.master_key.take().and_then(non_empty_string)
+        && previous != master_key
+      {
+        self.previous_master_keys.push(previous);
+      }
+      self.master_key = Some(master_key);
+    }
+    self
+  }
 }
 
 impl BackendRuntimeConfig {
@@ -20,16 +52,31 @@ impl BackendRuntimeConfig {
     let database_url = database_url_from_env()
       .or(app_config.database_url())
       .unwrap_or_else(|| "postgresql://localhost:5432/affine".to_string());
-    Ok(Self { database_url })
+    let crypto_private_key = crypto_private_key_from_env().or(app_config.crypto_private_key());
+    Ok(Self {
+      database_url,
+      pool: app_config.database_pool(),
+      replicas: app_config.database_replicas(),
+      crypto_private_key,
+      doc_encryption: app_config.doc_encryption(),
+    })
   }
 
-  pub(crate) async fn with_db_overrides(&self, pool: &PgPool) -> RuntimeResult<Self> {
+  pub(crate) async fn with_db_overrides(&self, database: &RuntimeDatabase) -> RuntimeResult<Self> {
     let mut app_config = app_config_from_config_files()?;
-    app_config.apply_file_config(load_app_config_overrides_from_db(pool).await?);
+    // `app_configs` is owned by the server's Prisma schema, which the
+    // embedded SQLite database does not carry.
+    if let RuntimeDatabase::Postgres(pool) = database {
+      app_config.apply_file_config(load_app_config_overrides_from_db(pool).await?);
+    }
     Ok(Self {
       // The DB override is loaded after this connection already exists, so it
-      // must not rewrite the active datasource URL.
+      // must not rewrite the active datasource URL or pool settings.
       database_url: self.database_url.clone(),
+      pool: self.pool.clone(),
+      replicas: self.replicas.clone(),
+      crypto_private_key: crypto_private_key_from_env().or(app_config.crypto_private_key()),
+      doc_encryption: app_config.doc_encryption(),
     })
   }
 }
@@ -37,12 +84,22 @@ impl BackendRuntimeConfig {
 #[derive(Debug, Default, Deserialize)]
 struct AppConfigFile {
   db: Option<DbConfigFile>,
+  crypto: Option<CryptoConfigFile>,
 }
 
 #[derive(Debug, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct DbConfigFile {
   datasource_url: Option<String>,
+  pool: Option<DatabasePoolConfig>,
+  replicas: Option<DatabaseReplicaConfig>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CryptoConfigFile {
+  private_key: Option<String>,
+  doc_encryption: Option<DocEncryptionConfig>,
 }
 
 impl AppConfigFile {
@@ -53,12 +110,41 @@ impl AppConfigFile {
       .and_then(|db| db.datasource_url.clone())
       .and_then(non_empty_string)
   }
+
+  fn database_pool(&self) -> DatabasePoolConfig {
+    self.db.as_ref().and_then(|db| db.pool.clone()).unwrap_or_default()
+  }
+
+  fn database_replicas(&self) -> DatabaseReplicaConfig {
+    self.db.as_ref().and_then(|db| db.replicas.clone()).unwrap_or_default()
+  }
+
+  fn crypto_private_key(&self) -> Option<String> {
+    self
+      .crypto
+      .as_ref()
+      .and_then(|crypto| crypto.private_key.clone())
+      .and_then(non_empty_string)
+  }
+
+  fn doc_encryption(&self) -> DocEncryptionConfig {
+    self
+      .crypto
+      .as_ref()
+      .and_then(|crypto| crypto.doc_encryption.clone())
+      .unwrap_or_default()
+      .with_env_override()
+  }
 }
 
 fn database_url_from_env() -> Option<String> {
   env::var("DATABASE_URL").ok().and_then(non_empty_string)
 }
 
+fn crypto_private_key_from_env() -> Option<String> {
+  env::var("AFFINE_PRIVATE_KEY").ok().and_then(non_empty_string)
+}
+
 fn non_empty_string(value: String) -> Option<String> {
   if value.trim().is_empty() { None } else { Some(value) }
 }
@@ -70,8 +156,15 @@ fn app_config_from_config_files() -> RuntimeResult<AppConfigFile> {
       continue;
     }
     let raw = fs::read_to_string(&path).map_err(|err| RuntimeError::io("failed to read config file", err))?;
-    let config: AppConfigFile =
+    let value: serde_json::Value =
       serde_json::from_str(&raw).map_err(|err| RuntimeError::json("failed to parse config file", err))?;
+    ensure_valid_app_config(
+      &value,
+      BACKEND_SECTIONS,
+      &format!("invalid config file {}", path.display()),
+    )?;
+    let config: AppConfigFile =
+      serde_json::from_value(value).map_err(|err| RuntimeError::json("failed to parse config file", err))?;
     merged.apply_file_config(config);
   }
 
@@ -83,6 +176,9 @@ impl AppConfigFile {
     if config.db.is_some() {
       self.db = config.db;
     }
+    if config.crypto.is_some() {
+      self.crypto = config.crypto;
+    }
   }
 }
 
@@ -118,8 +214,9 @@ where
     }
   }
 
-  serde_json::from_value(serde_json::Value::Object(root))
-    .map_err(|err| RuntimeError::json("invalid app config overrides", err))
+  let root = serde_json::Value::Object(root);
+  ensure_valid_app_config(&root, BACKEND_SECTIONS, "invalid app config overrides")?;
+  serde_json::from_value(root).map_err(|err| RuntimeError::json("invalid app config overrides", err))
 }
 
 pub(super) fn config_json_paths() -> Vec<PathBuf> {
@@ -197,4 +294,26 @@ mod tests {
       Some("postgresql://example/runtime")
     );
   }
+
+  #[test]
+  fn reads_crypto_private_key_overrides() {
+    let app_config =
+      app_config_from_flat_overrides([("crypto.privateKey", serde_json::json!("runtime-secret"))]).unwrap();
+    assert_eq!(app_config.crypto_private_key().as_deref(), Some("runtime-secret"));
+
+    let blank = app_config_from_flat_overrides([("crypto.privateKey", serde_json::json!(" "))]).unwrap();
+    assert_eq!(blank.crypto_private_key(), None);
+  }
+
+  #[test]
+  fn reads_doc_encryption_overrides() {
+    let app_config = app_config_from_flat_overrides([(
+      "crypto.docEncryption",
+      serde_json::json!({"masterKey": "current", "previousMasterKeys": ["retired"]}),
+    )])
+    .unwrap();
+    let crypto = app_config.crypto.unwrap().doc_encryption.unwrap();
+    assert_eq!(crypto.master_key.as_deref(), Some("current"));
+    assert_eq!(crypto.previous_master_keys, vec!["retired".to_string()]);
+  }
 }
@@ -0,0 +1,357 @@
+use std::{
+  str::FromStr,
+  sync::{
+    Arc, Mutex,
+    atomic::{AtomicUsize, Ordering},
+  },
+  time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use sqlx::{
+  PgPool, SqlitePool,
+  postgres::{PgConnectOptions, PgPoolOptions},
+  sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions},
+};
+
+use super::{RuntimeError, RuntimeResult, metrics};
+
+const REPLICA_LAG_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+const REPLICA_LAG_CHECK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// `db.pool`: sizing and timeouts for the runtime connection pools. Replica
+/// pools use the same settings as the primary.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct DatabasePoolConfig {
+  pub(crate) max_connections: u32,
+  pub(crate) min_connections: u32,
+  pub(crate) acquire_timeout_ms: u64,
+  pub(crate) idle_timeout_ms: Option<u64>,
+  pub(crate) statement_timeout_ms: Option<u64>,
+}
+
+impl Default for DatabasePoolConfig {
+  fn default() -> Self {
+    Self {
+      max_connections: 5,
+      min_connections: 0,
+      acquire_timeout_ms: 5_000,
+      idle_timeout_ms: None,
+      statement_timeout_ms: None,
+    }
+  }
+}
+
+/// `db.replicas`: read replicas for read-only runtime queries. A replica
+/// whose replay lag exceeds `max_lag_ms` is skipped until it catches up.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct DatabaseReplicaConfig {
+  pub(crate) urls: Vec<String>,
+  pub(crate) max_lag_ms: u64,
+}
+
+impl Default for DatabaseReplicaConfig {
+  fn default() -> Self {
+    Self {
+      urls: Vec::new(),
+      max_lag_ms: 10_000,
+    }
+  }
+}
+
+impl DatabasePoolConfig {
+  fn pool_options(&self) -> PgPoolOptions {
+    PgPoolOptions::new()
+      .max_connections(self.max_connections)
+      .min_connections(self.min_connections.min(self.max_connections))
+      .acquire_timeout(Duration::from_millis(self.acquire_timeout_ms))
+      .idle_timeout(self.idle_timeout_ms.map(Duration::from_millis))
+  }
+
+  fn connect_options(&self, database_url: &str) -> Result<PgConnectOptions, sqlx::Error> {
+    let options = PgConnectOptions::from_str(database_url)?;
+    Ok(match self.statement_timeout_ms {
+      Some(timeout) => options.options([("statement_timeout", timeout.to_string())]),
+      None => options,
+    })
+  }
+}
+
+/// Connect the primary pool and run a health check. `runtime` prefixes
+/// error messages, e.g. `BackendRuntime failed to connect postgres`.
+pub(crate) async fn connect_primary(
+  database_url: &str,
+  config: &DatabasePoolConfig,
+  runtime: &str,
+) -> RuntimeResult<PgPool> {
+  let options = config
+    .connect_options(database_url)
+    .map_err(|err| RuntimeError::database(format!("{runtime} database url is invalid"), err))?;
+  let pool = config
+    .pool_options()
+    .connect_with(options)
+    .await
+    .map_err(|err| RuntimeError::database(format!("{runtime} failed to connect postgres"), err))?;
+
+  sqlx::query("SELECT 1")
+    .execute(&pool)
+    .await
+    .map_err(|err| RuntimeError::database(format!("{runtime} postgres health check failed"), err))?;
+  Ok(pool)
+}
+
+/// Primary database of the backend runtime. A `sqlite:` datasource URL
+/// selects the embedded SQLite mode for single-user deployments; anything
+/// else is treated as Postgres.
+#[derive(Clone)]
+pub(crate) enum RuntimeDatabase {
+  Postgres(PgPool),
+  Sqlite(SqlitePool),
+}
+
+impl RuntimeDatabase {
+  pub(crate) async fn connect(database_url: &str, config: &DatabasePoolConfig, runtime: &str) -> RuntimeResult<Self> {
+    if is_sqlite_url(database_url) {
+      connect_sqlite(database_url, config, runtime).await.map(Self::Sqlite)
+    } else {
+      connect_primary(database_url, config, runtime).await.map(Self::Postgres)
+    }
+  }
+
+  pub(crate) fn is_sqlite(&self) -> bool {
+    matches!(self, Self::Sqlite(_))
+  }
+
+  pub(crate) async fn ping(&self) -> bool {
+    let result = match self {
+      Self::Postgres(pool) => sqlx::query_scalar::<_, i32>("SELECT 1").fetch_one(pool).await,
+      Self::Sqlite(pool) => sqlx::query_scalar::<_, i32>("SELECT 1").fetch_one(pool).await,
+    };
+    result.is_ok_and(|value| value == 1)
+  }
+
+  pub(crate) async fn close(&self) {
+    match self {
+      Self::Postgres(pool) => pool.close().await,
+      Self::Sqlite(pool) => pool.close().await,
+    }
+  }
+}
+
+pub(crate) fn is_sqlite_url(database_url: &str) -> bool {
+  database_url.trim_start().starts_with("sqlite:")
+}
+
+/// Open the embedded SQLite database. The pool holds exactly one connection,
+/// so runtime transactions run one at a time; that stands in for the row and
+/// advisory locks the Postgres queries take, and keeps `sqlite::memory:`
+/// databases alive until the runtime stops.
+async fn connect_sqlite(database_url: &str, config: &DatabasePoolConfig, runtime: &str) -> RuntimeResult<SqlitePool> {
+  let options = SqliteConnectOptions::from_str(database_url)
+    .map_err(|err| RuntimeError::database(format!("{runtime} database url is invalid"), err))?
+    .create_if_missing(true)
+    .journal_mode(SqliteJournalMode::Wal)
+    .busy_timeout(Duration::from_millis(config.acquire_timeout_ms));
+  SqlitePoolOptions::new()
+    .max_connections(1)
+    .min_connections(1)
+    .acquire_timeout(Duration::from_millis(config.acquire_timeout_ms))
+    .idle_timeout(None)
+    .max_lifetime(None)
+    .connect_with(options)
+    .await
+    .map_err(|err| RuntimeError::database(format!("{runtime} failed to open sqlite"), err))
+}
+
+/// Read replicas with lag-aware round-robin selection. Replica pools connect
+/// lazily, so an unreachable replica never blocks startup; it is skipped
+/// like a lagging one.
+#[derive(Clone)]
+pub(crate) struct ReplicaSet {
+  inner: Arc<ReplicaSetInner>,
+}
+
+struct ReplicaSetInner {
+  replicas: Vec<Replica>,
+  max_lag: Duration,
+  next: AtomicUsize,
+}
+
+struct Replica {
+  pool: PgPool,
+  fresh: Mutex<Option<(Instant, bool)>>,
+}
+
+impl ReplicaSet {
+  pub(crate) fn connect(
+    config: &DatabaseReplicaConfig,
+    pool_config: &DatabasePoolConfig,
+    runtime: &str,
+  ) -> RuntimeResult<Option<Self>> {
+    if config.urls.is_empty() {
+      return Ok(None);
+    }
+
+    let mut replicas = Vec::with_capacity(config.urls.len());
+    for url in &config.urls {
+      let options = pool_config
+        .connect_options(url)
+        .map_err(|err| RuntimeError::database(format!("{runtime} replica database url is invalid"), err))?;
+      replicas.push(Replica {
+        pool: pool_config.pool_options().connect_lazy_with(options),
+        fresh: Mutex::new(None),
+      });
+    }
+    Ok(Some(Self {
+      inner: Arc::new(ReplicaSetInner {
+        replicas,
+        max_lag: Duration::from_millis(config.max_lag_ms),
+        next: AtomicUsize::new(0),
+      }),
+    }))
+  }
+
+  /// Pick the next replica within the lag budget, or fall back to `primary`.
+  pub(crate) async fn read_pool(&self, primary: &PgPool, runtime: &str) -> PgPool {
+    let replicas = &self.inner.replicas;
+    let start = self.inner.next.fetch_add(1, Ordering::Relaxed);
+    for offset in 0..replicas.len() {
+      let replica = &replicas[(start + offset) % replicas.len()];
+      if replica.is_fresh(self.inner.max_lag).await {
+        metrics::DB_READ_ROUTES.inc(&[("runtime", runtime), ("target", "replica")]);
+        return replica.pool.clone();
+      }
+    }
+    metrics::DB_READ_ROUTES.inc(&[("runtime", runtime), ("target", "primary")]);
+    primary.clone()
+  }
+
+  pub(crate) async fn close(&self) {
+    for replica in &self.inner.replicas {
+      replica.pool.close().await;
+    }
+  }
+}
+
+impl Replica {
+  async fn is_fresh(&self, max_lag: Duration) -> bool {
+    if let Ok(cached) = self.fresh.lock()
+      && let Some((checked_at, fresh)) = *cached
+      && checked_at.elapsed() < REPLICA_LAG_CHECK_INTERVAL
+    {
+      return fresh;
+    }
+
+    let fresh = match tokio::time::timeout(REPLICA_LAG_CHECK_TIMEOUT, replica_lag_ms(&self.pool)).await {
+      Ok(Ok(Some(lag_ms))) => lag_ms <= max_lag.as_millis() as f64,
+      _ => false,
+    };
+    if let Ok(mut cached) = self.fresh.lock() {
+      *cached = Some((Instant::now(), fresh));
+    }
+    fresh
+  }
+}
+
+/// Replay lag in milliseconds. An idle replica that has replayed everything
+/// it received reports zero rather than the age of the last transaction, and
+/// a server that is not in recovery is treated as fully caught up.
+async fn replica_lag_ms(pool: &PgPool) -> Result<Option<f64>, sqlx::Error> {
+  sqlx::query_scalar(
+    r#"
+    SELECT CASE
+      WHEN NOT pg_is_in_recovery() THEN 0::float8
+      WHEN pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() THEN 0::float8
+      ELSE (EXTRACT(EPOCH FROM (now() - pg_last_xact_replay_timestamp())) * 1000)::float8
+    END
+    "#,
+  )
+  .fetch_one(pool)
+  .await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn pool_config_defaults_match_previous_hard_coded_values() {
+    let config: DatabasePoolConfig = serde_json::from_value(serde_json::json!({})).unwrap();
+    assert_eq!(config, DatabasePoolConfig::default());
+    assert_eq!(config.max_connections, 5);
+    assert_eq!(config.acquire_timeout_ms, 5_000);
+
+    let config: DatabasePoolConfig =
+      serde_json::from_value(serde_json::json!({ "maxConnections": 20, "statementTimeoutMs": 30000 })).unwrap();
+    assert_eq!(config.max_connections, 20);
+    assert_eq!(config.statement_timeout_ms, Some(30_000));
+    assert_eq!(config.min_connections, 0);
+  }
+
+  #[test]
+  fn statement_timeout_is_sent_as_startup_option() {
+    let config = DatabasePoolConfig {
+      statement_timeout_ms: Some(1500),
+      ..Default::default()
+    };
+    let options = config
+      .connect_options("postgresql://affine@localhost:5432/affine")
+      .unwrap();
+    assert_eq!(options.get_options(), Some("-c statement_timeout=1500"));
+    assert!(config.connect_options("not a url").is_err());
+  }
+
+  #[tokio::test]
+  async fn sqlite_urls_select_the_embedded_database() {
+    assert!(is_sqlite_url("sqlite::memory:"));
+    assert!(is_sqlite_url(" sqlite:///var/lib/affine/affine.db"));
+    assert!(!is_sqlite_url("postgresql://affine@localhost:5432/affine"));
+
+    let database = RuntimeDatabase::connect("sqlite::memory:", &DatabasePoolConfig::default(), "test")
+      .await
+      .unwrap();
+    assert!(database.is_sqlite());
+    assert!(database.ping().await);
+    database.close().await;
+    assert!(!database.ping().await);
+  }
+
+  #[tokio::test]
+  async fn replica_set_is_empty_without_urls() {
+    let replicas = ReplicaSet::connect(
+      &DatabaseReplicaConfig::default(),
+      &DatabasePoolConfig::default(),
+      "test",
+    )
+    .unwrap();
+    assert!(replicas.is_none());
+  }
+
+  #[tokio::test]
+  async fn unreachable_replica_falls_back_to_primary() {
+    let pool_config = DatabasePoolConfig {
+      acquire_timeout_ms: 100,
+      ..Default::default()
+    };
+    let replicas = ReplicaSet::connect(
+      &DatabaseReplicaConfig {
+        urls: vec!["postgresql://affine@127.0.0.1:1/affine".to_string()],
+        max_lag_ms: 1_000,
+      },
+      &pool_config,
+      "test",
+    )
+    .unwrap()
+    .unwrap();
+    let primary = pool_config.pool_options().connect_lazy_with(
+      pool_config
+        .connect_options("postgresql://affine@127.0.0.1:2/primary")
+        .unwrap(),
+    );
+
+    let pool = replicas.read_pool(&primary, "test").await;
+    assert_eq!(pool.connect_options().get_port(), 2);
+  }
+}
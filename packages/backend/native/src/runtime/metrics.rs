@@ -0,0 +1,423 @@
+use std::{
+  collections::BTreeMap,
+  fmt::Write as _,
+  sync::{LazyLock, Mutex, MutexGuard},
+  time::Instant,
+};
+
+use sqlx::PgPool;
+
+const LATENCY_BUCKETS: &[f64] = &[0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+const COUNT_BUCKETS: &[f64] = &[1.0, 2.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0];
+
+pub(crate) const BACKEND: &str = "backend";
+pub(crate) const STORAGE: &str = "storage";
+
+pub(crate) const DB_POOL_CONNECTIONS: Metric = Metric::gauge(
+  "affine_runtime_db_pool_connections",
+  "Postgres pool connections by state.",
+);
+pub(crate) const DB_POOL_MAX_CONNECTIONS: Metric = Metric::gauge(
+  "affine_runtime_db_pool_max_connections",
+  "Configured maximum size of the postgres pool.",
+);
+pub(crate) const DB_READ_ROUTES: Metric = Metric::counter(
+  "affine_runtime_db_read_routes_total",
+  "Read-only queries routed to a replica or back to the primary.",
+);
+pub(crate) const OPERATION_DURATION: Metric = Metric::histogram(
+  "affine_runtime_operation_duration_seconds",
+  "Latency of runtime database operations.",
+  LATENCY_BUCKETS,
+);
+pub(crate) const DOC_COMPACTIONS: Metric =
+  Metric::counter("affine_doc_compactions_total", "Doc compaction attempts by outcome.");
+pub(crate) const DOC_COMPACTION_MERGED_UPDATES: Metric = Metric::histogram(
+  "affine_doc_compaction_merged_updates",
+  "Number of pending updates merged by one compaction.",
+  COUNT_BUCKETS,
+);
+pub(crate) const COORDINATION_LEASE_ACQUIRES: Metric = Metric::counter(
+  "affine_coordination_lease_acquires_total",
+  "Coordination lease acquisition attempts by result.",
+);
+pub(crate) const COORDINATION_LEASES_LOST: Metric = Metric::counter(
+  "affine_coordination_leases_lost_total",
+  "Coordination leases lost while their work was still running.",
+);
+pub(crate) const BLOB_CLEANUP_OBJECTS: Metric = Metric::counter(
+  "affine_blob_cleanup_objects_total",
+  "Blobs handled by cleanup jobs by operation and outcome.",
+);
+pub(crate) const BLOB_INTEGRITY_FINDINGS: Metric = Metric::counter(
+  "affine_blob_integrity_findings_total",
+  "Blob integrity problems found by the scrubber by kind.",
+);
+pub(crate) const BLOB_QUARANTINE_READS: Metric = Metric::counter(
+  "affine_blob_quarantine_reads_total",
+  "Reads of blobs that blob cleanup had quarantined.",
+);
+pub(crate) const BLOB_QUOTA_REJECTIONS: Metric = Metric::counter(
+  "affine_blob_quota_rejections_total",
+  "Blob uploads rejected by workspace quota checks by reason.",
+);
+pub(crate) const STORAGE_USAGE_CORRECTIONS: Metric = Metric::counter(
+  "affine_storage_usage_corrections_total",
+  "Workspaces whose storage usage counters drifted from blobs and were corrected.",
+);
+pub(crate) const IMAGE_DERIVATIVES: Metric = Metric::counter(
+  "affine_image_derivatives_total",
+  "Image derivatives generated for completed blob uploads by result.",
+);
+pub(crate) const OBJECT_STORAGE_REQUEST_DURATION: Metric = Metric::histogram(
+  "affine_object_storage_request_duration_seconds",
+  "Latency of object storage requests by provider and operation.",
+  LATENCY_BUCKETS,
+);
+pub(crate) const OBJECT_STORAGE_REQUEST_ERRORS: Metric = Metric::counter(
+  "affine_object_storage_request_errors_total",
+  "Failed object storage requests by provider and operation.",
+);
+pub(crate) const ASSETPACK_PUTS: Metric = Metric::counter(
+  "affine_assetpack_puts_total",
+  "Assetpack writes by whether the content was already stored.",
+);
+pub(crate) const ASSETPACK_LOGICAL_BYTES: Metric = Metric::counter(
+  "affine_assetpack_logical_bytes_total",
+  "Bytes written to assetpack before deduplication.",
+);
+pub(crate) const ASSETPACK_STORED_BYTES: Metric = Metric::counter(
+  "affine_assetpack_stored_bytes_total",
+  "Bytes assetpack stored after deduplication and encoding.",
+);
+pub(crate) const ASSETPACK_DEDUP_RATIO: Metric = Metric::gauge(
+  "affine_assetpack_dedup_ratio",
+  "Logical bytes divided by stored bytes since process start.",
+);
+
+type Labels = Vec<(String, String)>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MetricKind {
+  Counter,
+  Gauge,
+  Histogram(&'static [f64]),
+}
+
+impl MetricKind {
+  fn as_str(self) -> &'static str {
+    match self {
+      Self::Counter => "counter",
+      Self::Gauge => "gauge",
+      Self::Histogram(_) => "histogram",
+    }
+  }
+}
+
+/// A metric family descriptor. Series are created on first use for each
+/// distinct label set.
+pub(crate) struct Metric {
+  name: &'static str,
+  help: &'static str,
+  kind: MetricKind,
+}
+
+#[derive(Default)]
+struct Series {
+  // Counter and gauge value, or the histogram sum.
+  value: f64,
+  buckets: Vec<u64>,
+  count: u64,
+}
+
+struct Family {
+  help: &'static str,
+  kind: MetricKind,
+  series: BTreeMap<Labels, Series>,
+}
+
+#[derive(Default)]
+struct Registry {
+  families: BTreeMap<&'static str, Family>,
+}
+
+static REGISTRY: LazyLock<Mutex<Registry>> = LazyLock::new(Default::default);
+
+fn registry() -> MutexGuard<'static, Registry> {
+  // A panic while holding the lock cannot leave a series half-written in a way
+  // that matters for reporting, so keep serving metrics.
+  REGISTRY.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl Metric {
+  const fn counter(name: &'static str, help: &'static str) -> Self {
+    Self {
+      name,
+      help,
+      kind: MetricKind::Counter,
+    }
+  }
+
+  const fn gauge(name: &'static str, help: &'static str) -> Self {
+    Self {
+      name,
+      help,
+      kind: MetricKind::Gauge,
+    }
+  }
+
+  const fn histogram(name: &'static str, help: &'static str, buckets: &'static [f64]) -> Self {
+    Self {
+      name,
+      help,
+      kind: MetricKind::Histogram(buckets),
+    }
+  }
+
+  fn update(&self, registry: &mut Registry, labels: &[(&str, &str)], apply: impl FnOnce(&mut Series)) {
+    let family = registry.families.entry(self.name).or_insert_with(|| Family {
+      help: self.help,
+      kind: self.kind,
+      series: BTreeMap::new(),
+    });
+    let labels = labels
+      .iter()
+      .map(|(name, value)| (name.to_string(), value.to_string()))
+      .collect();
+    apply(family.series.entry(labels).or_default());
+  }
+
+  pub(crate) fn inc(&self, labels: &[(&str, &str)]) {
+    self.inc_by(labels, 1.0);
+  }
+
+  pub(crate) fn inc_by(&self, labels: &[(&str, &str)], value: f64) {
+    debug_assert_eq!(self.kind, MetricKind::Counter);
+    self.update(&mut registry(), labels, |series| series.value += value);
+  }
+
+  pub(crate) fn set(&self, labels: &[(&str, &str)], value: f64) {
+    debug_assert_eq!(self.kind, MetricKind::Gauge);
+    self.update(&mut registry(), labels, |series| series.value = value);
+  }
+
+  pub(crate) fn observe(&self, labels: &[(&str, &str)], value: f64) {
+    let MetricKind::Histogram(bounds) = self.kind else {
+      debug_assert!(false, "{} is not a histogram", self.name);
+      return;
+    };
+    self.update(&mut registry(), labels, |series| {
+      series.buckets.resize(bounds.len(), 0);
+      for (bucket, bound) in series.buckets.iter_mut().zip(bounds) {
+        if value <= *bound {
+          *bucket += 1;
+        }
+      }
+      series.value += value;
+      series.count += 1;
+    });
+  }
+
+  fn value(&self, registry: &Registry, labels: &[(&str, &str)]) -> f64 {
+    registry
+      .families
+      .get(self.name)
+      .and_then(|family| {
+        family.series.iter().find_map(|(series_labels, series)| {
+          let matches = series_labels.len() == labels.len()
+            && series_labels
+              .iter()
+              .zip(labels)
+              .all(|((name, value), (expected_name, expected_value))| name == expected_name && value == expected_value);
+          matches.then_some(series.value)
+        })
+      })
+      .unwrap_or(0.0)
+  }
+}
+
+/// Await a runtime operation and record its latency and outcome.
+pub(crate) async fn timed<T, E>(
+  runtime: &str,
+  operation: &str,
+  future: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+  let started = Instant::now();
+  let result = future.await;
+  observe_operation(runtime, operation, started, result.is_ok());
+  result
+}
+
+pub(crate) fn observe_operation(runtime: &str, operation: &str, started: Instant, ok: bool) {
+  let outcome = if ok { "ok" } else { "error" };
+  OPERATION_DURATION.observe(
+    &[("runtime", runtime), ("operation", operation), ("outcome", outcome)],
+    started.elapsed().as_secs_f64(),
+  );
+}
+
+pub(crate) fn record_object_request(provider: &str, operation: &str, started: Instant, ok: bool) {
+  let labels = [("provider", provider), ("operation", operation)];
+  OBJECT_STORAGE_REQUEST_DURATION.observe(&labels, started.elapsed().as_secs_f64());
+  if !ok {
+    OBJECT_STORAGE_REQUEST_ERRORS.inc(&labels);
+  }
+}
+
+pub(crate) fn record_blob_cleanup(operation: &str, outcomes: &[(&str, i64)]) {
+  for (outcome, count) in outcomes {
+    BLOB_CLEANUP_OBJECTS.inc_by(&[("operation", operation), ("outcome", outcome)], *count as f64);
+  }
+}
+
+pub(crate) fn record_blob_integrity_finding(kind: &str) {
+  BLOB_INTEGRITY_FINDINGS.inc(&[("kind", kind)]);
+}
+
+pub(crate) fn record_blob_quarantine_read() {
+  BLOB_QUARANTINE_READS.inc(&[]);
+}
+
+pub(crate) fn record_blob_quota_rejection(reason: &str) {
+  BLOB_QUOTA_REJECTIONS.inc(&[("reason", reason)]);
+}
+
+pub(crate) fn record_storage_usage_corrections(count: i64) {
+  STORAGE_USAGE_CORRECTIONS.inc_by(&[], count as f64);
+}
+
+pub(crate) fn record_image_derivative(result: &str) {
+  IMAGE_DERIVATIVES.inc(&[("result", result)]);
+}
+
+/// Record one assetpack write. `stored_bytes` is zero when the content was
+/// already present.
+pub(crate) fn record_assetpack_put(logical_bytes: u64, stored_bytes: u64) {
+  let result = if stored_bytes == 0 { "duplicate" } else { "new" };
+  ASSETPACK_PUTS.inc(&[("result", result)]);
+  ASSETPACK_LOGICAL_BYTES.inc_by(&[], logical_bytes as f64);
+  ASSETPACK_STORED_BYTES.inc_by(&[], stored_bytes as f64);
+
+  let mut registry = registry();
+  let logical = ASSETPACK_LOGICAL_BYTES.value(&registry, &[]);
+  let stored = ASSETPACK_STORED_BYTES.value(&registry, &[]);
+  if stored > 0.0 {
+    ASSETPACK_DEDUP_RATIO.update(&mut registry, &[], |series| series.value = logical / stored);
+  }
+}
+
+/// Snapshot pool utilisation. Called on render so the gauges are current.
+pub(crate) fn record_pool(runtime: &str, pool: &PgPool) {
+  let size = pool.size() as f64;
+  let idle = pool.num_idle() as f64;
+  DB_POOL_CONNECTIONS.set(&[("runtime", runtime), ("state", "idle")], idle);
+  DB_POOL_CONNECTIONS.set(&[("runtime", runtime), ("state", "in_use")], (size - idle).max(0.0));
+  DB_POOL_MAX_CONNECTIONS.set(&[("runtime", runtime)], pool.options().get_max_connections() as f64);
+}
+
+fn escape_label_value(value: &str) -> String {
+  value.replace('\\', r"\\").replace('"', "\\\"").replace('\n', r"\n")
+}
+
+fn format_labels(labels: &Labels, extra: Option<(&str, &str)>) -> String {
+  let pairs = labels
+    .iter()
+    .map(|(name, value)| (name.as_str(), value.as_str()))
+    .chain(extra)
+    .map(|(name, value)| format!("{name}=\"{}\"", escape_label_value(value)))
+    .collect::<Vec<_>>();
+  if pairs.is_empty() {
+    String::new()
+  } else {
+    format!("{{{}}}", pairs.join(","))
+  }
+}
+
+/// Render every registered family in the Prometheus text exposition format.
+pub(crate) fn render() -> String {
+  let registry = registry();
+  let mut out = String::new();
+  for (name, family) in &registry.families {
+    let _ = writeln!(out, "# HELP {name} {}", family.help);
+    let _ = writeln!(out, "# TYPE {name} {}", family.kind.as_str());
+    for (labels, series) in &family.series {
+      match family.kind {
+        MetricKind::Counter | MetricKind::Gauge => {
+          let _ = writeln!(out, "{name}{} {}", format_labels(labels, None), series.value);
+        }
+        MetricKind::Histogram(bounds) => {
+          for (bound, bucket) in bounds.iter().zip(&series.buckets) {
+            let le = bound.to_string();
+            let _ = writeln!(
+              out,
+              "{name}_bucket{} {bucket}",
+              format_labels(labels, Some(("le", &le)))
+            );
+          }
+          let _ = writeln!(
+            out,
+            "{name}_bucket{} {}",
+            format_labels(labels, Some(("le", "+Inf"))),
+            series.count
+          );
+          let _ = writeln!(out, "{name}_sum{} {}", format_labels(labels, None), series.value);
+          let _ = writeln!(out, "{name}_count{} {}", format_labels(labels, None), series.count);
+        }
+      }
+    }
+  }
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // The registry is process-wide, so each test uses its own metric names.
+
+  #[test]
+  fn renders_counters_and_gauges_with_escaped_labels() {
+    const COUNTER: Metric = Metric::counter("test_render_counter_total", "Test counter.");
+    const GAUGE: Metric = Metric::gauge("test_render_gauge", "Test gauge.");
+    COUNTER.inc(&[("op", "a\"b")]);
+    COUNTER.inc_by(&[("op", "a\"b")], 2.0);
+    GAUGE.set(&[], 4.5);
+    GAUGE.set(&[], 1.5);
+
+    let text = render();
+    assert!(text.contains("# TYPE test_render_counter_total counter\n"));
+    assert!(text.contains("test_render_counter_total{op=\"a\\\"b\"} 3\n"));
+    assert!(text.contains("# HELP test_render_gauge Test gauge.\n"));
+    assert!(text.contains("test_render_gauge 1.5\n"));
+  }
+
+  #[test]
+  fn histograms_render_cumulative_buckets() {
+    const HISTOGRAM: Metric = Metric::histogram("test_render_histogram", "Test histogram.", &[1.0, 5.0]);
+    for value in [0.5, 3.0, 9.0] {
+      HISTOGRAM.observe(&[("op", "x")], value);
+    }
+
+    let text = render();
+    assert!(text.contains("test_render_histogram_bucket{op=\"x\",le=\"1\"} 1\n"));
+    assert!(text.contains("test_render_histogram_bucket{op=\"x\",le=\"5\"} 2\n"));
+    assert!(text.contains("test_render_histogram_bucket{op=\"x\",le=\"+Inf\"} 3\n"));
+    assert!(text.contains("test_render_histogram_sum{op=\"x\"} 12.5\n"));
+    assert!(text.contains("test_render_histogram_count{op=\"x\"} 3\n"));
+  }
+
+  #[tokio::test]
+  async fn timed_records_outcome() {
+    let ok: Result<i32, ()> = timed("test", "timed_ok", async { Ok(1) }).await;
+    let err: Result<i32, ()> = timed("test", "timed_err", async { Err(()) }).await;
+    assert_eq!((ok, err), (Ok(1), Err(())));
+
+    let text = render();
+    assert!(text.contains(
+      "affine_runtime_operation_duration_seconds_count{runtime=\"test\",operation=\"timed_ok\",outcome=\"ok\"} 1\n"
+    ));
+    assert!(text.contains(
+      "affine_runtime_operation_duration_seconds_count{runtime=\"test\",operation=\"timed_err\",outcome=\"error\"} 1\n"
+    ));
+  }
+}
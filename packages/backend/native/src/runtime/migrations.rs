@@ -1,15 +1,12 @@
-use sqlx::PgPool;
+use sqlx::{PgPool, SqlitePool};
 
-use super::{RuntimeError, RuntimeResult};
+use super::{RuntimeError, RuntimeResult, database::RuntimeDatabase};
 
 pub(crate) const RUNTIME_MIGRATIONS: &str = include_str!("sql/runtime_migrations.sql");
+pub(crate) const RUNTIME_SQLITE_MIGRATIONS: &str = include_str!("sql/runtime_sqlite_migrations.sql");
 
 pub(crate) async fn migrate_runtime_tables(pool: &PgPool) -> RuntimeResult<()> {
-  for statement in RUNTIME_MIGRATIONS
-    .split(';')
-    .map(str::trim)
-    .filter(|statement| !statement.is_empty())
-  {
+  for statement in statements(RUNTIME_MIGRATIONS) {
     sqlx::query(statement)
       .execute(pool)
       .await
@@ -18,3 +15,28 @@ pub(crate) async fn migrate_runtime_tables(pool: &PgPool) -> RuntimeResult<()> {
 
   Ok(())
 }
+
+pub(crate) async fn migrate_runtime_sqlite_tables(pool: &SqlitePool) -> RuntimeResult<()> {
+  for statement in statements(RUNTIME_SQLITE_MIGRATIONS) {
+    sqlx::query(statement)
+      .execute(pool)
+      .await
+      .map_err(|err| RuntimeError::database("Runtime SQLite migration failed", err))?;
+  }
+
+  Ok(())
+}
+
+pub(crate) async fn migrate_runtime_database(database: &RuntimeDatabase) -> RuntimeResult<()> {
+  match database {
+    RuntimeDatabase::Postgres(pool) => migrate_runtime_tables(pool).await,
+    RuntimeDatabase::Sqlite(pool) => migrate_runtime_sqlite_tables(pool).await,
+  }
+}
+
+fn statements(migrations: &str) -> impl Iterator<Item = &str> {
+  migrations
+    .split(';')
+    .map(str::trim)
+    .filter(|statement| !statement.is_empty())
+}
@@ -2,9 +2,12 @@ pub mod backend_runtime;
 pub mod storage_runtime;
 
 pub(crate) mod config;
+pub(crate) mod config_validation;
+pub(crate) mod database;
 pub(crate) mod error;
+pub(crate) mod metrics;
 pub(crate) mod migrations;
 pub(crate) mod types;
 
-pub(crate) use config::BackendRuntimeConfig;
+pub(crate) use config::{BackendRuntimeConfig, DocEncryptionConfig};
 pub(crate) use error::{RuntimeError, RuntimeResult, napi_error, to_napi_error};
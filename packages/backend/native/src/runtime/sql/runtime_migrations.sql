@@ -27,6 +27,32 @@ CREATE TABLE IF NOT EXISTS runtime_gates (
 CREATE INDEX IF NOT EXISTS runtime_gates_expires_at_idx
   ON runtime_gates (expires_at);
 
+CREATE TABLE IF NOT EXISTS runtime_gate_buckets (
+  key TEXT PRIMARY KEY,
+  capacity DOUBLE PRECISION NOT NULL,
+  refill_per_sec DOUBLE PRECISION NOT NULL,
+  tokens DOUBLE PRECISION NOT NULL,
+  allowed BOOLEAN NOT NULL,
+  refilled_at TIMESTAMPTZ(3) NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS runtime_gate_buckets_refilled_at_idx
+  ON runtime_gate_buckets (refilled_at);
+
+CREATE TABLE IF NOT EXISTS runtime_gate_window_hits (
+  id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  key TEXT NOT NULL,
+  cost BIGINT NOT NULL,
+  created_at TIMESTAMPTZ(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  expires_at TIMESTAMPTZ(3) NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS runtime_gate_window_hits_key_idx
+  ON runtime_gate_window_hits (key, created_at);
+
+CREATE INDEX IF NOT EXISTS runtime_gate_window_hits_expires_at_idx
+  ON runtime_gate_window_hits (expires_at);
+
 CREATE TABLE IF NOT EXISTS runtime_leases (
   key TEXT PRIMARY KEY,
   owner TEXT NOT NULL,
@@ -110,3 +136,126 @@ CREATE TABLE IF NOT EXISTS blob_cleanup_candidates (
 
 CREATE INDEX IF NOT EXISTS blob_cleanup_candidates_run_idx
   ON blob_cleanup_candidates (run_id, status);
+
+ALTER TABLE blob_cleanup_candidates ADD COLUMN IF NOT EXISTS restore_deadline TIMESTAMPTZ(3);
+
+CREATE INDEX IF NOT EXISTS blob_cleanup_candidates_quarantine_idx
+  ON blob_cleanup_candidates (status, restore_deadline);
+
+CREATE TABLE IF NOT EXISTS blob_integrity_findings (
+  run_id UUID NOT NULL,
+  workspace_id TEXT NOT NULL,
+  blob_key TEXT NOT NULL,
+  kind TEXT NOT NULL,
+  expected_size BIGINT,
+  actual_size BIGINT,
+  detail JSONB NOT NULL DEFAULT '{}',
+  found_at TIMESTAMPTZ(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (run_id, workspace_id, blob_key, kind)
+);
+
+CREATE INDEX IF NOT EXISTS blob_integrity_findings_workspace_idx
+  ON blob_integrity_findings (workspace_id, found_at DESC);
+
+CREATE TABLE IF NOT EXISTS workspace_storage_usage (
+  workspace_id TEXT PRIMARY KEY,
+  used_bytes BIGINT NOT NULL DEFAULT 0,
+  reserved_bytes BIGINT NOT NULL DEFAULT 0,
+  updated_at TIMESTAMPTZ(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  reconciled_at TIMESTAMPTZ(3)
+);
+
+CREATE TABLE IF NOT EXISTS blob_quota_reservations (
+  workspace_id TEXT NOT NULL,
+  blob_key TEXT NOT NULL,
+  size BIGINT NOT NULL,
+  upload_id TEXT,
+  created_at TIMESTAMPTZ(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  expires_at TIMESTAMPTZ(3) NOT NULL,
+  PRIMARY KEY (workspace_id, blob_key)
+);
+
+CREATE INDEX IF NOT EXISTS blob_quota_reservations_expires_idx
+  ON blob_quota_reservations (expires_at);
+
+CREATE TABLE IF NOT EXISTS blob_derivatives (
+  workspace_id TEXT NOT NULL,
+  blob_key TEXT NOT NULL,
+  max_edge INTEGER NOT NULL,
+  size BIGINT NOT NULL,
+  created_at TIMESTAMPTZ(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (workspace_id, blob_key, max_edge)
+);
+
+CREATE TABLE IF NOT EXISTS runtime_jobs (
+  id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+  queue TEXT NOT NULL,
+  dedupe_key TEXT,
+  payload JSONB NOT NULL DEFAULT '{}',
+  status TEXT NOT NULL DEFAULT 'pending',
+  attempts INTEGER NOT NULL DEFAULT 0,
+  max_attempts INTEGER NOT NULL,
+  run_at TIMESTAMPTZ(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  locked_by TEXT,
+  locked_until TIMESTAMPTZ(3),
+  last_error TEXT,
+  completed_at TIMESTAMPTZ(3),
+  created_at TIMESTAMPTZ(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMPTZ(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS runtime_jobs_active_dedupe_idx
+  ON runtime_jobs (queue, dedupe_key)
+  WHERE dedupe_key IS NOT NULL AND status IN ('pending', 'running');
+
+CREATE INDEX IF NOT EXISTS runtime_jobs_claim_idx
+  ON runtime_jobs (queue, status, run_at);
+
+CREATE INDEX IF NOT EXISTS runtime_jobs_locked_until_idx
+  ON runtime_jobs (queue, locked_until)
+  WHERE status = 'running';
+
+CREATE TABLE IF NOT EXISTS audit_events (
+  seq BIGSERIAL PRIMARY KEY,
+  workspace_id TEXT,
+  actor_id TEXT,
+  action TEXT NOT NULL,
+  target_type TEXT,
+  target_id TEXT,
+  details JSONB NOT NULL DEFAULT '{}',
+  prev_hash TEXT NOT NULL,
+  hash TEXT NOT NULL,
+  created_at TIMESTAMPTZ(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS audit_events_workspace_idx
+  ON audit_events (workspace_id, seq)
+  WHERE workspace_id IS NOT NULL;
+
+CREATE INDEX IF NOT EXISTS audit_events_actor_idx
+  ON audit_events (actor_id, seq)
+  WHERE actor_id IS NOT NULL;
+
+CREATE INDEX IF NOT EXISTS audit_events_action_idx
+  ON audit_events (action, seq);
+
+CREATE INDEX IF NOT EXISTS audit_events_created_at_idx
+  ON audit_events (created_at);
+
+CREATE TABLE IF NOT EXISTS doc_data_keys (
+  workspace_id TEXT NOT NULL,
+  version INTEGER NOT NULL,
+  master_key_id TEXT NOT NULL,
+  wrapped_key BYTEA NOT NULL,
+  active BOOLEAN NOT NULL DEFAULT TRUE,
+  created_at TIMESTAMPTZ(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  retired_at TIMESTAMPTZ(3),
+  PRIMARY KEY (workspace_id, version)
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS doc_data_keys_active_idx
+  ON doc_data_keys (workspace_id)
+  WHERE active;
+
+CREATE INDEX IF NOT EXISTS doc_data_keys_master_key_idx
+  ON doc_data_keys (master_key_id);
@@ -0,0 +1,102 @@
+-- Embedded SQLite schema for BackendRuntime. Timestamps are stored as unix
+-- epoch milliseconds, and the doc tables stand in for the Prisma-owned ones.
+CREATE TABLE IF NOT EXISTS runtime_states (
+  purpose TEXT NOT NULL,
+  token_hash TEXT NOT NULL,
+  lookup_key TEXT,
+  payload TEXT NOT NULL,
+  attempts INTEGER NOT NULL DEFAULT 0,
+  consumed_at INTEGER,
+  expires_at INTEGER NOT NULL,
+  created_at INTEGER NOT NULL,
+  updated_at INTEGER NOT NULL,
+  PRIMARY KEY (purpose, token_hash)
+);
+
+CREATE INDEX IF NOT EXISTS runtime_states_lookup_idx
+  ON runtime_states (purpose, lookup_key)
+  WHERE lookup_key IS NOT NULL AND consumed_at IS NULL;
+
+CREATE INDEX IF NOT EXISTS runtime_states_expires_at_idx
+  ON runtime_states (expires_at);
+
+CREATE TABLE IF NOT EXISTS runtime_gates (
+  key TEXT PRIMARY KEY,
+  expires_at INTEGER NOT NULL,
+  created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS runtime_gates_expires_at_idx
+  ON runtime_gates (expires_at);
+
+CREATE TABLE IF NOT EXISTS runtime_gate_buckets (
+  key TEXT PRIMARY KEY,
+  capacity REAL NOT NULL,
+  refill_per_sec REAL NOT NULL,
+  tokens REAL NOT NULL,
+  allowed INTEGER NOT NULL,
+  refilled_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS runtime_gate_buckets_refilled_at_idx
+  ON runtime_gate_buckets (refilled_at);
+
+CREATE TABLE IF NOT EXISTS runtime_gate_window_hits (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  key TEXT NOT NULL,
+  cost INTEGER NOT NULL,
+  created_at INTEGER NOT NULL,
+  expires_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS runtime_gate_window_hits_key_idx
+  ON runtime_gate_window_hits (key, created_at);
+
+CREATE INDEX IF NOT EXISTS runtime_gate_window_hits_expires_at_idx
+  ON runtime_gate_window_hits (expires_at);
+
+CREATE TABLE IF NOT EXISTS runtime_leases (
+  key TEXT PRIMARY KEY,
+  owner TEXT NOT NULL,
+  fencing_token INTEGER NOT NULL,
+  expires_at INTEGER NOT NULL,
+  created_at INTEGER NOT NULL,
+  updated_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS runtime_leases_expires_at_idx
+  ON runtime_leases (expires_at);
+
+CREATE TABLE IF NOT EXISTS snapshots (
+  workspace_id TEXT NOT NULL,
+  guid TEXT NOT NULL,
+  blob BLOB NOT NULL,
+  size INTEGER NOT NULL,
+  created_at INTEGER NOT NULL,
+  updated_at INTEGER NOT NULL,
+  created_by TEXT,
+  updated_by TEXT,
+  PRIMARY KEY (workspace_id, guid)
+);
+
+CREATE TABLE IF NOT EXISTS updates (
+  workspace_id TEXT NOT NULL,
+  guid TEXT NOT NULL,
+  created_at INTEGER NOT NULL,
+  blob BLOB NOT NULL,
+  created_by TEXT,
+  PRIMARY KEY (workspace_id, guid, created_at)
+);
+
+CREATE TABLE IF NOT EXISTS snapshot_histories (
+  workspace_id TEXT NOT NULL,
+  guid TEXT NOT NULL,
+  timestamp INTEGER NOT NULL,
+  blob BLOB NOT NULL,
+  expired_at INTEGER NOT NULL,
+  created_by TEXT,
+  PRIMARY KEY (workspace_id, guid, timestamp)
+);
+
+CREATE INDEX IF NOT EXISTS snapshot_histories_expired_at_idx
+  ON snapshot_histories (expired_at);
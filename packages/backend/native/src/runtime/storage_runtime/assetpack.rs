@@ -11,8 +11,8 @@ use assetpack_core::{
 use sqlx::Row;
 
 use super::{
-  FsStorageConfig, MAX_BLOB_SIZE, ObjectGetResult, ObjectListEntry, ObjectMetadata, ObjectPutMetadata, RuntimeError,
-  RuntimeResult, fs_bucket_path, normalize_storage_key, system_time_ms,
+  FsStorageConfig, MAX_BLOB_SIZE, ObjectEncryption, ObjectGetResult, ObjectListEntry, ObjectMetadata,
+  ObjectPutMetadata, RuntimeError, RuntimeResult, fs_bucket_path, metrics, normalize_storage_key, system_time_ms,
 };
 
 pub(super) async fn put(
@@ -91,10 +91,18 @@ pub(super) async fn put(
     content: recipe,
   });
 
+  let encoded_bytes = objects.iter().map(|object| object.content.len() as u64).sum::<u64>();
+
   let mut tx = store
     .begin_write_tx()
     .await
     .map_err(|err| RuntimeError::invalid_state(format!("Assetpack begin write failed: {err}")))?;
+  let duplicate = sqlx::query("SELECT 1 FROM storage_assetpack_blobs WHERE recipe_hash = ?1 LIMIT 1")
+    .bind(recipe_hash.to_hex())
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|err| RuntimeError::database("Assetpack dedup lookup failed", err))?
+    .is_some();
   store
     .put_objects_batch_tx(&mut tx, &objects)
     .await
@@ -108,22 +116,29 @@ pub(super) async fn put(
       .content_type
       .unwrap_or_else(|| "application/octet-stream".to_string()),
     content_length,
-    last_modified_ms: system_time_ms(SystemTime::now())?,
+    last_modified_ms: match metadata.last_modified_ms {
+      Some(last_modified_ms) => last_modified_ms,
+      None => system_time_ms(SystemTime::now())?,
+    },
     checksum_crc32: metadata.checksum_crc32,
+    encryption: metadata.encryption,
   };
 
   sqlx::query(
     r#"
     INSERT INTO storage_assetpack_blobs
-      (scope, key, recipe_hash, content_type, content_length, checksum_crc32, last_modified_ms)
-    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+      (scope, key, recipe_hash, content_type, content_length, checksum_crc32, last_modified_ms,
+       encryption_key_id, encryption_key_version)
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
     ON CONFLICT (scope, key)
     DO UPDATE SET
       recipe_hash = excluded.recipe_hash,
       content_type = excluded.content_type,
       content_length = excluded.content_length,
       checksum_crc32 = excluded.checksum_crc32,
-      last_modified_ms = excluded.last_modified_ms
+      last_modified_ms = excluded.last_modified_ms,
+      encryption_key_id = excluded.encryption_key_id,
+      encryption_key_version = excluded.encryption_key_version
     "#,
   )
   .bind(scope)
@@ -133,12 +148,25 @@ pub(super) async fn put(
   .bind(object_metadata.content_length)
   .bind(&object_metadata.checksum_crc32)
   .bind(object_metadata.last_modified_ms)
+  .bind(
+    object_metadata
+      .encryption
+      .as_ref()
+      .map(|encryption| encryption.key_id.as_str()),
+  )
+  .bind(
+    object_metadata
+      .encryption
+      .as_ref()
+      .map(|encryption| encryption.key_version),
+  )
   .execute(&mut *tx)
   .await
   .map_err(|err| RuntimeError::database("Assetpack manifest write failed", err))?;
   tx.commit()
     .await
     .map_err(|err| RuntimeError::invalid_state(format!("Assetpack commit failed: {err}")))?;
+  metrics::record_assetpack_put(content_length as u64, if duplicate { 0 } else { encoded_bytes });
 
   Ok(object_metadata)
 }
@@ -203,6 +231,47 @@ pub(super) async fn get(config: &FsStorageConfig, scope: &str, key: &str) -> Run
   }))
 }
 
+/// Re-hash the recipe and every chunk of `key` against its `Hash32`. Returns
+/// the hashes of missing or corrupted objects, or `None` when the key has no
+/// manifest row.
+pub(super) async fn verify_chunks(
+  config: &FsStorageConfig,
+  scope: &str,
+  key: &str,
+) -> RuntimeResult<Option<Vec<String>>> {
+  normalize_storage_key(key)?;
+  let store = open_store(config).await?;
+  let Some(row) = manifest_row(&store, scope, key).await? else {
+    return Ok(None);
+  };
+  let recipe_hash = Hash32::from_hex(&row.recipe_hash)
+    .map_err(|err| RuntimeError::invalid_state(format!("Assetpack manifest recipe hash is invalid: {err}")))?;
+  let recipe_object = store
+    .get_object(&recipe_hash)
+    .await
+    .map_err(|err| RuntimeError::invalid_state(format!("Assetpack recipe read failed: {err}")))?;
+  let Some(recipe) = recipe_object.and_then(|object| parse_recipe_checked(&object.content, &recipe_hash).ok()) else {
+    return Ok(Some(vec![row.recipe_hash]));
+  };
+
+  let mut corrupted = Vec::new();
+  for (chunk_hash, expected_len) in &recipe.chunks {
+    let intact = store
+      .get_object(chunk_hash)
+      .await
+      .map_err(|err| RuntimeError::invalid_state(format!("Assetpack chunk read failed: {err}")))?
+      .is_some_and(|chunk| {
+        chunk.kind == ObjectKind::Chunk
+          && chunk.size == *expected_len as u64
+          && Hash32::sha3_256(&chunk.content) == *chunk_hash
+      });
+    if !intact {
+      corrupted.push(chunk_hash.to_hex());
+    }
+  }
+  Ok(Some(corrupted))
+}
+
 pub(super) async fn list(
   config: &FsStorageConfig,
   scope: &str,
@@ -312,13 +381,31 @@ async fn ensure_manifest_schema(store: &SqliteStore) -> RuntimeResult<()> {
   .execute(store.pool())
   .await
   .map_err(|err| RuntimeError::database("Assetpack manifest index create failed", err))?;
+  // Stores created before blob encryption lack the key columns.
+  for column in ["encryption_key_id TEXT", "encryption_key_version INTEGER"] {
+    let name = column.split_once(' ').map_or(column, |(name, _)| name);
+    let exists = sqlx::query("SELECT 1 FROM pragma_table_info('storage_assetpack_blobs') WHERE name = ?1")
+      .bind(name)
+      .fetch_optional(store.pool())
+      .await
+      .map_err(|err| RuntimeError::database("Assetpack manifest schema read failed", err))?
+      .is_some();
+    if !exists {
+      sqlx::query(&format!("ALTER TABLE storage_assetpack_blobs ADD COLUMN {column}"))
+        .execute(store.pool())
+        .await
+        .map_err(|err| RuntimeError::database("Assetpack manifest schema upgrade failed", err))?;
+    }
+  }
   Ok(())
 }
 
 async fn manifest_row(store: &SqliteStore, scope: &str, key: &str) -> RuntimeResult<Option<ManifestRow>> {
   let row = sqlx::query(
     r#"
-    SELECT recipe_hash, content_type, content_length, checksum_crc32, last_modified_ms
+    SELECT
+      recipe_hash, content_type, content_length, checksum_crc32, last_modified_ms,
+      encryption_key_id, encryption_key_version
     FROM storage_assetpack_blobs
     WHERE scope = ?1 AND key = ?2
     "#,
@@ -338,6 +425,10 @@ async fn manifest_row(store: &SqliteStore, scope: &str, key: &str) -> RuntimeRes
           content_length: row.get("content_length"),
           checksum_crc32: row.get("checksum_crc32"),
           last_modified_ms: row.get("last_modified_ms"),
+          encryption: row
+            .get::<Option<String>, _>("encryption_key_id")
+            .zip(row.get::<Option<i32>, _>("encryption_key_version"))
+            .map(|(key_id, key_version)| ObjectEncryption { key_id, key_version }),
         },
       })
     })
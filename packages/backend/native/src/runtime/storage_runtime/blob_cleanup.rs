@@ -2,10 +2,27 @@ use chrono::{DateTime, Duration, Utc};
 use sqlx::{FromRow, PgPool};
 
 use super::{
-  RuntimeBlobCleanupExecuteResult, RuntimeBlobCleanupPlanResult, RuntimeError, RuntimeResult, StorageRuntime,
-  napi_error,
+  RuntimeBlobCleanupExecuteResult, RuntimeBlobCleanupPlanResult, RuntimeBlobCleanupPurgeResult,
+  RuntimeBlobCleanupRestoreResult, RuntimeError, RuntimeResult, StorageRuntime,
+  blob_derivatives::delete_blob_derivatives, blob_quota::release_blob_usage, blob_reclaimer::delete_object_idempotent,
+  metrics, napi_error, upsert_completed_blob,
 };
 
+// Executed candidates are moved here instead of deleted, so a bad cleanup
+// plan can be undone until the restore deadline.
+pub(super) const QUARANTINE_PREFIX: &str = ".quarantine";
+const DEFAULT_QUARANTINE_DAYS: i64 = 7;
+
+/// Quarantined objects of a workspace. They are kept per workspace so a
+/// workspace purge can remove them with the rest of its objects.
+pub(super) fn quarantine_prefix(workspace_id: &str) -> String {
+  format!("{QUARANTINE_PREFIX}/{workspace_id}/")
+}
+
+fn quarantine_key(workspace_id: &str, run_id: &str, blob_key: &str) -> String {
+  format!("{}{run_id}/{blob_key}", quarantine_prefix(workspace_id))
+}
+
 #[derive(FromRow)]
 struct BlobCandidateRow {
   workspace_id: String,
@@ -19,6 +36,23 @@ struct MarkedCandidateRow {
   blob_key: String,
 }
 
+#[derive(FromRow)]
+struct QuarantinedCandidateRow {
+  run_id: String,
+  workspace_id: String,
+  blob_key: String,
+  evidence: serde_json::Value,
+}
+
+impl QuarantinedCandidateRow {
+  fn quarantine_key(&self) -> Option<&str> {
+    self
+      .evidence
+      .pointer("/quarantine/key")
+      .and_then(|value| value.as_str())
+  }
+}
+
 fn push_workspace_once(workspace_ids: &mut Vec<String>, workspace_id: &str) {
   if !workspace_ids.iter().any(|id| id == workspace_id) {
     workspace_ids.push(workspace_id.to_string());
@@ -303,6 +337,132 @@ async fn mark_candidate_status(
   Ok(())
 }
 
+async fn mark_candidate_quarantined(
+  pool: &PgPool,
+  run_id: &str,
+  workspace_id: &str,
+  blob_key: &str,
+  quarantine_key: &str,
+  restore_deadline: DateTime<Utc>,
+  deleted_metadata: i64,
+) -> RuntimeResult<()> {
+  sqlx::query(
+    r#"
+    UPDATE blob_cleanup_candidates
+    SET status = 'quarantined',
+        executed_at = CURRENT_TIMESTAMP,
+        restore_deadline = $4,
+        evidence = evidence || $5,
+        error = NULL
+    WHERE workspace_id = $1 AND blob_key = $2 AND run_id = $3::uuid
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(blob_key)
+  .bind(run_id)
+  .bind(restore_deadline)
+  .bind(serde_json::json!({
+    "deletedMetadata": deleted_metadata,
+    "quarantine": {
+      "key": quarantine_key,
+      "restoreDeadlineMs": restore_deadline.timestamp_millis(),
+    },
+  }))
+  .execute(pool)
+  .await
+  .map_err(|err| RuntimeError::database("Blob cleanup mark candidate quarantined failed", err))?;
+  Ok(())
+}
+
+async fn load_quarantined_candidates(
+  pool: &PgPool,
+  run_id: Option<&str>,
+  object_keys: Option<&[String]>,
+  limit: i64,
+) -> RuntimeResult<Vec<QuarantinedCandidateRow>> {
+  sqlx::query_as::<_, QuarantinedCandidateRow>(
+    r#"
+    SELECT run_id::text AS run_id, workspace_id, blob_key, evidence
+    FROM blob_cleanup_candidates
+    WHERE status = 'quarantined'
+      AND ($1::text IS NULL OR run_id = $1::uuid)
+      AND ($2::text[] IS NULL OR workspace_id || '/' || blob_key = ANY($2))
+    ORDER BY executed_at ASC
+    LIMIT $3
+    "#,
+  )
+  .bind(run_id)
+  .bind(object_keys)
+  .bind(limit)
+  .fetch_all(pool)
+  .await
+  .map_err(|err| RuntimeError::database("Blob cleanup load quarantined candidates failed", err))
+}
+
+/// Drop the derivatives of a blob removed for good. Blob keys are content
+/// hashes, so a blob uploaded again under the same key keeps them.
+async fn delete_removed_blob_derivatives(
+  runtime: &StorageRuntime,
+  pool: &PgPool,
+  workspace_id: &str,
+  blob_key: &str,
+) -> RuntimeResult<()> {
+  let live = sqlx::query_scalar::<_, bool>(
+    "SELECT EXISTS (SELECT 1 FROM blobs WHERE workspace_id = $1 AND key = $2 AND deleted_at IS NULL)",
+  )
+  .bind(workspace_id)
+  .bind(blob_key)
+  .fetch_one(pool)
+  .await
+  .map_err(|err| RuntimeError::database("Blob cleanup load live blob failed", err))?;
+  if live {
+    return Ok(());
+  }
+  delete_blob_derivatives(runtime, pool, workspace_id, blob_key).await
+}
+
+async fn load_expired_quarantine(pool: &PgPool, limit: i64) -> RuntimeResult<Vec<QuarantinedCandidateRow>> {
+  sqlx::query_as::<_, QuarantinedCandidateRow>(
+    r#"
+    SELECT run_id::text AS run_id, workspace_id, blob_key, evidence
+    FROM blob_cleanup_candidates
+    WHERE status = 'quarantined' AND restore_deadline <= CURRENT_TIMESTAMP
+    ORDER BY restore_deadline ASC
+    LIMIT $1
+    "#,
+  )
+  .bind(limit)
+  .fetch_all(pool)
+  .await
+  .map_err(|err| RuntimeError::database("Blob cleanup load expired quarantine failed", err))
+}
+
+async fn record_quarantine_run(
+  pool: &PgPool,
+  kind: &str,
+  scanned: i64,
+  changed: i64,
+  failed: i64,
+  metadata: serde_json::Value,
+) -> RuntimeResult<()> {
+  sqlx::query(
+    r#"
+    INSERT INTO blob_reconciliation_runs
+      (kind, mode, status, finished_at, scanned, changed, failed, metadata)
+    VALUES ($1, 'execute', 'finished', CURRENT_TIMESTAMP, $2, $3, $4, $5)
+    "#,
+  )
+  .bind(kind)
+  .bind(scanned as i32)
+  .bind(changed as i32)
+  .bind(failed as i32)
+  .bind(metadata)
+  .execute(pool)
+  .await
+  .map_err(|err| RuntimeError::database("Blob cleanup quarantine run record failed", err))?;
+  Ok(())
+}
+
 async fn finish_execute_run(
   pool: &PgPool,
   run_id: &str,
@@ -326,6 +486,7 @@ async fn finish_execute_run(
   .bind(result.failed as i32)
   .bind(serde_json::json!({
     "deletedObjects": result.deleted_objects,
+    "quarantinedObjects": result.quarantined_objects,
     "deletedMetadata": result.deleted_metadata,
     "skippedStillReferenced": result.skipped_still_referenced,
     "failed": result.failed,
@@ -405,6 +566,9 @@ impl StorageRuntime {
     }
 
     let pool = self.pool().await?;
+    // Candidates found on a lagging replica are re-checked against the primary
+    // before anything is deleted, so the scan itself can run on a replica.
+    let read_pool = self.read_pool().await?;
     let run_id = create_run(&pool, &workspace_id).await?;
     let mut result = RuntimeBlobCleanupPlanResult {
       run_id: Some(run_id.clone()),
@@ -427,17 +591,17 @@ impl StorageRuntime {
     }
 
     let min_last_modified = Utc::now() - Duration::days(grace_period_days);
-    let rows = load_completed_blobs(&pool, &workspace_id, cursor.as_deref(), limit).await?;
+    let rows = load_completed_blobs(&read_pool, &workspace_id, cursor.as_deref(), limit).await?;
     let has_more = rows.len() == limit as usize;
     let mut last_blob_key = None;
     for row in rows {
       result.scanned_blobs += 1;
       last_blob_key = Some(row.key.clone());
-      if has_doc_ref(&pool, &row.workspace_id, &row.key).await? {
+      if has_doc_ref(&read_pool, &row.workspace_id, &row.key).await? {
         result.protected_by_doc_refs += 1;
         continue;
       }
-      if has_other_ref(&pool, &row.workspace_id, &row.key).await? {
+      if has_other_ref(&read_pool, &row.workspace_id, &row.key).await? {
         result.protected_by_other_refs += 1;
         continue;
       }
@@ -460,15 +624,30 @@ impl StorageRuntime {
     upsert_plan_checkpoint(&pool, &workspace_id, last_blob_key.as_deref(), !has_more).await?;
 
     finish_run(&pool, &run_id, &workspace_id, &result, Vec::new()).await?;
+    metrics::record_blob_cleanup(
+      "plan",
+      &[
+        ("marked", result.candidates_marked),
+        (
+          "protected",
+          result.protected_by_doc_refs + result.protected_by_metadata + result.protected_by_other_refs,
+        ),
+      ],
+    );
     Ok(result)
   }
 
+  /// Remove marked candidates. Objects are moved to the quarantine prefix and
+  /// can be restored for `quarantine_days` (default 7) before
+  /// `purge_quarantined_blobs` deletes them; `0` deletes them immediately.
+  /// Image derivatives are deleted together with the object.
   #[napi]
   pub async fn execute_blob_cleanup_candidates(
     &self,
     run_id: String,
     grace_period_days: i64,
     limit: i64,
+    quarantine_days: Option<i64>,
   ) -> napi::Result<RuntimeBlobCleanupExecuteResult> {
     if limit <= 0 {
       return Err(napi_error("blob cleanup execute limit must be positive"));
@@ -476,13 +655,19 @@ impl StorageRuntime {
     if grace_period_days < 0 {
       return Err(napi_error("blob cleanup grace period must be non-negative"));
     }
+    let quarantine_days = quarantine_days.unwrap_or(DEFAULT_QUARANTINE_DAYS);
+    if quarantine_days < 0 {
+      return Err(napi_error("blob cleanup quarantine period must be non-negative"));
+    }
 
     let pool = self.pool().await?;
     let min_last_modified = Utc::now() - Duration::days(grace_period_days);
+    let restore_deadline = Utc::now() + Duration::days(quarantine_days);
     let rows = load_marked_candidates(&pool, &run_id, limit).await?;
     let mut result = RuntimeBlobCleanupExecuteResult {
       scanned_candidates: rows.len() as i64,
       deleted_objects: 0,
+      quarantined_objects: 0,
       deleted_metadata: 0,
       skipped_still_referenced: 0,
       failed: 0,
@@ -510,6 +695,7 @@ impl StorageRuntime {
 
       let object_key = format!("{}/{}", row.workspace_id, row.blob_key);
       let mut object_was_missing = false;
+      let mut quarantined_at = None;
       let metadata = match self.object_storage_head(object_key.clone()).await {
         Ok(metadata) => metadata,
         Err(err) => {
@@ -544,51 +730,98 @@ impl StorageRuntime {
           .await?;
           continue;
         }
-        if let Err(err) = self.object_storage_delete(object_key).await {
-          result.failed += 1;
-          mark_candidate_status(
-            &pool,
-            &run_id,
-            &row.workspace_id,
-            &row.blob_key,
-            "failed",
-            serde_json::json!({ "failure": "object_delete_failed" }),
-            Some(&err.to_string()),
-          )
-          .await?;
-          continue;
-        }
-        result.deleted_objects += 1;
-      } else {
-        object_was_missing = true;
-      }
-
-      let deleted_metadata =
-        match sqlx::query("DELETE FROM blobs WHERE workspace_id = $1 AND key = $2 AND deleted_at IS NULL")
-          .bind(&row.workspace_id)
-          .bind(&row.blob_key)
-          .execute(&pool)
-          .await
-        {
-          Ok(result) => result.rows_affected() as i64,
+        let removed = if quarantine_days > 0 {
+          let target = quarantine_key(&row.workspace_id, &run_id, &row.blob_key);
+          self
+            .quarantine_object(&object_key, &target)
+            .await
+            .map(|moved| moved.then_some(target))
+        } else {
+          self.object_storage_delete(object_key).await.map(|()| None)
+        };
+        match removed {
+          Ok(Some(target)) => {
+            result.quarantined_objects += 1;
+            quarantined_at = Some(target);
+          }
+          Ok(None) if quarantine_days > 0 => object_was_missing = true,
+          Ok(None) => result.deleted_objects += 1,
           Err(err) => {
             result.failed += 1;
+            let failure = if quarantine_days > 0 {
+              "object_quarantine_failed"
+            } else {
+              "object_delete_failed"
+            };
             mark_candidate_status(
               &pool,
               &run_id,
               &row.workspace_id,
               &row.blob_key,
               "failed",
-              serde_json::json!({ "failure": "metadata_delete_failed" }),
+              serde_json::json!({ "failure": failure }),
               Some(&err.to_string()),
             )
             .await?;
             continue;
           }
-        };
+        }
+      } else {
+        object_was_missing = true;
+      }
+
+      // Returns the bytes each deleted row counted towards storage usage.
+      let freed_bytes = match sqlx::query_scalar::<_, i64>(
+        r#"
+        DELETE FROM blobs
+        WHERE workspace_id = $1 AND key = $2 AND deleted_at IS NULL
+        RETURNING CASE WHEN status = 'completed' THEN size ELSE 0 END::BIGINT
+        "#,
+      )
+      .bind(&row.workspace_id)
+      .bind(&row.blob_key)
+      .fetch_all(&pool)
+      .await
+      {
+        Ok(freed_bytes) => freed_bytes,
+        Err(err) => {
+          result.failed += 1;
+          mark_candidate_status(
+            &pool,
+            &run_id,
+            &row.workspace_id,
+            &row.blob_key,
+            "failed",
+            serde_json::json!({ "failure": "metadata_delete_failed" }),
+            Some(&err.to_string()),
+          )
+          .await?;
+          continue;
+        }
+      };
+      let deleted_metadata = freed_bytes.len() as i64;
+      release_blob_usage(&pool, &row.workspace_id, freed_bytes.iter().sum()).await?;
       result.deleted_metadata += deleted_metadata;
       push_workspace_once(&mut result.workspace_ids, &row.workspace_id);
 
+      // Quarantined blobs keep their derivatives until they are purged, so a
+      // restore brings both back.
+      if quarantined_at.is_none() {
+        delete_removed_blob_derivatives(self, &pool, &row.workspace_id, &row.blob_key).await?;
+      }
+      if let Some(target) = quarantined_at {
+        mark_candidate_quarantined(
+          &pool,
+          &run_id,
+          &row.workspace_id,
+          &row.blob_key,
+          &target,
+          restore_deadline,
+          deleted_metadata,
+        )
+        .await?;
+        continue;
+      }
       mark_candidate_status(
         &pool,
         &run_id,
@@ -605,6 +838,234 @@ impl StorageRuntime {
     }
 
     finish_execute_run(&pool, &run_id, &result).await?;
+    metrics::record_blob_cleanup(
+      "execute",
+      &[
+        ("deleted", result.deleted_objects),
+        ("quarantined", result.quarantined_objects),
+        ("skipped", result.skipped_still_referenced),
+        ("failed", result.failed),
+      ],
+    );
     Ok(result)
   }
+
+  /// Move quarantined candidates back to their original keys and restore
+  /// their `blobs` rows. Pass a cleanup `run_id`, the `workspaceId/blobKey`
+  /// object keys to restore, or both.
+  #[napi]
+  pub async fn restore_blob_cleanup_candidates(
+    &self,
+    run_id: Option<String>,
+    object_keys: Option<Vec<String>>,
+    limit: i64,
+  ) -> napi::Result<RuntimeBlobCleanupRestoreResult> {
+    if limit <= 0 {
+      return Err(napi_error("blob cleanup restore limit must be positive"));
+    }
+    if run_id.is_none() && object_keys.is_none() {
+      return Err(napi_error("blob cleanup restore requires a run id or object keys"));
+    }
+
+    let pool = self.pool().await?;
+    let rows = load_quarantined_candidates(&pool, run_id.as_deref(), object_keys.as_deref(), limit).await?;
+    let mut result = RuntimeBlobCleanupRestoreResult {
+      scanned_candidates: rows.len() as i64,
+      restored_objects: 0,
+      failed: 0,
+      workspace_ids: Vec::new(),
+    };
+    for row in rows {
+      match self.restore_candidate(&pool, &row).await {
+        Ok(()) => {
+          result.restored_objects += 1;
+          push_workspace_once(&mut result.workspace_ids, &row.workspace_id);
+          mark_candidate_status(
+            &pool,
+            &row.run_id,
+            &row.workspace_id,
+            &row.blob_key,
+            "restored",
+            serde_json::json!({ "restoredAtMs": Utc::now().timestamp_millis() }),
+            None,
+          )
+          .await?;
+        }
+        // The candidate stays quarantined so the restore can be retried.
+        Err(_) => result.failed += 1,
+      }
+    }
+
+    record_quarantine_run(
+      &pool,
+      "blob_cleanup_restore",
+      result.scanned_candidates,
+      result.restored_objects,
+      result.failed,
+      serde_json::json!({ "cleanupRunId": run_id, "objectKeys": object_keys }),
+    )
+    .await?;
+    metrics::record_blob_cleanup(
+      "restore",
+      &[("restored", result.restored_objects), ("failed", result.failed)],
+    );
+    Ok(result)
+  }
+
+  /// Delete quarantined objects whose restore deadline has passed, together
+  /// with their image derivatives.
+  #[napi]
+  pub async fn purge_quarantined_blobs(&self, limit: i64) -> napi::Result<RuntimeBlobCleanupPurgeResult> {
+    if limit <= 0 {
+      return Err(napi_error("blob cleanup purge limit must be positive"));
+    }
+
+    let pool = self.pool().await?;
+    let rows = load_expired_quarantine(&pool, limit).await?;
+    let mut result = RuntimeBlobCleanupPurgeResult {
+      scanned_candidates: rows.len() as i64,
+      purged_objects: 0,
+      failed: 0,
+    };
+    for row in rows {
+      let deleted = match row.quarantine_key() {
+        Some(key) => match delete_object_idempotent(self, key).await {
+          Ok(()) => delete_removed_blob_derivatives(self, &pool, &row.workspace_id, &row.blob_key).await,
+          Err(err) => Err(err),
+        },
+        None => Err(RuntimeError::invalid_state(
+          "blob cleanup quarantine location is missing",
+        )),
+      };
+      let (status, error) = match &deleted {
+        Ok(()) => {
+          result.purged_objects += 1;
+          ("purged", None)
+        }
+        Err(err) => {
+          result.failed += 1;
+          ("quarantined", Some(err.to_string()))
+        }
+      };
+      mark_candidate_status(
+        &pool,
+        &row.run_id,
+        &row.workspace_id,
+        &row.blob_key,
+        status,
+        serde_json::json!({}),
+        error.as_deref(),
+      )
+      .await?;
+    }
+
+    record_quarantine_run(
+      &pool,
+      "blob_cleanup_purge",
+      result.scanned_candidates,
+      result.purged_objects,
+      result.failed,
+      serde_json::json!({}),
+    )
+    .await?;
+    metrics::record_blob_cleanup("purge", &[("purged", result.purged_objects), ("failed", result.failed)]);
+    Ok(result)
+  }
+}
+
+impl StorageRuntime {
+  /// Copy an object to its quarantine key, then remove the original. Sealed
+  /// objects stay sealed and only decrypt again once restored to `key`.
+  /// Returns false when the object disappeared first.
+  async fn quarantine_object(&self, key: &str, quarantine_key: &str) -> RuntimeResult<bool> {
+    if self.stored_object_copy(key, quarantine_key).await?.is_none() {
+      return Ok(false);
+    }
+    delete_object_idempotent(self, key).await?;
+    Ok(true)
+  }
+
+  async fn restore_candidate(&self, pool: &PgPool, row: &QuarantinedCandidateRow) -> RuntimeResult<()> {
+    let quarantine_key = row
+      .quarantine_key()
+      .ok_or_else(|| RuntimeError::invalid_state("blob cleanup quarantine location is missing"))?;
+    let object_key = format!("{}/{}", row.workspace_id, row.blob_key);
+    // Blob keys are content hashes, so a blob uploaded again since the
+    // cleanup already holds the quarantined bytes.
+    if self.stored_object_head(&object_key).await?.is_none()
+      && self.stored_object_copy(quarantine_key, &object_key).await?.is_none()
+    {
+      return Err(RuntimeError::invalid_state(format!(
+        "blob cleanup quarantined object {quarantine_key} is missing"
+      )));
+    }
+    let metadata = self
+      .object_storage_head(object_key.clone())
+      .await?
+      .ok_or_else(|| RuntimeError::invalid_state(format!("blob cleanup restored object {object_key} is missing")))?;
+    upsert_completed_blob(
+      pool,
+      &row.workspace_id,
+      &row.blob_key,
+      &metadata.content_type,
+      metadata.content_length,
+    )
+    .await?;
+    delete_object_idempotent(self, quarantine_key).await
+  }
+
+  /// Count a read of a blob that is missing because cleanup quarantined it.
+  /// Such reads mean the cleanup plan was wrong; the count is kept on the
+  /// candidate and in metrics. Best effort: failures never affect the read.
+  pub(super) async fn record_quarantined_read(&self, scope: &str, key: &str) {
+    let Some((workspace_id, blob_key)) = key.split_once('/').filter(|_| scope == "blob") else {
+      return;
+    };
+    let Ok(pool) = self.pool().await else {
+      return;
+    };
+    let updated = sqlx::query(
+      r#"
+      UPDATE blob_cleanup_candidates
+      SET evidence = evidence || jsonb_build_object(
+        'requestCount', COALESCE((evidence->>'requestCount')::int, 0) + 1,
+        'lastRequestedAtMs', (EXTRACT(EPOCH FROM CURRENT_TIMESTAMP) * 1000)::bigint
+      )
+      WHERE workspace_id = $1 AND blob_key = $2 AND status = 'quarantined'
+      "#,
+    )
+    .bind(workspace_id)
+    .bind(blob_key)
+    .execute(&pool)
+    .await;
+    if updated.is_ok_and(|updated| updated.rows_affected() > 0) {
+      metrics::record_blob_quarantine_read();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn quarantine_keys_stay_under_the_workspace_quarantine_prefix() {
+    let key = quarantine_key("workspace", "run", "blob");
+    assert_eq!(key, ".quarantine/workspace/run/blob");
+    assert!(key.starts_with(&quarantine_prefix("workspace")));
+    assert!(!key.starts_with(&quarantine_prefix("work")));
+  }
+
+  #[test]
+  fn quarantined_candidates_read_their_location_from_evidence() {
+    let row = |evidence| QuarantinedCandidateRow {
+      run_id: "run".to_string(),
+      workspace_id: "workspace".to_string(),
+      blob_key: "blob".to_string(),
+      evidence,
+    };
+    let quarantined = row(serde_json::json!({ "quarantine": { "key": ".quarantine/workspace/run/blob" } }));
+    assert_eq!(quarantined.quarantine_key(), Some(".quarantine/workspace/run/blob"));
+    assert_eq!(row(serde_json::json!({ "metadataSize": 1 })).quarantine_key(), None);
+  }
 }
@@ -0,0 +1,451 @@
+use std::sync::Arc;
+
+use serde::Deserialize;
+use sqlx::PgPool;
+
+use super::{
+  ObjectPutMetadata, RuntimeBlobDerivativeResult, RuntimeError, RuntimeObjectGetResult, RuntimeResult, StorageRuntime,
+  blob_reclaimer::delete_object_idempotent, metrics, napi_error,
+};
+use crate::image::{image_dimensions, process_image_inner};
+
+/// Derivatives live under the workspace prefix next to their source, so they
+/// are sealed, migrated and purged together with the workspace's blobs.
+pub(super) const DERIVATIVE_DIR: &str = ".derivatives";
+const DERIVATIVE_MIME: &str = "image/webp";
+const DEFAULT_EDGES: [u32; 3] = [256, 512, 1024];
+// Larger sources are served as-is rather than downloaded again for resizing.
+const MAX_DERIVATIVE_SOURCE_BYTES: i64 = 64 * 1024 * 1024;
+
+/// `storages["blob.derivatives"]`: WebP renditions generated when an image
+/// blob upload completes, bounded to each configured longest edge.
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct ImageDerivativeConfig {
+  pub(super) enabled: bool,
+  pub(super) edges: Vec<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub(super) struct ImageDerivativeConfigFile {
+  #[serde(default)]
+  enabled: bool,
+  edges: Option<Vec<u32>>,
+}
+
+impl Default for ImageDerivativeConfig {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      edges: DEFAULT_EDGES.to_vec(),
+    }
+  }
+}
+
+impl From<ImageDerivativeConfigFile> for ImageDerivativeConfig {
+  fn from(config: ImageDerivativeConfigFile) -> Self {
+    let mut edges = config.edges.unwrap_or_else(|| DEFAULT_EDGES.to_vec());
+    edges.retain(|edge| *edge > 0);
+    edges.sort_unstable();
+    edges.dedup();
+    Self {
+      enabled: config.enabled,
+      edges,
+    }
+  }
+}
+
+pub(super) fn derivative_key(workspace_id: &str, blob_key: &str, max_edge: u32) -> String {
+  format!("{workspace_id}/{DERIVATIVE_DIR}/{blob_key}/{max_edge}")
+}
+
+pub(super) fn is_derivative_key(object_key: &str) -> bool {
+  object_key.split('/').nth(1) == Some(DERIVATIVE_DIR)
+}
+
+/// Vector images are already resolution independent and are not decoded by
+/// the image pipeline.
+fn is_derivable(content_type: &str) -> bool {
+  content_type.starts_with("image/") && !content_type.starts_with("image/svg")
+}
+
+/// Configured edges that actually shrink an image whose longest side is
+/// `source_edge`; the original already serves every larger request.
+fn derivative_edges(edges: &[u32], source_edge: u32) -> Vec<u32> {
+  edges.iter().copied().filter(|edge| *edge < source_edge).collect()
+}
+
+/// The smallest derivative that still covers `max_edge`, if any.
+fn best_edge(available: &[u32], max_edge: u32) -> Option<u32> {
+  available.iter().copied().filter(|edge| *edge >= max_edge).min()
+}
+
+async fn load_derivative_edges(pool: &PgPool, workspace_id: &str, blob_key: &str) -> RuntimeResult<Vec<u32>> {
+  let edges = sqlx::query_scalar::<_, i32>(
+    r#"
+    SELECT max_edge
+    FROM blob_derivatives
+    WHERE workspace_id = $1 AND blob_key = $2
+    ORDER BY max_edge ASC
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(blob_key)
+  .fetch_all(pool)
+  .await
+  .map_err(|err| RuntimeError::database("BlobDerivatives load derivatives failed", err))?;
+  Ok(edges.into_iter().filter_map(|edge| u32::try_from(edge).ok()).collect())
+}
+
+async fn upsert_derivative(
+  pool: &PgPool,
+  workspace_id: &str,
+  blob_key: &str,
+  max_edge: u32,
+  size: i64,
+) -> RuntimeResult<()> {
+  sqlx::query(
+    r#"
+    INSERT INTO blob_derivatives (workspace_id, blob_key, max_edge, size)
+    VALUES ($1, $2, $3, $4)
+    ON CONFLICT (workspace_id, blob_key, max_edge)
+    DO UPDATE SET size = EXCLUDED.size, created_at = CURRENT_TIMESTAMP
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(blob_key)
+  .bind(max_edge as i32)
+  .bind(size)
+  .execute(pool)
+  .await
+  .map_err(|err| RuntimeError::database("BlobDerivatives upsert derivative failed", err))?;
+  Ok(())
+}
+
+/// Delete the derivatives of a blob. Objects go first so a failed delete
+/// leaves the rows to retry from.
+pub(super) async fn delete_blob_derivatives(
+  runtime: &StorageRuntime,
+  pool: &PgPool,
+  workspace_id: &str,
+  blob_key: &str,
+) -> RuntimeResult<()> {
+  for edge in load_derivative_edges(pool, workspace_id, blob_key).await? {
+    delete_object_idempotent(runtime, &derivative_key(workspace_id, blob_key, edge)).await?;
+  }
+  sqlx::query("DELETE FROM blob_derivatives WHERE workspace_id = $1 AND blob_key = $2")
+    .bind(workspace_id)
+    .bind(blob_key)
+    .execute(pool)
+    .await
+    .map_err(|err| RuntimeError::database("BlobDerivatives delete derivatives failed", err))?;
+  Ok(())
+}
+
+#[napi_derive::napi]
+impl StorageRuntime {
+  /// Generate the configured derivatives of an existing image blob. The
+  /// server runs this as a job after each completed upload, since it reads
+  /// the whole source (up to 64 MiB) and resizes it once per edge.
+  #[napi]
+  pub async fn generate_blob_derivatives(
+    &self,
+    workspace_id: String,
+    key: String,
+  ) -> napi::Result<RuntimeBlobDerivativeResult> {
+    let Some(metadata) = self.object_storage_head(format!("{workspace_id}/{key}")).await? else {
+      return Err(napi_error("blob derivative source does not exist"));
+    };
+    let generated_edges = self
+      .generate_image_derivatives(&workspace_id, &key, &metadata.content_type, metadata.content_length)
+      .await?;
+    Ok(RuntimeBlobDerivativeResult { generated_edges })
+  }
+
+  /// Read a blob for display at most `max_edge` pixels on its longest side:
+  /// the smallest derivative that covers `max_edge`, or the original when
+  /// none does. Scopes other than `blob` always return the original.
+  #[napi]
+  pub async fn get_object_derivative(
+    &self,
+    scope: String,
+    key: String,
+    max_edge: u32,
+  ) -> napi::Result<Option<RuntimeObjectGetResult>> {
+    if max_edge == 0 {
+      return Err(napi_error("blob derivative max edge must be positive"));
+    }
+    if scope == "blob"
+      && let Some((workspace_id, blob_key)) = key.split_once('/')
+    {
+      let edges = load_derivative_edges(&self.read_pool().await?, workspace_id, blob_key).await?;
+      if let Some(edge) = best_edge(&edges, max_edge)
+        && let Some(object) = self
+          .object_storage_get(&derivative_key(workspace_id, blob_key, edge))
+          .await?
+      {
+        return Ok(Some(object.into()));
+      }
+    }
+    self.get_object(scope, key).await
+  }
+}
+
+impl StorageRuntime {
+  /// Resize an image blob to every configured edge below its own size and
+  /// store the WebP results. Sources that do not decode produce none.
+  /// Derivatives are not counted against the workspace storage quota.
+  pub(super) async fn generate_image_derivatives(
+    &self,
+    workspace_id: &str,
+    key: &str,
+    content_type: &str,
+    content_length: i64,
+  ) -> RuntimeResult<Vec<u32>> {
+    let config = self.config()?.image_derivatives;
+    if !config.enabled || !is_derivable(content_type) || content_length > MAX_DERIVATIVE_SOURCE_BYTES {
+      return Ok(Vec::new());
+    }
+    let Some(source) = self.object_storage_get(&format!("{workspace_id}/{key}")).await? else {
+      return Ok(Vec::new());
+    };
+    let Ok((width, height)) = image_dimensions(&source.body) else {
+      metrics::record_image_derivative("undecodable");
+      return Ok(Vec::new());
+    };
+
+    let pool = self.pool().await?;
+    let source = Arc::new(source.body);
+    let mut generated_edges = Vec::new();
+    for edge in derivative_edges(&config.edges, width.max(height)) {
+      let input = source.clone();
+      let output = tokio::task::spawn_blocking(move || process_image_inner(&input, edge, false))
+        .await
+        .map_err(|err| RuntimeError::invalid_state(format!("image derivative task failed: {err}")))?;
+      let Ok(body) = output else {
+        metrics::record_image_derivative("failed");
+        continue;
+      };
+      let size = body.len() as i64;
+      self
+        .object_storage_put(
+          &derivative_key(workspace_id, key, edge),
+          body,
+          ObjectPutMetadata {
+            content_type: Some(DERIVATIVE_MIME.to_string()),
+            content_length: Some(size),
+            ..Default::default()
+          },
+        )
+        .await?;
+      upsert_derivative(&pool, workspace_id, key, edge, size).await?;
+      metrics::record_image_derivative("generated");
+      generated_edges.push(edge);
+    }
+    Ok(generated_edges)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{collections::HashMap, sync::RwLock};
+
+  use image::{ExtendedColorType, ImageEncoder, codecs::png::PngEncoder};
+  use sqlx::postgres::PgPoolOptions;
+  use tokio::sync::Mutex;
+
+  use super::{
+    super::{
+      DatabasePoolConfig, DatabaseReplicaConfig, DocEncryptionConfig, FsStorageConfig, StorageBackendConfig,
+      StorageRuntimeConfig, migrate_runtime_tables,
+    },
+    *,
+  };
+
+  const TEST_WORKSPACE: &str = "rust-test-blob-derivatives";
+
+  fn encode_png(width: u32, height: u32) -> Vec<u8> {
+    let image = image::RgbaImage::from_pixel(width, height, image::Rgba([0, 0, 255, 255]));
+    let mut encoded = Vec::new();
+    PngEncoder::new(&mut encoded)
+      .write_image(image.as_raw(), width, height, ExtendedColorType::Rgba8)
+      .unwrap();
+    encoded
+  }
+
+  /// A runtime over the `DATABASE_URL` database with an fs blob backend
+  /// under `root`, or `None` when no database is configured.
+  async fn derivative_runtime(root: &std::path::Path) -> Option<(StorageRuntime, PgPool)> {
+    let database_url = std::env::var("DATABASE_URL").ok()?;
+    let pool = PgPoolOptions::new()
+      .max_connections(2)
+      .connect(&database_url)
+      .await
+      .unwrap();
+    migrate_runtime_tables(&pool).await.unwrap();
+    sqlx::query("DELETE FROM workspaces WHERE id = $1")
+      .bind(TEST_WORKSPACE)
+      .execute(&pool)
+      .await
+      .unwrap();
+    sqlx::query("DELETE FROM blob_derivatives WHERE workspace_id = $1")
+      .bind(TEST_WORKSPACE)
+      .execute(&pool)
+      .await
+      .unwrap();
+    sqlx::query("INSERT INTO workspaces (id, public) VALUES ($1, false)")
+      .bind(TEST_WORKSPACE)
+      .execute(&pool)
+      .await
+      .unwrap();
+
+    let backend = StorageBackendConfig::Fs(FsStorageConfig {
+      provider: "fs".to_string(),
+      root: root.to_string_lossy().to_string(),
+      bucket: "blob".to_string(),
+    });
+    let runtime = StorageRuntime {
+      config: RwLock::new(StorageRuntimeConfig {
+        database_url,
+        pool: DatabasePoolConfig::default(),
+        replicas: DatabaseReplicaConfig::default(),
+        backends: HashMap::from([("blob".to_string(), backend)]),
+        doc_encryption: DocEncryptionConfig::default(),
+        blob_encryption: false,
+        image_derivatives: ImageDerivativeConfig {
+          enabled: true,
+          edges: DEFAULT_EDGES.to_vec(),
+        },
+      }),
+      forwarded: RwLock::new(None),
+      pool: Mutex::new(Some(pool.clone())),
+      replicas: Mutex::new(None),
+    };
+    Some((runtime, pool))
+  }
+
+  #[test]
+  fn derivative_keys_link_back_to_the_source_blob() {
+    assert_eq!(derivative_key("ws", "blob", 256), "ws/.derivatives/blob/256");
+    assert!(is_derivative_key("ws/.derivatives/blob/256"));
+    assert!(!is_derivative_key("ws/blob"));
+    assert!(!is_derivative_key(".derivatives/blob"));
+  }
+
+  #[test]
+  fn config_normalizes_edges() {
+    let config = ImageDerivativeConfig::from(ImageDerivativeConfigFile {
+      enabled: true,
+      edges: Some(vec![1024, 0, 256, 1024]),
+    });
+    assert_eq!(config.edges, vec![256, 1024]);
+    assert_eq!(
+      ImageDerivativeConfig::from(ImageDerivativeConfigFile::default()).edges,
+      DEFAULT_EDGES.to_vec()
+    );
+    assert!(is_derivable("image/png"));
+    assert!(!is_derivable("image/svg+xml"));
+    assert!(!is_derivable("application/pdf"));
+  }
+
+  #[test]
+  fn picks_the_smallest_covering_derivative() {
+    assert_eq!(derivative_edges(&[256, 512, 1024], 800), vec![256, 512]);
+    assert_eq!(best_edge(&[256, 512], 300), Some(512));
+    assert_eq!(best_edge(&[256, 512], 256), Some(256));
+    assert_eq!(best_edge(&[256, 512], 600), None);
+  }
+
+  #[tokio::test]
+  async fn derivatives_are_generated_served_by_edge_and_released_with_their_blob() {
+    let temp = tempfile::tempdir().unwrap();
+    let Some((runtime, pool)) = derivative_runtime(temp.path()).await else {
+      return;
+    };
+    let source = encode_png(800, 600);
+    let source_key = format!("{TEST_WORKSPACE}/image");
+    runtime
+      .object_storage_put(
+        &source_key,
+        source.clone(),
+        ObjectPutMetadata {
+          content_type: Some("image/png".to_string()),
+          content_length: Some(source.len() as i64),
+          ..Default::default()
+        },
+      )
+      .await
+      .unwrap();
+
+    let result = runtime
+      .generate_blob_derivatives(TEST_WORKSPACE.to_string(), "image".to_string())
+      .await
+      .unwrap();
+    assert_eq!(result.generated_edges, vec![256, 512]);
+    assert_eq!(
+      load_derivative_edges(&pool, TEST_WORKSPACE, "image").await.unwrap(),
+      vec![256, 512]
+    );
+
+    for (max_edge, expected) in [(100, (256, 192)), (256, (256, 192)), (300, (512, 384))] {
+      let object = runtime
+        .get_object_derivative("blob".to_string(), source_key.clone(), max_edge)
+        .await
+        .unwrap()
+        .unwrap();
+      assert_eq!(object.metadata.content_type, DERIVATIVE_MIME);
+      assert_eq!(image_dimensions(&object.body).unwrap(), expected);
+    }
+    let original = runtime
+      .get_object_derivative("blob".to_string(), source_key.clone(), 700)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(original.metadata.content_type, "image/png");
+    assert_eq!(original.body.to_vec(), source);
+
+    sqlx::query(
+      r#"
+      INSERT INTO blobs (workspace_id, key, size, mime, status, deleted_at)
+      VALUES ($1, 'image', $2, 'image/png', 'completed', CURRENT_TIMESTAMP)
+      "#,
+    )
+    .bind(TEST_WORKSPACE)
+    .bind(source.len() as i32)
+    .execute(&pool)
+    .await
+    .unwrap();
+    let released = runtime
+      .release_deleted_blobs(TEST_WORKSPACE.to_string(), 10)
+      .await
+      .unwrap();
+    assert_eq!(released.deleted, 1);
+    assert!(
+      load_derivative_edges(&pool, TEST_WORKSPACE, "image")
+        .await
+        .unwrap()
+        .is_empty()
+    );
+    for edge in [256, 512] {
+      assert!(
+        runtime
+          .object_storage_head(derivative_key(TEST_WORKSPACE, "image", edge))
+          .await
+          .unwrap()
+          .is_none()
+      );
+    }
+    assert!(
+      runtime
+        .get_object_derivative("blob".to_string(), source_key, 300)
+        .await
+        .unwrap()
+        .is_none()
+    );
+
+    sqlx::query("DELETE FROM workspaces WHERE id = $1")
+      .bind(TEST_WORKSPACE)
+      .execute(&pool)
+      .await
+      .unwrap();
+  }
+}
@@ -0,0 +1,794 @@
+use std::collections::HashMap;
+
+use aes_gcm::{
+  Aes256Gcm, KeyInit,
+  aead::{Aead, Payload, generic_array::GenericArray},
+};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgPool};
+
+use super::{
+  RuntimeBlobReencryptionResult, RuntimeError, RuntimeResult, StorageRuntime, WorkspaceDataKeys,
+  byte_range::{ByteRange, ResolvedRange},
+  napi_error,
+  object_storage::types::{ObjectEncryption, ObjectGetResult, ObjectMetadata, ObjectPutMetadata},
+};
+
+/// Sealed blobs are a header followed by fixed-size plaintext chunks, each
+/// encrypted on its own so a byte range only needs the chunks it overlaps.
+const SEALED_MAGIC: &[u8] = b"AFBLOB";
+const SEALED_FORMAT: u8 = 1;
+const NONCE_PREFIX_BYTES: usize = 8;
+pub(super) const SEALED_HEADER_BYTES: u64 = (SEALED_MAGIC.len() + 1 + 4 + NONCE_PREFIX_BYTES) as u64;
+const CHUNK_BYTES: u64 = 64 * 1024;
+const TAG_BYTES: u64 = 16;
+const FRAME_BYTES: u64 = CHUNK_BYTES + TAG_BYTES;
+const BLOB_KEY_CONTEXT: &[u8] = b"affine:blob-encryption:";
+
+/// Stored size of a blob with `plaintext` bytes. Empty blobs still carry one
+/// empty final chunk so truncation to the bare header is detected.
+pub(super) fn sealed_length(plaintext: u64) -> u64 {
+  SEALED_HEADER_BYTES + plaintext + TAG_BYTES * plaintext.div_ceil(CHUNK_BYTES).max(1)
+}
+
+/// Inverse of [`sealed_length`]; `None` when no plaintext size seals to
+/// exactly `stored` bytes.
+pub(super) fn plaintext_length(stored: u64) -> Option<u64> {
+  let body = stored.checked_sub(SEALED_HEADER_BYTES)?;
+  let chunks = body.div_ceil(FRAME_BYTES).max(1);
+  let plaintext = body.checked_sub(chunks * TAG_BYTES)?;
+  (sealed_length(plaintext) == stored).then_some(plaintext)
+}
+
+/// Metadata as callers see it: the plaintext size and no checksum, since the
+/// provider checksum covers the ciphertext.
+pub(super) fn plaintext_metadata(metadata: ObjectMetadata) -> RuntimeResult<ObjectMetadata> {
+  if metadata.encryption.is_none() {
+    return Ok(metadata);
+  }
+  let content_length = u64::try_from(metadata.content_length)
+    .ok()
+    .and_then(plaintext_length)
+    .ok_or_else(|| RuntimeError::invalid_state("BlobEncryption sealed blob has an invalid length"))?;
+  Ok(ObjectMetadata {
+    content_length: content_length as i64,
+    checksum_crc32: None,
+    ..metadata
+  })
+}
+
+/// `{workspace_id}/{key}` is the only layout of the `blob` scope.
+pub(super) fn blob_workspace(object_key: &str) -> RuntimeResult<&str> {
+  object_key
+    .split_once('/')
+    .map(|(workspace_id, _)| workspace_id)
+    .filter(|workspace_id| !workspace_id.is_empty())
+    .ok_or_else(|| RuntimeError::invalid_input(format!("BlobEncryption key {object_key} has no workspace prefix")))
+}
+
+fn blob_header(version: i32, nonce_prefix: &[u8; NONCE_PREFIX_BYTES]) -> Vec<u8> {
+  let mut header = Vec::with_capacity(SEALED_HEADER_BYTES as usize);
+  header.extend_from_slice(SEALED_MAGIC);
+  header.push(SEALED_FORMAT);
+  header.extend_from_slice(&(version as u32).to_be_bytes());
+  header.extend_from_slice(nonce_prefix);
+  header
+}
+
+fn chunk_nonce(nonce_prefix: &[u8], index: u64) -> RuntimeResult<[u8; 12]> {
+  let index = u32::try_from(index).map_err(|_| RuntimeError::invalid_input("BlobEncryption blob is too large"))?;
+  let mut nonce = [0u8; 12];
+  nonce[..NONCE_PREFIX_BYTES].copy_from_slice(nonce_prefix);
+  nonce[NONCE_PREFIX_BYTES..].copy_from_slice(&index.to_be_bytes());
+  Ok(nonce)
+}
+
+// The header binds the key version and nonce prefix, the object key stops a
+// chunk from being replayed into another blob, and the final flag stops a
+// blob from being truncated at a chunk boundary.
+fn chunk_aad(header: &[u8], object_key: &str, index: u64, last: bool) -> Vec<u8> {
+  let mut aad = Vec::with_capacity(header.len() + object_key.len() + 10);
+  aad.extend_from_slice(header);
+  aad.extend_from_slice(object_key.as_bytes());
+  aad.push(0);
+  aad.extend_from_slice(&index.to_be_bytes());
+  aad.push(u8::from(last));
+  aad
+}
+
+struct BlobKey {
+  id: String,
+  cipher: Aes256Gcm,
+}
+
+impl BlobKey {
+  // Blob keys are derived rather than reusing the doc data key directly, so a
+  // doc envelope and a blob chunk never share a key.
+  fn derive(data_key: &[u8]) -> RuntimeResult<Self> {
+    let mut hasher = Sha256::new();
+    hasher.update(BLOB_KEY_CONTEXT);
+    hasher.update(data_key);
+    let key = hasher.finalize();
+    Ok(Self {
+      id: hex::encode(&Sha256::digest(key)[..8]),
+      cipher: Aes256Gcm::new_from_slice(&key)
+        .map_err(|_| RuntimeError::invalid_state("BlobEncryption data key is invalid"))?,
+    })
+  }
+}
+
+/// Blob keys of one workspace, derived from its doc data keys.
+pub(super) struct BlobCipher {
+  workspace_id: String,
+  active: Option<i32>,
+  keys: HashMap<i32, BlobKey>,
+}
+
+impl BlobCipher {
+  pub(super) fn from_data_keys(workspace_id: &str, data_keys: WorkspaceDataKeys) -> RuntimeResult<Self> {
+    let mut keys = HashMap::with_capacity(data_keys.keys.len());
+    for (version, data_key) in data_keys.keys {
+      keys.insert(version, BlobKey::derive(&data_key)?);
+    }
+    Ok(Self {
+      workspace_id: workspace_id.to_string(),
+      active: data_keys.active,
+      keys,
+    })
+  }
+
+  pub(super) fn active_encryption(&self) -> Option<ObjectEncryption> {
+    let version = self.active?;
+    Some(ObjectEncryption {
+      key_id: self.keys.get(&version)?.id.clone(),
+      key_version: version,
+    })
+  }
+
+  pub(super) fn sealer(&self, object_key: &str) -> RuntimeResult<BlobSealer> {
+    let (version, key) = self
+      .active
+      .and_then(|version| Some((version, self.keys.get(&version)?)))
+      .ok_or_else(|| {
+        RuntimeError::invalid_state(format!(
+          "BlobEncryption active data key for workspace {} is unavailable",
+          self.workspace_id
+        ))
+      })?;
+    let mut nonce_prefix = [0u8; NONCE_PREFIX_BYTES];
+    rand::rng().fill_bytes(&mut nonce_prefix);
+    Ok(BlobSealer {
+      cipher: key.cipher.clone(),
+      encryption: ObjectEncryption {
+        key_id: key.id.clone(),
+        key_version: version,
+      },
+      object_key: object_key.to_string(),
+      header: blob_header(version, &nonce_prefix),
+      nonce_prefix,
+      header_written: false,
+      index: 0,
+      pending: Vec::new(),
+    })
+  }
+
+  /// Open `range` of a blob sealed with `encryption`, or the whole blob.
+  pub(super) fn reader(
+    &self,
+    object_key: &str,
+    encryption: &ObjectEncryption,
+    plaintext_length: u64,
+    range: Option<ResolvedRange>,
+  ) -> RuntimeResult<SealedReader> {
+    let key = self.keys.get(&encryption.key_version).ok_or_else(|| {
+      RuntimeError::config(format!(
+        "BlobEncryption data key v{} of workspace {} is unavailable; check crypto.docEncryption",
+        encryption.key_version, self.workspace_id
+      ))
+    })?;
+    if key.id != encryption.key_id {
+      return Err(RuntimeError::invalid_state(format!(
+        "BlobEncryption blob {object_key} was sealed with key {} but v{} is {}",
+        encryption.key_id, encryption.key_version, key.id
+      )));
+    }
+    let last_index = plaintext_length.div_ceil(CHUNK_BYTES).max(1) - 1;
+    let (first_index, end_index, skip, take) = match range {
+      Some(range) => (
+        range.start / CHUNK_BYTES,
+        range.end / CHUNK_BYTES,
+        range.start % CHUNK_BYTES,
+        range.length(),
+      ),
+      None => (0, last_index, 0, plaintext_length),
+    };
+    Ok(SealedReader {
+      cipher: key.cipher.clone(),
+      key_version: encryption.key_version,
+      object_key: object_key.to_string(),
+      header: None,
+      stored_length: sealed_length(plaintext_length),
+      last_index,
+      final_frame: (plaintext_length - last_index * CHUNK_BYTES + TAG_BYTES) as usize,
+      ranged: range.is_some(),
+      index: first_index,
+      end_index,
+      skip: skip as usize,
+      take,
+      buffer: Vec::new(),
+    })
+  }
+
+  pub(super) fn seal(&self, object_key: &str, plaintext: &[u8]) -> RuntimeResult<(Vec<u8>, ObjectEncryption)> {
+    let mut sealer = self.sealer(object_key)?;
+    let encryption = sealer.encryption().clone();
+    let mut sealed = sealer.update(plaintext)?;
+    sealed.extend(sealer.finish()?);
+    Ok((sealed, encryption))
+  }
+
+  pub(super) fn open(&self, object_key: &str, encryption: &ObjectEncryption, sealed: &[u8]) -> RuntimeResult<Vec<u8>> {
+    let plaintext_length = plaintext_length(sealed.len() as u64)
+      .ok_or_else(|| RuntimeError::invalid_state(format!("BlobEncryption blob {object_key} has an invalid length")))?;
+    let mut reader = self.reader(object_key, encryption, plaintext_length, None)?;
+    reader.push(sealed);
+    let mut plaintext = Vec::with_capacity(plaintext_length as usize);
+    while let Some(chunk) = reader.next_plaintext()? {
+      plaintext.extend_from_slice(&chunk);
+    }
+    reader.finish()?;
+    Ok(plaintext)
+  }
+}
+
+/// Seals a blob incrementally. The last chunk is held back until `finish` so
+/// it can be flagged as final.
+pub(super) struct BlobSealer {
+  cipher: Aes256Gcm,
+  encryption: ObjectEncryption,
+  object_key: String,
+  header: Vec<u8>,
+  nonce_prefix: [u8; NONCE_PREFIX_BYTES],
+  header_written: bool,
+  index: u64,
+  pending: Vec<u8>,
+}
+
+impl BlobSealer {
+  pub(super) fn encryption(&self) -> &ObjectEncryption {
+    &self.encryption
+  }
+
+  /// Seal what `chunk` completes; the output may be empty.
+  pub(super) fn update(&mut self, chunk: &[u8]) -> RuntimeResult<Vec<u8>> {
+    self.pending.extend_from_slice(chunk);
+    let mut sealed = self.take_header();
+    let chunk_bytes = CHUNK_BYTES as usize;
+    while self.pending.len() > chunk_bytes {
+      let plaintext = self.pending.drain(..chunk_bytes).collect::<Vec<_>>();
+      sealed.extend(self.seal_chunk(&plaintext, false)?);
+    }
+    Ok(sealed)
+  }
+
+  pub(super) fn finish(mut self) -> RuntimeResult<Vec<u8>> {
+    let mut sealed = self.take_header();
+    let plaintext = std::mem::take(&mut self.pending);
+    sealed.extend(self.seal_chunk(&plaintext, true)?);
+    Ok(sealed)
+  }
+
+  fn take_header(&mut self) -> Vec<u8> {
+    if self.header_written {
+      return Vec::new();
+    }
+    self.header_written = true;
+    self.header.clone()
+  }
+
+  fn seal_chunk(&mut self, plaintext: &[u8], last: bool) -> RuntimeResult<Vec<u8>> {
+    let nonce = chunk_nonce(&self.nonce_prefix, self.index)?;
+    let aad = chunk_aad(&self.header, &self.object_key, self.index, last);
+    self.index += 1;
+    self
+      .cipher
+      .encrypt(
+        GenericArray::from_slice(&nonce),
+        Payload {
+          msg: plaintext,
+          aad: &aad,
+        },
+      )
+      .map_err(|_| RuntimeError::invalid_state("BlobEncryption blob encryption failed"))
+  }
+}
+
+/// Decrypts a sealed blob, or one byte range of it, as stored bytes arrive.
+/// Feed it with [`SealedReader::push`] and drain it with
+/// [`SealedReader::next_plaintext`] until that returns `None`.
+pub(super) struct SealedReader {
+  cipher: Aes256Gcm,
+  key_version: i32,
+  object_key: String,
+  header: Option<Vec<u8>>,
+  stored_length: u64,
+  last_index: u64,
+  final_frame: usize,
+  ranged: bool,
+  index: u64,
+  end_index: u64,
+  skip: usize,
+  take: u64,
+  buffer: Vec<u8>,
+}
+
+impl SealedReader {
+  /// Stored bytes to fetch, or `None` for the whole blob. The header is part
+  /// of the range only when it starts at the first chunk.
+  pub(super) fn stored_range(&self) -> Option<ByteRange> {
+    if !self.ranged {
+      return None;
+    }
+    let start = match self.index {
+      0 => 0,
+      index => SEALED_HEADER_BYTES + index * FRAME_BYTES,
+    };
+    let end = (SEALED_HEADER_BYTES + (self.end_index + 1) * FRAME_BYTES).min(self.stored_length) - 1;
+    Some(ByteRange::Bounded { start, end })
+  }
+
+  /// Whether the header has to be fetched separately before
+  /// [`SealedReader::stored_range`] can be opened.
+  pub(super) fn needs_header(&self) -> bool {
+    self.header.is_none() && self.index > 0
+  }
+
+  pub(super) fn set_header(&mut self, header: &[u8]) -> RuntimeResult<()> {
+    let magic = SEALED_MAGIC.len();
+    if header.len() != SEALED_HEADER_BYTES as usize
+      || &header[..magic] != SEALED_MAGIC
+      || header[magic] != SEALED_FORMAT
+    {
+      return Err(self.corrupted());
+    }
+    let version = u32::from_be_bytes([
+      header[magic + 1],
+      header[magic + 2],
+      header[magic + 3],
+      header[magic + 4],
+    ]);
+    if version as i32 != self.key_version {
+      return Err(self.corrupted());
+    }
+    self.header = Some(header.to_vec());
+    Ok(())
+  }
+
+  pub(super) fn push(&mut self, stored: &[u8]) {
+    self.buffer.extend_from_slice(stored);
+  }
+
+  /// Next decrypted piece of the requested range, or `None` when more stored
+  /// bytes are needed or the range is complete.
+  pub(super) fn next_plaintext(&mut self) -> RuntimeResult<Option<Vec<u8>>> {
+    if self.header.is_none() {
+      if self.index > 0 {
+        return Err(RuntimeError::invalid_state("BlobEncryption reader header is missing"));
+      }
+      if self.buffer.len() < SEALED_HEADER_BYTES as usize {
+        return Ok(None);
+      }
+      let header = self.buffer.drain(..SEALED_HEADER_BYTES as usize).collect::<Vec<_>>();
+      self.set_header(&header)?;
+    }
+    while !self.done() {
+      let last = self.index == self.last_index;
+      let frame_length = if last { self.final_frame } else { FRAME_BYTES as usize };
+      if self.buffer.len() < frame_length {
+        return Ok(None);
+      }
+      let frame = self.buffer.drain(..frame_length).collect::<Vec<_>>();
+      let header = self.header.as_deref().unwrap_or_default();
+      let nonce = chunk_nonce(&header[header.len() - NONCE_PREFIX_BYTES..], self.index)?;
+      let aad = chunk_aad(header, &self.object_key, self.index, last);
+      let plaintext = self
+        .cipher
+        .decrypt(GenericArray::from_slice(&nonce), Payload { msg: &frame, aad: &aad })
+        .map_err(|_| self.corrupted())?;
+      self.index += 1;
+
+      let start = std::mem::take(&mut self.skip).min(plaintext.len());
+      let end = (start as u64 + self.take).min(plaintext.len() as u64) as usize;
+      self.take -= (end - start) as u64;
+      if end > start {
+        return Ok(Some(plaintext[start..end].to_vec()));
+      }
+    }
+    Ok(None)
+  }
+
+  pub(super) fn done(&self) -> bool {
+    self.index > self.end_index
+  }
+
+  /// Call once the stored bytes are exhausted.
+  pub(super) fn finish(&self) -> RuntimeResult<()> {
+    if !self.done() {
+      return Err(RuntimeError::invalid_state(format!(
+        "BlobEncryption blob {} is truncated",
+        self.object_key
+      )));
+    }
+    Ok(())
+  }
+
+  fn corrupted(&self) -> RuntimeError {
+    RuntimeError::invalid_state(format!("BlobEncryption failed to decrypt blob {}", self.object_key))
+  }
+}
+
+#[derive(FromRow)]
+struct ReencryptionCheckpoint {
+  status: String,
+  last_key: Option<String>,
+  cursor: serde_json::Value,
+}
+
+impl ReencryptionCheckpoint {
+  fn continuation_token(&self) -> Option<String> {
+    self
+      .cursor
+      .get("continuationToken")
+      .and_then(|value| value.as_str())
+      .map(ToString::to_string)
+  }
+}
+
+async fn load_checkpoint(pool: &PgPool, workspace_id: &str) -> RuntimeResult<Option<ReencryptionCheckpoint>> {
+  sqlx::query_as::<_, ReencryptionCheckpoint>(
+    r#"
+    SELECT status, last_key, cursor
+    FROM blob_reconciliation_checkpoints
+    WHERE kind = 'blob_reencryption' AND scope = $1
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_optional(pool)
+  .await
+  .map_err(|err| RuntimeError::database("Blob reencryption checkpoint load failed", err))
+}
+
+async fn upsert_checkpoint(
+  pool: &PgPool,
+  workspace_id: &str,
+  last_key: Option<&str>,
+  continuation_token: Option<&str>,
+  completed: bool,
+  key_version: i32,
+) -> RuntimeResult<()> {
+  let status = if completed { "completed" } else { "running" };
+  sqlx::query(
+    r#"
+    INSERT INTO blob_reconciliation_checkpoints
+      (kind, scope, status, cursor, last_key, completed_at, metadata)
+    VALUES ('blob_reencryption', $1, $2, $3, $4, CASE WHEN $5 THEN CURRENT_TIMESTAMP ELSE NULL END, $6)
+    ON CONFLICT (kind, scope) DO UPDATE
+      SET status = EXCLUDED.status,
+          cursor = EXCLUDED.cursor,
+          last_key = EXCLUDED.last_key,
+          completed_at = CASE WHEN $5 THEN CURRENT_TIMESTAMP ELSE NULL END,
+          updated_at = CURRENT_TIMESTAMP,
+          metadata = EXCLUDED.metadata
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(status)
+  .bind(serde_json::json!({
+    "lastKey": last_key,
+    "continuationToken": continuation_token,
+  }))
+  .bind(last_key)
+  .bind(completed)
+  .bind(serde_json::json!({ "keyVersion": key_version }))
+  .execute(pool)
+  .await
+  .map_err(|err| RuntimeError::database("Blob reencryption checkpoint write failed", err))?;
+  Ok(())
+}
+
+impl StorageRuntime {
+  /// Whether new objects in `scope` are sealed. Only the `blob` scope is keyed
+  /// by workspace, so other scopes always stay plaintext.
+  pub(super) fn seals_scope(&self, scope: &str) -> RuntimeResult<bool> {
+    Ok(scope == "blob" && self.config()?.blob_encryption)
+  }
+
+  pub(super) async fn blob_cipher(&self, workspace_id: &str, for_write: bool) -> RuntimeResult<BlobCipher> {
+    let master_keys = self.doc_master_keys()?;
+    if for_write && !master_keys.enabled() {
+      return Err(RuntimeError::config(
+        "crypto.blobEncryption requires crypto.docEncryption.masterKey",
+      ));
+    }
+    let pool = self.pool().await?;
+    let mut conn = pool
+      .acquire()
+      .await
+      .map_err(|err| RuntimeError::database("BlobEncryption acquire connection failed", err))?;
+    let data_keys = if for_write {
+      WorkspaceDataKeys::for_write(&mut conn, &master_keys, workspace_id).await?
+    } else {
+      WorkspaceDataKeys::for_read(&mut conn, &master_keys, workspace_id).await?
+    };
+    BlobCipher::from_data_keys(workspace_id, data_keys)
+  }
+
+  /// Seal a put to `scope` when blob encryption is enabled. The requested
+  /// length and checksum are checked against the plaintext here because the
+  /// provider only sees ciphertext.
+  pub(super) async fn seal_put(
+    &self,
+    scope: &str,
+    key: &str,
+    body: Vec<u8>,
+    metadata: ObjectPutMetadata,
+  ) -> RuntimeResult<(Vec<u8>, ObjectPutMetadata)> {
+    if !self.seals_scope(scope)? {
+      return Ok((body, metadata));
+    }
+    let metadata = metadata.complete_for_body(&body);
+    if metadata.content_length != Some(body.len() as i64) {
+      return Err(RuntimeError::invalid_input("StorageRuntime content length mismatch"));
+    }
+    if metadata.checksum_crc32.as_deref() != Some(format!("{:x}", crc32fast::hash(&body)).as_str()) {
+      return Err(RuntimeError::invalid_input("StorageRuntime checksum mismatch"));
+    }
+    let cipher = self.blob_cipher(blob_workspace(key)?, true).await?;
+    let (sealed, encryption) = cipher.seal(key, &body)?;
+    Ok((
+      sealed,
+      ObjectPutMetadata {
+        content_type: metadata.content_type,
+        encryption: Some(encryption),
+        ..Default::default()
+      },
+    ))
+  }
+
+  /// Decrypt a fetched object if it was stored sealed. Sealed objects are
+  /// always opened, even after blob encryption is switched off.
+  pub(super) async fn open_object(&self, key: &str, object: ObjectGetResult) -> RuntimeResult<ObjectGetResult> {
+    let Some(encryption) = object.metadata.encryption.clone() else {
+      return Ok(object);
+    };
+    let cipher = self.blob_cipher(blob_workspace(key)?, false).await?;
+    Ok(ObjectGetResult {
+      body: cipher.open(key, &encryption, &object.body)?,
+      metadata: plaintext_metadata(object.metadata)?,
+    })
+  }
+
+  /// Re-seal one stored blob with the active key. Returns false when it was
+  /// already current or disappeared since it was listed.
+  async fn reencrypt_blob(&self, cipher: &BlobCipher, key: &str) -> RuntimeResult<bool> {
+    let Some(metadata) = self.stored_object_head(key).await? else {
+      return Ok(false);
+    };
+    if metadata.encryption.is_some() && metadata.encryption == cipher.active_encryption() {
+      return Ok(false);
+    }
+    let Some(object) = self.stored_object_get(key).await? else {
+      return Ok(false);
+    };
+    let body = match &object.metadata.encryption {
+      Some(encryption) => cipher.open(key, encryption, &object.body)?,
+      None => object.body,
+    };
+    // Blob keys are content hashes, so a concurrent upload to the same key
+    // carries the same bytes and overwriting it is harmless.
+    let (sealed, encryption) = cipher.seal(key, &body)?;
+    self
+      .stored_object_put(
+        key,
+        sealed,
+        ObjectPutMetadata {
+          content_type: Some(object.metadata.content_type),
+          encryption: Some(encryption),
+          ..Default::default()
+        },
+      )
+      .await?;
+    Ok(true)
+  }
+}
+
+#[napi_derive::napi]
+impl StorageRuntime {
+  /// Seal plaintext blobs of a workspace and re-seal blobs sealed with
+  /// retired data keys. Progress is checkpointed; call repeatedly until
+  /// `completed` is true. A call after completion starts a new pass.
+  #[napi]
+  pub async fn reencrypt_workspace_blobs(
+    &self,
+    workspace_id: String,
+    batch_limit: i64,
+  ) -> napi::Result<RuntimeBlobReencryptionResult> {
+    if batch_limit <= 0 {
+      return Err(napi_error("blob reencryption batch limit must be positive"));
+    }
+    let page_limit =
+      i32::try_from(batch_limit).map_err(|_| napi_error("blob reencryption batch limit exceeds i32::MAX"))?;
+    if !self.seals_scope("blob")? {
+      return Err(RuntimeError::config("crypto.blobEncryption is not enabled").into());
+    }
+
+    let cipher = self.blob_cipher(&workspace_id, true).await?;
+    let key_version = cipher.active.unwrap_or_default();
+    let pool = self.pool().await?;
+    let checkpoint = load_checkpoint(&pool, &workspace_id)
+      .await?
+      .filter(|checkpoint| checkpoint.status != "completed");
+    let page = self
+      .object_storage_list_page(
+        Some(format!("{workspace_id}/")),
+        checkpoint.as_ref().and_then(ReencryptionCheckpoint::continuation_token),
+        checkpoint.as_ref().and_then(|checkpoint| checkpoint.last_key.clone()),
+        page_limit,
+      )
+      .await?;
+    // fs and assetpack pages carry no continuation token; a full page may
+    // still have more keys after it.
+    let completed = page.next_continuation_token.is_none() && page.entries.len() < page_limit as usize;
+
+    let mut result = RuntimeBlobReencryptionResult {
+      workspace_id: workspace_id.clone(),
+      scanned: 0,
+      reencrypted: 0,
+      skipped: 0,
+      failed: 0,
+      completed,
+    };
+    for entry in &page.entries {
+      result.scanned += 1;
+      match self.reencrypt_blob(&cipher, &entry.key).await {
+        Ok(true) => result.reencrypted += 1,
+        Ok(false) => result.skipped += 1,
+        // Failed blobs are retried by the next pass.
+        Err(_) => result.failed += 1,
+      }
+    }
+    upsert_checkpoint(
+      &pool,
+      &workspace_id,
+      page.entries.last().map(|entry| entry.key.as_str()),
+      page.next_continuation_token.as_deref(),
+      completed,
+      key_version,
+    )
+    .await?;
+    Ok(result)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn workspace_keys(rows: &[(i32, bool, u8)]) -> BlobCipher {
+    BlobCipher::from_data_keys(
+      "workspace",
+      WorkspaceDataKeys {
+        active: rows
+          .iter()
+          .find(|(_, active, _)| *active)
+          .map(|(version, _, _)| *version),
+        keys: rows
+          .iter()
+          .map(|(version, _, byte)| (*version, vec![*byte; 32]))
+          .collect(),
+      },
+    )
+    .unwrap()
+  }
+
+  fn body(length: usize) -> Vec<u8> {
+    (0..length).map(|i| (i * 7) as u8).collect()
+  }
+
+  #[test]
+  fn sealed_lengths_round_trip() {
+    let chunk = CHUNK_BYTES as usize;
+    for length in [0, 1, chunk - 1, chunk, chunk + 1, chunk * 3, chunk * 3 + 17] {
+      let cipher = workspace_keys(&[(1, true, 1)]);
+      let (sealed, _) = cipher.seal("workspace/blob", &body(length)).unwrap();
+      assert_eq!(sealed.len() as u64, sealed_length(length as u64));
+      assert_eq!(plaintext_length(sealed.len() as u64), Some(length as u64));
+    }
+    assert_eq!(plaintext_length(SEALED_HEADER_BYTES), None);
+    assert_eq!(plaintext_length(SEALED_HEADER_BYTES + FRAME_BYTES + 5), None);
+  }
+
+  #[test]
+  fn sealed_blobs_round_trip_and_are_bound_to_the_key() {
+    let cipher = workspace_keys(&[(1, true, 1)]);
+    let plaintext = body(CHUNK_BYTES as usize * 2 + 100);
+    let (sealed, encryption) = cipher.seal("workspace/blob", &plaintext).unwrap();
+    assert_eq!(encryption.key_version, 1);
+    assert_eq!(Some(encryption.clone()), cipher.active_encryption());
+    assert_eq!(cipher.open("workspace/blob", &encryption, &sealed).unwrap(), plaintext);
+    assert!(cipher.open("workspace/other", &encryption, &sealed).is_err());
+
+    let (empty, encryption) = cipher.seal("workspace/empty", b"").unwrap();
+    assert!(cipher.open("workspace/empty", &encryption, &empty).unwrap().is_empty());
+  }
+
+  #[test]
+  fn sealer_output_does_not_depend_on_write_boundaries() {
+    let cipher = workspace_keys(&[(1, true, 1)]);
+    let plaintext = body(CHUNK_BYTES as usize * 2 + 3);
+    let mut sealer = cipher.sealer("workspace/blob").unwrap();
+    let encryption = sealer.encryption().clone();
+    let mut sealed = Vec::new();
+    for chunk in plaintext.chunks(1000) {
+      sealed.extend(sealer.update(chunk).unwrap());
+    }
+    sealed.extend(sealer.finish().unwrap());
+    assert_eq!(cipher.open("workspace/blob", &encryption, &sealed).unwrap(), plaintext);
+  }
+
+  #[test]
+  fn ranges_decrypt_only_the_overlapping_chunks() {
+    let cipher = workspace_keys(&[(1, true, 1)]);
+    let plaintext = body(CHUNK_BYTES as usize * 3 + 500);
+    let (sealed, encryption) = cipher.seal("workspace/blob", &plaintext).unwrap();
+    let total = plaintext.len() as u64;
+
+    for header in ["bytes=0-9", "bytes=65530-65546", "bytes=-600", "bytes=131072-"] {
+      let range = ByteRange::parse(header).unwrap().resolve(total).unwrap();
+      let mut reader = cipher
+        .reader("workspace/blob", &encryption, total, Some(range))
+        .unwrap();
+      let Some(ByteRange::Bounded { start, end }) = reader.stored_range() else {
+        panic!("ranged reads fetch a bounded span");
+      };
+      if reader.needs_header() {
+        reader.set_header(&sealed[..SEALED_HEADER_BYTES as usize]).unwrap();
+      }
+      reader.push(&sealed[start as usize..=end as usize]);
+      let mut read = Vec::new();
+      while let Some(chunk) = reader.next_plaintext().unwrap() {
+        read.extend(chunk);
+      }
+      reader.finish().unwrap();
+      assert_eq!(read, range.slice(&plaintext), "{header}");
+    }
+  }
+
+  #[test]
+  fn tampered_truncated_and_unknown_key_blobs_are_rejected() {
+    let cipher = workspace_keys(&[(1, true, 1)]);
+    let plaintext = body(CHUNK_BYTES as usize * 2);
+    let (sealed, encryption) = cipher.seal("workspace/blob", &plaintext).unwrap();
+
+    let mut tampered = sealed.clone();
+    tampered[SEALED_HEADER_BYTES as usize + 10] ^= 1;
+    assert!(cipher.open("workspace/blob", &encryption, &tampered).is_err());
+
+    let truncated = &sealed[..(SEALED_HEADER_BYTES + FRAME_BYTES) as usize];
+    assert!(cipher.open("workspace/blob", &encryption, truncated).is_err());
+
+    let rotated = workspace_keys(&[(1, false, 9), (2, true, 2)]);
+    assert!(rotated.open("workspace/blob", &encryption, &sealed).is_err());
+    let retired = workspace_keys(&[(1, false, 1), (2, true, 2)]);
+    assert_eq!(retired.open("workspace/blob", &encryption, &sealed).unwrap(), plaintext);
+    assert_ne!(retired.active_encryption(), Some(encryption));
+  }
+
+  #[test]
+  fn only_workspace_keys_are_sealable() {
+    assert_eq!(blob_workspace("workspace/blob").unwrap(), "workspace");
+    assert!(blob_workspace("blob").is_err());
+    assert!(blob_workspace("/blob").is_err());
+  }
+}
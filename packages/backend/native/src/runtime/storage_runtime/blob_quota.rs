@@ -0,0 +1,593 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{FromRow, PgPool, Postgres, Transaction};
+
+use super::{
+  MAX_BLOB_SIZE, RuntimeBlobQuota, RuntimeBlobQuotaReservation, RuntimeError, RuntimeObjectMetadata, RuntimeResult,
+  RuntimeStorageUsageReconcileResult, RuntimeWorkspaceStorageUsage, StorageRuntime, metrics, napi_error,
+};
+
+// Reservations that are neither committed nor released within this window
+// are released by `cleanup_expired_pending_blobs`.
+const RESERVATION_TTL_HOURS: i64 = 24;
+
+/// Storage counted for a workspace. `used_bytes` covers completed blobs and
+/// `reserved_bytes` uploads that were granted space but not completed yet.
+#[derive(Clone, Copy, Debug, Default, FromRow, PartialEq)]
+struct UsageRow {
+  used_bytes: i64,
+  reserved_bytes: i64,
+}
+
+#[derive(FromRow)]
+pub(super) struct ReservationRow {
+  pub(super) workspace_id: String,
+  pub(super) blob_key: String,
+  pub(super) upload_id: Option<String>,
+}
+
+#[derive(FromRow)]
+struct UsageSummaryRow {
+  used_bytes: i64,
+  reserved_bytes: i64,
+  reconciled_at: Option<DateTime<Utc>>,
+}
+
+/// Why a blob of `size` bytes does not fit `quota`. `reserved` and `counted`
+/// are the bytes already held for the same key by a reservation and by a
+/// completed blob; both are replaced by the new blob.
+fn quota_violation(
+  quota: &RuntimeBlobQuota,
+  usage: UsageRow,
+  size: i64,
+  reserved: i64,
+  counted: i64,
+) -> Option<&'static str> {
+  // Keys are content hashes, so a completed blob under the key already
+  // holds this content.
+  if counted >= size {
+    return None;
+  }
+  if size > quota.blob_limit {
+    return Some("blob_limit_exceeded");
+  }
+  if usage.used_bytes + usage.reserved_bytes - reserved - counted + size > quota.storage_quota {
+    return Some("storage_quota_exceeded");
+  }
+  None
+}
+
+fn reservation_result(reason: Option<&str>, usage: UsageRow, quota: &RuntimeBlobQuota) -> RuntimeBlobQuotaReservation {
+  RuntimeBlobQuotaReservation {
+    ok: reason.is_none(),
+    reason: reason.map(str::to_string),
+    used_bytes: usage.used_bytes,
+    reserved_bytes: usage.reserved_bytes,
+    storage_quota: quota.storage_quota,
+  }
+}
+
+async fn begin(pool: &PgPool) -> RuntimeResult<Transaction<'static, Postgres>> {
+  pool
+    .begin()
+    .await
+    .map_err(|err| RuntimeError::database("Blob quota transaction failed", err))
+}
+
+async fn commit(tx: Transaction<'static, Postgres>) -> RuntimeResult<()> {
+  tx.commit()
+    .await
+    .map_err(|err| RuntimeError::database("Blob quota transaction commit failed", err))
+}
+
+/// Lock the workspace's usage row for the rest of the transaction, creating
+/// it on first use. Every change to usage or reservations goes through this
+/// lock, which is what makes concurrent reservations safe.
+async fn lock_usage(tx: &mut Transaction<'static, Postgres>, workspace_id: &str) -> RuntimeResult<UsageRow> {
+  sqlx::query_as::<_, UsageRow>(
+    r#"
+    INSERT INTO workspace_storage_usage (workspace_id)
+    VALUES ($1)
+    ON CONFLICT (workspace_id) DO UPDATE SET workspace_id = EXCLUDED.workspace_id
+    RETURNING used_bytes, reserved_bytes
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_one(&mut **tx)
+  .await
+  .map_err(|err| RuntimeError::database("Blob quota lock usage failed", err))
+}
+
+async fn adjust_usage(
+  tx: &mut Transaction<'static, Postgres>,
+  workspace_id: &str,
+  used_delta: i64,
+  reserved_delta: i64,
+) -> RuntimeResult<UsageRow> {
+  sqlx::query_as::<_, UsageRow>(
+    r#"
+    UPDATE workspace_storage_usage
+    SET used_bytes = GREATEST(used_bytes + $2, 0),
+        reserved_bytes = GREATEST(reserved_bytes + $3, 0),
+        updated_at = CURRENT_TIMESTAMP
+    WHERE workspace_id = $1
+    RETURNING used_bytes, reserved_bytes
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(used_delta)
+  .bind(reserved_delta)
+  .fetch_one(&mut **tx)
+  .await
+  .map_err(|err| RuntimeError::database("Blob quota update usage failed", err))
+}
+
+async fn reserved_size(tx: &mut Transaction<'static, Postgres>, workspace_id: &str, key: &str) -> RuntimeResult<i64> {
+  sqlx::query_scalar::<_, i64>("SELECT size FROM blob_quota_reservations WHERE workspace_id = $1 AND blob_key = $2")
+    .bind(workspace_id)
+    .bind(key)
+    .fetch_optional(&mut **tx)
+    .await
+    .map(Option::unwrap_or_default)
+    .map_err(|err| RuntimeError::database("Blob quota load reservation failed", err))
+}
+
+async fn take_reservation(
+  tx: &mut Transaction<'static, Postgres>,
+  workspace_id: &str,
+  key: &str,
+) -> RuntimeResult<i64> {
+  sqlx::query_scalar::<_, i64>(
+    "DELETE FROM blob_quota_reservations WHERE workspace_id = $1 AND blob_key = $2 RETURNING size",
+  )
+  .bind(workspace_id)
+  .bind(key)
+  .fetch_optional(&mut **tx)
+  .await
+  .map(Option::unwrap_or_default)
+  .map_err(|err| RuntimeError::database("Blob quota delete reservation failed", err))
+}
+
+async fn counted_size(tx: &mut Transaction<'static, Postgres>, workspace_id: &str, key: &str) -> RuntimeResult<i64> {
+  sqlx::query_scalar::<_, i64>(
+    r#"
+    SELECT size::BIGINT
+    FROM blobs
+    WHERE workspace_id = $1 AND key = $2 AND status = 'completed' AND deleted_at IS NULL
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(key)
+  .fetch_optional(&mut **tx)
+  .await
+  .map(Option::unwrap_or_default)
+  .map_err(|err| RuntimeError::database("Blob quota load blob size failed", err))
+}
+
+/// Reserve space for an upload of `size` bytes, replacing an earlier
+/// reservation for the same key.
+pub(super) async fn reserve_blob_quota(
+  pool: &PgPool,
+  workspace_id: &str,
+  key: &str,
+  size: i64,
+  quota: &RuntimeBlobQuota,
+  upload_id: Option<&str>,
+) -> RuntimeResult<RuntimeBlobQuotaReservation> {
+  if !(0..=MAX_BLOB_SIZE).contains(&size) {
+    return Err(RuntimeError::invalid_input(
+      "blob quota reservation size is out of range",
+    ));
+  }
+
+  let mut tx = begin(pool).await?;
+  let usage = lock_usage(&mut tx, workspace_id).await?;
+  let reserved = reserved_size(&mut tx, workspace_id, key).await?;
+  let counted = counted_size(&mut tx, workspace_id, key).await?;
+  if let Some(reason) = quota_violation(quota, usage, size, reserved, counted) {
+    metrics::record_blob_quota_rejection(reason);
+    return Ok(reservation_result(Some(reason), usage, quota));
+  }
+
+  let reserve = (size - counted).max(0);
+  sqlx::query(
+    r#"
+    INSERT INTO blob_quota_reservations (workspace_id, blob_key, size, upload_id, expires_at)
+    VALUES ($1, $2, $3, $4, $5)
+    ON CONFLICT (workspace_id, blob_key) DO UPDATE
+      SET size = EXCLUDED.size,
+          upload_id = COALESCE(EXCLUDED.upload_id, blob_quota_reservations.upload_id),
+          expires_at = EXCLUDED.expires_at
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(key)
+  .bind(reserve)
+  .bind(upload_id)
+  .bind(Utc::now() + Duration::hours(RESERVATION_TTL_HOURS))
+  .execute(&mut *tx)
+  .await
+  .map_err(|err| RuntimeError::database("Blob quota insert reservation failed", err))?;
+  let usage = adjust_usage(&mut tx, workspace_id, 0, reserve - reserved).await?;
+  commit(tx).await?;
+  Ok(reservation_result(None, usage, quota))
+}
+
+/// Drop the reservation for a key. Returns false when there was none.
+pub(super) async fn release_blob_quota(pool: &PgPool, workspace_id: &str, key: &str) -> RuntimeResult<bool> {
+  let mut tx = begin(pool).await?;
+  lock_usage(&mut tx, workspace_id).await?;
+  let exists = sqlx::query_scalar::<_, bool>(
+    "SELECT EXISTS(SELECT 1 FROM blob_quota_reservations WHERE workspace_id = $1 AND blob_key = $2)",
+  )
+  .bind(workspace_id)
+  .bind(key)
+  .fetch_one(&mut *tx)
+  .await
+  .map_err(|err| RuntimeError::database("Blob quota load reservation failed", err))?;
+  if !exists {
+    return Ok(false);
+  }
+  let reserved = take_reservation(&mut tx, workspace_id, key).await?;
+  adjust_usage(&mut tx, workspace_id, 0, -reserved).await?;
+  commit(tx).await?;
+  Ok(true)
+}
+
+/// Record a completed blob in `blobs` and move its reservation, if any, into
+/// the workspace's used bytes. With a `quota`, a blob that does not fit is
+/// not recorded and the reason is returned instead.
+pub(super) async fn commit_completed_blob(
+  pool: &PgPool,
+  workspace_id: &str,
+  key: &str,
+  mime: &str,
+  size: i64,
+  quota: Option<&RuntimeBlobQuota>,
+) -> RuntimeResult<Option<&'static str>> {
+  if !(0..=MAX_BLOB_SIZE).contains(&size) {
+    return Err(RuntimeError::invalid_input("BlobComplete size exceeds limit"));
+  }
+  let row_size = i32::try_from(size).map_err(|_| RuntimeError::invalid_input("BlobComplete size exceeds limit"))?;
+
+  let mut tx = begin(pool).await?;
+  let usage = lock_usage(&mut tx, workspace_id).await?;
+  let reserved = reserved_size(&mut tx, workspace_id, key).await?;
+  let counted = counted_size(&mut tx, workspace_id, key).await?;
+  if let Some(reason) = quota.and_then(|quota| quota_violation(quota, usage, size, reserved, counted)) {
+    metrics::record_blob_quota_rejection(reason);
+    return Ok(Some(reason));
+  }
+
+  sqlx::query(
+    r#"
+    INSERT INTO blobs (workspace_id, key, mime, size, status, upload_id)
+    VALUES ($1, $2, $3, $4, 'completed', NULL)
+    ON CONFLICT (workspace_id, key)
+    DO UPDATE SET
+      mime = EXCLUDED.mime,
+      size = EXCLUDED.size,
+      status = EXCLUDED.status,
+      upload_id = NULL
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(key)
+  .bind(mime)
+  .bind(row_size)
+  .execute(&mut *tx)
+  .await
+  .map_err(|err| RuntimeError::database("BlobComplete upsert metadata failed", err))?;
+  take_reservation(&mut tx, workspace_id, key).await?;
+  adjust_usage(&mut tx, workspace_id, size - counted, -reserved).await?;
+  commit(tx).await?;
+  Ok(None)
+}
+
+/// Subtract blobs removed from `blobs` outside `commit_completed_blob`.
+pub(super) async fn release_blob_usage(pool: &PgPool, workspace_id: &str, bytes: i64) -> RuntimeResult<()> {
+  if bytes <= 0 {
+    return Ok(());
+  }
+  sqlx::query(
+    r#"
+    UPDATE workspace_storage_usage
+    SET used_bytes = GREATEST(used_bytes - $2, 0),
+        updated_at = CURRENT_TIMESTAMP
+    WHERE workspace_id = $1
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(bytes)
+  .execute(pool)
+  .await
+  .map_err(|err| RuntimeError::database("Blob quota release usage failed", err))?;
+  Ok(())
+}
+
+pub(super) async fn load_expired_reservations(pool: &PgPool, limit: i64) -> RuntimeResult<Vec<ReservationRow>> {
+  sqlx::query_as::<_, ReservationRow>(
+    r#"
+    SELECT workspace_id, blob_key, upload_id
+    FROM blob_quota_reservations
+    WHERE expires_at <= CURRENT_TIMESTAMP
+    ORDER BY expires_at ASC
+    LIMIT $1
+    "#,
+  )
+  .bind(limit)
+  .fetch_all(pool)
+  .await
+  .map_err(|err| RuntimeError::database("Blob quota load expired reservations failed", err))
+}
+
+/// Workspaces whose usage was reconciled longest ago, never reconciled first.
+async fn load_reconcile_batch(pool: &PgPool, limit: i64) -> RuntimeResult<Vec<String>> {
+  sqlx::query_scalar::<_, String>(
+    r#"
+    SELECT w.id
+    FROM workspaces w
+    LEFT JOIN workspace_storage_usage u ON u.workspace_id = w.id
+    ORDER BY u.reconciled_at ASC NULLS FIRST, w.id ASC
+    LIMIT $1
+    "#,
+  )
+  .bind(limit)
+  .fetch_all(pool)
+  .await
+  .map_err(|err| RuntimeError::database("Blob quota load reconcile batch failed", err))
+}
+
+/// Recount a workspace's usage from `blobs` and its reservations. Returns
+/// whether the stored counters had drifted.
+async fn reconcile_workspace(pool: &PgPool, workspace_id: &str) -> RuntimeResult<bool> {
+  let mut tx = begin(pool).await?;
+  let stored = lock_usage(&mut tx, workspace_id).await?;
+  let actual = sqlx::query_as::<_, UsageRow>(
+    r#"
+    SELECT
+      (SELECT COALESCE(SUM(size), 0)::BIGINT
+       FROM blobs
+       WHERE workspace_id = $1 AND status = 'completed' AND deleted_at IS NULL) AS used_bytes,
+      (SELECT COALESCE(SUM(size), 0)::BIGINT
+       FROM blob_quota_reservations
+       WHERE workspace_id = $1) AS reserved_bytes
+    "#,
+  )
+  .bind(workspace_id)
+  .fetch_one(&mut *tx)
+  .await
+  .map_err(|err| RuntimeError::database("Blob quota count usage failed", err))?;
+  sqlx::query(
+    r#"
+    UPDATE workspace_storage_usage
+    SET used_bytes = $2,
+        reserved_bytes = $3,
+        updated_at = CURRENT_TIMESTAMP,
+        reconciled_at = CURRENT_TIMESTAMP
+    WHERE workspace_id = $1
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(actual.used_bytes)
+  .bind(actual.reserved_bytes)
+  .execute(&mut *tx)
+  .await
+  .map_err(|err| RuntimeError::database("Blob quota reconcile usage failed", err))?;
+  commit(tx).await?;
+  Ok(stored != actual)
+}
+
+impl StorageRuntime {
+  /// Reserve quota for an object written to the blob scope. Only workspace
+  /// blobs (`workspaceId/blobKey`) are counted against a quota.
+  pub(super) async fn reserve_object_quota(
+    &self,
+    scope: &str,
+    key: &str,
+    size: Option<i64>,
+    quota: &RuntimeBlobQuota,
+    upload_id: Option<&str>,
+  ) -> RuntimeResult<()> {
+    let (workspace_id, blob_key) = quota_object_key(scope, key)?;
+    let size = size.ok_or_else(|| RuntimeError::invalid_input("blob quota reservation requires a content length"))?;
+    let reservation = reserve_blob_quota(&self.pool().await?, workspace_id, blob_key, size, quota, upload_id).await?;
+    match reservation.reason {
+      Some(reason) => Err(RuntimeError::invalid_input(format!(
+        "blob quota reservation rejected: {reason}"
+      ))),
+      None => Ok(()),
+    }
+  }
+
+  /// Settle the reservation of a quota-checked write: the blob is recorded
+  /// as completed once stored, and the reservation released if the write
+  /// failed.
+  pub(super) async fn settle_object_quota(
+    &self,
+    key: &str,
+    stored: &RuntimeResult<RuntimeObjectMetadata>,
+  ) -> RuntimeResult<()> {
+    let (workspace_id, blob_key) = quota_object_key("blob", key)?;
+    let pool = self.pool().await?;
+    match stored {
+      Ok(metadata) => {
+        commit_completed_blob(
+          &pool,
+          workspace_id,
+          blob_key,
+          &metadata.content_type,
+          metadata.content_length,
+          None,
+        )
+        .await?;
+      }
+      Err(_) => {
+        release_blob_quota(&pool, workspace_id, blob_key).await?;
+      }
+    }
+    Ok(())
+  }
+
+  pub(super) async fn release_object_quota(&self, scope: &str, key: &str) -> RuntimeResult<()> {
+    if scope != "blob" {
+      return Ok(());
+    }
+    let Some((workspace_id, blob_key)) = key.split_once('/') else {
+      return Ok(());
+    };
+    release_blob_quota(&self.pool().await?, workspace_id, blob_key).await?;
+    Ok(())
+  }
+}
+
+fn quota_object_key<'a>(scope: &str, key: &'a str) -> RuntimeResult<(&'a str, &'a str)> {
+  if scope != "blob" {
+    return Err(RuntimeError::invalid_input(format!(
+      "blob quota does not apply to scope {scope}"
+    )));
+  }
+  key
+    .split_once('/')
+    .filter(|(workspace_id, blob_key)| !workspace_id.is_empty() && !blob_key.is_empty())
+    .ok_or_else(|| RuntimeError::invalid_input("blob quota object key must be workspaceId/blobKey"))
+}
+
+#[napi_derive::napi]
+impl StorageRuntime {
+  /// Reserve space for an upload the runtime does not see, such as a proxied
+  /// presigned upload. The reservation is committed by
+  /// `complete_workspace_blob_upload` and released by
+  /// `release_workspace_blob_quota` or on expiry.
+  #[napi]
+  pub async fn reserve_workspace_blob_quota(
+    &self,
+    workspace_id: String,
+    key: String,
+    size: i64,
+    quota: RuntimeBlobQuota,
+    upload_id: Option<String>,
+  ) -> napi::Result<RuntimeBlobQuotaReservation> {
+    Ok(
+      reserve_blob_quota(
+        &self.pool().await?,
+        &workspace_id,
+        &key,
+        size,
+        &quota,
+        upload_id.as_deref(),
+      )
+      .await?,
+    )
+  }
+
+  #[napi]
+  pub async fn release_workspace_blob_quota(&self, workspace_id: String, key: String) -> napi::Result<bool> {
+    Ok(release_blob_quota(&self.pool().await?, &workspace_id, &key).await?)
+  }
+
+  #[napi]
+  pub async fn workspace_storage_usage(&self, workspace_id: String) -> napi::Result<RuntimeWorkspaceStorageUsage> {
+    let row = sqlx::query_as::<_, UsageSummaryRow>(
+      "SELECT used_bytes, reserved_bytes, reconciled_at FROM workspace_storage_usage WHERE workspace_id = $1",
+    )
+    .bind(&workspace_id)
+    .fetch_optional(&self.read_pool().await?)
+    .await
+    .map_err(|err| RuntimeError::database("Blob quota load usage failed", err))?;
+    Ok(match row {
+      Some(row) => RuntimeWorkspaceStorageUsage {
+        workspace_id,
+        used_bytes: row.used_bytes,
+        reserved_bytes: row.reserved_bytes,
+        reconciled_at_ms: row.reconciled_at.map(|time| time.timestamp_millis()),
+      },
+      None => RuntimeWorkspaceStorageUsage {
+        workspace_id,
+        used_bytes: 0,
+        reserved_bytes: 0,
+        reconciled_at_ms: None,
+      },
+    })
+  }
+
+  /// Recount storage usage from `blobs` for one workspace, or for the
+  /// `limit` workspaces reconciled longest ago. Blob rows written outside
+  /// the runtime only reach the counters through this.
+  #[napi]
+  pub async fn reconcile_workspace_storage_usage(
+    &self,
+    workspace_id: Option<String>,
+    limit: i64,
+  ) -> napi::Result<RuntimeStorageUsageReconcileResult> {
+    if limit <= 0 {
+      return Err(napi_error("storage usage reconcile limit must be positive"));
+    }
+
+    let pool = self.pool().await?;
+    let workspace_ids = match workspace_id {
+      Some(workspace_id) => vec![workspace_id],
+      None => load_reconcile_batch(&pool, limit).await?,
+    };
+    let mut result = RuntimeStorageUsageReconcileResult {
+      scanned_workspaces: workspace_ids.len() as i64,
+      corrected_workspaces: 0,
+      workspace_ids: Vec::new(),
+    };
+    for workspace_id in workspace_ids {
+      if reconcile_workspace(&pool, &workspace_id).await? {
+        result.corrected_workspaces += 1;
+        result.workspace_ids.push(workspace_id);
+      }
+    }
+    metrics::record_storage_usage_corrections(result.corrected_workspaces);
+    Ok(result)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const QUOTA: RuntimeBlobQuota = RuntimeBlobQuota {
+    blob_limit: 10,
+    storage_quota: 100,
+  };
+
+  fn usage(used_bytes: i64, reserved_bytes: i64) -> UsageRow {
+    UsageRow {
+      used_bytes,
+      reserved_bytes,
+    }
+  }
+
+  #[test]
+  fn rejects_blobs_over_the_blob_limit_or_storage_quota() {
+    assert_eq!(quota_violation(&QUOTA, usage(0, 0), 10, 0, 0), None);
+    assert_eq!(
+      quota_violation(&QUOTA, usage(0, 0), 11, 0, 0),
+      Some("blob_limit_exceeded")
+    );
+    assert_eq!(quota_violation(&QUOTA, usage(80, 10), 10, 0, 0), None);
+    assert_eq!(
+      quota_violation(&QUOTA, usage(85, 10), 10, 0, 0),
+      Some("storage_quota_exceeded")
+    );
+  }
+
+  #[test]
+  fn bytes_held_for_the_same_key_are_replaced() {
+    // The blob's own reservation is already part of reserved_bytes.
+    assert_eq!(quota_violation(&QUOTA, usage(90, 10), 10, 10, 0), None);
+    // A completed blob under the key needs no further space, even when the
+    // workspace is over quota or the plan's blob limit shrank.
+    assert_eq!(quota_violation(&QUOTA, usage(200, 0), 20, 0, 20), None);
+  }
+
+  #[test]
+  fn quota_only_applies_to_workspace_blob_keys() {
+    assert_eq!(quota_object_key("blob", "workspace/key").unwrap(), ("workspace", "key"));
+    assert!(quota_object_key("avatar", "workspace/key").is_err());
+    assert!(quota_object_key("blob", "key").is_err());
+    assert!(quota_object_key("blob", "/key").is_err());
+  }
+}
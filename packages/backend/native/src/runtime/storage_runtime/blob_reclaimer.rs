@@ -1,8 +1,23 @@
+use std::{future::Future, pin::Pin};
+
 use chrono::{DateTime, Utc};
 use napi::Result;
+use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, PgPool};
 
-use super::{RuntimeBlobCleanupResult, RuntimeError, RuntimeResult, StorageRuntime, napi_error};
+use super::{
+  RuntimeBlobCleanupResult, RuntimeError, RuntimeMultipartSweepResult, RuntimeResult, StorageBackendConfig,
+  StorageRuntime,
+  blob_derivatives::delete_blob_derivatives,
+  blob_quota::{load_expired_reservations, release_blob_quota, release_blob_usage},
+  measured, metrics, napi_error,
+  object_storage::{
+    client::ObjectStorageClient,
+    types::{MultipartUploadEntry, MultipartUploadPage},
+  },
+};
+
+const MULTIPART_SWEEP_PAGE_SIZE: i64 = 1000;
 
 #[derive(FromRow)]
 struct BlobRow {
@@ -74,24 +89,45 @@ impl BlobReclaimerStore {
     Ok(result.rows_affected() as i64)
   }
 
-  async fn delete_released_metadata(&self, workspace_id: &str, key: &str) -> RuntimeResult<i64> {
+  /// Drop the pending row an abandoned multipart upload belongs to.
+  async fn delete_pending_upload(&self, workspace_id: &str, key: &str, upload_id: &str) -> RuntimeResult<i64> {
     let result = sqlx::query(
       r#"
       DELETE FROM blobs
       WHERE workspace_id = $1 AND key = $2
-        AND deleted_at IS NOT NULL
+        AND upload_id = $3
+        AND status = 'pending'
+        AND deleted_at IS NULL
       "#,
     )
     .bind(workspace_id)
     .bind(key)
+    .bind(upload_id)
     .execute(&self.pool)
     .await
-    .map_err(|err| RuntimeError::database("BlobReclaimer delete blob metadata failed", err))?;
+    .map_err(|err| RuntimeError::database("BlobReclaimer delete pending upload metadata failed", err))?;
     Ok(result.rows_affected() as i64)
   }
+
+  /// Returns the bytes each deleted row counted towards storage usage.
+  async fn delete_released_metadata(&self, workspace_id: &str, key: &str) -> RuntimeResult<Vec<i64>> {
+    sqlx::query_scalar::<_, i64>(
+      r#"
+      DELETE FROM blobs
+      WHERE workspace_id = $1 AND key = $2
+        AND deleted_at IS NOT NULL
+      RETURNING CASE WHEN status = 'completed' THEN size ELSE 0 END::BIGINT
+      "#,
+    )
+    .bind(workspace_id)
+    .bind(key)
+    .fetch_all(&self.pool)
+    .await
+    .map_err(|err| RuntimeError::database("BlobReclaimer delete blob metadata failed", err))
+  }
 }
 
-async fn delete_object_idempotent(runtime: &StorageRuntime, key: &str) -> RuntimeResult<()> {
+pub(super) async fn delete_object_idempotent(runtime: &StorageRuntime, key: &str) -> RuntimeResult<()> {
   match runtime.object_storage_delete_object(key).await {
     Ok(()) => Ok(()),
     Err(err) if err.is_object_missing() => Ok(()),
@@ -99,7 +135,7 @@ async fn delete_object_idempotent(runtime: &StorageRuntime, key: &str) -> Runtim
   }
 }
 
-async fn abort_upload_idempotent(runtime: &StorageRuntime, key: &str, upload_id: &str) -> RuntimeResult<()> {
+pub(super) async fn abort_upload_idempotent(runtime: &StorageRuntime, key: &str, upload_id: &str) -> RuntimeResult<()> {
   match runtime.object_storage_abort_upload(key, upload_id).await {
     Ok(()) => Ok(()),
     Err(err) if err.is_object_missing() => Ok(()),
@@ -107,6 +143,175 @@ async fn abort_upload_idempotent(runtime: &StorageRuntime, key: &str, upload_id:
   }
 }
 
+/// Workspace and blob key of a blob object key. Other keys in the bucket
+/// have no `blobs` row to match.
+fn blob_upload_owner(key: &str) -> Option<(&str, &str)> {
+  let (workspace_id, blob_key) = key.split_once('/')?;
+  (!workspace_id.is_empty() && !blob_key.is_empty() && !blob_key.contains('/')).then_some((workspace_id, blob_key))
+}
+
+/// Abort an abandoned upload and return the bytes its parts held. An upload
+/// that is already gone frees nothing.
+async fn abort_abandoned_upload(client: &ObjectStorageClient, upload: &MultipartUploadEntry) -> RuntimeResult<i64> {
+  let freed_bytes = client.multipart_upload_size(&upload.key, &upload.upload_id).await?;
+  match client.abort_multipart_upload(&upload.key, &upload.upload_id).await {
+    Ok(()) => Ok(freed_bytes),
+    Err(err) if err.is_not_found() => Ok(0),
+    Err(err) => Err(err.into()),
+  }
+}
+
+type SweepFuture<'a, T> = Pin<Box<dyn Future<Output = RuntimeResult<T>> + Send + 'a>>;
+
+/// Where a multipart sweep resumes. S3 lists uploads by key and then by
+/// upload id, so a key with many uploads can span pages.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MultipartSweepCursor {
+  key_marker: String,
+  upload_id_marker: Option<String>,
+}
+
+impl MultipartSweepCursor {
+  fn after(page: &MultipartUploadPage) -> Option<Self> {
+    Some(Self {
+      key_marker: page.next_key_marker.clone()?,
+      upload_id_marker: page.next_upload_id_marker.clone(),
+    })
+  }
+}
+
+/// The bucket side of a multipart sweep.
+trait MultipartUploadSource: Sync {
+  fn list_page(&self, cursor: Option<MultipartSweepCursor>, max_uploads: i32) -> SweepFuture<'_, MultipartUploadPage>;
+
+  /// Abort an upload and return the bytes its parts held.
+  fn abort<'a>(&'a self, upload: &'a MultipartUploadEntry) -> SweepFuture<'a, i64>;
+}
+
+/// The database side of a multipart sweep.
+trait PendingUploadStore: Sync {
+  /// Drop the pending row an aborted upload belongs to; returns the rows
+  /// deleted.
+  fn release_pending_upload<'a>(
+    &'a self,
+    workspace_id: &'a str,
+    key: &'a str,
+    upload_id: &'a str,
+  ) -> SweepFuture<'a, i64>;
+
+  fn release_quota<'a>(&'a self, workspace_id: &'a str, key: &'a str) -> SweepFuture<'a, bool>;
+}
+
+struct BucketUploads<'a> {
+  provider: &'a str,
+  client: &'a ObjectStorageClient,
+}
+
+impl MultipartUploadSource for BucketUploads<'_> {
+  fn list_page(&self, cursor: Option<MultipartSweepCursor>, max_uploads: i32) -> SweepFuture<'_, MultipartUploadPage> {
+    let (key_marker, upload_id_marker) = cursor.map_or((None, None), |cursor| {
+      (Some(cursor.key_marker), cursor.upload_id_marker)
+    });
+    Box::pin(measured(self.provider, "list_multipart_uploads", async move {
+      self
+        .client
+        .list_multipart_uploads(None, key_marker, upload_id_marker, max_uploads)
+        .await
+        .map_err(Into::into)
+    }))
+  }
+
+  fn abort<'a>(&'a self, upload: &'a MultipartUploadEntry) -> SweepFuture<'a, i64> {
+    Box::pin(abort_abandoned_upload(self.client, upload))
+  }
+}
+
+impl PendingUploadStore for BlobReclaimerStore {
+  fn release_pending_upload<'a>(
+    &'a self,
+    workspace_id: &'a str,
+    key: &'a str,
+    upload_id: &'a str,
+  ) -> SweepFuture<'a, i64> {
+    Box::pin(self.delete_pending_upload(workspace_id, key, upload_id))
+  }
+
+  fn release_quota<'a>(&'a self, workspace_id: &'a str, key: &'a str) -> SweepFuture<'a, bool> {
+    Box::pin(release_blob_quota(&self.pool, workspace_id, key))
+  }
+}
+
+fn empty_sweep_result() -> RuntimeMultipartSweepResult {
+  RuntimeMultipartSweepResult {
+    scanned_uploads: 0,
+    aborted_uploads: 0,
+    matched_pending_blobs: 0,
+    freed_part_bytes: 0,
+    skipped_recent: 0,
+    skipped_foreign: 0,
+    failed: 0,
+    next_cursor: None,
+    workspace_ids: Vec::new(),
+  }
+}
+
+/// Abort blob uploads started before `cutoff_ms`, scanning at most `limit`
+/// uploads from `cursor` on, and drop the pending rows and reservations of
+/// the blobs they belong to. Uploads of other keys are skipped.
+async fn sweep_uploads(
+  uploads: &impl MultipartUploadSource,
+  store: &impl PendingUploadStore,
+  cutoff_ms: i64,
+  limit: i64,
+  mut cursor: Option<MultipartSweepCursor>,
+) -> RuntimeResult<RuntimeMultipartSweepResult> {
+  let mut result = empty_sweep_result();
+  while result.scanned_uploads < limit {
+    let max_uploads = (limit - result.scanned_uploads).min(MULTIPART_SWEEP_PAGE_SIZE) as i32;
+    let page = uploads.list_page(cursor.clone(), max_uploads).await?;
+    for upload in &page.uploads {
+      result.scanned_uploads += 1;
+      if upload.initiated_ms >= cutoff_ms {
+        result.skipped_recent += 1;
+        continue;
+      }
+      // Other uploads in the bucket are not ours to abort.
+      let Some((workspace_id, key)) = blob_upload_owner(&upload.key) else {
+        result.skipped_foreign += 1;
+        continue;
+      };
+      match uploads.abort(upload).await {
+        Ok(freed_bytes) => {
+          result.aborted_uploads += 1;
+          result.freed_part_bytes += freed_bytes;
+        }
+        Err(_) => {
+          result.failed += 1;
+          continue;
+        }
+      }
+      let affected = store
+        .release_pending_upload(workspace_id, key, &upload.upload_id)
+        .await?;
+      if affected > 0 {
+        result.matched_pending_blobs += affected;
+        store.release_quota(workspace_id, key).await?;
+        push_workspace_once(&mut result.workspace_ids, workspace_id);
+      }
+    }
+    cursor = MultipartSweepCursor::after(&page);
+    if cursor.is_none() {
+      break;
+    }
+  }
+  result.next_cursor = cursor
+    .map(|cursor| serde_json::to_string(&cursor))
+    .transpose()
+    .map_err(|err| RuntimeError::json("multipart upload sweep cursor encode failed", err))?;
+  Ok(result)
+}
+
 fn push_workspace_once(workspace_ids: &mut Vec<String>, workspace_id: &str) {
   if !workspace_ids.iter().any(|id| id == workspace_id) {
     workspace_ids.push(workspace_id.to_string());
@@ -115,6 +320,9 @@ fn push_workspace_once(workspace_ids: &mut Vec<String>, workspace_id: &str) {
 
 #[napi_derive::napi]
 impl StorageRuntime {
+  /// Delete pending uploads created before `cutoff_ms` and release their
+  /// quota reservations, along with reservations that expired without their
+  /// upload being completed.
   #[napi]
   pub async fn cleanup_expired_pending_blobs(&self, cutoff_ms: i64, limit: i64) -> Result<RuntimeBlobCleanupResult> {
     if limit <= 0 {
@@ -123,11 +331,13 @@ impl StorageRuntime {
 
     let cutoff = DateTime::<Utc>::from_timestamp_millis(cutoff_ms)
       .ok_or_else(|| RuntimeError::invalid_input("pending blob cleanup cutoff is invalid"))?;
-    let store = BlobReclaimerStore::new(self.pool().await?);
+    let pool = self.pool().await?;
+    let store = BlobReclaimerStore::new(pool.clone());
     let rows = store.load_expired_pending(cutoff, limit).await?;
 
     let mut deleted = 0;
     let mut aborted_multipart = 0;
+    let mut released_reservations = 0;
     let mut workspace_ids = Vec::new();
     for row in &rows {
       let object_key = format!("{}/{}", row.workspace_id, row.key);
@@ -141,42 +351,265 @@ impl StorageRuntime {
         deleted += affected;
         push_workspace_once(&mut workspace_ids, &row.workspace_id);
       }
+      if release_blob_quota(&pool, &row.workspace_id, &row.key).await? {
+        released_reservations += 1;
+      }
+    }
+
+    let reservations = load_expired_reservations(&pool, limit).await?;
+    for reservation in &reservations {
+      if let Some(upload_id) = reservation.upload_id.as_deref() {
+        let object_key = format!("{}/{}", reservation.workspace_id, reservation.blob_key);
+        abort_upload_idempotent(self, &object_key, upload_id).await?;
+        aborted_multipart += 1;
+      }
+      if release_blob_quota(&pool, &reservation.workspace_id, &reservation.blob_key).await? {
+        released_reservations += 1;
+        push_workspace_once(&mut workspace_ids, &reservation.workspace_id);
+      }
     }
 
+    metrics::record_blob_cleanup(
+      "expire_pending",
+      &[
+        ("deleted", deleted),
+        ("aborted_multipart", aborted_multipart),
+        ("released_reservations", released_reservations),
+      ],
+    );
     Ok(RuntimeBlobCleanupResult {
-      scanned: rows.len() as i64,
+      scanned: (rows.len() + reservations.len()) as i64,
       deleted,
       aborted_multipart,
+      released_reservations,
       workspace_ids,
     })
   }
 
+  /// Abort multipart uploads of the blob bucket that were started before
+  /// `cutoff_ms` and never completed, dropping the pending `blobs` rows they
+  /// belong to. Scans at most `limit` uploads after `cursor`; pass the
+  /// returned opaque `next_cursor` to continue. Uploads outside the
+  /// `<workspace>/<key>` layout and uploads whose start time the provider
+  /// does not report are left alone. Providers without multipart
+  /// listing report nothing.
+  #[napi]
+  pub async fn sweep_abandoned_multipart_uploads(
+    &self,
+    cutoff_ms: i64,
+    limit: i64,
+    cursor: Option<String>,
+  ) -> Result<RuntimeMultipartSweepResult> {
+    if limit <= 0 {
+      return Err(napi_error("multipart upload sweep limit must be positive"));
+    }
+
+    let cursor = cursor
+      .map(|cursor| serde_json::from_str::<MultipartSweepCursor>(&cursor))
+      .transpose()
+      .map_err(|_| napi_error("multipart upload sweep cursor is invalid"))?;
+    let backend = self.backend_for_scope("blob")?;
+    let StorageBackendConfig::S3(config) = &backend else {
+      return Ok(empty_sweep_result());
+    };
+    let client = config.build_client()?;
+    let uploads = BucketUploads {
+      provider: backend.provider(),
+      client: &client,
+    };
+    let store = BlobReclaimerStore::new(self.pool().await?);
+    let result = sweep_uploads(&uploads, &store, cutoff_ms, limit, cursor).await?;
+
+    metrics::record_blob_cleanup(
+      "multipart_sweep",
+      &[
+        ("aborted_multipart", result.aborted_uploads),
+        ("deleted", result.matched_pending_blobs),
+        ("failed", result.failed),
+      ],
+    );
+    Ok(result)
+  }
+
+  /// Delete soft-deleted blobs of a workspace together with their image
+  /// derivatives, and subtract them from the workspace's used bytes.
   #[napi]
   pub async fn release_deleted_blobs(&self, workspace_id: String, limit: i64) -> Result<RuntimeBlobCleanupResult> {
     if limit <= 0 {
       return Err(napi_error("deleted blob release limit must be positive"));
     }
 
-    let store = BlobReclaimerStore::new(self.pool().await?);
+    let pool = self.pool().await?;
+    let store = BlobReclaimerStore::new(pool.clone());
     let rows = store.load_deleted(&workspace_id, limit).await?;
 
     let mut deleted = 0;
     let mut workspace_ids = Vec::new();
     for row in &rows {
       let object_key = format!("{}/{}", row.workspace_id, row.key);
+      delete_blob_derivatives(self, &pool, &row.workspace_id, &row.key).await?;
       delete_object_idempotent(self, &object_key).await?;
-      let affected = store.delete_released_metadata(&row.workspace_id, &row.key).await?;
-      if affected > 0 {
-        deleted += affected;
+      let freed_bytes = store.delete_released_metadata(&row.workspace_id, &row.key).await?;
+      if !freed_bytes.is_empty() {
+        deleted += freed_bytes.len() as i64;
+        release_blob_usage(&pool, &row.workspace_id, freed_bytes.iter().sum()).await?;
         push_workspace_once(&mut workspace_ids, &row.workspace_id);
       }
     }
 
+    metrics::record_blob_cleanup("release_deleted", &[("deleted", deleted)]);
     Ok(RuntimeBlobCleanupResult {
       scanned: rows.len() as i64,
       deleted,
       aborted_multipart: 0,
+      released_reservations: 0,
       workspace_ids,
     })
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::{collections::VecDeque, sync::Mutex};
+
+  use super::*;
+
+  struct FakeUploads {
+    pages: Mutex<VecDeque<MultipartUploadPage>>,
+    cursors: Mutex<Vec<Option<MultipartSweepCursor>>>,
+    aborted: Mutex<Vec<String>>,
+  }
+
+  impl FakeUploads {
+    fn new(pages: Vec<MultipartUploadPage>) -> Self {
+      Self {
+        pages: Mutex::new(pages.into()),
+        cursors: Mutex::default(),
+        aborted: Mutex::default(),
+      }
+    }
+  }
+
+  impl MultipartUploadSource for FakeUploads {
+    fn list_page(
+      &self,
+      cursor: Option<MultipartSweepCursor>,
+      _max_uploads: i32,
+    ) -> SweepFuture<'_, MultipartUploadPage> {
+      self.cursors.lock().unwrap().push(cursor);
+      let page = self.pages.lock().unwrap().pop_front().unwrap();
+      Box::pin(async move { Ok(page) })
+    }
+
+    fn abort<'a>(&'a self, upload: &'a MultipartUploadEntry) -> SweepFuture<'a, i64> {
+      self.aborted.lock().unwrap().push(upload.upload_id.clone());
+      Box::pin(async move { Ok(10) })
+    }
+  }
+
+  #[derive(Default)]
+  struct FakeStore {
+    pending: Mutex<Vec<(String, String, String)>>,
+    released: Mutex<Vec<(String, String)>>,
+  }
+
+  impl PendingUploadStore for FakeStore {
+    fn release_pending_upload<'a>(
+      &'a self,
+      workspace_id: &'a str,
+      key: &'a str,
+      upload_id: &'a str,
+    ) -> SweepFuture<'a, i64> {
+      let mut pending = self.pending.lock().unwrap();
+      let before = pending.len();
+      pending.retain(|row| row != &(workspace_id.to_string(), key.to_string(), upload_id.to_string()));
+      let deleted = (before - pending.len()) as i64;
+      Box::pin(async move { Ok(deleted) })
+    }
+
+    fn release_quota<'a>(&'a self, workspace_id: &'a str, key: &'a str) -> SweepFuture<'a, bool> {
+      self
+        .released
+        .lock()
+        .unwrap()
+        .push((workspace_id.to_string(), key.to_string()));
+      Box::pin(async move { Ok(true) })
+    }
+  }
+
+  fn upload(key: &str, upload_id: &str, initiated_ms: i64) -> MultipartUploadEntry {
+    MultipartUploadEntry {
+      key: key.to_string(),
+      upload_id: upload_id.to_string(),
+      initiated_ms,
+    }
+  }
+
+  #[tokio::test]
+  async fn sweep_aborts_old_blob_uploads_and_releases_their_pending_blobs() {
+    let uploads = FakeUploads::new(vec![
+      MultipartUploadPage {
+        uploads: vec![upload("ws/a", "upload-1", 10), upload("ws/a", "upload-2", 500)],
+        next_key_marker: Some("ws/a".to_string()),
+        next_upload_id_marker: Some("upload-2".to_string()),
+      },
+      MultipartUploadPage {
+        uploads: vec![upload("ws/a", "upload-3", 20), upload("other/x/y", "upload-4", 30)],
+        next_key_marker: None,
+        next_upload_id_marker: None,
+      },
+    ]);
+    let store = FakeStore::default();
+    store
+      .pending
+      .lock()
+      .unwrap()
+      .push(("ws".to_string(), "a".to_string(), "upload-1".to_string()));
+
+    let result = sweep_uploads(&uploads, &store, 100, 10, None).await.unwrap();
+
+    assert_eq!(result.scanned_uploads, 4);
+    assert_eq!(result.skipped_recent, 1);
+    assert_eq!(result.skipped_foreign, 1);
+    assert_eq!(result.aborted_uploads, 2);
+    assert_eq!(result.freed_part_bytes, 20);
+    assert_eq!(result.matched_pending_blobs, 1);
+    assert_eq!(result.workspace_ids, ["ws"]);
+    assert_eq!(result.next_cursor, None);
+    assert_eq!(*uploads.aborted.lock().unwrap(), ["upload-1", "upload-3"]);
+    assert_eq!(*store.released.lock().unwrap(), [("ws".to_string(), "a".to_string())]);
+    assert_eq!(
+      *uploads.cursors.lock().unwrap(),
+      [
+        None,
+        Some(MultipartSweepCursor {
+          key_marker: "ws/a".to_string(),
+          upload_id_marker: Some("upload-2".to_string()),
+        }),
+      ]
+    );
+  }
+
+  #[tokio::test]
+  async fn sweep_returns_a_cursor_with_both_markers_when_the_limit_is_reached() {
+    let uploads = FakeUploads::new(vec![MultipartUploadPage {
+      uploads: vec![upload("ws/a", "upload-1", 500)],
+      next_key_marker: Some("ws/a".to_string()),
+      next_upload_id_marker: Some("upload-1".to_string()),
+    }]);
+
+    let result = sweep_uploads(&uploads, &FakeStore::default(), 100, 1, None)
+      .await
+      .unwrap();
+
+    let cursor = result.next_cursor.unwrap();
+    assert_eq!(
+      serde_json::from_str::<MultipartSweepCursor>(&cursor).unwrap(),
+      MultipartSweepCursor {
+        key_marker: "ws/a".to_string(),
+        upload_id_marker: Some("upload-1".to_string()),
+      }
+    );
+    assert!(uploads.aborted.lock().unwrap().is_empty());
+  }
+}
@@ -2,7 +2,8 @@ use chrono::{DateTime, Utc};
 use sqlx::{FromRow, PgPool};
 
 use super::{
-  RuntimeBlobMetadataBackfillResult, RuntimeError, RuntimeObjectMetadata, RuntimeResult, StorageRuntime, napi_error,
+  RuntimeBlobMetadataBackfillResult, RuntimeError, RuntimeObjectMetadata, RuntimeResult, StorageRuntime,
+  blob_cleanup::QUARANTINE_PREFIX, blob_derivatives::is_derivative_key, napi_error,
 };
 
 async fn workspace_exists(pool: &PgPool, workspace_id: &str) -> RuntimeResult<bool> {
@@ -206,6 +207,14 @@ impl StorageRuntime {
     for object in &page.entries {
       result.scanned_objects += 1;
       last_scanned_key = Some(object.key.clone());
+      // Quarantined objects get their metadata back through a cleanup restore.
+      if object.key.starts_with(QUARANTINE_PREFIX) {
+        continue;
+      }
+      // Derivatives are tracked against their source blob, not as blobs.
+      if is_derivative_key(&object.key) {
+        continue;
+      }
       let Some((object_workspace_id, key)) = split_workspace_blob_key(&object.key) else {
         result.failed += 1;
         continue;
@@ -0,0 +1,550 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+
+use super::{
+  RuntimeBlobScrubFinding, RuntimeBlobScrubFindingCount, RuntimeBlobScrubResult, RuntimeBlobScrubSummary, RuntimeError,
+  RuntimeResult, StorageBackendConfig, StorageRuntime, assetpack, metrics, napi_error, sha256_base64_url_matches,
+};
+
+const SCRUB_RUN_KIND: &str = "blob_integrity_scrub";
+
+/// Rows are checked against storage first, then storage is listed to find
+/// objects without a row.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum ScrubPhase {
+  #[default]
+  Rows,
+  Objects,
+  Done,
+}
+
+impl ScrubPhase {
+  fn as_str(self) -> &'static str {
+    match self {
+      Self::Rows => "rows",
+      Self::Objects => "objects",
+      Self::Done => "done",
+    }
+  }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct ScrubCursor {
+  phase: ScrubPhase,
+  last_key: Option<String>,
+  continuation_token: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Finding {
+  kind: &'static str,
+  expected_size: Option<i64>,
+  actual_size: Option<i64>,
+  detail: serde_json::Value,
+}
+
+impl Finding {
+  fn new(kind: &'static str, detail: serde_json::Value) -> Self {
+    Self {
+      kind,
+      expected_size: None,
+      actual_size: None,
+      detail,
+    }
+  }
+}
+
+/// Compare a fetched blob body with its `blobs` row. Blob keys are the
+/// SHA-256 of the content, so the key doubles as the expected checksum.
+fn body_findings(key: &str, expected_size: i64, body: &[u8], checksum_crc32: Option<&str>) -> Vec<Finding> {
+  let mut findings = Vec::new();
+  if body.len() as i64 != expected_size {
+    findings.push(Finding {
+      kind: "size_mismatch",
+      expected_size: Some(expected_size),
+      actual_size: Some(body.len() as i64),
+      detail: serde_json::json!({}),
+    });
+  }
+  let sha256_matches = sha256_base64_url_matches(body, key);
+  let crc32_matches = checksum_crc32.is_none_or(|expected| expected == format!("{:x}", crc32fast::hash(body)));
+  if !sha256_matches || !crc32_matches {
+    findings.push(Finding::new(
+      "checksum_mismatch",
+      serde_json::json!({ "sha256Matches": sha256_matches, "crc32Matches": crc32_matches }),
+    ));
+  }
+  findings
+}
+
+/// The blob key of a listed object, or `None` for keys that cannot belong to
+/// a `blobs` row of the workspace.
+fn listed_blob_key<'a>(workspace_id: &str, object_key: &'a str) -> Option<&'a str> {
+  object_key
+    .strip_prefix(workspace_id)?
+    .strip_prefix('/')
+    .filter(|key| !key.is_empty() && !key.contains('/'))
+}
+
+#[derive(FromRow)]
+struct BlobRow {
+  key: String,
+  size: i32,
+}
+
+#[derive(FromRow)]
+struct ScrubRun {
+  workspace_id: Option<String>,
+  status: String,
+  cursor: serde_json::Value,
+  scanned: i32,
+  changed: i32,
+  failed: i32,
+  started_at: DateTime<Utc>,
+  finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(FromRow)]
+struct FindingRow {
+  workspace_id: String,
+  blob_key: String,
+  kind: String,
+  expected_size: Option<i64>,
+  actual_size: Option<i64>,
+  detail: serde_json::Value,
+  found_at: DateTime<Utc>,
+}
+
+async fn create_run(pool: &PgPool, workspace_id: &str) -> RuntimeResult<String> {
+  sqlx::query_scalar::<_, String>(
+    r#"
+    INSERT INTO blob_reconciliation_runs (kind, mode, status, workspace_id, cursor)
+    VALUES ($1, 'verify', 'running', $2, $3)
+    RETURNING id::text
+    "#,
+  )
+  .bind(SCRUB_RUN_KIND)
+  .bind(workspace_id)
+  .bind(serde_json::json!({ "phase": ScrubPhase::Rows.as_str() }))
+  .fetch_one(pool)
+  .await
+  .map_err(|err| RuntimeError::database("Blob scrub create run failed", err))
+}
+
+async fn load_run(pool: &PgPool, run_id: &str) -> RuntimeResult<Option<ScrubRun>> {
+  sqlx::query_as::<_, ScrubRun>(
+    r#"
+    SELECT workspace_id, status, cursor, scanned, changed, failed, started_at, finished_at
+    FROM blob_reconciliation_runs
+    WHERE id = $1::uuid AND kind = $2
+    "#,
+  )
+  .bind(run_id)
+  .bind(SCRUB_RUN_KIND)
+  .fetch_optional(pool)
+  .await
+  .map_err(|err| RuntimeError::database("Blob scrub load run failed", err))
+}
+
+async fn advance_run(
+  pool: &PgPool,
+  run_id: &str,
+  cursor: &ScrubCursor,
+  result: &RuntimeBlobScrubResult,
+) -> RuntimeResult<()> {
+  sqlx::query(
+    r#"
+    UPDATE blob_reconciliation_runs
+    SET status = CASE WHEN $6 THEN 'finished' ELSE 'running' END,
+        finished_at = CASE WHEN $6 THEN CURRENT_TIMESTAMP ELSE NULL END,
+        cursor = $2,
+        scanned = scanned + $3,
+        changed = changed + $4,
+        failed = failed + $5
+    WHERE id = $1::uuid
+    "#,
+  )
+  .bind(run_id)
+  .bind(serde_json::to_value(cursor).map_err(|err| RuntimeError::json("Blob scrub cursor encode failed", err))?)
+  .bind(result.scanned as i32)
+  .bind(result.findings as i32)
+  .bind(result.failed as i32)
+  .bind(result.completed)
+  .execute(pool)
+  .await
+  .map_err(|err| RuntimeError::database("Blob scrub update run failed", err))?;
+  Ok(())
+}
+
+async fn load_completed_blobs(
+  pool: &PgPool,
+  workspace_id: &str,
+  after_key: Option<&str>,
+  limit: i64,
+) -> RuntimeResult<Vec<BlobRow>> {
+  sqlx::query_as::<_, BlobRow>(
+    r#"
+    SELECT key, size
+    FROM blobs
+    WHERE workspace_id = $1
+      AND status = 'completed'
+      AND deleted_at IS NULL
+      AND ($2::text IS NULL OR key > $2)
+    ORDER BY key ASC
+    LIMIT $3
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(after_key)
+  .bind(limit)
+  .fetch_all(pool)
+  .await
+  .map_err(|err| RuntimeError::database("Blob scrub load blobs failed", err))
+}
+
+async fn blob_row_exists(pool: &PgPool, workspace_id: &str, key: &str) -> RuntimeResult<bool> {
+  sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM blobs WHERE workspace_id = $1 AND key = $2)")
+    .bind(workspace_id)
+    .bind(key)
+    .fetch_one(pool)
+    .await
+    .map_err(|err| RuntimeError::database("Blob scrub blob check failed", err))
+}
+
+async fn record_finding(
+  pool: &PgPool,
+  run_id: &str,
+  workspace_id: &str,
+  blob_key: &str,
+  finding: Finding,
+) -> RuntimeResult<()> {
+  // A batch retried after a crash reports the same findings again.
+  sqlx::query(
+    r#"
+    INSERT INTO blob_integrity_findings
+      (run_id, workspace_id, blob_key, kind, expected_size, actual_size, detail)
+    VALUES ($1::uuid, $2, $3, $4, $5, $6, $7)
+    ON CONFLICT (run_id, workspace_id, blob_key, kind) DO UPDATE
+      SET expected_size = EXCLUDED.expected_size,
+          actual_size = EXCLUDED.actual_size,
+          detail = EXCLUDED.detail,
+          found_at = CURRENT_TIMESTAMP
+    "#,
+  )
+  .bind(run_id)
+  .bind(workspace_id)
+  .bind(blob_key)
+  .bind(finding.kind)
+  .bind(finding.expected_size)
+  .bind(finding.actual_size)
+  .bind(finding.detail)
+  .execute(pool)
+  .await
+  .map_err(|err| RuntimeError::database("Blob scrub record finding failed", err))?;
+  metrics::record_blob_integrity_finding(finding.kind);
+  Ok(())
+}
+
+impl StorageRuntime {
+  /// Check one `blobs` row against its object. Assetpack chunks are re-hashed
+  /// first so a corrupted chunk is reported by hash rather than as an
+  /// unreadable blob.
+  async fn scrub_blob(&self, workspace_id: &str, row: &BlobRow) -> RuntimeResult<Vec<Finding>> {
+    let object_key = format!("{workspace_id}/{}", row.key);
+    if let StorageBackendConfig::Assetpack(config) = self.backend_for_scope("blob")?
+      && let Some(chunks) = assetpack::verify_chunks(&config, "blob", &object_key).await?
+      && !chunks.is_empty()
+    {
+      return Ok(vec![Finding::new(
+        "assetpack_chunk_corrupt",
+        serde_json::json!({ "chunks": chunks }),
+      )]);
+    }
+    match self.object_storage_get(&object_key).await {
+      Ok(Some(object)) => Ok(body_findings(
+        &row.key,
+        row.size as i64,
+        &object.body,
+        object.metadata.checksum_crc32.as_deref(),
+      )),
+      Ok(None) => Ok(vec![Finding::new("missing_object", serde_json::json!({}))]),
+      Err(err) if err.is_object_missing() => Ok(vec![Finding::new("missing_object", serde_json::json!({}))]),
+      Err(err) => Ok(vec![Finding::new(
+        "unreadable",
+        serde_json::json!({ "error": err.to_string() }),
+      )]),
+    }
+  }
+
+  async fn scrub_rows(
+    &self,
+    pool: &PgPool,
+    run_id: &str,
+    workspace_id: &str,
+    cursor: &mut ScrubCursor,
+    limit: i64,
+    result: &mut RuntimeBlobScrubResult,
+  ) -> RuntimeResult<()> {
+    // Rows a lagging replica has not seen yet are simply checked by the next
+    // run, so the row scan can stay off the primary.
+    let rows = load_completed_blobs(
+      &self.read_pool().await?,
+      workspace_id,
+      cursor.last_key.as_deref(),
+      limit,
+    )
+    .await?;
+    for row in &rows {
+      result.scanned += 1;
+      match self.scrub_blob(workspace_id, row).await {
+        Ok(findings) => {
+          for finding in findings {
+            result.findings += 1;
+            record_finding(pool, run_id, workspace_id, &row.key, finding).await?;
+          }
+        }
+        Err(_) => result.failed += 1,
+      }
+    }
+    if rows.len() < limit as usize {
+      *cursor = ScrubCursor {
+        phase: ScrubPhase::Objects,
+        ..Default::default()
+      };
+    } else {
+      cursor.last_key = rows.last().map(|row| row.key.clone());
+    }
+    Ok(())
+  }
+
+  async fn scrub_objects(
+    &self,
+    pool: &PgPool,
+    run_id: &str,
+    workspace_id: &str,
+    cursor: &mut ScrubCursor,
+    limit: i32,
+    result: &mut RuntimeBlobScrubResult,
+  ) -> RuntimeResult<()> {
+    let page = self
+      .object_storage_list_page(
+        Some(format!("{workspace_id}/")),
+        cursor.continuation_token.take(),
+        cursor.last_key.take(),
+        limit,
+      )
+      .await?;
+    for entry in &page.entries {
+      result.scanned += 1;
+      let Some(key) = listed_blob_key(workspace_id, &entry.key) else {
+        continue;
+      };
+      // Checked on the primary: a blob completed after the row scan must not
+      // be reported as an orphan.
+      if !blob_row_exists(pool, workspace_id, key).await? {
+        result.findings += 1;
+        let finding = Finding {
+          kind: "orphan_object",
+          expected_size: None,
+          actual_size: Some(entry.content_length),
+          detail: serde_json::json!({ "lastModifiedMs": entry.last_modified_ms }),
+        };
+        record_finding(pool, run_id, workspace_id, key, finding).await?;
+      }
+    }
+    // fs and assetpack pages carry no continuation token; a full page may
+    // still have more keys after it.
+    if page.next_continuation_token.is_none() && page.entries.len() < limit as usize {
+      cursor.phase = ScrubPhase::Done;
+    } else {
+      cursor.last_key = page.entries.last().map(|entry| entry.key.clone());
+      cursor.continuation_token = page.next_continuation_token;
+    }
+    Ok(())
+  }
+}
+
+#[napi_derive::napi]
+impl StorageRuntime {
+  /// Verify the next batch of a workspace's blobs: every `blobs` row must
+  /// have an object of the recorded size whose content hashes to its key,
+  /// and every object must have a row. Findings are written to
+  /// `blob_integrity_findings` under the run id. Omit `run_id` to start a new
+  /// run and pass the returned id until `completed` is true.
+  #[napi]
+  pub async fn scrub_workspace_blobs(
+    &self,
+    workspace_id: String,
+    run_id: Option<String>,
+    batch_limit: i64,
+  ) -> napi::Result<RuntimeBlobScrubResult> {
+    if batch_limit <= 0 {
+      return Err(napi_error("blob scrub batch limit must be positive"));
+    }
+    let page_limit = i32::try_from(batch_limit).map_err(|_| napi_error("blob scrub batch limit exceeds i32::MAX"))?;
+
+    let pool = self.pool().await?;
+    let (run_id, mut cursor) = match run_id {
+      Some(run_id) => {
+        let run = load_run(&pool, &run_id)
+          .await?
+          .ok_or_else(|| RuntimeError::invalid_input(format!("blob scrub run {run_id} does not exist")))?;
+        if run.workspace_id.as_deref() != Some(workspace_id.as_str()) {
+          return Err(
+            RuntimeError::invalid_input(format!("blob scrub run {run_id} belongs to another workspace")).into(),
+          );
+        }
+        let cursor = serde_json::from_value::<ScrubCursor>(run.cursor)
+          .map_err(|err| RuntimeError::json("Blob scrub cursor decode failed", err))?;
+        (run_id, cursor)
+      }
+      None => (create_run(&pool, &workspace_id).await?, ScrubCursor::default()),
+    };
+
+    let mut result = RuntimeBlobScrubResult {
+      run_id: run_id.clone(),
+      phase: cursor.phase.as_str().to_string(),
+      scanned: 0,
+      findings: 0,
+      failed: 0,
+      completed: cursor.phase == ScrubPhase::Done,
+    };
+    match cursor.phase {
+      ScrubPhase::Rows => {
+        self
+          .scrub_rows(&pool, &run_id, &workspace_id, &mut cursor, batch_limit, &mut result)
+          .await?
+      }
+      ScrubPhase::Objects => {
+        self
+          .scrub_objects(&pool, &run_id, &workspace_id, &mut cursor, page_limit, &mut result)
+          .await?
+      }
+      ScrubPhase::Done => return Ok(result),
+    }
+    result.completed = cursor.phase == ScrubPhase::Done;
+    advance_run(&pool, &run_id, &cursor, &result).await?;
+    Ok(result)
+  }
+
+  /// Totals of a scrub run with its findings counted by kind and the most
+  /// recent `finding_limit` findings.
+  #[napi]
+  pub async fn blob_scrub_summary(&self, run_id: String, finding_limit: i64) -> napi::Result<RuntimeBlobScrubSummary> {
+    if finding_limit < 0 {
+      return Err(napi_error("blob scrub finding limit must be non-negative"));
+    }
+    let pool = self.read_pool().await?;
+    let run = load_run(&pool, &run_id)
+      .await?
+      .ok_or_else(|| RuntimeError::invalid_input(format!("blob scrub run {run_id} does not exist")))?;
+    let counts = sqlx::query_as::<_, (String, i64)>(
+      r#"
+      SELECT kind, COUNT(*)::bigint
+      FROM blob_integrity_findings
+      WHERE run_id = $1::uuid
+      GROUP BY kind
+      ORDER BY kind
+      "#,
+    )
+    .bind(&run_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|err| RuntimeError::database("Blob scrub count findings failed", err))?;
+    let findings = sqlx::query_as::<_, FindingRow>(
+      r#"
+      SELECT workspace_id, blob_key, kind, expected_size, actual_size, detail, found_at
+      FROM blob_integrity_findings
+      WHERE run_id = $1::uuid
+      ORDER BY found_at DESC, blob_key ASC
+      LIMIT $2
+      "#,
+    )
+    .bind(&run_id)
+    .bind(finding_limit)
+    .fetch_all(&pool)
+    .await
+    .map_err(|err| RuntimeError::database("Blob scrub load findings failed", err))?;
+
+    Ok(RuntimeBlobScrubSummary {
+      run_id,
+      workspace_id: run.workspace_id,
+      status: run.status,
+      scanned: run.scanned as i64,
+      findings: run.changed as i64,
+      failed: run.failed as i64,
+      started_at_ms: run.started_at.timestamp_millis(),
+      finished_at_ms: run.finished_at.map(|finished_at| finished_at.timestamp_millis()),
+      counts: counts
+        .into_iter()
+        .map(|(kind, count)| RuntimeBlobScrubFindingCount { kind, count })
+        .collect(),
+      recent_findings: findings
+        .into_iter()
+        .map(|row| RuntimeBlobScrubFinding {
+          workspace_id: row.workspace_id,
+          blob_key: row.blob_key,
+          kind: row.kind,
+          expected_size: row.expected_size,
+          actual_size: row.actual_size,
+          detail: row.detail,
+          found_at_ms: row.found_at.timestamp_millis(),
+        })
+        .collect(),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{super::sha256_base64_url, *};
+
+  #[test]
+  fn body_checks_report_size_and_checksum_drift() {
+    let body = b"attachment";
+    let key = sha256_base64_url(body);
+    let crc32 = format!("{:x}", crc32fast::hash(body));
+    assert!(body_findings(&key, body.len() as i64, body, Some(&crc32)).is_empty());
+    assert!(body_findings(&key, body.len() as i64, body, None).is_empty());
+
+    let findings = body_findings(&key, 3, body, None);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].kind, "size_mismatch");
+    assert_eq!(findings[0].expected_size, Some(3));
+    assert_eq!(findings[0].actual_size, Some(body.len() as i64));
+
+    let findings = body_findings(&key, body.len() as i64, b"attachmenT", None);
+    assert_eq!(findings[0].kind, "checksum_mismatch");
+    assert_eq!(findings[0].detail["sha256Matches"], false);
+    let findings = body_findings(&key, body.len() as i64, body, Some("deadbeef"));
+    assert_eq!(findings[0].detail["crc32Matches"], false);
+  }
+
+  #[test]
+  fn only_direct_workspace_children_are_blob_keys() {
+    assert_eq!(listed_blob_key("ws", "ws/blob"), Some("blob"));
+    assert_eq!(listed_blob_key("ws", "ws/nested/blob"), None);
+    assert_eq!(listed_blob_key("ws", "ws2/blob"), None);
+    assert_eq!(listed_blob_key("ws", "ws/"), None);
+  }
+
+  #[test]
+  fn cursor_round_trips_through_run_json() {
+    let cursor = ScrubCursor {
+      phase: ScrubPhase::Objects,
+      last_key: Some("ws/blob".to_string()),
+      continuation_token: None,
+    };
+    let value = serde_json::to_value(&cursor).unwrap();
+    assert_eq!(value["phase"], "objects");
+    assert_eq!(value["lastKey"], "ws/blob");
+    assert_eq!(serde_json::from_value::<ScrubCursor>(value).unwrap(), cursor);
+    assert_eq!(
+      serde_json::from_value::<ScrubCursor>(serde_json::json!({ "phase": "rows" })).unwrap(),
+      ScrubCursor::default()
+    );
+  }
+}
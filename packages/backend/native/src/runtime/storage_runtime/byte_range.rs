@@ -0,0 +1,157 @@
+use super::{RuntimeError, RuntimeResult};
+
+/// A single HTTP byte range as sent in a `Range: bytes=...` header. Multiple
+/// ranges are rejected; media players and PDF viewers only ask for one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ByteRange {
+  /// `bytes=start-end`, both inclusive.
+  Bounded { start: u64, end: u64 },
+  /// `bytes=start-`
+  From { start: u64 },
+  /// `bytes=-length`, the last `length` bytes.
+  Suffix { length: u64 },
+}
+
+/// A range resolved against the object size. `end` is inclusive, matching
+/// the `Content-Range` header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ResolvedRange {
+  pub(crate) start: u64,
+  pub(crate) end: u64,
+}
+
+impl ByteRange {
+  pub(crate) fn parse(header: &str) -> RuntimeResult<Self> {
+    let invalid = || RuntimeError::invalid_input(format!("Invalid byte range: {header}"));
+    let spec = header.trim().strip_prefix("bytes=").ok_or_else(invalid)?.trim();
+    if spec.contains(',') {
+      return Err(RuntimeError::invalid_input("Multiple byte ranges are not supported"));
+    }
+    let (start, end) = spec.split_once('-').ok_or_else(invalid)?;
+    let parse = |value: &str| value.trim().parse::<u64>().map_err(|_| invalid());
+    match (start.trim().is_empty(), end.trim().is_empty()) {
+      (true, false) => Ok(Self::Suffix { length: parse(end)? }),
+      (false, true) => Ok(Self::From { start: parse(start)? }),
+      (false, false) => {
+        let (start, end) = (parse(start)?, parse(end)?);
+        if end < start {
+          return Err(invalid());
+        }
+        Ok(Self::Bounded { start, end })
+      }
+      (true, true) => Err(invalid()),
+    }
+  }
+
+  /// Clamp the range to an object of `total` bytes. `None` means the range is
+  /// unsatisfiable and the caller should answer 416.
+  pub(crate) fn resolve(self, total: u64) -> Option<ResolvedRange> {
+    match self {
+      Self::Bounded { start, end } if start < total => Some(ResolvedRange {
+        start,
+        end: end.min(total - 1),
+      }),
+      Self::From { start } if start < total => Some(ResolvedRange { start, end: total - 1 }),
+      Self::Suffix { length } if length > 0 && total > 0 => Some(ResolvedRange {
+        start: total.saturating_sub(length),
+        end: total - 1,
+      }),
+      _ => None,
+    }
+  }
+
+  pub(crate) fn header_value(self) -> String {
+    match self {
+      Self::Bounded { start, end } => format!("bytes={start}-{end}"),
+      Self::From { start } => format!("bytes={start}-"),
+      Self::Suffix { length } => format!("bytes=-{length}"),
+    }
+  }
+}
+
+impl ResolvedRange {
+  pub(crate) fn length(self) -> u64 {
+    self.end - self.start + 1
+  }
+
+  /// Parse a `Content-Range: bytes start-end/total` response header.
+  pub(crate) fn from_content_range(value: &str) -> Option<(Self, u64)> {
+    let (range, total) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+    let (start, end) = range.split_once('-')?;
+    let range = Self {
+      start: start.parse().ok()?,
+      end: end.parse().ok()?,
+    };
+    (range.start <= range.end).then_some((range, total.parse().ok()?))
+  }
+
+  pub(crate) fn slice(self, body: &[u8]) -> &[u8] {
+    let end = usize::try_from(self.end.saturating_add(1))
+      .unwrap_or(usize::MAX)
+      .min(body.len());
+    let start = usize::try_from(self.start).unwrap_or(usize::MAX).min(end);
+    &body[start..end]
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_single_byte_ranges() {
+    assert_eq!(
+      ByteRange::parse("bytes=0-99").unwrap(),
+      ByteRange::Bounded { start: 0, end: 99 }
+    );
+    assert_eq!(
+      ByteRange::parse(" bytes=100- ").unwrap(),
+      ByteRange::From { start: 100 }
+    );
+    assert_eq!(
+      ByteRange::parse("bytes=-500").unwrap(),
+      ByteRange::Suffix { length: 500 }
+    );
+
+    for header in [
+      "",
+      "bytes=",
+      "bytes=-",
+      "bytes=5-1",
+      "bytes=a-b",
+      "items=0-1",
+      "bytes=0-1,4-5",
+    ] {
+      assert!(ByteRange::parse(header).is_err(), "{header}");
+    }
+  }
+
+  #[test]
+  fn resolves_ranges_against_object_size() {
+    let resolved = |header: &str, total| ByteRange::parse(header).unwrap().resolve(total);
+
+    assert_eq!(resolved("bytes=0-99", 50), Some(ResolvedRange { start: 0, end: 49 }));
+    assert_eq!(resolved("bytes=10-", 50), Some(ResolvedRange { start: 10, end: 49 }));
+    assert_eq!(resolved("bytes=-10", 50), Some(ResolvedRange { start: 40, end: 49 }));
+    assert_eq!(resolved("bytes=-100", 50), Some(ResolvedRange { start: 0, end: 49 }));
+    assert_eq!(resolved("bytes=50-", 50), None);
+    assert_eq!(resolved("bytes=-0", 50), None);
+    assert_eq!(resolved("bytes=0-", 0), None);
+    assert_eq!(resolved("bytes=-1", 0), None);
+  }
+
+  #[test]
+  fn parses_content_range_and_slices_bodies() {
+    assert_eq!(
+      ResolvedRange::from_content_range("bytes 2-4/10"),
+      Some((ResolvedRange { start: 2, end: 4 }, 10))
+    );
+    assert_eq!(ResolvedRange::from_content_range("bytes */10"), None);
+    assert_eq!(ResolvedRange::from_content_range("bytes 4-2/10"), None);
+
+    let range = ResolvedRange { start: 2, end: 4 };
+    assert_eq!(range.length(), 3);
+    assert_eq!(range.slice(b"0123456789"), b"234");
+    assert_eq!(ResolvedRange { start: 8, end: 20 }.slice(b"0123456789"), b"89");
+  }
+}
@@ -3,7 +3,9 @@ use chrono::{DateTime, Utc};
 use sqlx::{FromRow, PgPool};
 use y_octo::Doc;
 
-use super::{RuntimeDocBlobRefsResult, RuntimeError, RuntimeResult, StorageRuntime, napi_error};
+use super::{
+  DocMasterKeys, RuntimeDocBlobRefsResult, RuntimeError, RuntimeResult, StorageRuntime, WorkspaceDocCipher, napi_error,
+};
 
 const PARSER_VERSION: i32 = 1;
 
@@ -70,24 +72,35 @@ fn apply_doc_updates(updates: impl IntoIterator<Item = Vec<u8>>) -> RuntimeResul
     .map_err(|err| RuntimeError::invalid_state(format!("Doc blob refs encode failed: {err}")))
 }
 
-async fn load_current_doc(pool: &PgPool, workspace_id: &str, doc_id: &str) -> RuntimeResult<Option<SnapshotRow>> {
+async fn load_current_doc(
+  pool: &PgPool,
+  master_keys: &DocMasterKeys,
+  workspace_id: &str,
+  doc_id: &str,
+) -> RuntimeResult<Option<SnapshotRow>> {
   let snapshot = load_snapshot(pool, workspace_id, doc_id).await?;
   let updates = load_updates(pool, workspace_id, doc_id).await?;
   if snapshot.is_none() && updates.is_empty() {
     return Ok(None);
   }
 
+  let mut conn = pool
+    .acquire()
+    .await
+    .map_err(|err| RuntimeError::database("Doc blob refs acquire connection failed", err))?;
+  let cipher = WorkspaceDocCipher::for_read(&mut conn, master_keys, workspace_id).await?;
+
   let mut merge_inputs = Vec::with_capacity(updates.len() + usize::from(snapshot.is_some()));
   let mut updated_at = snapshot
     .as_ref()
     .map(|snapshot| snapshot.updated_at)
     .unwrap_or_else(Utc::now);
   if let Some(snapshot) = snapshot {
-    merge_inputs.push(snapshot.blob);
+    merge_inputs.push(cipher.decrypt(doc_id, snapshot.blob)?);
   }
   for update in updates {
     updated_at = update.created_at;
-    merge_inputs.push(update.blob);
+    merge_inputs.push(cipher.decrypt(doc_id, update.blob)?);
   }
 
   Ok(Some(SnapshotRow {
@@ -98,8 +111,12 @@ async fn load_current_doc(pool: &PgPool, workspace_id: &str, doc_id: &str) -> Ru
   }))
 }
 
-async fn load_workspace_doc_ids(pool: &PgPool, workspace_id: &str) -> RuntimeResult<Vec<String>> {
-  let Some(root) = load_current_doc(pool, workspace_id, workspace_id).await? else {
+async fn load_workspace_doc_ids(
+  pool: &PgPool,
+  master_keys: &DocMasterKeys,
+  workspace_id: &str,
+) -> RuntimeResult<Vec<String>> {
+  let Some(root) = load_current_doc(pool, master_keys, workspace_id, workspace_id).await? else {
     return Ok(Vec::new());
   };
   let ids = doc_parser::get_doc_ids_from_binary(root.blob, false)
@@ -328,6 +345,7 @@ async fn rebuild_doc_blob_refs_inner(
   doc_id: String,
 ) -> RuntimeResult<RuntimeDocBlobRefsResult> {
   let pool = runtime.pool().await?;
+  let master_keys = runtime.doc_master_keys()?;
   let mut result = RuntimeDocBlobRefsResult {
     scanned_docs: 1,
     parsed_docs: 0,
@@ -337,7 +355,7 @@ async fn rebuild_doc_blob_refs_inner(
     next_cursor: None,
   };
 
-  let Some(snapshot) = load_current_doc(&pool, &workspace_id, &doc_id).await? else {
+  let Some(snapshot) = load_current_doc(&pool, &master_keys, &workspace_id, &doc_id).await? else {
     result.failed_docs = 1;
     mark_doc_failed(&pool, &workspace_id, &doc_id, "snapshot_missing").await?;
     return Ok(result);
@@ -381,7 +399,7 @@ impl StorageRuntime {
     }
 
     let pool = self.pool().await?;
-    let doc_ids = match load_workspace_doc_ids(&pool, &workspace_id).await {
+    let doc_ids = match load_workspace_doc_ids(&pool, &self.doc_master_keys()?, &workspace_id).await {
       Ok(doc_ids) => doc_ids,
       Err(err) => {
         upsert_projection_failure_checkpoint(&pool, &workspace_id, &err.to_string()).await?;
@@ -1,9 +1,10 @@
 use std::{
   collections::HashMap,
   env, fs,
+  io::{Read, Seek, SeekFrom},
   path::{Path, PathBuf},
   sync::RwLock,
-  time::SystemTime,
+  time::{Instant, SystemTime},
 };
 
 use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
@@ -11,33 +12,62 @@ use napi::bindgen_prelude::Buffer;
 use serde::Deserialize;
 use serde_json::{Map, Value};
 use sha2::{Digest, Sha256};
-use sqlx::{PgPool, Row, postgres::PgPoolOptions};
+use sqlx::{PgPool, Row};
 use tokio::sync::Mutex;
 
 mod assetpack;
 mod blob_cleanup;
+mod blob_derivatives;
+mod blob_encryption;
+mod blob_quota;
 mod blob_reclaimer;
 mod blob_reconciliation;
+mod blob_scrubber;
+mod byte_range;
 mod doc_blob_refs;
 pub(crate) mod object_storage;
-
-use self::object_storage::{
-  ObjectStorageConfig, StorageProviderConfig,
-  types::{ObjectGetResult, ObjectListEntry, ObjectMetadata, ObjectPutMetadata},
+mod object_stream;
+mod storage_migration;
+mod workspace_archive;
+mod workspace_purge;
+
+use self::{
+  blob_derivatives::{ImageDerivativeConfig, ImageDerivativeConfigFile},
+  blob_encryption::plaintext_metadata,
+  byte_range::{ByteRange, ResolvedRange},
+  object_storage::{
+    ObjectStorageConfig, StorageProviderConfig,
+    resilience::circuit_state,
+    types::{
+      ObjectEncryption, ObjectGetResult, ObjectListEntry, ObjectMetadata, ObjectPutMetadata, ObjectRangeGetResult,
+    },
+  },
 };
 pub(super) use super::{
-  RuntimeError, RuntimeResult,
+  DocEncryptionConfig, RuntimeError, RuntimeResult,
+  backend_runtime::doc_encryption::{DocMasterKeys, WorkspaceDataKeys, WorkspaceDocCipher},
+  config_validation::{STORAGE_SECTIONS, ensure_valid_app_config},
+  database::{DatabasePoolConfig, DatabaseReplicaConfig, ReplicaSet, connect_primary, is_sqlite_url},
+  metrics,
   migrations::migrate_runtime_tables,
   napi_error, to_napi_error,
   types::{
-    RuntimeBlobCleanupExecuteResult, RuntimeBlobCleanupPlanResult, RuntimeBlobCleanupResult, RuntimeBlobCompleteResult,
-    RuntimeBlobMetadataBackfillResult, RuntimeDocBlobRefsResult, RuntimeMultipartUploadInit,
+    RuntimeBlobCleanupExecuteResult, RuntimeBlobCleanupPlanResult, RuntimeBlobCleanupPurgeResult,
+    RuntimeBlobCleanupRestoreResult, RuntimeBlobCleanupResult, RuntimeBlobCompleteResult, RuntimeBlobDerivativeResult,
+    RuntimeBlobMetadataBackfillResult, RuntimeBlobQuota, RuntimeBlobQuotaReservation, RuntimeBlobReencryptionResult,
+    RuntimeBlobScrubFinding, RuntimeBlobScrubFindingCount, RuntimeBlobScrubResult, RuntimeBlobScrubSummary,
+    RuntimeConfigReloadResult, RuntimeDocBlobRefsResult, RuntimeMultipartSweepResult, RuntimeMultipartUploadInit,
     RuntimeMultipartUploadPart, RuntimeObjectGetResult, RuntimeObjectListEntry, RuntimeObjectMetadata,
-    RuntimeObjectStoragePutOptions, RuntimePresignedObjectRequest,
+    RuntimeObjectRangeResult, RuntimeObjectStoragePutOptions, RuntimePresignedObjectRequest,
+    RuntimeStorageMigrationResult, RuntimeStorageUsageReconcileResult, RuntimeWorkspaceArchiveProgress,
+    RuntimeWorkspacePurgeResult, RuntimeWorkspaceStorageUsage,
   },
 };
 
 const MAX_BLOB_SIZE: i64 = i32::MAX as i64;
+// Streamed fs uploads are written here and renamed into place on finish, so
+// listings never see a partially written object.
+const FS_UPLOADS_DIR: &str = ".uploads";
 
 type Result<T> = RuntimeResult<T>;
 
@@ -48,6 +78,9 @@ pub struct StorageRuntimeHealth {
   pub provider_configured: bool,
   pub provider: Option<String>,
   pub bucket: Option<String>,
+  /// Circuit breaker state of the blob bucket: `closed`, `open` or
+  /// `half_open`. Unset for local providers.
+  pub circuit_state: Option<String>,
 }
 
 #[napi_derive::napi(object)]
@@ -68,7 +101,7 @@ pub struct StorageProviderCapabilities {
 #[derive(Clone, Debug)]
 enum StorageBackendConfig {
   Fs(FsStorageConfig),
-  S3(ObjectStorageConfig),
+  S3(Box<ObjectStorageConfig>),
   Assetpack(FsStorageConfig),
 }
 
@@ -82,7 +115,12 @@ struct FsStorageConfig {
 #[derive(Clone, Debug)]
 struct StorageRuntimeConfig {
   database_url: String,
+  pool: DatabasePoolConfig,
+  replicas: DatabaseReplicaConfig,
   backends: HashMap<String, StorageBackendConfig>,
+  doc_encryption: DocEncryptionConfig,
+  blob_encryption: bool,
+  image_derivatives: ImageDerivativeConfig,
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -91,12 +129,15 @@ struct AppConfigFile {
   #[serde(default)]
   storages: Option<HashMap<String, Value>>,
   copilot: Option<CopilotConfigFile>,
+  crypto: Option<CryptoConfigFile>,
 }
 
 #[derive(Debug, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct DbConfigFile {
   datasource_url: Option<String>,
+  pool: Option<DatabasePoolConfig>,
+  replicas: Option<DatabaseReplicaConfig>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -109,6 +150,21 @@ struct CopilotConfigFile {
   storage: Option<StorageProviderConfig>,
 }
 
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CryptoConfigFile {
+  doc_encryption: Option<DocEncryptionConfig>,
+  blob_encryption: Option<BlobEncryptionConfigFile>,
+}
+
+/// `crypto.blobEncryption`: seal `blob` scope objects with keys derived from
+/// the workspace doc data keys.
+#[derive(Debug, Default, Deserialize)]
+struct BlobEncryptionConfigFile {
+  #[serde(default)]
+  enabled: bool,
+}
+
 impl StorageRuntimeConfig {
   fn from_config_files() -> RuntimeResult<Self> {
     Self::from_app_config_file(app_config_from_config_files()?)
@@ -119,18 +175,60 @@ impl StorageRuntimeConfig {
       .or(app_config.database_url())
       .unwrap_or_else(|| "postgresql://localhost:5432/affine".to_string());
     let backends = app_config.storage_backends()?;
-    Ok(Self { database_url, backends })
+    Ok(Self {
+      database_url,
+      pool: app_config.database_pool(),
+      replicas: app_config.database_replicas(),
+      backends,
+      doc_encryption: app_config.doc_encryption().unwrap_or_default().with_env_override(),
+      blob_encryption: app_config.blob_encryption().unwrap_or_default(),
+      image_derivatives: app_config.image_derivatives()?.unwrap_or_default(),
+    })
   }
 
   async fn with_db_overrides(&self, pool: &PgPool) -> RuntimeResult<Self> {
     let app_config = load_app_config_overrides_from_db(pool).await?;
     let mut backends = self.backends.clone();
     backends.extend(app_config.storage_backends()?);
+    let doc_encryption = match app_config.doc_encryption() {
+      Some(doc_encryption) => doc_encryption.with_env_override(),
+      None => self.doc_encryption.clone(),
+    };
     Ok(Self {
       database_url: self.database_url.clone(),
+      pool: self.pool.clone(),
+      replicas: self.replicas.clone(),
       backends,
+      doc_encryption,
+      blob_encryption: app_config.blob_encryption().unwrap_or(self.blob_encryption),
+      image_derivatives: app_config
+        .image_derivatives()?
+        .unwrap_or_else(|| self.image_derivatives.clone()),
     })
   }
+
+  /// Apply the sections the server forwards through `configure`. The server
+  /// does not forward the `crypto` section or `storages."blob.derivatives"`,
+  /// so the keys, the blob encryption switch and the derivative settings
+  /// keep their current values unless the payload carries them.
+  fn with_forwarded(&self, forwarded: Value) -> RuntimeResult<Self> {
+    let app_config: AppConfigFile =
+      serde_json::from_value(forwarded).map_err(|err| RuntimeError::json("invalid storage runtime config", err))?;
+    let keeps_doc_encryption = app_config.doc_encryption().is_none();
+    let keeps_blob_encryption = app_config.blob_encryption().is_none();
+    let keeps_image_derivatives = app_config.image_derivatives()?.is_none();
+    let mut config = Self::from_app_config_file(app_config)?;
+    if keeps_doc_encryption {
+      config.doc_encryption = self.doc_encryption.clone();
+    }
+    if keeps_blob_encryption {
+      config.blob_encryption = self.blob_encryption;
+    }
+    if keeps_image_derivatives {
+      config.image_derivatives = self.image_derivatives.clone();
+    }
+    Ok(config)
+  }
 }
 
 impl StorageBackendConfig {
@@ -158,8 +256,8 @@ impl StorageBackendConfig {
           bucket: storage.bucket,
         })))
       }
-      "aws-s3" | "cloudflare-r2" => ObjectStorageConfig::from_provider_config(Some(storage))
-        .map(|v| v.map(Self::S3))
+      "aws-s3" | "cloudflare-r2" | "gcs" | "azure-blob" => ObjectStorageConfig::from_provider_config(Some(storage))
+        .map(|v| v.map(|config| Self::S3(Box::new(config))))
         .map_err(Into::into),
       provider => Err(RuntimeError::config(format!(
         "unsupported blob storage provider for StorageRuntime: {provider}"
@@ -181,6 +279,69 @@ impl StorageBackendConfig {
     }
   }
 
+  /// One page of keys after `start_after`. fs and assetpack list in memory
+  /// and never return a continuation token.
+  async fn list_page(
+    &self,
+    scope: &str,
+    prefix: Option<String>,
+    continuation_token: Option<String>,
+    start_after: Option<String>,
+    max_keys: i32,
+  ) -> RuntimeResult<object_storage::types::ObjectListPage> {
+    match self {
+      Self::Fs(config) => {
+        let mut entries = fs_list(config, prefix)?;
+        if let Some(start_after) = start_after {
+          entries.retain(|entry| entry.key > start_after);
+        }
+        if continuation_token.is_some() {
+          return Err(RuntimeError::invalid_input(
+            "StorageRuntime fs list continuation token is not supported",
+          ));
+        }
+        let max_keys = usize::try_from(max_keys)
+          .map_err(|_| RuntimeError::invalid_input("StorageRuntime list maxKeys must be positive"))?;
+        entries.truncate(max_keys);
+        Ok(object_storage::types::ObjectListPage {
+          entries,
+          next_continuation_token: None,
+        })
+      }
+      Self::Assetpack(config) => {
+        let mut entries = assetpack::list(config, scope, prefix).await?;
+        if let Some(start_after) = start_after {
+          entries.retain(|entry| entry.key > start_after);
+        }
+        if continuation_token.is_some() {
+          return Err(RuntimeError::invalid_input(
+            "StorageRuntime assetpack list continuation token is not supported",
+          ));
+        }
+        let max_keys = usize::try_from(max_keys)
+          .map_err(|_| RuntimeError::invalid_input("StorageRuntime list maxKeys must be positive"))?;
+        entries.truncate(max_keys);
+        Ok(object_storage::types::ObjectListPage {
+          entries,
+          next_continuation_token: None,
+        })
+      }
+      Self::S3(config) => config
+        .build_client()?
+        .list_page(prefix, continuation_token, start_after, max_keys)
+        .await
+        .map_err(Into::into),
+    }
+  }
+
+  async fn head(&self, scope: &str, key: &str) -> RuntimeResult<Option<ObjectMetadata>> {
+    match self {
+      Self::Fs(config) => fs_head(config, key),
+      Self::Assetpack(config) => assetpack::head(config, scope, key).await,
+      Self::S3(config) => config.build_client()?.head(key).await.map_err(Into::into),
+    }
+  }
+
   fn capabilities(&self) -> StorageProviderCapabilities {
     match self {
       Self::Fs(_) => StorageProviderCapabilities {
@@ -206,7 +367,7 @@ impl StorageBackendConfig {
           delete: true,
           presign_put: config.use_presigned_url,
           presign_get: config.use_presigned_url,
-          multipart_direct: config.use_presigned_url,
+          multipart_direct: config.supports_multipart_direct(),
           proxy_upload: config.proxy_upload,
           assetpack: false,
           server_mediated_only: !config.use_presigned_url,
@@ -238,6 +399,14 @@ impl AppConfigFile {
       .and_then(non_empty_string)
   }
 
+  fn database_pool(&self) -> DatabasePoolConfig {
+    self.db.as_ref().and_then(|db| db.pool.clone()).unwrap_or_default()
+  }
+
+  fn database_replicas(&self) -> DatabaseReplicaConfig {
+    self.db.as_ref().and_then(|db| db.replicas.clone()).unwrap_or_default()
+  }
+
   fn storage_backends(&self) -> RuntimeResult<HashMap<String, StorageBackendConfig>> {
     let mut backends = HashMap::new();
     if let Some(storage) = self.storage_provider_config("blob.storage")?
@@ -258,6 +427,29 @@ impl AppConfigFile {
     Ok(backends)
   }
 
+  fn doc_encryption(&self) -> Option<DocEncryptionConfig> {
+    self.crypto.as_ref().and_then(|crypto| crypto.doc_encryption.clone())
+  }
+
+  fn blob_encryption(&self) -> Option<bool> {
+    self
+      .crypto
+      .as_ref()
+      .and_then(|crypto| crypto.blob_encryption.as_ref())
+      .map(|blob_encryption| blob_encryption.enabled)
+  }
+
+  fn image_derivatives(&self) -> RuntimeResult<Option<ImageDerivativeConfig>> {
+    self
+      .storages
+      .as_ref()
+      .and_then(|storages| storages.get("blob.derivatives").cloned())
+      .map(serde_json::from_value::<ImageDerivativeConfigFile>)
+      .transpose()
+      .map(|config| config.map(Into::into))
+      .map_err(|err| RuntimeError::json("invalid blob derivatives config", err))
+  }
+
   fn storage_provider_config(&self, key: &str) -> RuntimeResult<Option<StorageProviderConfig>> {
     self
       .storages
@@ -280,13 +472,19 @@ impl AppConfigFile {
     if config.copilot.is_some() {
       self.copilot = config.copilot;
     }
+    if config.crypto.is_some() {
+      self.crypto = config.crypto;
+    }
   }
 }
 
 #[napi_derive::napi]
 pub struct StorageRuntime {
   config: RwLock<StorageRuntimeConfig>,
+  /// The last payload passed to `configure`, applied again on reload.
+  forwarded: RwLock<Option<Value>>,
   pool: Mutex<Option<PgPool>>,
+  replicas: Mutex<Option<ReplicaSet>>,
 }
 
 #[napi_derive::napi]
@@ -295,7 +493,9 @@ impl StorageRuntime {
   pub fn new() -> napi::Result<Self> {
     Ok(Self {
       config: RwLock::new(StorageRuntimeConfig::from_config_files().map_err(to_napi_error)?),
+      forwarded: RwLock::new(None),
       pool: Mutex::new(None),
+      replicas: Mutex::new(None),
     })
   }
 
@@ -306,9 +506,17 @@ impl StorageRuntime {
 
   #[napi]
   pub fn configure(&self, config_json: String) -> napi::Result<()> {
-    let app_config: AppConfigFile = serde_json::from_str(&config_json)
+    let value: Value = serde_json::from_str(&config_json)
       .map_err(|err| to_napi_error(RuntimeError::json("invalid storage runtime config", err)))?;
-    let config = StorageRuntimeConfig::from_app_config_file(app_config).map_err(to_napi_error)?;
+    ensure_valid_app_config(&value, STORAGE_SECTIONS, "invalid storage runtime config").map_err(to_napi_error)?;
+    let config = self
+      .config()
+      .and_then(|current| current.with_forwarded(value.clone()))
+      .map_err(to_napi_error)?;
+    *self
+      .forwarded
+      .write()
+      .map_err(|_| napi_error("StorageRuntime config lock poisoned"))? = Some(value);
     self.update_config(config).map_err(to_napi_error)
   }
 
@@ -318,31 +526,75 @@ impl StorageRuntime {
       return Ok(());
     }
 
-    let database_url = self.config()?.database_url;
-    let pool = PgPoolOptions::new()
-      .max_connections(5)
-      .acquire_timeout(std::time::Duration::from_secs(5))
-      .connect(&database_url)
-      .await
-      .map_err(|err| RuntimeError::database("StorageRuntime failed to connect postgres", err))?;
-
-    sqlx::query("SELECT 1")
-      .execute(&pool)
-      .await
-      .map_err(|err| RuntimeError::database("StorageRuntime postgres health check failed", err))?;
+    let config = self.config()?;
+    if is_sqlite_url(&config.database_url) {
+      return Err(RuntimeError::config(
+        "StorageRuntime requires postgres; embedded SQLite mode is only available to BackendRuntime",
+      ));
+    }
+    let pool = connect_primary(&config.database_url, &config.pool, "StorageRuntime").await?;
+    let replicas = ReplicaSet::connect(&config.replicas, &config.pool, "StorageRuntime")?;
 
-    let config = self.config()?.with_db_overrides(&pool).await?;
+    let config = config.with_db_overrides(&pool).await?;
     self.update_config(config)?;
+    *self.replicas.lock().await = replicas;
     *guard = Some(pool);
     Ok(())
   }
 
+  /// Re-read config files and, once started, `app_configs` overrides, then
+  /// swap the storage backends in place. Invalid config leaves the current
+  /// backends untouched.
+  #[napi]
+  pub async fn reload_config(&self) -> napi::Result<RuntimeConfigReloadResult> {
+    self.reload_config_inner().await.map_err(to_napi_error)
+  }
+
+  async fn reload_config_inner(&self) -> RuntimeResult<RuntimeConfigReloadResult> {
+    let pool = self.pool.lock().await.as_ref().cloned();
+    let mut config = StorageRuntimeConfig::from_config_files()?;
+    if let Some(pool) = pool.as_ref() {
+      config = config.with_db_overrides(pool).await?;
+    }
+    // What the server forwarded still takes precedence over the files, as
+    // it did before the reload.
+    let forwarded = self
+      .forwarded
+      .read()
+      .map_err(|_| RuntimeError::invalid_state("StorageRuntime config lock poisoned"))?
+      .clone();
+    if let Some(forwarded) = forwarded {
+      config = config.with_forwarded(forwarded)?;
+    }
+    let mut restart_required = false;
+    if pool.is_some() {
+      let active = self.config()?;
+      restart_required =
+        config.database_url != active.database_url || config.pool != active.pool || config.replicas != active.replicas;
+      config.database_url = active.database_url;
+      config.pool = active.pool;
+      config.replicas = active.replicas;
+    }
+
+    let mut backends = config.backends.keys().cloned().collect::<Vec<_>>();
+    backends.sort();
+    self.update_config(config)?;
+    Ok(RuntimeConfigReloadResult {
+      backends,
+      restart_required,
+    })
+  }
+
   #[napi]
   pub async fn stop(&self) -> napi::Result<()> {
     let pool = self.pool.lock().await.take();
     if let Some(pool) = pool {
       pool.close().await;
     }
+    let replicas = self.replicas.lock().await.take();
+    if let Some(replicas) = replicas {
+      replicas.close().await;
+    }
     Ok(())
   }
 
@@ -357,6 +609,15 @@ impl StorageRuntime {
     self.health_inner().await.map_err(to_napi_error)
   }
 
+  /// Render runtime metrics in the Prometheus text exposition format.
+  #[napi]
+  pub async fn render_metrics(&self) -> napi::Result<String> {
+    if let Some(pool) = self.pool.lock().await.as_ref() {
+      metrics::record_pool(metrics::STORAGE, pool);
+    }
+    Ok(metrics::render())
+  }
+
   async fn health_inner(&self) -> RuntimeResult<StorageRuntimeHealth> {
     let pool = self.pool.lock().await.as_ref().cloned();
     let database_connected = match pool.as_ref() {
@@ -375,17 +636,30 @@ impl StorageRuntime {
       provider_configured: !self.config()?.backends.is_empty(),
       provider: backend.as_ref().map(|backend| backend.provider().to_string()),
       bucket: backend.as_ref().map(|backend| backend.bucket().to_string()),
+      circuit_state: match &backend {
+        Some(StorageBackendConfig::S3(config)) => Some(circuit_state(&config.circuit_key()).to_string()),
+        _ => None,
+      },
     })
   }
 
   #[napi]
   pub async fn provider_capabilities(&self, scope: String) -> napi::Result<StorageProviderCapabilities> {
-    self
-      .backend_for_scope(&scope)
-      .map(|backend| backend.capabilities())
-      .map_err(to_napi_error)
+    let mut capabilities = self.backend_for_scope(&scope).map_err(to_napi_error)?.capabilities();
+    // Sealed objects can only be written and read through the runtime.
+    if self.seals_scope(&scope)? {
+      capabilities.presign_put = false;
+      capabilities.presign_get = false;
+      capabilities.multipart_direct = false;
+      capabilities.proxy_upload = false;
+      capabilities.server_mediated_only = true;
+    }
+    Ok(capabilities)
   }
 
+  /// Write an object. With a `quota`, a workspace blob is counted against
+  /// it: space is reserved before the write and the blob is recorded as
+  /// completed once stored.
   #[napi]
   pub async fn put_object(
     &self,
@@ -393,52 +667,116 @@ impl StorageRuntime {
     key: String,
     body: Buffer,
     metadata: Option<RuntimeObjectStoragePutOptions>,
+    quota: Option<RuntimeBlobQuota>,
   ) -> napi::Result<RuntimeObjectMetadata> {
-    match self.backend_for_scope(&_scope)? {
-      StorageBackendConfig::Fs(config) => Ok(
-        fs_put(
-          &config,
+    let backend = self.backend_for_scope(&_scope)?;
+    if let Some(quota) = &quota {
+      self
+        .reserve_object_quota(&_scope, &key, Some(body.len() as i64), quota, None)
+        .await?;
+    }
+    let stored: Result<RuntimeObjectMetadata> = async {
+      let (body, metadata) = self
+        .seal_put(
+          &_scope,
           &key,
           body.to_vec(),
           metadata.map(Into::into).unwrap_or_default(),
-        )?
-        .into(),
-      ),
-      StorageBackendConfig::Assetpack(config) => assetpack::put(
-        &config,
-        &_scope,
-        &key,
-        body.to_vec(),
-        metadata.map(Into::into).unwrap_or_default(),
-      )
+        )
+        .await?;
+      measured(backend.provider(), "put", async {
+        match &backend {
+          StorageBackendConfig::Fs(config) => fs_put(config, &key, body, metadata),
+          StorageBackendConfig::Assetpack(config) => assetpack::put(config, &_scope, &key, body, metadata).await,
+          StorageBackendConfig::S3(config) => config
+            .build_client()?
+            .put(&key, body, metadata)
+            .await
+            .map_err(Into::into),
+        }
+      })
       .await
+      .and_then(plaintext_metadata)
       .map(Into::into)
-      .map_err(napi::Error::from),
-      StorageBackendConfig::S3(config) => config
-        .build_client()?
-        .put(&key, body.to_vec(), metadata.map(Into::into).unwrap_or_default())
-        .await
-        .map(Into::into)
-        .map_err(napi::Error::from),
     }
+    .await;
+    if quota.is_some() {
+      self.settle_object_quota(&key, &stored).await?;
+    }
+    stored.map_err(napi::Error::from)
   }
 
   #[napi]
   pub async fn head_object(&self, _scope: String, key: String) -> napi::Result<Option<RuntimeObjectMetadata>> {
-    let metadata = match self.backend_for_scope(&_scope)? {
-      StorageBackendConfig::Fs(config) => fs_head(&config, &key)?,
-      StorageBackendConfig::Assetpack(config) => assetpack::head(&config, &_scope, &key).await?,
-      StorageBackendConfig::S3(config) => config.build_client()?.head(&key).await?,
-    };
-    Ok(metadata.map(Into::into))
+    let backend = self.backend_for_scope(&_scope)?;
+    let metadata = measured(backend.provider(), "head", async {
+      match &backend {
+        StorageBackendConfig::Fs(config) => fs_head(config, &key),
+        StorageBackendConfig::Assetpack(config) => assetpack::head(config, &_scope, &key).await,
+        StorageBackendConfig::S3(config) => config.build_client()?.head(&key).await.map_err(Into::into),
+      }
+    })
+    .await?;
+    Ok(metadata.map(plaintext_metadata).transpose()?.map(Into::into))
   }
 
   #[napi]
   pub async fn get_object(&self, _scope: String, key: String) -> napi::Result<Option<RuntimeObjectGetResult>> {
-    let object = match self.backend_for_scope(&_scope)? {
-      StorageBackendConfig::Fs(config) => fs_get(&config, &key)?,
-      StorageBackendConfig::Assetpack(config) => assetpack::get(&config, &_scope, &key).await?,
-      StorageBackendConfig::S3(config) => config.build_client()?.get(&key).await?,
+    let backend = self.backend_for_scope(&_scope)?;
+    let object = measured(backend.provider(), "get", async {
+      match &backend {
+        StorageBackendConfig::Fs(config) => fs_get(config, &key),
+        StorageBackendConfig::Assetpack(config) => assetpack::get(config, &_scope, &key).await,
+        StorageBackendConfig::S3(config) => config.build_client()?.get(&key).await.map_err(Into::into),
+      }
+    })
+    .await?;
+    let object = match object {
+      Some(object) => Some(self.open_object(&key, object).await?),
+      None => {
+        self.record_quarantined_read(&_scope, &key).await;
+        None
+      }
+    };
+    Ok(object.map(Into::into))
+  }
+
+  /// Read a single byte range of an object. `range` is an HTTP `Range`
+  /// header value such as `bytes=0-1023`, `bytes=1024-` or `bytes=-512`.
+  #[napi]
+  pub async fn get_object_range(
+    &self,
+    _scope: String,
+    key: String,
+    range: String,
+  ) -> napi::Result<Option<RuntimeObjectRangeResult>> {
+    let backend = self.backend_for_scope(&_scope)?;
+    let range = ByteRange::parse(&range)?;
+    let object = measured(backend.provider(), "get_range", async {
+      match &backend {
+        StorageBackendConfig::Fs(config) => fs_get_range(config, &key, range),
+        StorageBackendConfig::Assetpack(config) => Ok(
+          assetpack::get(config, &_scope, &key)
+            .await?
+            .map(|object| range_of_object(object, range)),
+        ),
+        StorageBackendConfig::S3(config) => config.build_client()?.get_range(&key, range).await.map_err(Into::into),
+      }
+    })
+    .await?;
+    // The range above addressed ciphertext; sealed objects are re-read
+    // through the chunks that cover the plaintext range.
+    let object = match object {
+      Some(object) if object.metadata.encryption.is_some() => {
+        self
+          .read_sealed_range(&backend, &_scope, &key, object.metadata, range)
+          .await?
+      }
+      Some(object) => Some(object),
+      None => {
+        self.record_quarantined_read(&_scope, &key).await;
+        None
+      }
     };
     Ok(object.map(Into::into))
   }
@@ -449,47 +787,69 @@ impl StorageRuntime {
     _scope: String,
     prefix: Option<String>,
   ) -> napi::Result<Vec<RuntimeObjectListEntry>> {
-    let entries = match self.backend_for_scope(&_scope)? {
-      StorageBackendConfig::Fs(config) => fs_list(&config, prefix)?,
-      StorageBackendConfig::Assetpack(config) => assetpack::list(&config, &_scope, prefix).await?,
-      StorageBackendConfig::S3(config) => config.build_client()?.list(prefix).await?,
-    };
+    let backend = self.backend_for_scope(&_scope)?;
+    let entries = measured(backend.provider(), "list", async {
+      match &backend {
+        StorageBackendConfig::Fs(config) => fs_list(config, prefix),
+        StorageBackendConfig::Assetpack(config) => assetpack::list(config, &_scope, prefix).await,
+        StorageBackendConfig::S3(config) => config.build_client()?.list(prefix).await.map_err(Into::into),
+      }
+    })
+    .await?;
     Ok(entries.into_iter().map(Into::into).collect())
   }
 
   #[napi]
   pub async fn delete_object(&self, _scope: String, key: String) -> napi::Result<()> {
-    match self.backend_for_scope(&_scope)? {
-      StorageBackendConfig::Fs(config) => Ok(fs_delete(&config, &key)?),
-      StorageBackendConfig::Assetpack(config) => assetpack::delete(&config, &_scope, &key)
-        .await
-        .map_err(napi::Error::from),
-      StorageBackendConfig::S3(config) => config.build_client()?.delete(&key).await.map_err(Into::into),
-    }
+    let backend = self.backend_for_scope(&_scope)?;
+    measured(backend.provider(), "delete", async {
+      match &backend {
+        StorageBackendConfig::Fs(config) => fs_delete(config, &key),
+        StorageBackendConfig::Assetpack(config) => assetpack::delete(config, &_scope, &key).await,
+        StorageBackendConfig::S3(config) => config.build_client()?.delete(&key).await.map_err(Into::into),
+      }
+    })
+    .await
+    .map_err(napi::Error::from)
   }
 
+  /// Presign a direct upload. With a `quota`, space for
+  /// `metadata.contentLength` bytes is reserved until the upload is
+  /// completed through `complete_workspace_blob_upload`.
   #[napi]
   pub async fn presign_put(
     &self,
     _scope: String,
     key: String,
     metadata: Option<RuntimeObjectStoragePutOptions>,
+    quota: Option<RuntimeBlobQuota>,
   ) -> napi::Result<Option<RuntimePresignedObjectRequest>> {
-    match self.backend_for_scope(&_scope)? {
-      StorageBackendConfig::Fs(_) | StorageBackendConfig::Assetpack(_) => Ok(None),
-      StorageBackendConfig::S3(config) => Ok(Some(
-        config
-          .build_client()?
-          .presign_put(&key, metadata.map(Into::into).unwrap_or_default())
-          .await
-          .map_err(napi::Error::from)?
-          .try_into()?,
-      )),
+    if self.seals_scope(&_scope)? {
+      return Ok(None);
     }
+    let content_length = metadata.as_ref().and_then(|metadata| metadata.content_length);
+    let request: RuntimePresignedObjectRequest = match self.backend_for_scope(&_scope)? {
+      StorageBackendConfig::Fs(_) | StorageBackendConfig::Assetpack(_) => return Ok(None),
+      StorageBackendConfig::S3(config) => config
+        .build_client()?
+        .presign_put(&key, metadata.map(Into::into).unwrap_or_default())
+        .await
+        .map_err(napi::Error::from)?
+        .try_into()?,
+    };
+    if let Some(quota) = &quota {
+      self
+        .reserve_object_quota(&_scope, &key, content_length, quota, None)
+        .await?;
+    }
+    Ok(Some(request))
   }
 
   #[napi]
   pub async fn presign_get(&self, _scope: String, key: String) -> napi::Result<Option<RuntimePresignedObjectRequest>> {
+    if self.seals_scope(&_scope)? {
+      return Ok(None);
+    }
     match self.backend_for_scope(&_scope)? {
       StorageBackendConfig::Fs(_) | StorageBackendConfig::Assetpack(_) => Ok(None),
       StorageBackendConfig::S3(config) => Ok(Some(
@@ -503,23 +863,44 @@ impl StorageRuntime {
     }
   }
 
+  /// Start a multipart upload. With a `quota`, space for
+  /// `metadata.contentLength` bytes is reserved for the upload; the upload
+  /// is aborted again when the reservation is rejected.
   #[napi]
   pub async fn create_multipart_upload(
     &self,
     _scope: String,
     key: String,
     metadata: Option<RuntimeObjectStoragePutOptions>,
+    quota: Option<RuntimeBlobQuota>,
   ) -> napi::Result<Option<RuntimeMultipartUploadInit>> {
+    // Parts uploaded directly would skip sealing; callers fall back to a
+    // streamed upload.
+    if self.seals_scope(&_scope)? {
+      return Ok(None);
+    }
+    let content_length = metadata.as_ref().and_then(|metadata| metadata.content_length);
     match self.backend_for_scope(&_scope)? {
       StorageBackendConfig::Fs(_) | StorageBackendConfig::Assetpack(_) => Ok(None),
-      StorageBackendConfig::S3(config) => Ok(
-        config
-          .build_client()?
+      StorageBackendConfig::S3(config) => {
+        let client = config.build_client()?;
+        let init = client
           .create_multipart_upload(&key, metadata.map(Into::into).unwrap_or_default())
           .await
-          .map_err(napi::Error::from)?
-          .map(Into::into),
-      ),
+          .map_err(napi::Error::from)?;
+        if let (Some(quota), Some(init)) = (&quota, &init)
+          && let Err(err) = self
+            .reserve_object_quota(&_scope, &key, content_length, quota, Some(&init.upload_id))
+            .await
+        {
+          client
+            .abort_multipart_upload(&key, &init.upload_id)
+            .await
+            .map_err(napi::Error::from)?;
+          return Err(err.into());
+        }
+        Ok(init.map(Into::into))
+      }
     }
   }
 
@@ -586,6 +967,10 @@ impl StorageRuntime {
     }
   }
 
+  /// Assemble a multipart upload. With a `quota`, the reservation is
+  /// re-checked against the assembled size, since the size given at create
+  /// time is only what the client announced; an upload that no longer fits
+  /// is deleted.
   #[napi]
   pub async fn complete_multipart_upload(
     &self,
@@ -593,15 +978,28 @@ impl StorageRuntime {
     key: String,
     upload_id: String,
     parts: Vec<RuntimeMultipartUploadPart>,
+    quota: Option<RuntimeBlobQuota>,
   ) -> napi::Result<bool> {
     match self.backend_for_scope(&_scope)? {
       StorageBackendConfig::Fs(_) | StorageBackendConfig::Assetpack(_) => Ok(false),
       StorageBackendConfig::S3(config) => {
-        config
-          .build_client()?
+        let client = config.build_client()?;
+        client
           .complete_multipart_upload(&key, &upload_id, parts.into_iter().map(Into::into).collect())
           .await
           .map_err(napi::Error::from)?;
+        if let Some(quota) = &quota {
+          let size = client
+            .head(&key)
+            .await
+            .map_err(napi::Error::from)?
+            .map(|metadata| metadata.content_length);
+          if let Err(err) = self.reserve_object_quota(&_scope, &key, size, quota, None).await {
+            client.delete(&key).await.map_err(napi::Error::from)?;
+            self.release_object_quota(&_scope, &key).await?;
+            return Err(err.into());
+          }
+        }
         Ok(true)
       }
     }
@@ -617,11 +1015,16 @@ impl StorageRuntime {
           .abort_multipart_upload(&key, &upload_id)
           .await
           .map_err(napi::Error::from)?;
+        self.release_object_quota(&_scope, &key).await?;
         Ok(true)
       }
     }
   }
 
+  /// Verify an uploaded blob and record it in `blobs`. With a `quota`, a
+  /// blob that does not fit is deleted and reported as
+  /// `blob_limit_exceeded` or `storage_quota_exceeded`. Image derivatives
+  /// are left to `generate_blob_derivatives` so completion stays fast.
   #[napi]
   pub async fn complete_workspace_blob_upload(
     &self,
@@ -629,18 +1032,20 @@ impl StorageRuntime {
     key: String,
     expected_size: i64,
     expected_mime: String,
+    quota: Option<RuntimeBlobQuota>,
   ) -> napi::Result<RuntimeBlobCompleteResult> {
+    let quota = quota.as_ref();
     match self.backend_for_scope("blob").map_err(napi::Error::from)? {
       StorageBackendConfig::Fs(config) => self
-        .complete_fs_workspace_blob(config, workspace_id, key, expected_size, expected_mime)
+        .complete_fs_workspace_blob(config, workspace_id, key, expected_size, expected_mime, quota)
         .await
         .map_err(napi::Error::from),
       StorageBackendConfig::Assetpack(config) => self
-        .complete_assetpack_workspace_blob(config, workspace_id, key, expected_size, expected_mime)
+        .complete_assetpack_workspace_blob(config, workspace_id, key, expected_size, expected_mime, quota)
         .await
         .map_err(napi::Error::from),
       StorageBackendConfig::S3(_) => self
-        .complete_s3_workspace_blob(workspace_id, key, expected_size, expected_mime)
+        .complete_s3_workspace_blob(workspace_id, key, expected_size, expected_mime, quota)
         .await
         .map_err(napi::Error::from),
     }
@@ -663,31 +1068,42 @@ impl StorageRuntime {
   }
 
   fn backend_for_scope(&self, scope: &str) -> Result<StorageBackendConfig> {
-    self
-      .config()?
+    // Resolve against a single snapshot so a concurrent reload cannot mix
+    // backends from two configs.
+    let config = self.config()?;
+    config
       .backends
       .get(scope)
+      .or_else(|| config.backends.get("blob"))
       .cloned()
-      .or_else(|| self.config().ok()?.backends.get("blob").cloned())
       .ok_or_else(|| RuntimeError::config(format!("StorageRuntime provider is not configured for scope {scope}")))
   }
 
   pub(crate) async fn object_storage_delete_object(&self, key: &str) -> Result<()> {
-    match self.backend_for_scope("blob")? {
-      StorageBackendConfig::Fs(config) => fs_delete(&config, key),
-      StorageBackendConfig::Assetpack(config) => assetpack::delete(&config, "blob", key).await,
-      StorageBackendConfig::S3(config) => config.build_client()?.delete(key).await.map_err(Into::into),
-    }
+    let backend = self.backend_for_scope("blob")?;
+    measured(backend.provider(), "delete", async {
+      match &backend {
+        StorageBackendConfig::Fs(config) => fs_delete(config, key),
+        StorageBackendConfig::Assetpack(config) => assetpack::delete(config, "blob", key).await,
+        StorageBackendConfig::S3(config) => config.build_client()?.delete(key).await.map_err(Into::into),
+      }
+    })
+    .await
   }
 
   pub(crate) async fn object_storage_abort_upload(&self, key: &str, upload_id: &str) -> Result<()> {
     match self.backend_for_scope("blob")? {
       StorageBackendConfig::Fs(_) | StorageBackendConfig::Assetpack(_) => Ok(()),
-      StorageBackendConfig::S3(config) => config
-        .build_client()?
-        .abort_multipart_upload(key, upload_id)
+      StorageBackendConfig::S3(config) => {
+        measured(&config.provider, "abort_multipart_upload", async {
+          config
+            .build_client()?
+            .abort_multipart_upload(key, upload_id)
+            .await
+            .map_err(Into::into)
+        })
         .await
-        .map_err(Into::into),
+      }
     }
   }
 
@@ -698,58 +1114,81 @@ impl StorageRuntime {
     start_after: Option<String>,
     max_keys: i32,
   ) -> Result<object_storage::types::ObjectListPage> {
-    match self.backend_for_scope("blob")? {
-      StorageBackendConfig::Fs(config) => {
-        let mut entries = fs_list(&config, prefix)?;
-        if let Some(start_after) = start_after {
-          entries.retain(|entry| entry.key > start_after);
-        }
-        if continuation_token.is_some() {
-          return Err(RuntimeError::invalid_input(
-            "StorageRuntime fs list continuation token is not supported",
-          ));
-        }
-        let max_keys = usize::try_from(max_keys)
-          .map_err(|_| RuntimeError::invalid_input("StorageRuntime list maxKeys must be positive"))?;
-        entries.truncate(max_keys);
-        Ok(object_storage::types::ObjectListPage {
-          entries,
-          next_continuation_token: None,
-        })
-      }
-      StorageBackendConfig::Assetpack(config) => {
-        let mut entries = assetpack::list(&config, "blob", prefix).await?;
-        if let Some(start_after) = start_after {
-          entries.retain(|entry| entry.key > start_after);
-        }
-        if continuation_token.is_some() {
-          return Err(RuntimeError::invalid_input(
-            "StorageRuntime assetpack list continuation token is not supported",
-          ));
-        }
-        let max_keys = usize::try_from(max_keys)
-          .map_err(|_| RuntimeError::invalid_input("StorageRuntime list maxKeys must be positive"))?;
-        entries.truncate(max_keys);
-        Ok(object_storage::types::ObjectListPage {
-          entries,
-          next_continuation_token: None,
-        })
-      }
-      StorageBackendConfig::S3(config) => config
-        .build_client()?
-        .list_page(prefix, continuation_token, start_after, max_keys)
-        .await
-        .map_err(Into::into),
-    }
+    let backend = self.backend_for_scope("blob")?;
+    measured(
+      backend.provider(),
+      "list",
+      backend.list_page("blob", prefix, continuation_token, start_after, max_keys),
+    )
+    .await
   }
 
   pub(crate) async fn object_storage_head(&self, key: String) -> Result<Option<RuntimeObjectMetadata>> {
-    let metadata = match self.backend_for_scope("blob")? {
-      StorageBackendConfig::Fs(config) => fs_head(&config, &key)?,
-      StorageBackendConfig::Assetpack(config) => assetpack::head(&config, "blob", &key).await?,
-      StorageBackendConfig::S3(config) => config.build_client()?.head(&key).await?,
-    };
-    Ok(metadata.map(Into::into))
+    let metadata = self.stored_object_head(&key).await?;
+    Ok(metadata.map(plaintext_metadata).transpose()?.map(Into::into))
+  }
+
+  pub(crate) async fn object_storage_get(&self, key: &str) -> Result<Option<ObjectGetResult>> {
+    match self.stored_object_get(key).await? {
+      Some(object) => self.open_object(key, object).await.map(Some),
+      None => Ok(None),
+    }
+  }
+
+  pub(crate) async fn object_storage_put(
+    &self,
+    key: &str,
+    body: Vec<u8>,
+    metadata: ObjectPutMetadata,
+  ) -> Result<ObjectMetadata> {
+    let (body, metadata) = self.seal_put("blob", key, body, metadata).await?;
+    plaintext_metadata(self.stored_object_put(key, body, metadata).await?)
+  }
+
+  /// Head of the object as stored; sealed objects report ciphertext metadata.
+  async fn stored_object_head(&self, key: &str) -> Result<Option<ObjectMetadata>> {
+    let backend = self.backend_for_scope("blob")?;
+    measured(backend.provider(), "head", backend.head("blob", key)).await
+  }
+
+  /// Copy a stored object to another key of the blob backend without
+  /// opening it. Returns `None` when `key` does not exist.
+  async fn stored_object_copy(&self, key: &str, target_key: &str) -> Result<Option<ObjectMetadata>> {
+    let backend = self.backend_for_scope("blob")?;
+    measured(
+      backend.provider(),
+      "copy",
+      object_stream::copy_object(&backend, &backend, "blob", key, target_key),
+    )
+    .await
+  }
+
+  async fn stored_object_get(&self, key: &str) -> Result<Option<ObjectGetResult>> {
+    let backend = self.backend_for_scope("blob")?;
+    measured(backend.provider(), "get", async {
+      match &backend {
+        StorageBackendConfig::Fs(config) => fs_get(config, key),
+        StorageBackendConfig::Assetpack(config) => assetpack::get(config, "blob", key).await,
+        StorageBackendConfig::S3(config) => config.build_client()?.get(key).await.map_err(Into::into),
+      }
+    })
+    .await
+  }
+
+  async fn stored_object_put(&self, key: &str, body: Vec<u8>, metadata: ObjectPutMetadata) -> Result<ObjectMetadata> {
+    let backend = self.backend_for_scope("blob")?;
+    measured(backend.provider(), "put", async {
+      match &backend {
+        StorageBackendConfig::Fs(config) => fs_put(config, key, body, metadata),
+        StorageBackendConfig::Assetpack(config) => assetpack::put(config, "blob", key, body, metadata).await,
+        StorageBackendConfig::S3(config) => config
+          .build_client()?
+          .put(key, body, metadata)
+          .await
+          .map_err(Into::into),
+      }
+    })
+    .await
   }
 
   pub(crate) async fn object_storage_delete(&self, key: String) -> Result<()> {
@@ -763,6 +1202,7 @@ impl StorageRuntime {
     key: String,
     expected_size: i64,
     expected_mime: String,
+    quota: Option<&RuntimeBlobQuota>,
   ) -> Result<RuntimeBlobCompleteResult> {
     if !(0..=MAX_BLOB_SIZE).contains(&expected_size) {
       return Ok(blob_complete_failure("size_too_large"));
@@ -770,7 +1210,7 @@ impl StorageRuntime {
 
     let storage_key = format!("{workspace_id}/{key}");
     let object = match fs_get(&config, &storage_key)? {
-      Some(object) => object,
+      Some(object) => self.open_object(&storage_key, object).await?,
       None => return Ok(blob_complete_failure("not_found")),
     };
     let metadata = object.metadata;
@@ -790,19 +1230,9 @@ impl StorageRuntime {
       return Ok(blob_complete_failure("checksum_mismatch"));
     }
 
-    upsert_completed_blob(
-      &self.pool().await?,
-      &workspace_id,
-      &key,
-      &metadata.content_type,
-      metadata.content_length,
-    )
-    .await?;
-    Ok(blob_complete_success(
-      metadata.content_type,
-      metadata.content_length,
-      metadata.last_modified_ms,
-    ))
+    self
+      .record_completed_workspace_blob(&workspace_id, &key, metadata, quota)
+      .await
   }
 
   async fn complete_assetpack_workspace_blob(
@@ -812,6 +1242,7 @@ impl StorageRuntime {
     key: String,
     expected_size: i64,
     expected_mime: String,
+    quota: Option<&RuntimeBlobQuota>,
   ) -> Result<RuntimeBlobCompleteResult> {
     if !(0..=MAX_BLOB_SIZE).contains(&expected_size) {
       return Ok(blob_complete_failure("size_too_large"));
@@ -819,7 +1250,7 @@ impl StorageRuntime {
 
     let storage_key = format!("{workspace_id}/{key}");
     let object = match assetpack::get(&config, "blob", &storage_key).await? {
-      Some(object) => object,
+      Some(object) => self.open_object(&storage_key, object).await?,
       None => return Ok(blob_complete_failure("not_found")),
     };
     let metadata = object.metadata;
@@ -839,19 +1270,9 @@ impl StorageRuntime {
       return Ok(blob_complete_failure("checksum_mismatch"));
     }
 
-    upsert_completed_blob(
-      &self.pool().await?,
-      &workspace_id,
-      &key,
-      &metadata.content_type,
-      metadata.content_length,
-    )
-    .await?;
-    Ok(blob_complete_success(
-      metadata.content_type,
-      metadata.content_length,
-      metadata.last_modified_ms,
-    ))
+    self
+      .record_completed_workspace_blob(&workspace_id, &key, metadata, quota)
+      .await
   }
 
   async fn complete_s3_workspace_blob(
@@ -860,6 +1281,7 @@ impl StorageRuntime {
     key: String,
     expected_size: i64,
     expected_mime: String,
+    quota: Option<&RuntimeBlobQuota>,
   ) -> Result<RuntimeBlobCompleteResult> {
     if !(0..=MAX_BLOB_SIZE).contains(&expected_size) {
       return Ok(blob_complete_failure("size_too_large"));
@@ -872,7 +1294,7 @@ impl StorageRuntime {
     };
     let client = config.build_client()?;
     let object = match client.get(&object_key).await.map_err(RuntimeError::from) {
-      Ok(Some(object)) => object,
+      Ok(Some(object)) => self.open_object(&object_key, object).await?,
       Ok(None) => return Ok(blob_complete_failure("not_found")),
       Err(err) if err.is_object_missing() => return Ok(blob_complete_failure("not_found")),
       Err(err) => return Err(err),
@@ -902,14 +1324,32 @@ impl StorageRuntime {
       return Ok(blob_complete_failure("checksum_mismatch"));
     }
 
-    upsert_completed_blob(
-      &self.pool().await?,
-      &workspace_id,
-      &key,
+    self
+      .record_completed_workspace_blob(&workspace_id, &key, metadata, quota)
+      .await
+  }
+
+  async fn record_completed_workspace_blob(
+    &self,
+    workspace_id: &str,
+    key: &str,
+    metadata: ObjectMetadata,
+    quota: Option<&RuntimeBlobQuota>,
+  ) -> Result<RuntimeBlobCompleteResult> {
+    let pool = self.pool().await?;
+    if let Some(reason) = blob_quota::commit_completed_blob(
+      &pool,
+      workspace_id,
+      key,
       &metadata.content_type,
       metadata.content_length,
+      quota,
     )
-    .await?;
+    .await?
+    {
+      blob_reclaimer::delete_object_idempotent(self, &format!("{workspace_id}/{key}")).await?;
+      return Ok(blob_complete_failure(reason));
+    }
     Ok(blob_complete_success(
       metadata.content_type,
       metadata.content_length,
@@ -926,6 +1366,21 @@ impl StorageRuntime {
       .cloned()
       .ok_or_else(|| RuntimeError::invalid_state("StorageRuntime must be started before using postgres operations"))
   }
+
+  /// Pool for read-only scans that tolerate replica lag. Falls back to the
+  /// primary when no replica is configured or none is fresh enough.
+  async fn read_pool(&self) -> Result<PgPool> {
+    let primary = self.pool().await?;
+    let replicas = self.replicas.lock().await.clone();
+    Ok(match replicas {
+      Some(replicas) => replicas.read_pool(&primary, metrics::STORAGE).await,
+      None => primary,
+    })
+  }
+
+  fn doc_master_keys(&self) -> Result<DocMasterKeys> {
+    DocMasterKeys::from_config(&self.config()?.doc_encryption)
+  }
 }
 
 fn database_url_from_env() -> Option<String> {
@@ -943,8 +1398,15 @@ fn app_config_from_config_files() -> Result<AppConfigFile> {
       continue;
     }
     let raw = fs::read_to_string(&path).map_err(|err| RuntimeError::io("failed to read config file", err))?;
-    let config: AppConfigFile =
+    let value: serde_json::Value =
       serde_json::from_str(&raw).map_err(|err| RuntimeError::json("failed to parse config file", err))?;
+    ensure_valid_app_config(
+      &value,
+      STORAGE_SECTIONS,
+      &format!("invalid config file {}", path.display()),
+    )?;
+    let config: AppConfigFile =
+      serde_json::from_value(value).map_err(|err| RuntimeError::json("failed to parse config file", err))?;
     merged.apply_file_config(config);
   }
 
@@ -983,8 +1445,9 @@ where
     }
   }
 
-  serde_json::from_value(serde_json::Value::Object(root))
-    .map_err(|err| RuntimeError::json("invalid app config overrides", err))
+  let root = serde_json::Value::Object(root);
+  ensure_valid_app_config(&root, STORAGE_SECTIONS, "invalid app config overrides")?;
+  serde_json::from_value(root).map_err(|err| RuntimeError::json("invalid app config overrides", err))
 }
 
 fn config_json_paths() -> Vec<PathBuf> {
@@ -1091,19 +1554,27 @@ fn fs_put(config: &FsStorageConfig, key: &str, body: Vec<u8>, metadata: ObjectPu
   }
   fs::write(&path, &body).map_err(|err| RuntimeError::io("StorageRuntime fs write object failed", err))?;
   let object_metadata = metadata.into_object_metadata(system_time_ms(SystemTime::now())?);
-  let metadata_json = serde_json::json!({
-    "contentType": &object_metadata.content_type,
-    "contentLength": object_metadata.content_length,
-    "lastModified": object_metadata.last_modified_ms,
-    "checksumCRC32": &object_metadata.checksum_crc32,
+  write_fs_metadata(&path, &object_metadata)?;
+  Ok(object_metadata)
+}
+
+fn write_fs_metadata(path: &Path, metadata: &ObjectMetadata) -> Result<()> {
+  let mut metadata_json = serde_json::json!({
+    "contentType": &metadata.content_type,
+    "contentLength": metadata.content_length,
+    "lastModified": metadata.last_modified_ms,
+    "checksumCRC32": &metadata.checksum_crc32,
   });
+  if let Some(encryption) = &metadata.encryption {
+    metadata_json["encryptionKeyId"] = Value::from(encryption.key_id.as_str());
+    metadata_json["encryptionKeyVersion"] = Value::from(encryption.key_version);
+  }
   fs::write(
     PathBuf::from(format!("{}.metadata.json", path.display())),
     serde_json::to_vec(&metadata_json)
       .map_err(|err| RuntimeError::json("StorageRuntime fs serialize metadata failed", err))?,
   )
-  .map_err(|err| RuntimeError::io("StorageRuntime fs write metadata failed", err))?;
-  Ok(object_metadata)
+  .map_err(|err| RuntimeError::io("StorageRuntime fs write metadata failed", err))
 }
 
 fn fs_head(config: &FsStorageConfig, key: &str) -> Result<Option<ObjectMetadata>> {
@@ -1124,6 +1595,88 @@ fn fs_get(config: &FsStorageConfig, key: &str) -> Result<Option<ObjectGetResult>
   Ok(Some(ObjectGetResult { body, metadata }))
 }
 
+/// An fs object opened for reading from the start of the requested range.
+/// `file` is `None` when a range was requested that the object cannot
+/// satisfy.
+struct FsObjectReader {
+  metadata: ObjectMetadata,
+  range: Option<ResolvedRange>,
+  file: Option<fs::File>,
+  remaining: u64,
+}
+
+fn fs_open_read(config: &FsStorageConfig, key: &str, range: Option<ByteRange>) -> Result<Option<FsObjectReader>> {
+  let path = fs_object_path(config, key)?;
+  let Some(metadata) = read_fs_metadata(&path)? else {
+    return Ok(None);
+  };
+  let mut file = match fs::File::open(&path) {
+    Ok(file) => file,
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+    Err(err) => return Err(RuntimeError::io("StorageRuntime fs read object failed", err)),
+  };
+  let total = file
+    .metadata()
+    .map_err(|err| RuntimeError::io("StorageRuntime fs metadata failed", err))?
+    .len();
+  let Some(range) = range else {
+    return Ok(Some(FsObjectReader {
+      metadata,
+      range: None,
+      file: Some(file),
+      remaining: total,
+    }));
+  };
+  let Some(resolved) = range.resolve(total) else {
+    return Ok(Some(FsObjectReader {
+      metadata,
+      range: None,
+      file: None,
+      remaining: 0,
+    }));
+  };
+  file
+    .seek(SeekFrom::Start(resolved.start))
+    .map_err(|err| RuntimeError::io("StorageRuntime fs seek object failed", err))?;
+  Ok(Some(FsObjectReader {
+    metadata,
+    range: Some(resolved),
+    file: Some(file),
+    remaining: resolved.length(),
+  }))
+}
+
+fn fs_get_range(config: &FsStorageConfig, key: &str, range: ByteRange) -> Result<Option<ObjectRangeGetResult>> {
+  let Some(reader) = fs_open_read(config, key, Some(range))? else {
+    return Ok(None);
+  };
+  let mut body = Vec::new();
+  if let Some(file) = reader.file {
+    file
+      .take(reader.remaining)
+      .read_to_end(&mut body)
+      .map_err(|err| RuntimeError::io("StorageRuntime fs read object failed", err))?;
+  }
+  Ok(Some(ObjectRangeGetResult {
+    body,
+    metadata: reader.metadata,
+    range: reader.range,
+  }))
+}
+
+/// Cut a range out of a fully materialized object, for backends that cannot
+/// read partial objects.
+fn range_of_object(object: ObjectGetResult, range: ByteRange) -> ObjectRangeGetResult {
+  let resolved = range.resolve(object.body.len() as u64);
+  ObjectRangeGetResult {
+    body: resolved
+      .map(|resolved| resolved.slice(&object.body).to_vec())
+      .unwrap_or_default(),
+    metadata: object.metadata,
+    range: resolved,
+  }
+}
+
 fn fs_list(config: &FsStorageConfig, prefix: Option<String>) -> Result<Vec<ObjectListEntry>> {
   let root = fs_bucket_path(config);
   let prefix = prefix.map(|prefix| normalize_storage_prefix(&prefix)).transpose()?;
@@ -1163,6 +1716,9 @@ fn collect_fs_entries(
     let entry = entry.map_err(|err| RuntimeError::io("StorageRuntime fs list entry failed", err))?;
     let path = entry.path();
     let name = entry.file_name().to_string_lossy().to_string();
+    if dir == root && name == FS_UPLOADS_DIR {
+      continue;
+    }
     if path.is_dir() {
       if name_prefix.is_none_or(|prefix| name.starts_with(prefix)) {
         collect_fs_entries(root, &path, None, entries)?;
@@ -1218,6 +1774,10 @@ fn read_fs_metadata(path: &Path) -> Result<Option<ObjectMetadata>> {
     content_length: metadata.content_length,
     last_modified_ms: metadata.last_modified,
     checksum_crc32: metadata.checksum_crc32,
+    encryption: metadata
+      .encryption_key_id
+      .zip(metadata.encryption_key_version)
+      .map(|(key_id, key_version)| ObjectEncryption { key_id, key_version }),
   }))
 }
 
@@ -1229,35 +1789,23 @@ struct FsBlobMetadata {
   last_modified: i64,
   #[serde(rename = "checksumCRC32")]
   checksum_crc32: Option<String>,
+  encryption_key_id: Option<String>,
+  encryption_key_version: Option<i32>,
 }
 
+/// Record a completed blob and count it towards the workspace's storage
+/// usage.
 async fn upsert_completed_blob(pool: &PgPool, workspace_id: &str, key: &str, mime: &str, size: i64) -> Result<()> {
-  if !(0..=MAX_BLOB_SIZE).contains(&size) {
-    return Err(RuntimeError::invalid_input("BlobComplete size exceeds limit"));
-  }
-  let size = i32::try_from(size).map_err(|_| RuntimeError::invalid_input("BlobComplete size exceeds limit"))?;
-
-  sqlx::query(
-    r#"
-    INSERT INTO blobs (workspace_id, key, mime, size, status, upload_id)
-    VALUES ($1, $2, $3, $4, 'completed', NULL)
-    ON CONFLICT (workspace_id, key)
-    DO UPDATE SET
-      mime = EXCLUDED.mime,
-      size = EXCLUDED.size,
-      status = EXCLUDED.status,
-      upload_id = NULL
-    "#,
-  )
-  .bind(workspace_id)
-  .bind(key)
-  .bind(mime)
-  .bind(size)
-  .execute(pool)
-  .await
-  .map_err(|err| RuntimeError::database("BlobComplete upsert metadata failed", err))?;
+  blob_quota::commit_completed_blob(pool, workspace_id, key, mime, size, None)
+    .await
+    .map(drop)
+}
 
-  Ok(())
+async fn measured<T>(provider: &str, operation: &str, future: impl Future<Output = Result<T>>) -> Result<T> {
+  let started = Instant::now();
+  let result = future.await;
+  metrics::record_object_request(provider, operation, started, result.is_ok());
+  result
 }
 
 fn blob_complete_failure(reason: &str) -> RuntimeBlobCompleteResult {
@@ -1303,7 +1851,10 @@ fn system_time_ms(time: SystemTime) -> Result<i64> {
 
 #[cfg(test)]
 mod tests {
-  use super::*;
+  use super::{
+    object_storage::resilience::{CircuitBreakerConfig, RetryPolicy},
+    *,
+  };
 
   #[test]
   fn fs_key_normalization_rejects_traversal() {
@@ -1354,7 +1905,7 @@ mod tests {
 
   #[test]
   fn capabilities_enable_presign_get_for_presigned_s3_provider() {
-    let capabilities = StorageBackendConfig::S3(ObjectStorageConfig {
+    let capabilities = StorageBackendConfig::S3(Box::new(ObjectStorageConfig {
       provider: "cloudflare-r2".to_string(),
       bucket: "blob".to_string(),
       endpoint: Some("https://account.r2.cloudflarestorage.com".to_string()),
@@ -1369,7 +1920,10 @@ mod tests {
       presign_sign_content_type_for_put: Some(true),
       use_presigned_url: true,
       proxy_upload: false,
-    })
+      retry: RetryPolicy::default(),
+      circuit_breaker: CircuitBreakerConfig::default(),
+      azure: None,
+    }))
     .capabilities();
 
     assert!(capabilities.presign_put);
@@ -1380,7 +1934,7 @@ mod tests {
 
   #[test]
   fn capabilities_expose_r2_proxy_upload() {
-    let capabilities = StorageBackendConfig::S3(ObjectStorageConfig {
+    let capabilities = StorageBackendConfig::S3(Box::new(ObjectStorageConfig {
       provider: "cloudflare-r2".to_string(),
       bucket: "blob".to_string(),
       endpoint: Some("https://account.r2.cloudflarestorage.com".to_string()),
@@ -1395,7 +1949,10 @@ mod tests {
       presign_sign_content_type_for_put: Some(true),
       use_presigned_url: true,
       proxy_upload: true,
-    })
+      retry: RetryPolicy::default(),
+      circuit_breaker: CircuitBreakerConfig::default(),
+      azure: None,
+    }))
     .capabilities();
 
     assert!(capabilities.proxy_upload);
@@ -1403,6 +1960,27 @@ mod tests {
     assert!(capabilities.multipart_direct);
   }
 
+  #[test]
+  fn capabilities_disable_multipart_direct_for_azure_blob_provider() {
+    let config = ObjectStorageConfig::from_azure_blob_config(StorageProviderConfig {
+      provider: "azure-blob".to_string(),
+      bucket: "blob".to_string(),
+      config: serde_json::json!({
+        "accountName": "account",
+        "credentials": { "accountKey": "a2V5" },
+        "usePresignedURL": { "enabled": true }
+      }),
+    })
+    .unwrap()
+    .unwrap();
+    let capabilities = StorageBackendConfig::S3(Box::new(config)).capabilities();
+
+    assert!(capabilities.presign_put);
+    assert!(capabilities.presign_get);
+    assert!(!capabilities.multipart_direct);
+    assert!(!capabilities.server_mediated_only);
+  }
+
   #[test]
   fn capabilities_are_explicit_for_assetpack_provider() {
     let capabilities = StorageBackendConfig::Assetpack(FsStorageConfig {
@@ -1449,6 +2027,8 @@ mod tests {
         content_type: Some("text/plain".to_string()),
         content_length: Some(body.len() as i64),
         checksum_crc32: Some(checksum.clone()),
+        encryption: None,
+        last_modified_ms: None,
       },
     )
     .unwrap();
@@ -1566,12 +2146,60 @@ mod tests {
     StorageRuntime {
       config: RwLock::new(StorageRuntimeConfig {
         database_url: "postgresql://unused".to_string(),
+        pool: DatabasePoolConfig::default(),
+        replicas: DatabaseReplicaConfig::default(),
         backends: HashMap::new(),
+        doc_encryption: DocEncryptionConfig::default(),
+        blob_encryption: false,
+        image_derivatives: ImageDerivativeConfig::default(),
       }),
+      forwarded: RwLock::new(None),
       pool: Mutex::new(None),
+      replicas: Mutex::new(None),
     }
   }
 
+  #[test]
+  fn configure_keeps_image_derivatives_unless_forwarded() {
+    let runtime = test_storage_runtime();
+    let mut config = runtime.config().unwrap();
+    config.image_derivatives = ImageDerivativeConfig {
+      enabled: true,
+      edges: vec![128],
+    };
+    runtime.update_config(config).unwrap();
+
+    runtime.configure(r#"{"storages":{}}"#.to_string()).unwrap();
+    assert_eq!(
+      runtime.config().unwrap().image_derivatives,
+      ImageDerivativeConfig {
+        enabled: true,
+        edges: vec![128],
+      }
+    );
+
+    runtime
+      .configure(r#"{"storages":{"blob.derivatives":{"enabled":false}}}"#.to_string())
+      .unwrap();
+    assert!(!runtime.config().unwrap().image_derivatives.enabled);
+  }
+
+  #[tokio::test]
+  async fn reload_config_keeps_forwarded_storages() {
+    let runtime = test_storage_runtime();
+    runtime
+      .configure(
+        r#"{"storages":{"blob.storage":{"provider":"fs","bucket":"blobs","config":{"path":"/tmp/affine-reload"}}}}"#
+          .to_string(),
+      )
+      .unwrap();
+
+    let result = runtime.reload_config_inner().await.unwrap();
+
+    assert_eq!(result.backends, ["blob"]);
+    assert_eq!(runtime.backend_for_scope("blob").unwrap().bucket(), "blobs");
+  }
+
   #[tokio::test]
   async fn fs_workspace_blob_complete_returns_native_failure_reasons_before_db_upsert() {
     let temp = tempfile::tempdir().unwrap();
@@ -1589,6 +2217,7 @@ mod tests {
         "missing".to_string(),
         1,
         "text/plain".to_string(),
+        None,
       )
       .await
       .unwrap();
@@ -1603,6 +2232,8 @@ mod tests {
         content_type: Some("text/plain".to_string()),
         content_length: Some(4),
         checksum_crc32: None,
+        encryption: None,
+        last_modified_ms: None,
       },
     )
     .unwrap();
@@ -1613,6 +2244,7 @@ mod tests {
         "blob".to_string(),
         5,
         "text/plain".to_string(),
+        None,
       )
       .await
       .unwrap();
@@ -1626,6 +2258,7 @@ mod tests {
         "blob".to_string(),
         4,
         "image/png".to_string(),
+        None,
       )
       .await
       .unwrap();
@@ -1639,6 +2272,7 @@ mod tests {
         "not-the-sha-key".to_string(),
         4,
         "text/plain".to_string(),
+        None,
       )
       .await
       .unwrap();
@@ -1653,6 +2287,8 @@ mod tests {
         content_type: Some("text/plain".to_string()),
         content_length: Some(4),
         checksum_crc32: None,
+        encryption: None,
+        last_modified_ms: None,
       },
     )
     .unwrap();
@@ -1663,6 +2299,7 @@ mod tests {
         "not-the-sha-key".to_string(),
         4,
         "text/plain".to_string(),
+        None,
       )
       .await
       .unwrap();
@@ -1677,6 +2314,7 @@ mod tests {
         "too-large".to_string(),
         MAX_BLOB_SIZE + 1,
         "text/plain".to_string(),
+        None,
       )
       .await
       .unwrap();
@@ -1702,6 +2340,8 @@ mod tests {
           content_type: None,
           content_length: Some(10),
           checksum_crc32: None,
+          encryption: None,
+          last_modified_ms: None,
         },
       )
       .is_err()
@@ -1715,6 +2355,8 @@ mod tests {
           content_type: None,
           content_length: None,
           checksum_crc32: Some("wrong".to_string()),
+          encryption: None,
+          last_modified_ms: None,
         },
       )
       .is_err()
@@ -1742,6 +2384,8 @@ mod tests {
         content_type: Some("text/plain".to_string()),
         content_length: Some(body.len() as i64),
         checksum_crc32: Some(format!("{:x}", crc32fast::hash(&body))),
+        encryption: None,
+        last_modified_ms: None,
       },
     )
     .await?;
@@ -1771,6 +2415,8 @@ mod tests {
           content_type: None,
           content_length: None,
           checksum_crc32: None,
+          encryption: None,
+          last_modified_ms: None,
         },
       )
       .await?;
@@ -0,0 +1,999 @@
+use std::{collections::HashMap, time::SystemTime};
+
+use base64::{
+  Engine as _,
+  engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+};
+use chrono::{DateTime, FixedOffset, Utc};
+use reqwest::{
+  Method, StatusCode,
+  header::{CONTENT_LENGTH, CONTENT_TYPE, HeaderMap, LAST_MODIFIED},
+};
+use ring::hmac;
+use url::{Url, form_urlencoded};
+
+use super::{
+  super::byte_range::ByteRange,
+  client::{
+    MAX_RESPONSE_BODY_BYTES, ObjectStream, ReqwestStorageHttpClient, StorageHttpClient, StorageHttpRequest,
+    StorageHttpResponse, buffer_response, checked_part_number, ensure_success_status, ensure_success_text,
+    expires_at_ms, http_status_error, operation_error, response_header, response_header_name, xml_elements, xml_escape,
+    xml_text,
+  },
+  error::{ObjectStorageError, ObjectStorageResult},
+  resilience::{Resilience, ResilientHttpClient},
+  types::{
+    MultipartUploadInitResult, MultipartUploadPart, ObjectEncryption, ObjectGetResult, ObjectListEntry, ObjectListPage,
+    ObjectMetadata, ObjectPutMetadata, PresignedObjectRequest, completed_multipart_parts,
+  },
+};
+
+const AZURE_STORAGE_VERSION: &str = "2021-08-06";
+const AZURE_MAX_LIST_RESULTS: i32 = 5000;
+const CHECKSUM_METADATA_HEADER: &str = "x-ms-meta-checksumcrc32";
+// Azure metadata names must be valid C# identifiers, so no dashes here.
+const ENCRYPTION_KEY_ID_HEADER: &str = "x-ms-meta-affinekeyid";
+const ENCRYPTION_KEY_VERSION_HEADER: &str = "x-ms-meta-affinekeyversion";
+const LAST_MODIFIED_METADATA_HEADER: &str = "x-ms-meta-affinelastmodified";
+
+#[derive(Clone, Debug)]
+pub(crate) struct AzureBlobCredentials {
+  pub(crate) account_name: String,
+  pub(crate) account_key: Option<String>,
+  pub(crate) sas_token: Option<String>,
+}
+
+/// Azure Blob Storage client. Requests are signed with SharedKey when an
+/// account key is configured and fall back to appending the configured SAS
+/// token otherwise. Multipart uploads are staged as uncommitted blocks of a
+/// block blob and committed with a block list.
+#[derive(Clone)]
+pub(crate) struct AzureBlobClient {
+  endpoint: Url,
+  container: String,
+  account_name: String,
+  account_key: Option<Vec<u8>>,
+  sas_token: Option<String>,
+  http: ResilientHttpClient,
+  presign_expires_in_seconds: u64,
+}
+
+impl AzureBlobClient {
+  pub(crate) fn new(
+    endpoint: Url,
+    container: String,
+    credentials: AzureBlobCredentials,
+    request_timeout_ms: Option<u64>,
+    resilience: Resilience,
+    presign_expires_in_seconds: u64,
+  ) -> ObjectStorageResult<Self> {
+    let account_key = credentials
+      .account_key
+      .filter(|key| !key.is_empty())
+      .map(|key| {
+        STANDARD
+          .decode(key)
+          .map_err(|err| ObjectStorageError::Config(format!("azure-blob accountKey is not valid base64: {err}")))
+      })
+      .transpose()?;
+    let sas_token = credentials
+      .sas_token
+      .map(|token| token.trim_start_matches('?').to_string())
+      .filter(|token| !token.is_empty());
+    if account_key.is_none() && sas_token.is_none() {
+      return Err(ObjectStorageError::Config(
+        "azure-blob requires credentials.accountKey or credentials.sasToken".to_string(),
+      ));
+    }
+    if endpoint.cannot_be_a_base() {
+      return Err(ObjectStorageError::Config(
+        "azure-blob endpoint must be an absolute http(s) url".to_string(),
+      ));
+    }
+
+    Ok(Self {
+      endpoint,
+      container,
+      account_name: credentials.account_name,
+      account_key,
+      sas_token,
+      http: ResilientHttpClient::new(ReqwestStorageHttpClient::new(request_timeout_ms)?, resilience),
+      presign_expires_in_seconds,
+    })
+  }
+
+  pub(crate) async fn put(
+    &self,
+    key: &str,
+    body: Vec<u8>,
+    metadata: ObjectPutMetadata,
+  ) -> ObjectStorageResult<ObjectMetadata> {
+    let metadata = metadata.complete_for_body(&body);
+    let object_metadata = metadata.clone().into_object_metadata(
+      crate::utils::system_time_millis(SystemTime::now())
+        .map(|millis| millis as i64)
+        .map_err(|err| ObjectStorageError::InvalidInput(format!("system time before unix epoch: {err}")))?,
+    );
+    let mut headers = HashMap::from([
+      ("x-ms-blob-type".to_string(), "BlockBlob".to_string()),
+      ("content-type".to_string(), object_metadata.content_type.clone()),
+      ("content-length".to_string(), object_metadata.content_length.to_string()),
+    ]);
+    if let Some(checksum) = object_metadata.checksum_crc32.clone() {
+      headers.insert(CHECKSUM_METADATA_HEADER.to_string(), checksum);
+    }
+    insert_encryption_headers(&mut headers, object_metadata.encryption.as_ref());
+    insert_last_modified_header(&mut headers, metadata.last_modified_ms);
+
+    let context = format!("ObjectStorage put failed for {key}");
+    let response = self
+      .send(Method::PUT, Some(key), &[], headers, Some(body), &context)
+      .await?;
+    ensure_success_status(&response, &context)?;
+    Ok(object_metadata)
+  }
+
+  pub(crate) fn presign_put(
+    &self,
+    key: &str,
+    metadata: ObjectPutMetadata,
+  ) -> ObjectStorageResult<PresignedObjectRequest> {
+    let content_type = metadata
+      .content_type
+      .unwrap_or_else(|| "application/octet-stream".to_string());
+    let mut headers = HashMap::from([
+      ("x-ms-blob-type".to_string(), "BlockBlob".to_string()),
+      ("Content-Type".to_string(), content_type),
+    ]);
+    if let Some(content_length) = metadata.content_length {
+      headers.insert("Content-Length".to_string(), content_length.to_string());
+    }
+
+    Ok(PresignedObjectRequest {
+      url: self.presigned_url(key, "cw", &[])?.to_string(),
+      headers,
+      expires_at_ms: expires_at_ms(self.presign_expires_in_seconds)?,
+    })
+  }
+
+  pub(crate) fn presign_get(&self, key: &str) -> ObjectStorageResult<PresignedObjectRequest> {
+    Ok(PresignedObjectRequest {
+      url: self.presigned_url(key, "r", &[])?.to_string(),
+      headers: HashMap::new(),
+      expires_at_ms: expires_at_ms(self.presign_expires_in_seconds)?,
+    })
+  }
+
+  /// Azure has no upload session to create: the upload id only namespaces the
+  /// block ids of this upload and carries the blob properties until commit.
+  pub(crate) fn create_multipart_upload(
+    &self,
+    metadata: ObjectPutMetadata,
+  ) -> ObjectStorageResult<MultipartUploadInitResult> {
+    let session = uuid::Uuid::new_v4().simple().to_string();
+    let content_type = metadata
+      .content_type
+      .map(|content_type| URL_SAFE_NO_PAD.encode(content_type))
+      .unwrap_or_default();
+    let (key_id, key_version) = metadata
+      .encryption
+      .map(|encryption| (encryption.key_id, encryption.key_version.to_string()))
+      .unwrap_or_default();
+    let last_modified = metadata
+      .last_modified_ms
+      .map(|last_modified_ms| last_modified_ms.to_string())
+      .unwrap_or_default();
+    // Put Block List sets the blob properties, so everything it needs travels
+    // in the upload id: `{session}.{content type}.{key id}.{key version}.{last
+    // modified}`, with trailing empty fields dropped.
+    let mut fields = vec![session, content_type, key_id, key_version, last_modified];
+    while fields.len() > 1 && fields.last().is_some_and(String::is_empty) {
+      fields.pop();
+    }
+    let upload_id = fields.join(".");
+    Ok(MultipartUploadInitResult {
+      upload_id,
+      expires_at_ms: expires_at_ms(self.presign_expires_in_seconds)?,
+    })
+  }
+
+  pub(crate) fn presign_upload_part(
+    &self,
+    key: &str,
+    upload_id: &str,
+    part_number: i32,
+  ) -> ObjectStorageResult<PresignedObjectRequest> {
+    let block_id = block_id(upload_id, part_number)?;
+    Ok(PresignedObjectRequest {
+      url: self
+        .presigned_url(key, "w", &[("comp", "block"), ("blockid", &block_id)])?
+        .to_string(),
+      headers: HashMap::new(),
+      expires_at_ms: expires_at_ms(self.presign_expires_in_seconds)?,
+    })
+  }
+
+  /// Stages one block and returns its block id, which stands in for the part
+  /// etag when the upload is completed.
+  pub(crate) async fn upload_part(
+    &self,
+    key: &str,
+    upload_id: &str,
+    part_number: i32,
+    body: Vec<u8>,
+  ) -> ObjectStorageResult<String> {
+    let block_id = block_id(upload_id, part_number)?;
+    let headers = HashMap::from([("content-length".to_string(), body.len().to_string())]);
+    let context = format!("ObjectStorage upload part failed for {key}");
+    let response = self
+      .send(
+        Method::PUT,
+        Some(key),
+        &[("comp", "block"), ("blockid", &block_id)],
+        headers,
+        Some(body),
+        &context,
+      )
+      .await?;
+    ensure_success_status(&response, &context)?;
+    Ok(block_id)
+  }
+
+  pub(crate) async fn list_multipart_upload_parts(
+    &self,
+    key: &str,
+    upload_id: &str,
+  ) -> ObjectStorageResult<Vec<MultipartUploadPart>> {
+    let context = format!("ObjectStorage list multipart upload parts failed for {key}");
+    let response = self
+      .send(
+        Method::GET,
+        Some(key),
+        &[("comp", "blocklist"), ("blocklisttype", "uncommitted")],
+        HashMap::new(),
+        None,
+        &context,
+      )
+      .await?;
+    if is_blob_not_found(&response) {
+      return Ok(Vec::new());
+    }
+    let body = ensure_success_text(response, context)?;
+    Ok(uncommitted_parts(&body, upload_session(upload_id)))
+  }
+
+  /// Commits the staged blocks in part order. Block ids are derived from the
+  /// upload id, so the caller-provided etags are not needed to rebuild them.
+  pub(crate) async fn complete_multipart_upload(
+    &self,
+    key: &str,
+    upload_id: &str,
+    parts: Vec<MultipartUploadPart>,
+  ) -> ObjectStorageResult<()> {
+    let parts = completed_multipart_parts(parts);
+    let mut body = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?><BlockList>");
+    for part in &parts {
+      body.push_str("<Latest>");
+      body.push_str(&xml_escape(&block_id(upload_id, part.part_number)?));
+      body.push_str("</Latest>");
+    }
+    body.push_str("</BlockList>");
+
+    let mut headers = HashMap::from([
+      ("content-type".to_string(), "application/xml".to_string()),
+      ("content-length".to_string(), body.len().to_string()),
+    ]);
+    if let Some(content_type) = upload_content_type(upload_id) {
+      headers.insert("x-ms-blob-content-type".to_string(), content_type);
+    }
+    insert_encryption_headers(&mut headers, upload_encryption(upload_id).as_ref());
+    insert_last_modified_header(&mut headers, upload_last_modified(upload_id));
+    let context = format!("ObjectStorage complete multipart upload failed for {key}");
+    let response = self
+      .send(
+        Method::PUT,
+        Some(key),
+        &[("comp", "blocklist")],
+        headers,
+        Some(body.into_bytes()),
+        &context,
+      )
+      .await?;
+    ensure_success_status(&response, &context)
+  }
+
+  /// Uncommitted blocks are garbage collected by Azure after a week, and
+  /// there is no API to discard them early without touching the blob.
+  pub(crate) fn abort_multipart_upload(&self, upload_id: &str) -> ObjectStorageResult<()> {
+    if upload_session(upload_id).is_empty() {
+      return Err(ObjectStorageError::InvalidInput(
+        "multipart upload id must not be empty".to_string(),
+      ));
+    }
+    Ok(())
+  }
+
+  pub(crate) async fn head(&self, key: &str) -> ObjectStorageResult<Option<ObjectMetadata>> {
+    let context = format!("ObjectStorage head failed for {key}");
+    let response = self
+      .send(Method::HEAD, Some(key), &[], HashMap::new(), None, &context)
+      .await?;
+    if is_blob_not_found(&response) {
+      return Ok(None);
+    }
+    ensure_success_status(&response, &context)?;
+    Ok(Some(metadata_from_headers(&response.headers)))
+  }
+
+  pub(crate) async fn get(&self, key: &str) -> ObjectStorageResult<Option<ObjectGetResult>> {
+    let context = format!("ObjectStorage get failed for {key}");
+    let response = self
+      .send(Method::GET, Some(key), &[], HashMap::new(), None, &context)
+      .await?;
+    if is_blob_not_found(&response) {
+      return Ok(None);
+    }
+    ensure_success_status(&response, &context)?;
+    let metadata = metadata_from_headers(&response.headers);
+    Ok(Some(ObjectGetResult {
+      body: response.body,
+      metadata,
+    }))
+  }
+
+  pub(crate) async fn open_stream(
+    &self,
+    key: &str,
+    range: Option<ByteRange>,
+  ) -> ObjectStorageResult<Option<ObjectStream>> {
+    let context = format!("ObjectStorage get stream failed for {key}");
+    let mut headers = HashMap::new();
+    if let Some(range) = range {
+      headers.insert("range".to_string(), range.header_value());
+    }
+    let request = self.signed_request(Method::GET, Some(key), &[], headers, None)?;
+    let response = self
+      .http
+      .send_streaming(request)
+      .await
+      .map_err(|source| operation_error(context.clone(), source))?;
+    match response.status() {
+      StatusCode::RANGE_NOT_SATISFIABLE => Ok(self.head(key).await?.map(ObjectStream::unsatisfiable)),
+      status if status.is_success() => Ok(Some(ObjectStream::from_response(
+        response,
+        range,
+        metadata_from_headers,
+      ))),
+      _ => {
+        let response = buffer_response(response, MAX_RESPONSE_BODY_BYTES)
+          .await
+          .map_err(|source| operation_error(context.clone(), source))?;
+        if is_blob_not_found(&response) {
+          return Ok(None);
+        }
+        Err(http_status_error(&response, &context))
+      }
+    }
+  }
+
+  /// Azure has no `start-after`; it is emulated by skipping listed names
+  /// until one sorts after it, following markers past fully skipped pages.
+  pub(crate) async fn list_page(
+    &self,
+    prefix: Option<String>,
+    continuation_token: Option<String>,
+    start_after: Option<String>,
+    max_keys: i32,
+  ) -> ObjectStorageResult<ObjectListPage> {
+    if max_keys <= 0 {
+      return Err(ObjectStorageError::InvalidInput("maxKeys must be positive".to_string()));
+    }
+    let max_results = max_keys.min(AZURE_MAX_LIST_RESULTS).to_string();
+    let start_after = start_after.filter(|_| continuation_token.is_none());
+    let mut marker = continuation_token;
+    loop {
+      let mut query = vec![
+        ("restype", "container"),
+        ("comp", "list"),
+        ("maxresults", max_results.as_str()),
+      ];
+      if let Some(prefix) = prefix.as_deref() {
+        query.push(("prefix", prefix));
+      }
+      if let Some(marker) = marker.as_deref() {
+        query.push(("marker", marker));
+      }
+      let response = self
+        .send(
+          Method::GET,
+          None,
+          &query,
+          HashMap::new(),
+          None,
+          "ObjectStorage list page failed",
+        )
+        .await?;
+      let body = ensure_success_text(response, "ObjectStorage list page failed".to_string())?;
+      let mut page = parse_list_blobs(&body);
+      if let Some(start_after) = start_after.as_deref() {
+        page.entries.retain(|entry| entry.key.as_str() > start_after);
+      }
+      if !page.entries.is_empty() || page.next_continuation_token.is_none() {
+        return Ok(page);
+      }
+      marker = page.next_continuation_token;
+    }
+  }
+
+  pub(crate) async fn delete(&self, key: &str) -> ObjectStorageResult<()> {
+    let context = format!("ObjectStorage delete failed for {key}");
+    let response = self
+      .send(Method::DELETE, Some(key), &[], HashMap::new(), None, &context)
+      .await?;
+    if is_blob_not_found(&response) {
+      return Ok(());
+    }
+    ensure_success_status(&response, &context)
+  }
+
+  #[cfg(test)]
+  pub(super) async fn create_container(&self) -> ObjectStorageResult<()> {
+    let context = format!("ObjectStorage create container failed for {}", self.container);
+    let response = self
+      .send(
+        Method::PUT,
+        None,
+        &[("restype", "container")],
+        HashMap::new(),
+        None,
+        &context,
+      )
+      .await?;
+    if response.status == StatusCode::CONFLICT {
+      return Ok(());
+    }
+    ensure_success_status(&response, &context)
+  }
+
+  async fn send(
+    &self,
+    method: Method,
+    key: Option<&str>,
+    query: &[(&str, &str)],
+    headers: HashMap<String, String>,
+    body: Option<Vec<u8>>,
+    context: &str,
+  ) -> ObjectStorageResult<StorageHttpResponse> {
+    let request = self.signed_request(method, key, query, headers, body)?;
+    self
+      .http
+      .execute(request)
+      .await
+      .map_err(|source| operation_error(context, source))
+  }
+
+  fn signed_request(
+    &self,
+    method: Method,
+    key: Option<&str>,
+    query: &[(&str, &str)],
+    mut headers: HashMap<String, String>,
+    body: Option<Vec<u8>>,
+  ) -> ObjectStorageResult<StorageHttpRequest> {
+    let mut url = self.resource_url(key)?;
+    headers.insert(
+      "x-ms-date".to_string(),
+      Utc::now().format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
+    );
+    headers.insert("x-ms-version".to_string(), AZURE_STORAGE_VERSION.to_string());
+    match &self.account_key {
+      Some(account_key) => {
+        set_query(&mut url, query, None);
+        let content_length = body.as_ref().map_or(0, Vec::len);
+        let string_to_sign = shared_key_string_to_sign(
+          method.as_str(),
+          &headers,
+          content_length,
+          &self.account_name,
+          url.path(),
+          query,
+        );
+        headers.insert(
+          "authorization".to_string(),
+          format!("SharedKey {}:{}", self.account_name, sign(account_key, &string_to_sign)),
+        );
+      }
+      None => set_query(&mut url, query, self.sas_token.as_deref()),
+    }
+
+    Ok(StorageHttpRequest {
+      method,
+      url,
+      headers,
+      body,
+      max_response_body_bytes: MAX_RESPONSE_BODY_BYTES,
+    })
+  }
+
+  fn resource_url(&self, key: Option<&str>) -> ObjectStorageResult<Url> {
+    let mut url = self.endpoint.clone();
+    {
+      let mut segments = url
+        .path_segments_mut()
+        .map_err(|_| ObjectStorageError::Config("azure-blob endpoint cannot be a base url".to_string()))?;
+      segments.pop_if_empty().push(&self.container);
+      if let Some(key) = key {
+        segments.extend(key.split('/'));
+      }
+    }
+    Ok(url)
+  }
+
+  /// Builds a service SAS url for a single blob. Without an account key the
+  /// configured SAS token is handed out as is, so its own expiry applies.
+  fn presigned_url(&self, key: &str, permissions: &str, query: &[(&str, &str)]) -> ObjectStorageResult<Url> {
+    let mut url = self.resource_url(Some(key))?;
+    let Some(account_key) = &self.account_key else {
+      set_query(&mut url, query, self.sas_token.as_deref());
+      return Ok(url);
+    };
+
+    let expires_at = DateTime::from_timestamp_millis(expires_at_ms(self.presign_expires_in_seconds)?)
+      .ok_or_else(|| ObjectStorageError::InvalidInput("presign expiration overflow".to_string()))?
+      .format("%Y-%m-%dT%H:%M:%SZ")
+      .to_string();
+    let canonical_resource = format!("/blob/{}/{}/{}", self.account_name, self.container, key);
+    let signature = sign(
+      account_key,
+      &service_sas_string_to_sign(permissions, &expires_at, &canonical_resource),
+    );
+    let mut sas_query = query.to_vec();
+    sas_query.extend([
+      ("sv", AZURE_STORAGE_VERSION),
+      ("sr", "b"),
+      ("sp", permissions),
+      ("se", expires_at.as_str()),
+      ("sig", signature.as_str()),
+    ]);
+    set_query(&mut url, &sas_query, None);
+    Ok(url)
+  }
+}
+
+fn set_query(url: &mut Url, query: &[(&str, &str)], sas_token: Option<&str>) {
+  let mut parts = query
+    .iter()
+    .map(|(name, value)| format!("{}={}", encode_query_component(name), encode_query_component(value)))
+    .collect::<Vec<_>>();
+  if let Some(sas_token) = sas_token {
+    parts.push(sas_token.to_string());
+  }
+  url.set_query((!parts.is_empty()).then(|| parts.join("&")).as_deref());
+}
+
+fn encode_query_component(value: &str) -> String {
+  // form encoding turns spaces into `+`, which Azure reads as a literal plus
+  form_urlencoded::byte_serialize(value.as_bytes())
+    .collect::<String>()
+    .replace('+', "%20")
+}
+
+fn sign(key: &[u8], string_to_sign: &str) -> String {
+  let key = hmac::Key::new(hmac::HMAC_SHA256, key);
+  STANDARD.encode(hmac::sign(&key, string_to_sign.as_bytes()).as_ref())
+}
+
+fn shared_key_string_to_sign(
+  method: &str,
+  headers: &HashMap<String, String>,
+  content_length: usize,
+  account_name: &str,
+  path: &str,
+  query: &[(&str, &str)],
+) -> String {
+  let header = |name: &str| {
+    headers
+      .iter()
+      .find(|(key, _)| key.eq_ignore_ascii_case(name))
+      .map(|(_, value)| value.clone())
+      .unwrap_or_default()
+  };
+  let content_length = if content_length == 0 {
+    String::new()
+  } else {
+    content_length.to_string()
+  };
+  let mut lines = vec![
+    method.to_string(),
+    header("content-encoding"),
+    header("content-language"),
+    content_length,
+    header("content-md5"),
+    header("content-type"),
+    String::new(),
+    header("if-modified-since"),
+    header("if-match"),
+    header("if-none-match"),
+    header("if-unmodified-since"),
+    header("range"),
+  ];
+
+  let mut ms_headers = headers
+    .iter()
+    .map(|(name, value)| (name.to_ascii_lowercase(), value.trim()))
+    .filter(|(name, _)| name.starts_with("x-ms-"))
+    .collect::<Vec<_>>();
+  ms_headers.sort();
+  lines.extend(ms_headers.into_iter().map(|(name, value)| format!("{name}:{value}")));
+
+  let mut resource = format!("/{account_name}{path}");
+  let mut params = query
+    .iter()
+    .map(|(name, value)| (name.to_ascii_lowercase(), *value))
+    .collect::<Vec<_>>();
+  params.sort();
+  for (name, value) in params {
+    resource.push_str(&format!("\n{name}:{value}"));
+  }
+  lines.push(resource);
+  lines.join("\n")
+}
+
+fn service_sas_string_to_sign(permissions: &str, expires_at: &str, canonical_resource: &str) -> String {
+  [
+    permissions,
+    "",
+    expires_at,
+    canonical_resource,
+    "",
+    "",
+    "",
+    AZURE_STORAGE_VERSION,
+    "b",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+  ]
+  .join("\n")
+}
+
+fn upload_session(upload_id: &str) -> &str {
+  upload_id.split_once('.').map_or(upload_id, |(session, _)| session)
+}
+
+fn upload_content_type(upload_id: &str) -> Option<String> {
+  let encoded = upload_id.split('.').nth(1).filter(|encoded| !encoded.is_empty())?;
+  URL_SAFE_NO_PAD
+    .decode(encoded)
+    .ok()
+    .and_then(|bytes| String::from_utf8(bytes).ok())
+}
+
+fn upload_encryption(upload_id: &str) -> Option<ObjectEncryption> {
+  let mut fields = upload_id.split('.').skip(2);
+  ObjectEncryption::from_parts(
+    fields.next().map(ToString::to_string),
+    fields.next().map(ToString::to_string),
+  )
+}
+
+fn upload_last_modified(upload_id: &str) -> Option<i64> {
+  upload_id.split('.').nth(4)?.parse().ok()
+}
+
+fn insert_last_modified_header(headers: &mut HashMap<String, String>, last_modified_ms: Option<i64>) {
+  if let Some(last_modified_ms) = last_modified_ms {
+    headers.insert(LAST_MODIFIED_METADATA_HEADER.to_string(), last_modified_ms.to_string());
+  }
+}
+
+fn insert_encryption_headers(headers: &mut HashMap<String, String>, encryption: Option<&ObjectEncryption>) {
+  if let Some(encryption) = encryption {
+    headers.insert(ENCRYPTION_KEY_ID_HEADER.to_string(), encryption.key_id.clone());
+    headers.insert(
+      ENCRYPTION_KEY_VERSION_HEADER.to_string(),
+      encryption.key_version.to_string(),
+    );
+  }
+}
+
+/// Block ids of a blob must all have the same length, so the part number is
+/// zero padded to the widest value S3-style multipart allows.
+fn block_id(upload_id: &str, part_number: i32) -> ObjectStorageResult<String> {
+  let part_number = checked_part_number(part_number)?;
+  let session = upload_session(upload_id);
+  if session.is_empty() {
+    return Err(ObjectStorageError::InvalidInput(
+      "multipart upload id must not be empty".to_string(),
+    ));
+  }
+  Ok(STANDARD.encode(format!("{session}-{part_number:05}")))
+}
+
+fn uncommitted_parts(body: &str, session: &str) -> Vec<MultipartUploadPart> {
+  let prefix = format!("{session}-");
+  let mut parts = xml_elements(body, "Block")
+    .filter_map(|block| {
+      let name = xml_text(block, "Name")?;
+      let decoded = String::from_utf8(STANDARD.decode(&name).ok()?).ok()?;
+      let part_number = decoded.strip_prefix(&prefix)?.parse::<i32>().ok()?;
+      Some(MultipartUploadPart {
+        part_number,
+        etag: name,
+      })
+    })
+    .collect::<Vec<_>>();
+  parts.sort_by_key(|part| part.part_number);
+  parts
+}
+
+fn parse_list_blobs(body: &str) -> ObjectListPage {
+  let entries = xml_elements(body, "Blob")
+    .filter_map(|blob| {
+      let key = xml_text(blob, "Name")?;
+      let properties = xml_elements(blob, "Properties").next().unwrap_or_default();
+      Some(ObjectListEntry {
+        key,
+        content_length: xml_text(properties, "Content-Length")
+          .and_then(|value| value.parse::<i64>().ok())
+          .unwrap_or(0),
+        last_modified_ms: xml_text(properties, "Last-Modified")
+          .as_deref()
+          .map(parse_rfc1123_ms)
+          .unwrap_or(0),
+      })
+    })
+    .collect();
+  ObjectListPage {
+    entries,
+    next_continuation_token: xml_text(body, "NextMarker").filter(|marker| !marker.is_empty()),
+  }
+}
+
+fn is_blob_not_found(response: &StorageHttpResponse) -> bool {
+  response.status == StatusCode::NOT_FOUND
+    && (response_header_name(&response.headers, "x-ms-error-code").as_deref() == Some("BlobNotFound")
+      || String::from_utf8_lossy(&response.body).contains("<Code>BlobNotFound</Code>"))
+}
+
+fn parse_rfc1123_ms(value: &str) -> i64 {
+  DateTime::<FixedOffset>::parse_from_rfc2822(value)
+    .map(|value| value.timestamp_millis())
+    .unwrap_or(0)
+}
+
+fn metadata_from_headers(headers: &HeaderMap) -> ObjectMetadata {
+  ObjectMetadata {
+    content_type: response_header(headers, CONTENT_TYPE).unwrap_or_else(|| "application/octet-stream".to_string()),
+    content_length: response_header(headers, CONTENT_LENGTH)
+      .and_then(|value| value.parse::<i64>().ok())
+      .unwrap_or(0),
+    last_modified_ms: response_header_name(headers, LAST_MODIFIED_METADATA_HEADER)
+      .and_then(|value| value.parse::<i64>().ok())
+      .or_else(|| response_header(headers, LAST_MODIFIED).as_deref().map(parse_rfc1123_ms))
+      .unwrap_or(0),
+    checksum_crc32: response_header_name(headers, CHECKSUM_METADATA_HEADER),
+    encryption: ObjectEncryption::from_parts(
+      response_header_name(headers, ENCRYPTION_KEY_ID_HEADER),
+      response_header_name(headers, ENCRYPTION_KEY_VERSION_HEADER),
+    ),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use reqwest::header::HeaderValue;
+
+  use super::*;
+
+  fn client(account_key: Option<&str>, sas_token: Option<&str>) -> AzureBlobClient {
+    AzureBlobClient::new(
+      Url::parse("http://127.0.0.1:10000/devstoreaccount1").unwrap(),
+      "blobs".to_string(),
+      AzureBlobCredentials {
+        account_name: "devstoreaccount1".to_string(),
+        account_key: account_key.map(ToString::to_string),
+        sas_token: sas_token.map(ToString::to_string),
+      },
+      None,
+      Resilience::default(),
+      60,
+    )
+    .unwrap()
+  }
+
+  #[test]
+  fn shared_key_string_to_sign_canonicalizes_headers_and_resource() {
+    let headers = HashMap::from([
+      ("x-ms-version".to_string(), AZURE_STORAGE_VERSION.to_string()),
+      ("x-ms-date".to_string(), "Mon, 01 Jan 2024 00:00:00 GMT".to_string()),
+      ("x-ms-blob-type".to_string(), "BlockBlob".to_string()),
+      ("content-type".to_string(), "text/plain".to_string()),
+    ]);
+
+    let string_to_sign = shared_key_string_to_sign(
+      "PUT",
+      &headers,
+      5,
+      "devstoreaccount1",
+      "/devstoreaccount1/blobs/workspace/key",
+      &[("comp", "block"), ("blockid", "YWJj")],
+    );
+
+    assert_eq!(
+      string_to_sign,
+      "PUT\n\n\n5\n\ntext/plain\n\n\n\n\n\n\nx-ms-blob-type:BlockBlob\nx-ms-date:Mon, 01 Jan 2024 00:00:00 \
+       GMT\nx-ms-version:2021-08-06\n/devstoreaccount1/devstoreaccount1/blobs/workspace/key\nblockid:YWJj\ncomp:block"
+    );
+
+    let empty_body = shared_key_string_to_sign("GET", &HashMap::new(), 0, "account", "/blobs", &[]);
+    assert_eq!(empty_body, "GET\n\n\n\n\n\n\n\n\n\n\n\n/account/blobs");
+  }
+
+  #[test]
+  fn presigned_urls_use_service_sas_or_configured_token() {
+    let signed = client(Some("a2V5"), None);
+    let url = signed.presigned_url("workspace/key", "r", &[]).unwrap();
+    assert_eq!(url.path(), "/devstoreaccount1/blobs/workspace/key");
+    let query = url.query().unwrap();
+    assert!(query.contains("sv=2021-08-06"));
+    assert!(query.contains("sr=b"));
+    assert!(query.contains("sp=r"));
+    assert!(query.contains("se="));
+    assert!(query.contains("sig="));
+
+    let token = client(None, Some("?sv=2021-08-06&sig=abc"));
+    let url = token
+      .presigned_url("key", "w", &[("comp", "block"), ("blockid", "a+b=")])
+      .unwrap();
+    assert_eq!(url.query(), Some("comp=block&blockid=a%2Bb%3D&sv=2021-08-06&sig=abc"));
+  }
+
+  #[test]
+  fn client_requires_account_key_or_sas_token() {
+    let result = AzureBlobClient::new(
+      Url::parse("https://account.blob.core.windows.net").unwrap(),
+      "blobs".to_string(),
+      AzureBlobCredentials {
+        account_name: "account".to_string(),
+        account_key: None,
+        sas_token: Some(String::new()),
+      },
+      None,
+      Resilience::default(),
+      60,
+    );
+    assert!(matches!(result, Err(ObjectStorageError::Config(_))));
+  }
+
+  #[test]
+  fn block_ids_are_fixed_width_and_scoped_to_upload() {
+    let upload_id = format!("session.{}", URL_SAFE_NO_PAD.encode("text/plain"));
+    let first = block_id(&upload_id, 1).unwrap();
+    let last = block_id(&upload_id, 10_000).unwrap();
+
+    assert_eq!(first.len(), last.len());
+    assert_eq!(STANDARD.decode(&first).unwrap(), b"session-00001");
+    assert_eq!(upload_content_type(&upload_id).as_deref(), Some("text/plain"));
+    assert_eq!(upload_content_type("session"), None);
+    assert_eq!(upload_encryption(&upload_id), None);
+    assert_eq!(upload_last_modified(&upload_id), None);
+
+    let sealed_upload_id = "session..0a1b2c3d4e5f6a7b.3";
+    assert_eq!(
+      STANDARD.decode(block_id(sealed_upload_id, 1).unwrap()).unwrap(),
+      b"session-00001"
+    );
+    assert_eq!(upload_content_type(sealed_upload_id), None);
+    assert_eq!(
+      upload_encryption(sealed_upload_id),
+      Some(ObjectEncryption {
+        key_id: "0a1b2c3d4e5f6a7b".to_string(),
+        key_version: 3,
+      })
+    );
+    let copied_upload_id = "session....1445412480000";
+    assert_eq!(upload_encryption(copied_upload_id), None);
+    assert_eq!(upload_last_modified(copied_upload_id), Some(1_445_412_480_000));
+    assert!(block_id(&upload_id, 0).is_err());
+    assert!(block_id("", 1).is_err());
+  }
+
+  #[test]
+  fn uncommitted_parts_keep_blocks_of_the_upload_only() {
+    let own_2 = STANDARD.encode("session-00002");
+    let own_1 = STANDARD.encode("session-00001");
+    let other = STANDARD.encode("other-00001");
+    let body = format!(
+      "<?xml version=\"1.0\" encoding=\"utf-8\"?><BlockList><UncommittedBlocks><Block><Name>{own_2}</Name><Size>4</\
+       Size></Block><Block><Name>{other}</Name><Size>4</Size></Block><Block><Name>{own_1}</Name><Size>4</Size></\
+       Block></UncommittedBlocks></BlockList>"
+    );
+
+    let parts = uncommitted_parts(&body, "session");
+
+    assert_eq!(
+      parts,
+      vec![
+        MultipartUploadPart {
+          part_number: 1,
+          etag: own_1,
+        },
+        MultipartUploadPart {
+          part_number: 2,
+          etag: own_2,
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn list_blobs_xml_parses_entries_and_marker() {
+    let body = r#"<?xml version="1.0" encoding="utf-8"?>
+<EnumerationResults ServiceEndpoint="http://127.0.0.1:10000/devstoreaccount1/" ContainerName="blobs">
+  <Prefix>workspace/</Prefix>
+  <MaxResults>2</MaxResults>
+  <Blobs>
+    <Blob>
+      <Name>workspace/a&amp;b</Name>
+      <Properties>
+        <Last-Modified>Wed, 21 Oct 2015 07:28:00 GMT</Last-Modified>
+        <Content-Length>42</Content-Length>
+      </Properties>
+    </Blob>
+    <Blob>
+      <Name>workspace/c</Name>
+      <Properties>
+        <Content-Length>7</Content-Length>
+      </Properties>
+    </Blob>
+  </Blobs>
+  <NextMarker>marker-2</NextMarker>
+</EnumerationResults>"#;
+
+    let page = parse_list_blobs(body);
+    assert_eq!(
+      page.entries,
+      vec![
+        ObjectListEntry {
+          key: "workspace/a&b".to_string(),
+          content_length: 42,
+          last_modified_ms: 1_445_412_480_000,
+        },
+        ObjectListEntry {
+          key: "workspace/c".to_string(),
+          content_length: 7,
+          last_modified_ms: 0,
+        },
+      ]
+    );
+    assert_eq!(page.next_continuation_token.as_deref(), Some("marker-2"));
+
+    let last = parse_list_blobs("<EnumerationResults><Blobs /><NextMarker /></EnumerationResults>");
+    assert!(last.entries.is_empty());
+    assert!(last.next_continuation_token.is_none());
+  }
+
+  #[test]
+  fn metadata_reads_checksum_from_blob_metadata_header() {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+    headers.insert(CONTENT_LENGTH, HeaderValue::from_static("5"));
+    headers.insert(CHECKSUM_METADATA_HEADER, HeaderValue::from_static("3610a686"));
+
+    let metadata = metadata_from_headers(&headers);
+
+    assert_eq!(metadata.content_type, "text/plain");
+    assert_eq!(metadata.content_length, 5);
+    assert_eq!(metadata.checksum_crc32.as_deref(), Some("3610a686"));
+
+    headers.insert(LAST_MODIFIED, HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"));
+    assert_eq!(metadata_from_headers(&headers).last_modified_ms, 1_445_412_480_000);
+    headers.insert(LAST_MODIFIED_METADATA_HEADER, HeaderValue::from_static("1000"));
+    assert_eq!(metadata_from_headers(&headers).last_modified_ms, 1000);
+  }
+}
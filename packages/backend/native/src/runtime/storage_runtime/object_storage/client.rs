@@ -8,7 +8,7 @@ use std::{
 use chrono::{DateTime, FixedOffset};
 use reqwest::{
   Client as ReqwestClient, Method, StatusCode,
-  header::{CONTENT_LENGTH, CONTENT_TYPE, ETAG, HeaderMap, HeaderName, HeaderValue, LAST_MODIFIED},
+  header::{CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, HeaderMap, HeaderName, HeaderValue, LAST_MODIFIED},
 };
 use rusty_s3::{
   Bucket, Credentials,
@@ -20,45 +20,55 @@ use rusty_s3::{
 use url::Url;
 
 use super::{
+  super::byte_range::{ByteRange, ResolvedRange},
+  azure::AzureBlobClient,
   error::{ObjectStorageError, ObjectStorageResult},
+  resilience::{Resilience, ResilientHttpClient},
   types::{
-    MultipartUploadInitResult, MultipartUploadPart, ObjectGetResult, ObjectListEntry, ObjectListPage, ObjectMetadata,
-    ObjectPutMetadata, PresignedObjectRequest, completed_multipart_parts, trim_etag,
+    MultipartUploadEntry, MultipartUploadInitResult, MultipartUploadPage, MultipartUploadPart, ObjectEncryption,
+    ObjectGetResult, ObjectListEntry, ObjectListPage, ObjectMetadata, ObjectPutMetadata, ObjectRangeGetResult,
+    PresignedObjectRequest, completed_multipart_parts, trim_etag,
   },
 };
 
 const DEFAULT_REQUEST_TIMEOUT_MS: u64 = 30_000;
 const MAX_MULTIPART_PART_NUMBER: i32 = 10_000;
-const MAX_RESPONSE_BODY_BYTES: usize = i32::MAX as usize;
+const MAX_PREALLOCATED_BYTES: usize = 64 * 1024 * 1024;
+pub(super) const MAX_RESPONSE_BODY_BYTES: usize = i32::MAX as usize;
+const ENCRYPTION_KEY_ID_HEADER: &str = "x-amz-meta-affine-key-id";
+const ENCRYPTION_KEY_VERSION_HEADER: &str = "x-amz-meta-affine-key-version";
+// Providers stamp their own Last-Modified; a preserved time is kept here.
+const LAST_MODIFIED_METADATA_HEADER: &str = "x-amz-meta-affine-last-modified";
 
-type StorageHttpFuture<'a> = Pin<Box<dyn Future<Output = ObjectStorageResult<StorageHttpResponse>> + Send + 'a>>;
+pub(super) type StorageHttpFuture<'a> =
+  Pin<Box<dyn Future<Output = ObjectStorageResult<StorageHttpResponse>> + Send + 'a>>;
 
 #[derive(Clone)]
-struct StorageHttpRequest {
-  method: Method,
-  url: Url,
-  headers: HashMap<String, String>,
-  body: Option<Vec<u8>>,
-  max_response_body_bytes: usize,
+pub(super) struct StorageHttpRequest {
+  pub(super) method: Method,
+  pub(super) url: Url,
+  pub(super) headers: HashMap<String, String>,
+  pub(super) body: Option<Vec<u8>>,
+  pub(super) max_response_body_bytes: usize,
 }
 
-struct StorageHttpResponse {
-  status: StatusCode,
-  headers: HeaderMap,
-  body: Vec<u8>,
+pub(super) struct StorageHttpResponse {
+  pub(super) status: StatusCode,
+  pub(super) headers: HeaderMap,
+  pub(super) body: Vec<u8>,
 }
 
-trait StorageHttpClient: Clone + Send + Sync + 'static {
+pub(super) trait StorageHttpClient: Clone + Send + Sync + 'static {
   fn execute(&self, request: StorageHttpRequest) -> StorageHttpFuture<'_>;
 }
 
 #[derive(Clone)]
-struct ReqwestStorageHttpClient {
+pub(super) struct ReqwestStorageHttpClient {
   client: ReqwestClient,
 }
 
 impl ReqwestStorageHttpClient {
-  fn new(request_timeout_ms: Option<u64>) -> ObjectStorageResult<Self> {
+  pub(super) fn new(request_timeout_ms: Option<u64>) -> ObjectStorageResult<Self> {
     let builder = ReqwestClient::builder().timeout(Duration::from_millis(
       request_timeout_ms.unwrap_or(DEFAULT_REQUEST_TIMEOUT_MS),
     ));
@@ -66,69 +76,419 @@ impl ReqwestStorageHttpClient {
       client: builder.build().map_err(ObjectStorageError::HttpClientBuild)?,
     })
   }
+
+  /// Send the request and hand back the response before its body is read, so
+  /// object downloads can be streamed chunk by chunk.
+  pub(super) async fn send_streaming(&self, request: StorageHttpRequest) -> ObjectStorageResult<reqwest::Response> {
+    let mut builder = self.client.request(request.method, request.url);
+    for (key, value) in request.headers {
+      let name =
+        HeaderName::from_bytes(key.as_bytes()).map_err(|err| ObjectStorageError::InvalidHeader(err.to_string()))?;
+      let value = HeaderValue::from_str(&value).map_err(|err| ObjectStorageError::InvalidHeader(err.to_string()))?;
+      builder = builder.header(name, value);
+    }
+    if let Some(body) = request.body {
+      builder = builder.body(body);
+    }
+    builder.send().await.map_err(ObjectStorageError::HttpRequest)
+  }
 }
 
 impl StorageHttpClient for ReqwestStorageHttpClient {
   fn execute(&self, request: StorageHttpRequest) -> StorageHttpFuture<'_> {
     Box::pin(async move {
-      let mut builder = self.client.request(request.method, request.url);
-      for (key, value) in request.headers {
-        let name =
-          HeaderName::from_bytes(key.as_bytes()).map_err(|err| ObjectStorageError::InvalidHeader(err.to_string()))?;
-        let value = HeaderValue::from_str(&value).map_err(|err| ObjectStorageError::InvalidHeader(err.to_string()))?;
-        builder = builder.header(name, value);
-      }
-      if let Some(body) = request.body {
-        builder = builder.body(body);
-      }
-      let mut response = builder.send().await.map_err(ObjectStorageError::HttpRequest)?;
-      let status = response.status();
-      let headers = response.headers().clone();
-      if response
-        .content_length()
-        .is_some_and(|length| length > request.max_response_body_bytes as u64)
-      {
-        return Err(ObjectStorageError::BodyTooLarge {
-          limit: request.max_response_body_bytes,
+      let max_response_body_bytes = request.max_response_body_bytes;
+      let response = self.send_streaming(request).await?;
+      buffer_response(response, max_response_body_bytes).await
+    })
+  }
+}
+
+pub(super) async fn buffer_response(
+  mut response: reqwest::Response,
+  max_response_body_bytes: usize,
+) -> ObjectStorageResult<StorageHttpResponse> {
+  let status = response.status();
+  let headers = response.headers().clone();
+  if response
+    .content_length()
+    .is_some_and(|length| length > max_response_body_bytes as u64)
+  {
+    return Err(ObjectStorageError::BodyTooLarge {
+      limit: max_response_body_bytes,
+    });
+  }
+  let mut body = Vec::new();
+  while let Some(chunk) = response.chunk().await.map_err(ObjectStorageError::HttpRequest)? {
+    if body.len() + chunk.len() > max_response_body_bytes {
+      return Err(ObjectStorageError::BodyTooLarge {
+        limit: max_response_body_bytes,
+      });
+    }
+    body.extend_from_slice(&chunk);
+  }
+  Ok(StorageHttpResponse { status, headers, body })
+}
+
+/// Body of a streamed object download. When the server answered a ranged
+/// request with the whole object, the bytes outside the range are skipped
+/// here so callers always see exactly the requested range.
+pub(crate) struct ObjectBodyStream {
+  response: reqwest::Response,
+  skip: u64,
+  remaining: u64,
+}
+
+impl ObjectBodyStream {
+  pub(crate) async fn next_chunk(&mut self) -> ObjectStorageResult<Option<Vec<u8>>> {
+    while self.remaining > 0 {
+      let Some(chunk) = self.response.chunk().await.map_err(ObjectStorageError::HttpRequest)? else {
+        return Err(ObjectStorageError::TruncatedBody {
+          missing: self.remaining,
         });
+      };
+      let skipped = usize::try_from(self.skip).unwrap_or(usize::MAX).min(chunk.len());
+      self.skip -= skipped as u64;
+      let chunk = &chunk[skipped..];
+      if chunk.is_empty() {
+        continue;
       }
-      let mut body = Vec::new();
-      while let Some(chunk) = response.chunk().await.map_err(ObjectStorageError::HttpRequest)? {
-        if body.len() + chunk.len() > request.max_response_body_bytes {
-          return Err(ObjectStorageError::BodyTooLarge {
-            limit: request.max_response_body_bytes,
-          });
-        }
-        body.extend_from_slice(&chunk);
+      let taken = usize::try_from(self.remaining).unwrap_or(usize::MAX).min(chunk.len());
+      self.remaining -= taken as u64;
+      return Ok(Some(chunk[..taken].to_vec()));
+    }
+    Ok(None)
+  }
+
+  pub(crate) async fn collect(mut self) -> ObjectStorageResult<Vec<u8>> {
+    let mut body = Vec::with_capacity(usize::try_from(self.remaining).unwrap_or(0).min(MAX_PREALLOCATED_BYTES));
+    while let Some(chunk) = self.next_chunk().await? {
+      body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+  }
+}
+
+/// An object opened for streaming. `range` is the resolved requested range;
+/// when a range was requested and `body` is `None`, it was unsatisfiable.
+pub(crate) struct ObjectStream {
+  pub(crate) metadata: ObjectMetadata,
+  pub(crate) range: Option<ResolvedRange>,
+  pub(crate) body: Option<ObjectBodyStream>,
+}
+
+impl ObjectStream {
+  pub(super) fn unsatisfiable(metadata: ObjectMetadata) -> Self {
+    Self {
+      metadata,
+      range: None,
+      body: None,
+    }
+  }
+
+  /// Build a stream from a 200 or 206 response to a GET with an optional
+  /// `Range` header; `metadata_from_headers` reads the provider's headers.
+  pub(super) fn from_response(
+    response: reqwest::Response,
+    range: Option<ByteRange>,
+    metadata_from_headers: fn(&HeaderMap) -> ObjectMetadata,
+  ) -> Self {
+    let mut metadata = metadata_from_headers(response.headers());
+    let content_range = response_header(response.headers(), CONTENT_RANGE)
+      .as_deref()
+      .and_then(ResolvedRange::from_content_range);
+    let (resolved, skip) = match (response.status(), content_range, range) {
+      (StatusCode::PARTIAL_CONTENT, Some((resolved, total)), _) => {
+        metadata.content_length = i64::try_from(total).unwrap_or(i64::MAX);
+        (Some(resolved), 0)
+      }
+      (_, _, Some(range)) => {
+        let Some(resolved) = range.resolve(metadata.content_length.max(0) as u64) else {
+          return Self::unsatisfiable(metadata);
+        };
+        (Some(resolved), resolved.start)
       }
-      Ok(StorageHttpResponse { status, headers, body })
+      _ => (None, 0),
+    };
+    let remaining = match resolved {
+      Some(resolved) => resolved.length(),
+      None => metadata.content_length.max(0) as u64,
+    };
+    Self {
+      metadata,
+      range: resolved,
+      body: Some(ObjectBodyStream {
+        response,
+        skip,
+        remaining,
+      }),
+    }
+  }
+
+  pub(crate) async fn into_range_result(self) -> ObjectStorageResult<ObjectRangeGetResult> {
+    let body = match self.body {
+      Some(body) => body.collect().await?,
+      None => Vec::new(),
+    };
+    Ok(ObjectRangeGetResult {
+      body,
+      metadata: self.metadata,
+      range: self.range,
     })
   }
 }
 
+/// Client for a configured object storage provider. S3-compatible providers,
+/// including GCS through its XML interoperability API, share the SigV4
+/// client; Azure Blob Storage speaks its own REST dialect.
+#[derive(Clone)]
+pub(crate) enum ObjectStorageClient {
+  S3(S3Client),
+  Azure(AzureBlobClient),
+}
+
+impl ObjectStorageClient {
+  pub(crate) async fn put(
+    &self,
+    key: &str,
+    body: Vec<u8>,
+    metadata: ObjectPutMetadata,
+  ) -> ObjectStorageResult<ObjectMetadata> {
+    match self {
+      Self::S3(client) => client.put(key, body, metadata).await,
+      Self::Azure(client) => client.put(key, body, metadata).await,
+    }
+  }
+
+  pub(crate) async fn presign_put(
+    &self,
+    key: &str,
+    metadata: ObjectPutMetadata,
+  ) -> ObjectStorageResult<PresignedObjectRequest> {
+    match self {
+      Self::S3(client) => client.presign_put(key, metadata).await,
+      Self::Azure(client) => client.presign_put(key, metadata),
+    }
+  }
+
+  pub(crate) async fn presign_get(&self, key: &str) -> ObjectStorageResult<PresignedObjectRequest> {
+    match self {
+      Self::S3(client) => client.presign_get(key).await,
+      Self::Azure(client) => client.presign_get(key),
+    }
+  }
+
+  pub(crate) async fn create_multipart_upload(
+    &self,
+    key: &str,
+    metadata: ObjectPutMetadata,
+  ) -> ObjectStorageResult<Option<MultipartUploadInitResult>> {
+    match self {
+      Self::S3(client) => client.create_multipart_upload(key, metadata).await,
+      Self::Azure(client) => client.create_multipart_upload(metadata).map(Some),
+    }
+  }
+
+  pub(crate) async fn presign_upload_part(
+    &self,
+    key: &str,
+    upload_id: &str,
+    part_number: i32,
+  ) -> ObjectStorageResult<PresignedObjectRequest> {
+    match self {
+      Self::S3(client) => client.presign_upload_part(key, upload_id, part_number).await,
+      Self::Azure(client) => client.presign_upload_part(key, upload_id, part_number),
+    }
+  }
+
+  pub(crate) async fn upload_part(
+    &self,
+    key: &str,
+    upload_id: &str,
+    part_number: i32,
+    body: Vec<u8>,
+    content_length: Option<i64>,
+  ) -> ObjectStorageResult<Option<String>> {
+    match self {
+      Self::S3(client) => {
+        client
+          .upload_part(key, upload_id, part_number, body, content_length)
+          .await
+      }
+      Self::Azure(client) => client.upload_part(key, upload_id, part_number, body).await.map(Some),
+    }
+  }
+
+  pub(crate) async fn list_multipart_upload_parts(
+    &self,
+    key: &str,
+    upload_id: &str,
+  ) -> ObjectStorageResult<Vec<MultipartUploadPart>> {
+    match self {
+      Self::S3(client) => client.list_multipart_upload_parts(key, upload_id).await,
+      Self::Azure(client) => client.list_multipart_upload_parts(key, upload_id).await,
+    }
+  }
+
+  /// One page of in-progress multipart uploads after `key_marker` and,
+  /// within that key, after `upload_id_marker`. Azure has no listing of
+  /// uncommitted blocks; the service discards them on its own after a week.
+  pub(crate) async fn list_multipart_uploads(
+    &self,
+    prefix: Option<String>,
+    key_marker: Option<String>,
+    upload_id_marker: Option<String>,
+    max_uploads: i32,
+  ) -> ObjectStorageResult<MultipartUploadPage> {
+    match self {
+      Self::S3(client) => {
+        client
+          .list_multipart_uploads(prefix, key_marker, upload_id_marker, max_uploads)
+          .await
+      }
+      Self::Azure(_) => Ok(MultipartUploadPage {
+        uploads: Vec::new(),
+        next_key_marker: None,
+        next_upload_id_marker: None,
+      }),
+    }
+  }
+
+  /// Bytes held by the parts uploaded so far.
+  pub(crate) async fn multipart_upload_size(&self, key: &str, upload_id: &str) -> ObjectStorageResult<i64> {
+    match self {
+      Self::S3(client) => client.multipart_upload_size(key, upload_id).await,
+      Self::Azure(_) => Ok(0),
+    }
+  }
+
+  pub(crate) async fn complete_multipart_upload(
+    &self,
+    key: &str,
+    upload_id: &str,
+    parts: Vec<MultipartUploadPart>,
+  ) -> ObjectStorageResult<()> {
+    match self {
+      Self::S3(client) => client.complete_multipart_upload(key, upload_id, parts).await,
+      Self::Azure(client) => client.complete_multipart_upload(key, upload_id, parts).await,
+    }
+  }
+
+  pub(crate) async fn abort_multipart_upload(&self, key: &str, upload_id: &str) -> ObjectStorageResult<()> {
+    match self {
+      Self::S3(client) => client.abort_multipart_upload(key, upload_id).await,
+      Self::Azure(client) => client.abort_multipart_upload(upload_id),
+    }
+  }
+
+  pub(crate) async fn head(&self, key: &str) -> ObjectStorageResult<Option<ObjectMetadata>> {
+    match self {
+      Self::S3(client) => client.head(key).await,
+      Self::Azure(client) => client.head(key).await,
+    }
+  }
+
+  pub(crate) async fn get(&self, key: &str) -> ObjectStorageResult<Option<ObjectGetResult>> {
+    match self {
+      Self::S3(client) => client.get(key).await,
+      Self::Azure(client) => client.get(key).await,
+    }
+  }
+
+  /// Open an object for streaming, optionally limited to a byte range.
+  pub(crate) async fn open_stream(
+    &self,
+    key: &str,
+    range: Option<ByteRange>,
+  ) -> ObjectStorageResult<Option<ObjectStream>> {
+    match self {
+      Self::S3(client) => client.open_stream(key, range).await,
+      Self::Azure(client) => client.open_stream(key, range).await,
+    }
+  }
+
+  pub(crate) async fn get_range(
+    &self,
+    key: &str,
+    range: ByteRange,
+  ) -> ObjectStorageResult<Option<ObjectRangeGetResult>> {
+    match self.open_stream(key, Some(range)).await? {
+      Some(stream) => stream.into_range_result().await.map(Some),
+      None => Ok(None),
+    }
+  }
+
+  pub(crate) async fn list(&self, prefix: Option<String>) -> ObjectStorageResult<Vec<ObjectListEntry>> {
+    let mut entries = Vec::new();
+    let mut token = None;
+    loop {
+      let page = self.list_page(prefix.clone(), token, None, 1000).await?;
+      entries.extend(page.entries);
+      if let Some(next_token) = page.next_continuation_token {
+        token = Some(next_token);
+      } else {
+        break;
+      }
+    }
+    Ok(entries)
+  }
+
+  pub(crate) async fn list_page(
+    &self,
+    prefix: Option<String>,
+    continuation_token: Option<String>,
+    start_after: Option<String>,
+    max_keys: i32,
+  ) -> ObjectStorageResult<ObjectListPage> {
+    match self {
+      Self::S3(client) => {
+        client
+          .list_page(prefix, continuation_token, start_after, max_keys)
+          .await
+      }
+      Self::Azure(client) => {
+        client
+          .list_page(prefix, continuation_token, start_after, max_keys)
+          .await
+      }
+    }
+  }
+
+  pub(crate) async fn delete(&self, key: &str) -> ObjectStorageResult<()> {
+    match self {
+      Self::S3(client) => client.delete(key).await,
+      Self::Azure(client) => client.delete(key).await,
+    }
+  }
+}
+
 #[derive(Clone)]
-pub(crate) struct ObjectStorageClient {
+pub(crate) struct S3Client {
   bucket: Bucket,
   credentials: Credentials,
-  http: ReqwestStorageHttpClient,
+  http: ResilientHttpClient,
   presign_expires_in_seconds: u64,
   presign_sign_content_type_for_put: bool,
+  send_checksum_crc32: bool,
 }
 
-impl ObjectStorageClient {
+impl S3Client {
   pub(crate) fn new(
     bucket: Bucket,
     credentials: Credentials,
     request_timeout_ms: Option<u64>,
+    resilience: Resilience,
     presign_expires_in_seconds: u64,
     presign_sign_content_type_for_put: bool,
+    send_checksum_crc32: bool,
   ) -> ObjectStorageResult<Self> {
     Ok(Self {
       bucket,
       credentials,
-      http: ReqwestStorageHttpClient::new(request_timeout_ms)?,
+      http: ResilientHttpClient::new(ReqwestStorageHttpClient::new(request_timeout_ms)?, resilience),
       presign_expires_in_seconds,
       presign_sign_content_type_for_put,
+      send_checksum_crc32,
     })
   }
 
@@ -148,9 +508,13 @@ impl ObjectStorageClient {
       ("content-type".to_string(), object_metadata.content_type.clone()),
       ("content-length".to_string(), object_metadata.content_length.to_string()),
     ]);
-    if let Some(checksum) = object_metadata.checksum_crc32.clone() {
+    if self.send_checksum_crc32
+      && let Some(checksum) = object_metadata.checksum_crc32.clone()
+    {
       headers.insert("x-amz-checksum-crc32".to_string(), checksum);
     }
+    insert_encryption_headers(&mut headers, object_metadata.encryption.as_ref());
+    insert_last_modified_header(&mut headers, metadata.last_modified_ms);
 
     let mut action = PutObject::new(&self.bucket, Some(&self.credentials), key);
     insert_action_headers(&mut action, &headers);
@@ -215,14 +579,14 @@ impl ObjectStorageClient {
     metadata: ObjectPutMetadata,
   ) -> ObjectStorageResult<Option<MultipartUploadInitResult>> {
     let mut action = CreateMultipartUpload::new(&self.bucket, Some(&self.credentials), key);
+    let mut headers = HashMap::new();
     if let Some(content_type) = metadata.content_type {
-      action.headers_mut().insert("content-type", content_type.clone());
-      let headers = HashMap::from([("content-type".to_string(), content_type)]);
-      return self.create_multipart_upload_with_headers(key, action, headers).await;
+      headers.insert("content-type".to_string(), content_type);
     }
-    self
-      .create_multipart_upload_with_headers(key, action, HashMap::new())
-      .await
+    insert_encryption_headers(&mut headers, metadata.encryption.as_ref());
+    insert_last_modified_header(&mut headers, metadata.last_modified_ms);
+    insert_action_headers(&mut action, &headers);
+    self.create_multipart_upload_with_headers(key, action, headers).await
   }
 
   async fn create_multipart_upload_with_headers(
@@ -316,6 +680,18 @@ impl ObjectStorageClient {
     key: &str,
     upload_id: &str,
   ) -> ObjectStorageResult<Vec<MultipartUploadPart>> {
+    let parts = self.list_sized_parts(key, upload_id).await?;
+    Ok(parts.into_iter().map(|(part, _)| part).collect())
+  }
+
+  pub(crate) async fn multipart_upload_size(&self, key: &str, upload_id: &str) -> ObjectStorageResult<i64> {
+    let parts = self.list_sized_parts(key, upload_id).await?;
+    let size = parts.iter().map(|(_, size)| *size).sum::<u64>();
+    Ok(i64::try_from(size).unwrap_or(i64::MAX))
+  }
+
+  /// Uploaded parts with their sizes in bytes.
+  async fn list_sized_parts(&self, key: &str, upload_id: &str) -> ObjectStorageResult<Vec<(MultipartUploadPart, u64)>> {
     let mut parts = Vec::new();
     let mut marker = None;
     loop {
@@ -350,9 +726,14 @@ impl ObjectStorageClient {
         context: format!("ObjectStorage parse multipart parts failed for {key}"),
         source,
       })?;
-      parts.extend(parsed.parts.into_iter().map(|part| MultipartUploadPart {
-        part_number: i32::from(part.number),
-        etag: trim_etag(&part.etag),
+      parts.extend(parsed.parts.into_iter().map(|part| {
+        (
+          MultipartUploadPart {
+            part_number: i32::from(part.number),
+            etag: trim_etag(&part.etag),
+          },
+          part.size,
+        )
       }));
       let Some(next_marker) = parsed.next_part_number_marker else {
         break;
@@ -362,6 +743,36 @@ impl ObjectStorageClient {
     Ok(parts)
   }
 
+  pub(crate) async fn list_multipart_uploads(
+    &self,
+    prefix: Option<String>,
+    key_marker: Option<String>,
+    upload_id_marker: Option<String>,
+    max_uploads: i32,
+  ) -> ObjectStorageResult<MultipartUploadPage> {
+    let max_uploads = usize::try_from(max_uploads)
+      .ok()
+      .filter(|max_uploads| *max_uploads > 0)
+      .ok_or_else(|| ObjectStorageError::InvalidInput("maxUploads must be positive".to_string()))?;
+    // rusty-s3 has no ListMultipartUploads action; it is a GET of the bucket
+    // root with the `uploads` sub-resource, signed like any other read.
+    let mut action = GetObject::new(&self.bucket, Some(&self.credentials), "");
+    let query = action.query_mut();
+    query.insert("uploads", "");
+    query.insert("max-uploads", max_uploads.to_string());
+    if let Some(prefix) = prefix {
+      query.insert("prefix", prefix);
+    }
+    // S3 ignores `upload-id-marker` without `key-marker`.
+    if let Some(key_marker) = key_marker {
+      query.insert("key-marker", key_marker);
+      if let Some(upload_id_marker) = upload_id_marker {
+        query.insert("upload-id-marker", upload_id_marker);
+      }
+    }
+    list_multipart_uploads_page(&self.http, action.sign(expires_in(self.presign_expires_in_seconds))).await
+  }
+
   pub(crate) async fn complete_multipart_upload(
     &self,
     key: &str,
@@ -480,22 +891,44 @@ impl ObjectStorageClient {
     }))
   }
 
-  pub(crate) async fn list(&self, prefix: Option<String>) -> ObjectStorageResult<Vec<ObjectListEntry>> {
-    let mut entries = Vec::new();
-    let mut token = None;
-    loop {
-      let page = self.list_page(prefix.clone(), token, None, 1000).await?;
-      entries.extend(page.entries);
-      if let Some(next_token) = page.next_continuation_token {
-        token = Some(next_token);
-      } else {
-        break;
+  async fn open_stream(&self, key: &str, range: Option<ByteRange>) -> ObjectStorageResult<Option<ObjectStream>> {
+    let action = GetObject::new(&self.bucket, Some(&self.credentials), key);
+    let context = format!("ObjectStorage get stream failed for {key}");
+    let mut headers = HashMap::new();
+    if let Some(range) = range {
+      headers.insert("range".to_string(), range.header_value());
+    }
+    let response = self
+      .http
+      .send_streaming(StorageHttpRequest {
+        method: Method::GET,
+        url: action.sign(expires_in(self.presign_expires_in_seconds)),
+        headers,
+        body: None,
+        max_response_body_bytes: MAX_RESPONSE_BODY_BYTES,
+      })
+      .await
+      .map_err(|source| operation_error(context.clone(), source))?;
+    match response.status() {
+      StatusCode::RANGE_NOT_SATISFIABLE => Ok(self.head(key).await?.map(ObjectStream::unsatisfiable)),
+      status if status.is_success() => Ok(Some(ObjectStream::from_response(
+        response,
+        range,
+        metadata_from_headers,
+      ))),
+      _ => {
+        let response = buffer_response(response, MAX_RESPONSE_BODY_BYTES)
+          .await
+          .map_err(|source| operation_error(context.clone(), source))?;
+        if response.status == StatusCode::NOT_FOUND && is_not_found_body(&response.body) {
+          return Ok(None);
+        }
+        Err(http_status_error(&response, &context))
       }
     }
-    Ok(entries)
   }
 
-  pub(crate) async fn list_page(
+  async fn list_page(
     &self,
     prefix: Option<String>,
     continuation_token: Option<String>,
@@ -562,34 +995,53 @@ impl ObjectStorageClient {
   }
 }
 
+fn insert_encryption_headers(headers: &mut HashMap<String, String>, encryption: Option<&ObjectEncryption>) {
+  if let Some(encryption) = encryption {
+    headers.insert(ENCRYPTION_KEY_ID_HEADER.to_string(), encryption.key_id.clone());
+    headers.insert(
+      ENCRYPTION_KEY_VERSION_HEADER.to_string(),
+      encryption.key_version.to_string(),
+    );
+  }
+}
+
+fn insert_last_modified_header(headers: &mut HashMap<String, String>, last_modified_ms: Option<i64>) {
+  if let Some(last_modified_ms) = last_modified_ms {
+    headers.insert(LAST_MODIFIED_METADATA_HEADER.to_string(), last_modified_ms.to_string());
+  }
+}
+
 fn insert_action_headers<'a, T: S3Action<'a>>(action: &mut T, headers: &HashMap<String, String>) {
   for (key, value) in headers {
     action.headers_mut().insert(key.clone(), value.clone());
   }
 }
 
-fn operation_error(context: impl Into<String>, source: ObjectStorageError) -> ObjectStorageError {
+pub(super) fn operation_error(context: impl Into<String>, source: ObjectStorageError) -> ObjectStorageError {
   ObjectStorageError::Operation {
     context: context.into(),
     source: Box::new(source),
   }
 }
 
-fn ensure_success_text(response: StorageHttpResponse, context: String) -> ObjectStorageResult<String> {
+pub(super) fn ensure_success_text(response: StorageHttpResponse, context: String) -> ObjectStorageResult<String> {
   ensure_success_status(&response, &context)?;
   String::from_utf8(response.body).map_err(|source| ObjectStorageError::InvalidUtf8 { context, source })
 }
 
-fn ensure_success_status(response: &StorageHttpResponse, context: &str) -> ObjectStorageResult<()> {
+pub(super) fn ensure_success_status(response: &StorageHttpResponse, context: &str) -> ObjectStorageResult<()> {
   if response.status.is_success() {
     return Ok(());
   }
-  let body = String::from_utf8_lossy(&response.body);
-  Err(ObjectStorageError::HttpStatus {
+  Err(http_status_error(response, context))
+}
+
+pub(super) fn http_status_error(response: &StorageHttpResponse, context: &str) -> ObjectStorageError {
+  ObjectStorageError::HttpStatus {
     context: context.to_string(),
     status: response.status,
-    body: body.to_string(),
-  })
+    body: String::from_utf8_lossy(&response.body).to_string(),
+  }
 }
 
 fn is_not_found_body(body: &[u8]) -> bool {
@@ -605,29 +1057,37 @@ fn metadata_from_headers(headers: &HeaderMap) -> ObjectMetadata {
     content_length: response_header(headers, CONTENT_LENGTH)
       .and_then(|value| value.parse::<i64>().ok())
       .unwrap_or(0),
-    last_modified_ms: response_header(headers, LAST_MODIFIED)
-      .and_then(|value| DateTime::<FixedOffset>::parse_from_rfc2822(&value).ok())
-      .map(|value| value.timestamp_millis())
+    last_modified_ms: response_header_name(headers, LAST_MODIFIED_METADATA_HEADER)
+      .and_then(|value| value.parse::<i64>().ok())
+      .or_else(|| {
+        response_header(headers, LAST_MODIFIED)
+          .and_then(|value| DateTime::<FixedOffset>::parse_from_rfc2822(&value).ok())
+          .map(|value| value.timestamp_millis())
+      })
       .unwrap_or(0),
     checksum_crc32: response_header_name(headers, "x-amz-checksum-crc32"),
+    encryption: ObjectEncryption::from_parts(
+      response_header_name(headers, ENCRYPTION_KEY_ID_HEADER),
+      response_header_name(headers, ENCRYPTION_KEY_VERSION_HEADER),
+    ),
   }
 }
 
-fn response_header(headers: &HeaderMap, name: HeaderName) -> Option<String> {
+pub(super) fn response_header(headers: &HeaderMap, name: HeaderName) -> Option<String> {
   headers
     .get(name)
     .and_then(|value| value.to_str().ok())
     .map(ToString::to_string)
 }
 
-fn response_header_name(headers: &HeaderMap, name: &str) -> Option<String> {
+pub(super) fn response_header_name(headers: &HeaderMap, name: &str) -> Option<String> {
   headers
     .get(name)
     .and_then(|value| value.to_str().ok())
     .map(ToString::to_string)
 }
 
-fn checked_part_number(part_number: i32) -> ObjectStorageResult<u16> {
+pub(super) fn checked_part_number(part_number: i32) -> ObjectStorageResult<u16> {
   if !(1..=MAX_MULTIPART_PART_NUMBER).contains(&part_number) {
     return Err(ObjectStorageError::InvalidInput(
       "multipart part number must be between 1 and 10000".to_string(),
@@ -648,6 +1108,46 @@ fn validate_completed_parts(parts: &[MultipartUploadPart]) -> ObjectStorageResul
   Ok(())
 }
 
+async fn list_multipart_uploads_page(
+  http: &impl StorageHttpClient,
+  url: Url,
+) -> ObjectStorageResult<MultipartUploadPage> {
+  let response = http
+    .execute(StorageHttpRequest {
+      method: Method::GET,
+      url,
+      headers: HashMap::new(),
+      body: None,
+      max_response_body_bytes: MAX_RESPONSE_BODY_BYTES,
+    })
+    .await
+    .map_err(|source| operation_error("ObjectStorage list multipart uploads failed", source))?;
+  let body = ensure_success_text(response, "ObjectStorage list multipart uploads failed".to_string())?;
+  Ok(parse_list_multipart_uploads(&body))
+}
+
+/// Uploads without a readable `Initiated` are left out: their age is
+/// unknown, so a sweep must not treat them as abandoned.
+fn parse_list_multipart_uploads(body: &str) -> MultipartUploadPage {
+  let uploads = xml_elements(body, "Upload")
+    .filter_map(|upload| {
+      let initiated = DateTime::parse_from_rfc3339(&xml_text(upload, "Initiated")?).ok()?;
+      Some(MultipartUploadEntry {
+        key: xml_text(upload, "Key")?,
+        upload_id: xml_text(upload, "UploadId")?,
+        initiated_ms: initiated.timestamp_millis(),
+      })
+    })
+    .collect();
+  let truncated = xml_text(body, "IsTruncated").as_deref() == Some("true");
+  let next_marker = |name: &str| xml_text(body, name).filter(|marker| truncated && !marker.is_empty());
+  MultipartUploadPage {
+    uploads,
+    next_key_marker: next_marker("NextKeyMarker"),
+    next_upload_id_marker: next_marker("NextUploadIdMarker"),
+  }
+}
+
 fn complete_multipart_body(parts: &[MultipartUploadPart]) -> String {
   let mut body = String::from("<CompleteMultipartUpload>");
   for part in parts {
@@ -661,15 +1161,44 @@ fn complete_multipart_body(parts: &[MultipartUploadPart]) -> String {
   body
 }
 
-fn xml_escape(value: &str) -> String {
+pub(super) fn xml_escape(value: &str) -> String {
   value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
 }
 
+/// Iterates the inner text of each `<tag>...</tag>` element. Azure and S3
+/// listing responses are flat enough that this avoids pulling a schema per
+/// operation.
+pub(super) fn xml_elements<'a>(body: &'a str, tag: &str) -> impl Iterator<Item = &'a str> {
+  let open = format!("<{tag}>");
+  let close = format!("</{tag}>");
+  let mut rest = body;
+  std::iter::from_fn(move || {
+    let start = rest.find(&open)? + open.len();
+    let end = start + rest[start..].find(&close)?;
+    let inner = &rest[start..end];
+    rest = &rest[end + close.len()..];
+    Some(inner)
+  })
+}
+
+pub(super) fn xml_text(body: &str, tag: &str) -> Option<String> {
+  xml_elements(body, tag).next().map(xml_unescape)
+}
+
+fn xml_unescape(value: &str) -> String {
+  value
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&quot;", "\"")
+    .replace("&apos;", "'")
+    .replace("&amp;", "&")
+}
+
 fn expires_in(seconds: u64) -> Duration {
   Duration::from_secs(seconds)
 }
 
-fn expires_at_ms(expires_in_seconds: u64) -> ObjectStorageResult<i64> {
+pub(super) fn expires_at_ms(expires_in_seconds: u64) -> ObjectStorageResult<i64> {
   let expires_at = SystemTime::now()
     .checked_add(Duration::from_secs(expires_in_seconds))
     .ok_or_else(|| ObjectStorageError::InvalidInput("presign expiration overflow".to_string()))?;
@@ -688,7 +1217,7 @@ fn parse_rfc3339_ms(value: &str) -> i64 {
 mod tests {
   use reqwest::header::HeaderValue;
 
-  use super::*;
+  use super::{super::test_http::ScriptedHttpClient, *};
 
   #[test]
   fn metadata_from_headers_uses_s3_defaults_and_checksum() {
@@ -704,6 +1233,19 @@ mod tests {
     assert_eq!(metadata.content_length, 42);
     assert_eq!(metadata.last_modified_ms, 1_445_412_480_000);
     assert_eq!(metadata.checksum_crc32.as_deref(), Some("checksum"));
+    assert!(metadata.encryption.is_none());
+
+    headers.insert(ENCRYPTION_KEY_ID_HEADER, HeaderValue::from_static("0a1b2c3d4e5f6a7b"));
+    headers.insert(ENCRYPTION_KEY_VERSION_HEADER, HeaderValue::from_static("2"));
+    assert_eq!(
+      metadata_from_headers(&headers).encryption,
+      Some(ObjectEncryption {
+        key_id: "0a1b2c3d4e5f6a7b".to_string(),
+        key_version: 2,
+      })
+    );
+    headers.insert(LAST_MODIFIED_METADATA_HEADER, HeaderValue::from_static("1000"));
+    assert_eq!(metadata_from_headers(&headers).last_modified_ms, 1000);
 
     let defaults = metadata_from_headers(&HeaderMap::new());
     assert_eq!(defaults.content_type, "application/octet-stream");
@@ -847,4 +1389,73 @@ mod tests {
     assert_eq!(parse_rfc3339_ms("2024-01-02T03:04:05Z"), 1_704_164_645_000);
     assert_eq!(parse_rfc3339_ms("not a date"), 0);
   }
+
+  #[tokio::test]
+  async fn list_multipart_uploads_parses_uploads_and_next_markers() {
+    let http = ScriptedHttpClient::replying(
+      StatusCode::OK,
+      r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListMultipartUploadsResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <Bucket>blobs</Bucket>
+  <KeyMarker></KeyMarker>
+  <UploadIdMarker></UploadIdMarker>
+  <NextKeyMarker>ws/b&amp;c</NextKeyMarker>
+  <NextUploadIdMarker>upload-2</NextUploadIdMarker>
+  <MaxUploads>2</MaxUploads>
+  <IsTruncated>true</IsTruncated>
+  <Upload>
+    <Key>ws/a</Key>
+    <UploadId>upload-1</UploadId>
+    <Initiator><ID>user</ID><DisplayName>user</DisplayName></Initiator>
+    <StorageClass>STANDARD</StorageClass>
+    <Initiated>2024-01-02T03:04:05.000Z</Initiated>
+  </Upload>
+  <Upload>
+    <Key>ws/b&amp;c</Key>
+    <UploadId>upload-2</UploadId>
+    <Initiated>not a date</Initiated>
+  </Upload>
+</ListMultipartUploadsResult>"#,
+    );
+    let url = Url::parse("https://blobs.s3.example.com/?uploads=&max-uploads=2").unwrap();
+
+    let page = list_multipart_uploads_page(&http, url.clone()).await.unwrap();
+
+    assert_eq!(
+      page,
+      MultipartUploadPage {
+        uploads: vec![MultipartUploadEntry {
+          key: "ws/a".to_string(),
+          upload_id: "upload-1".to_string(),
+          initiated_ms: 1_704_164_645_000,
+        }],
+        next_key_marker: Some("ws/b&c".to_string()),
+        next_upload_id_marker: Some("upload-2".to_string()),
+      }
+    );
+    let requests = http.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].method, Method::GET);
+    assert_eq!(requests[0].url, url);
+    assert!(requests[0].body.is_none());
+  }
+
+  #[tokio::test]
+  async fn list_multipart_uploads_ends_on_untruncated_page_and_surfaces_errors() {
+    let http = ScriptedHttpClient::replying(
+      StatusCode::OK,
+      r#"<ListMultipartUploadsResult>
+  <NextKeyMarker>ws/a</NextKeyMarker>
+  <IsTruncated>false</IsTruncated>
+</ListMultipartUploadsResult>"#,
+    );
+    let url = Url::parse("https://blobs.s3.example.com/?uploads=").unwrap();
+    let page = list_multipart_uploads_page(&http, url.clone()).await.unwrap();
+    assert!(page.uploads.is_empty());
+    assert_eq!(page.next_key_marker, None);
+    assert_eq!(page.next_upload_id_marker, None);
+
+    let http = ScriptedHttpClient::replying(StatusCode::FORBIDDEN, "<Error><Code>AccessDenied</Code></Error>");
+    assert!(list_multipart_uploads_page(&http, url).await.is_err());
+  }
 }
@@ -3,8 +3,10 @@ use serde::Deserialize;
 use url::Url;
 
 use super::{
-  client::ObjectStorageClient,
+  azure::{AzureBlobClient, AzureBlobCredentials},
+  client::{ObjectStorageClient, S3Client},
   error::{ObjectStorageError, ObjectStorageResult},
+  resilience::{CircuitBreakerConfig, Resilience, RetryPolicy},
   types::StorageProviderConfig,
 };
 
@@ -24,6 +26,11 @@ pub(crate) struct ObjectStorageConfig {
   pub(crate) presign_sign_content_type_for_put: Option<bool>,
   pub(crate) use_presigned_url: bool,
   pub(crate) proxy_upload: bool,
+  pub(crate) retry: RetryPolicy,
+  pub(crate) circuit_breaker: CircuitBreakerConfig,
+  /// Set for `azure-blob`, which is not S3 compatible; `bucket` is the
+  /// container name and `endpoint` the account blob endpoint.
+  pub(crate) azure: Option<AzureBlobCredentials>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -38,6 +45,10 @@ struct S3ConfigFile {
   presign: Option<S3PresignConfigFile>,
   #[serde(rename = "usePresignedURL")]
   use_presigned_url: Option<UsePresignedUrlConfigFile>,
+  #[serde(default)]
+  retry: RetryPolicy,
+  #[serde(default)]
+  circuit_breaker: CircuitBreakerConfig,
 }
 
 #[derive(Debug, Deserialize)]
@@ -52,6 +63,52 @@ struct R2ConfigFile {
   presign: Option<S3PresignConfigFile>,
   #[serde(rename = "usePresignedURL")]
   use_presigned_url: Option<UsePresignedUrlConfigFile>,
+  #[serde(default)]
+  retry: RetryPolicy,
+  #[serde(default)]
+  circuit_breaker: CircuitBreakerConfig,
+}
+
+/// GCS is reached through its XML interoperability API, which accepts SigV4
+/// signatures made with HMAC keys of a service account.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GcsConfigFile {
+  endpoint: Option<String>,
+  credentials: Option<S3CredentialsConfigFile>,
+  request_timeout_ms: Option<u64>,
+  min_part_size: Option<u64>,
+  presign: Option<S3PresignConfigFile>,
+  #[serde(rename = "usePresignedURL")]
+  use_presigned_url: Option<UsePresignedUrlConfigFile>,
+  #[serde(default)]
+  retry: RetryPolicy,
+  #[serde(default)]
+  circuit_breaker: CircuitBreakerConfig,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AzureBlobConfigFile {
+  account_name: String,
+  endpoint: Option<String>,
+  credentials: Option<AzureBlobCredentialsConfigFile>,
+  request_timeout_ms: Option<u64>,
+  min_part_size: Option<u64>,
+  presign: Option<S3PresignConfigFile>,
+  #[serde(rename = "usePresignedURL")]
+  use_presigned_url: Option<UsePresignedUrlConfigFile>,
+  #[serde(default)]
+  retry: RetryPolicy,
+  #[serde(default)]
+  circuit_breaker: CircuitBreakerConfig,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct AzureBlobCredentialsConfigFile {
+  account_key: Option<String>,
+  sas_token: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Default)]
@@ -86,6 +143,8 @@ impl ObjectStorageConfig {
     match storage.provider.as_str() {
       "aws-s3" => Self::from_s3_config(storage),
       "cloudflare-r2" => Self::from_r2_config(storage),
+      "gcs" => Self::from_gcs_config(storage),
+      "azure-blob" => Self::from_azure_blob_config(storage),
       "fs" => Ok(None),
       provider => Err(ObjectStorageError::Config(format!(
         "unsupported blob storage provider for StorageRuntime: {provider}"
@@ -117,6 +176,9 @@ impl ObjectStorageConfig {
       presign_sign_content_type_for_put: config.presign.as_ref().and_then(|v| v.sign_content_type_for_put),
       use_presigned_url: config.use_presigned_url.map(|v| v.enabled).unwrap_or(false),
       proxy_upload: false,
+      retry: config.retry,
+      circuit_breaker: config.circuit_breaker,
+      azure: None,
     }))
   }
 
@@ -155,10 +217,94 @@ impl ObjectStorageConfig {
       presign_sign_content_type_for_put: config.presign.as_ref().and_then(|v| v.sign_content_type_for_put),
       use_presigned_url,
       proxy_upload,
+      retry: config.retry,
+      circuit_breaker: config.circuit_breaker,
+      azure: None,
+    }))
+  }
+
+  pub(crate) fn from_gcs_config(storage: StorageProviderConfig) -> ObjectStorageResult<Option<Self>> {
+    let config: GcsConfigFile = serde_json::from_value(storage.config)
+      .map_err(|err| ObjectStorageError::Config(format!("invalid gcs blob storage config: {err}")))?;
+    let credentials = config.credentials.unwrap_or_default();
+
+    Ok(Some(Self {
+      provider: storage.provider,
+      bucket: storage.bucket,
+      endpoint: Some(
+        config
+          .endpoint
+          .unwrap_or_else(|| "https://storage.googleapis.com".to_string()),
+      ),
+      region: Some("auto".to_string()),
+      access_key_id: credentials.access_key_id,
+      secret_access_key: credentials.secret_access_key,
+      session_token: None,
+      force_path_style: true,
+      request_timeout_ms: config.request_timeout_ms,
+      min_part_size: config.min_part_size,
+      presign_expires_in_seconds: config.presign.as_ref().and_then(|v| v.expires_in_seconds),
+      presign_sign_content_type_for_put: config.presign.as_ref().and_then(|v| v.sign_content_type_for_put),
+      use_presigned_url: config.use_presigned_url.map(|v| v.enabled).unwrap_or(false),
+      proxy_upload: false,
+      retry: config.retry,
+      circuit_breaker: config.circuit_breaker,
+      azure: None,
+    }))
+  }
+
+  pub(crate) fn from_azure_blob_config(storage: StorageProviderConfig) -> ObjectStorageResult<Option<Self>> {
+    let config: AzureBlobConfigFile = serde_json::from_value(storage.config)
+      .map_err(|err| ObjectStorageError::Config(format!("invalid azure-blob blob storage config: {err}")))?;
+    let credentials = config.credentials.unwrap_or_default();
+    let endpoint = config
+      .endpoint
+      .unwrap_or_else(|| format!("https://{}.blob.core.windows.net", config.account_name));
+
+    Ok(Some(Self {
+      provider: storage.provider,
+      bucket: storage.bucket,
+      endpoint: Some(endpoint),
+      region: None,
+      access_key_id: None,
+      secret_access_key: None,
+      session_token: None,
+      force_path_style: true,
+      request_timeout_ms: config.request_timeout_ms,
+      min_part_size: config.min_part_size,
+      presign_expires_in_seconds: config.presign.as_ref().and_then(|v| v.expires_in_seconds),
+      presign_sign_content_type_for_put: None,
+      use_presigned_url: config.use_presigned_url.map(|v| v.enabled).unwrap_or(false),
+      proxy_upload: false,
+      retry: config.retry,
+      circuit_breaker: config.circuit_breaker,
+      azure: Some(AzureBlobCredentials {
+        account_name: config.account_name,
+        account_key: credentials.account_key,
+        sas_token: credentials.sas_token,
+      }),
     }))
   }
 
   pub(crate) fn build_client(&self) -> ObjectStorageResult<ObjectStorageClient> {
+    if let Some(azure) = &self.azure {
+      let endpoint = self
+        .endpoint
+        .as_deref()
+        .ok_or_else(|| ObjectStorageError::Config("azure-blob endpoint is required".to_string()))?;
+      let endpoint = Url::parse(endpoint)
+        .map_err(|err| ObjectStorageError::Config(format!("object storage endpoint is invalid: {err}")))?;
+      return AzureBlobClient::new(
+        endpoint,
+        self.bucket.clone(),
+        azure.clone(),
+        self.request_timeout_ms,
+        self.resilience(),
+        self.presign_expires_in_seconds.unwrap_or(60),
+      )
+      .map(ObjectStorageClient::Azure);
+    }
+
     let region = self
       .region
       .clone()
@@ -191,13 +337,35 @@ impl ObjectStorageConfig {
       None => Credentials::new(access_key_id, secret_access_key),
     };
 
-    ObjectStorageClient::new(
+    S3Client::new(
       bucket,
       credentials,
       self.request_timeout_ms,
+      self.resilience(),
       self.presign_expires_in_seconds.unwrap_or(60),
       self.presign_sign_content_type_for_put.unwrap_or(true),
+      // the GCS XML API rejects the S3 flexible checksum headers
+      self.provider != "gcs",
     )
+    .map(ObjectStorageClient::S3)
+  }
+
+  fn resilience(&self) -> Resilience {
+    Resilience::for_provider(&self.provider, &self.circuit_key(), self.retry, self.circuit_breaker)
+  }
+
+  /// Requests to the same provider, endpoint and bucket share a circuit
+  /// breaker. Without an explicit endpoint the region tells AWS buckets
+  /// apart.
+  pub(crate) fn circuit_key(&self) -> String {
+    let endpoint = self.endpoint.as_deref().or(self.region.as_deref()).unwrap_or_default();
+    format!("{}/{endpoint}/{}", self.provider, self.bucket)
+  }
+
+  /// Direct multipart uploads need the part etags reported back by the
+  /// browser, and Azure's Put Block responses carry none.
+  pub(crate) fn supports_multipart_direct(&self) -> bool {
+    self.use_presigned_url && self.azure.is_none()
   }
 }
 
@@ -18,6 +18,8 @@ pub(crate) enum ObjectStorageError {
   InvalidHeader(String),
   #[error("ObjectStorage response body exceeds {limit} bytes")]
   BodyTooLarge { limit: usize },
+  #[error("ObjectStorage response body ended {missing} bytes early")]
+  TruncatedBody { missing: u64 },
   #[error("{context}: status={status} body={body}")]
   HttpStatus {
     context: String,
@@ -38,6 +40,8 @@ pub(crate) enum ObjectStorageError {
   },
   #[error("ObjectStorage invalid input: {0}")]
   InvalidInput(String),
+  #[error("ObjectStorage circuit for {0} is open after repeated failures")]
+  CircuitOpen(String),
 }
 
 impl ObjectStorageError {
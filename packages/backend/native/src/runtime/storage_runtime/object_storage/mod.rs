@@ -1,6 +1,10 @@
+pub(crate) mod azure;
 pub(crate) mod client;
 pub(crate) mod config;
 pub(crate) mod error;
+pub(crate) mod resilience;
+#[cfg(test)]
+mod test_http;
 #[cfg(test)]
 mod tests;
 pub(crate) mod types;
@@ -0,0 +1,504 @@
+use std::{
+  collections::HashMap,
+  future::Future,
+  sync::{Arc, LazyLock, Mutex, MutexGuard},
+  time::{Duration, Instant, SystemTime},
+};
+
+use chrono::DateTime;
+use rand::Rng;
+use reqwest::{
+  Method, StatusCode,
+  header::{HeaderMap, RETRY_AFTER},
+};
+use serde::Deserialize;
+
+use super::{
+  client::{ReqwestStorageHttpClient, StorageHttpClient, StorageHttpFuture, StorageHttpRequest, StorageHttpResponse},
+  error::{ObjectStorageError, ObjectStorageResult},
+};
+
+/// `retry` of a provider config. Only idempotent requests are retried, after
+/// a jittered exponential backoff or the provider's `Retry-After`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "camelCase")]
+pub(crate) struct RetryPolicy {
+  /// Attempts per request, including the first one.
+  pub(crate) max_attempts: u32,
+  pub(crate) base_delay_ms: u64,
+  pub(crate) max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+  fn default() -> Self {
+    Self {
+      max_attempts: 3,
+      base_delay_ms: 100,
+      max_delay_ms: 5_000,
+    }
+  }
+}
+
+impl RetryPolicy {
+  /// Delay before retrying after `attempt` failed attempts: full jitter over
+  /// the exponential backoff, or the provider's hint when it gave one. Both
+  /// are capped at `max_delay_ms`.
+  fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+    let cap = Duration::from_millis(self.max_delay_ms);
+    if let Some(retry_after) = retry_after {
+      return retry_after.min(cap);
+    }
+    let backoff = self
+      .base_delay_ms
+      .saturating_mul(1u64 << attempt.saturating_sub(1).min(20))
+      .min(self.max_delay_ms);
+    Duration::from_millis(rand::rng().random_range(0..=backoff))
+  }
+}
+
+/// `circuitBreaker` of a provider config. After `failure_threshold`
+/// consecutive transient failures requests fail fast for `open_ms`, then a
+/// single probe decides whether the circuit closes again.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "camelCase")]
+pub(crate) struct CircuitBreakerConfig {
+  pub(crate) failure_threshold: u32,
+  pub(crate) open_ms: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+  fn default() -> Self {
+    Self {
+      failure_threshold: 5,
+      open_ms: 30_000,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CircuitState {
+  Closed { failures: u32 },
+  Open { until: Instant },
+  HalfOpen { probe_started: Instant },
+}
+
+struct CircuitInner {
+  config: CircuitBreakerConfig,
+  state: CircuitState,
+}
+
+pub(crate) struct CircuitBreaker {
+  inner: Mutex<CircuitInner>,
+}
+
+// Clients are built per operation, so breakers are shared per storage
+// target: one failing bucket or endpoint must not trip the others.
+static CIRCUIT_BREAKERS: LazyLock<Mutex<HashMap<String, Arc<CircuitBreaker>>>> = LazyLock::new(Default::default);
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+  // Breaker state is a few plain fields; a panic elsewhere cannot leave it
+  // inconsistent.
+  mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The shared breaker of a storage target (see
+/// `ObjectStorageConfig::circuit_key`), updated to the latest configuration.
+pub(crate) fn circuit_breaker(key: &str, config: CircuitBreakerConfig) -> Arc<CircuitBreaker> {
+  let breaker = lock(&CIRCUIT_BREAKERS)
+    .entry(key.to_string())
+    .or_insert_with(|| Arc::new(CircuitBreaker::new(config)))
+    .clone();
+  lock(&breaker.inner).config = config;
+  breaker
+}
+
+/// `closed`, `open` or `half_open`; a target that has not been called yet
+/// is closed.
+pub(crate) fn circuit_state(key: &str) -> &'static str {
+  lock(&CIRCUIT_BREAKERS)
+    .get(key)
+    .map_or("closed", |breaker| breaker.state())
+}
+
+impl CircuitBreaker {
+  fn new(config: CircuitBreakerConfig) -> Self {
+    Self {
+      inner: Mutex::new(CircuitInner {
+        config,
+        state: CircuitState::Closed { failures: 0 },
+      }),
+    }
+  }
+
+  /// Whether a request may be sent now. Once the open period is over one
+  /// request is let through as a probe; another probe is allowed if that
+  /// one never reports back within `open_ms`.
+  fn try_acquire(&self) -> bool {
+    let mut inner = lock(&self.inner);
+    let now = Instant::now();
+    let open_for = Duration::from_millis(inner.config.open_ms);
+    match inner.state {
+      CircuitState::Closed { .. } => true,
+      CircuitState::Open { until } if now < until => false,
+      CircuitState::HalfOpen { probe_started } if now < probe_started + open_for => false,
+      CircuitState::Open { .. } | CircuitState::HalfOpen { .. } => {
+        inner.state = CircuitState::HalfOpen { probe_started: now };
+        true
+      }
+    }
+  }
+
+  fn record_success(&self) {
+    lock(&self.inner).state = CircuitState::Closed { failures: 0 };
+  }
+
+  fn record_failure(&self) {
+    let mut inner = lock(&self.inner);
+    let open = CircuitState::Open {
+      until: Instant::now() + Duration::from_millis(inner.config.open_ms),
+    };
+    inner.state = match inner.state {
+      CircuitState::Closed { failures } if failures + 1 < inner.config.failure_threshold => {
+        CircuitState::Closed { failures: failures + 1 }
+      }
+      _ => open,
+    };
+  }
+
+  fn is_closed(&self) -> bool {
+    matches!(lock(&self.inner).state, CircuitState::Closed { .. })
+  }
+
+  fn state(&self) -> &'static str {
+    match lock(&self.inner).state {
+      CircuitState::Closed { .. } => "closed",
+      CircuitState::Open { .. } => "open",
+      CircuitState::HalfOpen { .. } => "half_open",
+    }
+  }
+}
+
+/// Retry policy and breaker a client sends its requests under.
+#[derive(Clone)]
+pub(crate) struct Resilience {
+  provider: String,
+  retry: RetryPolicy,
+  breaker: Arc<CircuitBreaker>,
+}
+
+impl Resilience {
+  pub(crate) fn for_provider(
+    provider: &str,
+    circuit_key: &str,
+    retry: RetryPolicy,
+    circuit_breaker_config: CircuitBreakerConfig,
+  ) -> Self {
+    Self {
+      provider: provider.to_string(),
+      retry,
+      breaker: circuit_breaker(circuit_key, circuit_breaker_config),
+    }
+  }
+}
+
+impl Default for Resilience {
+  /// Default retries with a breaker of its own, for clients built outside a
+  /// provider config.
+  fn default() -> Self {
+    Self {
+      provider: "object-storage".to_string(),
+      retry: RetryPolicy::default(),
+      breaker: Arc::new(CircuitBreaker::new(CircuitBreakerConfig::default())),
+    }
+  }
+}
+
+/// What a response or transport error says about the provider.
+enum Outcome {
+  Healthy,
+  Transient {
+    retry_after: Option<Duration>,
+  },
+  /// Failed before reaching the provider, e.g. an invalid header.
+  Neutral,
+}
+
+trait HttpResponseHead {
+  fn status(&self) -> StatusCode;
+  fn headers(&self) -> &HeaderMap;
+}
+
+impl HttpResponseHead for StorageHttpResponse {
+  fn status(&self) -> StatusCode {
+    self.status
+  }
+
+  fn headers(&self) -> &HeaderMap {
+    &self.headers
+  }
+}
+
+impl HttpResponseHead for reqwest::Response {
+  fn status(&self) -> StatusCode {
+    self.status()
+  }
+
+  fn headers(&self) -> &HeaderMap {
+    self.headers()
+  }
+}
+
+/// S3 answers throttling with `503 SlowDown`; R2, GCS and Azure use 429 or
+/// 503 as well.
+fn is_transient_status(status: StatusCode) -> bool {
+  matches!(
+    status,
+    StatusCode::TOO_MANY_REQUESTS
+      | StatusCode::INTERNAL_SERVER_ERROR
+      | StatusCode::BAD_GATEWAY
+      | StatusCode::SERVICE_UNAVAILABLE
+      | StatusCode::GATEWAY_TIMEOUT
+  )
+}
+
+/// POST creates or completes multipart uploads; sending it twice is not
+/// safe. Every other request the clients make can be repeated.
+fn is_idempotent(method: &Method) -> bool {
+  matches!(*method, Method::GET | Method::HEAD | Method::PUT | Method::DELETE)
+}
+
+/// `Retry-After` as delay seconds or an HTTP date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+  let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+  if let Ok(seconds) = value.parse::<u64>() {
+    return Some(Duration::from_secs(seconds));
+  }
+  let at = SystemTime::from(DateTime::parse_from_rfc2822(value).ok()?);
+  Some(at.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+fn outcome<R: HttpResponseHead>(result: &ObjectStorageResult<R>) -> Outcome {
+  match result {
+    Ok(response) if is_transient_status(response.status()) => Outcome::Transient {
+      retry_after: retry_after(response.headers()),
+    },
+    Ok(_) => Outcome::Healthy,
+    Err(ObjectStorageError::HttpRequest(err)) if err.is_timeout() || err.is_connect() => {
+      Outcome::Transient { retry_after: None }
+    }
+    Err(_) => Outcome::Neutral,
+  }
+}
+
+/// Sends requests through `inner` with retries and circuit breaking. A
+/// transient response that survives every attempt is returned as is, so
+/// callers keep reporting the provider's status.
+#[derive(Clone)]
+pub(super) struct ResilientHttpClient<H = ReqwestStorageHttpClient> {
+  inner: H,
+  resilience: Resilience,
+}
+
+impl<H> ResilientHttpClient<H> {
+  pub(super) fn new(inner: H, resilience: Resilience) -> Self {
+    Self { inner, resilience }
+  }
+
+  async fn send_with<R, F, Fut>(&self, request: StorageHttpRequest, send: F) -> ObjectStorageResult<R>
+  where
+    R: HttpResponseHead,
+    F: Fn(StorageHttpRequest) -> Fut,
+    Fut: Future<Output = ObjectStorageResult<R>>,
+  {
+    let Resilience {
+      provider,
+      retry,
+      breaker,
+    } = &self.resilience;
+    let max_attempts = if is_idempotent(&request.method) {
+      retry.max_attempts.max(1)
+    } else {
+      1
+    };
+    let mut request = Some(request);
+    let mut attempt = 1;
+    loop {
+      if !breaker.try_acquire() {
+        return Err(ObjectStorageError::CircuitOpen(provider.clone()));
+      }
+      // Only keep a copy of the body while another attempt may need it.
+      let attempt_request = if attempt < max_attempts {
+        request.clone()
+      } else {
+        request.take()
+      }
+      .ok_or_else(|| ObjectStorageError::InvalidInput("retried request was already sent".to_string()))?;
+      let result = send(attempt_request).await;
+      match outcome(&result) {
+        Outcome::Healthy => {
+          breaker.record_success();
+          return result;
+        }
+        Outcome::Neutral => return result,
+        Outcome::Transient { retry_after } => {
+          breaker.record_failure();
+          if attempt >= max_attempts || !breaker.is_closed() {
+            return result;
+          }
+          tokio::time::sleep(retry.delay(attempt, retry_after)).await;
+          attempt += 1;
+        }
+      }
+    }
+  }
+}
+
+impl ResilientHttpClient<ReqwestStorageHttpClient> {
+  /// Retries apply until response headers arrive; a body that fails while
+  /// streaming is not resent.
+  pub(super) async fn send_streaming(&self, request: StorageHttpRequest) -> ObjectStorageResult<reqwest::Response> {
+    self
+      .send_with(request, |request| self.inner.send_streaming(request))
+      .await
+  }
+}
+
+impl<H: StorageHttpClient> StorageHttpClient for ResilientHttpClient<H> {
+  fn execute(&self, request: StorageHttpRequest) -> StorageHttpFuture<'_> {
+    Box::pin(self.send_with(request, |request| self.inner.execute(request)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use reqwest::header::HeaderValue;
+  use url::Url;
+
+  use super::{super::test_http::ScriptedHttpClient, *};
+
+  fn request(method: Method) -> StorageHttpRequest {
+    StorageHttpRequest {
+      method,
+      url: Url::parse("https://bucket.s3.example.com/key").unwrap(),
+      headers: HashMap::new(),
+      body: None,
+      max_response_body_bytes: 1024,
+    }
+  }
+
+  fn client(inner: ScriptedHttpClient, failure_threshold: u32) -> ResilientHttpClient<ScriptedHttpClient> {
+    ResilientHttpClient::new(
+      inner,
+      Resilience {
+        provider: "aws-s3".to_string(),
+        retry: RetryPolicy {
+          max_attempts: 3,
+          base_delay_ms: 0,
+          max_delay_ms: 0,
+        },
+        breaker: Arc::new(CircuitBreaker::new(CircuitBreakerConfig {
+          failure_threshold,
+          open_ms: 60_000,
+        })),
+      },
+    )
+  }
+
+  #[tokio::test]
+  async fn retries_transient_responses_of_idempotent_requests() {
+    let inner = ScriptedHttpClient::with_statuses(&[
+      (StatusCode::SERVICE_UNAVAILABLE, Some("0")),
+      (StatusCode::INTERNAL_SERVER_ERROR, None),
+      (StatusCode::OK, None),
+    ]);
+    let client = client(inner.clone(), 5);
+
+    let response = client.execute(request(Method::GET)).await.unwrap();
+
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(inner.calls(), 3);
+    assert_eq!(client.resilience.breaker.state(), "closed");
+  }
+
+  #[tokio::test]
+  async fn returns_the_last_transient_response_and_never_retries_post() {
+    let inner = ScriptedHttpClient::with_statuses(&[(StatusCode::SERVICE_UNAVAILABLE, None)]);
+    let client = client(inner.clone(), 10);
+
+    let response = client.execute(request(Method::PUT)).await.unwrap();
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(inner.calls(), 3);
+
+    let response = client.execute(request(Method::POST)).await.unwrap();
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(inner.calls(), 4);
+  }
+
+  #[tokio::test]
+  async fn open_circuit_fails_fast_until_a_probe_succeeds() {
+    let inner = ScriptedHttpClient::with_statuses(&[(StatusCode::SERVICE_UNAVAILABLE, None), (StatusCode::OK, None)]);
+    let client = client(inner.clone(), 1);
+
+    let response = client.execute(request(Method::GET)).await.unwrap();
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(inner.calls(), 1);
+    assert_eq!(client.resilience.breaker.state(), "open");
+
+    let err = client.execute(request(Method::GET)).await.err().unwrap();
+    assert!(matches!(err, ObjectStorageError::CircuitOpen(provider) if provider == "aws-s3"));
+    assert_eq!(inner.calls(), 1);
+
+    lock(&client.resilience.breaker.inner).state = CircuitState::Open { until: Instant::now() };
+    let response = client.execute(request(Method::GET)).await.unwrap();
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(client.resilience.breaker.state(), "closed");
+  }
+
+  #[test]
+  fn backoff_is_jittered_capped_and_honours_retry_after() {
+    let policy = RetryPolicy {
+      max_attempts: 5,
+      base_delay_ms: 100,
+      max_delay_ms: 1_000,
+    };
+    for attempt in 1..=10 {
+      let cap = (100u64 << (attempt - 1)).min(1_000);
+      assert!(policy.delay(attempt, None) <= Duration::from_millis(cap));
+    }
+    assert_eq!(
+      policy.delay(1, Some(Duration::from_millis(300))),
+      Duration::from_millis(300)
+    );
+    assert_eq!(policy.delay(1, Some(Duration::from_secs(60))), Duration::from_secs(1));
+
+    let mut headers = HeaderMap::new();
+    headers.insert(RETRY_AFTER, HeaderValue::from_static("2"));
+    assert_eq!(retry_after(&headers), Some(Duration::from_secs(2)));
+    headers.insert(RETRY_AFTER, HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"));
+    assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+    headers.insert(RETRY_AFTER, HeaderValue::from_static("soon"));
+    assert_eq!(retry_after(&headers), None);
+  }
+
+  #[test]
+  fn breakers_are_shared_per_storage_target() {
+    let config = CircuitBreakerConfig {
+      failure_threshold: 1,
+      open_ms: 60_000,
+    };
+    let breaker = circuit_breaker("resilience-test/endpoint/bucket", config);
+    assert!(Arc::ptr_eq(
+      &breaker,
+      &circuit_breaker("resilience-test/endpoint/bucket", config)
+    ));
+    assert!(!Arc::ptr_eq(
+      &breaker,
+      &circuit_breaker("resilience-test/endpoint/other-bucket", config)
+    ));
+    assert_eq!(circuit_state("resilience-test/endpoint/bucket"), "closed");
+    breaker.record_failure();
+    assert_eq!(circuit_state("resilience-test/endpoint/bucket"), "open");
+    assert_eq!(circuit_state("resilience-test/endpoint/other-bucket"), "closed");
+    assert_eq!(circuit_state("resilience-test/unused"), "closed");
+  }
+}
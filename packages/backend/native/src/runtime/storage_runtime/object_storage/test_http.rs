@@ -0,0 +1,79 @@
+use std::{
+  collections::VecDeque,
+  sync::{Arc, Mutex},
+};
+
+use reqwest::{
+  StatusCode,
+  header::{HeaderMap, HeaderValue, RETRY_AFTER},
+};
+
+use super::client::{StorageHttpClient, StorageHttpFuture, StorageHttpRequest, StorageHttpResponse};
+
+type ScriptedResponse = (StatusCode, HeaderMap, &'static str);
+
+/// Replays scripted responses in order, repeating the last one, and keeps
+/// the requests it was sent.
+#[derive(Clone)]
+pub(super) struct ScriptedHttpClient {
+  responses: Arc<Mutex<VecDeque<ScriptedResponse>>>,
+  requests: Arc<Mutex<Vec<StorageHttpRequest>>>,
+}
+
+impl ScriptedHttpClient {
+  /// Answers every request with `status` and `body`.
+  pub(super) fn replying(status: StatusCode, body: &'static str) -> Self {
+    Self::new(vec![(status, HeaderMap::new(), body)])
+  }
+
+  /// Answers with empty bodies and `statuses`, each with an optional
+  /// `Retry-After`.
+  pub(super) fn with_statuses(statuses: &[(StatusCode, Option<&'static str>)]) -> Self {
+    Self::new(
+      statuses
+        .iter()
+        .map(|(status, retry_after)| {
+          let mut headers = HeaderMap::new();
+          if let Some(retry_after) = retry_after {
+            headers.insert(RETRY_AFTER, HeaderValue::from_static(retry_after));
+          }
+          (*status, headers, "")
+        })
+        .collect(),
+    )
+  }
+
+  fn new(responses: Vec<ScriptedResponse>) -> Self {
+    Self {
+      responses: Arc::new(Mutex::new(responses.into())),
+      requests: Arc::default(),
+    }
+  }
+
+  pub(super) fn requests(&self) -> Vec<StorageHttpRequest> {
+    self.requests.lock().unwrap().clone()
+  }
+
+  pub(super) fn calls(&self) -> usize {
+    self.requests.lock().unwrap().len()
+  }
+}
+
+impl StorageHttpClient for ScriptedHttpClient {
+  fn execute(&self, request: StorageHttpRequest) -> StorageHttpFuture<'_> {
+    self.requests.lock().unwrap().push(request);
+    let mut responses = self.responses.lock().unwrap();
+    let (status, headers, body) = if responses.len() > 1 {
+      responses.pop_front().unwrap()
+    } else {
+      responses[0].clone()
+    };
+    Box::pin(async move {
+      Ok(StorageHttpResponse {
+        status,
+        headers,
+        body: body.as_bytes().to_vec(),
+      })
+    })
+  }
+}
@@ -1,6 +1,7 @@
 use reqwest::StatusCode;
 
 use super::{
+  client::ObjectStorageClient,
   config::ObjectStorageConfig,
   error::ObjectStorageError,
   types::{MultipartUploadPart, ObjectPutMetadata, StorageProviderConfig, completed_multipart_parts, trim_etag},
@@ -46,6 +47,25 @@ fn resolves_r2_config_from_config_json_shape() {
   assert_eq!(config.access_key_id.as_deref(), Some("key"));
 }
 
+#[test]
+fn circuit_key_tells_buckets_of_one_endpoint_apart() {
+  let r2 = |bucket: &str| {
+    ObjectStorageConfig::from_r2_config(StorageProviderConfig {
+      provider: "cloudflare-r2".to_string(),
+      bucket: bucket.to_string(),
+      config: serde_json::json!({ "accountId": "account" }),
+    })
+    .unwrap()
+    .unwrap()
+  };
+
+  assert_eq!(
+    r2("workspace-blobs").circuit_key(),
+    "cloudflare-r2/https://account.r2.cloudflarestorage.com/workspace-blobs"
+  );
+  assert_ne!(r2("workspace-blobs").circuit_key(), r2("avatars").circuit_key());
+}
+
 #[test]
 fn resolves_r2_endpoint_cases_from_config_json_shape() {
   for (case, config, expected_endpoint) in [
@@ -365,3 +385,223 @@ fn object_storage_orders_completed_multipart_parts_and_trims_etags() {
   assert_eq!(parts[1].part_number, 2);
   assert_eq!(parts[1].etag, "b");
 }
+
+#[test]
+fn resolves_gcs_config_from_config_json_shape() {
+  let config = ObjectStorageConfig::from_provider_config(Some(storage_config(
+    "gcs",
+    serde_json::json!({
+      "credentials": {
+        "accessKeyId": "GOOG1",
+        "secretAccessKey": "secret"
+      },
+      "usePresignedURL": {
+        "enabled": true
+      }
+    }),
+  )))
+  .unwrap()
+  .unwrap();
+
+  assert_eq!(config.provider, "gcs");
+  assert_eq!(config.endpoint.as_deref(), Some("https://storage.googleapis.com"));
+  assert_eq!(config.region.as_deref(), Some("auto"));
+  assert!(config.force_path_style);
+  assert!(config.use_presigned_url);
+  assert!(config.supports_multipart_direct());
+  assert!(config.azure.is_none());
+}
+
+#[test]
+fn resolves_azure_blob_config_from_config_json_shape() {
+  let config = ObjectStorageConfig::from_provider_config(Some(storage_config(
+    "azure-blob",
+    serde_json::json!({
+      "accountName": "account",
+      "credentials": {
+        "sasToken": "?sv=2021-08-06&sig=abc"
+      },
+      "usePresignedURL": {
+        "enabled": true
+      }
+    }),
+  )))
+  .unwrap()
+  .unwrap();
+
+  assert_eq!(config.provider, "azure-blob");
+  assert_eq!(
+    config.endpoint.as_deref(),
+    Some("https://account.blob.core.windows.net")
+  );
+  assert_eq!(
+    config.azure.as_ref().map(|azure| azure.account_name.as_str()),
+    Some("account")
+  );
+  assert!(config.use_presigned_url);
+  assert!(!config.supports_multipart_direct());
+  assert!(matches!(config.build_client(), Ok(ObjectStorageClient::Azure(_))));
+
+  let missing_credentials = ObjectStorageConfig::from_azure_blob_config(storage_config(
+    "azure-blob",
+    serde_json::json!({ "accountName": "account" }),
+  ))
+  .unwrap()
+  .unwrap();
+  assert!(missing_credentials.build_client().is_err());
+  assert!(ObjectStorageConfig::from_azure_blob_config(storage_config("azure-blob", serde_json::json!({}))).is_err());
+}
+
+#[tokio::test]
+async fn azure_blob_presign_get_returns_service_sas_url() {
+  let config = ObjectStorageConfig::from_azure_blob_config(storage_config(
+    "azure-blob",
+    serde_json::json!({
+      "accountName": "account",
+      "credentials": {
+        "accountKey": "a2V5"
+      }
+    }),
+  ))
+  .unwrap()
+  .unwrap();
+  let client = config.build_client().unwrap();
+  let result = client.presign_get("workspace/key").await.unwrap();
+
+  assert!(
+    result
+      .url
+      .starts_with("https://account.blob.core.windows.net/test-bucket/workspace/key?")
+  );
+  assert!(result.url.contains("sp=r"));
+  assert!(result.url.contains("sig="));
+  assert!(result.headers.is_empty());
+  assert!(result.expires_at_ms > 0);
+}
+
+// Azurite's well-known development account key.
+const AZURITE_ACCOUNT_KEY: &str =
+  "Eby8vdM02xNOcqFlqUwJPLlmEtlCDXJ1OUzFT50uSRZ6IFsuFq2UVErCz4I6tq/K1SZFPTOtr/KBHBeksoGMGw==";
+
+async fn object_storage_round_trip(client: &ObjectStorageClient, prefix: &str) {
+  let key = format!("{prefix}/blob");
+  let body = b"hello object storage".to_vec();
+  let stored = client
+    .put(
+      &key,
+      body.clone(),
+      ObjectPutMetadata {
+        content_type: Some("text/plain".to_string()),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap();
+  assert_eq!(stored.content_length, body.len() as i64);
+
+  let head = client.head(&key).await.unwrap().unwrap();
+  assert_eq!(head.content_type, "text/plain");
+  assert_eq!(head.content_length, body.len() as i64);
+  let object = client.get(&key).await.unwrap().unwrap();
+  assert_eq!(object.body, body);
+
+  let listed = client.list(Some(format!("{prefix}/"))).await.unwrap();
+  assert_eq!(
+    listed.iter().map(|entry| entry.key.as_str()).collect::<Vec<_>>(),
+    vec![key.as_str()]
+  );
+
+  client.delete(&key).await.unwrap();
+  assert!(client.head(&key).await.unwrap().is_none());
+  assert!(client.get(&key).await.unwrap().is_none());
+  client.delete(&key).await.unwrap();
+}
+
+#[tokio::test]
+async fn azure_blob_round_trip_against_azurite() {
+  let Ok(endpoint) = std::env::var("AZURITE_BLOB_ENDPOINT") else {
+    return;
+  };
+  let config = ObjectStorageConfig::from_azure_blob_config(StorageProviderConfig {
+    provider: "azure-blob".to_string(),
+    bucket: "rust-test".to_string(),
+    config: serde_json::json!({
+      "accountName": "devstoreaccount1",
+      "endpoint": endpoint,
+      "credentials": {
+        "accountKey": AZURITE_ACCOUNT_KEY
+      }
+    }),
+  })
+  .unwrap()
+  .unwrap();
+  let client = config.build_client().unwrap();
+  let ObjectStorageClient::Azure(azure) = &client else {
+    unreachable!("azure-blob config builds an azure client");
+  };
+  azure.create_container().await.unwrap();
+  let prefix = format!("rust-test-{}", uuid::Uuid::new_v4().simple());
+
+  object_storage_round_trip(&client, &prefix).await;
+
+  let key = format!("{prefix}/multipart");
+  let upload = client
+    .create_multipart_upload(
+      &key,
+      ObjectPutMetadata {
+        content_type: Some("application/pdf".to_string()),
+        ..Default::default()
+      },
+    )
+    .await
+    .unwrap()
+    .unwrap();
+  for (part_number, chunk) in [(2, b"world".to_vec()), (1, b"hello ".to_vec())] {
+    client
+      .upload_part(&key, &upload.upload_id, part_number, chunk, None)
+      .await
+      .unwrap();
+  }
+  let parts = client
+    .list_multipart_upload_parts(&key, &upload.upload_id)
+    .await
+    .unwrap();
+  assert_eq!(
+    parts.iter().map(|part| part.part_number).collect::<Vec<_>>(),
+    vec![1, 2]
+  );
+  client
+    .complete_multipart_upload(&key, &upload.upload_id, parts)
+    .await
+    .unwrap();
+
+  let object = client.get(&key).await.unwrap().unwrap();
+  assert_eq!(object.body, b"hello world");
+  assert_eq!(object.metadata.content_type, "application/pdf");
+  client.delete(&key).await.unwrap();
+}
+
+#[tokio::test]
+async fn gcs_round_trip_against_fake_gcs_server() {
+  let Ok(endpoint) = std::env::var("FAKE_GCS_ENDPOINT") else {
+    return;
+  };
+  let bucket = std::env::var("FAKE_GCS_BUCKET").unwrap_or_else(|_| "rust-test".to_string());
+  let config = ObjectStorageConfig::from_gcs_config(StorageProviderConfig {
+    provider: "gcs".to_string(),
+    bucket,
+    config: serde_json::json!({
+      "endpoint": endpoint,
+      "credentials": {
+        "accessKeyId": "GOOG1",
+        "secretAccessKey": "secret"
+      }
+    }),
+  })
+  .unwrap()
+  .unwrap();
+  let client = config.build_client().unwrap();
+  let prefix = format!("rust-test-{}", uuid::Uuid::new_v4().simple());
+
+  object_storage_round_trip(&client, &prefix).await;
+}
@@ -4,7 +4,8 @@ use serde::Deserialize;
 
 use super::super::{
   RuntimeError, RuntimeMultipartUploadInit, RuntimeMultipartUploadPart, RuntimeObjectGetResult, RuntimeObjectListEntry,
-  RuntimeObjectMetadata, RuntimeObjectStoragePutOptions, RuntimePresignedObjectRequest, RuntimeResult,
+  RuntimeObjectMetadata, RuntimeObjectRangeResult, RuntimeObjectStoragePutOptions, RuntimePresignedObjectRequest,
+  RuntimeResult, byte_range::ResolvedRange,
 };
 
 #[derive(Clone, Debug, Default)]
@@ -12,6 +13,10 @@ pub(crate) struct ObjectPutMetadata {
   pub(crate) content_type: Option<String>,
   pub(crate) content_length: Option<i64>,
   pub(crate) checksum_crc32: Option<String>,
+  pub(crate) encryption: Option<ObjectEncryption>,
+  /// Keep this modification time instead of the write time, e.g. when an
+  /// object is copied between providers.
+  pub(crate) last_modified_ms: Option<i64>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -20,6 +25,34 @@ pub(crate) struct ObjectMetadata {
   pub(crate) content_length: i64,
   pub(crate) last_modified_ms: i64,
   pub(crate) checksum_crc32: Option<String>,
+  pub(crate) encryption: Option<ObjectEncryption>,
+}
+
+/// Data key an encrypted blob was sealed with, stored next to the object as
+/// provider metadata. `key_id` fingerprints the data key so a mismatched key
+/// is reported instead of failing authentication.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ObjectEncryption {
+  pub(crate) key_id: String,
+  pub(crate) key_version: i32,
+}
+
+impl ObjectEncryption {
+  pub(crate) fn from_parts(key_id: Option<String>, key_version: Option<String>) -> Option<Self> {
+    Some(Self {
+      key_id: key_id.filter(|key_id| !key_id.is_empty())?,
+      key_version: key_version?.trim().parse().ok()?,
+    })
+  }
+}
+
+/// A ranged read. `range` is `None` when the requested range does not
+/// overlap the object; `body` is empty in that case.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ObjectRangeGetResult {
+  pub(crate) body: Vec<u8>,
+  pub(crate) metadata: ObjectMetadata,
+  pub(crate) range: Option<ResolvedRange>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -35,6 +68,22 @@ pub(crate) struct ObjectListPage {
   pub(crate) next_continuation_token: Option<String>,
 }
 
+/// An in-progress multipart upload. `initiated_ms` is when the upload was
+/// created, not when its last part arrived.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct MultipartUploadEntry {
+  pub(crate) key: String,
+  pub(crate) upload_id: String,
+  pub(crate) initiated_ms: i64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct MultipartUploadPage {
+  pub(crate) uploads: Vec<MultipartUploadEntry>,
+  pub(crate) next_key_marker: Option<String>,
+  pub(crate) next_upload_id_marker: Option<String>,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub(crate) struct ObjectGetResult {
   pub(crate) body: Vec<u8>,
@@ -83,6 +132,8 @@ impl From<RuntimeObjectStoragePutOptions> for ObjectPutMetadata {
       content_type: options.content_type,
       content_length: options.content_length,
       checksum_crc32: options.checksum_crc32,
+      encryption: None,
+      last_modified_ms: None,
     }
   }
 }
@@ -105,8 +156,9 @@ impl ObjectPutMetadata {
         .content_type
         .unwrap_or_else(|| "application/octet-stream".to_string()),
       content_length: self.content_length.unwrap_or(0),
-      last_modified_ms,
+      last_modified_ms: self.last_modified_ms.unwrap_or(last_modified_ms),
       checksum_crc32: self.checksum_crc32,
+      encryption: self.encryption,
     }
   }
 }
@@ -154,6 +206,18 @@ impl From<ObjectGetResult> for RuntimeObjectGetResult {
   }
 }
 
+impl From<ObjectRangeGetResult> for RuntimeObjectRangeResult {
+  fn from(result: ObjectRangeGetResult) -> Self {
+    Self {
+      body: result.body.into(),
+      metadata: result.metadata.into(),
+      satisfiable: result.range.is_some(),
+      start: result.range.map_or(0, |range| range.start as i64),
+      end: result.range.map_or(0, |range| range.end as i64),
+    }
+  }
+}
+
 impl From<MultipartUploadInitResult> for RuntimeMultipartUploadInit {
   fn from(init: MultipartUploadInitResult) -> Self {
     Self {
@@ -0,0 +1,971 @@
+use std::{
+  borrow::Cow,
+  fs,
+  io::{Read, Write},
+  path::PathBuf,
+  time::SystemTime,
+};
+
+use napi::bindgen_prelude::Buffer;
+use tokio::sync::Mutex;
+
+use super::{
+  FS_UPLOADS_DIR, FsStorageConfig, RuntimeError, RuntimeObjectMetadata, RuntimeObjectStoragePutOptions, RuntimeResult,
+  StorageBackendConfig, StorageRuntime, assetpack,
+  blob_encryption::{
+    BlobCipher, BlobSealer, SEALED_HEADER_BYTES, SealedReader, blob_workspace, plaintext_metadata, sealed_length,
+  },
+  byte_range::{ByteRange, ResolvedRange},
+  fs_bucket_path, fs_object_path, fs_open_read, measured, napi_error,
+  object_storage::{
+    client::{ObjectBodyStream, ObjectStorageClient},
+    types::{MultipartUploadPart, ObjectEncryption, ObjectMetadata, ObjectPutMetadata, ObjectRangeGetResult},
+  },
+  system_time_ms, write_fs_metadata,
+};
+
+const STREAM_CHUNK_BYTES: usize = 256 * 1024;
+// S3 rejects non-final parts below 5 MiB.
+const MIN_STREAM_PART_BYTES: u64 = 5 * 1024 * 1024;
+const DEFAULT_STREAM_PART_BYTES: u64 = 8 * 1024 * 1024;
+
+enum ChunkSource {
+  File { file: fs::File, remaining: u64 },
+  Memory { body: Vec<u8>, offset: usize },
+  Remote(ObjectBodyStream),
+}
+
+impl ChunkSource {
+  async fn next_chunk(&mut self) -> RuntimeResult<Option<Vec<u8>>> {
+    match self {
+      Self::File { file, remaining } => {
+        if *remaining == 0 {
+          return Ok(None);
+        }
+        let mut chunk = vec![
+          0;
+          usize::try_from(*remaining)
+            .unwrap_or(usize::MAX)
+            .min(STREAM_CHUNK_BYTES)
+        ];
+        file
+          .read_exact(&mut chunk)
+          .map_err(|err| RuntimeError::io("StorageRuntime fs read object failed", err))?;
+        *remaining -= chunk.len() as u64;
+        Ok(Some(chunk))
+      }
+      Self::Memory { body, offset } => {
+        if *offset >= body.len() {
+          return Ok(None);
+        }
+        let end = (*offset + STREAM_CHUNK_BYTES).min(body.len());
+        let chunk = body[*offset..end].to_vec();
+        *offset = end;
+        Ok(Some(chunk))
+      }
+      Self::Remote(body) => body.next_chunk().await.map_err(Into::into),
+    }
+  }
+}
+
+enum ReadSource {
+  Plain(ChunkSource),
+  Sealed {
+    stored: ChunkSource,
+    reader: Box<SealedReader>,
+  },
+}
+
+impl ReadSource {
+  async fn next_chunk(&mut self) -> RuntimeResult<Option<Vec<u8>>> {
+    match self {
+      Self::Plain(source) => source.next_chunk().await,
+      Self::Sealed { stored, reader } => loop {
+        if let Some(chunk) = reader.next_plaintext()? {
+          return Ok(Some(chunk));
+        }
+        if reader.done() {
+          return Ok(None);
+        }
+        match stored.next_chunk().await? {
+          Some(chunk) => reader.push(&chunk),
+          None => {
+            reader.finish()?;
+            return Ok(None);
+          }
+        }
+      },
+    }
+  }
+}
+
+/// An object opened for chunked reading. Node pulls chunks with `read()`, so
+/// at most one chunk is held in memory regardless of the object size.
+#[napi_derive::napi]
+pub struct ObjectReadStream {
+  metadata: ObjectMetadata,
+  range: Option<ResolvedRange>,
+  source: Mutex<Option<ReadSource>>,
+  satisfiable: bool,
+}
+
+impl ObjectReadStream {
+  fn new(metadata: ObjectMetadata, range: Option<ResolvedRange>, source: Option<ChunkSource>) -> Self {
+    Self {
+      metadata,
+      range,
+      satisfiable: source.is_some(),
+      source: Mutex::new(source.map(ReadSource::Plain)),
+    }
+  }
+
+  async fn open(
+    backend: &StorageBackendConfig,
+    scope: &str,
+    key: &str,
+    range: Option<ByteRange>,
+  ) -> RuntimeResult<Option<Self>> {
+    match backend {
+      StorageBackendConfig::Fs(config) => Ok(fs_open_read(config, key, range)?.map(|reader| {
+        let remaining = reader.remaining;
+        let source = reader.file.map(|file| ChunkSource::File { file, remaining });
+        Self::new(reader.metadata, reader.range, source)
+      })),
+      StorageBackendConfig::Assetpack(config) => Ok(assetpack::get(config, scope, key).await?.map(|object| {
+        let Some(range) = range else {
+          return Self::new(
+            object.metadata,
+            None,
+            Some(ChunkSource::Memory {
+              body: object.body,
+              offset: 0,
+            }),
+          );
+        };
+        let resolved = range.resolve(object.body.len() as u64);
+        let source = resolved.map(|resolved| ChunkSource::Memory {
+          body: resolved.slice(&object.body).to_vec(),
+          offset: 0,
+        });
+        Self::new(object.metadata, resolved, source)
+      })),
+      StorageBackendConfig::S3(config) => Ok(
+        config
+          .build_client()?
+          .open_stream(key, range)
+          .await?
+          .map(|stream| Self::new(stream.metadata, stream.range, stream.body.map(ChunkSource::Remote))),
+      ),
+    }
+  }
+
+  /// Open a sealed object given its stored metadata. Only the chunks that
+  /// cover `range` are fetched; the header is read separately when the range
+  /// does not start in the first chunk.
+  async fn open_sealed(
+    backend: &StorageBackendConfig,
+    scope: &str,
+    key: &str,
+    cipher: &BlobCipher,
+    stored: ObjectMetadata,
+    range: Option<ByteRange>,
+  ) -> RuntimeResult<Option<Self>> {
+    let encryption = stored
+      .encryption
+      .clone()
+      .ok_or_else(|| RuntimeError::invalid_state(format!("StorageRuntime object {key} is not sealed")))?;
+    let metadata = plaintext_metadata(stored)?;
+    let total = metadata.content_length as u64;
+    let resolved = match range.map(|range| range.resolve(total)) {
+      Some(None) => return Ok(Some(Self::new(metadata, None, None))),
+      Some(resolved) => resolved,
+      None => None,
+    };
+    let mut reader = cipher.reader(key, &encryption, total, resolved)?;
+    if reader.needs_header() {
+      let header_range = ByteRange::Bounded {
+        start: 0,
+        end: SEALED_HEADER_BYTES - 1,
+      };
+      let Some(header) = Self::open(backend, scope, key, Some(header_range)).await? else {
+        return Ok(None);
+      };
+      reader.set_header(&header.collect().await?)?;
+    }
+    let Some(stream) = Self::open(backend, scope, key, reader.stored_range()).await? else {
+      return Ok(None);
+    };
+    let Some(ReadSource::Plain(stored)) = stream.source.into_inner() else {
+      return Err(RuntimeError::invalid_state(format!(
+        "BlobEncryption blob {key} is truncated"
+      )));
+    };
+    Ok(Some(Self {
+      metadata,
+      range: resolved,
+      satisfiable: true,
+      source: Mutex::new(Some(ReadSource::Sealed {
+        stored,
+        reader: Box::new(reader),
+      })),
+    }))
+  }
+
+  async fn collect(self) -> RuntimeResult<Vec<u8>> {
+    let mut body = Vec::new();
+    if let Some(mut source) = self.source.into_inner() {
+      while let Some(chunk) = source.next_chunk().await? {
+        body.extend_from_slice(&chunk);
+      }
+    }
+    Ok(body)
+  }
+}
+
+#[napi_derive::napi]
+impl ObjectReadStream {
+  /// Metadata of the whole object; `contentLength` is the full size even for
+  /// ranged reads.
+  #[napi(getter)]
+  pub fn metadata(&self) -> RuntimeObjectMetadata {
+    self.metadata.clone().into()
+  }
+
+  /// False when the requested range lies outside the object. Nothing can be
+  /// read and the caller should answer 416.
+  #[napi(getter)]
+  pub fn satisfiable(&self) -> bool {
+    self.satisfiable
+  }
+
+  /// First byte of the served range, for the `Content-Range` header.
+  #[napi(getter)]
+  pub fn range_start(&self) -> Option<i64> {
+    self.range.map(|range| range.start as i64)
+  }
+
+  /// Last byte (inclusive) of the served range.
+  #[napi(getter)]
+  pub fn range_end(&self) -> Option<i64> {
+    self.range.map(|range| range.end as i64)
+  }
+
+  /// Read the next chunk, or `null` once the object or range is exhausted.
+  #[napi]
+  pub async fn read(&self) -> napi::Result<Option<Buffer>> {
+    let mut source = self.source.lock().await;
+    let Some(current) = source.as_mut() else {
+      return Ok(None);
+    };
+    match current.next_chunk().await {
+      Ok(Some(chunk)) => Ok(Some(chunk.into())),
+      Ok(None) => {
+        source.take();
+        Ok(None)
+      }
+      Err(err) => {
+        source.take();
+        Err(err.into())
+      }
+    }
+  }
+
+  /// Stop reading early and release the file or connection.
+  #[napi]
+  pub async fn close(&self) {
+    self.source.lock().await.take();
+  }
+}
+
+/// A streamed fs upload. The temp file is removed on drop unless `finish`
+/// renamed it into place.
+struct FsUpload {
+  file: fs::File,
+  temp_path: PathBuf,
+  target_path: PathBuf,
+}
+
+impl Drop for FsUpload {
+  fn drop(&mut self) {
+    let _ = fs::remove_file(&self.temp_path);
+  }
+}
+
+struct ObjectUpload {
+  client: ObjectStorageClient,
+  part_size: usize,
+  buffer: Vec<u8>,
+  upload_id: Option<String>,
+  parts: Vec<MultipartUploadPart>,
+  encryption: Option<ObjectEncryption>,
+  last_modified_ms: Option<i64>,
+}
+
+impl ObjectUpload {
+  async fn upload_part(&mut self, key: &str, content_type: Option<String>, body: Vec<u8>) -> RuntimeResult<()> {
+    let upload_id = match &self.upload_id {
+      Some(upload_id) => upload_id.clone(),
+      None => {
+        let metadata = ObjectPutMetadata {
+          content_type,
+          encryption: self.encryption.clone(),
+          last_modified_ms: self.last_modified_ms,
+          ..Default::default()
+        };
+        let init = self
+          .client
+          .create_multipart_upload(key, metadata)
+          .await?
+          .ok_or_else(|| RuntimeError::invalid_state("ObjectStorage multipart upload is not supported"))?;
+        self.upload_id = Some(init.upload_id.clone());
+        init.upload_id
+      }
+    };
+    let part_number = self.parts.len() as i32 + 1;
+    let content_length = body.len() as i64;
+    let etag = self
+      .client
+      .upload_part(key, &upload_id, part_number, body, Some(content_length))
+      .await?
+      .ok_or_else(|| {
+        RuntimeError::invalid_state(format!("ObjectStorage part {part_number} of {key} returned no etag"))
+      })?;
+    self.parts.push(MultipartUploadPart { part_number, etag });
+    Ok(())
+  }
+
+  async fn commit(&mut self, key: &str, metadata: ObjectPutMetadata) -> RuntimeResult<ObjectMetadata> {
+    let body = std::mem::take(&mut self.buffer);
+    if self.upload_id.is_none() {
+      return self.client.put(key, body, metadata).await.map_err(Into::into);
+    }
+    if !body.is_empty() {
+      self.upload_part(key, metadata.content_type.clone(), body).await?;
+    }
+    let upload_id = self.upload_id.clone().unwrap_or_default();
+    self
+      .client
+      .complete_multipart_upload(key, &upload_id, self.parts.clone())
+      .await?;
+    Ok(metadata.into_object_metadata(system_time_ms(SystemTime::now())?))
+  }
+
+  async fn abort(&self, key: &str) -> RuntimeResult<()> {
+    if let Some(upload_id) = &self.upload_id {
+      self.client.abort_multipart_upload(key, upload_id).await?;
+    }
+    Ok(())
+  }
+}
+
+enum SinkTarget {
+  Fs(FsUpload),
+  // Assetpack objects are packed as a whole, so the body is collected first.
+  Buffered {
+    config: FsStorageConfig,
+    scope: String,
+    body: Vec<u8>,
+  },
+  Object(Box<ObjectUpload>),
+}
+
+impl SinkTarget {
+  async fn write(&mut self, key: &str, content_type: &Option<String>, bytes: &[u8]) -> RuntimeResult<()> {
+    match self {
+      Self::Fs(upload) => upload
+        .file
+        .write_all(bytes)
+        .map_err(|err| RuntimeError::io("StorageRuntime fs write object failed", err))?,
+      Self::Buffered { body, .. } => body.extend_from_slice(bytes),
+      Self::Object(upload) => {
+        upload.buffer.extend_from_slice(bytes);
+        while upload.buffer.len() >= upload.part_size {
+          let part = upload.buffer.drain(..upload.part_size).collect::<Vec<_>>();
+          upload.upload_part(key, content_type.clone(), part).await?;
+        }
+      }
+    }
+    Ok(())
+  }
+
+  async fn abort(self, key: &str) -> RuntimeResult<()> {
+    match self {
+      Self::Fs(upload) => drop(upload),
+      Self::Buffered { .. } => {}
+      Self::Object(upload) => upload.abort(key).await?,
+    }
+    Ok(())
+  }
+}
+
+struct ObjectSink {
+  key: String,
+  requested: ObjectPutMetadata,
+  content_type: Option<String>,
+  hasher: crc32fast::Hasher,
+  length: u64,
+  sealer: Option<BlobSealer>,
+  target: SinkTarget,
+}
+
+impl ObjectSink {
+  /// With a `sealer` the length, checksum and content type are taken over
+  /// the plaintext while the target receives ciphertext.
+  fn open(
+    backend: &StorageBackendConfig,
+    scope: &str,
+    key: &str,
+    requested: ObjectPutMetadata,
+    sealer: Option<BlobSealer>,
+  ) -> RuntimeResult<Self> {
+    let target = match backend {
+      StorageBackendConfig::Fs(config) => {
+        let target_path = fs_object_path(config, key)?;
+        let uploads = fs_bucket_path(config).join(FS_UPLOADS_DIR);
+        fs::create_dir_all(&uploads).map_err(|err| RuntimeError::io("StorageRuntime fs create dir failed", err))?;
+        let temp_path = uploads.join(uuid::Uuid::new_v4().to_string());
+        let file = fs::File::create(&temp_path)
+          .map_err(|err| RuntimeError::io("StorageRuntime fs create upload failed", err))?;
+        SinkTarget::Fs(FsUpload {
+          file,
+          temp_path,
+          target_path,
+        })
+      }
+      StorageBackendConfig::Assetpack(config) => SinkTarget::Buffered {
+        config: config.clone(),
+        scope: scope.to_string(),
+        body: Vec::new(),
+      },
+      StorageBackendConfig::S3(config) => SinkTarget::Object(Box::new(ObjectUpload {
+        client: config.build_client()?,
+        part_size: stream_part_size(config.min_part_size),
+        buffer: Vec::new(),
+        upload_id: None,
+        parts: Vec::new(),
+        encryption: sealer
+          .as_ref()
+          .map(|sealer| sealer.encryption().clone())
+          .or_else(|| requested.encryption.clone()),
+        last_modified_ms: requested.last_modified_ms,
+      })),
+    };
+    Ok(Self {
+      key: key.to_string(),
+      content_type: requested.content_type.clone(),
+      requested,
+      hasher: crc32fast::Hasher::new(),
+      length: 0,
+      sealer,
+      target,
+    })
+  }
+
+  async fn write(&mut self, chunk: &[u8]) -> RuntimeResult<()> {
+    if chunk.is_empty() {
+      return Ok(());
+    }
+    if self.content_type.is_none() {
+      self.content_type = Some(crate::file_type::get_mime(chunk));
+    }
+    self.hasher.update(chunk);
+    self.length += chunk.len() as u64;
+    let stored = match &mut self.sealer {
+      Some(sealer) => Cow::Owned(sealer.update(chunk)?),
+      None => Cow::Borrowed(chunk),
+    };
+    self.target.write(&self.key, &self.content_type, &stored).await
+  }
+
+  async fn finish(self) -> RuntimeResult<ObjectMetadata> {
+    let Self {
+      key,
+      requested,
+      content_type,
+      hasher,
+      length,
+      sealer,
+      mut target,
+    } = self;
+    let checksum = format!("{:x}", hasher.finalize());
+    let mismatch = if requested
+      .content_length
+      .is_some_and(|expected| expected != length as i64)
+    {
+      Some("StorageRuntime stream content length mismatch")
+    } else if requested
+      .checksum_crc32
+      .as_deref()
+      .is_some_and(|expected| expected != checksum)
+    {
+      Some("StorageRuntime stream checksum mismatch")
+    } else {
+      None
+    };
+    if let Some(reason) = mismatch {
+      target.abort(&key).await?;
+      return Err(RuntimeError::invalid_input(reason));
+    }
+
+    let metadata = match sealer {
+      Some(sealer) => {
+        let encryption = sealer.encryption().clone();
+        let written = match sealer.finish() {
+          Ok(tail) => target.write(&key, &content_type, &tail).await,
+          Err(err) => Err(err),
+        };
+        if let Err(err) = written {
+          let _ = target.abort(&key).await;
+          return Err(err);
+        }
+        ObjectPutMetadata {
+          content_type,
+          content_length: Some(sealed_length(length) as i64),
+          checksum_crc32: None,
+          encryption: Some(encryption),
+          last_modified_ms: requested.last_modified_ms,
+        }
+      }
+      // Without a sealer the bytes are written as given, so an already sealed
+      // body being copied keeps its encryption metadata.
+      None => ObjectPutMetadata {
+        content_type,
+        content_length: Some(length as i64),
+        checksum_crc32: Some(checksum),
+        encryption: requested.encryption,
+        last_modified_ms: requested.last_modified_ms,
+      },
+    };
+    let stored = match target {
+      SinkTarget::Fs(mut upload) => {
+        upload
+          .file
+          .flush()
+          .map_err(|err| RuntimeError::io("StorageRuntime fs write object failed", err))?;
+        if let Some(parent) = upload.target_path.parent() {
+          fs::create_dir_all(parent).map_err(|err| RuntimeError::io("StorageRuntime fs create dir failed", err))?;
+        }
+        fs::rename(&upload.temp_path, &upload.target_path)
+          .map_err(|err| RuntimeError::io("StorageRuntime fs write object failed", err))?;
+        let metadata = metadata.into_object_metadata(system_time_ms(SystemTime::now())?);
+        write_fs_metadata(&upload.target_path, &metadata)?;
+        Ok(metadata)
+      }
+      SinkTarget::Buffered { config, scope, body } => assetpack::put(&config, &scope, &key, body, metadata).await,
+      SinkTarget::Object(mut upload) => {
+        let result = upload.commit(&key, metadata).await;
+        if result.is_err() {
+          let _ = upload.abort(&key).await;
+        }
+        result
+      }
+    };
+    plaintext_metadata(stored?)
+  }
+}
+
+/// Copy `key` to `target_key` exactly as stored; sealed bodies are not opened
+/// and keep their encryption metadata, so they only decrypt again under their
+/// original key. The target verifies the length and, when the source reports
+/// one, the checksum before the copy becomes visible. Returns the source
+/// metadata, or `None` when the source is gone.
+pub(super) async fn copy_object(
+  source: &StorageBackendConfig,
+  target: &StorageBackendConfig,
+  scope: &str,
+  key: &str,
+  target_key: &str,
+) -> RuntimeResult<Option<ObjectMetadata>> {
+  let Some(stream) = ObjectReadStream::open(source, scope, key, None).await? else {
+    return Ok(None);
+  };
+  let metadata = stream.metadata.clone();
+  let requested = ObjectPutMetadata {
+    content_type: Some(metadata.content_type.clone()),
+    content_length: Some(metadata.content_length),
+    checksum_crc32: metadata.checksum_crc32.clone(),
+    encryption: metadata.encryption.clone(),
+    last_modified_ms: Some(metadata.last_modified_ms).filter(|last_modified_ms| *last_modified_ms > 0),
+  };
+  let mut sink = ObjectSink::open(target, scope, target_key, requested, None)?;
+  if let Some(mut source) = stream.source.into_inner()
+    && let Err(err) = pump(&mut source, &mut sink).await
+  {
+    let _ = sink.target.abort(target_key).await;
+    return Err(err);
+  }
+  sink.finish().await?;
+  Ok(Some(metadata))
+}
+
+async fn pump(source: &mut ReadSource, sink: &mut ObjectSink) -> RuntimeResult<()> {
+  while let Some(chunk) = source.next_chunk().await? {
+    sink.write(&chunk).await?;
+  }
+  Ok(())
+}
+
+fn stream_part_size(min_part_size: Option<u64>) -> usize {
+  let part_size = min_part_size
+    .unwrap_or(DEFAULT_STREAM_PART_BYTES)
+    .max(MIN_STREAM_PART_BYTES);
+  usize::try_from(part_size).unwrap_or(usize::MAX)
+}
+
+/// An object opened for chunked writing. Object storage backends upload a
+/// multipart part whenever a full part is buffered; objects smaller than one
+/// part are written with a single put on `finish()`.
+#[napi_derive::napi]
+pub struct ObjectWriteStream {
+  provider: String,
+  sink: Mutex<Option<ObjectSink>>,
+}
+
+#[napi_derive::napi]
+impl ObjectWriteStream {
+  #[napi]
+  pub async fn write(&self, chunk: Buffer) -> napi::Result<()> {
+    let mut guard = self.sink.lock().await;
+    let sink = guard
+      .as_mut()
+      .ok_or_else(|| napi_error("ObjectWriteStream is already closed"))?;
+    if let Err(err) = sink.write(&chunk).await {
+      if let Some(sink) = guard.take() {
+        let _ = sink.target.abort(&sink.key).await;
+      }
+      return Err(err.into());
+    }
+    Ok(())
+  }
+
+  /// Commit the object. A `contentLength` or `checksumCRC32` given when the
+  /// stream was opened is verified here; on mismatch nothing is stored.
+  #[napi]
+  pub async fn finish(&self) -> napi::Result<RuntimeObjectMetadata> {
+    let sink = self
+      .sink
+      .lock()
+      .await
+      .take()
+      .ok_or_else(|| napi_error("ObjectWriteStream is already closed"))?;
+    measured(&self.provider, "put_stream", sink.finish())
+      .await
+      .map(Into::into)
+      .map_err(napi::Error::from)
+  }
+
+  /// Discard everything written so far.
+  #[napi]
+  pub async fn abort(&self) -> napi::Result<()> {
+    let sink = self.sink.lock().await.take();
+    if let Some(sink) = sink {
+      sink.target.abort(&sink.key).await?;
+    }
+    Ok(())
+  }
+}
+
+impl StorageRuntime {
+  async fn open_read_stream(
+    &self,
+    backend: &StorageBackendConfig,
+    scope: &str,
+    key: &str,
+    range: Option<ByteRange>,
+  ) -> RuntimeResult<Option<ObjectReadStream>> {
+    // Sealed objects are only recognised by their stored metadata, so the
+    // plain stream is opened first and dropped before any body is read.
+    match ObjectReadStream::open(backend, scope, key, range).await? {
+      Some(stream) if stream.metadata.encryption.is_some() => {
+        let stored = stream.metadata.clone();
+        drop(stream);
+        let cipher = self.blob_cipher(blob_workspace(key)?, false).await?;
+        ObjectReadStream::open_sealed(backend, scope, key, &cipher, stored, range).await
+      }
+      stream => Ok(stream),
+    }
+  }
+
+  pub(super) async fn read_sealed_range(
+    &self,
+    backend: &StorageBackendConfig,
+    scope: &str,
+    key: &str,
+    stored: ObjectMetadata,
+    range: ByteRange,
+  ) -> RuntimeResult<Option<ObjectRangeGetResult>> {
+    let cipher = self.blob_cipher(blob_workspace(key)?, false).await?;
+    let Some(stream) = ObjectReadStream::open_sealed(backend, scope, key, &cipher, stored, Some(range)).await? else {
+      return Ok(None);
+    };
+    let metadata = stream.metadata.clone();
+    let range = stream.range;
+    Ok(Some(ObjectRangeGetResult {
+      body: stream.collect().await?,
+      metadata,
+      range,
+    }))
+  }
+}
+
+#[napi_derive::napi]
+impl StorageRuntime {
+  /// Open an object for chunked reading, optionally limited to an HTTP `Range`
+  /// header value. Resolves to `null` when the object does not exist.
+  #[napi]
+  pub async fn open_object_read_stream(
+    &self,
+    scope: String,
+    key: String,
+    range: Option<String>,
+  ) -> napi::Result<Option<ObjectReadStream>> {
+    let backend = self.backend_for_scope(&scope)?;
+    let range = range.as_deref().map(ByteRange::parse).transpose()?;
+    let stream = measured(
+      backend.provider(),
+      "get_stream",
+      self.open_read_stream(&backend, &scope, &key, range),
+    )
+    .await?;
+    if stream.is_none() {
+      self.record_quarantined_read(&scope, &key).await;
+    }
+    Ok(stream)
+  }
+
+  /// Open an object for chunked writing. The object becomes visible only once
+  /// `finish()` resolves.
+  #[napi]
+  pub async fn open_object_write_stream(
+    &self,
+    scope: String,
+    key: String,
+    metadata: Option<RuntimeObjectStoragePutOptions>,
+  ) -> napi::Result<ObjectWriteStream> {
+    let backend = self.backend_for_scope(&scope)?;
+    let sealer = if self.seals_scope(&scope)? {
+      Some(self.blob_cipher(blob_workspace(&key)?, true).await?.sealer(&key)?)
+    } else {
+      None
+    };
+    let sink = ObjectSink::open(
+      &backend,
+      &scope,
+      &key,
+      metadata.map(Into::into).unwrap_or_default(),
+      sealer,
+    )?;
+    Ok(ObjectWriteStream {
+      provider: backend.provider().to_string(),
+      sink: Mutex::new(Some(sink)),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{
+    super::{WorkspaceDataKeys, fs_head},
+    *,
+  };
+
+  fn fs_backend(root: &std::path::Path) -> StorageBackendConfig {
+    StorageBackendConfig::Fs(FsStorageConfig {
+      provider: "fs".to_string(),
+      root: root.to_string_lossy().to_string(),
+      bucket: "bucket".to_string(),
+    })
+  }
+
+  async fn read_all(stream: &ObjectReadStream) -> Vec<u8> {
+    let mut body = Vec::new();
+    let mut source = stream.source.lock().await;
+    while let Some(source) = source.as_mut()
+      && let Some(chunk) = source.next_chunk().await.unwrap()
+    {
+      body.extend_from_slice(&chunk);
+    }
+    body
+  }
+
+  #[tokio::test]
+  async fn fs_write_stream_commits_on_finish_and_reads_ranges() {
+    let temp = tempfile::tempdir().unwrap();
+    let backend = fs_backend(temp.path());
+    let body = (0..STREAM_CHUNK_BYTES * 2 + 10).map(|i| i as u8).collect::<Vec<_>>();
+
+    let mut sink = ObjectSink::open(&backend, "blob", "workspace/media", ObjectPutMetadata::default(), None).unwrap();
+    for chunk in body.chunks(100_000) {
+      sink.write(chunk).await.unwrap();
+    }
+    assert!(!temp.path().join("bucket/workspace/media").exists());
+    let metadata = sink.finish().await.unwrap();
+    assert_eq!(metadata.content_length, body.len() as i64);
+    assert_eq!(
+      metadata.checksum_crc32.as_deref(),
+      Some(format!("{:x}", crc32fast::hash(&body)).as_str())
+    );
+    assert_eq!(
+      fs::read_dir(temp.path().join("bucket").join(FS_UPLOADS_DIR))
+        .unwrap()
+        .count(),
+      0
+    );
+
+    let stream = ObjectReadStream::open(&backend, "blob", "workspace/media", None)
+      .await
+      .unwrap()
+      .unwrap();
+    assert!(stream.satisfiable);
+    assert_eq!(read_all(&stream).await, body);
+
+    let range = ByteRange::parse("bytes=-300000").unwrap();
+    let stream = ObjectReadStream::open(&backend, "blob", "workspace/media", Some(range))
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(stream.metadata.content_length, body.len() as i64);
+    assert_eq!(stream.range_start(), Some((body.len() - 300_000) as i64));
+    assert_eq!(read_all(&stream).await, &body[body.len() - 300_000..]);
+
+    let range = ByteRange::parse("bytes=999999999-").unwrap();
+    let stream = ObjectReadStream::open(&backend, "blob", "workspace/media", Some(range))
+      .await
+      .unwrap()
+      .unwrap();
+    assert!(!stream.satisfiable);
+    assert!(read_all(&stream).await.is_empty());
+
+    assert!(
+      ObjectReadStream::open(&backend, "blob", "workspace/missing", None)
+        .await
+        .unwrap()
+        .is_none()
+    );
+  }
+
+  #[tokio::test]
+  async fn fs_write_stream_discards_body_on_checksum_mismatch() {
+    let temp = tempfile::tempdir().unwrap();
+    let backend = fs_backend(temp.path());
+    let requested = ObjectPutMetadata {
+      checksum_crc32: Some("deadbeef".to_string()),
+      ..Default::default()
+    };
+
+    let mut sink = ObjectSink::open(&backend, "blob", "workspace/blob", requested, None).unwrap();
+    sink.write(b"hello").await.unwrap();
+    assert!(sink.finish().await.is_err());
+    assert!(!temp.path().join("bucket/workspace/blob").exists());
+    assert_eq!(
+      fs::read_dir(temp.path().join("bucket").join(FS_UPLOADS_DIR))
+        .unwrap()
+        .count(),
+      0
+    );
+  }
+
+  #[tokio::test]
+  async fn sealed_fs_stream_round_trips_ranges_without_exposing_plaintext() {
+    let temp = tempfile::tempdir().unwrap();
+    let backend = fs_backend(temp.path());
+    let cipher = BlobCipher::from_data_keys(
+      "workspace",
+      WorkspaceDataKeys {
+        active: Some(1),
+        keys: [(1, vec![7; 32])].into_iter().collect(),
+      },
+    )
+    .unwrap();
+    let body = (0..200_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+
+    let sealer = cipher.sealer("workspace/sealed").unwrap();
+    let mut sink = ObjectSink::open(
+      &backend,
+      "blob",
+      "workspace/sealed",
+      ObjectPutMetadata::default(),
+      Some(sealer),
+    )
+    .unwrap();
+    for chunk in body.chunks(70_000) {
+      sink.write(chunk).await.unwrap();
+    }
+    let metadata = sink.finish().await.unwrap();
+    assert_eq!(metadata.content_length, body.len() as i64);
+    assert_eq!(metadata.checksum_crc32, None);
+    let stored = fs::read(temp.path().join("bucket/workspace/sealed")).unwrap();
+    assert_eq!(stored.len() as u64, sealed_length(body.len() as u64));
+    assert!(!stored.windows(64).any(|window| window == &body[1000..1064]));
+
+    let head = fs_head_metadata(&backend, "workspace/sealed");
+    let stream = ObjectReadStream::open_sealed(&backend, "blob", "workspace/sealed", &cipher, head.clone(), None)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(read_all(&stream).await, body);
+
+    let range = ByteRange::parse("bytes=70000-140000").unwrap();
+    let stream = ObjectReadStream::open_sealed(&backend, "blob", "workspace/sealed", &cipher, head, Some(range))
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(stream.metadata.content_length, body.len() as i64);
+    assert_eq!(stream.range_start(), Some(70_000));
+    assert_eq!(read_all(&stream).await, &body[70_000..=140_000]);
+  }
+
+  #[tokio::test]
+  async fn copy_object_preserves_body_and_metadata() {
+    let source_root = tempfile::tempdir().unwrap();
+    let target_root = tempfile::tempdir().unwrap();
+    let source = fs_backend(source_root.path());
+    let target = fs_backend(target_root.path());
+    let body = (0..STREAM_CHUNK_BYTES + 10)
+      .map(|i| (i % 251) as u8)
+      .collect::<Vec<_>>();
+    let requested = ObjectPutMetadata {
+      content_type: Some("image/png".to_string()),
+      last_modified_ms: Some(1_700_000_000_000),
+      ..Default::default()
+    };
+    let mut sink = ObjectSink::open(&source, "blob", "workspace/copied", requested, None).unwrap();
+    sink.write(&body).await.unwrap();
+    sink.finish().await.unwrap();
+
+    let copied = copy_object(&source, &target, "blob", "workspace/copied", "workspace/copied")
+      .await
+      .unwrap()
+      .unwrap();
+    let head = fs_head_metadata(&target, "workspace/copied");
+    assert_eq!(head.content_type, "image/png");
+    assert_eq!(head.content_length, body.len() as i64);
+    assert_eq!(head.last_modified_ms, 1_700_000_000_000);
+    assert_eq!(head.checksum_crc32, copied.checksum_crc32);
+    assert_eq!(
+      fs::read(target_root.path().join("bucket/workspace/copied")).unwrap(),
+      body
+    );
+
+    assert!(
+      copy_object(&source, &target, "blob", "workspace/missing", "workspace/missing")
+        .await
+        .unwrap()
+        .is_none()
+    );
+  }
+
+  fn fs_head_metadata(backend: &StorageBackendConfig, key: &str) -> ObjectMetadata {
+    let StorageBackendConfig::Fs(config) = backend else {
+      unreachable!("tests use the fs backend");
+    };
+    fs_head(config, key).unwrap().unwrap()
+  }
+
+  #[test]
+  fn stream_parts_respect_the_s3_minimum() {
+    assert_eq!(stream_part_size(None), DEFAULT_STREAM_PART_BYTES as usize);
+    assert_eq!(stream_part_size(Some(1024)), MIN_STREAM_PART_BYTES as usize);
+    assert_eq!(stream_part_size(Some(16 << 20)), 16 << 20);
+  }
+}
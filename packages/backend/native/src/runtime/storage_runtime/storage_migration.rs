@@ -0,0 +1,715 @@
+use std::{future::Future, pin::Pin};
+
+use sqlx::{FromRow, PgPool};
+
+use super::{
+  RuntimeError, RuntimeResult, RuntimeStorageMigrationResult, StorageBackendConfig, StorageRuntime, measured,
+  napi_error,
+  object_storage::{
+    StorageProviderConfig,
+    types::{ObjectListPage, ObjectMetadata},
+  },
+  object_stream::copy_object,
+};
+
+// Migrations move the blob scope; other scopes are small enough to rebuild.
+const MIGRATION_SCOPE: &str = "blob";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MigrationPhase {
+  Copy,
+  Verify,
+}
+
+impl MigrationPhase {
+  fn as_str(self) -> &'static str {
+    match self {
+      Self::Copy => "copy",
+      Self::Verify => "verify",
+    }
+  }
+}
+
+enum CopyOutcome {
+  Copied,
+  Skipped,
+  Mismatched,
+}
+
+fn parse_backend(config_json: &str, label: &str) -> RuntimeResult<StorageBackendConfig> {
+  let config: StorageProviderConfig = serde_json::from_str(config_json)
+    .map_err(|err| RuntimeError::json(format!("invalid storage migration {label} config"), err))?;
+  StorageBackendConfig::from_provider_config(Some(config))?
+    .ok_or_else(|| RuntimeError::config(format!("storage migration {label} config is missing")))
+}
+
+/// Where a backend keeps its objects. fs buckets are only unique together
+/// with their root directory.
+fn backend_location(backend: &StorageBackendConfig) -> String {
+  match backend {
+    StorageBackendConfig::Fs(config) | StorageBackendConfig::Assetpack(config) => {
+      format!(
+        "{}:{}/{}",
+        config.provider,
+        config.root.trim_end_matches('/'),
+        config.bucket
+      )
+    }
+    StorageBackendConfig::S3(config) => format!("{}:{}", config.provider, config.bucket),
+  }
+}
+
+fn checkpoint_kind(dry_run: bool) -> &'static str {
+  if dry_run {
+    "storage_migration_dry_run"
+  } else {
+    "storage_migration"
+  }
+}
+
+/// Whether `target` holds the same stored object as `source`. Checksums are
+/// only compared when both providers report one (see `checksums_compared`);
+/// sealed objects must carry the same data key.
+fn same_object(source: &ObjectMetadata, target: &ObjectMetadata) -> bool {
+  source.content_length == target.content_length
+    && source.encryption == target.encryption
+    && match (&source.checksum_crc32, &target.checksum_crc32) {
+      (Some(source), Some(target)) => source.eq_ignore_ascii_case(target),
+      _ => true,
+    }
+}
+
+fn checksums_compared(source: &ObjectMetadata, target: &ObjectMetadata) -> bool {
+  source.checksum_crc32.is_some() && target.checksum_crc32.is_some()
+}
+
+#[derive(FromRow)]
+struct MigrationCheckpoint {
+  status: String,
+  last_key: Option<String>,
+  cursor: serde_json::Value,
+}
+
+impl MigrationCheckpoint {
+  fn phase(&self) -> MigrationPhase {
+    match self.cursor.get("phase").and_then(|value| value.as_str()) {
+      Some("verify") => MigrationPhase::Verify,
+      _ => MigrationPhase::Copy,
+    }
+  }
+
+  fn continuation_token(&self) -> Option<String> {
+    self
+      .cursor
+      .get("continuationToken")
+      .and_then(|value| value.as_str())
+      .map(ToString::to_string)
+  }
+}
+
+type MigrationFuture<'a, T> = Pin<Box<dyn Future<Output = RuntimeResult<T>> + Send + 'a>>;
+
+/// The bucket side of a migration: listing the source and moving objects to
+/// the target.
+trait MigrationBuckets: Sync {
+  fn list_source(
+    &self,
+    continuation_token: Option<String>,
+    start_after: Option<String>,
+    max_keys: i32,
+  ) -> MigrationFuture<'_, ObjectListPage>;
+
+  fn head_source<'a>(&'a self, key: &'a str) -> MigrationFuture<'a, Option<ObjectMetadata>>;
+
+  fn head_target<'a>(&'a self, key: &'a str) -> MigrationFuture<'a, Option<ObjectMetadata>>;
+
+  /// Copy `key` as stored and return the metadata it was copied with, or
+  /// `None` when the source no longer has it.
+  fn copy<'a>(&'a self, key: &'a str) -> MigrationFuture<'a, Option<ObjectMetadata>>;
+}
+
+/// Where a migration records its progress between calls.
+trait MigrationCheckpointStore: Sync {
+  fn load(&self) -> MigrationFuture<'_, Option<MigrationCheckpoint>>;
+
+  fn save<'a>(&'a self, update: CheckpointUpdate<'a>) -> MigrationFuture<'a, ()>;
+}
+
+struct BackendBuckets<'a> {
+  source: &'a StorageBackendConfig,
+  target: &'a StorageBackendConfig,
+}
+
+impl MigrationBuckets for BackendBuckets<'_> {
+  fn list_source(
+    &self,
+    continuation_token: Option<String>,
+    start_after: Option<String>,
+    max_keys: i32,
+  ) -> MigrationFuture<'_, ObjectListPage> {
+    Box::pin(measured(
+      self.source.provider(),
+      "list",
+      self
+        .source
+        .list_page(MIGRATION_SCOPE, None, continuation_token, start_after, max_keys),
+    ))
+  }
+
+  fn head_source<'a>(&'a self, key: &'a str) -> MigrationFuture<'a, Option<ObjectMetadata>> {
+    Box::pin(head(self.source, key))
+  }
+
+  fn head_target<'a>(&'a self, key: &'a str) -> MigrationFuture<'a, Option<ObjectMetadata>> {
+    Box::pin(head(self.target, key))
+  }
+
+  fn copy<'a>(&'a self, key: &'a str) -> MigrationFuture<'a, Option<ObjectMetadata>> {
+    Box::pin(measured(
+      self.target.provider(),
+      "copy",
+      copy_object(self.source, self.target, MIGRATION_SCOPE, key, key),
+    ))
+  }
+}
+
+struct PgCheckpoints<'a> {
+  pool: &'a PgPool,
+  kind: &'a str,
+  scope: &'a str,
+  metadata: serde_json::Value,
+}
+
+impl MigrationCheckpointStore for PgCheckpoints<'_> {
+  fn load(&self) -> MigrationFuture<'_, Option<MigrationCheckpoint>> {
+    Box::pin(load_checkpoint(self.pool, self.kind, self.scope))
+  }
+
+  fn save<'a>(&'a self, update: CheckpointUpdate<'a>) -> MigrationFuture<'a, ()> {
+    Box::pin(upsert_checkpoint(
+      self.pool,
+      self.kind,
+      self.scope,
+      update,
+      self.metadata.clone(),
+    ))
+  }
+}
+
+async fn load_checkpoint(pool: &PgPool, kind: &str, scope: &str) -> RuntimeResult<Option<MigrationCheckpoint>> {
+  sqlx::query_as::<_, MigrationCheckpoint>(
+    r#"
+    SELECT status, last_key, cursor
+    FROM blob_reconciliation_checkpoints
+    WHERE kind = $1 AND scope = $2
+    "#,
+  )
+  .bind(kind)
+  .bind(scope)
+  .fetch_optional(pool)
+  .await
+  .map_err(|err| RuntimeError::database("Storage migration checkpoint load failed", err))
+}
+
+struct CheckpointUpdate<'a> {
+  phase: MigrationPhase,
+  last_key: Option<&'a str>,
+  continuation_token: Option<&'a str>,
+  completed: bool,
+}
+
+impl CheckpointUpdate<'_> {
+  fn status(&self) -> &'static str {
+    if self.completed { "completed" } else { "running" }
+  }
+
+  fn cursor(&self) -> serde_json::Value {
+    serde_json::json!({
+      "phase": self.phase.as_str(),
+      "lastKey": self.last_key,
+      "continuationToken": self.continuation_token,
+    })
+  }
+}
+
+async fn upsert_checkpoint(
+  pool: &PgPool,
+  kind: &str,
+  scope: &str,
+  update: CheckpointUpdate<'_>,
+  metadata: serde_json::Value,
+) -> RuntimeResult<()> {
+  sqlx::query(
+    r#"
+    INSERT INTO blob_reconciliation_checkpoints
+      (kind, scope, status, cursor, last_key, completed_at, metadata)
+    VALUES ($1, $2, $3, $4, $5, CASE WHEN $6 THEN CURRENT_TIMESTAMP ELSE NULL END, $7)
+    ON CONFLICT (kind, scope) DO UPDATE
+      SET status = EXCLUDED.status,
+          cursor = EXCLUDED.cursor,
+          last_key = EXCLUDED.last_key,
+          completed_at = CASE WHEN $6 THEN CURRENT_TIMESTAMP ELSE NULL END,
+          updated_at = CURRENT_TIMESTAMP,
+          metadata = EXCLUDED.metadata
+    "#,
+  )
+  .bind(kind)
+  .bind(scope)
+  .bind(update.status())
+  .bind(update.cursor())
+  .bind(update.last_key)
+  .bind(update.completed)
+  .bind(metadata)
+  .execute(pool)
+  .await
+  .map_err(|err| RuntimeError::database("Storage migration checkpoint write failed", err))?;
+  Ok(())
+}
+
+async fn head(backend: &StorageBackendConfig, key: &str) -> RuntimeResult<Option<ObjectMetadata>> {
+  measured(backend.provider(), "head", backend.head(MIGRATION_SCOPE, key)).await
+}
+
+/// Copy one listed key unless the target already holds it. A dry run stops
+/// after the comparison and reports what would be copied.
+async fn migrate_object(buckets: &impl MigrationBuckets, key: &str, dry_run: bool) -> RuntimeResult<CopyOutcome> {
+  let Some(source_metadata) = buckets.head_source(key).await? else {
+    return Ok(CopyOutcome::Skipped);
+  };
+  if let Some(target_metadata) = buckets.head_target(key).await?
+    && same_object(&source_metadata, &target_metadata)
+  {
+    return Ok(CopyOutcome::Skipped);
+  }
+  if dry_run {
+    return Ok(CopyOutcome::Copied);
+  }
+  let Some(copied) = buckets.copy(key).await? else {
+    return Ok(CopyOutcome::Skipped);
+  };
+  match buckets.head_target(key).await? {
+    Some(target_metadata) if same_object(&copied, &target_metadata) => Ok(CopyOutcome::Copied),
+    _ => Ok(CopyOutcome::Mismatched),
+  }
+}
+
+/// Run one page of a migration from the stored checkpoint and record where
+/// the next call resumes.
+async fn migrate_page(
+  buckets: &impl MigrationBuckets,
+  checkpoints: &impl MigrationCheckpointStore,
+  page_limit: i32,
+  dry_run: bool,
+) -> RuntimeResult<RuntimeStorageMigrationResult> {
+  let checkpoint = checkpoints
+    .load()
+    .await?
+    .filter(|checkpoint| checkpoint.status != "completed");
+  let phase = checkpoint
+    .as_ref()
+    .map(MigrationCheckpoint::phase)
+    .unwrap_or(MigrationPhase::Copy);
+  let page = buckets
+    .list_source(
+      checkpoint.as_ref().and_then(MigrationCheckpoint::continuation_token),
+      checkpoint.as_ref().and_then(|checkpoint| checkpoint.last_key.clone()),
+      page_limit,
+    )
+    .await?;
+  // fs and assetpack pages carry no continuation token; a full page may
+  // still have more keys after it.
+  let exhausted = page.next_continuation_token.is_none() && page.entries.len() < page_limit as usize;
+
+  let mut result = RuntimeStorageMigrationResult {
+    phase: phase.as_str().to_string(),
+    dry_run,
+    scanned: 0,
+    copied: 0,
+    skipped: 0,
+    failed: 0,
+    missing_keys: Vec::new(),
+    mismatched_keys: Vec::new(),
+    failed_keys: Vec::new(),
+    unverified_checksums: 0,
+    completed: false,
+  };
+  for entry in &page.entries {
+    result.scanned += 1;
+    match phase {
+      MigrationPhase::Copy => match migrate_object(buckets, &entry.key, dry_run).await {
+        Ok(CopyOutcome::Copied) => result.copied += 1,
+        Ok(CopyOutcome::Skipped) => result.skipped += 1,
+        Ok(CopyOutcome::Mismatched) => {
+          result.failed += 1;
+          result.mismatched_keys.push(entry.key.clone());
+        }
+        Err(_) => {
+          result.failed += 1;
+          result.failed_keys.push(entry.key.clone());
+        }
+      },
+      MigrationPhase::Verify => {
+        let (source_metadata, target_metadata) = match (
+          buckets.head_source(&entry.key).await,
+          buckets.head_target(&entry.key).await,
+        ) {
+          (Ok(source_metadata), Ok(target_metadata)) => (source_metadata, target_metadata),
+          _ => {
+            result.failed += 1;
+            result.failed_keys.push(entry.key.clone());
+            continue;
+          }
+        };
+        match (source_metadata, target_metadata) {
+          // Deleted from the source since it was listed.
+          (None, _) => result.skipped += 1,
+          (Some(_), None) => result.missing_keys.push(entry.key.clone()),
+          (Some(source_metadata), Some(target_metadata)) if !same_object(&source_metadata, &target_metadata) => {
+            result.mismatched_keys.push(entry.key.clone())
+          }
+          (Some(source_metadata), Some(target_metadata)) => {
+            if !checksums_compared(&source_metadata, &target_metadata) {
+              result.unverified_checksums += 1;
+            }
+          }
+        }
+      }
+    }
+  }
+
+  // A dry run has nothing on the target to verify.
+  let update = if exhausted && phase == MigrationPhase::Copy && !dry_run {
+    CheckpointUpdate {
+      phase: MigrationPhase::Verify,
+      last_key: None,
+      continuation_token: None,
+      completed: false,
+    }
+  } else {
+    result.completed = exhausted;
+    CheckpointUpdate {
+      phase,
+      last_key: page.entries.last().map(|entry| entry.key.as_str()),
+      continuation_token: page.next_continuation_token.as_deref(),
+      completed: exhausted,
+    }
+  };
+  checkpoints.save(update).await?;
+  Ok(result)
+}
+
+#[napi_derive::napi]
+impl StorageRuntime {
+  /// Copy every blob from one provider config to another, e.g. `fs` to S3.
+  /// Objects are copied as stored, keeping content type, last-modified time
+  /// and encryption metadata. Once all keys are copied a verification pass
+  /// reports keys missing or different on the target, and how many only
+  /// matched on length because a provider has no checksum. Progress is
+  /// checkpointed; call repeatedly until `completed` is true. A dry run only
+  /// counts the keys it would copy.
+  #[napi]
+  pub async fn migrate_storage(
+    &self,
+    source_config_json: String,
+    target_config_json: String,
+    batch_limit: i64,
+    dry_run: Option<bool>,
+  ) -> napi::Result<RuntimeStorageMigrationResult> {
+    if batch_limit <= 0 {
+      return Err(napi_error("storage migration batch limit must be positive"));
+    }
+    let page_limit =
+      i32::try_from(batch_limit).map_err(|_| napi_error("storage migration batch limit exceeds i32::MAX"))?;
+    let dry_run = dry_run.unwrap_or(false);
+    let source = parse_backend(&source_config_json, "source")?;
+    let target = parse_backend(&target_config_json, "target")?;
+    let source_location = backend_location(&source);
+    let target_location = backend_location(&target);
+    if source_location == target_location {
+      return Err(RuntimeError::invalid_input("storage migration source and target are the same bucket").into());
+    }
+
+    let scope = format!("{source_location}->{target_location}");
+    let pool = self.pool().await?;
+    let checkpoints = PgCheckpoints {
+      pool: &pool,
+      kind: checkpoint_kind(dry_run),
+      scope: &scope,
+      metadata: serde_json::json!({ "source": source_location, "target": target_location }),
+    };
+    let buckets = BackendBuckets {
+      source: &source,
+      target: &target,
+    };
+    Ok(migrate_page(&buckets, &checkpoints, page_limit, dry_run).await?)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Mutex,
+  };
+
+  use super::{
+    super::{
+      FsStorageConfig,
+      object_storage::types::{ObjectEncryption, ObjectListEntry},
+    },
+    *,
+  };
+
+  fn metadata(content_length: i64, checksum: Option<&str>) -> ObjectMetadata {
+    ObjectMetadata {
+      content_type: "application/octet-stream".to_string(),
+      content_length,
+      last_modified_ms: 0,
+      checksum_crc32: checksum.map(ToString::to_string),
+      encryption: None,
+    }
+  }
+
+  #[test]
+  fn objects_match_on_length_checksum_and_encryption() {
+    assert!(same_object(&metadata(5, Some("abc")), &metadata(5, Some("ABC"))));
+    assert!(same_object(&metadata(5, Some("abc")), &metadata(5, None)));
+    assert!(!same_object(&metadata(5, Some("abc")), &metadata(5, Some("def"))));
+    assert!(!same_object(&metadata(5, None), &metadata(6, None)));
+    assert!(checksums_compared(&metadata(5, Some("abc")), &metadata(5, Some("abc"))));
+    assert!(!checksums_compared(&metadata(5, Some("abc")), &metadata(5, None)));
+
+    let mut sealed = metadata(5, None);
+    sealed.encryption = Some(ObjectEncryption {
+      key_id: "key".to_string(),
+      key_version: 1,
+    });
+    assert!(!same_object(&sealed, &metadata(5, None)));
+  }
+
+  #[test]
+  fn fs_locations_include_the_root() {
+    let fs = |root: &str| {
+      StorageBackendConfig::Fs(FsStorageConfig {
+        provider: "fs".to_string(),
+        root: root.to_string(),
+        bucket: "blobs".to_string(),
+      })
+    };
+    assert_eq!(backend_location(&fs("/data/")), "fs:/data/blobs");
+    assert_ne!(backend_location(&fs("/data")), backend_location(&fs("/backup")));
+    assert!(
+      parse_backend(
+        r#"{"provider":"fs","bucket":"blobs","config":{"path":"/data"}}"#,
+        "source"
+      )
+      .is_ok()
+    );
+    assert!(parse_backend(r#"{"provider":"ftp","bucket":"blobs"}"#, "source").is_err());
+  }
+
+  #[test]
+  fn checkpoints_resume_in_the_recorded_phase() {
+    let checkpoint = |cursor| MigrationCheckpoint {
+      status: "running".to_string(),
+      last_key: None,
+      cursor,
+    };
+    assert_eq!(checkpoint(serde_json::json!({})).phase(), MigrationPhase::Copy);
+    let verify = checkpoint(serde_json::json!({ "phase": "verify", "continuationToken": "next" }));
+    assert_eq!(verify.phase(), MigrationPhase::Verify);
+    assert_eq!(verify.continuation_token().as_deref(), Some("next"));
+    assert_ne!(checkpoint_kind(true), checkpoint_kind(false));
+  }
+
+  /// Two in-memory buckets. Listing pages S3-style with a continuation
+  /// token; copies of `failing` keys error and copies of `corrupting` keys
+  /// land with a different checksum.
+  #[derive(Default)]
+  struct ScriptedBuckets {
+    source: Mutex<BTreeMap<String, ObjectMetadata>>,
+    target: Mutex<BTreeMap<String, ObjectMetadata>>,
+    failing: HashSet<String>,
+    corrupting: HashSet<String>,
+    listed_after: Mutex<Vec<Option<String>>>,
+  }
+
+  impl MigrationBuckets for ScriptedBuckets {
+    fn list_source(
+      &self,
+      continuation_token: Option<String>,
+      start_after: Option<String>,
+      max_keys: i32,
+    ) -> MigrationFuture<'_, ObjectListPage> {
+      let after = continuation_token.or(start_after);
+      self.listed_after.lock().unwrap().push(after.clone());
+      let source = self.source.lock().unwrap();
+      let mut keys = source
+        .iter()
+        .filter(|(key, _)| after.as_ref().is_none_or(|after| *key > after))
+        .map(|(key, metadata)| ObjectListEntry {
+          key: key.clone(),
+          content_length: metadata.content_length,
+          last_modified_ms: metadata.last_modified_ms,
+        })
+        .peekable();
+      let entries: Vec<_> = keys.by_ref().take(max_keys as usize).collect();
+      let next_continuation_token = keys.peek().and_then(|_| entries.last()).map(|entry| entry.key.clone());
+      Box::pin(async move {
+        Ok(ObjectListPage {
+          entries,
+          next_continuation_token,
+        })
+      })
+    }
+
+    fn head_source<'a>(&'a self, key: &'a str) -> MigrationFuture<'a, Option<ObjectMetadata>> {
+      let metadata = self.source.lock().unwrap().get(key).cloned();
+      Box::pin(async move { Ok(metadata) })
+    }
+
+    fn head_target<'a>(&'a self, key: &'a str) -> MigrationFuture<'a, Option<ObjectMetadata>> {
+      let metadata = self.target.lock().unwrap().get(key).cloned();
+      Box::pin(async move { Ok(metadata) })
+    }
+
+    fn copy<'a>(&'a self, key: &'a str) -> MigrationFuture<'a, Option<ObjectMetadata>> {
+      if self.failing.contains(key) {
+        return Box::pin(async move { Err(RuntimeError::invalid_state(format!("copy of {key} failed"))) });
+      }
+      let copied = self.source.lock().unwrap().get(key).cloned();
+      if let Some(copied) = &copied {
+        let mut stored = copied.clone();
+        if self.corrupting.contains(key) {
+          stored.checksum_crc32 = Some("corrupted".to_string());
+        }
+        self.target.lock().unwrap().insert(key.to_string(), stored);
+      }
+      Box::pin(async move { Ok(copied) })
+    }
+  }
+
+  #[derive(Default)]
+  struct MemoryCheckpoints {
+    checkpoint: Mutex<Option<MigrationCheckpoint>>,
+  }
+
+  impl MigrationCheckpointStore for MemoryCheckpoints {
+    fn load(&self) -> MigrationFuture<'_, Option<MigrationCheckpoint>> {
+      let checkpoint = self
+        .checkpoint
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|checkpoint| MigrationCheckpoint {
+          status: checkpoint.status.clone(),
+          last_key: checkpoint.last_key.clone(),
+          cursor: checkpoint.cursor.clone(),
+        });
+      Box::pin(async move { Ok(checkpoint) })
+    }
+
+    fn save<'a>(&'a self, update: CheckpointUpdate<'a>) -> MigrationFuture<'a, ()> {
+      *self.checkpoint.lock().unwrap() = Some(MigrationCheckpoint {
+        status: update.status().to_string(),
+        last_key: update.last_key.map(ToString::to_string),
+        cursor: update.cursor(),
+      });
+      Box::pin(async move { Ok(()) })
+    }
+  }
+
+  fn objects(entries: &[(&str, ObjectMetadata)]) -> Mutex<BTreeMap<String, ObjectMetadata>> {
+    Mutex::new(
+      entries
+        .iter()
+        .map(|(key, metadata)| (key.to_string(), metadata.clone()))
+        .collect(),
+    )
+  }
+
+  fn keys(keys: &[&str]) -> Vec<String> {
+    keys.iter().map(ToString::to_string).collect()
+  }
+
+  #[tokio::test]
+  async fn migration_resumes_from_checkpoints_and_reports_failed_and_mismatched_keys() {
+    let buckets = ScriptedBuckets {
+      source: objects(&[
+        ("a", metadata(1, Some("aa"))),
+        ("b", metadata(2, Some("bb"))),
+        ("c", metadata(3, Some("cc"))),
+        ("d", metadata(4, Some("dd"))),
+        ("e", metadata(5, None)),
+      ]),
+      target: objects(&[("b", metadata(2, Some("BB")))]),
+      failing: HashSet::from(["c".to_string()]),
+      corrupting: HashSet::from(["d".to_string()]),
+      ..Default::default()
+    };
+    let checkpoints = MemoryCheckpoints::default();
+
+    let first = migrate_page(&buckets, &checkpoints, 2, false).await.unwrap();
+    assert_eq!(first.phase, "copy");
+    assert_eq!((first.scanned, first.copied, first.skipped), (2, 1, 1));
+    assert!(!first.completed);
+
+    let second = migrate_page(&buckets, &checkpoints, 2, false).await.unwrap();
+    assert_eq!((second.scanned, second.copied, second.failed), (2, 0, 2));
+    assert_eq!(second.failed_keys, keys(&["c"]));
+    assert_eq!(second.mismatched_keys, keys(&["d"]));
+
+    let third = migrate_page(&buckets, &checkpoints, 2, false).await.unwrap();
+    assert_eq!((third.scanned, third.copied), (1, 1));
+    assert!(!third.completed, "the copy phase hands over to verification");
+
+    let verify = migrate_page(&buckets, &checkpoints, 2, false).await.unwrap();
+    assert_eq!(verify.phase, "verify");
+    assert!(verify.missing_keys.is_empty() && verify.mismatched_keys.is_empty());
+    assert_eq!(verify.unverified_checksums, 0);
+
+    let verify = migrate_page(&buckets, &checkpoints, 2, false).await.unwrap();
+    assert_eq!(verify.missing_keys, keys(&["c"]));
+    assert_eq!(verify.mismatched_keys, keys(&["d"]));
+
+    let verify = migrate_page(&buckets, &checkpoints, 2, false).await.unwrap();
+    assert_eq!(verify.scanned, 1);
+    assert_eq!(verify.unverified_checksums, 1, "e has no source checksum");
+    assert!(verify.completed);
+
+    assert_eq!(
+      *buckets.listed_after.lock().unwrap(),
+      vec![
+        None,
+        Some("b".to_string()),
+        Some("d".to_string()),
+        None,
+        Some("b".to_string()),
+        Some("d".to_string()),
+      ]
+    );
+    assert_eq!(buckets.target.lock().unwrap().len(), 4);
+
+    // A completed migration starts over with a fresh copy pass.
+    let rerun = migrate_page(&buckets, &checkpoints, 10, false).await.unwrap();
+    assert_eq!(rerun.phase, "copy");
+    assert_eq!(rerun.failed_keys, keys(&["c"]));
+    assert_eq!(rerun.mismatched_keys, keys(&["d"]));
+    assert_eq!(rerun.skipped, 3);
+  }
+
+  #[tokio::test]
+  async fn dry_runs_count_copies_without_writing_or_verifying() {
+    let buckets = ScriptedBuckets {
+      source: objects(&[("a", metadata(1, Some("aa"))), ("b", metadata(2, Some("bb")))]),
+      target: objects(&[("a", metadata(1, Some("aa")))]),
+      ..Default::default()
+    };
+    let checkpoints = MemoryCheckpoints::default();
+
+    let result = migrate_page(&buckets, &checkpoints, 10, true).await.unwrap();
+    assert!(result.dry_run && result.completed);
+    assert_eq!((result.copied, result.skipped), (1, 1));
+    assert_eq!(buckets.target.lock().unwrap().len(), 1);
+  }
+}
@@ -0,0 +1,1328 @@
+use std::{
+  fs::{File, OpenOptions},
+  io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write},
+  path::Path,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use sqlx::{FromRow, PgPool};
+
+use super::{
+  MAX_BLOB_SIZE, ObjectPutMetadata, RuntimeError, RuntimeResult, RuntimeWorkspaceArchiveProgress, StorageRuntime,
+  WorkspaceDocCipher, napi_error, upsert_completed_blob,
+};
+
+const ARCHIVE_MAGIC: &[u8; 8] = b"AFWSARC\x01";
+const ARCHIVE_FORMAT_VERSION: i32 = 1;
+const EXPORT_CHECKPOINT_KIND: &str = "workspace_archive_export";
+const IMPORT_CHECKPOINT_KIND: &str = "workspace_archive_import";
+const MAX_RECORD_NAME_LEN: u32 = 4096;
+const MAX_RECORD_META_LEN: u32 = 1024 * 1024;
+const RECORD_HEADER_LEN: u64 = 1 + 4 + 4 + 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RecordKind {
+  Snapshot = 1,
+  Update = 2,
+  Blob = 3,
+  Manifest = 4,
+}
+
+impl RecordKind {
+  fn from_byte(byte: u8) -> RuntimeResult<Self> {
+    match byte {
+      1 => Ok(Self::Snapshot),
+      2 => Ok(Self::Update),
+      3 => Ok(Self::Blob),
+      4 => Ok(Self::Manifest),
+      other => Err(RuntimeError::invalid_input(format!(
+        "WorkspaceArchive record kind {other} is unknown"
+      ))),
+    }
+  }
+
+  fn phase(self) -> ArchivePhase {
+    match self {
+      Self::Snapshot => ArchivePhase::Snapshots,
+      Self::Update => ArchivePhase::Updates,
+      Self::Blob => ArchivePhase::Blobs,
+      Self::Manifest => ArchivePhase::Done,
+    }
+  }
+}
+
+/// One entry of the archive stream:
+/// `[kind u8][name len u32][meta len u32][data len u64][name][meta json][data]`.
+#[derive(Debug, PartialEq)]
+struct ArchiveRecord {
+  kind: RecordKind,
+  name: String,
+  meta: Vec<u8>,
+  data: Vec<u8>,
+}
+
+impl ArchiveRecord {
+  fn new(kind: RecordKind, name: impl Into<String>, meta: &impl Serialize, data: Vec<u8>) -> RuntimeResult<Self> {
+    Ok(Self {
+      kind,
+      name: name.into(),
+      meta: serde_json::to_vec(meta).map_err(|err| RuntimeError::json("WorkspaceArchive encode record meta", err))?,
+      data,
+    })
+  }
+
+  fn decode_meta<T: DeserializeOwned>(&self) -> RuntimeResult<T> {
+    serde_json::from_slice(&self.meta).map_err(|err| RuntimeError::json("WorkspaceArchive decode record meta", err))
+  }
+
+  fn encoded_len(&self) -> u64 {
+    RECORD_HEADER_LEN + self.name.len() as u64 + self.meta.len() as u64 + self.data.len() as u64
+  }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum ArchivePhase {
+  #[default]
+  Snapshots,
+  Updates,
+  Blobs,
+  Manifest,
+  Done,
+}
+
+impl ArchivePhase {
+  fn as_str(self) -> &'static str {
+    match self {
+      Self::Snapshots => "snapshots",
+      Self::Updates => "updates",
+      Self::Blobs => "blobs",
+      Self::Manifest => "manifest",
+      Self::Done => "done",
+    }
+  }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct ArchiveCursor {
+  phase: ArchivePhase,
+  last_key: Option<String>,
+  last_timestamp_ms: Option<i64>,
+  offset: u64,
+  docs: i64,
+  updates: i64,
+  blobs: i64,
+  missing_blobs: i64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct ArchiveCheckpointMetadata {
+  workspace_id: String,
+  target_workspace_id: Option<String>,
+  path: String,
+}
+
+impl ArchiveCheckpointMetadata {
+  fn ensure_matches(&self, expected: &Self) -> RuntimeResult<()> {
+    if self.path != expected.path
+      || self.target_workspace_id != expected.target_workspace_id
+      || (!expected.workspace_id.is_empty() && self.workspace_id != expected.workspace_id)
+    {
+      return Err(RuntimeError::invalid_input(
+        "WorkspaceArchive id is already used by a different workspace or path",
+      ));
+    }
+    Ok(())
+  }
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct ArchiveManifest {
+  format_version: i32,
+  source_workspace_id: String,
+  root_doc_id: String,
+  doc_properties_doc_id: String,
+  exported_at_ms: i64,
+  docs: i64,
+  updates: i64,
+  blobs: i64,
+  missing_blobs: i64,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct DocRecordMeta {
+  created_at_ms: i64,
+  updated_at_ms: Option<i64>,
+  created_by: Option<String>,
+  updated_by: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct BlobRecordMeta {
+  mime: String,
+}
+
+#[derive(FromRow)]
+struct SnapshotRow {
+  guid: String,
+  blob: Vec<u8>,
+  created_at_ms: i64,
+  updated_at_ms: i64,
+  created_by: Option<String>,
+  updated_by: Option<String>,
+}
+
+#[derive(FromRow)]
+struct UpdateRow {
+  guid: String,
+  blob: Vec<u8>,
+  created_at_ms: i64,
+  created_by: Option<String>,
+}
+
+#[derive(FromRow)]
+struct BlobRow {
+  key: String,
+  mime: String,
+}
+
+#[derive(FromRow)]
+struct CheckpointRow {
+  cursor: serde_json::Value,
+  metadata: serde_json::Value,
+}
+
+fn archive_io_error(context: &str) -> impl FnOnce(std::io::Error) -> RuntimeError + '_ {
+  move |err| {
+    if err.kind() == ErrorKind::UnexpectedEof {
+      RuntimeError::invalid_input("WorkspaceArchive file is truncated")
+    } else {
+      RuntimeError::io(context, err)
+    }
+  }
+}
+
+fn write_record(writer: &mut impl Write, record: &ArchiveRecord) -> RuntimeResult<u64> {
+  let name_len = u32::try_from(record.name.len())
+    .ok()
+    .filter(|len| *len <= MAX_RECORD_NAME_LEN)
+    .ok_or_else(|| RuntimeError::invalid_input("WorkspaceArchive record name is too long"))?;
+  let meta_len = u32::try_from(record.meta.len())
+    .ok()
+    .filter(|len| *len <= MAX_RECORD_META_LEN)
+    .ok_or_else(|| RuntimeError::invalid_input("WorkspaceArchive record meta is too large"))?;
+  if record.data.len() as u64 > MAX_BLOB_SIZE as u64 {
+    return Err(RuntimeError::invalid_input("WorkspaceArchive record data is too large"));
+  }
+
+  let mut header = Vec::with_capacity(RECORD_HEADER_LEN as usize);
+  header.push(record.kind as u8);
+  header.extend_from_slice(&name_len.to_be_bytes());
+  header.extend_from_slice(&meta_len.to_be_bytes());
+  header.extend_from_slice(&(record.data.len() as u64).to_be_bytes());
+  for part in [&header[..], record.name.as_bytes(), &record.meta[..], &record.data[..]] {
+    writer
+      .write_all(part)
+      .map_err(archive_io_error("WorkspaceArchive write record failed"))?;
+  }
+  Ok(record.encoded_len())
+}
+
+/// Read a record header, returning `None` at a clean end of stream.
+fn read_record_header(reader: &mut impl Read) -> RuntimeResult<Option<(RecordKind, u32, u32, u64)>> {
+  let mut header = [0u8; RECORD_HEADER_LEN as usize];
+  let mut filled = 0;
+  while filled < header.len() {
+    match reader.read(&mut header[filled..]) {
+      Ok(0) if filled == 0 => return Ok(None),
+      Ok(0) => return Err(RuntimeError::invalid_input("WorkspaceArchive file is truncated")),
+      Ok(read) => filled += read,
+      Err(err) if err.kind() == ErrorKind::Interrupted => {}
+      Err(err) => return Err(RuntimeError::io("WorkspaceArchive read record failed", err)),
+    }
+  }
+
+  let kind = RecordKind::from_byte(header[0])?;
+  let name_len = u32::from_be_bytes(header[1..5].try_into().expect("slice length is fixed"));
+  let meta_len = u32::from_be_bytes(header[5..9].try_into().expect("slice length is fixed"));
+  let data_len = u64::from_be_bytes(header[9..17].try_into().expect("slice length is fixed"));
+  if name_len > MAX_RECORD_NAME_LEN || meta_len > MAX_RECORD_META_LEN || data_len > MAX_BLOB_SIZE as u64 {
+    return Err(RuntimeError::invalid_input("WorkspaceArchive record header is invalid"));
+  }
+  Ok(Some((kind, name_len, meta_len, data_len)))
+}
+
+fn read_record(reader: &mut impl Read) -> RuntimeResult<Option<ArchiveRecord>> {
+  let Some((kind, name_len, meta_len, data_len)) = read_record_header(reader)? else {
+    return Ok(None);
+  };
+  let mut read_part = |len: usize| -> RuntimeResult<Vec<u8>> {
+    let mut buf = vec![0u8; len];
+    reader
+      .read_exact(&mut buf)
+      .map_err(archive_io_error("WorkspaceArchive read record failed"))?;
+    Ok(buf)
+  };
+  let name = String::from_utf8(read_part(name_len as usize)?)
+    .map_err(|_| RuntimeError::invalid_input("WorkspaceArchive record name is not utf-8"))?;
+  let meta = read_part(meta_len as usize)?;
+  let data = read_part(data_len as usize)?;
+  Ok(Some(ArchiveRecord { kind, name, meta, data }))
+}
+
+fn check_magic(reader: &mut impl Read) -> RuntimeResult<()> {
+  let mut magic = [0u8; ARCHIVE_MAGIC.len()];
+  reader
+    .read_exact(&mut magic)
+    .map_err(archive_io_error("WorkspaceArchive read header failed"))?;
+  if &magic != ARCHIVE_MAGIC {
+    return Err(RuntimeError::invalid_input(
+      "WorkspaceArchive file has an unsupported format",
+    ));
+  }
+  Ok(())
+}
+
+/// Walk the archive without loading record bodies and return its manifest.
+/// The manifest is written last, so this also proves the export finished.
+fn read_manifest(path: &Path) -> RuntimeResult<ArchiveManifest> {
+  let file = File::open(path).map_err(|err| RuntimeError::io("WorkspaceArchive open failed", err))?;
+  let mut reader = BufReader::new(file);
+  check_magic(&mut reader)?;
+
+  while let Some((kind, name_len, meta_len, data_len)) = read_record_header(&mut reader)? {
+    if kind == RecordKind::Manifest {
+      let mut meta = vec![0u8; meta_len as usize];
+      reader
+        .seek_relative(i64::from(name_len))
+        .map_err(|err| RuntimeError::io("WorkspaceArchive seek failed", err))?;
+      reader
+        .read_exact(&mut meta)
+        .map_err(archive_io_error("WorkspaceArchive read manifest failed"))?;
+      let manifest = serde_json::from_slice::<ArchiveManifest>(&meta)
+        .map_err(|err| RuntimeError::json("WorkspaceArchive decode manifest", err))?;
+      if manifest.format_version != ARCHIVE_FORMAT_VERSION {
+        return Err(RuntimeError::invalid_input(format!(
+          "WorkspaceArchive format version {} is unsupported",
+          manifest.format_version
+        )));
+      }
+      return Ok(manifest);
+    }
+    let skip = i64::from(name_len) + i64::from(meta_len) + data_len as i64;
+    reader
+      .seek_relative(skip)
+      .map_err(|err| RuntimeError::io("WorkspaceArchive seek failed", err))?;
+  }
+
+  Err(RuntimeError::invalid_input(
+    "WorkspaceArchive manifest is missing; the export did not finish",
+  ))
+}
+
+/// Open the archive for appending at a checkpointed offset. Anything written
+/// after the last checkpoint is discarded so a retried batch is not duplicated.
+fn open_for_append(path: &Path, offset: u64) -> RuntimeResult<(BufWriter<File>, u64)> {
+  let mut file = OpenOptions::new()
+    .create(true)
+    .truncate(false)
+    .read(true)
+    .write(true)
+    .open(path)
+    .map_err(|err| RuntimeError::io("WorkspaceArchive open failed", err))?;
+
+  let offset = if offset == 0 {
+    file
+      .set_len(0)
+      .map_err(|err| RuntimeError::io("WorkspaceArchive truncate failed", err))?;
+    file
+      .write_all(ARCHIVE_MAGIC)
+      .map_err(|err| RuntimeError::io("WorkspaceArchive write header failed", err))?;
+    ARCHIVE_MAGIC.len() as u64
+  } else {
+    let len = file
+      .metadata()
+      .map_err(|err| RuntimeError::io("WorkspaceArchive stat failed", err))?
+      .len();
+    if len < offset {
+      return Err(RuntimeError::invalid_state(
+        "WorkspaceArchive file is shorter than its checkpoint",
+      ));
+    }
+    file
+      .set_len(offset)
+      .map_err(|err| RuntimeError::io("WorkspaceArchive truncate failed", err))?;
+    file
+      .seek(SeekFrom::End(0))
+      .map_err(|err| RuntimeError::io("WorkspaceArchive seek failed", err))?;
+    offset
+  };
+
+  Ok((BufWriter::new(file), offset))
+}
+
+fn finish_append(writer: BufWriter<File>) -> RuntimeResult<()> {
+  let file = writer
+    .into_inner()
+    .map_err(|err| RuntimeError::io("WorkspaceArchive flush failed", err.into_error()))?;
+  file
+    .sync_all()
+    .map_err(|err| RuntimeError::io("WorkspaceArchive sync failed", err))
+}
+
+fn open_at(path: &Path, offset: u64) -> RuntimeResult<BufReader<File>> {
+  let mut file = File::open(path).map_err(|err| RuntimeError::io("WorkspaceArchive open failed", err))?;
+  file
+    .seek(SeekFrom::Start(offset))
+    .map_err(|err| RuntimeError::io("WorkspaceArchive seek failed", err))?;
+  Ok(BufReader::new(file))
+}
+
+/// Rewrite workspace-scoped doc ids for the import target. The root doc id is
+/// the workspace id, and ids such as `db${workspaceId}$docProperties` embed it
+/// as a `$`-separated segment.
+fn remap_doc_id(doc_id: &str, source_workspace_id: &str, target_workspace_id: &str) -> String {
+  if source_workspace_id == target_workspace_id {
+    return doc_id.to_string();
+  }
+  doc_id
+    .split('$')
+    .map(|segment| {
+      if segment == source_workspace_id {
+        target_workspace_id
+      } else {
+        segment
+      }
+    })
+    .collect::<Vec<_>>()
+    .join("$")
+}
+
+fn timestamp_from_ms(timestamp_ms: i64) -> RuntimeResult<DateTime<Utc>> {
+  DateTime::<Utc>::from_timestamp_millis(timestamp_ms)
+    .ok_or_else(|| RuntimeError::invalid_input(format!("WorkspaceArchive timestamp {timestamp_ms} is invalid")))
+}
+
+fn progress(
+  archive_id: &str,
+  metadata: &ArchiveCheckpointMetadata,
+  cursor: &ArchiveCursor,
+) -> RuntimeWorkspaceArchiveProgress {
+  RuntimeWorkspaceArchiveProgress {
+    archive_id: archive_id.to_string(),
+    source_workspace_id: metadata.workspace_id.clone(),
+    target_workspace_id: metadata.target_workspace_id.clone(),
+    phase: cursor.phase.as_str().to_string(),
+    docs: cursor.docs,
+    updates: cursor.updates,
+    blobs: cursor.blobs,
+    missing_blobs: cursor.missing_blobs,
+    bytes: cursor.offset as i64,
+    completed: cursor.phase == ArchivePhase::Done,
+  }
+}
+
+async fn load_checkpoint(
+  pool: &PgPool,
+  kind: &str,
+  archive_id: &str,
+) -> RuntimeResult<Option<(ArchiveCursor, ArchiveCheckpointMetadata)>> {
+  let Some(row) = sqlx::query_as::<_, CheckpointRow>(
+    "SELECT cursor, metadata FROM blob_reconciliation_checkpoints WHERE kind = $1 AND scope = $2",
+  )
+  .bind(kind)
+  .bind(archive_id)
+  .fetch_optional(pool)
+  .await
+  .map_err(|err| RuntimeError::database("WorkspaceArchive checkpoint load failed", err))?
+  else {
+    return Ok(None);
+  };
+
+  let cursor = serde_json::from_value(row.cursor)
+    .map_err(|err| RuntimeError::json("WorkspaceArchive decode checkpoint cursor", err))?;
+  let metadata = serde_json::from_value(row.metadata)
+    .map_err(|err| RuntimeError::json("WorkspaceArchive decode checkpoint metadata", err))?;
+  Ok(Some((cursor, metadata)))
+}
+
+async fn upsert_checkpoint(
+  pool: &PgPool,
+  kind: &str,
+  archive_id: &str,
+  cursor: &ArchiveCursor,
+  metadata: &ArchiveCheckpointMetadata,
+) -> RuntimeResult<()> {
+  let completed = cursor.phase == ArchivePhase::Done;
+  let status = if completed { "completed" } else { "running" };
+  let cursor_json =
+    serde_json::to_value(cursor).map_err(|err| RuntimeError::json("WorkspaceArchive encode checkpoint cursor", err))?;
+  let metadata_json = serde_json::to_value(metadata)
+    .map_err(|err| RuntimeError::json("WorkspaceArchive encode checkpoint metadata", err))?;
+  sqlx::query(
+    r#"
+    INSERT INTO blob_reconciliation_checkpoints
+      (kind, scope, status, cursor, last_key, completed_at, metadata)
+    VALUES ($1, $2, $3, $4, $5, CASE WHEN $6 THEN CURRENT_TIMESTAMP ELSE NULL END, $7)
+    ON CONFLICT (kind, scope) DO UPDATE
+      SET status = EXCLUDED.status,
+          cursor = EXCLUDED.cursor,
+          last_key = EXCLUDED.last_key,
+          completed_at = EXCLUDED.completed_at,
+          updated_at = CURRENT_TIMESTAMP,
+          metadata = EXCLUDED.metadata
+    "#,
+  )
+  .bind(kind)
+  .bind(archive_id)
+  .bind(status)
+  .bind(cursor_json)
+  .bind(cursor.last_key.as_deref())
+  .bind(completed)
+  .bind(metadata_json)
+  .execute(pool)
+  .await
+  .map_err(|err| RuntimeError::database("WorkspaceArchive checkpoint write failed", err))?;
+  Ok(())
+}
+
+/// Serialize runs of the same archive id so two callers never append to or
+/// replay the same file concurrently. The lock lives as long as the returned
+/// transaction.
+async fn lock_archive(
+  pool: &PgPool,
+  kind: &str,
+  archive_id: &str,
+) -> RuntimeResult<sqlx::Transaction<'static, sqlx::Postgres>> {
+  let mut tx = pool
+    .begin()
+    .await
+    .map_err(|err| RuntimeError::database("WorkspaceArchive begin lock transaction failed", err))?;
+  let locked = sqlx::query_scalar::<_, bool>("SELECT pg_try_advisory_xact_lock(hashtextextended($1, 0))")
+    .bind(format!("{kind}:{archive_id}"))
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|err| RuntimeError::database("WorkspaceArchive acquire lock failed", err))?;
+  if !locked {
+    return Err(RuntimeError::invalid_state(format!(
+      "WorkspaceArchive {archive_id} is already running"
+    )));
+  }
+  Ok(tx)
+}
+
+async fn release_archive_lock(tx: sqlx::Transaction<'static, sqlx::Postgres>) -> RuntimeResult<()> {
+  tx.commit()
+    .await
+    .map_err(|err| RuntimeError::database("WorkspaceArchive release lock failed", err))
+}
+
+async fn workspace_exists(pool: &PgPool, workspace_id: &str) -> RuntimeResult<bool> {
+  sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM workspaces WHERE id = $1)")
+    .bind(workspace_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|err| RuntimeError::database("WorkspaceArchive workspace check failed", err))
+}
+
+fn validate_archive_args(archive_id: &str, path: &str, batch_limit: i64) -> napi::Result<()> {
+  if archive_id.trim().is_empty() {
+    return Err(napi_error("workspace archive id must not be empty"));
+  }
+  if path.trim().is_empty() {
+    return Err(napi_error("workspace archive path must not be empty"));
+  }
+  if batch_limit <= 0 {
+    return Err(napi_error("workspace archive batch limit must be positive"));
+  }
+  Ok(())
+}
+
+impl StorageRuntime {
+  async fn export_archive_batch(
+    &self,
+    pool: &PgPool,
+    workspace_id: &str,
+    writer: &mut impl Write,
+    cursor: &mut ArchiveCursor,
+    batch_limit: i64,
+  ) -> RuntimeResult<()> {
+    let master_keys = self.doc_master_keys()?;
+    let mut conn = pool
+      .acquire()
+      .await
+      .map_err(|err| RuntimeError::database("WorkspaceArchive acquire connection failed", err))?;
+    let cipher = WorkspaceDocCipher::for_read(&mut conn, &master_keys, workspace_id).await?;
+    let mut remaining = batch_limit;
+
+    while remaining > 0 && cursor.phase == ArchivePhase::Snapshots {
+      let rows = sqlx::query_as::<_, SnapshotRow>(
+        r#"
+        SELECT
+          guid,
+          blob,
+          (EXTRACT(EPOCH FROM created_at) * 1000)::BIGINT AS created_at_ms,
+          (EXTRACT(EPOCH FROM updated_at) * 1000)::BIGINT AS updated_at_ms,
+          created_by,
+          updated_by
+        FROM snapshots
+        WHERE workspace_id = $1
+          AND ($2::VARCHAR IS NULL OR guid > $2)
+        ORDER BY guid
+        LIMIT $3
+        "#,
+      )
+      .bind(workspace_id)
+      .bind(cursor.last_key.as_deref())
+      .bind(remaining)
+      .fetch_all(&mut *conn)
+      .await
+      .map_err(|err| RuntimeError::database("WorkspaceArchive load snapshots failed", err))?;
+      let exhausted = (rows.len() as i64) < remaining;
+      remaining -= rows.len() as i64;
+
+      for row in rows {
+        let meta = DocRecordMeta {
+          created_at_ms: row.created_at_ms,
+          updated_at_ms: Some(row.updated_at_ms),
+          created_by: row.created_by,
+          updated_by: row.updated_by,
+        };
+        let blob = cipher.decrypt(&row.guid, row.blob)?;
+        cursor.offset += write_record(
+          writer,
+          &ArchiveRecord::new(RecordKind::Snapshot, row.guid.clone(), &meta, blob)?,
+        )?;
+        cursor.docs += 1;
+        cursor.last_key = Some(row.guid);
+      }
+      if exhausted {
+        cursor.phase = ArchivePhase::Updates;
+        cursor.last_key = None;
+      }
+    }
+
+    while remaining > 0 && cursor.phase == ArchivePhase::Updates {
+      let after = cursor.last_timestamp_ms.map(timestamp_from_ms).transpose()?;
+      let rows = sqlx::query_as::<_, UpdateRow>(
+        r#"
+        SELECT
+          guid,
+          blob,
+          (EXTRACT(EPOCH FROM created_at) * 1000)::BIGINT AS created_at_ms,
+          created_by
+        FROM updates
+        WHERE workspace_id = $1
+          AND ($2::VARCHAR IS NULL OR (guid, created_at) > ($2, $3))
+        ORDER BY guid, created_at
+        LIMIT $4
+        "#,
+      )
+      .bind(workspace_id)
+      .bind(cursor.last_key.as_deref())
+      .bind(after)
+      .bind(remaining)
+      .fetch_all(&mut *conn)
+      .await
+      .map_err(|err| RuntimeError::database("WorkspaceArchive load updates failed", err))?;
+      let exhausted = (rows.len() as i64) < remaining;
+      remaining -= rows.len() as i64;
+
+      for row in rows {
+        let meta = DocRecordMeta {
+          created_at_ms: row.created_at_ms,
+          created_by: row.created_by,
+          ..Default::default()
+        };
+        let blob = cipher.decrypt(&row.guid, row.blob)?;
+        cursor.offset += write_record(
+          writer,
+          &ArchiveRecord::new(RecordKind::Update, row.guid.clone(), &meta, blob)?,
+        )?;
+        cursor.updates += 1;
+        cursor.last_key = Some(row.guid);
+        cursor.last_timestamp_ms = Some(row.created_at_ms);
+      }
+      if exhausted {
+        cursor.phase = ArchivePhase::Blobs;
+        cursor.last_key = None;
+        cursor.last_timestamp_ms = None;
+      }
+    }
+    drop(conn);
+
+    while remaining > 0 && cursor.phase == ArchivePhase::Blobs {
+      let rows = sqlx::query_as::<_, BlobRow>(
+        r#"
+        SELECT key, mime
+        FROM blobs
+        WHERE workspace_id = $1
+          AND status = 'completed'
+          AND deleted_at IS NULL
+          AND ($2::VARCHAR IS NULL OR key > $2)
+        ORDER BY key
+        LIMIT $3
+        "#,
+      )
+      .bind(workspace_id)
+      .bind(cursor.last_key.as_deref())
+      .bind(remaining)
+      .fetch_all(pool)
+      .await
+      .map_err(|err| RuntimeError::database("WorkspaceArchive load blobs failed", err))?;
+      let exhausted = (rows.len() as i64) < remaining;
+      remaining -= rows.len() as i64;
+
+      for row in rows {
+        match self.object_storage_get(&format!("{workspace_id}/{}", row.key)).await? {
+          Some(object) => {
+            let meta = BlobRecordMeta { mime: row.mime };
+            cursor.offset += write_record(
+              writer,
+              &ArchiveRecord::new(RecordKind::Blob, row.key.clone(), &meta, object.body)?,
+            )?;
+            cursor.blobs += 1;
+          }
+          None => cursor.missing_blobs += 1,
+        }
+        cursor.last_key = Some(row.key);
+      }
+      if exhausted {
+        cursor.phase = ArchivePhase::Manifest;
+        cursor.last_key = None;
+      }
+    }
+
+    if cursor.phase == ArchivePhase::Manifest {
+      let manifest = ArchiveManifest {
+        format_version: ARCHIVE_FORMAT_VERSION,
+        source_workspace_id: workspace_id.to_string(),
+        root_doc_id: workspace_id.to_string(),
+        doc_properties_doc_id: format!("db${workspace_id}$docProperties"),
+        exported_at_ms: Utc::now().timestamp_millis(),
+        docs: cursor.docs,
+        updates: cursor.updates,
+        blobs: cursor.blobs,
+        missing_blobs: cursor.missing_blobs,
+      };
+      cursor.offset += write_record(
+        writer,
+        &ArchiveRecord::new(RecordKind::Manifest, "manifest", &manifest, Vec::new())?,
+      )?;
+      cursor.phase = ArchivePhase::Done;
+    }
+
+    Ok(())
+  }
+
+  async fn import_archive_record(
+    &self,
+    pool: &PgPool,
+    cipher: &WorkspaceDocCipher,
+    conn: &mut sqlx::PgConnection,
+    source_workspace_id: &str,
+    target_workspace_id: &str,
+    record: ArchiveRecord,
+  ) -> RuntimeResult<()> {
+    match record.kind {
+      RecordKind::Snapshot => {
+        let meta = record.decode_meta::<DocRecordMeta>()?;
+        let doc_id = remap_doc_id(&record.name, source_workspace_id, target_workspace_id);
+        let size = record.data.len() as i64;
+        let sealed = cipher.encrypt(&doc_id, &record.data)?;
+        sqlx::query(
+          r#"
+          INSERT INTO snapshots
+            (workspace_id, guid, blob, size, created_at, updated_at, created_by, updated_by)
+          VALUES
+            ($1, $2, $3, $4, $5, $6,
+             (SELECT id FROM users WHERE id = $7),
+             (SELECT id FROM users WHERE id = $8))
+          ON CONFLICT (workspace_id, guid)
+          DO UPDATE SET
+            blob = EXCLUDED.blob,
+            size = EXCLUDED.size,
+            updated_at = EXCLUDED.updated_at,
+            updated_by = EXCLUDED.updated_by
+          "#,
+        )
+        .bind(target_workspace_id)
+        .bind(&doc_id)
+        .bind(sealed)
+        .bind(size)
+        .bind(timestamp_from_ms(meta.created_at_ms)?)
+        .bind(timestamp_from_ms(meta.updated_at_ms.unwrap_or(meta.created_at_ms))?)
+        .bind(meta.created_by.as_deref())
+        .bind(meta.updated_by.as_deref())
+        .execute(&mut *conn)
+        .await
+        .map_err(|err| RuntimeError::database("WorkspaceArchive import snapshot failed", err))?;
+      }
+      RecordKind::Update => {
+        let meta = record.decode_meta::<DocRecordMeta>()?;
+        let doc_id = remap_doc_id(&record.name, source_workspace_id, target_workspace_id);
+        let sealed = cipher.encrypt(&doc_id, &record.data)?;
+        sqlx::query(
+          r#"
+          INSERT INTO updates (workspace_id, guid, blob, created_at, created_by)
+          VALUES ($1, $2, $3, $4, (SELECT id FROM users WHERE id = $5))
+          ON CONFLICT (workspace_id, guid, created_at) DO NOTHING
+          "#,
+        )
+        .bind(target_workspace_id)
+        .bind(&doc_id)
+        .bind(sealed)
+        .bind(timestamp_from_ms(meta.created_at_ms)?)
+        .bind(meta.created_by.as_deref())
+        .execute(&mut *conn)
+        .await
+        .map_err(|err| RuntimeError::database("WorkspaceArchive import update failed", err))?;
+      }
+      RecordKind::Blob => {
+        let meta = record.decode_meta::<BlobRecordMeta>()?;
+        let size = record.data.len() as i64;
+        let put_metadata = ObjectPutMetadata {
+          content_type: Some(meta.mime.clone()),
+          ..Default::default()
+        }
+        .complete_for_body(&record.data);
+        self
+          .object_storage_put(
+            &format!("{target_workspace_id}/{}", record.name),
+            record.data,
+            put_metadata,
+          )
+          .await?;
+        upsert_completed_blob(pool, target_workspace_id, &record.name, &meta.mime, size).await?;
+      }
+      RecordKind::Manifest => {}
+    }
+    Ok(())
+  }
+}
+
+#[napi_derive::napi]
+impl StorageRuntime {
+  /// Append the next batch of a workspace export to the archive at `path`.
+  /// Call repeatedly with the same `archive_id` until `completed` is true;
+  /// progress is checkpointed so an interrupted export resumes where the last
+  /// committed batch ended.
+  #[napi]
+  pub async fn export_workspace_archive(
+    &self,
+    workspace_id: String,
+    archive_id: String,
+    path: String,
+    batch_limit: i64,
+  ) -> napi::Result<RuntimeWorkspaceArchiveProgress> {
+    validate_archive_args(&archive_id, &path, batch_limit)?;
+
+    let pool = self.pool().await?;
+    let lock = lock_archive(&pool, EXPORT_CHECKPOINT_KIND, &archive_id).await?;
+    let metadata = ArchiveCheckpointMetadata {
+      workspace_id: workspace_id.clone(),
+      target_workspace_id: None,
+      path: path.clone(),
+    };
+    let mut cursor = match load_checkpoint(&pool, EXPORT_CHECKPOINT_KIND, &archive_id).await? {
+      Some((cursor, existing)) => {
+        existing.ensure_matches(&metadata)?;
+        cursor
+      }
+      None => ArchiveCursor::default(),
+    };
+    if cursor.phase == ArchivePhase::Done {
+      release_archive_lock(lock).await?;
+      return Ok(progress(&archive_id, &metadata, &cursor));
+    }
+
+    let (mut writer, offset) = open_for_append(Path::new(&path), cursor.offset)?;
+    cursor.offset = offset;
+    self
+      .export_archive_batch(&pool, &workspace_id, &mut writer, &mut cursor, batch_limit)
+      .await?;
+    finish_append(writer)?;
+
+    upsert_checkpoint(&pool, EXPORT_CHECKPOINT_KIND, &archive_id, &cursor, &metadata).await?;
+    release_archive_lock(lock).await?;
+    Ok(progress(&archive_id, &metadata, &cursor))
+  }
+
+  /// Replay the next batch of an exported archive into `target_workspace_id`,
+  /// remapping workspace-scoped doc ids. The target workspace must already
+  /// exist. Records are written idempotently, so a batch retried after a crash
+  /// does not duplicate data.
+  #[napi]
+  pub async fn import_workspace_archive(
+    &self,
+    archive_id: String,
+    path: String,
+    target_workspace_id: String,
+    batch_limit: i64,
+  ) -> napi::Result<RuntimeWorkspaceArchiveProgress> {
+    validate_archive_args(&archive_id, &path, batch_limit)?;
+
+    let pool = self.pool().await?;
+    let lock = lock_archive(&pool, IMPORT_CHECKPOINT_KIND, &archive_id).await?;
+    let expected = ArchiveCheckpointMetadata {
+      workspace_id: String::new(),
+      target_workspace_id: Some(target_workspace_id.clone()),
+      path: path.clone(),
+    };
+    let (mut cursor, metadata) = match load_checkpoint(&pool, IMPORT_CHECKPOINT_KIND, &archive_id).await? {
+      Some((cursor, existing)) => {
+        existing.ensure_matches(&expected)?;
+        (cursor, existing)
+      }
+      None => {
+        let manifest = read_manifest(Path::new(&path))?;
+        let cursor = ArchiveCursor {
+          offset: ARCHIVE_MAGIC.len() as u64,
+          ..Default::default()
+        };
+        let metadata = ArchiveCheckpointMetadata {
+          workspace_id: manifest.source_workspace_id,
+          ..expected
+        };
+        (cursor, metadata)
+      }
+    };
+    if cursor.phase == ArchivePhase::Done {
+      release_archive_lock(lock).await?;
+      return Ok(progress(&archive_id, &metadata, &cursor));
+    }
+    if !workspace_exists(&pool, &target_workspace_id).await? {
+      return Err(napi_error(format!(
+        "workspace archive target workspace {target_workspace_id} does not exist"
+      )));
+    }
+
+    let master_keys = self.doc_master_keys()?;
+    let mut conn = pool
+      .acquire()
+      .await
+      .map_err(|err| RuntimeError::database("WorkspaceArchive acquire connection failed", err))?;
+    let cipher = WorkspaceDocCipher::for_write(&mut conn, &master_keys, &target_workspace_id).await?;
+    let mut reader = open_at(Path::new(&path), cursor.offset)?;
+    for _ in 0..batch_limit {
+      let Some(record) = read_record(&mut reader)? else {
+        return Err(napi_error("workspace archive ended before its manifest"));
+      };
+      let next_offset = cursor.offset + record.encoded_len();
+      let kind = record.kind;
+      self
+        .import_archive_record(
+          &pool,
+          &cipher,
+          &mut conn,
+          &metadata.workspace_id,
+          &target_workspace_id,
+          record,
+        )
+        .await?;
+
+      cursor.offset = next_offset;
+      cursor.phase = kind.phase();
+      match kind {
+        RecordKind::Snapshot => cursor.docs += 1,
+        RecordKind::Update => cursor.updates += 1,
+        RecordKind::Blob => cursor.blobs += 1,
+        RecordKind::Manifest => break,
+      }
+    }
+    drop(conn);
+
+    upsert_checkpoint(&pool, IMPORT_CHECKPOINT_KIND, &archive_id, &cursor, &metadata).await?;
+    release_archive_lock(lock).await?;
+    Ok(progress(&archive_id, &metadata, &cursor))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    collections::{BTreeMap, HashMap},
+    io::Cursor,
+    sync::RwLock,
+  };
+
+  use sqlx::postgres::PgPoolOptions;
+  use tokio::sync::Mutex;
+
+  use super::{
+    super::{
+      DatabasePoolConfig, DatabaseReplicaConfig, DocEncryptionConfig, FsStorageConfig, ImageDerivativeConfig,
+      StorageBackendConfig, StorageRuntimeConfig, migrate_runtime_tables,
+    },
+    *,
+  };
+
+  const SOURCE_WORKSPACE: &str = "rust-test-archive-source";
+  const TARGET_WORKSPACE: &str = "rust-test-archive-target";
+  const ARCHIVE_ID: &str = "rust-test-archive";
+
+  fn doc_record(kind: RecordKind, name: &str, data: &[u8]) -> ArchiveRecord {
+    ArchiveRecord::new(
+      kind,
+      name,
+      &DocRecordMeta {
+        created_at_ms: 1_700_000_000_000,
+        ..Default::default()
+      },
+      data.to_vec(),
+    )
+    .unwrap()
+  }
+
+  #[test]
+  fn records_round_trip_and_report_encoded_length() {
+    let records = [
+      doc_record(RecordKind::Snapshot, "ws-1", b"root"),
+      doc_record(RecordKind::Update, "db$ws-1$docProperties", b"props"),
+      ArchiveRecord::new(
+        RecordKind::Blob,
+        "blob-key",
+        &BlobRecordMeta {
+          mime: "image/png".to_string(),
+        },
+        vec![7; 32],
+      )
+      .unwrap(),
+    ];
+
+    let mut buf = Vec::new();
+    let mut written = 0;
+    for record in &records {
+      written += write_record(&mut buf, record).unwrap();
+    }
+    assert_eq!(written, buf.len() as u64);
+
+    let mut reader = Cursor::new(buf);
+    for record in &records {
+      assert_eq!(read_record(&mut reader).unwrap().as_ref(), Some(record));
+    }
+    assert!(read_record(&mut reader).unwrap().is_none());
+    assert_eq!(
+      records[0].decode_meta::<DocRecordMeta>().unwrap().created_at_ms,
+      1_700_000_000_000
+    );
+  }
+
+  #[test]
+  fn truncated_records_are_rejected() {
+    let mut buf = Vec::new();
+    write_record(&mut buf, &doc_record(RecordKind::Snapshot, "doc", b"payload")).unwrap();
+
+    for len in [3, RECORD_HEADER_LEN as usize, buf.len() - 1] {
+      let err = read_record(&mut Cursor::new(&buf[..len])).unwrap_err();
+      assert!(err.to_string().contains("truncated"), "{err}");
+    }
+
+    buf[0] = 9;
+    assert!(read_record(&mut Cursor::new(&buf)).is_err());
+  }
+
+  #[test]
+  fn manifest_is_required_and_found_after_records() {
+    let temp = tempfile::tempdir().unwrap();
+    let path = temp.path().join("workspace.afarchive");
+
+    let (mut writer, _) = open_for_append(&path, 0).unwrap();
+    let offset =
+      ARCHIVE_MAGIC.len() as u64 + write_record(&mut writer, &doc_record(RecordKind::Snapshot, "ws", b"root")).unwrap();
+    finish_append(writer).unwrap();
+    assert!(
+      read_manifest(&path)
+        .unwrap_err()
+        .to_string()
+        .contains("manifest is missing")
+    );
+
+    // A torn write after the checkpoint is discarded on resume.
+    let (mut writer, _) = open_for_append(&path, offset).unwrap();
+    writer.write_all(b"partial").unwrap();
+    finish_append(writer).unwrap();
+    let (mut writer, resumed) = open_for_append(&path, offset).unwrap();
+    assert_eq!(resumed, offset);
+    let manifest = ArchiveManifest {
+      format_version: ARCHIVE_FORMAT_VERSION,
+      source_workspace_id: "ws".to_string(),
+      root_doc_id: "ws".to_string(),
+      doc_properties_doc_id: "db$ws$docProperties".to_string(),
+      exported_at_ms: 1,
+      docs: 1,
+      updates: 0,
+      blobs: 0,
+      missing_blobs: 0,
+    };
+    write_record(
+      &mut writer,
+      &ArchiveRecord::new(RecordKind::Manifest, "manifest", &manifest, Vec::new()).unwrap(),
+    )
+    .unwrap();
+    finish_append(writer).unwrap();
+
+    assert_eq!(read_manifest(&path).unwrap(), manifest);
+    let mut reader = open_at(&path, ARCHIVE_MAGIC.len() as u64).unwrap();
+    assert_eq!(read_record(&mut reader).unwrap().unwrap().name, "ws");
+    assert_eq!(read_record(&mut reader).unwrap().unwrap().kind, RecordKind::Manifest);
+    assert!(read_record(&mut reader).unwrap().is_none());
+  }
+
+  #[test]
+  fn remaps_workspace_scoped_doc_ids() {
+    for (doc_id, expected) in [
+      ("old-ws", "new-ws"),
+      ("db$old-ws$docProperties", "db$new-ws$docProperties"),
+      ("db$old-ws$folders", "db$new-ws$folders"),
+      ("userdata$user-1$old-ws$favorite", "userdata$user-1$new-ws$favorite"),
+      ("page-1", "page-1"),
+      ("old-ws-suffix", "old-ws-suffix"),
+    ] {
+      assert_eq!(remap_doc_id(doc_id, "old-ws", "new-ws"), expected);
+    }
+    assert_eq!(remap_doc_id("db$ws$docProperties", "ws", "ws"), "db$ws$docProperties");
+  }
+
+  #[test]
+  fn checkpoint_metadata_must_match_on_resume() {
+    let stored = ArchiveCheckpointMetadata {
+      workspace_id: "ws".to_string(),
+      target_workspace_id: Some("target".to_string()),
+      path: "/tmp/a".to_string(),
+    };
+    let import = ArchiveCheckpointMetadata {
+      workspace_id: String::new(),
+      ..stored.clone()
+    };
+    assert!(stored.ensure_matches(&import).is_ok());
+    assert!(
+      stored
+        .ensure_matches(&ArchiveCheckpointMetadata {
+          path: "/tmp/b".to_string(),
+          ..import
+        })
+        .is_err()
+    );
+  }
+
+  /// A runtime over the `DATABASE_URL` database with an fs blob backend
+  /// under `root` and empty source and target workspaces, or `None` when no
+  /// database is configured.
+  async fn archive_runtime(root: &Path) -> Option<(StorageRuntime, PgPool)> {
+    let database_url = std::env::var("DATABASE_URL").ok()?;
+    let pool = PgPoolOptions::new()
+      .max_connections(4)
+      .connect(&database_url)
+      .await
+      .unwrap();
+    migrate_runtime_tables(&pool).await.unwrap();
+    for sql in [
+      "DELETE FROM snapshots WHERE workspace_id = ANY($1)",
+      "DELETE FROM updates WHERE workspace_id = ANY($1)",
+      "DELETE FROM workspaces WHERE id = ANY($1)",
+    ] {
+      sqlx::query(sql)
+        .bind([SOURCE_WORKSPACE, TARGET_WORKSPACE])
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+    sqlx::query("DELETE FROM blob_reconciliation_checkpoints WHERE kind = ANY($1) AND scope = $2")
+      .bind([EXPORT_CHECKPOINT_KIND, IMPORT_CHECKPOINT_KIND])
+      .bind(ARCHIVE_ID)
+      .execute(&pool)
+      .await
+      .unwrap();
+    sqlx::query("INSERT INTO workspaces (id, public) VALUES ($1, false), ($2, false)")
+      .bind(SOURCE_WORKSPACE)
+      .bind(TARGET_WORKSPACE)
+      .execute(&pool)
+      .await
+      .unwrap();
+
+    let backend = StorageBackendConfig::Fs(FsStorageConfig {
+      provider: "fs".to_string(),
+      root: root.to_string_lossy().to_string(),
+      bucket: "blob".to_string(),
+    });
+    let runtime = StorageRuntime {
+      config: RwLock::new(StorageRuntimeConfig {
+        database_url,
+        pool: DatabasePoolConfig::default(),
+        replicas: DatabaseReplicaConfig::default(),
+        backends: HashMap::from([("blob".to_string(), backend)]),
+        doc_encryption: DocEncryptionConfig::default(),
+        blob_encryption: false,
+        image_derivatives: ImageDerivativeConfig::default(),
+      }),
+      forwarded: RwLock::new(None),
+      pool: Mutex::new(Some(pool.clone())),
+      replicas: Mutex::new(None),
+    };
+    Some((runtime, pool))
+  }
+
+  async fn workspace_docs(pool: &PgPool, workspace_id: &str) -> BTreeMap<String, Vec<u8>> {
+    sqlx::query_as::<_, (String, Vec<u8>)>("SELECT guid, blob FROM snapshots WHERE workspace_id = $1")
+      .bind(workspace_id)
+      .fetch_all(pool)
+      .await
+      .unwrap()
+      .into_iter()
+      .collect()
+  }
+
+  async fn workspace_updates(pool: &PgPool, workspace_id: &str) -> Vec<(String, Vec<u8>, i64)> {
+    sqlx::query_as::<_, (String, Vec<u8>, i64)>(
+      r#"
+      SELECT guid, blob, (EXTRACT(EPOCH FROM created_at) * 1000)::BIGINT
+      FROM updates
+      WHERE workspace_id = $1
+      ORDER BY guid, created_at
+      "#,
+    )
+    .bind(workspace_id)
+    .fetch_all(pool)
+    .await
+    .unwrap()
+  }
+
+  async fn workspace_blobs(pool: &PgPool, workspace_id: &str) -> Vec<(String, String, i32)> {
+    sqlx::query_as::<_, (String, String, i32)>(
+      "SELECT key, mime, size FROM blobs WHERE workspace_id = $1 AND status = 'completed' ORDER BY key",
+    )
+    .bind(workspace_id)
+    .fetch_all(pool)
+    .await
+    .unwrap()
+  }
+
+  #[tokio::test]
+  async fn exported_workspaces_import_into_a_fresh_workspace_across_batches() {
+    let temp = tempfile::tempdir().unwrap();
+    let Some((runtime, pool)) = archive_runtime(temp.path()).await else {
+      return;
+    };
+    let properties_id = format!("db${SOURCE_WORKSPACE}$docProperties");
+    for (guid, blob) in [
+      (SOURCE_WORKSPACE, b"root".as_slice()),
+      (&properties_id, b"props"),
+      ("page-1", b"page"),
+    ] {
+      sqlx::query("INSERT INTO snapshots (workspace_id, guid, blob, updated_at) VALUES ($1, $2, $3, $4)")
+        .bind(SOURCE_WORKSPACE)
+        .bind(guid)
+        .bind(blob)
+        .bind(timestamp_from_ms(1_700_000_000_000).unwrap())
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+    for (created_at_ms, blob) in [(1_700_000_001_000, b"update-1"), (1_700_000_002_000, b"update-2")] {
+      sqlx::query("INSERT INTO updates (workspace_id, guid, blob, created_at) VALUES ($1, 'page-1', $2, $3)")
+        .bind(SOURCE_WORKSPACE)
+        .bind(blob.as_slice())
+        .bind(timestamp_from_ms(created_at_ms).unwrap())
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+    let image = vec![7; 64];
+    runtime
+      .object_storage_put(
+        &format!("{SOURCE_WORKSPACE}/image"),
+        image.clone(),
+        ObjectPutMetadata {
+          content_type: Some("image/png".to_string()),
+          ..Default::default()
+        }
+        .complete_for_body(&image),
+      )
+      .await
+      .unwrap();
+    upsert_completed_blob(&pool, SOURCE_WORKSPACE, "image", "image/png", image.len() as i64)
+      .await
+      .unwrap();
+    // Listed in the database but gone from storage.
+    upsert_completed_blob(&pool, SOURCE_WORKSPACE, "lost", "image/png", 10)
+      .await
+      .unwrap();
+
+    let path = temp.path().join("workspace.afarchive").to_string_lossy().to_string();
+    let mut export_calls = 0;
+    let exported = loop {
+      export_calls += 1;
+      let progress = runtime
+        .export_workspace_archive(SOURCE_WORKSPACE.to_string(), ARCHIVE_ID.to_string(), path.clone(), 2)
+        .await
+        .unwrap();
+      if progress.completed {
+        break progress;
+      }
+    };
+    assert!(export_calls > 1, "the export spans several batches");
+    assert_eq!(
+      (exported.docs, exported.updates, exported.blobs, exported.missing_blobs),
+      (3, 2, 1, 1)
+    );
+
+    let import = |batch_limit| {
+      runtime.import_workspace_archive(
+        ARCHIVE_ID.to_string(),
+        path.clone(),
+        TARGET_WORKSPACE.to_string(),
+        batch_limit,
+      )
+    };
+    let partial = import(2).await.unwrap();
+    assert_eq!(
+      (partial.phase.as_str(), partial.docs, partial.completed),
+      ("snapshots", 2, false)
+    );
+    assert_eq!(partial.source_workspace_id, SOURCE_WORKSPACE);
+    assert_eq!(workspace_docs(&pool, TARGET_WORKSPACE).await.len(), 2);
+
+    let resumed = import(2).await.unwrap();
+    assert_eq!((resumed.docs, resumed.updates), (3, 1));
+    assert!(resumed.bytes > partial.bytes);
+    let mut imported = resumed;
+    while !imported.completed {
+      imported = import(2).await.unwrap();
+    }
+    assert_eq!((imported.docs, imported.updates, imported.blobs), (3, 2, 1));
+    let replayed = import(2).await.unwrap();
+    assert!(replayed.completed);
+    assert_eq!(replayed.bytes, imported.bytes);
+
+    let remap = |doc_id: &str| remap_doc_id(doc_id, SOURCE_WORKSPACE, TARGET_WORKSPACE);
+    let expected_docs = workspace_docs(&pool, SOURCE_WORKSPACE)
+      .await
+      .into_iter()
+      .map(|(guid, blob)| (remap(&guid), blob))
+      .collect::<BTreeMap<_, _>>();
+    assert!(expected_docs.contains_key(TARGET_WORKSPACE));
+    assert!(expected_docs.contains_key(&format!("db${TARGET_WORKSPACE}$docProperties")));
+    assert_eq!(workspace_docs(&pool, TARGET_WORKSPACE).await, expected_docs);
+    assert_eq!(
+      workspace_updates(&pool, TARGET_WORKSPACE).await,
+      workspace_updates(&pool, SOURCE_WORKSPACE).await
+    );
+    assert_eq!(
+      workspace_blobs(&pool, TARGET_WORKSPACE).await,
+      vec![("image".to_string(), "image/png".to_string(), 64)]
+    );
+    let object = runtime
+      .object_storage_get(&format!("{TARGET_WORKSPACE}/image"))
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(object.body, image);
+  }
+}
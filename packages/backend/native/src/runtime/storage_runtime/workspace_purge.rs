@@ -0,0 +1,474 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+
+use super::{
+  RuntimeError, RuntimeResult, RuntimeWorkspacePurgeResult, StorageRuntime,
+  blob_cleanup::quarantine_prefix,
+  blob_reclaimer::{abort_upload_idempotent, delete_object_idempotent},
+  napi_error,
+};
+
+const PURGE_CHECKPOINT_KIND: &str = "workspace_purge";
+// Remaining-work counts stop at this many rows per table so reporting stays
+// cheap on very large workspaces.
+const PURGE_REMAINING_COUNT_CAP: i64 = 10_000;
+
+/// Purge steps in execution order. Doc data keys go last so snapshots and
+/// updates stay decryptable until they are gone.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum PurgePhase {
+  #[default]
+  Updates,
+  SnapshotHistories,
+  Snapshots,
+  DocBlobRefs,
+  BlobCleanupCandidates,
+  BlobIntegrityFindings,
+  BlobQuotaReservations,
+  WorkspaceStorageUsage,
+  BlobDerivatives,
+  Blobs,
+  Objects,
+  RuntimeStates,
+  DocDataKeys,
+  Done,
+}
+
+/// Table, key columns and workspace predicate of a row-deleting phase.
+struct PurgeTable {
+  table: &'static str,
+  key: &'static str,
+  predicate: &'static str,
+}
+
+const ROW_PHASES: [PurgePhase; 12] = [
+  PurgePhase::Updates,
+  PurgePhase::SnapshotHistories,
+  PurgePhase::Snapshots,
+  PurgePhase::DocBlobRefs,
+  PurgePhase::BlobCleanupCandidates,
+  PurgePhase::BlobIntegrityFindings,
+  PurgePhase::BlobQuotaReservations,
+  PurgePhase::WorkspaceStorageUsage,
+  PurgePhase::BlobDerivatives,
+  PurgePhase::Blobs,
+  PurgePhase::RuntimeStates,
+  PurgePhase::DocDataKeys,
+];
+
+impl PurgePhase {
+  fn as_str(self) -> &'static str {
+    match self {
+      Self::Updates => "updates",
+      Self::SnapshotHistories => "snapshot_histories",
+      Self::Snapshots => "snapshots",
+      Self::DocBlobRefs => "doc_blob_refs",
+      Self::BlobCleanupCandidates => "blob_cleanup_candidates",
+      Self::BlobIntegrityFindings => "blob_integrity_findings",
+      Self::BlobQuotaReservations => "blob_quota_reservations",
+      Self::WorkspaceStorageUsage => "workspace_storage_usage",
+      Self::BlobDerivatives => "blob_derivatives",
+      Self::Blobs => "blobs",
+      Self::Objects => "objects",
+      Self::RuntimeStates => "runtime_states",
+      Self::DocDataKeys => "doc_data_keys",
+      Self::Done => "done",
+    }
+  }
+
+  fn next(self) -> Self {
+    match self {
+      Self::Updates => Self::SnapshotHistories,
+      Self::SnapshotHistories => Self::Snapshots,
+      Self::Snapshots => Self::DocBlobRefs,
+      Self::DocBlobRefs => Self::BlobCleanupCandidates,
+      Self::BlobCleanupCandidates => Self::BlobIntegrityFindings,
+      Self::BlobIntegrityFindings => Self::BlobQuotaReservations,
+      Self::BlobQuotaReservations => Self::WorkspaceStorageUsage,
+      Self::WorkspaceStorageUsage => Self::BlobDerivatives,
+      Self::BlobDerivatives => Self::Blobs,
+      Self::Blobs => Self::Objects,
+      Self::Objects => Self::RuntimeStates,
+      Self::RuntimeStates => Self::DocDataKeys,
+      Self::DocDataKeys | Self::Done => Self::Done,
+    }
+  }
+
+  fn table(self) -> Option<PurgeTable> {
+    let (table, key, predicate) = match self {
+      Self::Updates => ("updates", "workspace_id, guid, created_at", "workspace_id = $1"),
+      Self::SnapshotHistories => (
+        "snapshot_histories",
+        "workspace_id, guid, timestamp",
+        "workspace_id = $1",
+      ),
+      Self::Snapshots => ("snapshots", "workspace_id, guid", "workspace_id = $1"),
+      Self::DocBlobRefs => (
+        "doc_blob_refs",
+        "workspace_id, doc_id, blob_key, block_id",
+        "workspace_id = $1",
+      ),
+      Self::BlobCleanupCandidates => ("blob_cleanup_candidates", "workspace_id, blob_key", "workspace_id = $1"),
+      Self::BlobIntegrityFindings => (
+        "blob_integrity_findings",
+        "run_id, workspace_id, blob_key, kind",
+        "workspace_id = $1",
+      ),
+      Self::BlobQuotaReservations => ("blob_quota_reservations", "workspace_id, blob_key", "workspace_id = $1"),
+      Self::WorkspaceStorageUsage => ("workspace_storage_usage", "workspace_id", "workspace_id = $1"),
+      Self::BlobDerivatives => (
+        "blob_derivatives",
+        "workspace_id, blob_key, max_edge",
+        "workspace_id = $1",
+      ),
+      Self::Blobs => ("blobs", "workspace_id, key", "workspace_id = $1"),
+      Self::RuntimeStates => ("runtime_states", "purpose, token_hash", "payload->>'workspaceId' = $1"),
+      Self::DocDataKeys => ("doc_data_keys", "workspace_id, version", "workspace_id = $1"),
+      Self::Objects | Self::Done => return None,
+    };
+    Some(PurgeTable { table, key, predicate })
+  }
+}
+
+impl PurgeTable {
+  fn delete_sql(&self) -> String {
+    let Self { table, key, predicate } = self;
+    format!("DELETE FROM {table} WHERE ({key}) IN (SELECT {key} FROM {table} WHERE {predicate} LIMIT $2)")
+  }
+
+  fn count_sql(&self) -> String {
+    let Self { table, predicate, .. } = self;
+    format!("SELECT COUNT(*) FROM (SELECT 1 FROM {table} WHERE {predicate} LIMIT $2) AS remaining")
+  }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct PurgeCursor {
+  phase: PurgePhase,
+  deleted_rows: i64,
+  deleted_objects: i64,
+}
+
+#[derive(FromRow)]
+struct BlobRow {
+  key: String,
+  upload_id: Option<String>,
+}
+
+struct WorkspacePurgeStore {
+  pool: PgPool,
+}
+
+impl WorkspacePurgeStore {
+  fn new(pool: PgPool) -> Self {
+    Self { pool }
+  }
+
+  async fn workspace_exists(&self, workspace_id: &str) -> RuntimeResult<bool> {
+    sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM workspaces WHERE id = $1)")
+      .bind(workspace_id)
+      .fetch_one(&self.pool)
+      .await
+      .map_err(|err| RuntimeError::database("WorkspacePurge workspace check failed", err))
+  }
+
+  async fn delete_rows(&self, table: &PurgeTable, workspace_id: &str, limit: i64) -> RuntimeResult<i64> {
+    let result = sqlx::query(&table.delete_sql())
+      .bind(workspace_id)
+      .bind(limit)
+      .execute(&self.pool)
+      .await
+      .map_err(|err| RuntimeError::database(format!("WorkspacePurge delete {} failed", table.table), err))?;
+    Ok(result.rows_affected() as i64)
+  }
+
+  async fn count_remaining(&self, table: &PurgeTable, workspace_id: &str) -> RuntimeResult<i64> {
+    sqlx::query_scalar::<_, i64>(&table.count_sql())
+      .bind(workspace_id)
+      .bind(PURGE_REMAINING_COUNT_CAP)
+      .fetch_one(&self.pool)
+      .await
+      .map_err(|err| RuntimeError::database(format!("WorkspacePurge count {} failed", table.table), err))
+  }
+
+  async fn load_blobs(&self, workspace_id: &str, limit: i64) -> RuntimeResult<Vec<BlobRow>> {
+    sqlx::query_as::<_, BlobRow>("SELECT key, upload_id FROM blobs WHERE workspace_id = $1 ORDER BY key LIMIT $2")
+      .bind(workspace_id)
+      .bind(limit)
+      .fetch_all(&self.pool)
+      .await
+      .map_err(|err| RuntimeError::database("WorkspacePurge load blobs failed", err))
+  }
+
+  async fn delete_blob(&self, workspace_id: &str, key: &str) -> RuntimeResult<i64> {
+    let result = sqlx::query("DELETE FROM blobs WHERE workspace_id = $1 AND key = $2")
+      .bind(workspace_id)
+      .bind(key)
+      .execute(&self.pool)
+      .await
+      .map_err(|err| RuntimeError::database("WorkspacePurge delete blob failed", err))?;
+    Ok(result.rows_affected() as i64)
+  }
+
+  async fn load_cursor(&self, workspace_id: &str) -> RuntimeResult<Option<PurgeCursor>> {
+    let cursor = sqlx::query_scalar::<_, serde_json::Value>(
+      "SELECT cursor FROM blob_reconciliation_checkpoints WHERE kind = $1 AND scope = $2",
+    )
+    .bind(PURGE_CHECKPOINT_KIND)
+    .bind(workspace_id)
+    .fetch_optional(&self.pool)
+    .await
+    .map_err(|err| RuntimeError::database("WorkspacePurge checkpoint load failed", err))?;
+    cursor
+      .map(|cursor| {
+        serde_json::from_value(cursor).map_err(|err| RuntimeError::json("WorkspacePurge decode checkpoint", err))
+      })
+      .transpose()
+  }
+
+  async fn upsert_cursor(&self, workspace_id: &str, cursor: &PurgeCursor, completed: bool) -> RuntimeResult<()> {
+    let status = if completed { "completed" } else { "running" };
+    let cursor_json =
+      serde_json::to_value(cursor).map_err(|err| RuntimeError::json("WorkspacePurge encode checkpoint", err))?;
+    sqlx::query(
+      r#"
+      INSERT INTO blob_reconciliation_checkpoints
+        (kind, scope, status, cursor, completed_at)
+      VALUES ($1, $2, $3, $4, CASE WHEN $5 THEN CURRENT_TIMESTAMP ELSE NULL END)
+      ON CONFLICT (kind, scope) DO UPDATE
+        SET status = EXCLUDED.status,
+            cursor = EXCLUDED.cursor,
+            completed_at = EXCLUDED.completed_at,
+            updated_at = CURRENT_TIMESTAMP
+      "#,
+    )
+    .bind(PURGE_CHECKPOINT_KIND)
+    .bind(workspace_id)
+    .bind(status)
+    .bind(cursor_json)
+    .bind(completed)
+    .execute(&self.pool)
+    .await
+    .map_err(|err| RuntimeError::database("WorkspacePurge checkpoint write failed", err))?;
+    Ok(())
+  }
+}
+
+impl StorageRuntime {
+  /// Without a blob storage backend there are no objects to delete, so only
+  /// the rows go.
+  fn has_blob_backend(&self) -> RuntimeResult<bool> {
+    Ok(self.config()?.backends.contains_key("blob"))
+  }
+
+  async fn purge_workspace_blobs(
+    &self,
+    store: &WorkspacePurgeStore,
+    workspace_id: &str,
+    limit: i64,
+  ) -> RuntimeResult<(i64, i64)> {
+    let delete_objects = self.has_blob_backend()?;
+    let rows = store.load_blobs(workspace_id, limit).await?;
+    let mut deleted_rows = 0;
+    for row in &rows {
+      let object_key = format!("{workspace_id}/{}", row.key);
+      if delete_objects {
+        if let Some(upload_id) = row.upload_id.as_deref() {
+          abort_upload_idempotent(self, &object_key, upload_id).await?;
+        }
+        delete_object_idempotent(self, &object_key).await?;
+      }
+      deleted_rows += store.delete_blob(workspace_id, &row.key).await?;
+    }
+    Ok((rows.len() as i64, deleted_rows))
+  }
+
+  /// Objects of a workspace live under its own prefix and, once quarantined
+  /// by blob cleanup, under its quarantine prefix.
+  fn workspace_object_prefixes(workspace_id: &str) -> [String; 2] {
+    [format!("{workspace_id}/"), quarantine_prefix(workspace_id)]
+  }
+
+  /// Delete objects left under the workspace prefix without a `blobs` row.
+  /// Each call lists from the start because deleted keys drop out of the
+  /// listing.
+  async fn purge_workspace_objects(&self, workspace_id: &str, limit: i64) -> RuntimeResult<(i64, bool)> {
+    if !self.has_blob_backend()? {
+      return Ok((0, true));
+    }
+    let mut deleted = 0;
+    for prefix in Self::workspace_object_prefixes(workspace_id) {
+      let remaining = limit - deleted;
+      let max_keys = i32::try_from(remaining).unwrap_or(i32::MAX);
+      let page = self
+        .object_storage_list_page(Some(prefix), None, None, max_keys)
+        .await?;
+      for entry in &page.entries {
+        delete_object_idempotent(self, &entry.key).await?;
+      }
+      deleted += page.entries.len() as i64;
+      if (page.entries.len() as i64) >= remaining || page.next_continuation_token.is_some() {
+        return Ok((deleted, false));
+      }
+    }
+    Ok((deleted, true))
+  }
+
+  async fn has_workspace_objects(&self, workspace_id: &str) -> RuntimeResult<bool> {
+    if !self.has_blob_backend()? {
+      return Ok(false);
+    }
+    for prefix in Self::workspace_object_prefixes(workspace_id) {
+      let page = self.object_storage_list_page(Some(prefix), None, None, 1).await?;
+      if !page.entries.is_empty() {
+        return Ok(true);
+      }
+    }
+    Ok(false)
+  }
+}
+
+#[napi_derive::napi]
+impl StorageRuntime {
+  /// Delete what is left of a deleted workspace in bounded batches: doc rows,
+  /// blob metadata and objects, runtime state and doc data keys. At most
+  /// `batch_limit` rows or objects are removed per call, and progress is
+  /// checkpointed so housekeeping can call this until `completed` is true.
+  /// Without a blob storage backend only rows are purged.
+  #[napi]
+  pub async fn purge_workspace(
+    &self,
+    workspace_id: String,
+    batch_limit: i64,
+  ) -> napi::Result<RuntimeWorkspacePurgeResult> {
+    if workspace_id.trim().is_empty() {
+      return Err(napi_error("workspace purge workspace id must not be empty"));
+    }
+    if batch_limit <= 0 {
+      return Err(napi_error("workspace purge batch limit must be positive"));
+    }
+
+    let store = WorkspacePurgeStore::new(self.pool().await?);
+    if store.workspace_exists(&workspace_id).await? {
+      return Err(napi_error(format!(
+        "workspace {workspace_id} still exists; delete it before purging its data"
+      )));
+    }
+
+    let mut cursor = store.load_cursor(&workspace_id).await?.unwrap_or_default();
+    if cursor.phase == PurgePhase::Done {
+      // Re-verify from the start; phases with nothing left are cheap.
+      cursor.phase = PurgePhase::default();
+    }
+
+    let mut remaining = batch_limit;
+    let mut deleted_rows = 0;
+    let mut deleted_objects = 0;
+    while remaining > 0 && cursor.phase != PurgePhase::Done {
+      let (processed, exhausted) = match cursor.phase {
+        PurgePhase::Blobs => {
+          let (scanned, deleted) = self.purge_workspace_blobs(&store, &workspace_id, remaining).await?;
+          deleted_rows += deleted;
+          deleted_objects += scanned;
+          (scanned, scanned < remaining)
+        }
+        PurgePhase::Objects => {
+          let (deleted, exhausted) = self.purge_workspace_objects(&workspace_id, remaining).await?;
+          deleted_objects += deleted;
+          (deleted, exhausted)
+        }
+        phase => {
+          let table = phase
+            .table()
+            .ok_or_else(|| RuntimeError::invalid_state("WorkspacePurge phase has no table"))?;
+          let deleted = store.delete_rows(&table, &workspace_id, remaining).await?;
+          deleted_rows += deleted;
+          (deleted, deleted < remaining)
+        }
+      };
+      remaining -= processed;
+      if exhausted {
+        cursor.phase = cursor.phase.next();
+      }
+    }
+    cursor.deleted_rows += deleted_rows;
+    cursor.deleted_objects += deleted_objects;
+
+    let mut remaining_rows = 0;
+    for phase in ROW_PHASES {
+      if let Some(table) = phase.table() {
+        remaining_rows += store.count_remaining(&table, &workspace_id).await?;
+      }
+    }
+    let remaining_objects = self.has_workspace_objects(&workspace_id).await?;
+    let completed = cursor.phase == PurgePhase::Done && remaining_rows == 0 && !remaining_objects;
+    if cursor.phase == PurgePhase::Done && !completed {
+      // Something was written behind the sweep; start another pass next call.
+      cursor.phase = PurgePhase::default();
+    }
+    store.upsert_cursor(&workspace_id, &cursor, completed).await?;
+
+    Ok(RuntimeWorkspacePurgeResult {
+      workspace_id,
+      phase: cursor.phase.as_str().to_string(),
+      deleted_rows,
+      deleted_objects,
+      total_deleted_rows: cursor.deleted_rows,
+      total_deleted_objects: cursor.deleted_objects,
+      remaining_rows,
+      remaining_objects,
+      completed,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn phases_advance_in_order_and_cover_every_table() {
+    let mut phase = PurgePhase::default();
+    let mut seen = Vec::new();
+    while phase != PurgePhase::Done {
+      seen.push(phase);
+      phase = phase.next();
+    }
+    assert_eq!(seen.len(), ROW_PHASES.len() + 1);
+    assert_eq!(seen.last(), Some(&PurgePhase::DocDataKeys));
+    for phase in ROW_PHASES {
+      assert!(seen.contains(&phase));
+      assert!(phase.table().is_some(), "{phase:?}");
+    }
+    assert!(PurgePhase::Objects.table().is_none());
+    assert_eq!(PurgePhase::Done.next(), PurgePhase::Done);
+  }
+
+  #[test]
+  fn builds_bounded_delete_statements() {
+    let table = PurgePhase::Updates.table().unwrap();
+    assert_eq!(
+      table.delete_sql(),
+      "DELETE FROM updates WHERE (workspace_id, guid, created_at) IN \
+       (SELECT workspace_id, guid, created_at FROM updates WHERE workspace_id = $1 LIMIT $2)"
+    );
+    assert_eq!(
+      PurgePhase::RuntimeStates.table().unwrap().count_sql(),
+      "SELECT COUNT(*) FROM (SELECT 1 FROM runtime_states WHERE payload->>'workspaceId' = $1 LIMIT $2) AS remaining"
+    );
+  }
+
+  #[test]
+  fn cursor_round_trips_through_checkpoint_json() {
+    let cursor = PurgeCursor {
+      phase: PurgePhase::BlobCleanupCandidates,
+      deleted_rows: 42,
+      deleted_objects: 7,
+    };
+    let value = serde_json::to_value(&cursor).unwrap();
+    assert_eq!(value["phase"], "blob_cleanup_candidates");
+    assert_eq!(serde_json::from_value::<PurgeCursor>(value).unwrap(), cursor);
+  }
+}
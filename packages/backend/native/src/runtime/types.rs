@@ -34,6 +34,10 @@ pub struct RuntimeWorkspaceInviteLinkRecord {
   pub workspace_id: String,
   pub invite_id: String,
   pub inviter_user_id: String,
+  pub role: Option<String>,
+  pub max_uses: Option<i64>,
+  pub uses: i64,
+  pub email_domain: Option<String>,
   pub expires_at_ms: i64,
 }
 
@@ -56,6 +60,33 @@ pub struct RuntimeDocHistoryInput {
   pub history_max_age_ms: i64,
 }
 
+#[napi_derive::napi(object)]
+pub struct RuntimeDocRecord {
+  pub blob: Buffer,
+  pub timestamp_ms: i64,
+  pub editor_id: Option<String>,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeDocDataKey {
+  pub workspace_id: String,
+  pub version: i32,
+  pub master_key_id: String,
+  pub active: bool,
+  pub created_at_ms: i64,
+  pub retired_at_ms: Option<i64>,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeDocReencryptionResult {
+  pub lease_acquired: bool,
+  pub workspace_id: String,
+  pub snapshots: i64,
+  pub updates: i64,
+  pub histories: i64,
+  pub completed: bool,
+}
+
 #[napi_derive::napi(object)]
 pub struct RuntimeObjectStoragePutOptions {
   pub content_type: Option<String>,
@@ -84,6 +115,18 @@ pub struct RuntimeObjectGetResult {
   pub metadata: RuntimeObjectMetadata,
 }
 
+/// Result of a ranged read. `start` and `end` are inclusive offsets for the
+/// `Content-Range` header; when `satisfiable` is false the body is empty and
+/// the caller should answer 416 with `metadata.contentLength`.
+#[napi_derive::napi(object)]
+pub struct RuntimeObjectRangeResult {
+  pub body: Buffer,
+  pub metadata: RuntimeObjectMetadata,
+  pub satisfiable: bool,
+  pub start: i64,
+  pub end: i64,
+}
+
 #[napi_derive::napi(object)]
 pub struct RuntimePresignedObjectRequest {
   pub url: String,
@@ -108,6 +151,55 @@ pub struct RuntimeBlobCleanupResult {
   pub scanned: i64,
   pub deleted: i64,
   pub aborted_multipart: i64,
+  pub released_reservations: i64,
+  pub workspace_ids: Vec<String>,
+}
+
+/// Outcome of one multipart upload sweep. `freed_part_bytes` counts the
+/// parts of aborted uploads; `skipped_foreign` counts uploads outside the
+/// blob key layout, which are left alone; `next_cursor` is set while uploads
+/// remain.
+#[napi_derive::napi(object)]
+pub struct RuntimeMultipartSweepResult {
+  pub scanned_uploads: i64,
+  pub aborted_uploads: i64,
+  pub matched_pending_blobs: i64,
+  pub freed_part_bytes: i64,
+  pub skipped_recent: i64,
+  pub skipped_foreign: i64,
+  pub failed: i64,
+  pub next_cursor: Option<String>,
+  pub workspace_ids: Vec<String>,
+}
+
+/// Limits of a workspace's plan, as resolved by `resolveEntitlementV1`.
+#[napi_derive::napi(object)]
+pub struct RuntimeBlobQuota {
+  pub blob_limit: i64,
+  pub storage_quota: i64,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeBlobQuotaReservation {
+  pub ok: bool,
+  pub reason: Option<String>,
+  pub used_bytes: i64,
+  pub reserved_bytes: i64,
+  pub storage_quota: i64,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeWorkspaceStorageUsage {
+  pub workspace_id: String,
+  pub used_bytes: i64,
+  pub reserved_bytes: i64,
+  pub reconciled_at_ms: Option<i64>,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeStorageUsageReconcileResult {
+  pub scanned_workspaces: i64,
+  pub corrected_workspaces: i64,
   pub workspace_ids: Vec<String>,
 }
 
@@ -120,6 +212,12 @@ pub struct RuntimeBlobCompleteResult {
   pub last_modified_ms: Option<i64>,
 }
 
+/// Longest-edge sizes of the image derivatives that were written.
+#[napi_derive::napi(object)]
+pub struct RuntimeBlobDerivativeResult {
+  pub generated_edges: Vec<u32>,
+}
+
 #[napi_derive::napi(object)]
 pub struct RuntimeBlobMetadataBackfillResult {
   pub scanned_objects: i64,
@@ -132,6 +230,77 @@ pub struct RuntimeBlobMetadataBackfillResult {
   pub workspace_ids: Vec<String>,
 }
 
+#[napi_derive::napi(object)]
+pub struct RuntimeBlobReencryptionResult {
+  pub workspace_id: String,
+  pub scanned: i64,
+  pub reencrypted: i64,
+  pub skipped: i64,
+  pub failed: i64,
+  pub completed: bool,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeBlobScrubResult {
+  pub run_id: String,
+  pub phase: String,
+  pub scanned: i64,
+  pub findings: i64,
+  pub failed: i64,
+  pub completed: bool,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeBlobScrubFinding {
+  pub workspace_id: String,
+  pub blob_key: String,
+  pub kind: String,
+  pub expected_size: Option<i64>,
+  pub actual_size: Option<i64>,
+  pub detail: serde_json::Value,
+  pub found_at_ms: i64,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeBlobScrubFindingCount {
+  pub kind: String,
+  pub count: i64,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeBlobScrubSummary {
+  pub run_id: String,
+  pub workspace_id: Option<String>,
+  pub status: String,
+  pub scanned: i64,
+  pub findings: i64,
+  pub failed: i64,
+  pub started_at_ms: i64,
+  pub finished_at_ms: Option<i64>,
+  pub counts: Vec<RuntimeBlobScrubFindingCount>,
+  pub recent_findings: Vec<RuntimeBlobScrubFinding>,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeStorageMigrationResult {
+  pub phase: String,
+  pub dry_run: bool,
+  pub scanned: i64,
+  pub copied: i64,
+  pub skipped: i64,
+  pub failed: i64,
+  pub missing_keys: Vec<String>,
+  pub mismatched_keys: Vec<String>,
+  /// Keys that could not be copied or compared in this batch. The checkpoint
+  /// moves past them; the verification pass reports them again if the
+  /// target still lacks them.
+  pub failed_keys: Vec<String>,
+  /// Verified keys that matched on length and encryption only, because a
+  /// provider reported no CRC32 checksum for them.
+  pub unverified_checksums: i64,
+  pub completed: bool,
+}
+
 #[napi_derive::napi(object)]
 pub struct RuntimeDocBlobRefsResult {
   pub scanned_docs: i64,
@@ -157,12 +326,28 @@ pub struct RuntimeBlobCleanupPlanResult {
 pub struct RuntimeBlobCleanupExecuteResult {
   pub scanned_candidates: i64,
   pub deleted_objects: i64,
+  pub quarantined_objects: i64,
   pub deleted_metadata: i64,
   pub skipped_still_referenced: i64,
   pub failed: i64,
   pub workspace_ids: Vec<String>,
 }
 
+#[napi_derive::napi(object)]
+pub struct RuntimeBlobCleanupRestoreResult {
+  pub scanned_candidates: i64,
+  pub restored_objects: i64,
+  pub failed: i64,
+  pub workspace_ids: Vec<String>,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeBlobCleanupPurgeResult {
+  pub scanned_candidates: i64,
+  pub purged_objects: i64,
+  pub failed: i64,
+}
+
 #[napi_derive::napi(object)]
 pub struct RuntimeDocCompactionResult {
   pub lease_acquired: bool,
@@ -200,3 +385,232 @@ pub struct RuntimeWorkspaceStatsDailyRecalibrationResult {
   pub snapshotted: i64,
   pub skipped: bool,
 }
+
+#[napi_derive::napi(object)]
+pub struct RuntimeJobEnqueueInput {
+  pub queue: String,
+  pub payload: serde_json::Value,
+  pub dedupe_key: Option<String>,
+  pub run_at_ms: Option<i64>,
+  pub max_attempts: Option<i32>,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeJobEnqueueResult {
+  pub job_id: String,
+  pub deduplicated: bool,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeJobRecord {
+  pub job_id: String,
+  pub queue: String,
+  pub dedupe_key: Option<String>,
+  pub payload: serde_json::Value,
+  pub attempts: i32,
+  pub max_attempts: i32,
+  pub locked_until_ms: i64,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeJobFailResult {
+  pub status: String,
+  pub next_run_at_ms: Option<i64>,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeRateLimitResult {
+  pub allowed: bool,
+  pub remaining: f64,
+  pub retry_after_ms: Option<i64>,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeTotpEnrollment {
+  pub secret: String,
+  pub otpauth_url: String,
+  pub expires_at_ms: i64,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeTotpVerifyResult {
+  pub ok: bool,
+  pub reason: Option<String>,
+  pub recovery_codes: Option<Vec<String>>,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeWebauthnRegistrationInput {
+  pub client_data_json: Buffer,
+  pub attestation_object: Buffer,
+  pub rp_id: String,
+  pub expected_origins: Vec<String>,
+  pub require_user_verification: Option<bool>,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeWebauthnCredential {
+  pub credential_id: String,
+  pub public_key: Buffer,
+  pub algorithm: i32,
+  pub sign_count: i64,
+  pub aaguid: String,
+  pub attestation_format: String,
+  pub user_verified: bool,
+  pub backup_eligible: bool,
+  pub backed_up: bool,
+  pub challenge_payload: serde_json::Value,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeWebauthnAssertionInput {
+  pub credential_id: String,
+  pub client_data_json: Buffer,
+  pub authenticator_data: Buffer,
+  pub signature: Buffer,
+  pub public_key: Buffer,
+  pub stored_sign_count: i64,
+  pub rp_id: String,
+  pub expected_origins: Vec<String>,
+  pub require_user_verification: Option<bool>,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeWebauthnAssertion {
+  pub credential_id: String,
+  pub sign_count: i64,
+  pub user_verified: bool,
+  pub backed_up: bool,
+  pub challenge_payload: serde_json::Value,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeWorkspaceInviteLinkInput {
+  pub workspace_id: String,
+  pub invite_id: String,
+  pub inviter_user_id: String,
+  pub role: Option<String>,
+  pub max_uses: Option<i64>,
+  pub email_domain: Option<String>,
+  pub ttl_ms: i64,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeWorkspaceInviteLinkRedeemResult {
+  pub ok: bool,
+  pub reason: Option<String>,
+  pub link: Option<RuntimeWorkspaceInviteLinkRecord>,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeAuditEventInput {
+  pub workspace_id: Option<String>,
+  pub actor_id: Option<String>,
+  pub action: String,
+  pub target_type: Option<String>,
+  pub target_id: Option<String>,
+  pub details: Option<serde_json::Value>,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeAuditEvent {
+  pub seq: i64,
+  pub workspace_id: Option<String>,
+  pub actor_id: Option<String>,
+  pub action: String,
+  pub target_type: Option<String>,
+  pub target_id: Option<String>,
+  pub details: serde_json::Value,
+  pub prev_hash: String,
+  pub hash: String,
+  pub created_at_ms: i64,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeAuditEventQuery {
+  pub workspace_id: Option<String>,
+  pub actor_id: Option<String>,
+  pub action: Option<String>,
+  pub since_ms: Option<i64>,
+  pub until_ms: Option<i64>,
+  pub cursor: Option<String>,
+  pub limit: Option<i64>,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeAuditEventPage {
+  pub events: Vec<RuntimeAuditEvent>,
+  pub next_cursor: Option<String>,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeAuditChainVerification {
+  pub checked: i64,
+  pub last_seq: Option<i64>,
+  pub broken_at_seq: Option<i64>,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeWorkspaceStatsSeriesPoint {
+  pub bucket_start_ms: i64,
+  pub storage_bytes: Option<i64>,
+  pub doc_count: Option<i64>,
+  pub member_count: Option<i64>,
+  pub blob_count: Option<i64>,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeWorkspaceStatsSeries {
+  pub workspace_id: String,
+  pub points: Vec<RuntimeWorkspaceStatsSeriesPoint>,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeWorkspaceStatsGrowth {
+  pub workspace_id: String,
+  pub start_value: i64,
+  pub end_value: i64,
+  pub growth: i64,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeWorkspaceArchiveProgress {
+  pub archive_id: String,
+  pub source_workspace_id: String,
+  pub target_workspace_id: Option<String>,
+  pub phase: String,
+  pub docs: i64,
+  pub updates: i64,
+  pub blobs: i64,
+  pub missing_blobs: i64,
+  pub bytes: i64,
+  pub completed: bool,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeWorkspacePurgeResult {
+  pub workspace_id: String,
+  pub phase: String,
+  pub deleted_rows: i64,
+  pub deleted_objects: i64,
+  pub total_deleted_rows: i64,
+  pub total_deleted_objects: i64,
+  pub remaining_rows: i64,
+  pub remaining_objects: bool,
+  pub completed: bool,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeConfigIssue {
+  pub path: String,
+  pub message: String,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeConfigReloadResult {
+  /// Storage scopes with a configured backend after the reload.
+  pub backends: Vec<String>,
+  /// The configured datasource URL, pool or replica settings differ from the
+  /// connected ones; they only take effect after `stop()` and `start()`.
+  pub restart_required: bool,
+}
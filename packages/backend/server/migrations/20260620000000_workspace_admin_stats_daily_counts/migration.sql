@@ -0,0 +1,4 @@
+-- AlterTable
+ALTER TABLE "workspace_admin_stats_daily"
+  ADD COLUMN IF NOT EXISTS "snapshot_count" BIGINT NOT NULL DEFAULT 0,
+  ADD COLUMN IF NOT EXISTS "blob_count" BIGINT NOT NULL DEFAULT 0;
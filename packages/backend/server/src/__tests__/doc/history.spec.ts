@@ -92,7 +92,7 @@ test('should create doc history if never created before', async t => {
 });
 
 test('should not create history if timestamp equals to last record', async t => {
-  const timestamp = new Date();
+  const timestamp = Date.now();
 
   // @ts-expect-error private method
   Sinon.stub(adapter, 'lastDocHistory').resolves({ timestamp, state: null });
@@ -111,11 +111,11 @@ test('should not create history if timestamp equals to last record', async t =>
 });
 
 test('should not create history if time diff is less than interval config', async t => {
-  const timestamp = new Date();
+  const timestamp = Date.now();
 
   // @ts-expect-error private method
   Sinon.stub(adapter, 'lastDocHistory').resolves({
-    timestamp: new Date(timestamp.getTime() - 1000),
+    timestamp: timestamp - 1000,
     state: Buffer.from([0, 1]),
   });
 
@@ -133,11 +133,11 @@ test('should not create history if time diff is less than interval config', asyn
 });
 
 test('should create history if time diff is larger than interval config and state diff', async t => {
-  const timestamp = new Date();
+  const timestamp = Date.now();
 
   // @ts-expect-error private method
   Sinon.stub(adapter, 'lastDocHistory').resolves({
-    timestamp: new Date(timestamp.getTime() - 1000 * 60 * 20),
+    timestamp: timestamp - 1000 * 60 * 20,
     state: Buffer.from([0, 1]),
   });
 
@@ -155,11 +155,11 @@ test('should create history if time diff is larger than interval config and stat
 });
 
 test('should create history with force flag even if time diff in small', async t => {
-  const timestamp = new Date();
+  const timestamp = Date.now();
 
   // @ts-expect-error private method
   Sinon.stub(adapter, 'lastDocHistory').resolves({
-    timestamp: new Date(timestamp.getTime() - 1),
+    timestamp: timestamp - 1,
     state: Buffer.from([0, 1]),
   });
 
@@ -16,7 +16,9 @@ async function ensureAnalyticsTables(db: PrismaClient) {
     CREATE TABLE IF NOT EXISTS workspace_admin_stats_daily (
       workspace_id VARCHAR NOT NULL,
       date DATE NOT NULL,
+      snapshot_count BIGINT NOT NULL DEFAULT 0,
       snapshot_size BIGINT NOT NULL DEFAULT 0,
+      blob_count BIGINT NOT NULL DEFAULT 0,
       blob_size BIGINT NOT NULL DEFAULT 0,
       member_count BIGINT NOT NULL DEFAULT 0,
       updated_at TIMESTAMPTZ(3) NOT NULL DEFAULT NOW(),
@@ -117,6 +117,7 @@ const storageRuntime = {
   presignUploadPart: async () => undefined,
   listMultipartUploadParts: async () => undefined,
   completeMultipartUpload: async () => undefined,
+  generateBlobDerivatives: async () => ({ generatedEdges: [] }),
   completeWorkspaceBlobUpload: async (workspaceId: string, key: string) => {
     const objectKey = `${workspaceId}/${key}`;
     const configured = completeResults.get(objectKey);
@@ -209,6 +209,24 @@ test('should be able to get permission granted workspace', async t => {
   t.is(res.text, 'blob');
 });
 
+test('should serve an image derivative for the requested size', async t => {
+  const { app, storage } = t.context;
+
+  storage.getDerivative.resolves(blob());
+  let res = await app.GET('/api/workspaces/public/blobs/test?size=256');
+
+  t.is(res.status, HttpStatus.OK);
+  t.is(res.text, 'blob');
+  t.deepEqual(storage.getDerivative.lastCall.args, ['public', 'test', 256]);
+
+  storage.getDerivative.resetHistory();
+  storage.get.resolves(blob());
+  res = await app.GET('/api/workspaces/public/blobs/test?size=large');
+
+  t.is(res.status, HttpStatus.OK);
+  t.false(storage.getDerivative.called);
+});
+
 test('should return 404 if blob not found', async t => {
   const { app, storage } = t.context;
 
@@ -3,11 +3,26 @@ import { z } from 'zod';
 
 import { defineModuleConfig } from '../config';
 
+export interface DatabasePoolConfig {
+  maxConnections?: number;
+  minConnections?: number;
+  acquireTimeoutMs?: number;
+  idleTimeoutMs?: number;
+  statementTimeoutMs?: number;
+}
+
+export interface DatabaseReplicaConfig {
+  urls?: string[];
+  maxLagMs?: number;
+}
+
 declare global {
   interface AppConfigSchema {
     db: {
       datasourceUrl: string;
       prisma: ConfigItem<Prisma.PrismaClientOptions>;
+      pool: ConfigItem<DatabasePoolConfig>;
+      replicas: ConfigItem<DatabaseReplicaConfig>;
     };
   }
 }
@@ -24,4 +39,18 @@ defineModuleConfig('db', {
     default: {},
     link: 'https://www.prisma.io/docs/reference/api-reference/prisma-client-reference',
   },
+  pool: {
+    desc: 'Connection pool sizing and timeouts for the native backend and storage runtimes.',
+    default: {
+      maxConnections: 5,
+      acquireTimeoutMs: 5000,
+    },
+  },
+  replicas: {
+    desc: 'Read replicas for read-only native runtime queries. Replicas lagging more than `maxLagMs` are skipped in favour of the primary.',
+    default: {
+      urls: [],
+      maxLagMs: 10000,
+    },
+  },
 });
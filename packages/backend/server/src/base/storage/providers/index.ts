@@ -4,6 +4,8 @@ export type StorageProviderName =
   | 'fs'
   | 'aws-s3'
   | 'cloudflare-r2'
+  | 'gcs'
+  | 'azure-blob'
   | 'assetpack';
 
 export interface FsStorageConfig {
@@ -41,6 +43,37 @@ export interface R2StorageConfig extends Omit<S3StorageConfig, 'endpoint'> {
   };
 }
 
+export interface GcsStorageConfig {
+  endpoint?: string;
+  credentials?: {
+    accessKeyId?: string;
+    secretAccessKey?: string;
+  };
+  requestTimeoutMs?: number;
+  minPartSize?: number;
+  presign?: S3StorageConfig['presign'];
+  usePresignedURL?: {
+    enabled: boolean;
+  };
+}
+
+export interface AzureBlobStorageConfig {
+  accountName: string;
+  endpoint?: string;
+  credentials?: {
+    accountKey?: string;
+    sasToken?: string;
+  };
+  requestTimeoutMs?: number;
+  minPartSize?: number;
+  presign?: {
+    expiresInSeconds?: number;
+  };
+  usePresignedURL?: {
+    enabled: boolean;
+  };
+}
+
 export type StorageProviderConfig = { bucket: string } & (
   | {
       provider: 'fs';
@@ -54,6 +87,14 @@ export type StorageProviderConfig = { bucket: string } & (
       provider: 'cloudflare-r2';
       config: R2StorageConfig;
     }
+  | {
+      provider: 'gcs';
+      config: GcsStorageConfig;
+    }
+  | {
+      provider: 'azure-blob';
+      config: AzureBlobStorageConfig;
+    }
   | {
       provider: 'assetpack';
       config: AssetpackStorageConfig;
@@ -215,6 +256,110 @@ export const StorageJSONSchema: JSONSchema = {
         },
       },
     },
+    {
+      type: 'object',
+      properties: {
+        provider: {
+          type: 'string',
+          enum: ['gcs'],
+        },
+        bucket: {
+          type: 'string',
+        },
+        config: {
+          type: 'object',
+          description:
+            'The config for Google Cloud Storage, accessed through its XML API with HMAC keys.',
+          properties: {
+            endpoint: {
+              type: 'string',
+              description:
+                'Optional; defaults to "https://storage.googleapis.com".',
+            },
+            credentials: {
+              type: 'object',
+              description: 'The HMAC key of a service account.',
+              properties: {
+                accessKeyId: { type: 'string' },
+                secretAccessKey: { type: 'string' },
+              },
+            },
+            requestTimeoutMs:
+              S3ConfigPropertiesWithoutEndpoint.requestTimeoutMs,
+            minPartSize: S3ConfigPropertiesWithoutEndpoint.minPartSize,
+            presign: S3ConfigPropertiesWithoutEndpoint.presign,
+            usePresignedURL: {
+              type: 'object',
+              properties: {
+                enabled: {
+                  type: 'boolean',
+                  description: 'Whether to hand out presigned urls.',
+                },
+              },
+            },
+          },
+        },
+      },
+    },
+    {
+      type: 'object',
+      properties: {
+        provider: {
+          type: 'string',
+          enum: ['azure-blob'],
+        },
+        bucket: {
+          type: 'string',
+          description: 'The blob container name.',
+        },
+        config: {
+          type: 'object',
+          description: 'The config for Azure Blob Storage.',
+          properties: {
+            accountName: {
+              type: 'string',
+              description: 'The storage account name.',
+            },
+            endpoint: {
+              type: 'string',
+              description:
+                'Optional; defaults to "https://{accountName}.blob.core.windows.net". Example for Azurite: "http://127.0.0.1:10000/devstoreaccount1"',
+            },
+            credentials: {
+              type: 'object',
+              description:
+                'Either the account key (SharedKey signing) or a container SAS token.',
+              properties: {
+                accountKey: { type: 'string' },
+                sasToken: { type: 'string' },
+              },
+            },
+            requestTimeoutMs:
+              S3ConfigPropertiesWithoutEndpoint.requestTimeoutMs,
+            minPartSize: S3ConfigPropertiesWithoutEndpoint.minPartSize,
+            presign: {
+              type: 'object',
+              properties: {
+                expiresInSeconds: {
+                  type: 'number',
+                  description: 'Expiration time in seconds for SAS urls.',
+                },
+              },
+            },
+            usePresignedURL: {
+              type: 'object',
+              properties: {
+                enabled: {
+                  type: 'boolean',
+                  description: 'Whether to hand out SAS urls.',
+                },
+              },
+            },
+          },
+          required: ['accountName'],
+        },
+      },
+    },
     {
       type: 'object',
       properties: {
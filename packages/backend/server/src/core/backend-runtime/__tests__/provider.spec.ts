@@ -39,3 +39,18 @@ test('backend-runtime provider measures explicit typed methods', async t => {
   t.is(result, 3);
   t.true(runtime.cleanupExpiredRuntimeStates.calledOnceWithExactly(1000));
 });
+
+test('backend-runtime provider reloads its config on config changes', async t => {
+  const provider = new BackendRuntimeProvider();
+  const runtime = {
+    reloadConfig: Sinon.stub().resolves({
+      backends: [],
+      restartRequired: false,
+    }),
+  };
+  (provider as any).runtime = runtime;
+
+  await provider.onConfigChanged();
+
+  t.true(runtime.reloadConfig.calledOnce);
+});
@@ -5,8 +5,14 @@ import {
   type OnApplicationShutdown,
 } from '@nestjs/common';
 
+import { OnEvent } from '../../base';
 import { wrapCallMetric } from '../../base/metrics';
-import { BackendRuntime, type BackendRuntimeHealth } from '../../native';
+import {
+  BackendRuntime,
+  type BackendRuntimeHealth,
+  type RuntimeConfigReloadResult,
+  type RuntimeDocHistoryInput,
+} from '../../native';
 
 type RuntimeInstance = InstanceType<typeof BackendRuntime>;
 
@@ -42,6 +48,72 @@ export class BackendRuntimeProvider
     return await this.runtime.health();
   }
 
+  @OnEvent('config.changed')
+  async onConfigChanged() {
+    await this.reloadConfig();
+  }
+
+  async reloadConfig(): Promise<RuntimeConfigReloadResult> {
+    const result = await this.runtime.reloadConfig();
+    if (result.restartRequired) {
+      this.logger.warn(
+        'backend runtime datasource changed, restart required to apply it'
+      );
+    }
+    return result;
+  }
+
+  async renderMetrics(): Promise<string> {
+    return await this.runtime.renderMetrics();
+  }
+
+  async getDocSnapshot(workspaceId: string, docId: string) {
+    return await this.measured('getDocSnapshot', rt =>
+      rt.getDocSnapshot(workspaceId, docId)
+    );
+  }
+
+  async getDocUpdates(workspaceId: string, docId: string) {
+    return await this.measured('getDocUpdates', rt =>
+      rt.getDocUpdates(workspaceId, docId)
+    );
+  }
+
+  async getDocHistory(workspaceId: string, docId: string, timestamp: number) {
+    return await this.measured('getDocHistory', rt =>
+      rt.getDocHistory(workspaceId, docId, timestamp)
+    );
+  }
+
+  async pushDocUpdates(
+    workspaceId: string,
+    docId: string,
+    updates: Buffer[],
+    editorId?: string
+  ) {
+    return await this.measured('pushDocUpdates', rt =>
+      rt.pushDocUpdates(workspaceId, docId, updates, editorId)
+    );
+  }
+
+  async upsertDocSnapshot(
+    workspaceId: string,
+    docId: string,
+    blob: Buffer,
+    timestamp: number,
+    editorId?: string
+  ) {
+    return await this.measured('upsertDocSnapshot', rt =>
+      rt.upsertDocSnapshot(workspaceId, docId, blob, timestamp, editorId)
+    );
+  }
+
+  async createDocHistory(input: RuntimeDocHistoryInput) {
+    return await this.measured('createDocHistory', rt =>
+      rt.createDocHistory(input)
+    );
+  }
+
   async cleanupExpiredSnapshotHistories(limit: number) {
     return await this.measured('cleanupExpiredSnapshotHistories', rt =>
       rt.cleanupExpiredSnapshotHistories(limit)
@@ -1,5 +1,4 @@
 import { Injectable, Logger } from '@nestjs/common';
-import { chunk } from 'lodash-es';
 
 import {
   DocHistoryNotFound,
@@ -13,6 +12,7 @@ import {
 } from '../../../base';
 import { retryable } from '../../../base/utils/promise';
 import { Models } from '../../../models';
+import { BackendRuntimeProvider } from '../../backend-runtime';
 import { DocStorageOptions } from '../options';
 import {
   DocRecord,
@@ -43,6 +43,7 @@ export class PgWorkspaceDocStorageAdapter extends DocStorageAdapter {
 
   constructor(
     private readonly models: Models,
+    private readonly runtime: BackendRuntimeProvider,
     private readonly mutex: Mutex,
     private readonly event: EventBus,
     protected override readonly options: DocStorageOptions,
@@ -66,51 +67,31 @@ export class PgWorkspaceDocStorageAdapter extends DocStorageAdapter {
 
     const isNewDoc = !(await this.models.doc.exists(workspaceId, docId));
 
-    let pendings = updates;
-    let done = 0;
     let timestamp = Date.now();
     try {
-      await retryable(async () => {
-        if (done !== 0) {
-          pendings = pendings.slice(done);
-        }
-
-        let turn = 0;
-        const batchCount = 10;
-        for (const batch of chunk(pendings, batchCount)) {
-          const now = Date.now();
-          await this.models.doc.createUpdates(
-            batch.map((update, i) => {
-              const subSeq = turn * batchCount + i + 1;
-              const createdAt = now + subSeq;
-              timestamp = Math.max(timestamp, createdAt);
-
-              return {
-                spaceId: workspaceId,
-                docId,
-                blob: Buffer.from(update),
-                timestamp: createdAt,
-                editorId,
-              };
-            })
-          );
-          await this.queue.add(
-            'doc.mergePendingDocUpdates',
-            {
-              workspaceId,
-              docId,
-            },
-            {
-              // keep it simple to let all update merged in one job
-              jobId: `doc:merge-pending-updates:${workspaceId}:${docId}`,
-              delay: 5 * 1000 /* 5s */,
-              priority: 100,
-            }
-          );
-          turn++;
-          done += batch.length;
+      // the runtime inserts (and seals) all updates in one statement, so a
+      // retry never duplicates a partially written batch
+      timestamp = await retryable(() =>
+        this.runtime.pushDocUpdates(
+          workspaceId,
+          docId,
+          updates.map(update => Buffer.from(update)),
+          editorId
+        )
+      );
+      await this.queue.add(
+        'doc.mergePendingDocUpdates',
+        {
+          workspaceId,
+          docId,
+        },
+        {
+          // keep it simple to let all update merged in one job
+          jobId: `doc:merge-pending-updates:${workspaceId}:${docId}`,
+          delay: 5 * 1000 /* 5s */,
+          priority: 100,
         }
-      });
+      );
 
       if (isNewDoc) {
         this.event.emitDetached('doc.created', {
@@ -128,11 +109,11 @@ export class PgWorkspaceDocStorageAdapter extends DocStorageAdapter {
   }
 
   protected async getDocUpdates(workspaceId: string, docId: string) {
-    const rows = await this.models.doc.findUpdates(workspaceId, docId);
+    const rows = await this.runtime.getDocUpdates(workspaceId, docId);
 
     return rows.map(row => ({
       bin: row.blob,
-      timestamp: row.timestamp,
+      timestamp: row.timestampMs,
       editor: row.editorId,
     }));
   }
@@ -176,7 +157,7 @@ export class PgWorkspaceDocStorageAdapter extends DocStorageAdapter {
   }
 
   async getDocHistory(workspaceId: string, docId: string, timestamp: number) {
-    const history = await this.models.history.get(
+    const history = await this.runtime.getDocHistory(
       workspaceId,
       docId,
       timestamp
@@ -190,8 +171,8 @@ export class PgWorkspaceDocStorageAdapter extends DocStorageAdapter {
       spaceId: workspaceId,
       docId,
       bin: history.blob,
-      timestamp: history.timestamp,
-      editor: history.editor?.id,
+      timestamp: history.timestampMs,
+      editor: history.editorId,
     };
   }
 
@@ -279,16 +260,21 @@ export class PgWorkspaceDocStorageAdapter extends DocStorageAdapter {
         return false;
       }
 
-      await this.models.history.create(
-        {
-          spaceId: snapshot.spaceId,
-          docId: snapshot.docId,
-          timestamp: snapshot.timestamp,
-          blob: Buffer.from(snapshot.bin),
-          editorId: snapshot.editor,
-        },
-        historyMaxAge
-      );
+      const created = await this.runtime.createDocHistory({
+        workspaceId: snapshot.spaceId,
+        docId: snapshot.docId,
+        blob: Buffer.from(snapshot.bin),
+        timestampMs: snapshot.timestamp,
+        editorId: snapshot.editor,
+        force,
+        historyMinIntervalMs: this.options.historyMinInterval(
+          snapshot.spaceId
+        ),
+        historyMaxAgeMs: historyMaxAge,
+      });
+      if (!created) {
+        return false;
+      }
 
       metrics.doc
         .counter('history_created_counter', {
@@ -305,17 +291,17 @@ export class PgWorkspaceDocStorageAdapter extends DocStorageAdapter {
   }
 
   protected async getDocSnapshot(workspaceId: string, docId: string) {
-    const snapshot = await this.models.doc.get(workspaceId, docId);
+    const snapshot = await this.runtime.getDocSnapshot(workspaceId, docId);
 
     if (!snapshot) {
       return null;
     }
 
     return {
-      spaceId: snapshot.spaceId,
-      docId: snapshot.docId,
+      spaceId: workspaceId,
+      docId,
       bin: snapshot.blob,
-      timestamp: snapshot.timestamp,
+      timestamp: snapshot.timestampMs,
       // creator and editor may null if their account is deleted
       editor: snapshot.editorId,
     };
@@ -328,13 +314,13 @@ export class PgWorkspaceDocStorageAdapter extends DocStorageAdapter {
 
     try {
       const blob = Buffer.from(snapshot.bin);
-      const updatedSnapshot = await this.models.doc.upsert({
-        spaceId: snapshot.spaceId,
-        docId: snapshot.docId,
+      const updatedSnapshot = await this.runtime.upsertDocSnapshot(
+        snapshot.spaceId,
+        snapshot.docId,
         blob,
-        timestamp: snapshot.timestamp,
-        editorId: snapshot.editor,
-      });
+        snapshot.timestamp,
+        snapshot.editor
+      );
 
       if (updatedSnapshot) {
         this.event.emitDetached('doc.snapshot.updated', {
@@ -344,7 +330,7 @@ export class PgWorkspaceDocStorageAdapter extends DocStorageAdapter {
         });
       }
 
-      return !!updatedSnapshot;
+      return updatedSnapshot;
     } catch (e) {
       metrics.doc.counter('snapshot_upsert_failed').add(1);
       this.logger.error('Failed to upsert snapshot', e);
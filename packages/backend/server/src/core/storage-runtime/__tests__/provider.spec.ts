@@ -37,6 +37,10 @@ function createProvider() {
     start: Sinon.stub().resolves(),
     stop: Sinon.stub().resolves(),
     runMigrations: Sinon.stub().resolves(),
+    reloadConfig: Sinon.stub().resolves({
+      backends: ['avatar', 'blob', 'copilot'],
+      restartRequired: false,
+    }),
     health: Sinon.stub().resolves({
       started: true,
       databaseConnected: true,
@@ -47,19 +51,22 @@ function createProvider() {
   return { provider, runtime };
 }
 
-test('storage-runtime provider restarts on storage config changes', async t => {
+test('storage-runtime provider reconfigures and reloads on storage config changes', async t => {
   const { provider, runtime } = createProvider();
 
   await provider.start();
   await provider.onConfigChanged({ updates: { storages: {} } });
 
-  t.is(runtime.stop.callCount, 1);
+  t.is(runtime.stop.callCount, 0);
   t.is(runtime.configure.callCount, 2);
-  t.is(runtime.start.callCount, 2);
-  t.is(runtime.runMigrations.callCount, 2);
+  t.is(runtime.reloadConfig.callCount, 1);
+  t.true(
+    runtime.configure.secondCall.calledBefore(runtime.reloadConfig.firstCall)
+  );
+  t.is(runtime.start.callCount, 1);
 });
 
-test('storage-runtime provider restarts on copilot storage config changes', async t => {
+test('storage-runtime provider reloads on copilot storage config changes', async t => {
   const { provider, runtime } = createProvider();
 
   await provider.start();
@@ -75,19 +82,31 @@ test('storage-runtime provider restarts on copilot storage config changes', asyn
     },
   });
 
-  t.is(runtime.stop.callCount, 1);
+  t.is(runtime.stop.callCount, 0);
   t.is(runtime.configure.callCount, 2);
-  t.is(runtime.start.callCount, 2);
-  t.is(runtime.runMigrations.callCount, 2);
+  t.is(runtime.reloadConfig.callCount, 1);
 });
 
-test('storage-runtime provider ignores unrelated config changes', async t => {
+test('storage-runtime provider reloads sections it does not forward', async t => {
   const { provider, runtime } = createProvider();
 
   await provider.start();
-  await provider.onConfigChanged({ updates: { flags: {} } });
+  await provider.onConfigChanged({ updates: { crypto: {} } });
 
   t.is(runtime.stop.callCount, 0);
+  t.is(runtime.reloadConfig.callCount, 1);
   t.is(runtime.start.callCount, 1);
-  t.is(runtime.runMigrations.callCount, 1);
+});
+
+test('storage-runtime provider restarts on database config changes', async t => {
+  const { provider, runtime } = createProvider();
+
+  await provider.start();
+  await provider.onConfigChanged({ updates: { db: {} } });
+
+  t.is(runtime.stop.callCount, 1);
+  t.is(runtime.configure.callCount, 2);
+  t.is(runtime.start.callCount, 2);
+  t.is(runtime.runMigrations.callCount, 2);
+  t.is(runtime.reloadConfig.callCount, 0);
 });
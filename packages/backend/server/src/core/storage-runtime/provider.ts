@@ -14,9 +14,16 @@ import type {
   PresignedUpload,
   PutObjectMetadata,
 } from '../../base';
-import { Config, OnEvent } from '../../base';
+import {
+  BlobQuotaExceeded,
+  Config,
+  OnEvent,
+  StorageQuotaExceeded,
+} from '../../base';
 import { wrapCallMetric } from '../../base/metrics';
 import {
+  type RuntimeBlobQuota,
+  type RuntimeConfigReloadResult,
   type RuntimeObjectGetResult,
   type RuntimeObjectListEntry,
   type RuntimeObjectMetadata,
@@ -68,20 +75,34 @@ export class StorageRuntimeProvider
 
   @OnEvent('config.changed')
   async onConfigChanged({ updates }: Events['config.changed']) {
-    if (
-      !('storages' in updates) &&
-      !('db' in updates) &&
-      !updates.copilot?.storage
-    ) {
+    if ('db' in updates) {
+      await this.restart();
       return;
     }
-    await this.restart();
+    // Storage backends can be swapped without reconnecting postgres. The
+    // reload picks up the sections that are not forwarded, e.g. `crypto`.
+    this.configureRuntime();
+    await this.reloadConfig();
+  }
+
+  async reloadConfig(): Promise<RuntimeConfigReloadResult> {
+    const result = await this.runtime.reloadConfig();
+    if (result.restartRequired) {
+      this.logger.warn(
+        'storage runtime datasource changed, restart required to apply it'
+      );
+    }
+    return result;
   }
 
   async health(): Promise<StorageRuntimeHealth> {
     return await this.runtime.health();
   }
 
+  async renderMetrics(): Promise<string> {
+    return await this.runtime.renderMetrics();
+  }
+
   async providerCapabilities(
     scope: string
   ): Promise<StorageProviderCapabilities> {
@@ -94,10 +115,13 @@ export class StorageRuntimeProvider
     scope: string,
     key: string,
     body: Buffer,
-    metadata?: PutObjectMetadata
+    metadata?: PutObjectMetadata,
+    quota?: RuntimeBlobQuota
   ) {
     const result = await this.measured('putObject', rt =>
-      rt.putObject(scope, key, body, toRuntimeMetadata(metadata))
+      rt
+        .putObject(scope, key, body, toRuntimeMetadata(metadata), quota)
+        .catch(rethrowQuotaRejection)
     );
     return fromRuntimeMetadata(result);
   }
@@ -119,6 +143,17 @@ export class StorageRuntimeProvider
     return result ? fromRuntimeGetResult(result) : {};
   }
 
+  async getObjectDerivative(
+    scope: string,
+    key: string,
+    maxEdge: number
+  ): Promise<StorageRuntimeGetObjectResult> {
+    const result = await this.measured('getObjectDerivative', rt =>
+      rt.getObjectDerivative(scope, key, maxEdge)
+    );
+    return result ? fromRuntimeGetResult(result) : {};
+  }
+
   async listObjects(scope: string, prefix?: string) {
     const entries = await this.measured('listObjects', rt =>
       rt.listObjects(scope, prefix)
@@ -130,9 +165,16 @@ export class StorageRuntimeProvider
     await this.measured('deleteObject', rt => rt.deleteObject(scope, key));
   }
 
-  async presignPut(scope: string, key: string, metadata?: PutObjectMetadata) {
+  async presignPut(
+    scope: string,
+    key: string,
+    metadata?: PutObjectMetadata,
+    quota?: RuntimeBlobQuota
+  ) {
     const result = await this.measured('presignPut', rt =>
-      rt.presignPut(scope, key, toRuntimeMetadata(metadata))
+      rt
+        .presignPut(scope, key, toRuntimeMetadata(metadata), quota)
+        .catch(rethrowQuotaRejection)
     );
     return result ? fromRuntimePresigned(result) : undefined;
   }
@@ -147,10 +189,13 @@ export class StorageRuntimeProvider
   async createMultipartUpload(
     scope: string,
     key: string,
-    metadata?: PutObjectMetadata
+    metadata?: PutObjectMetadata,
+    quota?: RuntimeBlobQuota
   ) {
     const result = await this.measured('createMultipartUpload', rt =>
-      rt.createMultipartUpload(scope, key, toRuntimeMetadata(metadata))
+      rt
+        .createMultipartUpload(scope, key, toRuntimeMetadata(metadata), quota)
+        .catch(rethrowQuotaRejection)
     );
     return result
       ? { uploadId: result.uploadId, expiresAt: new Date(result.expiresAtMs) }
@@ -203,10 +248,13 @@ export class StorageRuntimeProvider
     scope: string,
     key: string,
     uploadId: string,
-    parts: { partNumber: number; etag: string }[]
+    parts: { partNumber: number; etag: string }[],
+    quota?: RuntimeBlobQuota
   ) {
     return await this.measured('completeMultipartUpload', rt =>
-      rt.completeMultipartUpload(scope, key, uploadId, parts)
+      rt
+        .completeMultipartUpload(scope, key, uploadId, parts, quota)
+        .catch(rethrowQuotaRejection)
     );
   }
 
@@ -219,30 +267,57 @@ export class StorageRuntimeProvider
   async completeWorkspaceBlobUpload(
     workspaceId: string,
     key: string,
-    expected: { size: number; mime: string }
+    expected: { size: number; mime: string },
+    quota?: RuntimeBlobQuota
   ) {
     return await this.measured('completeWorkspaceBlobUpload', rt =>
       rt.completeWorkspaceBlobUpload(
         workspaceId,
         key,
         expected.size,
-        expected.mime
+        expected.mime,
+        quota
       )
     );
   }
 
+  async generateBlobDerivatives(workspaceId: string, key: string) {
+    return await this.measured('generateBlobDerivatives', rt =>
+      rt.generateBlobDerivatives(workspaceId, key)
+    );
+  }
+
   async cleanupExpiredPendingBlobs(cutoffMs: number, limit: number) {
     return await this.measured('cleanupExpiredPendingBlobs', rt =>
       rt.cleanupExpiredPendingBlobs(cutoffMs, limit)
     );
   }
 
+  async sweepAbandonedMultipartUploads(
+    cutoffMs: number,
+    limit: number,
+    cursor?: string
+  ) {
+    return await this.measured('sweepAbandonedMultipartUploads', rt =>
+      rt.sweepAbandonedMultipartUploads(cutoffMs, limit, cursor)
+    );
+  }
+
   async releaseDeletedBlobs(workspaceId: string, limit: number) {
     return await this.measured('releaseDeletedBlobs', rt =>
       rt.releaseDeletedBlobs(workspaceId, limit)
     );
   }
 
+  async reconcileWorkspaceStorageUsage(
+    workspaceId: string | undefined,
+    limit: number
+  ) {
+    return await this.measured('reconcileWorkspaceStorageUsage', rt =>
+      rt.reconcileWorkspaceStorageUsage(workspaceId, limit)
+    );
+  }
+
   async backfillMissingBlobMetadata(
     workspaceId: string | null | undefined,
     limit: number
@@ -258,6 +333,12 @@ export class StorageRuntimeProvider
     );
   }
 
+  async purgeWorkspace(workspaceId: string, batchLimit: number) {
+    return await this.measured('purgeWorkspace', rt =>
+      rt.purgeWorkspace(workspaceId, batchLimit)
+    );
+  }
+
   async planUnreferencedWorkspaceBlobs(
     workspaceId: string,
     gracePeriodDays: number,
@@ -271,10 +352,22 @@ export class StorageRuntimeProvider
   async executeBlobCleanupCandidates(
     runId: string,
     gracePeriodDays: number,
-    limit: number
+    limit: number,
+    quarantineDays?: number
   ) {
     return await this.measured('executeBlobCleanupCandidates', rt =>
-      rt.executeBlobCleanupCandidates(runId, gracePeriodDays, limit)
+      rt.executeBlobCleanupCandidates(
+        runId,
+        gracePeriodDays,
+        limit,
+        quarantineDays
+      )
+    );
+  }
+
+  async purgeQuarantinedBlobs(limit: number) {
+    return await this.measured('purgeQuarantinedBlobs', rt =>
+      rt.purgeQuarantinedBlobs(limit)
     );
   }
 
@@ -306,6 +399,8 @@ export class StorageRuntimeProvider
       JSON.stringify({
         db: {
           datasourceUrl: this.config.db.datasourceUrl,
+          pool: this.config.db.pool,
+          replicas: this.config.db.replicas,
         },
         storages: {
           'blob.storage': this.config.storages.blob.storage,
@@ -319,6 +414,22 @@ export class StorageRuntimeProvider
   }
 }
 
+// The runtime rejects quota reservations with the exceeded limit as reason.
+function rethrowQuotaRejection(err: unknown): never {
+  const message = err instanceof Error ? err.message : '';
+  if (
+    message.includes('blob quota reservation rejected: blob_limit_exceeded')
+  ) {
+    throw new BlobQuotaExceeded();
+  }
+  if (
+    message.includes('blob quota reservation rejected: storage_quota_exceeded')
+  ) {
+    throw new StorageQuotaExceeded();
+  }
+  throw err;
+}
+
 function toRuntimeMetadata(metadata?: PutObjectMetadata) {
   return metadata
     ? {
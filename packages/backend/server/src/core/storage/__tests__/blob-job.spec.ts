@@ -10,6 +10,11 @@ interface Context {
     rebuildWorkspaceDocBlobRefs: Sinon.SinonStub;
     planUnreferencedWorkspaceBlobs: Sinon.SinonStub;
     executeBlobCleanupCandidates: Sinon.SinonStub;
+    purgeQuarantinedBlobs: Sinon.SinonStub;
+    reconcileWorkspaceStorageUsage: Sinon.SinonStub;
+    sweepAbandonedMultipartUploads: Sinon.SinonStub;
+    purgeWorkspace: Sinon.SinonStub;
+    generateBlobDerivatives: Sinon.SinonStub;
   };
   event: {
     emitAsync: Sinon.SinonStub;
@@ -38,6 +43,11 @@ test.beforeEach(t => {
     rebuildWorkspaceDocBlobRefs: Sinon.stub(),
     planUnreferencedWorkspaceBlobs: Sinon.stub(),
     executeBlobCleanupCandidates: Sinon.stub(),
+    purgeQuarantinedBlobs: Sinon.stub(),
+    reconcileWorkspaceStorageUsage: Sinon.stub(),
+    sweepAbandonedMultipartUploads: Sinon.stub(),
+    purgeWorkspace: Sinon.stub(),
+    generateBlobDerivatives: Sinon.stub(),
   };
   t.context.event = {
     emitAsync: Sinon.stub().resolves(undefined),
@@ -81,6 +91,22 @@ const objectStorageRequiredCases: {
       context.event.emitAsync,
     ],
   },
+  {
+    name: 'quarantined blob purge',
+    run: context => context.job.purgeQuarantinedBlobs({}),
+    untouched: context => [
+      context.runtime.purgeQuarantinedBlobs,
+      context.queue.add,
+    ],
+  },
+  {
+    name: 'multipart upload sweep',
+    run: context => context.job.sweepAbandonedMultipartUploads({}),
+    untouched: context => [
+      context.runtime.sweepAbandonedMultipartUploads,
+      context.queue.add,
+    ],
+  },
   {
     name: 'blob cleanup planning sweep',
     run: context => context.job.planUnreferencedWorkspaceBlobsBySid({}),
@@ -156,3 +182,136 @@ test('doc blob refs sweep continues after one workspace fails', async t => {
     )
   );
 });
+
+test('workspace purge runs without object storage and requeues until done', async t => {
+  t.context.runtime.health.resolves({
+    databaseConnected: true,
+    providerConfigured: false,
+    provider: undefined,
+  });
+  t.context.runtime.purgeWorkspace.resolves({
+    workspaceId: 'workspace-1',
+    phase: 'snapshots',
+    deletedRows: 10,
+    deletedObjects: 0,
+    totalDeletedRows: 10,
+    totalDeletedObjects: 0,
+    remainingRows: 5,
+    remainingObjects: false,
+    completed: false,
+  });
+
+  await t.context.job.purgeWorkspace({ workspaceId: 'workspace-1', limit: 10 });
+
+  t.deepEqual(t.context.runtime.purgeWorkspace.firstCall.args, [
+    'workspace-1',
+    10,
+  ]);
+  t.true(
+    t.context.queue.add.calledWith('backendRuntime.purgeWorkspace', {
+      workspaceId: 'workspace-1',
+      limit: 10,
+    })
+  );
+});
+
+test('quarantined blob purge requeues full pages with progress', async t => {
+  t.context.runtime.purgeQuarantinedBlobs
+    .onFirstCall()
+    .resolves({ scannedCandidates: 2, purgedObjects: 1, failed: 1 })
+    .onSecondCall()
+    .resolves({ scannedCandidates: 2, purgedObjects: 0, failed: 2 });
+
+  await t.context.job.purgeQuarantinedBlobs({ limit: 2 });
+  await t.context.job.purgeQuarantinedBlobs({ limit: 2 });
+
+  t.deepEqual(t.context.runtime.purgeQuarantinedBlobs.firstCall.args, [2]);
+  t.true(t.context.queue.add.calledOnce);
+  t.true(
+    t.context.queue.add.calledWith('backendRuntime.purgeQuarantinedBlobs', {
+      limit: 2,
+    })
+  );
+});
+
+test('storage usage reconcile recounts the oldest workspaces', async t => {
+  t.context.runtime.reconcileWorkspaceStorageUsage.resolves({
+    scannedWorkspaces: 2,
+    correctedWorkspaces: 1,
+    workspaceIds: ['workspace-1'],
+  });
+
+  await t.context.job.reconcileWorkspaceStorageUsage({ limit: 2 });
+
+  t.deepEqual(
+    t.context.runtime.reconcileWorkspaceStorageUsage.firstCall.args,
+    [undefined, 2]
+  );
+});
+
+test('multipart upload sweep requeues with the next cursor', async t => {
+  t.context.runtime.sweepAbandonedMultipartUploads
+    .onFirstCall()
+    .resolves({
+      scannedUploads: 2,
+      abortedUploads: 1,
+      matchedPendingBlobs: 1,
+      freedPartBytes: 10,
+      skippedRecent: 0,
+      skippedForeign: 1,
+      failed: 0,
+      nextCursor: 'cursor-1',
+      workspaceIds: ['workspace-1'],
+    })
+    .onSecondCall()
+    .resolves({
+      scannedUploads: 1,
+      abortedUploads: 0,
+      matchedPendingBlobs: 0,
+      freedPartBytes: 0,
+      skippedRecent: 1,
+      skippedForeign: 0,
+      failed: 0,
+      workspaceIds: [],
+    });
+
+  await t.context.job.sweepAbandonedMultipartUploads({ limit: 2 });
+  const [name, payload] = t.context.queue.add.firstCall.args;
+  t.is(name, 'backendRuntime.sweepAbandonedMultipartUploads');
+  t.deepEqual(payload, { cursor: 'cursor-1', limit: 2 });
+  await t.context.job.sweepAbandonedMultipartUploads(payload);
+
+  const [, firstLimit, firstCursor] =
+    t.context.runtime.sweepAbandonedMultipartUploads.firstCall.args;
+  const [, secondLimit, secondCursor] =
+    t.context.runtime.sweepAbandonedMultipartUploads.secondCall.args;
+  t.is(firstLimit, 2);
+  t.is(firstCursor, undefined);
+  t.is(secondLimit, 2);
+  t.is(secondCursor, 'cursor-1');
+  t.true(t.context.queue.add.calledOnce);
+  t.true(
+    t.context.event.emitAsync.calledOnceWith('workspace.blobs.updated', {
+      workspaceId: 'workspace-1',
+    })
+  );
+});
+
+test('completed blob uploads generate derivatives in a job', async t => {
+  t.context.runtime.generateBlobDerivatives.resolves({
+    generatedEdges: [256],
+  });
+
+  await t.context.job.onWorkspaceBlobCompleted({
+    workspaceId: 'workspace-1',
+    key: 'blob-1',
+  });
+  const [name, payload] = t.context.queue.add.firstCall.args;
+  t.is(name, 'backendRuntime.generateBlobDerivatives');
+  await t.context.job.generateBlobDerivatives(payload);
+
+  t.deepEqual(t.context.runtime.generateBlobDerivatives.firstCall.args, [
+    'workspace-1',
+    'blob-1',
+  ]);
+});
@@ -2,7 +2,7 @@ import { Injectable, Logger } from '@nestjs/common';
 import { Cron, CronExpression } from '@nestjs/schedule';
 import { PrismaClient } from '@prisma/client';
 
-import { EventBus, JobQueue, OnJob } from '../../base';
+import { EventBus, JobQueue, OneDay, OnEvent, OnJob } from '../../base';
 import { StorageRuntimeProvider } from '../storage-runtime';
 
 // Queue keys are persisted API; keep the legacy backendRuntime.* names while
@@ -42,6 +42,25 @@ declare global {
       runId: string;
       gracePeriodDays?: number;
       limit?: number;
+      quarantineDays?: number;
+    };
+    'backendRuntime.purgeQuarantinedBlobs': {
+      limit?: number;
+    };
+    'backendRuntime.reconcileWorkspaceStorageUsage': {
+      limit?: number;
+    };
+    'backendRuntime.sweepAbandonedMultipartUploads': {
+      cursor?: string;
+      limit?: number;
+    };
+    'backendRuntime.purgeWorkspace': {
+      workspaceId: string;
+      limit?: number;
+    };
+    'backendRuntime.generateBlobDerivatives': {
+      workspaceId: string;
+      key: string;
     };
   }
 }
@@ -164,15 +183,45 @@ export class StorageBlobJob {
   async enqueueExecuteBlobCleanupCandidates(
     runId: string,
     gracePeriodDays = 30,
-    limit = 1000
+    limit = 1000,
+    quarantineDays?: number
   ) {
     await this.queue.add('backendRuntime.executeBlobCleanupCandidates', {
       runId,
       gracePeriodDays,
       limit,
+      quarantineDays,
     });
   }
 
+  async enqueuePurgeWorkspace(workspaceId: string, limit = 1000) {
+    await this.queue.add(
+      'backendRuntime.purgeWorkspace',
+      { workspaceId, limit },
+      { jobId: `backend-runtime-purge-workspace-${workspaceId}` }
+    );
+  }
+
+  // Resizing reads the whole image, so it runs outside the upload request.
+  @OnEvent('workspace.blob.completed')
+  async onWorkspaceBlobCompleted({
+    workspaceId,
+    key,
+  }: Events['workspace.blob.completed']) {
+    await this.queue.add(
+      'backendRuntime.generateBlobDerivatives',
+      { workspaceId, key },
+      { jobId: `backend-runtime-blob-derivatives-${workspaceId}-${key}` }
+    );
+  }
+
+  // Deleted workspaces are purged by this job alone; deleting every blob
+  // inline from the event handler timed out on large workspaces.
+  @OnEvent('workspace.deleted')
+  async onWorkspaceDeleted({ id }: Events['workspace.deleted']) {
+    await this.enqueuePurgeWorkspace(id);
+  }
+
   @Cron(CronExpression.EVERY_DAY_AT_1AM)
   async dailyBlobMetadataBackfill() {
     await this.queue.add(
@@ -200,6 +249,39 @@ export class StorageBlobJob {
     );
   }
 
+  // Cleanup execution moves objects under the quarantine prefix; this purge
+  // deletes them once their restore deadline has passed.
+  @Cron(CronExpression.EVERY_DAY_AT_4AM)
+  async dailyQuarantinedBlobsPurge() {
+    await this.queue.add(
+      'backendRuntime.purgeQuarantinedBlobs',
+      {},
+      { jobId: 'daily-backend-runtime-quarantined-blobs-purge' }
+    );
+  }
+
+  // Blob rows written or deleted through Prisma only reach the storage usage
+  // counters through this recount.
+  @Cron(CronExpression.EVERY_DAY_AT_5AM)
+  async dailyStorageUsageReconcile() {
+    await this.queue.add(
+      'backendRuntime.reconcileWorkspaceStorageUsage',
+      {},
+      { jobId: 'daily-backend-runtime-storage-usage-reconcile' }
+    );
+  }
+
+  // Multipart uploads that were never completed keep their parts billed in
+  // the bucket until they are aborted.
+  @Cron(CronExpression.EVERY_DAY_AT_6AM)
+  async dailyMultipartUploadsSweep() {
+    await this.queue.add(
+      'backendRuntime.sweepAbandonedMultipartUploads',
+      {},
+      { jobId: 'daily-backend-runtime-multipart-uploads-sweep' }
+    );
+  }
+
   @OnJob('backendRuntime.backfillMissingBlobMetadata')
   async backfillMissingBlobMetadata({
     workspaceId,
@@ -346,6 +428,7 @@ export class StorageBlobJob {
     runId,
     gracePeriodDays = 30,
     limit = 1000,
+    quarantineDays,
   }: Jobs['backendRuntime.executeBlobCleanupCandidates']) {
     if (!(await this.hasObjectStorage('blob cleanup execution'))) {
       return;
@@ -354,16 +437,114 @@ export class StorageBlobJob {
     const result = await this.rt.executeBlobCleanupCandidates(
       runId,
       gracePeriodDays,
+      limit,
+      quarantineDays
+    );
+    await Promise.all(
+      result.workspaceIds.map((workspaceId: string) =>
+        this.event.emitAsync('workspace.blobs.updated', { workspaceId })
+      )
+    );
+    this.logger.log(
+      `executed blob cleanup run=${runId} deleted=${result.deletedObjects} quarantined=${result.quarantinedObjects} skipped=${result.skippedStillReferenced} failed=${result.failed}`
+    );
+  }
+
+  @OnJob('backendRuntime.purgeQuarantinedBlobs')
+  async purgeQuarantinedBlobs({
+    limit = 1000,
+  }: Jobs['backendRuntime.purgeQuarantinedBlobs']) {
+    if (!(await this.hasObjectStorage('quarantined blob purge'))) {
+      return;
+    }
+
+    const result = await this.rt.purgeQuarantinedBlobs(limit);
+    this.logger.log(
+      `purged quarantined blobs scanned=${result.scannedCandidates} purged=${result.purgedObjects} failed=${result.failed}`
+    );
+    // Failed candidates stay expired and would be loaded again, so only a
+    // full page with progress continues in a new job.
+    if (result.scannedCandidates === limit && result.purgedObjects > 0) {
+      await this.queue.add('backendRuntime.purgeQuarantinedBlobs', { limit });
+    }
+  }
+
+  @OnJob('backendRuntime.reconcileWorkspaceStorageUsage')
+  async reconcileWorkspaceStorageUsage({
+    limit = 1000,
+  }: Jobs['backendRuntime.reconcileWorkspaceStorageUsage']) {
+    // Each run recounts the workspaces reconciled longest ago, so daily runs
+    // cycle through all of them.
+    const result = await this.rt.reconcileWorkspaceStorageUsage(
+      undefined,
       limit
     );
+    this.logger.log(
+      `reconciled storage usage scanned=${result.scannedWorkspaces} corrected=${result.correctedWorkspaces}`
+    );
+  }
+
+  @OnJob('backendRuntime.sweepAbandonedMultipartUploads')
+  async sweepAbandonedMultipartUploads({
+    cursor,
+    limit = 1000,
+  }: Jobs['backendRuntime.sweepAbandonedMultipartUploads']) {
+    if (!(await this.hasObjectStorage('multipart upload sweep'))) {
+      return;
+    }
+
+    const result = await this.rt.sweepAbandonedMultipartUploads(
+      Date.now() - OneDay,
+      limit,
+      cursor
+    );
     await Promise.all(
       result.workspaceIds.map((workspaceId: string) =>
         this.event.emitAsync('workspace.blobs.updated', { workspaceId })
       )
     );
     this.logger.log(
-      `executed blob cleanup run=${runId} deleted=${result.deletedObjects} skipped=${result.skippedStillReferenced} failed=${result.failed}`
+      `swept multipart uploads scanned=${result.scannedUploads} aborted=${result.abortedUploads} pending=${result.matchedPendingBlobs} skipped=${result.skippedRecent + result.skippedForeign} failed=${result.failed}`
     );
+    if (result.nextCursor) {
+      await this.queue.add('backendRuntime.sweepAbandonedMultipartUploads', {
+        cursor: result.nextCursor,
+        limit,
+      });
+    }
+  }
+
+  @OnJob('backendRuntime.generateBlobDerivatives')
+  async generateBlobDerivatives({
+    workspaceId,
+    key,
+  }: Jobs['backendRuntime.generateBlobDerivatives']) {
+    const result = await this.rt.generateBlobDerivatives(workspaceId, key);
+    if (result.generatedEdges.length) {
+      this.logger.log(
+        `generated blob derivatives workspace=${workspaceId} key=${key} edges=${result.generatedEdges.join(',')}`
+      );
+    }
+  }
+
+  @OnJob('backendRuntime.purgeWorkspace')
+  async purgeWorkspace({
+    workspaceId,
+    limit = 1000,
+  }: Jobs['backendRuntime.purgeWorkspace']) {
+    // Rows are purged on every deployment; the runtime skips object deletion
+    // when no blob storage is configured.
+    const result = await this.rt.purgeWorkspace(workspaceId, limit);
+    this.logger.log(
+      `purged workspace=${workspaceId} phase=${result.phase} rows=${result.deletedRows} objects=${result.deletedObjects} remainingRows=${result.remainingRows}`
+    );
+    if (!result.completed) {
+      // One batch per job keeps each run short; the next batch is a new job.
+      await this.queue.add('backendRuntime.purgeWorkspace', {
+        workspaceId,
+        limit,
+      });
+    }
   }
 
   private async drainBlobMetadataBackfill(
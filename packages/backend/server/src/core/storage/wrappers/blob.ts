@@ -7,7 +7,6 @@ import {
   Config,
   EventBus,
   type GetObjectMetadata,
-  OnEvent,
   PROXY_MULTIPART_PATH,
   PROXY_UPLOAD_PATH,
   type PutObjectMetadata,
@@ -17,17 +16,20 @@ import {
   URLHelper,
 } from '../../../base';
 import { Models } from '../../../models';
-import type { StorageProviderCapabilities } from '../../../native';
+import type {
+  RuntimeBlobQuota,
+  StorageProviderCapabilities,
+} from '../../../native';
 import { StorageRuntimeProvider } from '../../storage-runtime';
 
 declare global {
   interface Events {
-    'workspace.blob.delete': {
+    'workspace.blobs.updated': {
       workspaceId: string;
-      key: string;
     };
-    'workspace.blobs.updated': {
+    'workspace.blob.completed': {
       workspaceId: string;
+      key: string;
     };
   }
 }
@@ -41,7 +43,9 @@ type BlobCompleteResult =
         | 'size_mismatch'
         | 'mime_mismatch'
         | 'checksum_mismatch'
-        | 'size_too_large';
+        | 'size_too_large'
+        | 'blob_limit_exceeded'
+        | 'storage_quota_exceeded';
     };
 
 type BlobGetResult = {
@@ -67,11 +71,18 @@ export class WorkspaceBlobStorage {
     private readonly config: Config
   ) {}
 
-  async put(workspaceId: string, key: string, blob: Buffer) {
+  async put(
+    workspaceId: string,
+    key: string,
+    blob: Buffer,
+    quota?: RuntimeBlobQuota
+  ) {
     const metadata = await this.rt.putObject(
       'blob',
       `${workspaceId}/${key}`,
-      blob
+      blob,
+      undefined,
+      quota
     );
     await this.upsert(workspaceId, key, {
       contentType: metadata.contentType,
@@ -111,27 +122,50 @@ export class WorkspaceBlobStorage {
     return this.rt.getObject('blob', `${workspaceId}/${key}`);
   }
 
+  // Derivatives are read through the runtime rather than a signed URL, which
+  // could only point at the original.
+  async getDerivative(
+    workspaceId: string,
+    key: string,
+    maxEdge: number
+  ): Promise<BlobGetResult> {
+    return this.rt.getObjectDerivative(
+      'blob',
+      `${workspaceId}/${key}`,
+      maxEdge
+    );
+  }
+
   async presignPut(
     workspaceId: string,
     key: string,
-    metadata?: PutObjectMetadata
+    metadata?: PutObjectMetadata,
+    quota?: RuntimeBlobQuota
   ) {
     const proxy = this.r2ProxyConfig();
     if (proxy) {
+      // proxied uploads are counted against the quota on completion
       return this.createProxyUploadUrl(workspaceId, key, metadata, proxy);
     }
-    return this.rt.presignPut('blob', `${workspaceId}/${key}`, metadata);
+    return this.rt.presignPut(
+      'blob',
+      `${workspaceId}/${key}`,
+      metadata,
+      quota
+    );
   }
 
   async createMultipartUpload(
     workspaceId: string,
     key: string,
-    metadata?: PutObjectMetadata
+    metadata?: PutObjectMetadata,
+    quota?: RuntimeBlobQuota
   ) {
     return this.rt.createMultipartUpload(
       'blob',
       `${workspaceId}/${key}`,
-      metadata
+      metadata,
+      quota
     );
   }
 
@@ -175,13 +209,15 @@ export class WorkspaceBlobStorage {
     workspaceId: string,
     key: string,
     uploadId: string,
-    parts: { partNumber: number; etag: string }[]
+    parts: { partNumber: number; etag: string }[],
+    quota?: RuntimeBlobQuota
   ) {
     return await this.rt.completeMultipartUpload(
       'blob',
       `${workspaceId}/${key}`,
       uploadId,
-      parts
+      parts,
+      quota
     );
   }
 
@@ -204,12 +240,14 @@ export class WorkspaceBlobStorage {
   async complete(
     workspaceId: string,
     key: string,
-    expected: { size: number; mime: string }
+    expected: { size: number; mime: string },
+    quota?: RuntimeBlobQuota
   ): Promise<BlobCompleteResult> {
     const result = await this.rt.completeWorkspaceBlobUpload(
       workspaceId,
       key,
-      expected
+      expected,
+      quota
     );
     if (!result.ok) {
       return {
@@ -220,6 +258,10 @@ export class WorkspaceBlobStorage {
         >['reason'],
       };
     }
+    await this.event.emitAsync('workspace.blob.completed', {
+      workspaceId,
+      key,
+    });
     return {
       ok: true,
       metadata: {
@@ -287,27 +329,6 @@ export class WorkspaceBlobStorage {
     });
   }
 
-  @OnEvent('workspace.deleted')
-  async onWorkspaceDeleted({ id }: Events['workspace.deleted']) {
-    const blobs = await this.list(id);
-
-    // to reduce cpu time holding
-    blobs.forEach(blob => {
-      this.event.emit('workspace.blob.delete', {
-        workspaceId: id,
-        key: blob.key,
-      });
-    });
-  }
-
-  @OnEvent('workspace.blob.delete')
-  async onDeleteWorkspaceBlob({
-    workspaceId,
-    key,
-  }: Events['workspace.blob.delete']) {
-    await this.delete(workspaceId, key, true);
-  }
-
   private r2ProxyConfig() {
     const storage = this.config.storages.blob.storage as StorageProviderConfig;
     if (storage.provider !== 'cloudflare-r2') {
@@ -105,6 +105,7 @@ export class WorkspacesController {
     @Param('id') workspaceId: string,
     @Param('name') name: string,
     @Query('redirect') redirect: string | undefined,
+    @Query('size') size: string | undefined,
     @Res() res: Response
   ) {
     const canReadWorkspace = await this.ac
@@ -116,11 +117,13 @@ export class WorkspacesController {
     if (!canReadWorkspace && !canReadSharedWorkspaceBlobs) {
       throw new SpaceAccessDenied({ spaceId: workspaceId });
     }
-    const { body, metadata, redirectUrl } = await this.storage.get(
-      workspaceId,
-      name,
-      true
-    );
+    // `size` asks for the image at most that many pixels on its longest
+    // side; anything else serves the original.
+    const maxEdge = Number(size);
+    const { body, metadata, redirectUrl } =
+      Number.isSafeInteger(maxEdge) && maxEdge > 0
+        ? await this.storage.getDerivative(workspaceId, name, maxEdge)
+        : await this.storage.get(workspaceId, name, true);
 
     if (redirectUrl) {
       // redirect to signed url
@@ -20,10 +20,11 @@ import {
   BlobNotFound,
   BlobQuotaExceeded,
   CloudThrottlerGuard,
-  readBuffer,
+  readBufferWithLimit,
   StorageQuotaExceeded,
 } from '../../../base';
 import { Models } from '../../../models';
+import type { RuntimeBlobQuota } from '../../../native';
 import { CurrentUser } from '../../auth';
 import { PermissionAccess } from '../../permission';
 import { QuotaService } from '../../quota';
@@ -180,19 +181,14 @@ export class WorkspaceBlobResolver {
       .workspace(workspaceId)
       .assert('Workspace.Blobs.Write');
 
-    const checkExceeded =
-      await this.quota.getWorkspaceQuotaCalculator(workspaceId);
-
-    let result = checkExceeded(0);
-    if (result?.blobQuotaExceeded) {
-      throw new BlobQuotaExceeded();
-    } else if (result?.storageQuotaExceeded) {
-      throw new StorageQuotaExceeded();
-    }
-
-    const buffer = await readBuffer(blob.createReadStream(), checkExceeded);
+    // the storage quota is reserved by the runtime when the blob is written
+    const quota = await this.blobQuota(workspaceId);
+    const buffer = await readBufferWithLimit(
+      blob.createReadStream(),
+      quota.blobLimit
+    );
 
-    await this.storage.put(workspaceId, blob.filename, buffer);
+    await this.storage.put(workspaceId, blob.filename, buffer, quota);
     return blob.filename;
   }
 
@@ -238,15 +234,7 @@ export class WorkspaceBlobResolver {
       }
     }
 
-    const checkExceeded =
-      await this.quota.getWorkspaceQuotaCalculator(workspaceId);
-    const result = checkExceeded(record ? 0 : size);
-    if (result?.blobQuotaExceeded) {
-      throw new BlobQuotaExceeded();
-    } else if (result?.storageQuotaExceeded) {
-      throw new StorageQuotaExceeded();
-    }
-
+    const quota = await this.blobQuota(workspaceId);
     const metadata = { contentType: mime, contentLength: size };
     const capabilities = await this.storage.capabilities();
     let init: BlobUploadInit | null = null;
@@ -275,7 +263,8 @@ export class WorkspaceBlobResolver {
       const multipart = await this.storage.createMultipartUpload(
         workspaceId,
         key,
-        metadata
+        metadata,
+        quota
       );
       if (multipart) {
         uploadIdForRecord = multipart.uploadId;
@@ -294,7 +283,8 @@ export class WorkspaceBlobResolver {
       const presigned = await this.storage.presignPut(
         workspaceId,
         key,
-        metadata
+        metadata,
+        quota
       );
       if (presigned) {
         init = {
@@ -352,6 +342,7 @@ export class WorkspaceBlobResolver {
       return key;
     }
 
+    const quota = await this.blobQuota(workspaceId);
     const hasMultipartInput =
       uploadId !== undefined || (parts?.length ?? 0) > 0;
     const hasMultipartRecord = !!record.uploadId;
@@ -371,7 +362,8 @@ export class WorkspaceBlobResolver {
           workspaceId,
           key,
           uploadId,
-          parts
+          parts,
+          quota
         );
         if (!completed) {
           throw new BlobInvalid('Multipart upload is not supported');
@@ -381,10 +373,12 @@ export class WorkspaceBlobResolver {
       throw new BlobInvalid('Multipart upload is not initialized');
     }
 
-    const result = await this.storage.complete(workspaceId, key, {
-      size: record.size,
-      mime: record.mime,
-    });
+    const result = await this.storage.complete(
+      workspaceId,
+      key,
+      { size: record.size, mime: record.mime },
+      quota
+    );
     if (!result.ok) {
       if (result.reason === 'not_found') {
         throw new BlobNotFound({
@@ -401,6 +395,12 @@ export class WorkspaceBlobResolver {
       if (result.reason === 'size_too_large') {
         throw new BlobInvalid('Blob size too large');
       }
+      if (result.reason === 'blob_limit_exceeded') {
+        throw new BlobQuotaExceeded();
+      }
+      if (result.reason === 'storage_quota_exceeded') {
+        throw new StorageQuotaExceeded();
+      }
       throw new BlobInvalid('Blob key mismatch');
     }
 
@@ -422,6 +422,12 @@ export class WorkspaceBlobResolver {
     return this.storage.abortMultipartUpload(workspaceId, key, uploadId);
   }
 
+  private async blobQuota(workspaceId: string): Promise<RuntimeBlobQuota> {
+    const { blobLimit, storageQuota } =
+      await this.quota.getWorkspaceQuota(workspaceId);
+    return { blobLimit, storageQuota };
+  }
+
   private async getUploadPart(
     user: CurrentUser,
     workspaceId: string,
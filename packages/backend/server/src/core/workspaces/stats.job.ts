@@ -362,7 +362,9 @@ export class WorkspaceStatsJob {
       INSERT INTO workspace_admin_stats_daily (
         workspace_id,
         date,
+        snapshot_count,
         snapshot_size,
+        blob_count,
         blob_size,
         member_count,
         updated_at
@@ -370,14 +372,18 @@ export class WorkspaceStatsJob {
       SELECT
         workspace_id,
         CURRENT_DATE,
+        snapshot_count,
         snapshot_size,
+        blob_count,
         blob_size,
         member_count,
         NOW()
       FROM workspace_admin_stats
       ON CONFLICT (workspace_id, date)
       DO UPDATE SET
+        snapshot_count = EXCLUDED.snapshot_count,
         snapshot_size = EXCLUDED.snapshot_size,
+        blob_count = EXCLUDED.blob_count,
         blob_size = EXCLUDED.blob_size,
         member_count = EXCLUDED.member_count,
         updated_at = EXCLUDED.updated_at
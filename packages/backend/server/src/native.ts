@@ -51,9 +51,14 @@ import serverNativeModule, {
   type RuntimeBlobCleanupResult,
   type RuntimeBlobCompleteResult,
   type RuntimeBlobMetadataBackfillResult,
+  type RuntimeBlobQuota,
   type RuntimeByokLocalLeaseRecord,
+  type RuntimeConfigIssue,
+  type RuntimeConfigReloadResult,
   type RuntimeDocBlobRefsResult,
   type RuntimeDocCompactionResult,
+  type RuntimeDocHistoryInput,
+  type RuntimeDocRecord,
   type RuntimeMagicLinkOtpConsumeResult,
   type RuntimeMultipartUploadInit,
   type RuntimeMultipartUploadPart,
@@ -101,9 +106,14 @@ export type {
   RuntimeBlobCleanupResult,
   RuntimeBlobCompleteResult,
   RuntimeBlobMetadataBackfillResult,
+  RuntimeBlobQuota,
   RuntimeByokLocalLeaseRecord,
+  RuntimeConfigIssue,
+  RuntimeConfigReloadResult,
   RuntimeDocBlobRefsResult,
   RuntimeDocCompactionResult,
+  RuntimeDocHistoryInput,
+  RuntimeDocRecord,
   RuntimeMagicLinkOtpConsumeResult,
   RuntimeMultipartUploadInit,
   RuntimeMultipartUploadPart,
@@ -277,6 +287,7 @@ export const AFFINE_PRO_LICENSE_AES_KEY =
   serverNativeModule.AFFINE_PRO_LICENSE_AES_KEY;
 export const BackendRuntime = serverNativeModule.BackendRuntime;
 export const StorageRuntime = serverNativeModule.StorageRuntime;
+export const validateRuntimeConfig = serverNativeModule.validateConfig;
 
 export type PermissionWorkspaceRole = 'external' | 'member' | 'admin' | 'owner';
 export type PermissionDocRole =
@@ -11,6 +11,7 @@ import {
   OnEvent,
   OnJob,
 } from '../../../base';
+import { BackendRuntimeProvider } from '../../../core/backend-runtime';
 import { DocReader } from '../../../core/doc';
 import { WorkspaceBlobStorage } from '../../../core/storage';
 import { readAllDocIdsFromWorkspaceSnapshot } from '../../../core/utils/blocksuite';
@@ -35,6 +36,7 @@ export class CopilotEmbeddingJob {
     private readonly doc: DocReader,
     private readonly event: EventBus,
     private readonly models: Models,
+    private readonly runtime: BackendRuntimeProvider,
     private readonly queue: JobQueue,
     private readonly storage: CopilotStorage,
     private readonly workspaceStorage: WorkspaceBlobStorage
@@ -145,7 +147,7 @@ export class CopilotEmbeddingJob {
         return;
       }
       // filter out trashed docs
-      const rootSnapshot = await this.models.doc.getSnapshot(
+      const rootSnapshot = await this.runtime.getDocSnapshot(
         workspaceId,
         workspaceId
       );
@@ -559,7 +561,7 @@ export class CopilotEmbeddingJob {
     }
 
     const oneMonthAgo = new Date(Date.now() - OneDay * 30);
-    const snapshot = await this.models.doc.getSnapshot(
+    const snapshot = await this.runtime.getDocSnapshot(
       workspaceId,
       workspaceId
     );
@@ -577,7 +579,7 @@ export class CopilotEmbeddingJob {
     } else if (
       // always check if never cleared
       workspace.lastCheckEmbeddings > new Date(0) &&
-      snapshot.updatedAt < oneMonthAgo
+      snapshot.timestampMs < oneMonthAgo.getTime()
     ) {
       this.logger.verbose(
         `workspace ${workspaceId} is too old, skipping embeddings cleanup`
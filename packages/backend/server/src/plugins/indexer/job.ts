@@ -1,6 +1,7 @@
 import { Injectable, Logger } from '@nestjs/common';
 
 import { Config, JOB_SIGNAL, JobQueue, OnJob } from '../../base';
+import { BackendRuntimeProvider } from '../../core/backend-runtime';
 import { readAllDocIdsFromWorkspaceSnapshot } from '../../core/utils/blocksuite';
 import { Models } from '../../models';
 import { IndexerService } from './service';
@@ -33,6 +34,7 @@ export class IndexerJob {
 
   constructor(
     private readonly models: Models,
+    private readonly runtime: BackendRuntimeProvider,
     private readonly service: IndexerService,
     private readonly queue: JobQueue,
     private readonly config: Config
@@ -79,7 +81,7 @@ export class IndexerJob {
       return;
     }
 
-    const snapshot = await this.models.doc.getSnapshot(
+    const snapshot = await this.runtime.getDocSnapshot(
       workspaceId,
       workspaceId
     );
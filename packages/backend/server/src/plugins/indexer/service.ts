@@ -6,6 +6,7 @@ import {
   JobQueue,
   SearchProviderNotFound,
 } from '../../base';
+import { BackendRuntimeProvider } from '../../core/backend-runtime';
 import { readAllBlocksFromDocSnapshot } from '../../core/utils/blocksuite';
 import { Models } from '../../models';
 import { SearchProviderType } from './config';
@@ -110,6 +111,7 @@ export class IndexerService {
 
   constructor(
     private readonly models: Models,
+    private readonly runtime: BackendRuntimeProvider,
     private readonly factory: SearchProviderFactory,
     private readonly queue: JobQueue
   ) {}
@@ -268,26 +270,34 @@ export class IndexerService {
     docId: string,
     options?: OperationOptions
   ) {
-    const docSnapshot = await this.models.doc.getSnapshot(workspaceId, docId);
-    if (!docSnapshot) {
+    // the blob may be sealed, so only its metadata is read from the table
+    const [docSnapshot, doc] = await Promise.all([
+      this.models.doc.getSnapshot(workspaceId, docId, {
+        select: {
+          createdAt: true,
+          createdBy: true,
+          updatedAt: true,
+          updatedBy: true,
+        },
+      }),
+      this.runtime.getDocSnapshot(workspaceId, docId),
+    ]);
+    if (!docSnapshot || !doc) {
       this.logger.debug(`doc ${workspaceId}/${docId} not found`);
       return;
     }
-    if (docSnapshot.blob.length <= 2) {
+    if (doc.blob.length <= 2) {
       this.logger.debug(`doc ${workspaceId}/${docId} is empty, skip indexing`);
       return;
     }
     const metadata = {
       workspaceId,
       docId,
-      docSnapshotSize: docSnapshot.blob.length,
+      docSnapshotSize: doc.blob.length,
     };
 
     try {
-      const result = await readAllBlocksFromDocSnapshot(
-        docId,
-        docSnapshot.blob
-      );
+      const result = await readAllBlocksFromDocSnapshot(docId, doc.blob);
       await this.write(
         SearchTable.doc,
         [